@@ -0,0 +1,348 @@
+//! Machine-local answers to the prompt variables of profiles.
+//!
+//! Profiles can declare `prompt_variables` whose values are asked from the
+//! user on the first deployment. The answers are stored per profile in the
+//! file given by the `answers_file` of the global
+//! [`Config`](`crate::config::Config`) or in `<data dir>/punktf/answers.yaml`
+//! (e.g. `~/.local/share/punktf/answers.yaml` on linux) and reused by all
+//! subsequent invocations.
+//!
+//! Answers can also be supplied with `deploy --answers <file>`, which allows
+//! non-interactive deployments of profiles with prompt variables. Supplied
+//! answers take precedence over stored answers and are not stored.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Input, Password};
+use punktf_lib::profile::variables::{PromptVariable, Variables};
+use punktf_lib::profile::LayeredProfile;
+
+use crate::config::Config;
+use crate::util;
+
+/// Name of the profile layer which holds the stored answers.
+pub const ANSWERS_LAYER: &str = "prompt_answers";
+
+/// Name of the profile layer which holds the answers supplied with
+/// `--answers`.
+pub const SUPPLIED_ANSWERS_LAYER: &str = "supplied_prompt_answers";
+
+/// Stored answers to prompt variables.
+#[derive(Debug)]
+pub struct Answers {
+	/// Path of the answers file.
+	path: PathBuf,
+
+	/// Answers by profile name and variable name.
+	profiles: BTreeMap<String, BTreeMap<String, String>>,
+}
+
+impl Answers {
+	/// Returns the default path of the answers file.
+	fn default_path() -> Option<PathBuf> {
+		dirs::data_dir().map(|dir| dir.join("punktf").join("answers.yaml"))
+	}
+
+	/// Loads the answers from the path given by the config or the default path.
+	///
+	/// A missing file results in no answers.
+	///
+	/// # Errors
+	///
+	/// An error is returned if no path could be determined or if the file
+	/// exists but can not be read or parsed.
+	pub fn load_default(config: &Config) -> Result<Self> {
+		let path = config
+			.answers_file
+			.clone()
+			.or_else(Self::default_path)
+			.ok_or_else(|| eyre!("Failed to determine the path of the answers file"))?;
+
+		Self::load(path)
+	}
+
+	/// Loads the answers from `path`.
+	///
+	/// A missing file results in no answers.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file exists but can not be read or parsed.
+	pub fn load(path: PathBuf) -> Result<Self> {
+		let profiles = match std::fs::read_to_string(&path) {
+			Ok(content) => serde_yaml::from_str(&content)
+				.wrap_err_with(|| format!("Failed to parse answers file {}", path.display()))?,
+			Err(err) if err.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+			Err(err) => {
+				return Err(err)
+					.wrap_err_with(|| format!("Failed to read answers file {}", path.display()))
+			}
+		};
+
+		Ok(Self { path, profiles })
+	}
+
+	/// Writes the answers back to the file.
+	///
+	/// As answers can contain secrets, the file is only readable by the
+	/// current user on unix.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file could not be written.
+	pub fn save(&self) -> Result<()> {
+		if let Some(parent) = self.path.parent() {
+			std::fs::create_dir_all(parent)
+				.wrap_err_with(|| format!("Failed to create directory {}", parent.display()))?;
+		}
+
+		let content = serde_yaml::to_string(&self.profiles)?;
+
+		write_private(&self.path, content.as_bytes())
+			.wrap_err_with(|| format!("Failed to write answers file {}", self.path.display()))
+	}
+
+	/// Returns the path of the answers file.
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Returns the stored answers of the profile `profile_name`.
+	pub fn get(&self, profile_name: &str) -> Option<&BTreeMap<String, String>> {
+		self.profiles.get(profile_name)
+	}
+
+	/// Stores the answer `value` to the variable `name` of the profile
+	/// `profile_name`.
+	pub fn insert(&mut self, profile_name: &str, name: String, value: String) {
+		self.profiles
+			.entry(profile_name.to_owned())
+			.or_default()
+			.insert(name, value);
+	}
+
+	/// Removes all stored answers of the profile `profile_name`.
+	///
+	/// Returns `true` if there were any answers.
+	pub fn remove(&mut self, profile_name: &str) -> bool {
+		self.profiles.remove(profile_name).is_some()
+	}
+
+	/// Removes all stored answers.
+	pub fn clear(&mut self) {
+		self.profiles.clear();
+	}
+}
+
+/// Reads a file with answers to prompt variables, which maps variable names to
+/// values.
+///
+/// # Errors
+///
+/// An error is returned if the file can not be read or parsed.
+fn read_supplied(path: &Path) -> Result<BTreeMap<String, String>> {
+	let content = std::fs::read_to_string(path)
+		.wrap_err_with(|| format!("Failed to read answers file {}", path.display()))?;
+
+	serde_yaml::from_str(&content)
+		.wrap_err_with(|| format!("Failed to parse answers file {}", path.display()))
+}
+
+/// Writes `content` to the file at `path`, which is only readable and
+/// writable by the current user.
+///
+/// New files are created with these permissions and the permissions of
+/// existing files are restricted before their content is replaced, so the
+/// content is never readable by other users.
+#[cfg(unix)]
+fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+	use std::fs::{OpenOptions, Permissions};
+	use std::io::Write as _;
+	use std::os::unix::fs::{OpenOptionsExt as _, PermissionsExt as _};
+
+	let mut file = OpenOptions::new()
+		.write(true)
+		.create(true)
+		.truncate(false)
+		.mode(0o600)
+		.open(path)?;
+
+	file.set_permissions(Permissions::from_mode(0o600))?;
+	file.set_len(0)?;
+	file.write_all(content)
+}
+
+/// Writes `content` to the file at `path`.
+#[cfg(not(unix))]
+fn write_private(path: &Path, content: &[u8]) -> std::io::Result<()> {
+	std::fs::write(path, content)
+}
+
+/// Returns the prompt variables of `profile` which have no value yet.
+fn unanswered(profile: &LayeredProfile) -> Vec<PromptVariable> {
+	profile
+		.prompt_variables()
+		.filter(|variable| !profile.variables().inner.contains_key(&variable.name))
+		.cloned()
+		.collect()
+}
+
+/// Adds the answers to the prompt variables of `profile` as the lowest
+/// variable layers.
+///
+/// The answers of the `supplied` answers file are added above the stored
+/// answers, so they take precedence over them.
+///
+/// # Errors
+///
+/// An error is returned if an answers file could not be loaded.
+pub fn apply(
+	profile: &mut LayeredProfile,
+	profile_name: &str,
+	supplied: Option<&Path>,
+) -> Result<()> {
+	if profile.prompt_variables.is_empty() {
+		return Ok(());
+	}
+
+	if let Some(supplied) = supplied {
+		add_answers(profile, SUPPLIED_ANSWERS_LAYER, &read_supplied(supplied)?);
+	}
+
+	let answers = Answers::load_default(&Config::load()?)?;
+	if let Some(stored) = answers.get(profile_name) {
+		add_answers(profile, ANSWERS_LAYER, stored);
+	}
+
+	Ok(())
+}
+
+/// Adds the values of `answers` to the prompt variables of `profile` as the
+/// lowest variable layer with the name `layer`.
+fn add_answers(profile: &mut LayeredProfile, layer: &str, answers: &BTreeMap<String, String>) {
+	let variables = Variables::from_items(profile.prompt_variables().filter_map(|variable| {
+		Some((variable.name.clone(), answers.get(&variable.name)?.clone()))
+	}));
+
+	if !variables.inner.is_empty() {
+		profile.add_fallback_variables(String::from(layer), variables);
+	}
+}
+
+/// Asks the user for the value of `variable`.
+fn prompt(theme: &ColorfulTheme, variable: &PromptVariable) -> Result<String> {
+	let prompt = variable.description.as_deref().unwrap_or(&variable.name);
+
+	let value = if variable.secret {
+		Password::with_theme(theme)
+			.with_prompt(prompt)
+			.allow_empty_password(variable.default.is_some())
+			.interact()
+			.map(|value| match &variable.default {
+				Some(default) if value.is_empty() => default.clone(),
+				_ => value,
+			})?
+	} else {
+		let mut input = Input::<String>::with_theme(theme).with_prompt(prompt);
+		if let Some(default) = &variable.default {
+			input = input.default(default.clone());
+		}
+		input.interact_text()?
+	};
+
+	Ok(value)
+}
+
+/// Asks the user for all prompt variables of `profile` which have no value
+/// yet and stores the answers.
+///
+/// In non-interactive sessions the defaults of the variables are used without
+/// storing them.
+///
+/// # Errors
+///
+/// An error is returned if a variable has no value and the user can not be
+/// asked for one, or if the answers could not be read or stored.
+pub fn ask(profile: &mut LayeredProfile, profile_name: &str) -> Result<()> {
+	let unanswered = unanswered(profile);
+	if unanswered.is_empty() {
+		return Ok(());
+	}
+
+	let interactive = util::is_interactive();
+	let theme = ColorfulTheme::default();
+
+	let mut answers = Answers::load_default(&Config::load()?)?;
+	let mut answered = false;
+	let mut values = Variables::default();
+
+	for variable in unanswered {
+		let value = if interactive {
+			prompt(&theme, &variable)?
+		} else {
+			let value = variable.default.ok_or_else(|| {
+				eyre!(
+					"The prompt variable `{}` has no value. Either deploy interactively, supply it \
+					 with `--answers` or set it with `--var`",
+					variable.name
+				)
+			})?;
+			values.inner.insert(variable.name, value);
+			continue;
+		};
+
+		answers.insert(profile_name, variable.name.clone(), value.clone());
+		answered = true;
+		values.inner.insert(variable.name, value);
+	}
+
+	if answered {
+		answers.save()?;
+	}
+
+	profile.add_fallback_variables(String::from(ANSWERS_LAYER), values);
+
+	Ok(())
+}
+
+/// Opens the answers file in the editor of the user and validates it
+/// afterwards.
+///
+/// # Errors
+///
+/// An error is returned if the editor fails or the edited file is invalid.
+pub fn edit() -> Result<()> {
+	let answers = Answers::load_default(&Config::load()?)?;
+	if !answers.path().exists() {
+		answers.save()?;
+	}
+
+	util::open_editor(answers.path())?;
+	Answers::load(answers.path().to_path_buf())?;
+
+	Ok(())
+}
+
+/// Removes the stored answers of the profile `profile_name` or of all profiles.
+///
+/// # Errors
+///
+/// An error is returned if the answers could not be read or stored.
+pub fn clear(profile_name: Option<&str>) -> Result<()> {
+	let mut answers = Answers::load_default(&Config::load()?)?;
+
+	match profile_name {
+		Some(name) if !answers.remove(name) => {
+			println!("No answers stored for profile {name}");
+			return Ok(());
+		}
+		Some(_) => {}
+		None => answers.clear(),
+	}
+
+	answers.save()
+}
@@ -0,0 +1,261 @@
+//! Benchmark of the compilation of a profile (`punktf bench`).
+//!
+//! The profile is resolved and all of its dotfiles are compiled multiple times
+//! without writing anything. The time spent in each stage (profile
+//! resolution, reading, template parsing, template rendering and hashing) is
+//! measured separately.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use color_eyre::Result;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::LayeredProfile;
+use punktf_lib::template::source::Source;
+use punktf_lib::template::Template;
+use punktf_lib::visit::{self, Directory, Errored, File, Rejected, Symlink, Visitor, Walker};
+use sha2::{Digest as _, Sha256};
+
+/// Time spent in each stage of the compilation.
+#[derive(Debug, Default, Clone, Copy)]
+struct Stages {
+	/// Reading and layering the profiles.
+	resolve: Duration,
+
+	/// Reading the dotfiles.
+	read: Duration,
+
+	/// Parsing the templates.
+	parse: Duration,
+
+	/// Rendering the templates.
+	render: Duration,
+
+	/// Hashing the compiled contents.
+	hash: Duration,
+}
+
+/// Compile time of a single template.
+#[derive(Debug, Default, Clone, Copy)]
+struct TemplateTime {
+	/// Time spent parsing the template.
+	parse: Duration,
+
+	/// Time spent rendering the template.
+	render: Duration,
+}
+
+impl TemplateTime {
+	/// Returns the time spent in all stages.
+	fn total(&self) -> Duration {
+		self.parse + self.render
+	}
+}
+
+/// Compiles all files of a profile while measuring each stage.
+#[derive(Debug, Default)]
+struct Bench {
+	/// Accumulated time of each stage.
+	stages: Stages,
+
+	/// Accumulated compile time of each template by source path.
+	templates: HashMap<PathBuf, TemplateTime>,
+
+	/// Amount of compiled files per iteration.
+	files: usize,
+
+	/// Files which failed to compile.
+	errors: Vec<String>,
+}
+
+impl Bench {
+	/// Reads, compiles and hashes a single `file`.
+	fn compile(
+		&mut self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		file: &File<'_>,
+	) -> Result<()> {
+		let start = Instant::now();
+		let content = std::fs::read(&file.source_path)?;
+		self.stages.read += start.elapsed();
+
+		let content = match String::from_utf8(content) {
+			Ok(content) if file.dotfile().is_template() => {
+				let time = self
+					.templates
+					.entry(file.relative_source_path.clone())
+					.or_default();
+
+				let start = Instant::now();
+				let template = Template::parse(Source::file(&file.source_path, &content))?;
+				let parse = start.elapsed();
+
+				let start = Instant::now();
+				let rendered = template.resolve_for_profile(
+					profile,
+					file.dotfile().variables.as_ref(),
+					source,
+				)?;
+				let render = start.elapsed();
+
+				time.parse += parse;
+				time.render += render;
+				self.stages.parse += parse;
+				self.stages.render += render;
+
+				rendered.into_bytes()
+			}
+			Ok(content) => content.into_bytes(),
+			Err(err) => err.into_bytes(),
+		};
+
+		let start = Instant::now();
+		let _ = Sha256::digest(&content);
+		self.stages.hash += start.elapsed();
+
+		Ok(())
+	}
+}
+
+impl Visitor for Bench {
+	fn accept_file<'a>(
+		&mut self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		file: &File<'a>,
+	) -> visit::Result {
+		self.files += 1;
+
+		if let Err(err) = self.compile(source, profile, file) {
+			self.errors
+				.push(format!("{}: {err}", file.relative_source_path.display()));
+		}
+
+		Ok(())
+	}
+
+	fn accept_directory<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Directory<'a>,
+	) -> visit::Result {
+		Ok(())
+	}
+
+	fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, _: &Symlink) -> visit::Result {
+		Ok(())
+	}
+
+	fn accept_rejected<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Rejected<'a>,
+	) -> visit::Result {
+		Ok(())
+	}
+
+	fn accept_errored<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		errored: &Errored<'a>,
+	) -> visit::Result {
+		self.errors.push(errored.to_string());
+
+		Ok(())
+	}
+}
+
+/// Prints a line of the stage table with the `total` time over all
+/// `iterations`.
+fn print_stage(name: &str, total: Duration, iterations: u32) {
+	println!(
+		"{name:<12} {:>12} {:>12}",
+		format!("{total:.2?}"),
+		format!("{:.2?}", total / iterations)
+	);
+}
+
+/// Compiles the profile `iterations` times and prints the timings of each
+/// stage together with the `count` slowest templates.
+///
+/// The profile is resolved anew for each iteration by calling `resolve`.
+///
+/// # Errors
+///
+/// An error is returned if the profile could not be resolved or walked.
+pub fn run(
+	source: &PunktfSource,
+	mut resolve: impl FnMut() -> Result<LayeredProfile>,
+	iterations: u32,
+	count: usize,
+) -> Result<()> {
+	let iterations = iterations.max(1);
+	let mut bench = Bench::default();
+
+	for _ in 0..iterations {
+		bench.files = 0;
+		bench.errors.clear();
+
+		let start = Instant::now();
+		let mut profile = resolve()?;
+		bench.stages.resolve += start.elapsed();
+
+		Walker::new(&mut profile)
+			.walk(source, &mut bench)
+			.map_err(|err| color_eyre::eyre::eyre!("{err}"))?;
+	}
+
+	let Stages {
+		resolve,
+		read,
+		parse,
+		render,
+		hash,
+	} = bench.stages;
+
+	println!(
+		"Compiled {} files ({} templates) {iterations} times\n",
+		bench.files,
+		bench.templates.len()
+	);
+	println!("{:<12} {:>12} {:>12}", "Stage", "Total", "Mean");
+	print_stage("resolution", resolve, iterations);
+	print_stage("read", read, iterations);
+	print_stage("parse", parse, iterations);
+	print_stage("render", render, iterations);
+	print_stage("hashing", hash, iterations);
+	print_stage("total", resolve + read + parse + render + hash, iterations);
+
+	let mut templates = bench.templates.into_iter().collect::<Vec<_>>();
+	templates.sort_by(|(a_path, a), (b_path, b)| {
+		b.total().cmp(&a.total()).then_with(|| a_path.cmp(b_path))
+	});
+
+	if !templates.is_empty() {
+		println!(
+			"\nSlowest templates (mean):\n{:>12} {:>12} {:>12}  Path",
+			"Total", "Parse", "Render"
+		);
+
+		for (path, time) in templates.into_iter().take(count) {
+			println!(
+				"{:>12} {:>12} {:>12}  {}",
+				format!("{:.2?}", time.total() / iterations),
+				format!("{:.2?}", time.parse / iterations),
+				format!("{:.2?}", time.render / iterations),
+				path.display()
+			);
+		}
+	}
+
+	for err in &bench.errors {
+		tracing::warn!("Failed to compile {err}");
+	}
+
+	Ok(())
+}
@@ -0,0 +1,103 @@
+//! Generation of bootstrap scripts which set up a new machine.
+//!
+//! A bootstrap script installs `punktf` from the latest GitHub release (after
+//! verifying its sha256 checksum), clones the dotfiles repository and deploys a
+//! profile. Answers to prompt variables of the profile can be supplied with the
+//! [`PUNKTF_ANSWERS_ENVVAR`](`crate::PUNKTF_ANSWERS_ENVVAR`) environment
+//! variable when running the script.
+
+use std::path::Path;
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+
+use crate::git;
+use crate::opt::ScriptKind;
+use crate::script::{quote_ps1, quote_sh};
+
+/// Template of the posix shell bootstrap script.
+const SH_TEMPLATE: &str = include_str!("bootstrap/install.sh");
+
+/// Template of the powershell bootstrap script.
+const PS1_TEMPLATE: &str = include_str!("bootstrap/install.ps1");
+
+/// Values which are filled into a bootstrap script.
+#[derive(Debug)]
+pub struct Bootstrap {
+	/// Url of the dotfiles repository.
+	pub repo: String,
+
+	/// Path of the source directory relative to the root of the repository.
+	pub prefix: String,
+
+	/// Name of the profile to deploy.
+	pub profile: String,
+
+	/// Directory into which the repository is cloned.
+	///
+	/// Defaults to `~/.dotfiles`.
+	pub dir: Option<String>,
+}
+
+impl Bootstrap {
+	/// Determines the repository url and the prefix of the source directory
+	/// from its git repository.
+	///
+	/// A `repo` url given by the user takes precedence over the url of the
+	/// `origin` remote.
+	pub fn from_source(
+		source: &Path,
+		repo: Option<String>,
+		profile: String,
+		dir: Option<String>,
+	) -> Result<Self> {
+		let repo = match repo {
+			Some(repo) => repo,
+			None => git::remote_url(source).wrap_err_with(|| {
+				format!(
+					"Failed to determine the repository of {}. Use `--repo` to set it",
+					source.display()
+				)
+			})?,
+		};
+
+		let prefix = git::repo_prefix(source).unwrap_or_default();
+
+		Ok(Self {
+			repo,
+			prefix,
+			profile,
+			dir,
+		})
+	}
+
+	/// Renders the bootstrap script of the given `kind`.
+	pub fn render(&self, kind: ScriptKind) -> String {
+		let (template, quote, default_dir): (_, fn(&str) -> String, _) = match kind {
+			ScriptKind::Sh => (SH_TEMPLATE, quote_sh, "$HOME/.dotfiles"),
+			ScriptKind::PowerShell => (PS1_TEMPLATE, quote_ps1, "(Join-Path $HOME '.dotfiles')"),
+		};
+
+		let dir = self
+			.dir
+			.as_deref()
+			.map_or_else(|| default_dir.to_owned(), quote);
+
+		template
+			.replace("@REPO@", &quote(&self.repo))
+			.replace("@PREFIX@", &quote(&self.prefix))
+			.replace("@PROFILE@", &quote(&self.profile))
+			.replace("@DIR@", &dir)
+	}
+}
+
+/// Returns the kind of script for the file `output` based on its extension.
+pub fn script_kind(output: &Path) -> Result<ScriptKind> {
+	match output.extension().and_then(|ext| ext.to_str()) {
+		Some("sh") | None => Ok(ScriptKind::Sh),
+		Some("ps1") => Ok(ScriptKind::PowerShell),
+		Some(ext) => Err(eyre!(
+			"Unknown script type `.{ext}`. Use `--kind` to set the type of the script"
+		)),
+	}
+}
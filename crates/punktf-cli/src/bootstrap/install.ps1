@@ -0,0 +1,67 @@
+# Generated by `punktf bootstrap`.
+#
+# Installs punktf (if not already installed), clones the dotfiles repository
+# and deploys a profile.
+#
+# Environment variables:
+#   PUNKTF_BOOTSTRAP_DIR - directory into which the repository is cloned
+#   PUNKTF_BIN_DIR       - directory into which punktf is installed
+$ErrorActionPreference = 'Stop'
+$ProgressPreference = 'SilentlyContinue'
+
+$Repo = @REPO@
+$Prefix = @PREFIX@
+$ProfileName = @PROFILE@
+$DotfilesDir = if ($env:PUNKTF_BOOTSTRAP_DIR) { $env:PUNKTF_BOOTSTRAP_DIR } else { @DIR@ }
+$BinDir = if ($env:PUNKTF_BIN_DIR) { $env:PUNKTF_BIN_DIR } else { Join-Path $env:LOCALAPPDATA 'punktf\bin' }
+$ReleaseUrl = 'https://github.com/Shemnei/punktf/releases/latest/download'
+
+function Install-Punktf {
+    $target = if ([Environment]::Is64BitOperatingSystem) { 'x86_64-pc-windows-msvc' } else { 'i686-pc-windows-msvc' }
+    $asset = "punktf-$target.zip"
+    $tmp = Join-Path ([IO.Path]::GetTempPath()) ([Guid]::NewGuid())
+    New-Item -ItemType Directory -Path $tmp | Out-Null
+
+    try {
+        Write-Host "Downloading $asset..."
+        Invoke-WebRequest -UseBasicParsing -Uri "$ReleaseUrl/$asset" -OutFile (Join-Path $tmp $asset)
+        Invoke-WebRequest -UseBasicParsing -Uri "$ReleaseUrl/$asset.sha256" -OutFile (Join-Path $tmp "$asset.sha256")
+
+        $expected = (Get-Content (Join-Path $tmp "$asset.sha256") -Raw).Trim()
+        $actual = (Get-FileHash -Path (Join-Path $tmp $asset) -Algorithm SHA256).Hash
+        if ($expected -ne $actual) {
+            throw "checksum mismatch for $asset (expected $expected, got $actual)"
+        }
+
+        Expand-Archive -Path (Join-Path $tmp $asset) -DestinationPath $tmp
+        New-Item -ItemType Directory -Force -Path $BinDir | Out-Null
+        Copy-Item (Join-Path $tmp "punktf-$target\punktf.exe") (Join-Path $BinDir 'punktf.exe') -Force
+    } finally {
+        Remove-Item -Recurse -Force $tmp
+    }
+
+    Write-Host "Installed punktf to $BinDir"
+}
+
+if (Get-Command punktf -ErrorAction SilentlyContinue) {
+    $Punktf = 'punktf'
+} else {
+    Install-Punktf
+    $Punktf = Join-Path $BinDir 'punktf.exe'
+}
+
+if (-not (Get-Command git -ErrorAction SilentlyContinue)) {
+    throw 'git is required'
+}
+
+if (Test-Path (Join-Path $DotfilesDir '.git')) {
+    Write-Host "Updating $DotfilesDir..."
+    git -C $DotfilesDir pull --ff-only
+} else {
+    Write-Host "Cloning $Repo into $DotfilesDir..."
+    git clone $Repo $DotfilesDir
+}
+if ($LASTEXITCODE -ne 0) { exit $LASTEXITCODE }
+
+& $Punktf deploy --source (Join-Path $DotfilesDir $Prefix) --profile $ProfileName
+exit $LASTEXITCODE
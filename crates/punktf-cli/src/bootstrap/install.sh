@@ -0,0 +1,85 @@
+#!/bin/sh
+# Generated by `punktf bootstrap`.
+#
+# Installs punktf (if not already installed), clones the dotfiles repository
+# and deploys a profile.
+#
+# Environment variables:
+#   PUNKTF_BOOTSTRAP_DIR - directory into which the repository is cloned
+#   PUNKTF_BIN_DIR       - directory into which punktf is installed
+set -eu
+
+REPO=@REPO@
+PREFIX=@PREFIX@
+PROFILE=@PROFILE@
+DOTFILES_DIR="${PUNKTF_BOOTSTRAP_DIR:-@DIR@}"
+BIN_DIR="${PUNKTF_BIN_DIR:-$HOME/.local/bin}"
+RELEASE_URL="https://github.com/Shemnei/punktf/releases/latest/download"
+
+error() {
+	echo "error: $*" >&2
+	exit 1
+}
+
+download() {
+	if command -v curl >/dev/null 2>&1; then
+		curl -fsSL "$1" -o "$2"
+	elif command -v wget >/dev/null 2>&1; then
+		wget -qO "$2" "$1"
+	else
+		error "curl or wget is required"
+	fi
+}
+
+sha256() {
+	if command -v sha256sum >/dev/null 2>&1; then
+		sha256sum "$1" | cut -d ' ' -f 1
+	else
+		shasum -a 256 "$1" | cut -d ' ' -f 1
+	fi
+}
+
+install_punktf() {
+	case "$(uname -s)-$(uname -m)" in
+		Linux-x86_64) target="x86_64-unknown-linux-musl" ;;
+		*) error "no prebuilt punktf release for $(uname -s) $(uname -m); install it with \`cargo install punktf\`" ;;
+	esac
+
+	asset="punktf-${target}.tar.gz"
+	tmp="$(mktemp -d)"
+	trap 'rm -rf "$tmp"' EXIT
+
+	echo "Downloading ${asset}..."
+	download "${RELEASE_URL}/${asset}" "${tmp}/${asset}"
+	download "${RELEASE_URL}/${asset}.sha256" "${tmp}/${asset}.sha256"
+
+	expected="$(tr -d ' \r\n' <"${tmp}/${asset}.sha256" | tr 'A-F' 'a-f')"
+	actual="$(sha256 "${tmp}/${asset}")"
+	[ "$expected" = "$actual" ] || error "checksum mismatch for ${asset} (expected ${expected}, got ${actual})"
+
+	tar xzf "${tmp}/${asset}" -C "$tmp"
+	mkdir -p "$BIN_DIR"
+	cp "${tmp}/punktf-${target}/punktf" "${BIN_DIR}/punktf"
+	chmod 755 "${BIN_DIR}/punktf"
+
+	echo "Installed punktf to ${BIN_DIR}/punktf"
+}
+
+if command -v punktf >/dev/null 2>&1; then
+	PUNKTF="punktf"
+else
+	install_punktf
+	PUNKTF="${BIN_DIR}/punktf"
+fi
+
+command -v git >/dev/null 2>&1 || error "git is required"
+
+if [ -d "${DOTFILES_DIR}/.git" ]; then
+	echo "Updating ${DOTFILES_DIR}..."
+	git -C "$DOTFILES_DIR" pull --ff-only
+else
+	echo "Cloning ${REPO} into ${DOTFILES_DIR}..."
+	git clone "$REPO" "$DOTFILES_DIR"
+fi
+
+"$PUNKTF" deploy --source "${DOTFILES_DIR}/${PREFIX}" --profile "$PROFILE"
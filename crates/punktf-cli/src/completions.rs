@@ -0,0 +1,208 @@
+//! Generation of shell completions.
+//!
+//! The completions generated by [`clap_complete`] are extended to complete
+//! profile names for `-p/--profile`. For this, the shell calls
+//! `punktf __complete-profiles -- <words>` with the words of the current
+//! command line, which scans the `profiles` directories of the given source
+//! directories.
+
+use std::path::{Path, PathBuf};
+
+use clap::CommandFactory as _;
+use clap_complete::{Generator as _, Shell};
+use color_eyre::Result;
+use punktf_lib::profile::source::ConflictPolicy;
+
+use crate::{opt, BINARY_NAME};
+
+/// Argument which makes `punktf` print all profile names instead of running
+/// a command.
+const COMPLETE_PROFILES_ARG: &str = "__complete-profiles";
+
+/// Command which prints all profile names for the words of the current command
+/// line.
+const COMPLETE_PROFILES: &str = "punktf __complete-profiles --";
+
+/// Generates the completions for `shell` and writes them into the directory
+/// `output`.
+///
+/// # Errors
+///
+/// An error is returned if the completions could not be written.
+pub fn generate(shell: Shell, output: &Path) -> Result<()> {
+	let mut buffer: Vec<u8> = Default::default();
+	clap_complete::generate(shell, &mut opt::Opts::command(), BINARY_NAME, &mut buffer);
+
+	let script = String::from_utf8_lossy(&buffer);
+	let script = match shell {
+		Shell::Bash => with_bash_profiles(&script),
+		Shell::Zsh => with_zsh_profiles(&script),
+		Shell::Fish => with_fish_profiles(&script),
+		Shell::PowerShell => with_powershell_profiles(&script),
+		_ => script.into_owned(),
+	};
+
+	std::fs::write(output.join(shell.file_name(BINARY_NAME)), script)?;
+
+	Ok(())
+}
+
+/// Replaces the file completion of `-p/--profile` with the profile names.
+fn with_bash_profiles(script: &str) -> String {
+	let mut is_profile = false;
+
+	script
+		.lines()
+		.map(|line| {
+			let line = if is_profile {
+				line.replace(
+					r#"compgen -f "${cur}""#,
+					&format!(
+						r#"compgen -W "$({COMPLETE_PROFILES} "${{COMP_WORDS[@]}}" 2>/dev/null)" -- "${{cur}}""#
+					),
+				)
+			} else {
+				line.to_owned()
+			};
+
+			is_profile = matches!(line.trim(), "--profile)" | "-p)");
+
+			line + "\n"
+		})
+		.collect()
+}
+
+/// Completes the values of `-p/--profile` with the profile names.
+fn with_zsh_profiles(script: &str) -> String {
+	let script = script.replace(":PROFILE: '", ":PROFILE:_punktf_profiles'");
+
+	let function = format!(
+		r#"(( $+functions[_punktf_profiles] )) ||
+_punktf_profiles() {{
+    local -a profiles
+    profiles=(${{(f)"$({COMPLETE_PROFILES} ${{words[@]}} 2>/dev/null)"}})
+    _describe -t profiles 'profile' profiles
+}}
+
+"#
+	);
+
+	match script.rfind("if [ \"$funcstack[1]\"") {
+		Some(idx) => format!("{}{function}{}", &script[..idx], &script[idx..]),
+		None => script,
+	}
+}
+
+/// Completes the values of `-p/--profile` with the profile names.
+fn with_fish_profiles(script: &str) -> String {
+	script
+		.lines()
+		.map(|line| {
+			if line.contains(" -l profile ") {
+				format!("{line} -f -a '({COMPLETE_PROFILES} (commandline -opc))'\n")
+			} else {
+				format!("{line}\n")
+			}
+		})
+		.collect()
+}
+
+/// Completes the values of `-p/--profile` with the profile names.
+fn with_powershell_profiles(script: &str) -> String {
+	let anchor = "    $commandElements = $commandAst.CommandElements\n";
+
+	let profiles = format!(
+		r#"    $previous = $commandElements | Where-Object {{ $_.Extent.EndOffset -lt $cursorPosition }} | Select-Object -Last 1
+    if ($previous -and @('-p', '--profile') -contains $previous.ToString()) {{
+        $words = @($commandElements | ForEach-Object {{ $_.ToString() }})
+        {COMPLETE_PROFILES} @words 2>$null |
+            Where-Object {{ $_ -like "$wordToComplete*" }} |
+            ForEach-Object {{ [CompletionResult]::new($_, $_, [CompletionResultType]::ParameterValue, $_) }}
+        return
+    }}
+"#
+	);
+
+	script.replacen(anchor, &format!("{anchor}{profiles}"), 1)
+}
+
+/// Expands a leading `~` of a path typed into the shell.
+fn expand_home(path: &str) -> PathBuf {
+	match (path.strip_prefix('~'), dirs::home_dir()) {
+		(Some(rest), Some(home)) if rest.is_empty() || rest.starts_with(['/', '\\']) => {
+			home.join(rest.trim_start_matches(['/', '\\']))
+		}
+		_ => PathBuf::from(path),
+	}
+}
+
+/// Extracts the source directories from the `words` of a command line.
+///
+/// Falls back to the environment variable
+/// [`PUNKTF_SOURCE_ENVVAR`](`crate::PUNKTF_SOURCE_ENVVAR`) if no source
+/// directory is given.
+fn source_dirs(words: &[String]) -> Vec<PathBuf> {
+	let mut sources = Vec::new();
+	let mut words = words.iter().peekable();
+
+	while let Some(word) = words.next() {
+		let value = match word.as_str() {
+			"-s" | "--source" => {
+				// Bash splits `--source=path` into three words
+				words.next_if(|w| *w == "=");
+				words.next().cloned()
+			}
+			_ => word
+				.strip_prefix("--source=")
+				.or_else(|| word.strip_prefix("-s").filter(|v| !v.is_empty()))
+				.map(str::to_owned),
+		};
+
+		if let Some(value) = value {
+			sources.push(expand_home(&value));
+		}
+	}
+
+	if sources.is_empty() {
+		sources.extend(std::env::var_os(crate::PUNKTF_SOURCE_ENVVAR).map(PathBuf::from));
+	}
+
+	sources
+}
+
+/// Handles a completion request of the shell.
+///
+/// If `punktf` was called with [`COMPLETE_PROFILES_ARG`], the profile names
+/// are printed and `true` is returned. Otherwise nothing happens.
+pub fn complete() -> bool {
+	let args: Vec<String> = std::env::args().skip(1).collect();
+
+	match args.split_first() {
+		Some((arg, words)) if arg == COMPLETE_PROFILES_ARG => {
+			let words = words.strip_prefix(&["--".to_owned()]).unwrap_or(words);
+			print_profiles(words);
+			true
+		}
+		_ => false,
+	}
+}
+
+/// Prints the names of all profiles of the source directories given in
+/// `words`, one per line.
+///
+/// Nothing is printed if the source directories can not be determined.
+fn print_profiles(words: &[String]) {
+	let sources = source_dirs(words);
+	if sources.is_empty() {
+		return;
+	}
+
+	let names = crate::setup_source(sources, ConflictPolicy::FirstWins)
+		.and_then(|source| source.profile_names());
+
+	if let Ok(names) = names {
+		for name in names {
+			println!("{name}");
+		}
+	}
+}
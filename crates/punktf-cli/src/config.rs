@@ -0,0 +1,106 @@
+//! Global configuration of `punktf`.
+//!
+//! The configuration is read from the path given by the environment variable
+//! [`PUNKTF_CONFIG_ENVVAR`](`crate::PUNKTF_CONFIG_ENVVAR`) or from
+//! `<config dir>/punktf/config.yaml` (e.g. `~/.config/punktf/config.yaml` on
+//! linux).
+
+use std::fs::File;
+use std::path::PathBuf;
+
+use color_eyre::eyre::Context;
+use color_eyre::Result;
+use serde::Deserialize;
+
+/// Global configuration of `punktf`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	/// Name of the profile to use if none is given by the command line or the
+	/// environment.
+	pub default_profile: Option<String>,
+
+	/// Path of a file to which all log messages are written, regardless of the
+	/// verbosity. Can be overwritten with `--log-file`.
+	pub log_file: Option<PathBuf>,
+
+	/// Path of the database in which all deployments are recorded.
+	///
+	/// Defaults to `<data dir>/punktf/history.sqlite`.
+	pub history_file: Option<PathBuf>,
+
+	/// Hashes all files written by a deployment for the history, including
+	/// the ones the deployer did not hash itself (e.g. copied files).
+	///
+	/// This reads every written file again after the deployment.
+	pub history_hashes: bool,
+
+	/// Path of the file in which the answers to the prompt variables of the
+	/// profiles are stored.
+	///
+	/// Defaults to `<data dir>/punktf/answers.yaml`.
+	pub answers_file: Option<PathBuf>,
+
+	/// Directory in which the secrets referenced by `from:secret:<name>`
+	/// variables are stored, with one directory per namespace.
+	///
+	/// Defaults to `<data dir>/punktf/secrets`.
+	pub secrets_dir: Option<PathBuf>,
+
+	/// Directory from which the WebAssembly plugins (`*.wasm`) are loaded.
+	///
+	/// Defaults to `<config dir>/punktf/plugins`.
+	pub plugins_dir: Option<PathBuf>,
+
+	/// Caches resolved profiles in `<cache dir>/punktf/profile-cache.json`.
+	///
+	/// The cache is invalidated as soon as any profile file changes.
+	pub cache_profiles: bool,
+
+	/// Caches parsed templates in `<cache dir>/punktf/template-cache.json`.
+	///
+	/// A template is only parsed again if its content changes.
+	pub cache_templates: bool,
+
+	/// Never asks for confirmation before a deployment overwrites existing
+	/// files (the same as always passing `--yes`).
+	pub assume_yes: bool,
+}
+
+impl Config {
+	/// Returns the path to the configuration file.
+	///
+	/// Returns `None` if neither the environment variable is set nor the
+	/// config directory of the platform could be determined.
+	pub fn path() -> Option<PathBuf> {
+		std::env::var_os(super::PUNKTF_CONFIG_ENVVAR)
+			.map(PathBuf::from)
+			.or_else(|| dirs::config_dir().map(|dir| dir.join("punktf").join("config.yaml")))
+	}
+
+	/// Loads the configuration.
+	///
+	/// A missing configuration file results in the default configuration.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file exists but can not be read or parsed.
+	pub fn load() -> Result<Self> {
+		let Some(path) = Self::path() else {
+			return Ok(Self::default());
+		};
+
+		if !path.exists() {
+			tracing::debug!("No config found at {}", path.display());
+			return Ok(Self::default());
+		}
+
+		tracing::debug!("Loading config from {}", path.display());
+
+		let file = File::open(&path)
+			.wrap_err_with(|| format!("Failed to open config file {}", path.display()))?;
+
+		serde_yaml::from_reader(file)
+			.wrap_err_with(|| format!("Failed to parse config file {}", path.display()))
+	}
+}
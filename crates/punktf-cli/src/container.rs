@@ -0,0 +1,377 @@
+//! Verification of profiles inside of containers (`test --in-container`).
+//!
+//! For each image and profile a new container is started with a container
+//! runtime (`docker` or `podman`). The `punktf` binary and all source
+//! directories are mounted read-only into it, the profile is deployed inside
+//! of the container and the deployment status is read back from a json report.
+//! The container runs as the current user with a temporary home directory.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use console::style;
+use punktf_lib::profile::source::ConflictPolicy;
+use punktf_lib::profile::Priority;
+use punktf_lib::visit::deploy::deployment::Deployment;
+
+use crate::util;
+
+/// Path of the `punktf` binary inside of the container.
+const CONTAINER_BINARY: &str = "/usr/local/bin/punktf";
+
+/// Directory inside of the container below which the sources are mounted.
+const CONTAINER_SOURCES: &str = "/punktf/source";
+
+/// Directory inside of the container to which the deployment report is
+/// written.
+const CONTAINER_OUTPUT: &str = "/punktf/output";
+
+/// Home directory inside of the container.
+///
+/// The container runs as the current user, which usually has no home directory
+/// in the image.
+const CONTAINER_HOME: &str = "/punktf/home";
+
+/// File name of the deployment report.
+const REPORT_FILE: &str = "deployment.json";
+
+/// Container runtimes which are searched for if none is given.
+const RUNTIMES: [&str; 2] = ["docker", "podman"];
+
+/// Options for the verification of profiles in containers.
+#[derive(Debug)]
+pub struct ContainerOptions {
+	/// Container runtime to use (e.g. `docker` or `podman`).
+	///
+	/// If not set, the first runtime found is used.
+	pub runtime: Option<String>,
+
+	/// Images in which the profiles are deployed.
+	pub images: Vec<String>,
+
+	/// Names of the profiles to deploy.
+	pub profiles: Vec<String>,
+
+	/// Source directories as given on the command line (including an optional
+	/// `@priority` suffix).
+	pub sources: Vec<PathBuf>,
+
+	/// Conflict policy for the sources.
+	pub conflict_policy: ConflictPolicy,
+
+	/// The `punktf` binary which is mounted into the container.
+	///
+	/// If not set, the currently running binary is used.
+	pub binary: Option<PathBuf>,
+}
+
+/// Returns the first available container runtime.
+fn find_runtime() -> Result<String> {
+	RUNTIMES
+		.into_iter()
+		.find(|runtime| {
+			Command::new(runtime)
+				.arg("--version")
+				.output()
+				.is_ok_and(|output| output.status.success())
+		})
+		.map(String::from)
+		.ok_or_else(|| {
+			eyre!(
+				"No container runtime found (tried: {}). Use `--runtime` to specify one",
+				RUNTIMES.join(", ")
+			)
+		})
+}
+
+/// Resolves `path` to an absolute path, as required for volume mounts.
+fn absolute(path: &Path) -> Result<PathBuf> {
+	path.canonicalize()
+		.wrap_err_with(|| format!("Failed to resolve {}", path.display()))
+}
+
+/// Returns the user and group (`<uid>:<gid>`) which own `path`.
+#[cfg(unix)]
+fn owner(path: &Path) -> Result<Option<String>> {
+	use std::os::unix::fs::MetadataExt;
+
+	let metadata = path
+		.metadata()
+		.wrap_err_with(|| format!("Failed to read metadata of {}", path.display()))?;
+
+	Ok(Some(format!("{}:{}", metadata.uid(), metadata.gid())))
+}
+
+/// Returns the user and group (`<uid>:<gid>`) which own `path`.
+#[cfg(not(unix))]
+const fn owner(_: &Path) -> Result<Option<String>> {
+	Ok(None)
+}
+
+/// Builds the command to deploy `profile` inside of a new container of
+/// `image`.
+///
+/// The container runs as the owner of the `output` directory, so all files it
+/// writes to the directory belong to the current user.
+fn deploy_command(
+	runtime: &str,
+	image: &str,
+	profile: &str,
+	binary: &Path,
+	sources: &[(PathBuf, Priority)],
+	conflict_policy: ConflictPolicy,
+	output: &Path,
+) -> Result<Command> {
+	let mut command = Command::new(runtime);
+
+	command.args(["run", "--rm"]);
+
+	if let Some(user) = owner(output)? {
+		command.args(["--user", &user]);
+	}
+
+	command
+		.arg("--volume")
+		.arg(format!("{}:{CONTAINER_BINARY}:ro", binary.display()));
+
+	for (idx, (root, _)) in sources.iter().enumerate() {
+		command
+			.arg("--volume")
+			.arg(format!("{}:{CONTAINER_SOURCES}/{idx}:ro", root.display()));
+	}
+
+	command
+		.arg("--volume")
+		.arg(format!("{}:{CONTAINER_OUTPUT}", output.display()))
+		.arg("--volume")
+		.arg(format!(
+			"{}:{CONTAINER_HOME}",
+			output.join("home").display()
+		))
+		.args(["--env", &format!("HOME={CONTAINER_HOME}")])
+		.arg(image)
+		.args([CONTAINER_BINARY, "--quiet", "deploy", "--profile", profile])
+		.args(["--conflict-policy", conflict_policy.as_str()])
+		.args([
+			"--json-output",
+			&format!("{CONTAINER_OUTPUT}/{REPORT_FILE}"),
+		]);
+
+	for (idx, (_, priority)) in sources.iter().enumerate() {
+		command.args([
+			"--source",
+			&format!("{CONTAINER_SOURCES}/{idx}@{}", priority.0),
+		]);
+	}
+
+	Ok(command)
+}
+
+/// Deploys `profile` inside of a new container of `image` and returns the
+/// deployment read from the report.
+///
+/// Returns `None` if the container did not produce a report (e.g. because the
+/// binary could not be executed or the profile could not be resolved).
+fn run_one(
+	options: &ContainerOptions,
+	runtime: &str,
+	image: &str,
+	profile: &str,
+	binary: &Path,
+	sources: &[(PathBuf, Priority)],
+) -> Result<Option<Deployment>> {
+	let output = tempfile::Builder::new()
+		.prefix("punktf-container-")
+		.tempdir()
+		.wrap_err("Failed to create a temporary directory")?;
+
+	let home = output.path().join("home");
+	std::fs::create_dir(&home)
+		.wrap_err_with(|| format!("Failed to create directory {}", home.display()))?;
+
+	let mut command = deploy_command(
+		runtime,
+		image,
+		profile,
+		binary,
+		sources,
+		options.conflict_policy,
+		output.path(),
+	)?;
+
+	tracing::debug!("Running {command:?}");
+
+	let status = command
+		.status()
+		.wrap_err_with(|| format!("Failed to execute container runtime `{runtime}`"))?;
+
+	let report = output.path().join(REPORT_FILE);
+	let deployment = std::fs::read_to_string(&report)
+		.ok()
+		.map(|content| {
+			serde_json::from_str::<Deployment>(&content)
+				.wrap_err_with(|| format!("Failed to parse {}", report.display()))
+		})
+		.transpose();
+
+	let path = output.path().to_path_buf();
+	if let Err(err) = output.close() {
+		tracing::warn!("Failed to remove {}: {err}", path.display());
+	}
+
+	let deployment = deployment?;
+
+	if deployment.is_none() {
+		tracing::error!("Container exited with {status} without a deployment report");
+	}
+
+	Ok(deployment)
+}
+
+/// Deploys each profile in each image and prints the results.
+///
+/// Returns `true` if all deployments succeeded.
+///
+/// # Errors
+///
+/// An error is returned if no container runtime was found or if a container
+/// could not be started.
+pub fn run(options: &ContainerOptions) -> Result<bool> {
+	if options.profiles.is_empty() {
+		return Err(eyre!(
+			"At least one profile (`--profile`) is required for `--in-container`"
+		));
+	}
+
+	let runtime = match &options.runtime {
+		Some(runtime) => runtime.clone(),
+		None => find_runtime()?,
+	};
+
+	let binary = match &options.binary {
+		Some(binary) => absolute(binary)?,
+		None => std::env::current_exe().wrap_err("Failed to get the path of punktf")?,
+	};
+
+	if options.binary.is_none() && !cfg!(target_os = "linux") {
+		tracing::warn!(
+			"The running punktf binary is not built for linux. Use `--binary` to provide one which \
+			 runs inside of the containers"
+		);
+	}
+
+	let sources = options
+		.sources
+		.iter()
+		.map(|source| {
+			let (root, priority) = crate::split_source_priority(source.clone());
+			Ok((absolute(&root)?, priority))
+		})
+		.collect::<Result<Vec<_>>>()?;
+
+	let mut results = Vec::new();
+
+	for image in &options.images {
+		for profile in &options.profiles {
+			println!(
+				"{} Deploying profile {} in {}",
+				style("==>").bold().cyan(),
+				style(profile).bold(),
+				style(image).bold()
+			);
+
+			let deployment = run_one(options, &runtime, image, profile, &binary, &sources)?;
+
+			if let Some(deployment) = &deployment {
+				util::print_deployment(deployment);
+			}
+
+			let success = deployment.is_some_and(|d| d.status().is_success());
+			results.push((image, profile, success));
+			println!();
+		}
+	}
+
+	let image_width = options
+		.images
+		.iter()
+		.map(|image| console::measure_text_width(image))
+		.max()
+		.unwrap_or_default();
+
+	println!("{}", style("Results").bold());
+	for (image, profile, success) in &results {
+		let status = if *success {
+			style("success").green()
+		} else {
+			style("failed").red()
+		};
+
+		println!("{status:<7}  {image:<image_width$}  {profile}");
+	}
+
+	Ok(results.iter().all(|(_, _, success)| *success))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn container_command() -> Result<()> {
+		let output = tempfile::tempdir()?;
+
+		let command = deploy_command(
+			"docker",
+			"alpine",
+			"test",
+			Path::new("/bin/punktf"),
+			&[(PathBuf::from("/dotfiles"), Priority::new(1))],
+			ConflictPolicy::default(),
+			output.path(),
+		)?;
+
+		let args = command
+			.get_args()
+			.map(|arg| arg.to_string_lossy().into_owned())
+			.collect::<Vec<_>>();
+
+		let mut expected = vec![String::from("run"), String::from("--rm")];
+		if let Some(user) = owner(output.path())? {
+			expected.extend([String::from("--user"), user]);
+		}
+		expected.extend(
+			[
+				"--volume",
+				&format!("/bin/punktf:{CONTAINER_BINARY}:ro"),
+				"--volume",
+				&format!("/dotfiles:{CONTAINER_SOURCES}/0:ro"),
+				"--volume",
+				&format!("{}:{CONTAINER_OUTPUT}", output.path().display()),
+				"--volume",
+				&format!("{}:{CONTAINER_HOME}", output.path().join("home").display()),
+				"--env",
+				&format!("HOME={CONTAINER_HOME}"),
+				"alpine",
+				CONTAINER_BINARY,
+				"--quiet",
+				"deploy",
+				"--profile",
+				"test",
+				"--conflict-policy",
+				ConflictPolicy::default().as_str(),
+				"--json-output",
+				&format!("{CONTAINER_OUTPUT}/{REPORT_FILE}"),
+				"--source",
+				&format!("{CONTAINER_SOURCES}/0@1"),
+			]
+			.map(String::from),
+		);
+
+		assert_eq!(args, expected);
+
+		Ok(())
+	}
+}
@@ -53,7 +53,7 @@ pub fn diff(format: DiffFormat, event: Event<'_>) {
 }
 
 /// Prints a file diff with the gnu unified format.
-fn print_udiff(target: &Path, old: &str, new: &str) {
+pub fn print_udiff(target: &Path, old: &str, new: &str) {
 	let diff = TextDiff::from_lines(old, new);
 
 	println!("--- {path}\r\n+++ {path}", path = target.display());
@@ -0,0 +1,342 @@
+//! Management of encrypted dotfiles (`punktf encrypt`, `punktf decrypt` and
+//! `punktf reencrypt`).
+//!
+//! The sources are rewritten in place with the
+//! [encryption](`punktf_lib::profile::Profile::encryption`) of the profile and
+//! the `encrypted` flag of all dotfile entries with the same path is updated in
+//! the profile files of the source.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use console::style;
+use punktf_lib::profile::encryption::{BackendKind, Encryption, EncryptionBackend};
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::{LayeredProfile, Profile};
+use serde_json::Value;
+
+use crate::fmt::profile_files;
+
+/// Returns the encryption of the `profile`.
+fn encryption(profile: &LayeredProfile) -> Result<&Encryption> {
+	profile
+		.encryption()
+		.ok_or_else(|| eyre!("The profile has no `encryption` configured"))
+}
+
+/// Returns the path of `path` relative to the `dotfiles` directory of any
+/// layer of `source`.
+///
+/// The `path` is either relative to the current directory or relative to the
+/// `dotfiles` directory.
+fn relative_dotfile_path(source: &PunktfSource, path: &Path) -> Result<PathBuf> {
+	if let Ok(absolute) = path.canonicalize() {
+		let relative = source
+			.layers()
+			.find_map(|layer| absolute.strip_prefix(&layer.dotfiles).ok());
+
+		if let Some(relative) = relative {
+			return Ok(relative.to_path_buf());
+		}
+	}
+
+	if source.dotfile_path(path).exists() {
+		Ok(path.to_path_buf())
+	} else {
+		Err(eyre!(
+			"`{}` is not inside of the dotfiles directory",
+			path.display()
+		))
+	}
+}
+
+/// Returns all files at `path`, which are all files below it for directories.
+fn files(path: &Path) -> Result<Vec<PathBuf>> {
+	if !path.is_dir() {
+		return Ok(vec![path.to_path_buf()]);
+	}
+
+	let mut files = Vec::new();
+
+	for entry in std::fs::read_dir(path)
+		.wrap_err_with(|| format!("Failed to read directory {}", path.display()))?
+	{
+		files.extend(self::files(&entry?.path())?);
+	}
+
+	files.sort();
+
+	Ok(files)
+}
+
+/// Applies `f` to the content of all `files` and writes the results only once
+/// all of them succeeded, so no file is left half converted.
+fn rewrite(files: &[PathBuf], f: impl Fn(&[u8]) -> Result<Vec<u8>>) -> Result<()> {
+	let contents = files
+		.iter()
+		.map(|file| {
+			let content = std::fs::read(file)
+				.wrap_err_with(|| format!("Failed to read {}", file.display()))?;
+
+			f(&content).wrap_err_with(|| format!("File: {}", file.display()))
+		})
+		.collect::<Result<Vec<_>>>()?;
+
+	for (file, content) in files.iter().zip(contents) {
+		std::fs::write(file, content)
+			.wrap_err_with(|| format!("Failed to write {}", file.display()))?;
+	}
+
+	Ok(())
+}
+
+/// Applies `edit` to all profile files of all layers of `source` and writes
+/// the profiles for which it returned `true`.
+fn edit_profiles(source: &PunktfSource, edit: impl Fn(&mut Value) -> bool) -> Result<()> {
+	for layer in source.layers() {
+		for (path, format) in profile_files(layer.root())? {
+			let content = std::fs::read_to_string(&path)
+				.wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+
+			let mut value = format
+				.parse(&content)
+				.wrap_err_with(|| format!("Profile: {}", path.display()))?;
+
+			if !edit(&mut value) {
+				continue;
+			}
+
+			let profile: Profile = serde_json::from_value(value)
+				.wrap_err_with(|| format!("Invalid profile {}", path.display()))?;
+
+			std::fs::write(&path, format.serialize(&profile)?)
+				.wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+			println!("{} {}", style("updated").green(), path.display());
+		}
+	}
+
+	Ok(())
+}
+
+/// Sets the `encrypted` flag of all dotfile entries with the path `relative`
+/// in the profile `value`.
+///
+/// Returns `true` if any entry was changed.
+fn set_encrypted(value: &mut Value, relative: &Path, encrypted: bool) -> bool {
+	let Some(Value::Array(dotfiles)) = value.get_mut("dotfiles") else {
+		return false;
+	};
+
+	let mut changed = false;
+
+	for dotfile in dotfiles.iter_mut().filter_map(Value::as_object_mut) {
+		if dotfile.get("path").and_then(Value::as_str).map(Path::new) != Some(relative) {
+			continue;
+		}
+
+		if encrypted {
+			dotfile.insert(String::from("encrypted"), Value::Bool(true));
+		} else {
+			dotfile.remove("encrypted");
+		}
+
+		changed = true;
+	}
+
+	changed
+}
+
+/// Encrypts (or decrypts if `encrypt` is not set) the source of the dotfile
+/// at `path` and updates its `encrypted` flag in all profiles.
+fn convert(
+	source: &PunktfSource,
+	profile: &LayeredProfile,
+	path: &Path,
+	encrypt: bool,
+) -> Result<()> {
+	let relative = relative_dotfile_path(source, path)?;
+
+	let dotfile = profile
+		.dotfiles()
+		.find(|dotfile| dotfile.path == relative)
+		.ok_or_else(|| eyre!("`{}` is not a dotfile of the profile", relative.display()))?;
+
+	if dotfile.is_encrypted() == encrypt {
+		return Err(eyre!(
+			"`{}` is already {}",
+			relative.display(),
+			if encrypt { "encrypted" } else { "decrypted" }
+		));
+	}
+
+	let backend = encryption(profile)?.backend()?;
+	let files = files(&source.dotfile_path(&relative))?;
+
+	if encrypt {
+		rewrite(&files, |content| backend.encrypt(content))?;
+	} else {
+		rewrite(&files, |content| backend.decrypt(content))?;
+	}
+
+	for file in &files {
+		println!(
+			"{} {}",
+			style(if encrypt { "encrypted" } else { "decrypted" }).green(),
+			file.display()
+		);
+	}
+
+	edit_profiles(source, |value| set_encrypted(value, &relative, encrypt))
+}
+
+/// Encrypts the source of the dotfile at `path` with the encryption of the
+/// `profile` and marks the dotfile as encrypted in all profiles.
+///
+/// # Errors
+///
+/// An error is returned if the path is no dotfile of the profile, if it is
+/// already encrypted or if it could not be encrypted.
+pub fn encrypt(source: &PunktfSource, profile: &LayeredProfile, path: &Path) -> Result<()> {
+	convert(source, profile, path, true)
+}
+
+/// Decrypts the source of the dotfile at `path` with the encryption of the
+/// `profile` and removes the encrypted flag of the dotfile in all profiles.
+///
+/// # Errors
+///
+/// An error is returned if the path is no dotfile of the profile, if it is
+/// not encrypted or if it could not be decrypted.
+pub fn decrypt(source: &PunktfSource, profile: &LayeredProfile, path: &Path) -> Result<()> {
+	convert(source, profile, path, false)
+}
+
+/// Generates a new age identity with `age-keygen`.
+///
+/// Returns the content of the identity file and its public key.
+fn generate_age_identity() -> Result<(String, String)> {
+	let output = Command::new("age-keygen")
+		.output()
+		.wrap_err("Failed to run `age-keygen`")?;
+
+	if !output.status.success() {
+		return Err(eyre!(
+			"`age-keygen` failed with {}: {}",
+			output.status,
+			String::from_utf8_lossy(&output.stderr).trim()
+		));
+	}
+
+	let identity = String::from_utf8(output.stdout).wrap_err("Invalid output of `age-keygen`")?;
+	let recipient = identity
+		.lines()
+		.find_map(|line| line.strip_prefix("# public key: "))
+		.ok_or_else(|| eyre!("`age-keygen` printed no public key"))?
+		.to_owned();
+
+	Ok((identity, recipient))
+}
+
+/// Appends `extension` to the file name of `path`.
+fn with_extension(path: &Path, extension: &str) -> PathBuf {
+	let mut path = path.to_path_buf().into_os_string();
+	path.push(extension);
+
+	PathBuf::from(path)
+}
+
+/// Replaces the recipients of all encryptions in the profile `value` with
+/// `recipient`.
+///
+/// Returns `true` if any encryption was changed.
+fn set_recipient(value: &mut Value, recipient: &str) -> bool {
+	let Some(Value::Object(encryption)) = value.get_mut("encryption") else {
+		return false;
+	};
+
+	encryption.insert(
+		String::from("recipients"),
+		Value::Array(vec![Value::String(recipient.to_owned())]),
+	);
+
+	true
+}
+
+/// Encrypts the sources of all encrypted dotfiles of the `profile` again, e.g.
+/// after the recipients were changed.
+///
+/// If `rotate_key` is set, a new age identity is generated first. The sources
+/// are decrypted with the old identity and encrypted for the new one, which
+/// then replaces the old identity (kept with the extension `.old`) and the
+/// recipients of the profiles. The new identity is staged with the extension
+/// `.new` until all sources are written.
+///
+/// # Errors
+///
+/// An error is returned if any source could not be decrypted or encrypted, or
+/// if the key should be rotated for another backend than age.
+pub fn reencrypt(source: &PunktfSource, profile: &LayeredProfile, rotate_key: bool) -> Result<()> {
+	let encryption = encryption(profile)?;
+	let old = encryption.backend()?;
+
+	let files = profile
+		.dotfiles()
+		.filter(|dotfile| dotfile.is_encrypted())
+		.map(|dotfile| files(&source.dotfile_path(&dotfile.path)))
+		.collect::<Result<Vec<_>>>()?
+		.concat();
+
+	if !rotate_key {
+		rewrite(&files, |content| old.encrypt(&old.decrypt(content)?))?;
+
+		for file in &files {
+			println!("{} {}", style("reencrypted").green(), file.display());
+		}
+
+		return Ok(());
+	}
+
+	if encryption.backend != BackendKind::Age {
+		return Err(eyre!(
+			"Keys can only be rotated for `age`, rotate the keys of `{}` with its own tools",
+			encryption.backend
+		));
+	}
+
+	let identity = encryption
+		.identity()?
+		.ok_or_else(|| eyre!("No identity to decrypt with age is configured"))?;
+
+	let (new_identity, recipient) = generate_age_identity()?;
+	let new: Box<dyn EncryptionBackend> = Encryption {
+		recipients: vec![recipient.clone()],
+		..encryption.clone()
+	}
+	.backend()?;
+
+	// The new identity is written before any source is changed, so it is never
+	// lost if the sources could not be written
+	let staged = with_extension(&identity, ".new");
+	std::fs::write(&staged, new_identity)
+		.wrap_err_with(|| format!("Failed to write identity {}", staged.display()))?;
+	if let Ok(metadata) = std::fs::metadata(&identity) {
+		std::fs::set_permissions(&staged, metadata.permissions())?;
+	}
+
+	rewrite(&files, |content| new.encrypt(&old.decrypt(content)?))?;
+
+	for file in &files {
+		println!("{} {}", style("reencrypted").green(), file.display());
+	}
+
+	let backup = with_extension(&identity, ".old");
+	std::fs::rename(&identity, &backup)
+		.wrap_err_with(|| format!("Failed to back up identity {}", identity.display()))?;
+	std::fs::rename(&staged, &identity)
+		.wrap_err_with(|| format!("Failed to write identity {}", identity.display()))?;
+	println!("{} {}", style("rotated").green(), identity.display());
+
+	edit_profiles(source, |value| set_recipient(value, &recipient))
+}
@@ -0,0 +1,436 @@
+//! Export of profiles into [chezmoi](https://www.chezmoi.io/) source
+//! directories.
+//!
+//! All items of the profile are rendered as they would be deployed and written
+//! to the source directory with the attributes of chezmoi encoded in their
+//! names (e.g. `dot_`, `executable_` or `symlink_`). Pre-hooks and post-hooks
+//! become `run_before_`/`run_after_` scripts.
+//!
+//! As chezmoi manages the home directory, items which are deployed outside of
+//! it are reported as needing manual attention.
+
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use console::style;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::{LayeredProfile, MergeMode};
+use punktf_lib::visit::plan::{Action, ItemKind, PlanItem, Planner};
+
+use crate::import::chezmoi::ATTRIBUTES;
+
+/// Directory of chezmoi in which scripts are run without creating a directory
+/// in the target.
+const SCRIPTS_DIR: &str = ".chezmoiscripts";
+
+/// Options for the export into a chezmoi source directory.
+#[derive(Debug)]
+pub struct ChezmoiOptions {
+	/// The chezmoi source directory.
+	pub dir: PathBuf,
+
+	/// Overwrites existing files in the source directory.
+	pub force: bool,
+}
+
+/// Encodes a single file or directory `name` of the target together with its
+/// `attributes` (in the order chezmoi expects them) into a source name.
+fn encode_name(name: &str, attributes: &[&str], is_file: bool) -> String {
+	let mut encoded = attributes
+		.iter()
+		.map(|attribute| format!("{attribute}_"))
+		.collect::<String>();
+
+	match name.strip_prefix('.') {
+		Some(rest) => {
+			encoded.push_str("dot_");
+			encoded.push_str(rest);
+		}
+		None => {
+			// Names which look like attributes would be parsed as such
+			let is_ambiguous = ATTRIBUTES
+				.iter()
+				.chain(&["dot", "literal"])
+				.any(|attribute| {
+					name.strip_prefix(attribute)
+						.is_some_and(|rest| rest.starts_with('_'))
+				});
+
+			if is_ambiguous {
+				encoded.push_str("literal_");
+			}
+
+			encoded.push_str(name);
+		}
+	}
+
+	if is_file
+		&& [".tmpl", ".literal"]
+			.iter()
+			.any(|suffix| name.ends_with(suffix))
+	{
+		encoded.push_str(".literal");
+	}
+
+	encoded
+}
+
+/// Encodes the `relative` target path of an item into a path inside of the
+/// chezmoi source directory. Only the last component gets the `attributes`.
+fn encode_path(relative: &Path, attributes: &[&str], is_file: bool) -> PathBuf {
+	let mut components = relative
+		.components()
+		.map(|component| component.as_os_str().to_string_lossy().into_owned())
+		.collect::<Vec<_>>();
+
+	let last = components.pop().unwrap_or_default();
+
+	components
+		.iter()
+		.map(|component| encode_name(component, &[], false))
+		.chain(std::iter::once(encode_name(&last, attributes, is_file)))
+		.collect()
+}
+
+/// State of an export.
+#[derive(Debug)]
+struct Exporter<'a> {
+	/// The chezmoi source directory.
+	dir: &'a Path,
+
+	/// Overwrite existing files.
+	force: bool,
+
+	/// Home directory, which is managed by chezmoi.
+	home: PathBuf,
+
+	/// Amount of items which need manual attention.
+	attention: Cell<usize>,
+}
+
+impl Exporter<'_> {
+	/// Reports an item which needs manual attention after the export.
+	fn attention(&self, path: &Path, reason: &str) {
+		println!(
+			"{} {} ({reason})",
+			style("attention").magenta(),
+			path.display()
+		);
+
+		self.attention.set(self.attention.get() + 1);
+	}
+
+	/// Writes `content` to `relative` inside of the chezmoi source directory.
+	fn write(&self, relative: &Path, content: &[u8]) -> Result<()> {
+		let to = self.dir.join(relative);
+
+		if !self.force && to.symlink_metadata().is_ok() {
+			return Err(eyre!(
+				"{} already exists. Use `--force` to overwrite it",
+				to.display()
+			));
+		}
+
+		if let Some(parent) = to.parent() {
+			std::fs::create_dir_all(parent)
+				.wrap_err_with(|| format!("Failed to create directory {}", parent.display()))?;
+		}
+
+		std::fs::write(&to, content).wrap_err_with(|| format!("Failed to write {}", to.display()))
+	}
+
+	/// Exports a single planned `item`.
+	fn export(
+		&self,
+		source: &PunktfSource,
+		profile: &LayeredProfile,
+		item: PlanItem,
+	) -> Result<()> {
+		let Ok(relative) = item.target_path.strip_prefix(&self.home) else {
+			self.attention(
+				&item.source_path,
+				"deployed outside of the home directory, which chezmoi does not manage",
+			);
+			return Ok(());
+		};
+
+		match (item.kind, item.action) {
+			(_, Action::Skip) => println!(
+				"{} {} ({})",
+				style("skipped").yellow(),
+				item.source_path.display(),
+				item.reason.as_deref().unwrap_or("skipped")
+			),
+			(_, Action::Error) => self.attention(
+				&item.source_path,
+				item.reason.as_deref().unwrap_or("failed to render"),
+			),
+			(ItemKind::Directory, _) => {
+				let to = self.dir.join(encode_path(relative, &[], false));
+				std::fs::create_dir_all(&to)
+					.wrap_err_with(|| format!("Failed to create directory {}", to.display()))?;
+			}
+			(ItemKind::Link, _) => {
+				let to = encode_path(relative, &["symlink"], true);
+				self.write(&to, item.source_path.to_string_lossy().as_bytes())?;
+				print_exported(&item.source_path, &self.dir.join(to));
+			}
+			(ItemKind::File, _) => {
+				let Some(content) = item.content else {
+					self.attention(&item.source_path, "file could not be rendered");
+					return Ok(());
+				};
+
+				let mut attributes = Vec::new();
+				if merge_mode(profile, &item.source_path) == Some(MergeMode::Keep) {
+					attributes.push("create");
+				}
+				if content.is_empty() {
+					attributes.push("empty");
+				}
+				if is_executable(&source.dotfile_path(&item.source_path)) {
+					attributes.push("executable");
+				}
+
+				let to = encode_path(relative, &attributes, true);
+				self.write(&to, &content)?;
+				print_exported(&item.source_path, &self.dir.join(to));
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Writes the `hooks` as script `name` into the scripts directory.
+	fn export_hooks(&self, name: &str, hooks: &[String]) -> Result<()> {
+		if hooks.is_empty() {
+			return Ok(());
+		}
+
+		let mut content = String::from("#!/bin/sh\nset -e\n\n");
+		for hook in hooks {
+			content.push_str(hook);
+			content.push('\n');
+		}
+
+		let to = Path::new(SCRIPTS_DIR).join(name);
+		self.write(&to, content.as_bytes())?;
+
+		println!(
+			"{} {} hook(s) -> {}",
+			style("exported").green(),
+			hooks.len(),
+			self.dir.join(to).display()
+		);
+
+		Ok(())
+	}
+}
+
+/// Returns the merge mode of the dotfile which contains the file at
+/// `relative` (inside of the `dotfiles` directory).
+fn merge_mode(profile: &LayeredProfile, relative: &Path) -> Option<MergeMode> {
+	profile
+		.dotfiles()
+		.filter(|dotfile| relative.starts_with(&dotfile.path))
+		.max_by_key(|dotfile| dotfile.path.components().count())
+		.and_then(|dotfile| dotfile.merge)
+}
+
+/// Checks if the file at `path` is executable.
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+	use std::os::unix::fs::PermissionsExt;
+
+	path.metadata()
+		.is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+}
+
+/// Checks if the file at `path` is executable.
+#[cfg(not(unix))]
+const fn is_executable(_: &Path) -> bool {
+	false
+}
+
+/// Prints a line for an exported item.
+fn print_exported(from: &Path, to: &Path) {
+	println!(
+		"{} {} -> {}",
+		style("exported").green(),
+		from.display(),
+		to.display()
+	);
+}
+
+/// Exports the `profile` into a chezmoi source directory and returns the
+/// amount of items which need manual attention.
+///
+/// All items are rendered as if none of their targets existed, so the export
+/// does not depend on the current state of the target.
+///
+/// # Errors
+///
+/// An error is returned if the home directory could not be determined or if
+/// any file could not be written.
+pub fn export(
+	source: &PunktfSource,
+	profile: &mut LayeredProfile,
+	options: ChezmoiOptions,
+) -> Result<usize> {
+	let home = dirs::home_dir().ok_or_else(|| eyre!("Failed to get the home directory"))?;
+
+	export_home(source, profile, options, home)
+}
+
+/// Exports the `profile` like [`export`] with `home` as the home directory
+/// managed by chezmoi.
+fn export_home(
+	source: &PunktfSource,
+	profile: &mut LayeredProfile,
+	options: ChezmoiOptions,
+	home: PathBuf,
+) -> Result<usize> {
+	let plan = Planner::new()
+		.with_contents(true)
+		.ignore_targets(true)
+		.plan(source, profile);
+
+	std::fs::create_dir_all(&options.dir)
+		.wrap_err_with(|| format!("Failed to create directory {}", options.dir.display()))?;
+
+	let exporter = Exporter {
+		dir: &options.dir,
+		force: options.force,
+		home,
+		attention: Cell::new(0),
+	};
+
+	for item in plan.items {
+		exporter.export(source, profile, item)?;
+	}
+
+	exporter.export_hooks("run_before_punktf-pre-hooks.sh", &plan.pre_hooks)?;
+	exporter.export_hooks("run_after_punktf-post-hooks.sh", &plan.post_hooks)?;
+
+	if !plan.pre_hooks.is_empty() || !plan.post_hooks.is_empty() {
+		exporter.attention(
+			&options.dir.join(SCRIPTS_DIR),
+			"hooks run on every `chezmoi apply` and inside of the home directory instead of \
+			 the profiles directory",
+		);
+	}
+
+	Ok(exporter.attention.get())
+}
+
+#[cfg(test)]
+mod tests {
+	use punktf_lib::profile::resolve_profile;
+
+	use super::*;
+	use crate::import::tests::{files, fixture};
+	use crate::import::{self, Destination};
+
+	#[test]
+	fn encode_names() {
+		assert_eq!(encode_name(".bashrc", &[], true), "dot_bashrc");
+		assert_eq!(
+			encode_name(".profile", &["create"], true),
+			"create_dot_profile"
+		);
+		assert_eq!(
+			encode_name("hello", &["executable"], true),
+			"executable_hello"
+		);
+		assert_eq!(encode_name("run_me", &[], true), "literal_run_me");
+		assert_eq!(encode_name("dot_vim", &[], false), "literal_dot_vim");
+		assert_eq!(
+			encode_name("settings.tmpl", &[], true),
+			"settings.tmpl.literal"
+		);
+		assert_eq!(encode_name("vim.tmpl", &[], false), "vim.tmpl");
+		assert_eq!(
+			encode_path(Path::new(".config/nvim/init.vim"), &["empty"], true),
+			Path::new("dot_config/nvim/empty_init.vim")
+		);
+	}
+
+	#[test]
+	fn round_trip() -> Result<()> {
+		let dir = tempfile::tempdir()?;
+		let root = dir.path().join("source");
+		let home = dir.path().join("home");
+
+		let destination = Destination::create(root.clone(), false)?;
+		let profile = import::chezmoi::import(
+			&destination,
+			import::chezmoi::ChezmoiOptions {
+				dir: Some(fixture("chezmoi")),
+				target: Some(home.clone()),
+			},
+		)?;
+		destination.write_profile("chezmoi", &profile)?;
+
+		let source = PunktfSource::from_root(root)?;
+		let mut builder = LayeredProfile::build();
+		resolve_profile(&mut builder, &source, "chezmoi")?;
+		let mut profile = builder.finish();
+
+		let exported = dir.path().join("chezmoi");
+		let attention = export_home(
+			&source,
+			&mut profile,
+			ChezmoiOptions {
+				dir: exported.clone(),
+				force: false,
+			},
+			home.clone(),
+		)?;
+
+		// The hooks, which run inside of the home directory
+		assert_eq!(attention, 1);
+
+		// Templates are rendered and permissions, which are not imported, are
+		// lost
+		assert_eq!(
+			files(&exported),
+			[
+				".chezmoiscripts/run_after_punktf-post-hooks.sh",
+				".chezmoiscripts/run_before_punktf-pre-hooks.sh",
+				"create_dot_profile",
+				"dot_config/app/settings.tmpl.literal",
+				"dot_gitconfig",
+				"dot_local/bin/hello",
+				"dot_ssh/config",
+				"empty_dot_hushlogin",
+				"literal_run_me",
+				"symlink_dot_vimrc",
+			]
+		);
+
+		for unchanged in [
+			"create_dot_profile",
+			"dot_config/app/settings.tmpl.literal",
+			"literal_run_me",
+		] {
+			assert_eq!(
+				std::fs::read(exported.join(unchanged))?,
+				std::fs::read(fixture("chezmoi").join(unchanged))?,
+				"{unchanged}"
+			);
+		}
+
+		assert_eq!(
+			std::fs::read_to_string(exported.join("dot_gitconfig"))?,
+			"[user]\n\tname = Me\n\temail = me@example.com\n"
+		);
+		assert_eq!(
+			std::fs::read_to_string(exported.join("symlink_dot_vimrc"))?,
+			home.join(".config/nvim/init.vim").to_string_lossy()
+		);
+
+		Ok(())
+	}
+}
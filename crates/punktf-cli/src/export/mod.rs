@@ -0,0 +1,7 @@
+//! Exports of profiles into the formats of other tools (`punktf export`).
+//!
+//! Each exporter renders the resolved profile (templates, transformers and
+//! merge modes applied) into the layout of another tool, so the dotfiles can be
+//! used without punktf.
+
+pub mod chezmoi;
@@ -0,0 +1,212 @@
+//! Formatting of profile files (`punktf fmt`).
+//!
+//! Profiles are normalized by parsing and serializing them again, which orders
+//! the keys in the order of the profile definition and uses a consistent
+//! indentation. Names of enum variants (e.g. merge modes) are matched case
+//! insensitively and written in their canonical casing.
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use console::style;
+use punktf_lib::profile::Profile;
+use serde_json::Value;
+
+/// Keys which name an enum variant (e.g. `FileExists: ~/.bashrc`).
+const VARIANT_KEYS: &[&str] = &["LineTerminator", "FileExists", "FileContains", "Command"];
+
+/// Keys whose values are enum variants and their possible values.
+const VARIANT_VALUES: &[(&str, &[&str])] = &[
+	("merge", &["Overwrite", "Keep", "Ask"]),
+	("LineTerminator", &["LF", "CRLF"]),
+	("backend", &["Age", "Gpg"]),
+];
+
+/// File format of a profile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+	/// A `.json` profile.
+	Json,
+
+	/// A `.yaml`/`.yml` profile.
+	Yaml,
+}
+
+impl Format {
+	/// Determines the format of the profile at `path` by its extension.
+	pub fn from_path(path: &Path) -> Option<Self> {
+		let extension = path.extension()?.to_string_lossy().to_lowercase();
+
+		match extension.as_str() {
+			"json" => Some(Self::Json),
+			"yaml" | "yml" => Some(Self::Yaml),
+			_ => None,
+		}
+	}
+
+	/// Parses the `content` of a profile file without interpreting it.
+	pub fn parse(self, content: &str) -> Result<Value> {
+		match self {
+			Self::Json => serde_json::from_str(content).wrap_err("Failed to parse profile"),
+			Self::Yaml => {
+				let mut value: serde_yaml::Value =
+					serde_yaml::from_str(content).wrap_err("Failed to parse profile")?;
+				untag(&mut value);
+
+				serde_json::to_value(value).wrap_err("Failed to parse profile")
+			}
+		}
+	}
+
+	/// Serializes the `profile` in its canonical form.
+	pub fn serialize(self, profile: &Profile) -> Result<String> {
+		match self {
+			Self::Json => serde_json::to_string_pretty(profile)
+				.map(|content| format!("{content}\n"))
+				.wrap_err("Failed to serialize profile"),
+			Self::Yaml => serde_yaml::to_string(profile).wrap_err("Failed to serialize profile"),
+		}
+	}
+}
+
+/// Replaces all tagged values (e.g. `!LineTerminator LF`), which `serde_yaml`
+/// uses for enums, with a map of the tag to the value (`LineTerminator: LF`),
+/// the same as enums are represented in json.
+fn untag(value: &mut serde_yaml::Value) {
+	match value {
+		serde_yaml::Value::Tagged(tagged) => {
+			let tag = tagged.tag.to_string();
+			let mut inner = std::mem::take(&mut tagged.value);
+			untag(&mut inner);
+
+			let mut map = serde_yaml::Mapping::new();
+			map.insert(tag.trim_start_matches('!').into(), inner);
+			*value = serde_yaml::Value::Mapping(map);
+		}
+		serde_yaml::Value::Mapping(map) => map.values_mut().for_each(untag),
+		serde_yaml::Value::Sequence(values) => values.iter_mut().for_each(untag),
+		_ => {}
+	}
+}
+
+/// Returns all profile files inside of the `profiles` directory of `source`
+/// sorted by name.
+pub fn profile_files(source: &Path) -> Result<Vec<(PathBuf, Format)>> {
+	let profiles = source.join("profiles");
+
+	let mut files = std::fs::read_dir(&profiles)
+		.wrap_err_with(|| format!("Failed to read directory {}", profiles.display()))?
+		.map(|entry| entry.map(|entry| entry.path()))
+		.collect::<std::io::Result<Vec<_>>>()?
+		.into_iter()
+		.filter(|path| path.is_file())
+		.filter_map(|path| Format::from_path(&path).map(|format| (path, format)))
+		.collect::<Vec<_>>();
+	files.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+	Ok(files)
+}
+
+/// Returns the variant out of `variants` which matches `name` case
+/// insensitively.
+fn canonical_variant<'a>(name: &str, variants: &[&'a str]) -> Option<&'a str> {
+	variants
+		.iter()
+		.find(|variant| variant.eq_ignore_ascii_case(name))
+		.copied()
+}
+
+/// Replaces the names of all enum variants in `value` with their canonical
+/// casing.
+fn canonicalize(value: &mut Value) {
+	match value {
+		Value::Object(map) => {
+			let keys = map.keys().cloned().collect::<Vec<_>>();
+
+			for key in keys {
+				let Some(canonical) = canonical_variant(&key, VARIANT_KEYS) else {
+					continue;
+				};
+
+				if canonical != key {
+					if let Some(value) = map.remove(&key) {
+						map.insert(String::from(canonical), value);
+					}
+				}
+			}
+
+			for (key, variants) in VARIANT_VALUES {
+				if let Some(Value::String(name)) = map.get_mut(*key) {
+					if let Some(canonical) = canonical_variant(name, variants) {
+						*name = String::from(canonical);
+					}
+				}
+			}
+
+			map.values_mut().for_each(canonicalize);
+		}
+		Value::Array(values) => values.iter_mut().for_each(canonicalize),
+		_ => {}
+	}
+}
+
+/// Formats the profile `content` and returns the canonical form.
+fn format(content: &str, format: Format) -> Result<String> {
+	let mut value = format.parse(content)?;
+	canonicalize(&mut value);
+
+	let profile: Profile = serde_json::from_value(value).wrap_err("Invalid profile")?;
+
+	format.serialize(&profile)
+}
+
+/// Formats all profiles inside of the `profiles` directory of `source`.
+///
+/// If `check` is set, no profile is written and an error is returned if any
+/// of them is not formatted.
+///
+/// # Errors
+///
+/// An error is returned if any profile could not be read, formatted or
+/// written.
+pub fn run(source: &Path, check: bool) -> Result<()> {
+	let mut failed = 0;
+	let mut unformatted = 0;
+
+	for (path, profile_format) in profile_files(source)? {
+		let content = std::fs::read_to_string(&path)
+			.wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+
+		let formatted = match format(&content, profile_format) {
+			Ok(formatted) => formatted,
+			Err(err) => {
+				println!("{} {} ({err:#})", style("failed").red(), path.display());
+				failed += 1;
+				continue;
+			}
+		};
+
+		if formatted == content {
+			continue;
+		}
+
+		unformatted += 1;
+
+		if check {
+			println!("{} {}", style("unformatted").yellow(), path.display());
+		} else {
+			std::fs::write(&path, formatted)
+				.wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+			println!("{} {}", style("formatted").green(), path.display());
+		}
+	}
+
+	if failed > 0 {
+		Err(eyre!("{failed} profile(s) failed to format"))
+	} else if check && unformatted > 0 {
+		Err(eyre!("{unformatted} profile(s) are not formatted"))
+	} else {
+		Ok(())
+	}
+}
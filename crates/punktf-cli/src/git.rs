@@ -0,0 +1,85 @@
+//! Helpers to query information from git repositories.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+
+/// Runs git with the given arguments inside of `dir` and returns the output.
+fn git(dir: &Path, args: &[&str]) -> Result<String> {
+	let output = Command::new("git")
+		.arg("-C")
+		.arg(dir)
+		.args(args)
+		.output()
+		.wrap_err("Failed to execute git")?;
+
+	if !output.status.success() {
+		return Err(eyre!(
+			"git {} failed: {}",
+			args.join(" "),
+			String::from_utf8_lossy(&output.stderr).trim()
+		));
+	}
+
+	Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Runs git with the given arguments inside of `dir` and returns each line of
+/// the output as path.
+fn git_paths(dir: &Path, args: &[&str]) -> Result<Vec<PathBuf>> {
+	Ok(git(dir, args)?
+		.lines()
+		.filter(|line| !line.is_empty())
+		.map(PathBuf::from)
+		.collect())
+}
+
+/// Returns all files inside of `dir` which changed since the git revision
+/// `rev`.
+///
+/// This includes uncommitted and untracked files. All paths are relative to
+/// `dir`.
+pub fn changed_files(dir: &Path, rev: &str) -> Result<Vec<PathBuf>> {
+	let mut files = git_paths(dir, &["diff", "--name-only", "--relative", rev, "--"])?;
+	files.extend(git_paths(
+		dir,
+		&["ls-files", "--others", "--exclude-standard"],
+	)?);
+
+	files.sort();
+	files.dedup();
+
+	Ok(files)
+}
+
+/// Returns all files which are tracked by the repository `git_dir` (e.g. a
+/// bare repository) with the work tree `work_tree`.
+///
+/// All paths are relative to `work_tree`.
+pub fn tracked_files(git_dir: &Path, work_tree: &Path) -> Result<Vec<PathBuf>> {
+	git_paths(
+		work_tree,
+		&[
+			"--git-dir",
+			&git_dir.to_string_lossy(),
+			"--work-tree",
+			".",
+			"ls-files",
+		],
+	)
+}
+
+/// Returns the url of the remote `origin` of the repository containing `dir`.
+pub fn remote_url(dir: &Path) -> Result<String> {
+	Ok(git(dir, &["remote", "get-url", "origin"])?
+		.trim()
+		.to_owned())
+}
+
+/// Returns the path of `dir` relative to the root of its repository (e.g.
+/// `dotfiles/` or an empty string for the root itself).
+pub fn repo_prefix(dir: &Path) -> Result<String> {
+	Ok(git(dir, &["rev-parse", "--show-prefix"])?.trim().to_owned())
+}
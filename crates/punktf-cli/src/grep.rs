@@ -0,0 +1,83 @@
+//! Search of a pattern in the dotfiles of a profile (`punktf grep`).
+//!
+//! Both the raw dotfiles in the source and, optionally, their rendered
+//! contents (templates resolved and transformers applied) are searched, as
+//! templating might rewrite the values one is looking for.
+
+use color_eyre::Result;
+use console::style;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::LayeredProfile;
+use punktf_lib::visit::plan::{ItemKind, Planner};
+use regex::Regex;
+
+/// Returns all lines of `content` which match `regex` together with their line
+/// number, with all matches highlighted.
+fn matching_lines(regex: &Regex, content: &str) -> Vec<(usize, String)> {
+	content
+		.lines()
+		.enumerate()
+		.filter(|(_, line)| regex.is_match(line))
+		.map(|(idx, line)| {
+			let highlighted = regex.replace_all(line, |captures: &regex::Captures<'_>| {
+				style(&captures[0]).red().bold().to_string()
+			});
+
+			(idx + 1, highlighted.into_owned())
+		})
+		.collect()
+}
+
+/// Prints the matching `lines` of the `kind` of content (e.g. `source`).
+fn print_lines(kind: &str, lines: &[(usize, String)]) {
+	for (line_nr, line) in lines {
+		println!("  {}:{line_nr}: {line}", style(kind).dim());
+	}
+}
+
+/// Searches all files of the `profile` for `regex` and prints the matching
+/// lines.
+///
+/// Returns the amount of files with a match.
+pub fn run(
+	source: &PunktfSource,
+	profile: &mut LayeredProfile,
+	regex: &Regex,
+	rendered: bool,
+) -> Result<usize> {
+	let plan = Planner::new()
+		.with_contents(rendered)
+		.ignore_targets(true)
+		.plan(source, profile);
+
+	let mut matched = 0;
+
+	for item in plan.items.iter().filter(|item| item.kind == ItemKind::File) {
+		let source_lines = std::fs::read_to_string(source.dotfile_path(&item.source_path))
+			.map(|content| matching_lines(regex, &content))
+			.unwrap_or_default();
+
+		let rendered_lines = item
+			.content
+			.as_deref()
+			.and_then(|content| std::str::from_utf8(content).ok())
+			.map(|content| matching_lines(regex, content))
+			.unwrap_or_default();
+
+		if source_lines.is_empty() && rendered_lines.is_empty() {
+			continue;
+		}
+
+		matched += 1;
+
+		println!(
+			"{} -> {}",
+			style(item.source_path.display()).bold(),
+			style(item.target_path.display()).cyan()
+		);
+		print_lines("source", &source_lines);
+		print_lines("rendered", &rendered_lines);
+	}
+
+	Ok(matched)
+}
@@ -0,0 +1,669 @@
+//! History of all deployments, stored in a SQLite database.
+//!
+//! For each deployment the profile, start time, duration and status are
+//! recorded together with all items (status, change, content hash, duration
+//! and bytes written) and the full deployment report as json.
+//!
+//! Deployed files are not read again to record their content hash. The hashes
+//! which the deployer computed anyway (for templates and transformed files)
+//! are recorded, and unchanged files keep the hash of their last recorded
+//! deployment. Other files (e.g. copied files) are only hashed if
+//! `history_hashes` is set in the global configuration.
+//!
+//! The database is read from the path given by the `history_file` of the global
+//! [`Config`](`crate::config::Config`) or from
+//! `<data dir>/punktf/history.sqlite` (e.g.
+//! `~/.local/share/punktf/history.sqlite` on linux).
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use punktf_lib::visit::deploy::deployment::{
+	DeployedDotfile, Deployment, DeploymentDiff, DeploymentStatus, ItemChange, ItemStatus,
+};
+use rusqlite::{params, Connection, OptionalExtension as _};
+use sha2::{Digest as _, Sha256};
+
+use crate::config::Config;
+use crate::util::{self, Group};
+
+/// Version of the database schema.
+///
+/// Must be increased (and a migration added to [`History::migrate`]) on each
+/// change to the schema.
+const SCHEMA_VERSION: i64 = 1;
+
+/// Initial database schema.
+const SCHEMA_V1: &str = r"
+CREATE TABLE deployments (
+	id INTEGER PRIMARY KEY AUTOINCREMENT,
+	profile TEXT NOT NULL,
+	started_at INTEGER NOT NULL,
+	duration_ms INTEGER NOT NULL,
+	status TEXT NOT NULL,
+	reason TEXT,
+	report TEXT NOT NULL
+);
+
+CREATE INDEX deployments_profile ON deployments (profile, started_at);
+
+CREATE TABLE items (
+	deployment_id INTEGER NOT NULL REFERENCES deployments (id) ON DELETE CASCADE,
+	kind TEXT NOT NULL,
+	target TEXT NOT NULL,
+	status TEXT NOT NULL,
+	change TEXT,
+	reason TEXT,
+	hash TEXT,
+	duration_us INTEGER NOT NULL DEFAULT 0,
+	bytes INTEGER NOT NULL DEFAULT 0,
+	PRIMARY KEY (deployment_id, kind, target)
+);
+
+CREATE INDEX items_target ON items (target);
+";
+
+/// Returns the sha256 hash of the file at `path` as hex string.
+///
+/// Returns `None` if `path` is not a readable file.
+fn file_hash(path: &Path) -> Option<String> {
+	if !path.is_file() {
+		return None;
+	}
+
+	let content = std::fs::read(path).ok()?;
+
+	Some(
+		Sha256::digest(content)
+			.iter()
+			.map(|byte| format!("{byte:02x}"))
+			.collect(),
+	)
+}
+
+/// Returns the content hash of the successfully deployed `dotfile` at `path`.
+///
+/// The hash computed by the deployer is used if there is one. Otherwise an
+/// unchanged file keeps the hash of its last recorded deployment, and other
+/// files are only hashed if `hash_files` is set.
+fn content_hash(
+	conn: &Connection,
+	path: &Path,
+	dotfile: &DeployedDotfile,
+	hash_files: bool,
+) -> Result<Option<String>> {
+	if let Some(hash) = dotfile.content_hash() {
+		return Ok(Some(hash.to_owned()));
+	}
+
+	if dotfile.change() == Some(ItemChange::Unchanged) {
+		let previous = conn
+			.query_row(
+				"SELECT hash FROM items WHERE kind = 'file' AND target = ?1 AND hash IS NOT NULL \
+				 ORDER BY deployment_id DESC LIMIT 1",
+				params![path.to_string_lossy()],
+				|row| row.get(0),
+			)
+			.optional()?;
+
+		if previous.is_some() {
+			return Ok(previous);
+		}
+	}
+
+	Ok(hash_files.then(|| file_hash(path)).flatten())
+}
+
+/// Converts a point in time to milliseconds since the unix epoch.
+fn unix_millis(time: SystemTime) -> i64 {
+	time.duration_since(SystemTime::UNIX_EPOCH)
+		.map_or(0, duration_millis)
+}
+
+/// Converts a duration to milliseconds, saturating on overflow.
+fn duration_millis(duration: Duration) -> i64 {
+	i64::try_from(duration.as_millis()).unwrap_or(i64::MAX)
+}
+
+/// Returns the name and reason of an item status.
+fn status_parts(status: &ItemStatus) -> (&'static str, Option<String>) {
+	let reason = match status {
+		ItemStatus::Success | ItemStatus::Cancelled => None,
+		ItemStatus::Skipped(reason) => Some(reason.to_string()),
+		ItemStatus::Failed(err) => Some(err.to_string()),
+	};
+
+	(status.action(), reason)
+}
+
+/// Amount of items of a recorded deployment by group.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ItemCounts {
+	/// Items whose target was created.
+	pub created: usize,
+
+	/// Items whose target was changed.
+	pub updated: usize,
+
+	/// Items whose target already had the same contents.
+	pub unchanged: usize,
+
+	/// Items which were skipped.
+	pub skipped: usize,
+
+	/// Items which failed to deploy.
+	pub failed: usize,
+}
+
+/// A recorded deployment.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeploymentRecord {
+	/// Id of the deployment.
+	pub id: i64,
+
+	/// Name of the deployed profile.
+	pub profile: String,
+
+	/// Local start time of the deployment (`YYYY-MM-DD HH:MM:SS`).
+	pub time: String,
+
+	/// Duration of the deployment.
+	pub duration: Duration,
+
+	/// Status of the deployment (`success` or `failed`).
+	pub status: String,
+
+	/// Reason why the deployment failed.
+	pub reason: Option<String>,
+
+	/// Amount of items by group.
+	pub counts: ItemCounts,
+}
+
+/// Query which selects all columns of a [`DeploymentRecord`].
+const SELECT_DEPLOYMENT: &str = "
+SELECT
+	d.id,
+	d.profile,
+	datetime(d.started_at / 1000, 'unixepoch', 'localtime'),
+	d.duration_ms,
+	d.status,
+	d.reason,
+	COUNT(CASE WHEN i.status = 'deployed' AND i.change = 'created' THEN 1 END),
+	COUNT(CASE WHEN i.status = 'deployed' AND (i.change = 'updated' OR i.change IS NULL) THEN 1 END),
+	COUNT(CASE WHEN i.status = 'deployed' AND i.change = 'unchanged' THEN 1 END),
+	COUNT(CASE WHEN i.status = 'skipped' THEN 1 END),
+	COUNT(CASE WHEN i.status = 'failed' THEN 1 END)
+FROM deployments d
+LEFT JOIN items i ON i.deployment_id = d.id
+";
+
+impl ItemCounts {
+	/// Returns the amount of items of `group`.
+	pub const fn get(&self, group: Group) -> usize {
+		match group {
+			Group::Created => self.created,
+			Group::Updated => self.updated,
+			Group::Unchanged => self.unchanged,
+			Group::Skipped => self.skipped,
+			Group::Failed => self.failed,
+		}
+	}
+}
+
+impl DeploymentRecord {
+	/// Reads a record from a row of [`SELECT_DEPLOYMENT`].
+	fn from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Self> {
+		let count = |idx: usize| {
+			row.get::<_, i64>(idx)
+				.map(|c| usize::try_from(c).unwrap_or(0))
+		};
+
+		Ok(Self {
+			id: row.get(0)?,
+			profile: row.get(1)?,
+			time: row.get(2)?,
+			duration: Duration::from_millis(u64::try_from(row.get::<_, i64>(3)?).unwrap_or(0)),
+			status: row.get(4)?,
+			reason: row.get(5)?,
+			counts: ItemCounts {
+				created: count(6)?,
+				updated: count(7)?,
+				unchanged: count(8)?,
+				skipped: count(9)?,
+				failed: count(10)?,
+			},
+		})
+	}
+}
+
+/// Store of all recorded deployments.
+#[derive(Debug)]
+pub struct History {
+	/// Connection to the database.
+	conn: Connection,
+}
+
+impl History {
+	/// Returns the default path of the database.
+	fn default_path() -> Option<PathBuf> {
+		dirs::data_dir().map(|dir| dir.join("punktf").join("history.sqlite"))
+	}
+
+	/// Opens the database at the path given by the config or the default path.
+	///
+	/// # Errors
+	///
+	/// An error is returned if no path could be determined or if the database
+	/// could not be opened.
+	pub fn open_default(config: &Config) -> Result<Self> {
+		let path = config
+			.history_file
+			.clone()
+			.or_else(Self::default_path)
+			.ok_or_else(|| eyre!("Failed to determine the path of the history database"))?;
+
+		Self::open(&path)
+	}
+
+	/// Opens (and if necessary creates) the database at `path`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the database could not be opened or migrated.
+	pub fn open(path: &Path) -> Result<Self> {
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent)
+				.wrap_err_with(|| format!("Failed to create directory {}", parent.display()))?;
+		}
+
+		let conn = Connection::open(path)
+			.wrap_err_with(|| format!("Failed to open history database {}", path.display()))?;
+
+		let mut history = Self { conn };
+		history.migrate()?;
+
+		Ok(history)
+	}
+
+	/// Migrates the database schema to [`SCHEMA_VERSION`].
+	fn migrate(&mut self) -> Result<()> {
+		self.conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+
+		let version: i64 = self
+			.conn
+			.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+		if version > SCHEMA_VERSION {
+			return Err(eyre!(
+				"The history database was created by a newer version of punktf (schema version \
+				 {version})"
+			));
+		}
+
+		let tx = self.conn.transaction()?;
+
+		if version < 1 {
+			tx.execute_batch(SCHEMA_V1)?;
+		}
+
+		tx.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+		tx.commit()?;
+
+		Ok(())
+	}
+
+	/// Records a finished `deployment` of the profile `profile_name`.
+	///
+	/// If `hash_files` is set, deployed files whose content hash is not known
+	/// otherwise are read and hashed, so this must be called directly after the
+	/// deployment.
+	///
+	/// Returns the id of the recorded deployment.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the deployment could not be written to the
+	/// database.
+	pub fn record(
+		&mut self,
+		profile_name: &str,
+		deployment: &Deployment,
+		hash_files: bool,
+	) -> Result<i64> {
+		let (status, reason) = match deployment.status() {
+			DeploymentStatus::Success => ("success", None),
+			DeploymentStatus::Failed(err) => ("failed", Some(err.to_string())),
+		};
+
+		let tx = self.conn.transaction()?;
+
+		tx.execute(
+			"INSERT INTO deployments (profile, started_at, duration_ms, status, reason, report) \
+			 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+			params![
+				profile_name,
+				unix_millis(*deployment.time_start()),
+				duration_millis(deployment.duration().unwrap_or_default()),
+				status,
+				reason,
+				serde_json::to_string(deployment)?,
+			],
+		)?;
+
+		let id = tx.last_insert_rowid();
+
+		{
+			let mut insert = tx.prepare(
+				"INSERT INTO items (deployment_id, kind, target, status, change, reason, hash, \
+				 duration_us, bytes) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+			)?;
+
+			for (path, dotfile) in deployment.dotfiles() {
+				let (status, reason) = status_parts(dotfile.status());
+				let hash = if dotfile.status().is_success() {
+					content_hash(&tx, path, dotfile, hash_files)?
+				} else {
+					None
+				};
+
+				insert.execute(params![
+					id,
+					"file",
+					path.to_string_lossy(),
+					status,
+					dotfile.change().map(|change| change.as_str()),
+					reason,
+					hash,
+					i64::try_from(dotfile.metrics().total_time().as_micros()).unwrap_or(i64::MAX),
+					i64::try_from(dotfile.metrics().bytes_written).unwrap_or(i64::MAX),
+				])?;
+			}
+
+			for (path, link) in deployment.symlinks() {
+				let (status, reason) = status_parts(link.status());
+
+				insert.execute(params![
+					id,
+					"link",
+					path.to_string_lossy(),
+					status,
+					link.change().map(|change| change.as_str()),
+					reason,
+					None::<String>,
+					0,
+					0,
+				])?;
+			}
+		}
+
+		tx.commit()?;
+
+		Ok(id)
+	}
+
+	/// Returns the latest `limit` deployments, newest first.
+	///
+	/// If `profile` is given, only deployments of this profile are returned.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the database could not be queried.
+	pub fn list(&self, profile: Option<&str>, limit: usize) -> Result<Vec<DeploymentRecord>> {
+		let mut stmt = self.conn.prepare(&format!(
+			"{SELECT_DEPLOYMENT} WHERE ?1 IS NULL OR d.profile = ?1 GROUP BY d.id ORDER BY d.id \
+			 DESC LIMIT ?2"
+		))?;
+
+		let records = stmt
+			.query_map(
+				params![profile, i64::try_from(limit).unwrap_or(i64::MAX)],
+				DeploymentRecord::from_row,
+			)?
+			.collect::<rusqlite::Result<_>>()?;
+
+		Ok(records)
+	}
+
+	/// Returns the deployment with the given `id`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if there is no deployment with the id or the
+	/// database could not be queried.
+	pub fn get(&self, id: i64) -> Result<DeploymentRecord> {
+		self.conn
+			.query_row(
+				&format!("{SELECT_DEPLOYMENT} WHERE d.id = ?1 GROUP BY d.id"),
+				params![id],
+				DeploymentRecord::from_row,
+			)
+			.map_err(|err| match err {
+				rusqlite::Error::QueryReturnedNoRows => eyre!("No deployment with the id {id}"),
+				err => err.into(),
+			})
+	}
+
+	/// Returns the full report of the deployment with the given `id`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if there is no deployment with the id or the
+	/// report could not be read.
+	pub fn report(&self, id: i64) -> Result<Deployment> {
+		let report: String = self
+			.conn
+			.query_row(
+				"SELECT report FROM deployments WHERE id = ?1",
+				params![id],
+				|row| row.get(0),
+			)
+			.map_err(|err| match err {
+				rusqlite::Error::QueryReturnedNoRows => eyre!("No deployment with the id {id}"),
+				err => err.into(),
+			})?;
+
+		serde_json::from_str(&report)
+			.wrap_err_with(|| format!("Failed to read report of deployment {id}"))
+	}
+
+	/// Returns the latest deployment which contains an item with the `target`
+	/// path together with the status of the item.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the database could not be queried.
+	pub fn last_deployment_of(&self, target: &Path) -> Result<Option<(DeploymentRecord, String)>> {
+		let item: Option<(i64, String)> = self
+			.conn
+			.query_row(
+				"SELECT deployment_id, status FROM items WHERE target = ?1 ORDER BY deployment_id \
+				 DESC LIMIT 1",
+				params![target.to_string_lossy()],
+				|row| Ok((row.get(0)?, row.get(1)?)),
+			)
+			.optional()?;
+
+		item.map(|(id, status)| Ok((self.get(id)?, status)))
+			.transpose()
+	}
+
+	/// Returns the content hashes of all deployed files of the deployment with
+	/// the given `id` by their target path.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the database could not be queried.
+	pub fn hashes(&self, id: i64) -> Result<HashMap<PathBuf, String>> {
+		let mut stmt = self.conn.prepare(
+			"SELECT target, hash FROM items WHERE deployment_id = ?1 AND hash IS NOT NULL",
+		)?;
+
+		let hashes = stmt
+			.query_map(params![id], |row| {
+				Ok((PathBuf::from(row.get::<_, String>(0)?), row.get(1)?))
+			})?
+			.collect::<rusqlite::Result<_>>()?;
+
+		Ok(hashes)
+	}
+}
+
+/// Formats a duration with millisecond precision.
+fn format_duration(duration: Duration) -> String {
+	if duration < Duration::from_secs(1) {
+		format!("{}ms", duration.as_millis())
+	} else {
+		format!("{:.2}s", duration.as_secs_f64())
+	}
+}
+
+/// Styles the status of a deployment.
+fn style_status(status: &str) -> console::StyledObject<&str> {
+	if status == "success" {
+		console::style(status).green()
+	} else {
+		console::style(status).red()
+	}
+}
+
+/// Prints a table of the recorded deployments with their time, profile,
+/// status, amount of items by group and duration.
+pub fn print_list(records: &[DeploymentRecord]) {
+	if records.is_empty() {
+		println!("No deployments recorded");
+		return;
+	}
+
+	let profile_width = records
+		.iter()
+		.map(|record| console::measure_text_width(&record.profile))
+		.max()
+		.unwrap_or_default()
+		.max("Profile".len());
+
+	let groups = Group::ALL
+		.iter()
+		.map(|group| {
+			let name = group.name();
+			format!("{:>11}", name[..1].to_uppercase() + &name[1..])
+		})
+		.collect::<String>();
+
+	println!(
+		"{}",
+		console::style(format!(
+			"{:>5}  {:<19}  {:<profile_width$}  {:<7}{groups}  {:>10}",
+			"Id", "Time", "Profile", "Status", "Duration"
+		))
+		.bold()
+	);
+
+	for record in records {
+		let counts = Group::ALL
+			.iter()
+			.map(|group| {
+				let count = record.counts.get(*group);
+				let text = format!("{count:>11}");
+
+				if count == 0 {
+					console::style(text).dim().to_string()
+				} else {
+					group.style().apply_to(text).to_string()
+				}
+			})
+			.collect::<String>();
+
+		println!(
+			"{:>5}  {:<19}  {:<profile_width$}  {}{counts}  {:>10}",
+			record.id,
+			record.time,
+			record.profile,
+			style_status(&format!("{:<7}", record.status)),
+			format_duration(record.duration),
+		);
+	}
+}
+
+/// Prints a recorded deployment together with all of its items.
+pub fn print_record(record: &DeploymentRecord, deployment: &Deployment) {
+	println!(
+		"Deployment {} of profile {} at {} ({})\n",
+		console::style(record.id).bold(),
+		console::style(&record.profile).bold(),
+		record.time,
+		style_status(&record.status),
+	);
+
+	util::print_deployment(deployment);
+}
+
+/// Formats an item status together with its reason.
+fn format_status(status: &ItemStatus) -> String {
+	match status_parts(status) {
+		(name, Some(reason)) => format!("{name} ({reason})"),
+		(name, None) => name.to_string(),
+	}
+}
+
+/// Prints the differences between two recorded deployments.
+///
+/// Besides the items which were added, removed or changed their status (see
+/// [`Deployment::diff`]), all files which were deployed by both deployments
+/// with a different content hash are listed.
+pub fn print_diff(
+	old: &DeploymentRecord,
+	new: &DeploymentRecord,
+	diff: &DeploymentDiff,
+	old_hashes: &HashMap<PathBuf, String>,
+	new_hashes: &HashMap<PathBuf, String>,
+) {
+	println!(
+		"Comparing deployment {} ({}) with {} ({})\n",
+		console::style(old.id).bold(),
+		old.time,
+		console::style(new.id).bold(),
+		new.time,
+	);
+
+	let mut content_changed = new_hashes
+		.iter()
+		.filter(|(path, hash)| old_hashes.get(*path).is_some_and(|old| old != *hash))
+		.map(|(path, _)| path)
+		.collect::<Vec<_>>();
+	content_changed.sort();
+
+	if diff.is_empty() && content_changed.is_empty() {
+		println!("No differences");
+		return;
+	}
+
+	for path in &diff.added {
+		println!("{} {}", console::style("+").green(), path.display());
+	}
+
+	for path in &diff.removed {
+		println!("{} {}", console::style("-").red(), path.display());
+	}
+
+	for change in &diff.status_changed {
+		println!(
+			"{} {}: {} -> {}",
+			console::style("~").yellow(),
+			change.path.display(),
+			format_status(&change.old),
+			format_status(&change.new),
+		);
+	}
+
+	for path in content_changed {
+		println!(
+			"{} {}: content changed",
+			console::style("~").cyan(),
+			path.display()
+		);
+	}
+}
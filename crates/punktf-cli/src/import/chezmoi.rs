@@ -0,0 +1,870 @@
+//! Import of [chezmoi](https://www.chezmoi.io/) source directories.
+//!
+//! The attributes which chezmoi encodes in the names of its source files
+//! (e.g. `dot_`, `private_` or `.tmpl`) are translated into dotfiles of the
+//! profile. Data files (`.chezmoidata.*`) are flattened into profile variables
+//! and simple templates are translated into the punktf template syntax.
+//!
+//! Everything without an equivalent in punktf is reported as needing manual
+//! attention. Template actions which could not be translated are escaped, so
+//! they show up as is in the deployed file.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use punktf_lib::profile::hook::Hook;
+use punktf_lib::profile::link::Symlink;
+use punktf_lib::profile::variables::Variables;
+use punktf_lib::profile::{MergeMode, Profile};
+
+use super::{Destination, ESCAPED_OPEN};
+
+/// Attributes which can prefix the name of a chezmoi source file.
+pub const ATTRIBUTES: &[&str] = &[
+	"after",
+	"before",
+	"create",
+	"empty",
+	"encrypted",
+	"exact",
+	"executable",
+	"external",
+	"modify",
+	"once",
+	"onchange",
+	"private",
+	"readonly",
+	"remove",
+	"run",
+	"symlink",
+];
+
+/// Template variables of chezmoi (`.chezmoi.<name>`) and the punktf variable
+/// with the same value.
+const BUILTINS: &[(&str, &str)] = &[
+	("os", "$PUNKTF_TARGET_OS"),
+	("arch", "$PUNKTF_TARGET_ARCH"),
+	("homeDir", "$HOME"),
+	("username", "$USER"),
+	("sourceDir", "$PUNKTF_CURRENT_SOURCE"),
+];
+
+/// Values of `.chezmoi.os` and `.chezmoi.arch` and their punktf counterparts.
+const BUILTIN_VALUES: &[(&str, &str, &str)] = &[
+	("os", "darwin", "macos"),
+	("arch", "amd64", "x86_64"),
+	("arch", "arm64", "aarch64"),
+	("arch", "386", "x86"),
+];
+
+/// Options for the import of a chezmoi source directory.
+#[derive(Debug)]
+pub struct ChezmoiOptions {
+	/// The chezmoi source directory.
+	///
+	/// Defaults to `~/.local/share/chezmoi`.
+	pub dir: Option<PathBuf>,
+
+	/// Target directory of the profile.
+	///
+	/// Defaults to `~`, the same as for chezmoi.
+	pub target: Option<PathBuf>,
+}
+
+/// A source file or directory with the attributes encoded in its name.
+#[derive(Debug)]
+struct Entry {
+	/// Name of the entry in the target.
+	name: String,
+
+	/// Attribute prefixes of the name (e.g. `private`).
+	attributes: Vec<&'static str>,
+
+	/// Indicates if the entry is a template (`.tmpl` suffix).
+	template: bool,
+}
+
+impl Entry {
+	/// Parses the `name` of a source file (or directory if `is_file` is not
+	/// set).
+	fn parse(name: &str, is_file: bool) -> Self {
+		let mut attributes = Vec::new();
+		let mut rest = name;
+		let mut dot = false;
+
+		loop {
+			if let Some(stripped) = rest.strip_prefix("literal_") {
+				rest = stripped;
+				break;
+			}
+
+			if let Some(stripped) = rest.strip_prefix("dot_") {
+				rest = stripped;
+				dot = true;
+				break;
+			}
+
+			let attribute = ATTRIBUTES.iter().find_map(|attribute| {
+				Some((*attribute, rest.strip_prefix(attribute)?.strip_prefix('_')?))
+			});
+
+			match attribute {
+				Some((attribute, stripped)) => {
+					attributes.push(attribute);
+					rest = stripped;
+				}
+				None => break,
+			}
+		}
+
+		let mut template = false;
+
+		if is_file {
+			if let Some(stripped) = rest.strip_suffix(".literal") {
+				rest = stripped;
+			} else {
+				if attributes.contains(&"encrypted") {
+					rest = rest
+						.strip_suffix(".age")
+						.or_else(|| rest.strip_suffix(".asc"))
+						.unwrap_or(rest);
+				}
+
+				if let Some(stripped) = rest.strip_suffix(".tmpl") {
+					rest = stripped;
+					template = true;
+				}
+			}
+		}
+
+		Self {
+			name: if dot { format!(".{rest}") } else { rest.into() },
+			attributes,
+			template,
+		}
+	}
+
+	/// Checks if the entry has the `attribute`.
+	fn has(&self, attribute: &str) -> bool {
+		self.attributes.contains(&attribute)
+	}
+
+	/// Returns all attributes which change the permissions of the entry.
+	fn permissions(&self) -> Vec<&'static str> {
+		self.attributes
+			.iter()
+			.copied()
+			.filter(|attribute| ["private", "readonly", "executable"].contains(attribute))
+			.collect()
+	}
+}
+
+/// Converts the path of a chezmoi data value (e.g. `git.email`) into a punktf
+/// variable name (e.g. `git_email`).
+fn variable_name(path: &str) -> String {
+	path.chars()
+		.map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+		.collect()
+}
+
+/// Translates a variable reference (e.g. `.email` or `.chezmoi.os`) into the
+/// name of a punktf variable.
+fn variable(expr: &str) -> Option<String> {
+	let path = expr.strip_prefix('.')?;
+
+	if path.is_empty()
+		|| !path
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+	{
+		return None;
+	}
+
+	match path.strip_prefix("chezmoi.") {
+		Some(builtin) => BUILTINS
+			.iter()
+			.find(|(name, _)| *name == builtin)
+			.map(|(_, variable)| String::from(*variable)),
+		None => Some(variable_name(path)),
+	}
+}
+
+/// Translates the condition of an `if` action.
+///
+/// Supported are variables (`.var`), their negation (`not .var`) and
+/// comparisons with a string literal (`eq .var "value"` and `ne .var
+/// "value"`).
+fn condition(cond: &str) -> Option<String> {
+	let words = cond.split_whitespace().collect::<Vec<_>>();
+
+	match words.as_slice() {
+		[var] => Some(format!("{{{{{}}}}}", variable(var)?)),
+		["not", var] => Some(format!("!{{{{{}}}}}", variable(var)?)),
+		[op @ ("eq" | "ne"), lhs, rhs] => {
+			let (var, literal) = if lhs.starts_with('"') {
+				(rhs, lhs)
+			} else {
+				(lhs, rhs)
+			};
+
+			let mut literal = literal.strip_prefix('"')?.strip_suffix('"')?;
+
+			if let Some(builtin) = var.strip_prefix(".chezmoi.") {
+				if let Some((_, _, value)) = BUILTIN_VALUES
+					.iter()
+					.find(|(name, value, _)| *name == builtin && *value == literal)
+				{
+					literal = value;
+				}
+			}
+
+			let op = if *op == "eq" { "==" } else { "!=" };
+
+			Some(format!("{{{{{}}}}} {op} \"{literal}\"", variable(var)?))
+		}
+		_ => None,
+	}
+}
+
+/// Kind of a block which was opened by a template action.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+	/// An `if` block which was translated.
+	If,
+
+	/// Any block which was not translated.
+	Other,
+}
+
+/// Translates a single template action (without the surrounding braces and
+/// trim markers).
+///
+/// Returns `None` if the action has no equivalent in punktf.
+fn translate_action(action: &str, blocks: &mut Vec<Block>) -> Option<String> {
+	if let Some(comment) = action
+		.strip_prefix("/*")
+		.and_then(|action| action.strip_suffix("*/"))
+	{
+		return Some(format!("{{{{!--{comment}--}}}}"));
+	}
+
+	let (keyword, rest) = action
+		.split_once(char::is_whitespace)
+		.map_or((action, ""), |(keyword, rest)| (keyword, rest.trim()));
+
+	match keyword {
+		"if" => match condition(rest) {
+			Some(cond) => {
+				blocks.push(Block::If);
+				Some(format!("{{{{@if {cond}}}}}"))
+			}
+			None => {
+				blocks.push(Block::Other);
+				None
+			}
+		},
+		"else" if blocks.last() == Some(&Block::If) => match rest.strip_prefix("if ") {
+			Some(cond) => Some(format!("{{{{@elif {}}}}}", condition(cond)?)),
+			None if rest.is_empty() => Some(String::from("{{@else}}")),
+			None => None,
+		},
+		"end" => match blocks.pop() {
+			Some(Block::If) => Some(String::from("{{@fi}}")),
+			_ => None,
+		},
+		"range" | "with" | "define" | "block" => {
+			blocks.push(Block::Other);
+			None
+		}
+		_ => Some(format!("{{{{{}}}}}", variable(action)?)),
+	}
+}
+
+/// Translates a chezmoi (go) template into a punktf template.
+///
+/// Returns the translated template together with all actions which could not
+/// be translated.
+fn translate_template(content: &str) -> (String, Vec<String>) {
+	let mut translated = String::with_capacity(content.len());
+	let mut untranslated = Vec::new();
+	let mut blocks = Vec::new();
+	let mut rest = content;
+
+	while let Some(low) = rest.find("{{") {
+		let Some(high) = rest[low..].find("}}").map(|high| low + high + 2) else {
+			break;
+		};
+
+		translated.push_str(&rest[..low]);
+
+		let raw = &rest[low..high];
+		let action = raw[2..raw.len() - 2].trim();
+		let action = action.strip_prefix("- ").unwrap_or(action).trim_start();
+		let action = action.strip_suffix(" -").unwrap_or(action).trim_end();
+
+		match translate_action(action, &mut blocks) {
+			Some(action) => translated.push_str(&action),
+			None => {
+				translated.push_str(ESCAPED_OPEN);
+				translated.push_str(&raw[2..]);
+				untranslated.push(String::from(raw));
+			}
+		}
+
+		rest = &rest[high..];
+	}
+
+	if let Some(low) = rest.find("{{") {
+		translated.push_str(&rest[..low]);
+		translated.push_str(ESCAPED_OPEN);
+		translated.push_str(&rest[low + 2..]);
+		untranslated.push(String::from(&rest[low..]));
+	} else {
+		translated.push_str(rest);
+	}
+
+	(translated, untranslated)
+}
+
+/// Parses a chezmoi data file.
+fn parse_data(path: &Path) -> Result<serde_json::Value> {
+	let content = std::fs::read_to_string(path)
+		.wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+
+	let extension = path
+		.extension()
+		.map(|extension| extension.to_string_lossy().to_lowercase())
+		.unwrap_or_default();
+
+	match extension.as_str() {
+		"json" => serde_json::from_str(&content).map_err(Into::into),
+		"yaml" | "yml" => serde_yaml::from_str(&content).map_err(Into::into),
+		"toml" => toml::from_str(&content).map_err(Into::into),
+		_ => Err(eyre!("Unknown data format")),
+	}
+	.wrap_err_with(|| format!("Failed to parse {}", path.display()))
+}
+
+/// State of an import.
+#[derive(Debug)]
+struct Importer<'a> {
+	/// Source into which the dotfiles are imported.
+	destination: &'a Destination,
+
+	/// Target directory of the profile.
+	target: PathBuf,
+
+	/// The generated profile.
+	profile: Profile,
+}
+
+impl Importer<'_> {
+	/// Loads all data files of the source directory `root` into variables.
+	fn load_data(&mut self, root: &Path) -> Result<HashMap<String, String>> {
+		let mut files = Vec::new();
+
+		for entry in std::fs::read_dir(root)
+			.wrap_err_with(|| format!("Failed to read directory {}", root.display()))?
+		{
+			let path = entry?.path();
+			let is_data = path
+				.file_name()
+				.is_some_and(|name| name.to_string_lossy().starts_with(".chezmoidata"));
+
+			if is_data && path.is_dir() {
+				for entry in std::fs::read_dir(&path)
+					.wrap_err_with(|| format!("Failed to read directory {}", path.display()))?
+				{
+					files.push(entry?.path());
+				}
+			} else if is_data {
+				files.push(path);
+			}
+		}
+
+		files.sort();
+
+		let mut variables = HashMap::new();
+
+		for file in files {
+			let data = parse_data(&file)?;
+			self.flatten(&file, "", data, &mut variables);
+			super::print_imported(&file, Path::new("variables"));
+		}
+
+		Ok(variables)
+	}
+
+	/// Flattens the data `value` at `path` (e.g. `git.email`) into `variables`.
+	fn flatten(
+		&mut self,
+		file: &Path,
+		path: &str,
+		value: serde_json::Value,
+		variables: &mut HashMap<String, String>,
+	) {
+		let value = match value {
+			serde_json::Value::Object(map) => {
+				for (key, value) in map {
+					let path = if path.is_empty() {
+						key
+					} else {
+						format!("{path}.{key}")
+					};
+
+					self.flatten(file, &path, value, variables);
+				}
+
+				return;
+			}
+			serde_json::Value::Array(_) => {
+				self.destination
+					.attention(file, &format!("list `{path}` can not be used as variable"));
+				return;
+			}
+			serde_json::Value::Null => return,
+			serde_json::Value::String(value) => value,
+			value => value.to_string(),
+		};
+
+		variables.insert(variable_name(path), value);
+	}
+
+	/// Imports all entries of the source directory `dir`, which are deployed
+	/// to `target` (relative to the target of the profile).
+	fn import_dir(&mut self, dir: &Path, target: &Path) -> Result<()> {
+		let mut entries = std::fs::read_dir(dir)
+			.wrap_err_with(|| format!("Failed to read directory {}", dir.display()))?
+			.collect::<std::io::Result<Vec<_>>>()?;
+		entries.sort_by_key(|entry| entry.file_name());
+
+		for entry in entries {
+			let path = entry.path();
+
+			let Some(name) = entry.file_name().to_str().map(String::from) else {
+				self.destination
+					.attention(&path, "name includes non UTF-8 characters");
+				continue;
+			};
+
+			if name.starts_with('.') {
+				self.import_special(&path, &name)?;
+				continue;
+			}
+
+			let is_file = !path.is_dir();
+			let source = Entry::parse(&name, is_file);
+
+			if source.has("remove") {
+				self.destination
+					.attention(&path, "removal of targets is not supported");
+			} else if source.has("external") {
+				self.destination
+					.attention(&path, "external directories are not supported");
+			} else if is_file {
+				self.import_file(&path, &source, &target.join(&source.name))?;
+			} else {
+				if source.has("exact") {
+					self.destination
+						.attention(&path, "exact directories are not supported");
+				}
+
+				if !source.permissions().is_empty() {
+					self.destination
+						.attention(&path, "permissions are not preserved");
+				}
+
+				self.import_dir(&path, &target.join(&source.name))?;
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Imports the special chezmoi file `path`.
+	///
+	/// All other files starting with a `.` are ignored, the same as chezmoi
+	/// does.
+	fn import_special(&mut self, path: &Path, name: &str) -> Result<()> {
+		match name {
+			".chezmoiscripts" if path.is_dir() => {
+				let mut entries = std::fs::read_dir(path)
+					.wrap_err_with(|| format!("Failed to read directory {}", path.display()))?
+					.collect::<std::io::Result<Vec<_>>>()?;
+				entries.sort_by_key(|entry| entry.file_name());
+
+				for entry in entries {
+					let script = Entry::parse(&entry.file_name().to_string_lossy(), true);
+
+					if script.has("run") {
+						self.import_script(&entry.path(), &script)?;
+					} else {
+						super::print_skipped(&entry.path(), "not a script");
+					}
+				}
+			}
+			".chezmoiignore" => self.destination.attention(
+				path,
+				"ignore patterns are not supported, remove the ignored dotfiles from the profile",
+			),
+			".chezmoiremove" => self
+				.destination
+				.attention(path, "removal of targets is not supported"),
+			".chezmoitemplates" => self
+				.destination
+				.attention(path, "shared templates are not supported"),
+			_ if name.starts_with(".chezmoiexternal") => {
+				self.destination
+					.attention(path, "external files and archives are not supported");
+			}
+			_ if name.starts_with(".chezmoi.") => self.destination.attention(
+				path,
+				"configuration templates are not supported, add their data as variables",
+			),
+			_ => {}
+		}
+
+		Ok(())
+	}
+
+	/// Imports the source file `path`, which is deployed to `target` (relative
+	/// to the target of the profile).
+	fn import_file(&mut self, path: &Path, source: &Entry, target: &Path) -> Result<()> {
+		if source.has("modify") {
+			self.destination
+				.attention(path, "modify scripts are not supported");
+			return Ok(());
+		}
+
+		if source.has("encrypted") {
+			self.destination
+				.attention(path, "encrypted files are not supported");
+			return Ok(());
+		}
+
+		if source.has("run") {
+			return self.import_script(path, source);
+		}
+
+		if source.has("symlink") {
+			return self.import_symlink(path, source, target);
+		}
+
+		let mut content =
+			std::fs::read(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+
+		if source.template {
+			let (translated, untranslated) = translate_template(&String::from_utf8_lossy(&content));
+
+			for action in untranslated {
+				self.destination.attention(
+					path,
+					&format!("template action `{action}` was not translated"),
+				);
+			}
+
+			content = translated.into_bytes();
+		}
+
+		let permissions = source.permissions();
+		if !permissions.is_empty() {
+			self.destination.attention(
+				path,
+				&format!("permissions ({}) are not preserved", permissions.join(", ")),
+			);
+		}
+
+		self.destination.write_file(target, &content)?;
+		super::print_imported(path, &self.destination.dotfile(target));
+
+		let mut dotfile = super::dotfile(target.to_path_buf(), source.template);
+		if source.has("create") {
+			dotfile.merge = Some(MergeMode::Keep);
+		}
+
+		self.profile.dotfiles.push(dotfile);
+
+		Ok(())
+	}
+
+	/// Imports the script `path` as hook.
+	fn import_script(&mut self, path: &Path, source: &Entry) -> Result<()> {
+		let content =
+			std::fs::read(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+
+		if source.template {
+			self.destination
+				.attention(path, "templated scripts are copied without being rendered");
+		}
+
+		if source.has("once") || source.has("onchange") {
+			self.destination
+				.attention(path, "script is run on every deployment");
+		}
+
+		let relative = self.destination.write_script(&source.name, &content)?;
+		super::print_imported(path, &relative);
+
+		let hook = Hook::new(format!(
+			"sh \"$PUNKTF_CURRENT_SOURCE/{}\"",
+			relative.to_string_lossy().replace('\\', "/")
+		));
+
+		if source.has("before") {
+			self.profile.pre_hooks.push(hook);
+		} else {
+			self.profile.post_hooks.push(hook);
+		}
+
+		Ok(())
+	}
+
+	/// Imports the symlink `path`, which is deployed to `target` (relative to
+	/// the target of the profile).
+	fn import_symlink(&mut self, path: &Path, source: &Entry, target: &Path) -> Result<()> {
+		if source.template {
+			self.destination
+				.attention(path, "templated symlinks are not supported");
+			return Ok(());
+		}
+
+		let content = std::fs::read_to_string(path)
+			.wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+		let link = PathBuf::from(content.trim());
+
+		let source_path = if link.is_absolute() || link.starts_with("~") {
+			link
+		} else {
+			self.target
+				.join(target.parent().unwrap_or(Path::new("")))
+				.join(link)
+		};
+
+		let target_path = self.target.join(target);
+		super::print_imported(path, &target_path);
+
+		self.profile.symlinks.push(Symlink {
+			source_path,
+			target_path,
+			replace: true,
+		});
+
+		Ok(())
+	}
+}
+
+/// Imports a chezmoi source directory into `destination` and returns the
+/// generated profile.
+///
+/// # Errors
+///
+/// An error is returned if the source directory could not be read or if any
+/// file could not be copied.
+pub fn import(destination: &Destination, options: ChezmoiOptions) -> Result<Profile> {
+	let dir = match options.dir {
+		Some(dir) => dir,
+		None => dirs::home_dir()
+			.ok_or_else(|| eyre!("Failed to get the home directory"))?
+			.join(".local")
+			.join("share")
+			.join("chezmoi"),
+	};
+
+	let mut root = dir
+		.canonicalize()
+		.wrap_err_with(|| format!("Failed to resolve chezmoi directory {}", dir.display()))?;
+
+	// The source state can be located in a sub directory
+	if let Ok(subdir) = std::fs::read_to_string(root.join(".chezmoiroot")) {
+		root = root.join(subdir.trim());
+	}
+
+	let target = options.target.unwrap_or_else(|| PathBuf::from("~"));
+
+	let mut importer = Importer {
+		destination,
+		target: target.clone(),
+		profile: Profile {
+			description: Some(format!(
+				"Imported from chezmoi directory {}",
+				root.display()
+			)),
+			target: Some(target),
+			..Default::default()
+		},
+	};
+
+	let variables = importer.load_data(&root)?;
+	if !variables.is_empty() {
+		importer.profile.variables = Some(Variables::from_items(variables));
+	}
+
+	importer.import_dir(&root, Path::new(""))?;
+
+	Ok(importer.profile)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::import::tests::{files, fixture};
+
+	#[test]
+	fn parse_entry() {
+		let parse = |name: &str, is_file: bool| {
+			let entry = Entry::parse(name, is_file);
+			(entry.name, entry.attributes, entry.template)
+		};
+
+		assert_eq!(parse("dot_bashrc", true), (".bashrc".into(), vec![], false));
+		assert_eq!(
+			parse("private_dot_ssh", false),
+			(".ssh".into(), vec!["private"], false)
+		);
+		assert_eq!(
+			parse("executable_hello", true),
+			("hello".into(), vec!["executable"], false)
+		);
+		assert_eq!(
+			parse("dot_gitconfig.tmpl", true),
+			(".gitconfig".into(), vec![], true)
+		);
+		assert_eq!(
+			parse("run_once_before_install.sh.tmpl", true),
+			("install.sh".into(), vec!["run", "once", "before"], true)
+		);
+		assert_eq!(
+			parse("create_empty_dot_hushlogin", true),
+			(".hushlogin".into(), vec!["create", "empty"], false)
+		);
+		assert_eq!(
+			parse("symlink_dot_vimrc", true),
+			(".vimrc".into(), vec!["symlink"], false)
+		);
+		assert_eq!(
+			parse("encrypted_private_dot_netrc.age", true),
+			(".netrc".into(), vec!["encrypted", "private"], false)
+		);
+		assert_eq!(
+			parse("literal_run_me.tmpl.literal", true),
+			("run_me.tmpl".into(), vec![], false)
+		);
+		// Suffixes are only parsed for files
+		assert_eq!(
+			parse("dot_vim.tmpl", false),
+			(".vim.tmpl".into(), vec![], false)
+		);
+	}
+
+	#[test]
+	fn translate_templates() {
+		let (translated, untranslated) = translate_template(
+			"{{/* comment */}}{{ .email }}\n{{- if eq .chezmoi.os \"darwin\" }}mac{{ else if .work \
+			 }}work{{ else }}{{ .chezmoi.homeDir }}{{ end }}\n{{ range .list }}{{ . }}{{ end }}",
+		);
+
+		assert_eq!(
+			translated,
+			format!(
+				"{{{{!-- comment --}}}}{{{{email}}}}\n{{{{@if {{{{$PUNKTF_TARGET_OS}}}} == \
+				 \"macos\"}}}}mac{{{{@elif {{{{work}}}}}}}}work{{{{@else}}}}{{{{$HOME}}}}{{{{@fi}}}}\n\
+				 {ESCAPED_OPEN} range .list }}}}{ESCAPED_OPEN} . }}}}{ESCAPED_OPEN} end }}}}"
+			)
+		);
+		assert_eq!(untranslated, ["{{ range .list }}", "{{ . }}", "{{ end }}"]);
+	}
+
+	#[test]
+	fn import_fixture() -> Result<()> {
+		let dir = tempfile::tempdir()?;
+		let destination = Destination::create(dir.path().to_path_buf(), false)?;
+		let home = dir.path().join("home");
+
+		let profile = import(
+			&destination,
+			ChezmoiOptions {
+				dir: Some(fixture("chezmoi")),
+				target: Some(home.clone()),
+			},
+		)?;
+
+		let dotfile = |path: &str, template: bool| crate::import::dotfile(path.into(), template);
+		let mut profile_dotfile = dotfile(".profile", false);
+		profile_dotfile.merge = Some(MergeMode::Keep);
+
+		assert_eq!(profile.target, Some(home.clone()));
+		assert_eq!(
+			profile.variables,
+			Some(Variables::from_items([
+				("email", "me@example.com"),
+				("git_name", "Me")
+			]))
+		);
+		assert_eq!(
+			profile.dotfiles,
+			vec![
+				profile_dotfile,
+				dotfile(".config/app/settings.tmpl", false),
+				dotfile(".gitconfig", true),
+				dotfile(".local/bin/hello", false),
+				dotfile(".hushlogin", false),
+				dotfile("run_me", false),
+				dotfile(".ssh/config", false),
+			]
+		);
+		assert_eq!(
+			profile.pre_hooks,
+			[Hook::new(
+				"sh \"$PUNKTF_CURRENT_SOURCE/scripts/install.sh\""
+			)]
+		);
+		assert_eq!(
+			profile.post_hooks,
+			[Hook::new(
+				"sh \"$PUNKTF_CURRENT_SOURCE/scripts/cleanup.sh\""
+			)]
+		);
+		assert_eq!(
+			profile.symlinks,
+			[Symlink {
+				source_path: home.join(".config/nvim/init.vim"),
+				target_path: home.join(".vimrc"),
+				replace: true,
+			}]
+		);
+
+		// The ignore file, the private directory, the executable, the script
+		// which is run once, the encrypted and the modified file
+		assert_eq!(destination.attention_count(), 6);
+
+		let dotfiles = dir.path().join("dotfiles");
+		assert_eq!(
+			files(&dotfiles),
+			[
+				".config/app/settings.tmpl",
+				".gitconfig",
+				".hushlogin",
+				".local/bin/hello",
+				".profile",
+				".ssh/config",
+				"run_me",
+			]
+		);
+		assert_eq!(
+			std::fs::read_to_string(dotfiles.join(".gitconfig"))?,
+			"[user]\n\tname = {{git_name}}\n\temail = {{email}}\n"
+		);
+		assert_eq!(
+			std::fs::read_to_string(dotfiles.join(".config/app/settings.tmpl"))?,
+			"key = \"{{ value }}\"\n"
+		);
+		assert_eq!(
+			files(&dir.path().join("scripts")),
+			["cleanup.sh", "install.sh"]
+		);
+
+		Ok(())
+	}
+}
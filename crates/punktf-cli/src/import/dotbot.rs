@@ -0,0 +1,425 @@
+//! Import of [dotbot](https://github.com/anishathalye/dotbot) configurations.
+//!
+//! The sources of all `link` directives are copied to the `dotfiles` directory
+//! and added as dotfiles, with their link path as target. `create` directives
+//! become pre-hooks which create the directories. `shell` directives become
+//! pre-hooks if they appear before the first `link` directive and post-hooks
+//! otherwise.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use punktf_lib::profile::hook::Hook;
+use punktf_lib::profile::Profile;
+use serde_yaml::{Mapping, Value};
+
+use super::Destination;
+
+/// Options for the import of a dotbot configuration.
+#[derive(Debug)]
+pub struct DotbotOptions {
+	/// The dotbot configuration file (e.g. `install.conf.yaml`).
+	pub config: PathBuf,
+
+	/// Base directory against which the sources of the links are resolved.
+	///
+	/// Defaults to the directory of the configuration file.
+	pub base_dir: Option<PathBuf>,
+
+	/// Target directory of the profile.
+	///
+	/// Defaults to `~`. Links below `~` are deployed relative to it.
+	pub target: Option<PathBuf>,
+}
+
+/// Returns the path of `link` relative to the home directory, if it is below
+/// it.
+fn home_relative(link: &str) -> Option<&str> {
+	["~/", "$HOME/", "${HOME}/"]
+		.iter()
+		.find_map(|prefix| link.strip_prefix(prefix))
+}
+
+/// Replaces a leading `~` of `path` with `$HOME`, so it is expanded by shells
+/// within quotes.
+fn shell_path(path: &str) -> String {
+	match path.strip_prefix('~') {
+		Some(rest) => format!("$HOME{rest}"),
+		None => String::from(path),
+	}
+}
+
+/// State of an import.
+#[derive(Debug)]
+struct Importer<'a> {
+	/// Source into which the dotfiles are imported.
+	destination: &'a Destination,
+
+	/// The dotbot configuration file.
+	config: PathBuf,
+
+	/// Base directory of the link sources.
+	base: PathBuf,
+
+	/// Default options for links (from the `defaults` directive).
+	link_defaults: Mapping,
+
+	/// Indicates if a `link` directive was already imported.
+	linked: bool,
+
+	/// Sources which were already copied (a source can be linked multiple
+	/// times).
+	copied: HashSet<PathBuf>,
+
+	/// The generated profile.
+	profile: Profile,
+}
+
+impl Importer<'_> {
+	/// Imports all `directives` of the configuration.
+	fn import(&mut self, directives: Vec<Value>) -> Result<()> {
+		for directive in directives {
+			let Value::Mapping(directive) = directive else {
+				self.destination
+					.attention(&self.config, "directives must be maps");
+				continue;
+			};
+
+			for (name, value) in directive {
+				match name.as_str().unwrap_or_default() {
+					"defaults" => {
+						self.link_defaults = value
+							.get("link")
+							.and_then(Value::as_mapping)
+							.cloned()
+							.unwrap_or_default();
+					}
+					"link" => self.import_links(value)?,
+					"create" => self.import_create(value),
+					"shell" => self.import_shell(value),
+					"clean" => self
+						.destination
+						.attention(&self.config, "`clean` directives are not supported"),
+					name => self.destination.attention(
+						&self.config,
+						&format!("unknown directive `{name}` (e.g. of a plugin) is not supported"),
+					),
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Imports the links of a `link` directive.
+	fn import_links(&mut self, links: Value) -> Result<()> {
+		let Value::Mapping(links) = links else {
+			self.destination
+				.attention(&self.config, "`link` directive must be a map");
+			return Ok(());
+		};
+
+		for (link, value) in links {
+			let Some(link) = link.as_str() else {
+				continue;
+			};
+
+			let mut options = self.link_defaults.clone();
+			match value {
+				Value::Null => {}
+				Value::String(path) => {
+					options.insert("path".into(), path.into());
+				}
+				Value::Mapping(mapping) => options.extend(mapping),
+				_ => {
+					self.destination
+						.attention(&self.config, &format!("invalid options for link `{link}`"));
+					continue;
+				}
+			}
+
+			self.import_link(link, &options)?;
+		}
+
+		self.linked = true;
+
+		Ok(())
+	}
+
+	/// Imports a single link with its `options`.
+	fn import_link(&mut self, link: &str, options: &Mapping) -> Result<()> {
+		let option = |name: &str| options.get(name);
+
+		// Dotbot uses the file name of the link without a leading `.` as
+		// default source
+		let source = match option("path").and_then(Value::as_str) {
+			Some(path) => PathBuf::from(path),
+			None => PathBuf::from(
+				Path::new(link)
+					.file_name()
+					.map(|name| name.to_string_lossy().trim_start_matches('.').to_owned())
+					.unwrap_or_default(),
+			),
+		};
+
+		let from = self.base.join(&source);
+
+		if option("glob").and_then(Value::as_bool) == Some(true) {
+			self.destination
+				.attention(&from, &format!("glob link `{link}` is not supported"));
+			return Ok(());
+		}
+
+		if source.as_os_str().is_empty() {
+			super::print_skipped(&from, "link to the base directory itself");
+			return Ok(());
+		}
+
+		if source.is_absolute() {
+			self.destination.attention(
+				&from,
+				"sources outside of the base directory are not supported",
+			);
+			return Ok(());
+		}
+
+		if !from.exists() {
+			if option("ignore-missing").and_then(Value::as_bool) == Some(true) {
+				super::print_skipped(&from, "source does not exist");
+			} else {
+				self.destination
+					.attention(&from, "source of the link does not exist");
+			}
+
+			return Ok(());
+		}
+
+		if let Some(condition) = option("if").and_then(Value::as_str) {
+			self.destination.attention(
+				&from,
+				&format!("link is always deployed, ignoring the condition `{condition}`"),
+			);
+		}
+
+		let mut dotfile = super::dotfile(source.clone(), false);
+
+		match home_relative(link) {
+			// Directories are deployed into the target if they are not renamed
+			Some(relative) if from.is_dir() || Path::new(relative) != source => {
+				dotfile.rename = Some(relative.into());
+			}
+			Some(_) => {}
+			None if Path::new(link).is_absolute() => {
+				let link = Path::new(link);
+				dotfile.overwrite_target = link.parent().map(Path::to_path_buf);
+				dotfile.rename = link.file_name().map(PathBuf::from);
+			}
+			None => {
+				self.destination
+					.attention(&from, &format!("relative link `{link}` is not supported"));
+				return Ok(());
+			}
+		}
+
+		if self.copied.insert(source.clone()) {
+			if from.is_dir() {
+				self.destination.copy_dir(&from, &source)?;
+			} else {
+				self.destination.copy_file(&from, &source)?;
+			}
+
+			super::print_imported(&from, &self.destination.dotfile(&source));
+		}
+
+		self.profile.dotfiles.push(dotfile);
+
+		Ok(())
+	}
+
+	/// Imports the directories of a `create` directive as pre-hooks.
+	fn import_create(&mut self, create: Value) {
+		let directories = match create {
+			Value::Sequence(paths) => paths.into_iter().map(|path| (path, None)).collect(),
+			Value::Mapping(paths) => paths
+				.into_iter()
+				.map(|(path, options)| (path, options.get("mode").cloned()))
+				.collect(),
+			_ => Vec::new(),
+		};
+
+		for (path, mode) in directories {
+			let Some(path) = path.as_str() else {
+				continue;
+			};
+
+			if mode.is_some() {
+				self.destination.attention(
+					&self.config,
+					&format!("mode of the created directory `{path}` is not applied"),
+				);
+			}
+
+			self.profile
+				.pre_hooks
+				.push(Hook::new(format!("mkdir -p \"{}\"", shell_path(path))));
+		}
+	}
+
+	/// Imports the commands of a `shell` directive as hooks.
+	fn import_shell(&mut self, shell: Value) {
+		let Value::Sequence(commands) = shell else {
+			self.destination
+				.attention(&self.config, "`shell` directive must be a list");
+			return;
+		};
+
+		for command in commands {
+			let command = match &command {
+				Value::String(command) => Some(command.as_str()),
+				Value::Sequence(command) => command.first().and_then(Value::as_str),
+				Value::Mapping(_) => command.get("command").and_then(Value::as_str),
+				_ => None,
+			};
+
+			let Some(command) = command else {
+				self.destination
+					.attention(&self.config, "invalid command in `shell` directive");
+				continue;
+			};
+
+			self.destination.attention(
+				&self.config,
+				&format!(
+					"command `{command}` runs inside of the profiles directory instead of the base \
+					 directory"
+				),
+			);
+
+			let hook = Hook::new(command);
+
+			if self.linked {
+				self.profile.post_hooks.push(hook);
+			} else {
+				self.profile.pre_hooks.push(hook);
+			}
+		}
+	}
+}
+
+/// Imports a dotbot configuration into `destination` and returns the
+/// generated profile.
+///
+/// # Errors
+///
+/// An error is returned if the configuration could not be parsed or if any
+/// file could not be copied.
+pub fn import(destination: &Destination, options: DotbotOptions) -> Result<Profile> {
+	let config = options.config.canonicalize().wrap_err_with(|| {
+		format!(
+			"Failed to resolve dotbot configuration {}",
+			options.config.display()
+		)
+	})?;
+
+	let base = match options.base_dir {
+		Some(base) => base,
+		None => config
+			.parent()
+			.ok_or_else(|| eyre!("Configuration {} has no parent", config.display()))?
+			.to_path_buf(),
+	};
+
+	let content = std::fs::read_to_string(&config)
+		.wrap_err_with(|| format!("Failed to read {}", config.display()))?;
+	// Json configurations are valid yaml
+	let directives: Vec<Value> = serde_yaml::from_str(&content)
+		.wrap_err_with(|| format!("Failed to parse {}", config.display()))?;
+
+	let mut importer = Importer {
+		destination,
+		profile: Profile {
+			description: Some(format!(
+				"Imported from dotbot configuration {}",
+				config.display()
+			)),
+			target: Some(options.target.unwrap_or_else(|| PathBuf::from("~"))),
+			..Default::default()
+		},
+		config,
+		base,
+		link_defaults: Mapping::new(),
+		linked: false,
+		copied: HashSet::new(),
+	};
+
+	importer.import(directives)?;
+
+	Ok(importer.profile)
+}
+
+#[cfg(test)]
+mod tests {
+	use punktf_lib::profile::dotfile::Dotfile;
+
+	use super::*;
+	use crate::import::tests::{files, fixture};
+
+	/// Creates a dotfile for `path` which is deployed as `rename`.
+	fn renamed(path: &str, rename: &str) -> Dotfile {
+		let mut dotfile = crate::import::dotfile(path.into(), false);
+		dotfile.rename = Some(rename.into());
+		dotfile
+	}
+
+	#[test]
+	fn import_fixture() -> Result<()> {
+		let dir = tempfile::tempdir()?;
+		let destination = Destination::create(dir.path().to_path_buf(), false)?;
+
+		let profile = import(
+			&destination,
+			DotbotOptions {
+				config: fixture("dotbot").join("install.conf.yaml"),
+				base_dir: None,
+				target: None,
+			},
+		)?;
+
+		let mut hosts = renamed("hosts", "hosts.local");
+		hosts.overwrite_target = Some("/etc".into());
+
+		assert_eq!(profile.target, Some("~".into()));
+		assert_eq!(
+			profile.dotfiles,
+			[
+				renamed("bashrc", ".bashrc"),
+				renamed("vim/vimrc", ".vimrc"),
+				renamed("nvim", ".config/nvim"),
+				hosts,
+				renamed("zshrc", ".zshrc"),
+			]
+		);
+
+		// Shell commands are pre-hooks until the first link directive
+		assert_eq!(
+			profile.pre_hooks,
+			[
+				Hook::new("mkdir -p \"$HOME/.cache/vim\""),
+				Hook::new("git submodule update --init"),
+			]
+		);
+		assert_eq!(profile.post_hooks, [Hook::new("echo done")]);
+
+		// The clean directive, both shell commands and the link condition
+		assert_eq!(destination.attention_count(), 4);
+
+		assert_eq!(
+			files(&dir.path().join("dotfiles")),
+			["bashrc", "hosts", "nvim/init.vim", "vim/vimrc", "zshrc"]
+		);
+
+		Ok(())
+	}
+}
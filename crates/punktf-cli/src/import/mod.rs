@@ -0,0 +1,242 @@
+//! Imports of dotfiles managed by other tools (`punktf import`).
+//!
+//! Each importer converts the layout of another tool into the `dotfiles`
+//! directory of a punktf source and generates an equivalent profile in
+//! `profiles/<name>.yaml`.
+
+pub mod chezmoi;
+pub mod dotbot;
+pub mod stow;
+pub mod yadm;
+
+use std::cell::Cell;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use console::style;
+use punktf_lib::profile::dotfile::Dotfile;
+use punktf_lib::profile::Profile;
+
+/// Directory in the source to which scripts are imported, which are run as
+/// hooks.
+pub const SCRIPTS_DIR: &str = "scripts";
+
+/// Escaped opening of a template block (`{{`), used to copy template actions
+/// of other tools as is.
+pub const ESCAPED_OPEN: &str = "{{{{{}}}";
+
+/// A punktf source directory into which dotfiles are imported.
+#[derive(Debug)]
+pub struct Destination {
+	/// Root of the source directory.
+	root: PathBuf,
+
+	/// Overwrite existing dotfiles and profiles.
+	force: bool,
+
+	/// Amount of items which need manual attention.
+	attention: Cell<usize>,
+}
+
+impl Destination {
+	/// Creates the `profiles` and `dotfiles` directories below `root` if they do
+	/// not exist yet.
+	pub fn create(root: PathBuf, force: bool) -> Result<Self> {
+		for dir in ["profiles", "dotfiles"] {
+			let dir = root.join(dir);
+			std::fs::create_dir_all(&dir)
+				.wrap_err_with(|| format!("Failed to create directory {}", dir.display()))?;
+		}
+
+		Ok(Self {
+			root,
+			force,
+			attention: Cell::new(0),
+		})
+	}
+
+	/// Returns the path of `relative` inside of the `dotfiles` directory.
+	pub fn dotfile(&self, relative: &Path) -> PathBuf {
+		self.root.join("dotfiles").join(relative)
+	}
+
+	/// Fails if `path` already exists and existing items should not be
+	/// overwritten.
+	fn check_overwrite(&self, path: &Path) -> Result<()> {
+		if !self.force && path.symlink_metadata().is_ok() {
+			return Err(eyre!(
+				"{} already exists. Use `--force` to overwrite it",
+				path.display()
+			));
+		}
+
+		Ok(())
+	}
+
+	/// Copies the file `from` to `relative` inside of the `dotfiles` directory.
+	pub fn copy_file(&self, from: &Path, relative: &Path) -> Result<()> {
+		self.write_file(
+			relative,
+			&std::fs::read(from).wrap_err_with(|| format!("Failed to read {}", from.display()))?,
+		)
+	}
+
+	/// Copies the directory `from` recursively to `relative` inside of the
+	/// `dotfiles` directory and returns the amount of copied files.
+	pub fn copy_dir(&self, from: &Path, relative: &Path) -> Result<usize> {
+		let mut copied = 0;
+
+		for entry in std::fs::read_dir(from)
+			.wrap_err_with(|| format!("Failed to read directory {}", from.display()))?
+		{
+			let path = entry?.path();
+			let relative = relative.join(path.file_name().unwrap_or_default());
+
+			if path.is_dir() {
+				copied += self.copy_dir(&path, &relative)?;
+			} else {
+				self.copy_file(&path, &relative)?;
+				copied += 1;
+			}
+		}
+
+		Ok(copied)
+	}
+
+	/// Writes `content` to `relative` inside of the `dotfiles` directory.
+	pub fn write_file(&self, relative: &Path, content: &[u8]) -> Result<()> {
+		self.write(&self.dotfile(relative), content)
+	}
+
+	/// Writes `content` to the script `name` inside of the [`SCRIPTS_DIR`]
+	/// directory and returns its path relative to the source directory.
+	pub fn write_script(&self, name: &str, content: &[u8]) -> Result<PathBuf> {
+		let relative = Path::new(SCRIPTS_DIR).join(name);
+		self.write(&self.root.join(&relative), content)?;
+
+		Ok(relative)
+	}
+
+	/// Writes `content` to the file `to`.
+	fn write(&self, to: &Path, content: &[u8]) -> Result<()> {
+		self.check_overwrite(to)?;
+
+		if let Some(parent) = to.parent() {
+			std::fs::create_dir_all(parent)
+				.wrap_err_with(|| format!("Failed to create directory {}", parent.display()))?;
+		}
+
+		std::fs::write(to, content).wrap_err_with(|| format!("Failed to write {}", to.display()))
+	}
+
+	/// Reports an item which needs manual attention after the import.
+	pub fn attention(&self, path: &Path, reason: &str) {
+		println!(
+			"{} {} ({reason})",
+			style("attention").magenta(),
+			path.display()
+		);
+
+		self.attention.set(self.attention.get() + 1);
+	}
+
+	/// Returns the amount of items which need manual attention.
+	pub const fn attention_count(&self) -> usize {
+		self.attention.get()
+	}
+
+	/// Writes the `profile` to `profiles/<name>.yaml`.
+	pub fn write_profile(&self, name: &str, profile: &Profile) -> Result<PathBuf> {
+		let path = self.root.join("profiles").join(format!("{name}.yaml"));
+		self.check_overwrite(&path)?;
+
+		let content = serde_yaml::to_string(profile).wrap_err("Failed to serialize profile")?;
+
+		std::fs::write(&path, content)
+			.wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+
+		Ok(path)
+	}
+}
+
+/// Creates a dotfile for `path` with the default settings.
+pub fn dotfile(path: PathBuf, template: bool) -> Dotfile {
+	Dotfile {
+		path,
+		rename: None,
+		overwrite_target: None,
+		priority: None,
+		variables: None,
+		transformers: Vec::new(),
+		transform: Vec::new(),
+		merge: None,
+		template: Some(template),
+		encrypted: None,
+		max_depth: None,
+		max_file_size: None,
+		on_limit: None,
+		preserve_mtime: None,
+		dir_mode: None,
+		create_parents: None,
+		force: None,
+		when: None,
+		instances: Box::default(),
+		for_each: None,
+	}
+}
+
+/// Prints a line for an imported item.
+pub fn print_imported(from: &Path, to: &Path) {
+	println!(
+		"{} {} -> {}",
+		style("imported").green(),
+		from.display(),
+		to.display()
+	);
+}
+
+/// Prints a line for an item which was not imported.
+pub fn print_skipped(path: &Path, reason: &str) {
+	println!(
+		"{} {} ({reason})",
+		style("skipped").yellow(),
+		path.display()
+	);
+}
+
+#[cfg(test)]
+pub mod tests {
+	use std::path::{Path, PathBuf};
+
+	/// Returns the path of the fixture `name` (`tests/fixtures/<name>`).
+	pub fn fixture(name: &str) -> PathBuf {
+		Path::new(env!("CARGO_MANIFEST_DIR"))
+			.join("tests")
+			.join("fixtures")
+			.join(name)
+	}
+
+	/// Returns the paths of all files below `dir` (relative to it and with `/`
+	/// as separator) sorted alphabetically.
+	pub fn files(dir: &Path) -> Vec<String> {
+		fn collect(dir: &Path, relative: &Path, files: &mut Vec<String>) {
+			for entry in std::fs::read_dir(dir.join(relative)).expect("Directory to be readable") {
+				let entry = entry.expect("Directory entry to be readable");
+				let relative = relative.join(entry.file_name());
+
+				if entry.path().is_dir() {
+					collect(dir, &relative, files);
+				} else {
+					files.push(relative.to_string_lossy().replace('\\', "/"));
+				}
+			}
+		}
+
+		let mut files = Vec::new();
+		collect(dir, Path::new(""), &mut files);
+		files.sort();
+
+		files
+	}
+}
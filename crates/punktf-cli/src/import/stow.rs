@@ -0,0 +1,338 @@
+//! Import of [GNU Stow](https://www.gnu.org/software/stow/) directories.
+//!
+//! Each package (top-level directory) of the stow directory is copied to
+//! `dotfiles/<package>` and added as directory dotfile to the profile. As
+//! directories are deployed into the target of the profile, this mirrors how
+//! stow links the contents of a package into its target directory.
+
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use punktf_lib::profile::Profile;
+use regex::Regex;
+
+use super::Destination;
+
+/// Name of the per package ignore file of stow.
+const LOCAL_IGNORE_FILE: &str = ".stow-local-ignore";
+
+/// Ignore patterns which stow uses if a package has no
+/// [`LOCAL_IGNORE_FILE`].
+const DEFAULT_IGNORE: &[&str] = &[
+	"RCS",
+	".+,v",
+	"CVS",
+	r"\.\#.+",
+	r"\.cvsignore",
+	r"\.svn",
+	"_darcs",
+	r"\.hg",
+	r"\.git",
+	r"\.gitignore",
+	r"\.gitmodules",
+	".+~",
+	r"\#.*\#",
+	"^/README.*",
+	"^/LICENSE.*",
+	"^/COPYING",
+];
+
+/// Options for the import of a stow directory.
+#[derive(Debug)]
+pub struct StowOptions {
+	/// The stow directory which contains the packages.
+	pub dir: PathBuf,
+
+	/// Names of the packages to import. If empty, all packages are imported.
+	pub packages: Vec<String>,
+
+	/// Target directory of the packages.
+	///
+	/// Defaults to the parent of the stow directory, the same as stow does.
+	pub target: Option<PathBuf>,
+
+	/// Replaces the prefix `dot-` of file names with `.` (stow's `--dotfiles`).
+	pub dotfiles: bool,
+}
+
+/// Ignore patterns of a single package.
+#[derive(Debug)]
+struct Ignore {
+	/// Patterns which are matched against the file name.
+	names: Vec<Regex>,
+
+	/// Patterns which are matched against the path relative to the package
+	/// (with a leading `/`).
+	paths: Vec<Regex>,
+}
+
+impl Ignore {
+	/// Loads the ignore patterns of the package at `package`.
+	fn load(package: &Path) -> Result<Self> {
+		let local = package.join(LOCAL_IGNORE_FILE);
+
+		let content = match std::fs::read_to_string(&local) {
+			Ok(content) => content,
+			Err(_) => DEFAULT_IGNORE.join("\n"),
+		};
+
+		let mut ignore = Self {
+			names: Vec::new(),
+			paths: Vec::new(),
+		};
+
+		for line in content.lines().map(str::trim) {
+			if line.is_empty() || line.starts_with('#') {
+				continue;
+			}
+
+			let regex = Regex::new(&format!("^(?:{line})$")).wrap_err_with(|| {
+				format!("Invalid ignore pattern `{line}` in {}", local.display())
+			})?;
+
+			if line.contains('/') {
+				ignore.paths.push(regex);
+			} else {
+				ignore.names.push(regex);
+			}
+		}
+
+		Ok(ignore)
+	}
+
+	/// Checks if the item at `relative` inside of the package is ignored.
+	fn is_ignored(&self, relative: &Path) -> bool {
+		let name = relative
+			.file_name()
+			.map(|name| name.to_string_lossy())
+			.unwrap_or_default();
+
+		if name == LOCAL_IGNORE_FILE || self.names.iter().any(|regex| regex.is_match(&name)) {
+			return true;
+		}
+
+		let path = format!("/{}", relative.to_string_lossy().replace('\\', "/"));
+		self.paths.iter().any(|regex| regex.is_match(&path))
+	}
+}
+
+/// Returns the name under which the item `name` is deployed.
+fn deployed_name(name: OsString, dotfiles: bool) -> OsString {
+	match name.to_str().and_then(|name| name.strip_prefix("dot-")) {
+		Some(stripped) if dotfiles => format!(".{stripped}").into(),
+		_ => name,
+	}
+}
+
+/// Copies all files of the package directory `package` below `relative` to
+/// `dotfiles/<deployed>`.
+///
+/// Returns the amount of imported files.
+fn import_dir(
+	destination: &Destination,
+	ignore: &Ignore,
+	package: &Path,
+	relative: &Path,
+	deployed: &Path,
+	dotfiles: bool,
+) -> Result<usize> {
+	let dir = package.join(relative);
+
+	let mut entries = std::fs::read_dir(&dir)
+		.wrap_err_with(|| format!("Failed to read directory {}", dir.display()))?
+		.collect::<std::io::Result<Vec<_>>>()?;
+	entries.sort_by_key(|entry| entry.file_name());
+
+	let mut imported = 0;
+
+	for entry in entries {
+		let relative = relative.join(entry.file_name());
+		let path = entry.path();
+
+		if ignore.is_ignored(&relative) {
+			super::print_skipped(&path, "ignored");
+			continue;
+		}
+
+		let deployed = deployed.join(deployed_name(entry.file_name(), dotfiles));
+
+		if path.is_dir() {
+			imported += import_dir(destination, ignore, package, &relative, &deployed, dotfiles)?;
+		} else {
+			destination.copy_file(&path, &deployed)?;
+			super::print_imported(&path, &destination.dotfile(&deployed));
+			imported += 1;
+		}
+	}
+
+	Ok(imported)
+}
+
+/// Returns the names of all packages inside of the stow directory `dir`.
+fn find_packages(dir: &Path) -> Result<Vec<String>> {
+	let mut packages = Vec::new();
+
+	for entry in std::fs::read_dir(dir)
+		.wrap_err_with(|| format!("Failed to read stow directory {}", dir.display()))?
+	{
+		let entry = entry?;
+		let name = entry.file_name().to_string_lossy().into_owned();
+
+		if entry.path().is_dir() && !name.starts_with('.') {
+			packages.push(name);
+		}
+	}
+
+	packages.sort();
+
+	Ok(packages)
+}
+
+/// Imports the packages of a stow directory into `destination` and returns
+/// the generated profile.
+///
+/// # Errors
+///
+/// An error is returned if a package does not exist or if any file could not
+/// be copied.
+pub fn import(destination: &Destination, options: StowOptions) -> Result<Profile> {
+	let dir = options
+		.dir
+		.canonicalize()
+		.wrap_err_with(|| format!("Failed to resolve stow directory {}", options.dir.display()))?;
+
+	let packages = if options.packages.is_empty() {
+		find_packages(&dir)?
+	} else {
+		options.packages
+	};
+
+	if packages.is_empty() {
+		return Err(eyre!("No packages found in {}", dir.display()));
+	}
+
+	let target = match options.target {
+		Some(target) => target,
+		None => dir
+			.parent()
+			.ok_or_else(|| eyre!("Stow directory {} has no parent", dir.display()))?
+			.to_path_buf(),
+	};
+
+	let mut dotfiles = Vec::new();
+
+	for package in packages {
+		let path = dir.join(&package);
+
+		if !path.is_dir() {
+			return Err(eyre!(
+				"Package {package} does not exist in {}",
+				dir.display()
+			));
+		}
+
+		let ignore = Ignore::load(&path)?;
+		let imported = import_dir(
+			destination,
+			&ignore,
+			&path,
+			Path::new(""),
+			Path::new(&package),
+			options.dotfiles,
+		)?;
+
+		if imported == 0 {
+			super::print_skipped(&path, "empty package");
+			continue;
+		}
+
+		// Stow packages contain plain files
+		dotfiles.push(super::dotfile(package.into(), false));
+	}
+
+	Ok(Profile {
+		description: Some(format!("Imported from stow directory {}", dir.display())),
+		target: Some(target),
+		dotfiles,
+		..Default::default()
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::import::tests::{files, fixture};
+
+	#[test]
+	fn import_fixture() -> Result<()> {
+		let dir = tempfile::tempdir()?;
+		let destination = Destination::create(dir.path().to_path_buf(), false)?;
+		let home = dir.path().join("home");
+
+		let profile = import(
+			&destination,
+			StowOptions {
+				dir: fixture("stow"),
+				packages: Vec::new(),
+				target: Some(home.clone()),
+				dotfiles: true,
+			},
+		)?;
+
+		assert_eq!(profile.target, Some(home));
+		// The package `empty` only contains ignored files
+		assert_eq!(
+			profile.dotfiles,
+			[
+				crate::import::dotfile("bash".into(), false),
+				crate::import::dotfile("git".into(), false),
+				crate::import::dotfile("nvim".into(), false),
+			]
+		);
+
+		// The default ignore patterns only apply to packages without an
+		// ignore file
+		assert_eq!(
+			files(&dir.path().join("dotfiles")),
+			[
+				"bash/.bashrc",
+				"git/.gitconfig",
+				"nvim/.config/nvim/init.vim",
+			]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn import_packages() -> Result<()> {
+		let dir = tempfile::tempdir()?;
+		let destination = Destination::create(dir.path().to_path_buf(), false)?;
+
+		let options = |packages: &[&str]| StowOptions {
+			dir: fixture("stow"),
+			packages: packages
+				.iter()
+				.map(|package| String::from(*package))
+				.collect(),
+			target: None,
+			dotfiles: false,
+		};
+
+		let profile = import(&destination, options(&["git"]))?;
+
+		// Stow deploys to the parent of the stow directory by default
+		assert_eq!(profile.target, Some(fixture("").canonicalize()?));
+		assert_eq!(
+			profile.dotfiles,
+			[crate::import::dotfile("git".into(), false)]
+		);
+		assert_eq!(files(&dir.path().join("dotfiles")), ["git/dot-gitconfig"]);
+
+		assert!(import(&destination, options(&["missing"])).is_err());
+
+		Ok(())
+	}
+}
@@ -0,0 +1,533 @@
+//! Import of home directories managed by [yadm](https://yadm.io/).
+//!
+//! All files tracked by the yadm repository are copied from the home directory
+//! into the `dotfiles` directory. Files without alternates and default
+//! alternates (`##default`) are added to the base profile, class alternates
+//! (`##class.<class>`) to a profile per class which extends the base profile.
+//! Templates of the default template processor are translated into the punktf
+//! template syntax.
+//!
+//! Encrypted files, alternates for other conditions (e.g. `##os.Linux`) and
+//! the bootstrap script are reported as needing manual attention.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use punktf_lib::profile::variables::Variables;
+use punktf_lib::profile::{Priority, Profile};
+
+use super::{Destination, ESCAPED_OPEN};
+
+/// Directory of the yadm configuration, relative to the home directory.
+const CONFIG_DIR: &str = ".config/yadm";
+
+/// Template variables of yadm and the punktf variable with the same value.
+const VARIABLES: &[(&str, &str)] = &[
+	("yadm.class", "class"),
+	("yadm.os", "$PUNKTF_TARGET_OS"),
+	("yadm.arch", "$PUNKTF_TARGET_ARCH"),
+	("yadm.user", "$USER"),
+];
+
+/// Values of `yadm.os` and `yadm.arch` and their punktf counterparts.
+const VALUES: &[(&str, &str, &str)] = &[
+	("yadm.os", "Linux", "linux"),
+	("yadm.os", "Darwin", "macos"),
+	("yadm.os", "WSL", "linux"),
+	("yadm.arch", "arm64", "aarch64"),
+];
+
+/// Options for the import of a yadm managed home directory.
+#[derive(Debug)]
+pub struct YadmOptions {
+	/// The yadm repository.
+	///
+	/// Defaults to `~/.local/share/yadm/repo.git`.
+	pub repo: Option<PathBuf>,
+
+	/// The home directory (work tree of the repository).
+	///
+	/// Defaults to `~`.
+	pub home: Option<PathBuf>,
+
+	/// Target directory of the profiles.
+	///
+	/// Defaults to `~`.
+	pub target: Option<PathBuf>,
+}
+
+/// Conditions of an alternate file (e.g. `##class.Work,template`).
+#[derive(Debug, Default)]
+struct Alternate {
+	/// Class for which the alternate is used.
+	class: Option<String>,
+
+	/// Template processor if the alternate is a template.
+	template: Option<String>,
+
+	/// Conditions without an equivalent in punktf.
+	unsupported: Vec<String>,
+}
+
+impl Alternate {
+	/// Adds the comma separated `conditions` to the alternate.
+	fn parse(&mut self, conditions: &str) {
+		for condition in conditions
+			.split(',')
+			.filter(|condition| !condition.is_empty())
+		{
+			let (key, value) = condition
+				.split_once('.')
+				.map_or((condition, None), |(key, value)| (key, Some(value)));
+
+			match (key, value) {
+				("default" | "e" | "extension", _) => {}
+				("t" | "template", processor) => {
+					self.template = Some(processor.unwrap_or("default").into());
+				}
+				("c" | "class", Some(class)) => self.class = Some(class.into()),
+				_ => self.unsupported.push(condition.into()),
+			}
+		}
+	}
+}
+
+/// Splits `path` into the path it is deployed to (without any alternate
+/// suffixes) and the conditions of the alternate, if it is one.
+fn parse_path(path: &Path) -> (PathBuf, Option<Alternate>) {
+	let mut plain = PathBuf::new();
+	let mut alternate: Option<Alternate> = None;
+
+	for component in path.components() {
+		let component = component.as_os_str().to_string_lossy();
+
+		match component.split_once("##") {
+			Some((name, conditions)) => {
+				plain.push(name);
+				alternate
+					.get_or_insert_with(Default::default)
+					.parse(conditions);
+			}
+			None => plain.push(component.as_ref()),
+		}
+	}
+
+	(plain, alternate)
+}
+
+/// Translates a variable of a template (e.g. `yadm.class` or `env.EDITOR`)
+/// into the name of a punktf variable.
+fn variable(expr: &str) -> Option<String> {
+	if let Some(name) = expr.strip_prefix("env.") {
+		return (!name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+			.then(|| format!("${name}"));
+	}
+
+	VARIABLES
+		.iter()
+		.find(|(name, _)| *name == expr)
+		.map(|(_, variable)| String::from(*variable))
+}
+
+/// Kind of a block which was opened by a template statement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Block {
+	/// An `if` block which was translated.
+	If,
+
+	/// An `if` block which was not translated.
+	Other,
+}
+
+/// Translates a single template statement (`{% ... %}` without the
+/// surrounding braces).
+///
+/// Returns `None` if the statement has no equivalent in punktf.
+fn translate_statement(statement: &str, blocks: &mut Vec<Block>) -> Option<String> {
+	let words = statement.split_whitespace().collect::<Vec<_>>();
+
+	match words.as_slice() {
+		["if", var, op @ ("==" | "!="), literal] => {
+			let translated = variable(var).zip(
+				literal
+					.strip_prefix('"')
+					.and_then(|literal| literal.strip_suffix('"')),
+			);
+
+			let Some((variable, literal)) = translated else {
+				blocks.push(Block::Other);
+				return None;
+			};
+
+			let literal = VALUES
+				.iter()
+				.find(|(name, value, _)| name == var && *value == literal)
+				.map_or(literal, |(_, _, value)| value);
+
+			blocks.push(Block::If);
+			Some(format!("{{{{@if {{{{{variable}}}}} {op} \"{literal}\"}}}}"))
+		}
+		["if", ..] => {
+			blocks.push(Block::Other);
+			None
+		}
+		["else"] if blocks.last() == Some(&Block::If) => Some(String::from("{{@else}}")),
+		["endif"] => match blocks.pop() {
+			Some(Block::If) => Some(String::from("{{@fi}}")),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// Translates a template of the default yadm template processor into a punktf
+/// template.
+///
+/// Returns the translated template together with all expressions and
+/// statements which could not be translated. Untranslated statements are
+/// copied as is, untranslated expressions are escaped.
+fn translate_template(content: &str) -> (String, Vec<String>) {
+	let mut translated = String::with_capacity(content.len());
+	let mut untranslated = Vec::new();
+	let mut blocks = Vec::new();
+	let mut rest = content;
+
+	while let Some(low) = [rest.find("{{"), rest.find("{%")]
+		.into_iter()
+		.flatten()
+		.min()
+	{
+		let is_statement = rest[low..].starts_with("{%");
+		let close = if is_statement { "%}" } else { "}}" };
+
+		let Some(high) = rest[low..].find(close).map(|high| low + high + 2) else {
+			break;
+		};
+
+		translated.push_str(&rest[..low]);
+
+		let raw = &rest[low..high];
+		let inner = raw[2..raw.len() - 2].trim();
+
+		let action = if is_statement {
+			translate_statement(inner, &mut blocks)
+		} else {
+			variable(inner).map(|variable| format!("{{{{{variable}}}}}"))
+		};
+
+		match action {
+			Some(action) => translated.push_str(&action),
+			None => {
+				if is_statement {
+					translated.push_str(raw);
+				} else {
+					translated.push_str(ESCAPED_OPEN);
+					translated.push_str(&raw[2..]);
+				}
+
+				untranslated.push(String::from(raw));
+			}
+		}
+
+		rest = &rest[high..];
+	}
+
+	match rest.find("{{") {
+		Some(low) => {
+			translated.push_str(&rest[..low]);
+			translated.push_str(ESCAPED_OPEN);
+			translated.push_str(&rest[low + 2..]);
+			untranslated.push(String::from(&rest[low..]));
+		}
+		None => translated.push_str(rest),
+	}
+
+	(translated, untranslated)
+}
+
+/// Returns the default location of the yadm repository.
+fn default_repo(home: &Path) -> Result<PathBuf> {
+	let data = std::env::var_os("XDG_DATA_HOME")
+		.map(PathBuf::from)
+		.unwrap_or_else(|| home.join(".local").join("share"));
+
+	[
+		data.join("yadm").join("repo.git"),
+		home.join(CONFIG_DIR).join("repo.git"),
+	]
+	.into_iter()
+	.find(|repo| repo.is_dir())
+	.ok_or_else(|| eyre!("No yadm repository found. Use `--repo` to specify it"))
+}
+
+/// Imports a file of the yadm configuration directory.
+fn import_config(destination: &Destination, from: &Path, file: &Path) -> Result<()> {
+	match file.file_name().and_then(|name| name.to_str()) {
+		Some("bootstrap") => {
+			let content = std::fs::read(from)
+				.wrap_err_with(|| format!("Failed to read {}", from.display()))?;
+			let relative = destination.write_script("bootstrap", &content)?;
+
+			super::print_imported(from, &relative);
+			destination.attention(
+				from,
+				"bootstrap script is not run, add it as hook if needed",
+			);
+		}
+		Some("encrypt") => {
+			let content = std::fs::read_to_string(from)
+				.wrap_err_with(|| format!("Failed to read {}", from.display()))?;
+
+			for pattern in content
+				.lines()
+				.map(str::trim)
+				.filter(|line| !line.is_empty() && !line.starts_with('#'))
+			{
+				destination.attention(
+					from,
+					&format!("encrypted files matching `{pattern}` are not imported"),
+				);
+			}
+		}
+		_ => super::print_skipped(from, "yadm configuration"),
+	}
+
+	Ok(())
+}
+
+/// Imports a yadm managed home directory into `destination`.
+///
+/// Returns the base profile with the given `name` and a profile for each
+/// class (`<name>-<class>`), which extends the base profile.
+///
+/// # Errors
+///
+/// An error is returned if the tracked files could not be listed or if any
+/// file could not be copied.
+pub fn import(
+	destination: &Destination,
+	name: &str,
+	options: YadmOptions,
+) -> Result<Vec<(String, Profile)>> {
+	let home = match options.home {
+		Some(home) => home,
+		None => dirs::home_dir().ok_or_else(|| eyre!("Failed to get the home directory"))?,
+	};
+
+	let repo = match options.repo {
+		Some(repo) => repo,
+		None => default_repo(&home)?,
+	};
+
+	let files = crate::git::tracked_files(&repo, &home)?;
+
+	let mut base = Profile {
+		description: Some(format!("Imported from yadm repository {}", repo.display())),
+		target: Some(options.target.unwrap_or_else(|| PathBuf::from("~"))),
+		..Default::default()
+	};
+	let mut classes = BTreeMap::new();
+
+	for file in files {
+		let from = home.join(&file);
+
+		if file.starts_with(CONFIG_DIR) {
+			import_config(destination, &from, &file)?;
+			continue;
+		}
+
+		if !from.is_file() {
+			destination.attention(&from, "file does not exist in the home directory");
+			continue;
+		}
+
+		let (plain, alternate) = parse_path(&file);
+		let alternate = alternate.unwrap_or_default();
+
+		let mut content =
+			std::fs::read(&from).wrap_err_with(|| format!("Failed to read {}", from.display()))?;
+		let mut template = false;
+
+		match alternate.template.as_deref() {
+			Some("default") => {
+				let (translated, untranslated) =
+					translate_template(&String::from_utf8_lossy(&content));
+
+				for expr in untranslated {
+					destination.attention(&from, &format!("template `{expr}` was not translated"));
+				}
+
+				content = translated.into_bytes();
+				template = true;
+			}
+			Some(processor) => destination.attention(
+				&from,
+				&format!("templates of the processor `{processor}` are not supported"),
+			),
+			None => {}
+		}
+
+		destination.write_file(&file, &content)?;
+		super::print_imported(&from, &destination.dotfile(&file));
+
+		if !alternate.unsupported.is_empty() {
+			destination.attention(
+				&from,
+				&format!(
+					"alternate for `{}` is not added to any profile",
+					alternate.unsupported.join(",")
+				),
+			);
+			continue;
+		}
+
+		let mut dotfile = super::dotfile(file.clone(), template);
+		if plain != file {
+			dotfile.rename = Some(plain);
+			// Priorities are only compared if both dotfiles have one
+			dotfile.priority = Some(Priority::default());
+		}
+
+		match alternate.class {
+			Some(class) => {
+				// Class alternates take precedence over the default ones
+				dotfile.priority = Some(Priority::new(1));
+
+				classes
+					.entry(class.clone())
+					.or_insert_with(|| Profile {
+						description: Some(format!("Class {class} of {name}")),
+						extends: vec![name.into()],
+						variables: Some(Variables::from_items([("class", class)])),
+						..Default::default()
+					})
+					.dotfiles
+					.push(dotfile);
+			}
+			None => base.dotfiles.push(dotfile),
+		}
+	}
+
+	let mut profiles = vec![(String::from(name), base)];
+	profiles.extend(
+		classes
+			.into_iter()
+			.map(|(class, profile)| (format!("{name}-{}", class.to_lowercase()), profile)),
+	);
+
+	Ok(profiles)
+}
+
+#[cfg(test)]
+mod tests {
+	use std::process::Command;
+
+	use punktf_lib::profile::dotfile::Dotfile;
+
+	use super::*;
+	use crate::import::tests::{files, fixture};
+
+	/// Creates a dotfile for the alternate `path` which is deployed as `rename`
+	/// with the given `priority`.
+	fn alternate(path: &str, rename: &str, priority: Priority, template: bool) -> Dotfile {
+		let mut dotfile = crate::import::dotfile(path.into(), template);
+		dotfile.rename = Some(rename.into());
+		dotfile.priority = Some(priority);
+		dotfile
+	}
+
+	#[test]
+	fn import_fixture() -> Result<()> {
+		let dir = tempfile::tempdir()?;
+		let root = dir.path().join("source");
+		let repo = dir.path().join("repo.git");
+		let home = fixture("yadm").join("home");
+
+		// yadm tracks the files of the home directory with a bare repository
+		for args in [
+			vec!["init", "--quiet", "--bare", &*repo.to_string_lossy()],
+			vec![
+				"--git-dir",
+				&*repo.to_string_lossy(),
+				"--work-tree",
+				&*home.to_string_lossy(),
+				"add",
+				".",
+			],
+		] {
+			assert!(Command::new("git").args(args).status()?.success());
+		}
+
+		let destination = Destination::create(root.clone(), false)?;
+		let profiles = import(
+			&destination,
+			"dotfiles",
+			YadmOptions {
+				repo: Some(repo),
+				home: Some(home),
+				target: None,
+			},
+		)?;
+
+		let [(base_name, base), (work_name, work)] = profiles.as_slice() else {
+			panic!("Expected a base and a class profile: {profiles:#?}");
+		};
+
+		assert_eq!(base_name, "dotfiles");
+		assert_eq!(base.target, Some("~".into()));
+		assert_eq!(
+			base.dotfiles,
+			[
+				crate::import::dotfile(".bashrc".into(), false),
+				alternate(
+					".gitconfig##default",
+					".gitconfig",
+					Priority::default(),
+					false
+				),
+				alternate(".vimrc##template", ".vimrc", Priority::default(), true),
+			]
+		);
+
+		assert_eq!(work_name, "dotfiles-work");
+		assert_eq!(work.extends, ["dotfiles"]);
+		assert_eq!(
+			work.variables,
+			Some(Variables::from_items([("class", "Work")]))
+		);
+		assert_eq!(
+			work.dotfiles,
+			[alternate(
+				".gitconfig##class.Work",
+				".gitconfig",
+				Priority::new(1),
+				false
+			)]
+		);
+
+		// The bootstrap script, the encrypted files and the alternate for an
+		// operating system
+		assert_eq!(destination.attention_count(), 3);
+
+		assert_eq!(
+			files(&root.join("dotfiles")),
+			[
+				".bashrc",
+				".gitconfig##class.Work",
+				".gitconfig##default",
+				".profile##os.Linux",
+				".vimrc##template",
+			]
+		);
+		assert_eq!(files(&root.join("scripts")), ["bootstrap"]);
+		assert_eq!(
+			std::fs::read_to_string(root.join("dotfiles").join(".vimrc##template"))?,
+			"{{@if {{$PUNKTF_TARGET_OS}} == \"macos\"}}set \
+			 clipboard=unnamed{{@fi}}\nlet g:user = \"{{$USER}}\"\n"
+		);
+
+		Ok(())
+	}
+}
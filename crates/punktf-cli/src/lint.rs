@@ -0,0 +1,133 @@
+//! Static checks of all templates of a profile (`punktf lint`).
+//!
+//! The templates are only parsed and never resolved, so problems like unknown
+//! variables or branches which can never be taken are found without deploying
+//! anything.
+
+use color_eyre::Result;
+use console::style;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::LayeredProfile;
+use punktf_lib::template::source::Source;
+use punktf_lib::template::Template;
+use punktf_lib::visit::{self, Directory, Errored, File, Rejected, Symlink, Visitor, Walker};
+
+/// Collects the problems of all templates of a profile.
+#[derive(Debug, Default)]
+struct Linter {
+	/// Amount of checked templates.
+	templates: usize,
+
+	/// All found problems, prefixed with the relative path of their template.
+	problems: Vec<String>,
+}
+
+impl Linter {
+	/// Parses and checks the template `file`.
+	fn lint(&mut self, profile: &LayeredProfile, file: &File<'_>) -> Result<()> {
+		let content = std::fs::read_to_string(&file.source_path)?;
+		let path = file.relative_source_path.display();
+
+		self.templates += 1;
+
+		let template = match Template::parse(Source::file(&file.source_path, &content)) {
+			Ok(template) => template,
+			Err(err) => {
+				self.problems.push(format!("{path}: {err} [parse-error]"));
+				return Ok(());
+			}
+		};
+
+		let lints =
+			template.lint_with_vars(Some(profile.variables()), file.dotfile().variables.as_ref());
+
+		self.problems
+			.extend(lints.into_iter().map(|lint| format!("{path}:{lint}")));
+
+		Ok(())
+	}
+}
+
+impl Visitor for Linter {
+	fn accept_file<'a>(
+		&mut self,
+		_: &PunktfSource,
+		profile: &LayeredProfile,
+		file: &File<'a>,
+	) -> visit::Result {
+		if !file.dotfile().is_template() {
+			return Ok(());
+		}
+
+		if let Err(err) = self.lint(profile, file) {
+			self.problems
+				.push(format!("{}: {err}", file.relative_source_path.display()));
+		}
+
+		Ok(())
+	}
+
+	fn accept_directory<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Directory<'a>,
+	) -> visit::Result {
+		Ok(())
+	}
+
+	fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, _: &Symlink) -> visit::Result {
+		Ok(())
+	}
+
+	fn accept_rejected<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Rejected<'a>,
+	) -> visit::Result {
+		Ok(())
+	}
+
+	fn accept_errored<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		errored: &Errored<'a>,
+	) -> visit::Result {
+		self.problems.push(errored.to_string());
+
+		Ok(())
+	}
+}
+
+/// Checks all templates of the `profile` and prints the found problems.
+///
+/// Returns the amount of found problems.
+///
+/// # Errors
+///
+/// An error is returned if the profile could not be walked.
+pub fn run(source: &PunktfSource, profile: &mut LayeredProfile) -> Result<usize> {
+	let mut linter = Linter::default();
+
+	Walker::new(profile)
+		.walk(source, &mut linter)
+		.map_err(|err| color_eyre::eyre::eyre!("{err}"))?;
+
+	for problem in &linter.problems {
+		println!("{problem}");
+	}
+
+	println!(
+		"{}",
+		style(format!(
+			"Checked {} templates, found {} problems",
+			linter.templates,
+			linter.problems.len()
+		))
+		.dim()
+	);
+
+	Ok(linter.problems.len())
+}
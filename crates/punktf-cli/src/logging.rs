@@ -0,0 +1,169 @@
+//! Setup of the logging for `punktf`.
+//!
+//! Log messages are printed to stderr, filtered by the verbosity given on the
+//! command line. Optionally all messages (down to the `trace` level) are
+//! additionally written to a log file.
+//!
+//! With a verbosity of at least `info`, the time spent in each span (e.g.
+//! profile resolution, compilation of items, writing and hooks) is printed
+//! once the span closes.
+
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use color_eyre::eyre::Context;
+use color_eyre::Result;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::filter::{filter_fn, EnvFilter, FilterExt as _};
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt as _;
+use tracing_subscriber::util::SubscriberInitExt as _;
+use tracing_subscriber::{fmt, Layer};
+
+use crate::opt::{LogFormat, Shared};
+
+/// Size in bytes after which the log file is rotated.
+const MAX_LOG_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Amount of rotated log files which are kept (e.g. `punktf.log.1` to
+/// `punktf.log.3`).
+const MAX_ROTATED_LOG_FILES: usize = 3;
+
+/// Indicates if log messages are printed to stderr.
+static CONSOLE_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enables or disables printing of log messages to stderr.
+///
+/// Messages are still written to the log file.
+pub fn set_console_enabled(enabled: bool) {
+	CONSOLE_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Rotates the log file at `path` if it exceeds [`MAX_LOG_FILE_SIZE`].
+///
+/// The file is renamed to `<path>.1`, already rotated files are shifted by
+/// one and the oldest one is removed.
+fn rotate(path: &Path) -> std::io::Result<()> {
+	let too_large = path
+		.metadata()
+		.is_ok_and(|metadata| metadata.len() >= MAX_LOG_FILE_SIZE);
+
+	if !too_large {
+		return Ok(());
+	}
+
+	let rotated = |idx: usize| {
+		let mut name = path.as_os_str().to_os_string();
+		name.push(format!(".{idx}"));
+		PathBuf::from(name)
+	};
+
+	let oldest = rotated(MAX_ROTATED_LOG_FILES);
+	if oldest.exists() {
+		std::fs::remove_file(oldest)?;
+	}
+
+	for idx in (1..MAX_ROTATED_LOG_FILES).rev() {
+		let from = rotated(idx);
+		if from.exists() {
+			std::fs::rename(from, rotated(idx + 1))?;
+		}
+	}
+
+	std::fs::rename(path, rotated(1))
+}
+
+/// Opens the log file at `path` for appending, rotating it if necessary.
+fn open_log_file(path: &Path) -> std::io::Result<File> {
+	if let Some(parent) = path.parent() {
+		std::fs::create_dir_all(parent)?;
+	}
+
+	rotate(path)?;
+
+	OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// Creates a formatting layer in the given `format`.
+///
+/// If `ansi` is set, text is colored with ansi escape codes.
+fn format_layer<S, W>(
+	format: LogFormat,
+	writer: W,
+	ansi: bool,
+	span_events: FmtSpan,
+) -> Box<dyn Layer<S> + Send + Sync>
+where
+	S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+	W: for<'w> fmt::MakeWriter<'w> + Send + Sync + 'static,
+{
+	let layer = fmt::layer()
+		.with_writer(writer)
+		.with_ansi(ansi)
+		.with_span_events(span_events);
+
+	match format {
+		LogFormat::Text => layer.boxed(),
+		LogFormat::Json => layer.json().flatten_event(true).boxed(),
+	}
+}
+
+/// Sets up the global subscriber.
+///
+/// If `log_file` is given, all messages regardless of the verbosity are
+/// additionally written to it.
+///
+/// # Errors
+///
+/// An error is returned if the log file could not be opened.
+pub fn init(shared: &Shared, log_file: Option<&Path>) -> Result<()> {
+	let log_level = if shared.quiet {
+		LevelFilter::ERROR
+	} else {
+		match shared.verbose {
+			// Default if no value for `verbose` is given
+			0 => LevelFilter::WARN,
+			1 => LevelFilter::INFO,
+			2 => LevelFilter::DEBUG,
+			_ => LevelFilter::TRACE,
+		}
+	};
+
+	let span_events = if log_level >= LevelFilter::INFO {
+		FmtSpan::CLOSE
+	} else {
+		FmtSpan::NONE
+	};
+
+	let console_filter = EnvFilter::builder()
+		.with_default_directive(log_level.into())
+		.from_env_lossy()
+		.and(filter_fn(|_| CONSOLE_ENABLED.load(Ordering::Relaxed)));
+
+	let console = format_layer(
+		shared.log_format,
+		std::io::stderr,
+		console::colors_enabled_stderr(),
+		span_events,
+	)
+	.with_filter(console_filter);
+
+	let file = log_file
+		.map(|path| {
+			open_log_file(path)
+				.map(|file| {
+					format_layer(shared.log_format, Mutex::new(file), false, FmtSpan::CLOSE)
+						.with_filter(LevelFilter::TRACE)
+				})
+				.wrap_err_with(|| format!("Failed to open log file {}", path.display()))
+		})
+		.transpose()?;
+
+	tracing_subscriber::registry()
+		.with(console)
+		.with(file)
+		.try_init()
+		.wrap_err("Failed to set up logging")
+}
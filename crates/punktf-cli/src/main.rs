@@ -127,27 +127,71 @@
 )]
 #![cfg_attr(docsrs, feature(doc_cfg), feature(doc_alias))]
 
+mod answers;
+mod bench;
+mod bootstrap;
+mod completions;
+mod config;
+mod container;
 mod diff;
+mod encrypt;
+mod export;
+mod fmt;
+mod git;
+mod grep;
+mod history;
+mod import;
+mod lint;
+mod logging;
+mod migrate;
+mod new_profile;
+mod notification;
 mod opt;
+mod plan;
+#[cfg(feature = "plugins")]
+mod plugin;
+mod report;
+mod sandbox;
+mod script;
+mod secret;
+mod snapshot;
+mod ui;
+#[cfg(feature = "self-update")]
+mod update;
 mod util;
+mod vars;
+mod watch;
+mod which;
+use std::collections::HashMap;
 use std::fs::{File, OpenOptions};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use clap::{CommandFactory, Parser};
-use color_eyre::eyre::eyre;
+use color_eyre::eyre::{eyre, Context};
 use color_eyre::Result;
 use opt::Command;
+use punktf_lib::profile::cache::ProfileCache;
 use punktf_lib::profile::dotfile::Dotfile;
-use punktf_lib::profile::source::PunktfSource;
-use punktf_lib::profile::{resolve_profile, LayeredProfile, Profile};
+use punktf_lib::profile::source::{ConflictPolicy, PunktfSource};
+use punktf_lib::profile::variables::Variables;
+use punktf_lib::profile::{
+	resolve_profile, LayeredProfile, LayeredProfileBuilder, Priority, Profile,
+};
 use punktf_lib::template::source::Source;
 use punktf_lib::template::Template;
-use punktf_lib::visit::deploy::{deployment::Deployment, *};
+use punktf_lib::visit::deploy::*;
 use punktf_lib::visit::diff::Diff;
+use punktf_lib::visit::filter::PathFilter;
+use punktf_lib::visit::plan::{Action, Planner};
+use serde::Serialize;
 
 /// Name of this binary.
 const BINARY_NAME: &str = env!("CARGO_BIN_NAME");
 
+/// Amount of dotfiles printed by `deploy --timings`.
+const SLOWEST_DOTFILES_COUNT: usize = 10;
+
 /// Name of the environment variable which defines the default source path for
 /// `punktf`.
 pub const PUNKTF_SOURCE_ENVVAR: &str = "PUNKTF_SOURCE";
@@ -160,60 +204,299 @@ pub const PUNKTF_TARGET_ENVVAR: &str = "PUNKTF_TARGET";
 /// `punktf`.
 pub const PUNKTF_PROFILE_ENVVAR: &str = "PUNKTF_PROFILE";
 
+/// Prefix of environment variables which set template variables (e.g.
+/// `PUNKTF_VAR_editor` sets the variable `editor`).
+pub const PUNKTF_VAR_ENVVAR_PREFIX: &str = "PUNKTF_VAR_";
+
+/// Name of the environment variable which defines the path to the global
+/// configuration file of `punktf`.
+pub const PUNKTF_CONFIG_ENVVAR: &str = "PUNKTF_CONFIG";
+
+/// Name of the environment variable which defines the file with answers to the
+/// prompt variables for `deploy`.
+pub const PUNKTF_ANSWERS_ENVVAR: &str = "PUNKTF_ANSWERS";
+
 /// Entry point for `punktf`.
 fn main() -> Result<()> {
 	color_eyre::install()?;
 
+	if completions::complete() {
+		return Ok(());
+	}
+
 	let opts = opt::Opts::parse();
 
-	let log_level = if opts.shared.quite {
-		log::Level::Error
-	} else {
-		match opts.shared.verbose {
-			// Default if no value for `verbose` is given
-			0 => log::Level::Warn,
-			1 => log::Level::Info,
-			2 => log::Level::Debug,
-			_ => log::Level::Trace,
-		}
+	let log_file = match &opts.shared.log_file {
+		Some(path) => Some(path.clone()),
+		None => config::Config::load()?.log_file,
 	};
 
-	env_logger::Builder::from_env(env_logger::Env::default().default_filter_or(log_level.as_str()))
-		.init();
+	match opts.shared.color {
+		opt::ColorChoice::Auto => {}
+		opt::ColorChoice::Always => {
+			console::set_colors_enabled(true);
+			console::set_colors_enabled_stderr(true);
+		}
+		opt::ColorChoice::Never => {
+			console::set_colors_enabled(false);
+			console::set_colors_enabled_stderr(false);
+		}
+	}
+
+	logging::init(&opts.shared, log_file.as_deref())?;
 
-	log::debug!("Parsed Opts:\n{:#?}", opts);
+	if let Some(time) = &opts.shared.frozen_time {
+		std::env::set_var(punktf_lib::expr::FROZEN_TIME_ENVVAR, time);
+	}
 
-	handle_command(opts.command)
+	tracing::debug!("Parsed Opts:\n{:#?}", opts);
+
+	handle_command(opts.command, opts.shared.quiet)
 }
 
 /// Gets the parsed command line arguments and evaluates them.
-fn handle_command(command: Command) -> Result<()> {
+///
+/// If `quiet` is set, only a short summary of deployments is printed.
+fn handle_command(command: Command, quiet: bool) -> Result<()> {
 	match command {
-		Command::Deploy(c) => handle_command_deploy(c),
+		Command::Deploy(c) => handle_command_deploy(c, quiet),
 		Command::Render(c) => handle_command_render(c),
-		Command::Verify(c) => handle_command_verify(c),
+		Command::DebugTemplate(c) => handle_command_debug_template(c),
+		Command::Edit(c) => handle_command_edit(c, quiet),
+		Command::Verify(c) => handle_command_verify(c, quiet),
 		Command::Diff(c) => handle_command_diff(c),
+		Command::Plan(c) => handle_command_plan(c),
+		Command::Check(c) => handle_command_check(c),
+		Command::Test(c) => handle_command_test(c),
+		Command::Ui(c) => handle_command_ui(c),
+		Command::Watch(c) => handle_command_watch(c, quiet),
+		Command::Bootstrap(c) => handle_command_bootstrap(c),
+		Command::History(c) => handle_command_history(c),
+		Command::Import(c) => handle_command_import(c),
+		Command::Export(c) => handle_command_export(c),
+		Command::Schema(c) => handle_command_schema(c),
+		Command::Migrate(opt::Migrate { source, dry_run }) => migrate::run(&source, dry_run),
+		Command::Fmt(opt::Fmt { source, check }) => fmt::run(&source, check),
+		Command::Grep(c) => handle_command_grep(c),
+		Command::Lint(c) => handle_command_lint(c),
+		Command::Bench(c) => handle_command_bench(c),
+		Command::Which(c) => handle_command_which(c),
+		Command::Vars(c) => handle_command_vars(c),
+		Command::Encrypt(opt::Encrypt { shared, file }) => {
+			let (ptf_src, profile) = setup_repo(shared)?;
+			encrypt::encrypt(&ptf_src, &profile, &file)
+		}
+		Command::Decrypt(opt::Decrypt { shared, file }) => {
+			let (ptf_src, profile) = setup_repo(shared)?;
+			encrypt::decrypt(&ptf_src, &profile, &file)
+		}
+		Command::Reencrypt(opt::Reencrypt { shared, rotate_key }) => {
+			let (ptf_src, profile) = setup_repo(shared)?;
+			encrypt::reencrypt(&ptf_src, &profile, rotate_key)
+		}
+		Command::Answers(opt::Answers { command }) => match command {
+			opt::AnswersCommand::Edit(opt::AnswersEdit {}) => answers::edit(),
+			opt::AnswersCommand::Clear(opt::AnswersClear { profile }) => {
+				answers::clear(profile.as_deref())
+			}
+		},
+		Command::Secret(opt::Secret { command }) => match command {
+			opt::SecretCommand::Set(opt::SecretSet {
+				shared: opt::SecretShared { namespace },
+				name,
+				value,
+			}) => secret::set(&namespace, &name, value),
+			opt::SecretCommand::Get(opt::SecretGet {
+				shared: opt::SecretShared { namespace },
+				name,
+			}) => secret::get(&namespace, &name),
+			opt::SecretCommand::List(opt::SecretList {
+				shared: opt::SecretShared { namespace },
+			}) => secret::list(&namespace),
+			opt::SecretCommand::Rm(opt::SecretRm {
+				shared: opt::SecretShared { namespace },
+				name,
+			}) => secret::remove(&namespace, &name),
+		},
+		Command::NewProfile(c) => handle_command_new_profile(c),
 		Command::Man(c) => handle_command_man(c),
 		Command::Completions(c) => handle_command_completions(c),
+		#[cfg(feature = "self-update")]
+		Command::SelfUpdate(opt::SelfUpdate { check, force }) => update::run(check, force),
 	}
 }
 
+/// Determines the name of the profile to use.
+///
+/// The profile is looked up in the following order:
+///
+/// 1. Command line argument `-p/--profile`
+/// 2. Environment variable [`PUNKTF_PROFILE_ENVVAR`]
+/// 3. `default_profile` of the global [`Config`](`config::Config`)
+/// 4. Interactive selection by the user
+///
+/// # Errors
+///
+/// An error is returned if no profile name was found and the session is not
+/// interactive.
+fn get_profile_name(profile_name: Option<String>, source: &PunktfSource) -> Result<String> {
+	if let Some(profile_name) = profile_name {
+		return Ok(profile_name);
+	}
+
+	if let Some(profile_name) = config::Config::load()?.default_profile {
+		tracing::info!("Using default profile `{profile_name}` from the config");
+		return Ok(profile_name);
+	}
+
+	if !util::is_interactive() {
+		return Err(eyre!(
+			"No profile given. Either use the command line argument `-p/--profile`, the \
+			 environment variable `{PUNKTF_PROFILE_ENVVAR}` or set `default_profile` in the \
+			 config file"
+		));
+	}
+
+	util::select_profile(source)
+}
+
+/// Creates the source from all given source directories.
+///
+/// A source directory can be suffixed with `@<priority>` (e.g.
+/// `dotfiles@10`) to set the priority used by [`ConflictPolicy::Priority`].
+fn setup_source(sources: Vec<PathBuf>, conflict_policy: ConflictPolicy) -> Result<PunktfSource> {
+	let (roots, priorities): (Vec<_>, Vec<_>) =
+		sources.into_iter().map(split_source_priority).unzip();
+
+	let mut source = PunktfSource::from_roots(roots)?;
+	source.conflict_policy = conflict_policy;
+
+	let mut priorities = priorities.into_iter();
+	source.priority = priorities.next().unwrap_or_default();
+	for (overlay, priority) in source.overlays.iter_mut().zip(priorities) {
+		overlay.priority = priority;
+	}
+
+	Ok(source)
+}
+
+/// Splits an optional `@<priority>` suffix from a source directory.
+///
+/// If the path has no valid suffix, it is returned unchanged together with
+/// the default priority.
+fn split_source_priority(path: PathBuf) -> (PathBuf, Priority) {
+	let split = path.to_str().and_then(|p| {
+		let (root, priority) = p.rsplit_once('@')?;
+		Some((PathBuf::from(root), Priority::new(priority.parse().ok()?)))
+	});
+
+	split.unwrap_or_else(|| (path, Priority::default()))
+}
+
+/// Creates a filter which only accepts items matching any of the `only`
+/// patterns and none of the `exclude` patterns.
+fn setup_filter(only: &[String], exclude: &[String]) -> Result<PathFilter> {
+	let filter = only
+		.iter()
+		.try_fold(PathFilter::new(), |filter, pattern| filter.only(pattern))?;
+
+	exclude
+		.iter()
+		.try_fold(filter, |filter, pattern| filter.exclude(pattern))
+}
+
+/// Determines which dotfiles of `profile` are affected by changes made since
+/// the git revision `rev`.
+///
+/// Returns the paths of the affected dotfiles. If a profile changed, an empty
+/// list is returned as all dotfiles need to be deployed. If nothing changed,
+/// `None` is returned.
+fn changed_dotfiles(
+	source: &PunktfSource,
+	profile: &LayeredProfile,
+	rev: &str,
+) -> Result<Option<Vec<String>>> {
+	let mut changed = Vec::new();
+
+	for layer in source.layers() {
+		for file in git::changed_files(layer.root(), rev)? {
+			let file = layer.root().join(file);
+
+			if file.starts_with(layer.profiles()) {
+				tracing::info!(
+					"Profile {} changed since {rev}; deploying all dotfiles",
+					file.display()
+				);
+				return Ok(Some(Vec::new()));
+			}
+
+			if let Ok(relative) = file.strip_prefix(layer.dotfiles()) {
+				changed.push(relative.to_path_buf());
+			}
+		}
+	}
+
+	let affected: Vec<String> = profile
+		.dotfiles()
+		.filter(|dotfile| changed.iter().any(|c| c.starts_with(&dotfile.path)))
+		.map(|dotfile| dotfile.path.display().to_string())
+		.collect();
+
+	tracing::info!("Dotfiles changed since {rev}: {affected:?}");
+
+	Ok((!affected.is_empty()).then_some(affected))
+}
+
+/// Name of the profile layer which holds the command line arguments.
+pub const CLI_LAYER: &str = "cli_arguments";
+
+/// Name of the profile layer which holds the variables set by environment
+/// variables.
+pub const ENV_VARIABLES_LAYER: &str = "environment_variables";
+
 /// Reads and creates a profile from a path.
+///
+/// The `target` and `vars` given on the command line are added as the top
+/// layer, followed by the variables of the environment (see
+/// [`PUNKTF_VAR_ENVVAR_PREFIX`]). The stored answers to the prompt variables
+/// of the profile are added as the lowest variable layer.
 fn setup_profile(
 	profile_name: &str,
 	source: &PunktfSource,
 	target: Option<PathBuf>,
+	vars: &[(String, String)],
+) -> Result<LayeredProfile> {
+	setup_profile_with_answers(profile_name, source, target, vars, None)
+}
+
+/// Reads and creates a profile from a path like [`setup_profile`].
+///
+/// The answers of the `answers` file are added above the stored answers to the
+/// prompt variables.
+fn setup_profile_with_answers(
+	profile_name: &str,
+	source: &PunktfSource,
+	target: Option<PathBuf>,
+	vars: &[(String, String)],
+	answers: Option<&Path>,
 ) -> Result<LayeredProfile> {
 	let mut builder = LayeredProfile::build();
 
-	// Add target cli argument to top
-	let target_cli_profile = Profile {
+	// Add cli arguments to top
+	let cli_profile = Profile {
 		target,
+		variables: (!vars.is_empty()).then(|| Variables::from_items(vars.iter().cloned())),
+		..Default::default()
+	};
+	builder.add(String::from(CLI_LAYER), cli_profile);
+
+	let env_profile = Profile {
+		variables: util::get_variables(),
 		..Default::default()
 	};
-	builder.add(String::from("target_cli_argument"), target_cli_profile);
+	builder.add(String::from(ENV_VARIABLES_LAYER), env_profile);
 
-	resolve_profile(&mut builder, source, profile_name)?;
+	resolve_profile_layers(&mut builder, source, profile_name)?;
 
 	// Add target environment variable to bottom
 	let target_env_profile = Profile {
@@ -225,7 +508,54 @@ fn setup_profile(
 		target_env_profile,
 	);
 
-	Ok(builder.finish())
+	let mut profile = builder.finish();
+	#[cfg_attr(not(feature = "plugins"), allow(unused_mut))]
+	let mut providers = secret::providers(profile.secret_namespace())?;
+	#[cfg(feature = "plugins")]
+	plugin::load(profile.plugins_mut(), &mut providers)?;
+	profile.resolve_variables(&providers)?;
+	answers::apply(&mut profile, profile_name, answers)?;
+
+	Ok(profile)
+}
+
+/// Resolves the profile `profile_name` and adds all layers to `builder`.
+///
+/// If `cache_profiles` is set in the global configuration, the layers are
+/// taken from and stored in the profile cache.
+fn resolve_profile_layers(
+	builder: &mut LayeredProfileBuilder,
+	source: &PunktfSource,
+	profile_name: &str,
+) -> Result<()> {
+	let path = config::Config::load()?
+		.cache_profiles
+		.then(dirs::cache_dir)
+		.flatten()
+		.map(|dir| dir.join("punktf").join("profile-cache.json"));
+
+	let Some(path) = path else {
+		return resolve_profile(builder, source, profile_name);
+	};
+
+	let mut cache = ProfileCache::load(&path)?;
+	cache.resolve(builder, source, profile_name)?;
+
+	if let Err(err) = cache.save(&path) {
+		tracing::warn!("Failed to save profile cache to {}: {err}", path.display());
+	}
+
+	Ok(())
+}
+
+/// Returns the path of the template cache if `cache_templates` is set in the
+/// global configuration.
+fn template_cache_path() -> Result<Option<PathBuf>> {
+	Ok(config::Config::load()?
+		.cache_templates
+		.then(dirs::cache_dir)
+		.flatten()
+		.map(|dir| dir.join("punktf").join("template-cache.json")))
 }
 
 /// Sets up the environment with PUNKTF specific variables.
@@ -243,8 +573,9 @@ fn handle_output(
 	opt::OutputShared {
 		json_output,
 		yaml_output,
+		..
 	}: opt::OutputShared,
-	deployment: &Deployment,
+	output: &impl Serialize,
 ) {
 	/// Creates a new file. Fails if the file exists.
 	///
@@ -258,13 +589,13 @@ fn handle_output(
 			let mut file = match create_file(&json_path) {
 				Ok(file) => file,
 				Err(err) => {
-					log::error!("Failed to create json output file: {err}");
+					tracing::error!("Failed to create json output file: {err}");
 					break 'json;
 				}
 			};
 
-			if let Err(err) = serde_json::to_writer_pretty(&mut file, deployment) {
-				log::error!("Failed to write deployment status to json output file: {err}");
+			if let Err(err) = serde_json::to_writer_pretty(&mut file, output) {
+				tracing::error!("Failed to write deployment status to json output file: {err}");
 				break 'json;
 			}
 		}
@@ -275,34 +606,128 @@ fn handle_output(
 			let mut file = match create_file(&yaml_path) {
 				Ok(file) => file,
 				Err(err) => {
-					log::error!("Failed to create yaml output file: {err}");
+					tracing::error!("Failed to create yaml output file: {err}");
 					break 'yaml;
 				}
 			};
 
-			if let Err(err) = serde_yaml::to_writer(&mut file, deployment) {
-				log::error!("Failed to write deployment status to yaml output file: {err}");
+			if let Err(err) = serde_yaml::to_writer(&mut file, output) {
+				tracing::error!("Failed to write deployment status to yaml output file: {err}");
 				break 'yaml;
 			}
 		}
 	}
 }
 
+/// Asks the user to confirm a deployment of `profile` which overwrites
+/// existing files.
+///
+/// A summary of all planned actions is printed before asking. Returns `true`
+/// without asking if no existing file would be overwritten.
+fn confirm_deployment(
+	source: &PunktfSource,
+	profile: &mut LayeredProfile,
+	filter: PathFilter,
+	force: bool,
+) -> Result<bool> {
+	let plan = Planner::new()
+		.with_filter(filter)
+		.with_force(force)
+		.plan(source, profile);
+
+	let overwrites = plan
+		.items
+		.iter()
+		.filter(|item| item.action == Action::Overwrite)
+		.count();
+
+	if overwrites == 0 {
+		return Ok(true);
+	}
+
+	println!(
+		"Deploying to {}: {}",
+		profile.target_path().map_or_else(
+			|| String::from("<unknown>"),
+			|path| path.display().to_string()
+		),
+		plan::action_counts(&plan)
+	);
+
+	util::confirm(&format!(
+		"{overwrites} existing file(s) will be overwritten. Continue?"
+	))
+}
+
+/// Records the `deployment` in the [history](`history::History`).
+///
+/// Errors are only logged, as they should not fail the deployment.
+fn record_history(profile_name: &str, deployment: &deployment::Deployment) {
+	let result = config::Config::load().and_then(|config| {
+		history::History::open_default(&config)?.record(
+			profile_name,
+			deployment,
+			config.history_hashes,
+		)
+	});
+
+	match result {
+		Ok(id) => tracing::debug!("Recorded deployment {id} in the history"),
+		Err(err) => tracing::warn!("Failed to record deployment in the history: {err:?}"),
+	}
+}
+
+/// Writes the html report of the `deployment` to `path` (if given).
+///
+/// Errors are only logged, the same as for the other output files.
+fn handle_html_output(
+	path: Option<&Path>,
+	profile_name: &str,
+	deployment: &deployment::Deployment,
+	diffs: HashMap<PathBuf, String>,
+) {
+	if let Some(path) = path {
+		if let Err(err) = report::write(path, profile_name, deployment, &diffs) {
+			tracing::error!("{err:?}");
+		}
+	}
+}
+
 /// Handles the `deploy` command processing.
 fn handle_command_deploy(
 	opt::Deploy {
-		shared: opt::RepoShared {
-			source,
-			profile: profile_name,
-			..
-		},
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
 		target,
 		dry_run,
+		sandbox,
+		only,
+		exclude,
+		since,
+		merge,
+		backup_dir,
+		skip_unchanged,
+		no_hooks,
+		cache,
+		fast_compare,
+		force,
+		yes,
+		answers,
+		timings,
 		output,
 	}: opt::Deploy,
+	quiet: bool,
 ) -> Result<()> {
-	let ptf_src = PunktfSource::from_root(source)?;
-	let mut profile = setup_profile(&profile_name, &ptf_src, target)?;
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+	let mut profile =
+		setup_profile_with_answers(&profile_name, &ptf_src, target, &vars, answers.as_deref())?;
+	answers::ask(&mut profile, &profile_name)?;
 
 	// Ensure target is set
 	if profile.target_path().is_none() {
@@ -313,24 +738,135 @@ fn handle_command_deploy(
 		)
 	}
 
-	log::debug!("Profile:\n{:#?}", profile);
-	log::debug!("Source: {}", ptf_src.root().display());
-	log::debug!("Target: {:?}", profile.target_path());
+	tracing::debug!("Profile:\n{:#?}", profile);
+	tracing::debug!("Source: {}", ptf_src.root().display());
+	tracing::debug!("Target: {:?}", profile.target_path());
 
 	setup_env(&ptf_src, &profile, &profile_name);
 
-	let options = DeployOptions { dry_run };
-	let deployment = Deployer::new(options, util::ask_user_merge).deploy(&ptf_src, &mut profile);
+	let only = match since {
+		Some(rev) => {
+			let Some(only) = changed_dotfiles(&ptf_src, &profile, &rev)? else {
+				println!("No dotfiles changed since {rev}");
+				return Ok(());
+			};
 
-	log::debug!("Deployment:\n{:#?}", deployment);
-	util::log_deployment(&deployment, true);
+			only
+		}
+		None => only,
+	};
 
-	handle_output(output, &deployment);
+	let filter = setup_filter(&only, &exclude)?;
+	if filter.is_restricted() {
+		tracing::info!("Only deploying items matching {filter}; skipping hooks and assertions");
+		profile.pre_hooks.clear();
+		profile.post_hooks.clear();
+		profile.assertions.clear();
+	} else if !filter.is_empty() {
+		tracing::info!("Only deploying items matching {filter}");
+	}
 
-	if options.dry_run {
-		log::info!("Note: No files were actually deployed, since dry run mode was enabled");
+	let sandbox = sandbox.map(sandbox::create).transpose()?;
+	if let Some(sandbox) = &sandbox {
+		tracing::info!("Deploying into sandbox {}", sandbox.display());
+		sandbox::apply(&mut profile, sandbox)?;
 	}
 
+	if dry_run {
+		let mut plan = Planner::new()
+			.with_filter(filter)
+			.with_force(force)
+			.plan(&ptf_src, &mut profile);
+
+		if no_hooks {
+			plan.pre_hooks.clear();
+			plan.post_hooks.clear();
+		}
+
+		plan::print_tree(&plan);
+
+		if output.html_output.is_some() {
+			tracing::warn!("The html report is not supported for dry runs");
+		}
+
+		handle_output(output, &plan);
+
+		tracing::info!("Note: No files were actually deployed, since dry run mode was enabled");
+
+		return if plan.has_errors() {
+			Err(eyre!("Some dotfiles failed to plan"))
+		} else {
+			Ok(())
+		};
+	}
+
+	let confirm = !yes && sandbox.is_none() && util::is_interactive();
+	if confirm
+		&& !config::Config::load()?.assume_yes
+		&& !confirm_deployment(&ptf_src, &mut profile, filter.clone(), force)?
+	{
+		println!("Deployment aborted");
+		return Ok(());
+	}
+
+	// Diffs must be collected before the deployment changes the files
+	let diffs = output
+		.html_output
+		.is_some()
+		.then(|| report::collect_diffs(&ptf_src, &mut profile));
+
+	let mut options = DeployOptions::new()
+		.with_filter(filter)
+		.with_skip_unchanged(skip_unchanged)
+		.with_skip_hooks(no_hooks)
+		.with_fast_compare(fast_compare)
+		.with_force(force);
+	if let Some(merge) = merge {
+		options = options.with_merge_mode(merge);
+	}
+	if let Some(backup_dir) = backup_dir {
+		options = options.with_backup_dir(backup_dir);
+	}
+	if let Some(cache) = cache {
+		let path = cache
+			.or_else(|| dirs::cache_dir().map(|dir| dir.join("punktf").join("deploy-cache")))
+			.ok_or_else(|| eyre!("Failed to determine the path of the deploy cache"))?;
+		options = options.with_cache(path);
+	}
+	if let Some(path) = template_cache_path()? {
+		options = options.with_template_cache(path);
+	}
+
+	let deployment = Deployer::new(options, util::ask_user_merge)
+		.with_cancellation(util::cancel_on_interrupt())
+		.deploy(&ptf_src, &mut profile);
+
+	tracing::debug!("Deployment:\n{:#?}", deployment);
+	if quiet {
+		util::print_summary(&deployment);
+	} else {
+		util::print_deployment(&deployment);
+	}
+
+	if timings {
+		util::print_timings(&deployment, SLOWEST_DOTFILES_COUNT);
+	}
+
+	if let Some(sandbox) = &sandbox {
+		println!();
+		sandbox::print_tree(sandbox)?;
+	} else {
+		record_history(&profile_name, &deployment);
+	}
+
+	handle_html_output(
+		output.html_output.as_deref(),
+		&profile_name,
+		&deployment,
+		diffs.unwrap_or_default(),
+	);
+	handle_output(output, &deployment);
+
 	if deployment.status().is_failed() {
 		Err(eyre!("Some dotfiles failed to deploy"))
 	} else {
@@ -338,17 +874,35 @@ fn handle_command_deploy(
 	}
 }
 
-/// Handles the `render` command processing.
-fn handle_command_render(
-	opt::Render {
-		shared: opt::RepoShared {
-			source,
-			profile: profile_name,
-			..
-		},
-		dotfile,
-	}: opt::Render,
-) -> Result<()> {
+/// A template dotfile together with the profile it is resolved with.
+struct DotfileTemplate {
+	/// Source of the dotfiles.
+	source: PunktfSource,
+
+	/// Profile whose variables are used to resolve the template.
+	profile: LayeredProfile,
+
+	/// Variables of the dotfile which contains the template.
+	variables: Option<Variables>,
+
+	/// Absolute path of the template.
+	path: PathBuf,
+
+	/// Content of the template.
+	content: String,
+}
+
+/// Loads the `dotfile` (relative to the `dotfiles` directory) together with
+/// the profile given by `shared`.
+fn load_dotfile_template(
+	opt::RepoShared {
+		source,
+		profile: profile_name,
+		conflict_policy,
+		vars,
+	}: opt::RepoShared,
+	dotfile: &Path,
+) -> Result<DotfileTemplate> {
 	/// Finds the relevant dotfile from which includes the file a the given
 	/// `relative_source_path`.
 	/// This is needed as some files might stem from a directory dotfile.
@@ -379,62 +933,213 @@ fn handle_command_render(
 			.map(|(d, _)| d)
 	}
 
-	let ptf_src = PunktfSource::from_root(source)?;
-	let profile = setup_profile(&profile_name, &ptf_src, None)?;
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+	let profile = setup_profile(&profile_name, &ptf_src, None, &vars)?;
 
-	log::debug!("Profile:\n{:#?}", profile);
-	log::debug!("Source: {}", ptf_src.root().display());
-	log::debug!("Target: {:?}", profile.target_path());
+	tracing::debug!("Profile:\n{:#?}", profile);
+	tracing::debug!("Source: {}", ptf_src.root().display());
+	tracing::debug!("Target: {:?}", profile.target_path());
 
 	setup_env(&ptf_src, &profile, &profile_name);
 
-	let dotfile_vars = if let Some(dotfile) = find_dotfile(profile.dotfiles(), &dotfile) {
-		log::debug!("Dotfile found in profile");
-		dotfile.variables.as_ref()
+	let variables = if let Some(dotfile) = find_dotfile(profile.dotfiles(), dotfile) {
+		tracing::debug!("Dotfile found in profile");
+		dotfile.variables.clone()
 	} else {
-		log::warn!("Dotfile not found in profile");
+		tracing::warn!("Dotfile not found in profile");
 		None
 	};
 
-	let file = ptf_src.dotfiles().join(dotfile);
-	let content = std::fs::read_to_string(&file)?;
-	let file_source = Source::file(&file, &content);
-	let template = Template::parse(file_source)?;
-	let resolved = template.resolve(Some(profile.variables()), dotfile_vars)?;
+	let path = ptf_src.dotfile_path(dotfile);
+	let content = std::fs::read_to_string(&path)?;
+
+	Ok(DotfileTemplate {
+		source: ptf_src,
+		profile,
+		variables,
+		path,
+		content,
+	})
+}
+
+/// Handles the `render` command processing.
+fn handle_command_render(opt::Render { shared, dotfile }: opt::Render) -> Result<()> {
+	let DotfileTemplate {
+		source,
+		profile,
+		variables,
+		path,
+		content,
+	} = load_dotfile_template(shared, &dotfile)?;
+
+	let template = Template::parse(Source::file(&path, &content))?;
+	let resolved = template.resolve_for_profile(&profile, variables.as_ref(), &source)?;
 
 	print!("{resolved}");
 
 	Ok(())
 }
 
+/// Handles the `debug-template` command processing.
+fn handle_command_debug_template(
+	opt::DebugTemplate {
+		shared,
+		dotfile,
+		trace,
+	}: opt::DebugTemplate,
+) -> Result<()> {
+	let DotfileTemplate {
+		source,
+		profile,
+		variables,
+		path,
+		content,
+	} = load_dotfile_template(shared, &dotfile)?;
+
+	let template = Template::parse(Source::file(&path, &content))?;
+
+	print!("{}", template.tree());
+
+	if !trace {
+		return Ok(());
+	}
+
+	let mut events = Vec::new();
+	let resolved = template.resolve_traced(&profile, variables.as_ref(), &source, &mut events);
+
+	println!("\nTrace:");
+	for event in events {
+		println!("{event}");
+	}
+
+	resolved.map(|_| ())
+}
+
+/// Handles the `edit` command processing.
+fn handle_command_edit(
+	opt::Edit {
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
+		target,
+		dotfile,
+		no_deploy,
+	}: opt::Edit,
+	quiet: bool,
+) -> Result<()> {
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+	let mut profile = setup_profile(&profile_name, &ptf_src, target, &vars)?;
+
+	if !profile.dotfiles().any(|d| dotfile.starts_with(&d.path)) {
+		return Err(eyre!(
+			"Dotfile {} is not part of the profile {profile_name}",
+			dotfile.display()
+		));
+	}
+
+	let file = ptf_src.dotfile_path(&dotfile);
+	let before = std::fs::read(&file).ok();
+
+	util::open_editor(&file)?;
+
+	if no_deploy {
+		return Ok(());
+	}
+
+	if file.is_file() && std::fs::read(&file).ok() == before {
+		println!("{} was not changed", dotfile.display());
+		return Ok(());
+	}
+
+	if profile.target_path().is_none() {
+		return Err(eyre!(
+			"No target path for the deployment set. Either use the command line argument \
+			 `-t/--target`, the profile attribute `target` or the environment variable \
+			 `{PUNKTF_TARGET_ENVVAR}`"
+		));
+	}
+
+	setup_env(&ptf_src, &profile, &profile_name);
+
+	// Only the edited dotfile is deployed, like `deploy --only`
+	let filter = PathFilter::new().only(&dotfile.to_string_lossy())?;
+	profile.assertions.clear();
+
+	let options = DeployOptions::new()
+		.with_filter(filter)
+		.with_skip_hooks(true);
+	let deployment = Deployer::new(options, util::ask_user_merge)
+		.with_cancellation(util::cancel_on_interrupt())
+		.deploy(&ptf_src, &mut profile);
+
+	if quiet {
+		util::print_summary(&deployment);
+	} else {
+		util::print_deployment(&deployment);
+	}
+
+	record_history(&profile_name, &deployment);
+
+	if deployment.status().is_failed() {
+		Err(eyre!("Failed to deploy {}", dotfile.display()))
+	} else {
+		Ok(())
+	}
+}
+
 /// Handles the `verify` command processing.
 ///
 /// This is basically a alias for `deploy --dry-run`.
 fn handle_command_verify(
 	opt::Verify {
-		shared: opt::RepoShared {
-			source,
-			profile: profile_name,
-			..
-		},
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
 		output,
 	}: opt::Verify,
+	quiet: bool,
 ) -> Result<()> {
-	let ptf_src = PunktfSource::from_root(source)?;
-	let mut profile = setup_profile(&profile_name, &ptf_src, None)?;
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+	let mut profile = setup_profile(&profile_name, &ptf_src, None, &vars)?;
 
-	log::debug!("Profile:\n{:#?}", profile);
-	log::debug!("Source: {}", ptf_src.root().display());
-	log::debug!("Target: {:?}", profile.target_path());
+	tracing::debug!("Profile:\n{:#?}", profile);
+	tracing::debug!("Source: {}", ptf_src.root().display());
+	tracing::debug!("Target: {:?}", profile.target_path());
 
 	setup_env(&ptf_src, &profile, &profile_name);
 
-	let options = DeployOptions { dry_run: true };
+	let diffs = output
+		.html_output
+		.is_some()
+		.then(|| report::collect_diffs(&ptf_src, &mut profile));
+
+	let options = DeployOptions::new().with_dry_run(true);
 	let deployment = Deployer::new(options, util::ask_user_merge).deploy(&ptf_src, &mut profile);
 
-	log::debug!("Deployment:\n{:#?}", deployment);
-	util::log_deployment(&deployment, true);
+	tracing::debug!("Deployment:\n{:#?}", deployment);
+	if quiet {
+		util::print_summary(&deployment);
+	} else {
+		util::print_deployment(&deployment);
+	}
 
+	handle_html_output(
+		output.html_output.as_deref(),
+		&profile_name,
+		&deployment,
+		diffs.unwrap_or_default(),
+	);
 	handle_output(output, &deployment);
 
 	Ok(())
@@ -443,20 +1148,23 @@ fn handle_command_verify(
 /// Handles the `diff` command processing.
 fn handle_command_diff(
 	opt::Diff {
-		shared: opt::RepoShared {
-			source,
-			profile: profile_name,
-			..
-		},
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
 		format,
 	}: opt::Diff,
 ) -> Result<()> {
-	let ptf_src = PunktfSource::from_root(source)?;
-	let mut profile = setup_profile(&profile_name, &ptf_src, None)?;
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+	let mut profile = setup_profile(&profile_name, &ptf_src, None, &vars)?;
 
-	log::debug!("Profile:\n{:#?}", profile);
-	log::debug!("Source: {}", ptf_src.root().display());
-	log::debug!("Target: {:?}", profile.target_path());
+	tracing::debug!("Profile:\n{:#?}", profile);
+	tracing::debug!("Source: {}", ptf_src.root().display());
+	tracing::debug!("Target: {:?}", profile.target_path());
 
 	setup_env(&ptf_src, &profile, &profile_name);
 
@@ -465,22 +1173,601 @@ fn handle_command_diff(
 	Ok(())
 }
 
-/// Handles the `man` command processing.
-fn handle_command_man(opt::Man { output }: opt::Man) -> Result<()> {
-	let output = output.join(format!("{BINARY_NAME}.1"));
+/// Handles the `plan` command processing.
+fn handle_command_plan(
+	opt::Plan {
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
+		target,
+		format,
+		emit_script,
+		force,
+	}: opt::Plan,
+) -> Result<()> {
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+	let mut profile = setup_profile(&profile_name, &ptf_src, target, &vars)?;
 
-	let man = clap_mangen::Man::new(opt::Opts::command());
-	let mut buffer: Vec<u8> = Default::default();
-	man.render(&mut buffer)?;
+	if profile.target_path().is_none() {
+		return Err(eyre!(
+			"No target path for the deployment set. Either use the command line argument \
+			 `-t/--target`, the profile attribute `target` or the environment variable \
+			 `{PUNKTF_TARGET_ENVVAR}`"
+		));
+	}
+
+	tracing::debug!("Profile:\n{:#?}", profile);
+	tracing::debug!("Source: {}", ptf_src.root().display());
+	tracing::debug!("Target: {:?}", profile.target_path());
+
+	setup_env(&ptf_src, &profile, &profile_name);
+
+	let plan = Planner::new()
+		.with_contents(emit_script.is_some())
+		.with_force(force)
+		.plan(&ptf_src, &mut profile);
+
+	match format {
+		opt::PlanFormat::Tree => plan::print_tree(&plan),
+		opt::PlanFormat::Json => println!("{}", serde_json::to_string_pretty(&plan)?),
+	}
+
+	if let Some(output) = emit_script {
+		script::write(&plan, &profile_name, &output)?;
+
+		if format == opt::PlanFormat::Tree {
+			println!("Wrote deployment scripts to {}", output.display());
+		}
+	}
+
+	if plan.has_errors() {
+		Err(eyre!("Some dotfiles failed to plan"))
+	} else {
+		Ok(())
+	}
+}
+
+/// Handles the `check` command processing.
+///
+/// Exits the process with the exit code `1` if any drift was detected.
+fn handle_command_check(
+	opt::Check {
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
+		target,
+		format,
+	}: opt::Check,
+) -> Result<()> {
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+	let mut profile = setup_profile(&profile_name, &ptf_src, target, &vars)?;
+
+	if profile.target_path().is_none() {
+		return Err(eyre!(
+			"No target path for the deployment set. Either use the command line argument \
+			 `-t/--target`, the profile attribute `target` or the environment variable \
+			 `{PUNKTF_TARGET_ENVVAR}`"
+		));
+	}
 
-	std::fs::write(output, buffer)?;
+	tracing::debug!("Profile:\n{:#?}", profile);
+	tracing::debug!("Source: {}", ptf_src.root().display());
+	tracing::debug!("Target: {:?}", profile.target_path());
+
+	setup_env(&ptf_src, &profile, &profile_name);
+
+	let plan = Planner::new().plan(&ptf_src, &mut profile);
+	let summary = plan::CheckSummary::new(&profile_name, &plan);
+
+	match format {
+		opt::CheckFormat::Json => println!("{}", serde_json::to_string_pretty(&summary)?),
+		opt::CheckFormat::Text => summary.print_text(),
+	}
+
+	if summary.drift {
+		std::process::exit(1);
+	}
 
 	Ok(())
 }
 
-/// Handles the `completions` command processing.
-fn handle_command_completions(opt::Completions { shell, output }: opt::Completions) -> Result<()> {
-	clap_complete::generate_to(shell, &mut opt::Opts::command(), BINARY_NAME, output)?;
+/// Handles the `test` command processing.
+fn handle_command_test(
+	opt::Test {
+		source,
+		conflict_policy,
+		cases,
+		accept,
+		in_container,
+		profile,
+		runtime,
+		binary,
+	}: opt::Test,
+) -> Result<()> {
+	if !in_container.is_empty() {
+		let options = container::ContainerOptions {
+			runtime,
+			images: in_container,
+			profiles: profile,
+			sources: source,
+			conflict_policy,
+			binary,
+		};
+
+		if !container::run(&options)? {
+			std::process::exit(1);
+		}
+
+		return Ok(());
+	}
+
+	let ptf_src = setup_source(source, conflict_policy)?;
+
+	tracing::debug!("Source: {}", ptf_src.root().display());
+
+	if !snapshot::run(&ptf_src, &cases, accept)? {
+		std::process::exit(1);
+	}
+
+	Ok(())
+}
+
+/// Handles the `watch` command processing.
+fn handle_command_watch(
+	opt::Watch {
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
+		target,
+		debounce,
+		notify,
+		watch_targets,
+	}: opt::Watch,
+	quiet: bool,
+) -> Result<()> {
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+
+	watch::run(
+		&ptf_src,
+		&watch::WatchOptions {
+			profile_name,
+			target,
+			vars,
+			debounce: Duration::from_millis(debounce),
+			notify,
+			quiet,
+			drift: watch_targets,
+		},
+	)
+}
+
+/// Handles the `ui` command processing.
+fn handle_command_ui(
+	opt::Ui {
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
+	}: opt::Ui,
+) -> Result<()> {
+	let ptf_src = setup_source(source, conflict_policy)?;
+
+	ui::run(ptf_src, profile_name, vars)
+}
+
+/// Handles the `bootstrap` command processing.
+fn handle_command_bootstrap(
+	opt::Bootstrap {
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars: _,
+			},
+		output,
+		kind,
+		repo,
+		dir,
+	}: opt::Bootstrap,
+) -> Result<()> {
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+
+	let kind = match kind {
+		Some(kind) => kind,
+		None => bootstrap::script_kind(&output)?,
+	};
+
+	let script =
+		bootstrap::Bootstrap::from_source(ptf_src.root(), repo, profile_name, dir)?.render(kind);
+
+	std::fs::write(&output, script)
+		.wrap_err_with(|| format!("Failed to write {}", output.display()))?;
+
+	#[cfg(unix)]
+	if kind == opt::ScriptKind::Sh {
+		use std::os::unix::fs::PermissionsExt as _;
+		std::fs::set_permissions(&output, std::fs::Permissions::from_mode(0o755))?;
+	}
+
+	println!("Wrote bootstrap script to {}", output.display());
 
 	Ok(())
 }
+
+/// Handles the `history` command processing.
+fn handle_command_history(
+	opt::History {
+		command,
+		profile,
+		limit,
+	}: opt::History,
+) -> Result<()> {
+	let history = history::History::open_default(&config::Config::load()?)?;
+
+	match command {
+		None => history::print_list(&history.list(profile.as_deref(), limit)?),
+		Some(opt::HistoryCommand::Show(opt::HistoryShow { id })) => {
+			history::print_record(&history.get(id)?, &history.report(id)?);
+		}
+		Some(opt::HistoryCommand::Diff(opt::HistoryDiff { old, new })) => {
+			let diff = history.report(old)?.diff(&history.report(new)?);
+
+			history::print_diff(
+				&history.get(old)?,
+				&history.get(new)?,
+				&diff,
+				&history.hashes(old)?,
+				&history.hashes(new)?,
+			);
+		}
+	}
+
+	Ok(())
+}
+
+/// Handles the `import` command processing.
+fn handle_command_import(opt::Import { command }: opt::Import) -> Result<()> {
+	let (destination, profiles) = match command {
+		opt::ImportCommand::Stow(opt::ImportStow {
+			shared,
+			dir,
+			packages,
+			target,
+			dotfiles,
+		}) => {
+			let destination = import::Destination::create(shared.source, shared.force)?;
+			let profile = import::stow::import(
+				&destination,
+				import::stow::StowOptions {
+					dir,
+					packages,
+					target,
+					dotfiles,
+				},
+			)?;
+
+			(destination, vec![(shared.profile, profile)])
+		}
+		opt::ImportCommand::Chezmoi(opt::ImportChezmoi {
+			shared,
+			dir,
+			target,
+		}) => {
+			let destination = import::Destination::create(shared.source, shared.force)?;
+			let profile = import::chezmoi::import(
+				&destination,
+				import::chezmoi::ChezmoiOptions { dir, target },
+			)?;
+
+			(destination, vec![(shared.profile, profile)])
+		}
+		opt::ImportCommand::Dotbot(opt::ImportDotbot {
+			shared,
+			config,
+			base_dir,
+			target,
+		}) => {
+			let destination = import::Destination::create(shared.source, shared.force)?;
+			let profile = import::dotbot::import(
+				&destination,
+				import::dotbot::DotbotOptions {
+					config,
+					base_dir,
+					target,
+				},
+			)?;
+
+			(destination, vec![(shared.profile, profile)])
+		}
+		opt::ImportCommand::Yadm(opt::ImportYadm {
+			shared,
+			repo,
+			home,
+			target,
+		}) => {
+			let destination = import::Destination::create(shared.source, shared.force)?;
+			let profiles = import::yadm::import(
+				&destination,
+				&shared.profile,
+				import::yadm::YadmOptions { repo, home, target },
+			)?;
+
+			(destination, profiles)
+		}
+	};
+
+	for (name, profile) in profiles {
+		let path = destination.write_profile(&name, &profile)?;
+		println!("Wrote profile {name} to {}", path.display());
+	}
+
+	let attention = destination.attention_count();
+	if attention > 0 {
+		println!(
+			"{}",
+			console::style(format!("{attention} item(s) need manual attention")).magenta()
+		);
+	}
+
+	Ok(())
+}
+
+/// Handles the `export` command processing.
+fn handle_command_export(opt::Export { command }: opt::Export) -> Result<()> {
+	let opt::ExportCommand::Chezmoi(opt::ExportChezmoi {
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
+		dir,
+		force,
+	}) = command;
+
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+	let mut profile = setup_profile(&profile_name, &ptf_src, None, &vars)?;
+
+	setup_env(&ptf_src, &profile, &profile_name);
+
+	let attention = export::chezmoi::export(
+		&ptf_src,
+		&mut profile,
+		export::chezmoi::ChezmoiOptions {
+			dir: dir.clone(),
+			force,
+		},
+	)?;
+
+	println!("Exported profile {profile_name} to {}", dir.display());
+
+	if attention > 0 {
+		println!(
+			"{}",
+			console::style(format!("{attention} item(s) need manual attention")).magenta()
+		);
+	}
+
+	Ok(())
+}
+
+/// Handles the `grep` command processing.
+fn handle_command_grep(
+	opt::Grep {
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
+		pattern,
+		rendered,
+		ignore_case,
+	}: opt::Grep,
+) -> Result<()> {
+	let regex = regex::RegexBuilder::new(&pattern)
+		.case_insensitive(ignore_case)
+		.build()
+		.wrap_err_with(|| format!("Invalid pattern `{pattern}`"))?;
+
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+	let mut profile = setup_profile(&profile_name, &ptf_src, None, &vars)?;
+
+	setup_env(&ptf_src, &profile, &profile_name);
+
+	if grep::run(&ptf_src, &mut profile, &regex, rendered)? == 0 {
+		Err(eyre!("No dotfile matches `{pattern}`"))
+	} else {
+		Ok(())
+	}
+}
+
+/// Handles the `lint` command processing.
+fn handle_command_lint(
+	opt::Lint {
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
+	}: opt::Lint,
+) -> Result<()> {
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+	let mut profile = setup_profile(&profile_name, &ptf_src, None, &vars)?;
+
+	setup_env(&ptf_src, &profile, &profile_name);
+
+	match lint::run(&ptf_src, &mut profile)? {
+		0 => Ok(()),
+		problems => Err(eyre!("Found {problems} problems in the templates")),
+	}
+}
+
+/// Handles the `bench` command processing.
+fn handle_command_bench(
+	opt::Bench {
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
+		iterations,
+		slowest,
+	}: opt::Bench,
+) -> Result<()> {
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+
+	let profile = setup_profile(&profile_name, &ptf_src, None, &vars)?;
+
+	if profile.target_path().is_none() {
+		return Err(eyre!(
+			"No target path for the benchmark set. Either use the profile attribute `target` \
+			 or the environment variable `{PUNKTF_TARGET_ENVVAR}`"
+		));
+	}
+
+	setup_env(&ptf_src, &profile, &profile_name);
+
+	bench::run(
+		&ptf_src,
+		|| setup_profile(&profile_name, &ptf_src, None, &vars),
+		iterations,
+		slowest,
+	)
+}
+
+/// Handles the `which` command processing.
+fn handle_command_which(
+	opt::Which {
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
+		path,
+	}: opt::Which,
+) -> Result<()> {
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+	let mut profile = setup_profile(&profile_name, &ptf_src, None, &vars)?;
+
+	setup_env(&ptf_src, &profile, &profile_name);
+
+	which::run(&ptf_src, &mut profile, &path)
+}
+
+/// Handles the `vars` command processing.
+fn handle_command_vars(
+	opt::Vars {
+		shared:
+			opt::RepoShared {
+				source,
+				profile: profile_name,
+				conflict_policy,
+				vars,
+			},
+	}: opt::Vars,
+) -> Result<()> {
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+	let profile = setup_profile(&profile_name, &ptf_src, None, &vars)?;
+
+	if vars::run(&profile, &profile_name) == 0 {
+		println!("Profile {profile_name} defines no variables");
+	}
+
+	Ok(())
+}
+
+/// Creates the source and resolves the profile given by the `shared`
+/// arguments.
+fn setup_repo(
+	opt::RepoShared {
+		source,
+		profile: profile_name,
+		conflict_policy,
+		vars,
+	}: opt::RepoShared,
+) -> Result<(PunktfSource, LayeredProfile)> {
+	let ptf_src = setup_source(source, conflict_policy)?;
+	let profile_name = get_profile_name(profile_name, &ptf_src)?;
+	let profile = setup_profile(&profile_name, &ptf_src, None, &vars)?;
+
+	Ok((ptf_src, profile))
+}
+
+/// Handles the `new-profile` command processing.
+fn handle_command_new_profile(opt::NewProfile { source, name }: opt::NewProfile) -> Result<()> {
+	let path = new_profile::run(source, name)?;
+
+	println!("Created profile {}", path.display());
+
+	Ok(())
+}
+
+/// Handles the `schema` command processing.
+fn handle_command_schema(opt::Schema { output, report }: opt::Schema) -> Result<()> {
+	let schema = if report {
+		deployment::Deployment::json_schema()
+	} else {
+		Profile::json_schema()
+	};
+	let schema = serde_json::to_string_pretty(&schema).wrap_err("Failed to serialize schema")?;
+
+	match output {
+		Some(output) => std::fs::write(&output, schema)
+			.wrap_err_with(|| format!("Failed to write {}", output.display())),
+		None => {
+			println!("{schema}");
+			Ok(())
+		}
+	}
+}
+
+/// Handles the `gen-man` command processing.
+fn handle_command_man(opt::Man { dir, output }: opt::Man) -> Result<()> {
+	let output = dir.or(output).unwrap_or_else(|| PathBuf::from("."));
+
+	std::fs::create_dir_all(&output)
+		.wrap_err_with(|| format!("Failed to create directory {}", output.display()))?;
+
+	clap_mangen::generate_to(opt::Opts::command(), &output)
+		.wrap_err_with(|| format!("Failed to write man pages to {}", output.display()))
+}
+
+/// Handles the `completions` command processing.
+fn handle_command_completions(opt::Completions { shell, output }: opt::Completions) -> Result<()> {
+	completions::generate(shell, &output)
+}
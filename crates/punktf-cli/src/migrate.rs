@@ -0,0 +1,77 @@
+//! Migration of all profiles of a source to the current profile format
+//! version (`punktf migrate`).
+
+use std::path::Path;
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use console::style;
+use punktf_lib::profile::migrate::{self, Migrated};
+use punktf_lib::profile::PROFILE_VERSION;
+
+use crate::fmt::Format;
+
+/// Parses, migrates and serializes a single profile `content`.
+fn migrate_content(content: &str, format: Format) -> Result<(Migrated, String)> {
+	let migrated = migrate::migrate(format.parse(content)?)?;
+	let content = format.serialize(&migrated.profile)?;
+
+	Ok((migrated, content))
+}
+
+/// Migrates all profiles inside of the `profiles` directory of `source`.
+///
+/// Only profiles with an older format version are rewritten. If `dry_run` is
+/// set, the changes are only printed.
+///
+/// # Errors
+///
+/// An error is returned if the `profiles` directory could not be read or if
+/// any profile could not be migrated or written.
+pub fn run(source: &Path, dry_run: bool) -> Result<()> {
+	let mut failed = 0;
+
+	for (path, format) in crate::fmt::profile_files(source)? {
+		let content = std::fs::read_to_string(&path)
+			.wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+
+		let (migrated, content) = match migrate_content(&content, format) {
+			Ok(migrated) => migrated,
+			Err(err) => {
+				println!("{} {} ({err:#})", style("failed").red(), path.display());
+				failed += 1;
+				continue;
+			}
+		};
+
+		if migrated.is_current() {
+			println!("{} {}", style("current").dim(), path.display());
+			continue;
+		}
+
+		println!(
+			"{} {} (version {} -> {PROFILE_VERSION})",
+			style("migrated").green(),
+			path.display(),
+			migrated.from
+		);
+		for change in &migrated.changes {
+			println!("  {change}");
+		}
+
+		if !dry_run {
+			std::fs::write(&path, content)
+				.wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+		}
+	}
+
+	if dry_run {
+		tracing::info!("Note: No profiles were actually written, since dry run mode was enabled");
+	}
+
+	if failed > 0 {
+		Err(eyre!("{failed} profile(s) failed to migrate"))
+	} else {
+		Ok(())
+	}
+}
@@ -0,0 +1,211 @@
+//! Interactive creation of a new profile (`punktf new-profile`).
+//!
+//! Asks for the target path, the operating system, the profiles to extend and
+//! the dotfiles of the profile. Dotfiles are either picked from the `dotfiles`
+//! directory of the source or found by scanning a directory (e.g. the home
+//! directory), in which case they are copied into the source.
+
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::{Confirm, Input, MultiSelect, Select};
+use punktf_lib::profile::variables::Variables;
+use punktf_lib::profile::{Profile, PROFILE_VERSION};
+
+use crate::import::{self, Destination};
+
+/// Operating systems which can be selected for a profile.
+const OPERATING_SYSTEMS: &[&str] = &["any", "linux", "macos", "windows"];
+
+/// Returns the sorted names of all entries of the directory `dir`.
+///
+/// Returns an empty list if the directory does not exist.
+fn dir_entries(dir: &Path) -> Result<Vec<String>> {
+	if !dir.is_dir() {
+		return Ok(Vec::new());
+	}
+
+	let mut entries = std::fs::read_dir(dir)
+		.wrap_err_with(|| format!("Failed to read directory {}", dir.display()))?
+		.map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+		.collect::<Result<Vec<_>, _>>()?;
+	entries.sort();
+
+	Ok(entries)
+}
+
+/// Expands a leading `~` of `path` to the home directory.
+fn expand_home(path: &str) -> PathBuf {
+	match (path.strip_prefix('~'), dirs::home_dir()) {
+		(Some(rest), Some(home)) => home.join(rest.trim_start_matches(['/', '\\'])),
+		_ => PathBuf::from(path),
+	}
+}
+
+/// Asks for a directory to scan and copies the selected hidden entries of it,
+/// which are not yet part of the `dotfiles` directory, into the source.
+///
+/// Returns the names of the copied entries.
+fn scan(
+	theme: &ColorfulTheme,
+	destination: &Destination,
+	target: &str,
+	existing: &[String],
+) -> Result<Vec<String>> {
+	let dir: String = Input::with_theme(theme)
+		.with_prompt("Directory to scan")
+		.default(String::from(target))
+		.interact_text()?;
+	let dir = expand_home(&dir);
+
+	let candidates = dir_entries(&dir)?
+		.into_iter()
+		.filter(|name| name.starts_with('.') && !existing.contains(name))
+		.collect::<Vec<_>>();
+
+	if candidates.is_empty() {
+		println!("No new dotfiles found in {}", dir.display());
+		return Ok(Vec::new());
+	}
+
+	let selected = MultiSelect::with_theme(theme)
+		.with_prompt("Dotfiles to copy into the source")
+		.items(&candidates)
+		.interact()?;
+
+	let mut copied = Vec::with_capacity(selected.len());
+
+	for name in selected.into_iter().map(|idx| candidates[idx].clone()) {
+		let from = dir.join(&name);
+		let relative = Path::new(&name);
+
+		if from.is_dir() {
+			destination.copy_dir(&from, relative)?;
+		} else {
+			destination.copy_file(&from, relative)?;
+		}
+
+		import::print_imported(&from, &destination.dotfile(relative));
+		copied.push(name);
+	}
+
+	Ok(copied)
+}
+
+/// Creates a new profile in the `source` directory by asking the user for all
+/// settings and returns the path of the written profile.
+///
+/// # Errors
+///
+/// An error is returned if the terminal is not interactive, if a profile with
+/// the name already exists or if the profile could not be written.
+pub fn run(source: PathBuf, name: Option<String>) -> Result<PathBuf> {
+	if !crate::util::is_interactive() {
+		return Err(eyre!("Creating a profile requires an interactive terminal"));
+	}
+
+	let theme = ColorfulTheme::default();
+	let destination = Destination::create(source.clone(), false)?;
+
+	let profiles = crate::fmt::profile_files(&source)?
+		.into_iter()
+		.filter_map(|(path, _)| {
+			path.file_stem()
+				.and_then(|stem| stem.to_str())
+				.map(String::from)
+		})
+		.collect::<Vec<_>>();
+
+	let name = match name {
+		Some(name) => name,
+		None => Input::with_theme(&theme)
+			.with_prompt("Name of the profile")
+			.validate_with(|name: &String| {
+				if name.is_empty() || name.contains(['/', '\\', '.']) {
+					Err("The name must not be empty or contain `/`, `\\` or `.`")
+				} else if profiles.contains(name) {
+					Err("A profile with this name already exists")
+				} else {
+					Ok(())
+				}
+			})
+			.interact_text()?,
+	};
+
+	if profiles.contains(&name) {
+		return Err(eyre!("A profile with the name `{name}` already exists"));
+	}
+
+	let description: String = Input::with_theme(&theme)
+		.with_prompt("Description")
+		.allow_empty(true)
+		.interact_text()?;
+
+	let target: String = Input::with_theme(&theme)
+		.with_prompt("Target path")
+		.default(String::from("~"))
+		.interact_text()?;
+
+	let os = Select::with_theme(&theme)
+		.with_prompt("Operating system")
+		.items(OPERATING_SYSTEMS)
+		.default(
+			OPERATING_SYSTEMS
+				.iter()
+				.position(|os| *os == std::env::consts::OS)
+				.unwrap_or_default(),
+		)
+		.interact()?;
+
+	let extends = if profiles.is_empty() {
+		Vec::new()
+	} else {
+		MultiSelect::with_theme(&theme)
+			.with_prompt("Profiles to extend")
+			.items(&profiles)
+			.interact()?
+			.into_iter()
+			.map(|idx| profiles[idx].clone())
+			.collect()
+	};
+
+	let existing = dir_entries(&destination.dotfile(Path::new("")))?;
+	let mut dotfiles = if existing.is_empty() {
+		Vec::new()
+	} else {
+		MultiSelect::with_theme(&theme)
+			.with_prompt("Dotfiles of the source to add")
+			.items(&existing)
+			.interact()?
+			.into_iter()
+			.map(|idx| import::dotfile(PathBuf::from(&existing[idx]), true))
+			.collect()
+	};
+
+	if Confirm::with_theme(&theme)
+		.with_prompt("Scan a directory for dotfiles which are not yet in the source?")
+		.default(existing.is_empty())
+		.interact()?
+	{
+		// Copied dotfiles were not written with punktf templates in mind
+		dotfiles.extend(
+			scan(&theme, &destination, &target, &existing)?
+				.into_iter()
+				.map(|name| import::dotfile(PathBuf::from(name), false)),
+		);
+	}
+
+	let profile = Profile {
+		version: Some(PROFILE_VERSION),
+		extends,
+		description: (!description.is_empty()).then_some(description),
+		variables: (os != 0).then(|| Variables::from_items([("os", OPERATING_SYSTEMS[os])])),
+		target: Some(PathBuf::from(target)),
+		dotfiles,
+		..Default::default()
+	};
+
+	destination.write_profile(&name, &profile)
+}
@@ -0,0 +1,34 @@
+//! Desktop notifications (Windows toast, libnotify and macOS notifications).
+
+/// Shows a desktop notification with the given `summary` and `body`.
+///
+/// If `failure` is set, the notification is marked as critical where
+/// supported. Errors while showing the notification are only logged.
+#[cfg(feature = "notifications")]
+pub fn send(summary: &str, body: &str, failure: bool) {
+	let mut notification = notify_rust::Notification::new();
+	notification.appname("punktf").summary(summary).body(body);
+
+	#[cfg(all(unix, not(target_os = "macos")))]
+	if failure {
+		notification.urgency(notify_rust::Urgency::Critical);
+	}
+
+	#[cfg(not(all(unix, not(target_os = "macos"))))]
+	let _ = failure;
+
+	if let Err(err) = notification.show() {
+		tracing::warn!("Failed to show desktop notification: {err}");
+	}
+}
+
+/// Shows a desktop notification with the given `summary` and `body`.
+///
+/// As `punktf` was built without the `notifications` feature, only a warning
+/// is logged.
+#[cfg(not(feature = "notifications"))]
+pub fn send(summary: &str, body: &str, _failure: bool) {
+	tracing::warn!(
+		"punktf was built without support for desktop notifications ({summary}: {body})"
+	);
+}
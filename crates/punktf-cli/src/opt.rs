@@ -13,6 +13,8 @@ use std::path::PathBuf;
 
 use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
 use clap_complete::Shell;
+use punktf_lib::profile::source::ConflictPolicy;
+use punktf_lib::profile::MergeMode;
 
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
@@ -29,7 +31,7 @@ pub struct Opts {
 	group(
 		ArgGroup::new("verbosity")
 			.required(false)
-			.args(["verbose", "quite"]),
+			.args(["verbose", "quiet"]),
 	)
 )]
 pub struct Shared {
@@ -43,11 +45,69 @@ pub struct Shared {
 	#[arg(short, long, action = clap::ArgAction::Count)]
 	pub verbose: u8,
 
-	/// Quite mode
+	/// Quiet mode
 	///
-	/// Will only print errors
-	#[arg(short, long)]
-	pub quite: bool,
+	/// Will only print errors and, for deployments, a short summary at the end
+	/// (deployed/skipped/failed counts, duration and all failures).
+	#[arg(short, long, alias = "quite")]
+	pub quiet: bool,
+
+	/// Format of the log messages printed to stderr.
+	#[arg(value_enum, long, default_value_t = LogFormat::Text)]
+	pub log_format: LogFormat,
+
+	/// Writes all log messages (down to the `trace` level) to the given file,
+	/// regardless of the verbosity.
+	///
+	/// The file is rotated once it exceeds 10 MiB, keeping three old files.
+	/// Overwrites the `log_file` of the global configuration.
+	#[arg(long, value_name = "PATH")]
+	pub log_file: Option<PathBuf>,
+
+	/// Controls when colored output is used.
+	#[arg(value_enum, long, default_value_t = ColorChoice::Auto)]
+	pub color: ColorChoice,
+
+	/// Freezes the time returned by the `now` and `today` template functions
+	/// (e.g. to keep snapshot tests deterministic).
+	///
+	/// The time is either a RFC 3339 timestamp (e.g. `2024-01-31T12:00:00Z`)
+	/// or the seconds since the unix epoch. It can also be set with the
+	/// `PUNKTF_FROZEN_TIME` environment variable.
+	#[arg(long, global = true, value_name = "TIME", value_parser = parse_frozen_time)]
+	pub frozen_time: Option<String>,
+}
+
+/// Validates a time given to `--frozen-time`.
+fn parse_frozen_time(time: &str) -> Result<String, String> {
+	punktf_lib::expr::parse_time(time).map(|_| time.to_owned())
+}
+
+/// When to use colored output.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ColorChoice {
+	/// Use colors if the output is a terminal (respects `NO_COLOR` and
+	/// `CLICOLOR_FORCE`).
+	#[default]
+	Auto,
+
+	/// Always use colors.
+	Always,
+
+	/// Never use colors.
+	Never,
+}
+
+/// Format of the log messages.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+	/// Human readable text.
+	#[default]
+	Text,
+
+	/// One json object per line and event (`timestamp`, `level`, `target`,
+	/// `message` and, for deployed items, `path` and `action`).
+	Json,
 }
 
 #[derive(Debug, Args)]
@@ -59,30 +119,101 @@ pub struct OutputShared {
 	/// Writes the deployment status as yaml to the given path.
 	#[arg(long)]
 	pub yaml_output: Option<PathBuf>,
+
+	/// Writes a report of the deployment as standalone html page to the given
+	/// path.
+	///
+	/// The report contains a summary, the status of each item and collapsible
+	/// diffs of all changed files. Not supported for dry runs.
+	#[arg(long)]
+	pub html_output: Option<PathBuf>,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum Command {
 	Deploy(Deploy),
 	Render(Render),
+	DebugTemplate(DebugTemplate),
+	Edit(Edit),
 	Verify(Verify),
 	Diff(Diff),
+	Plan(Plan),
+	Check(Check),
+	Test(Test),
+	Ui(Ui),
+	Watch(Watch),
+	Bootstrap(Bootstrap),
+	History(History),
+	Import(Import),
+	Export(Export),
+	Schema(Schema),
+	Migrate(Migrate),
+	Fmt(Fmt),
+	Grep(Grep),
+	Lint(Lint),
+	Bench(Bench),
+	Which(Which),
+	Vars(Vars),
+	Encrypt(Encrypt),
+	Decrypt(Decrypt),
+	Reencrypt(Reencrypt),
+	Answers(Answers),
+	Secret(Secret),
+	NewProfile(NewProfile),
+	#[command(name = "gen-man", alias = "man")]
 	Man(Man),
 	Completions(Completions),
+	#[cfg(feature = "self-update")]
+	SelfUpdate(SelfUpdate),
 }
 
 #[derive(Debug, Args)]
 pub struct RepoShared {
 	/// The source directory where the profiles and dotfiles are located.
-	#[arg(short, long, env = super::PUNKTF_SOURCE_ENVVAR)]
-	pub source: PathBuf,
+	///
+	/// Can be given multiple times to layer multiple source directories on top
+	/// of each other (e.g. `--source work --source personal`). The `profiles`
+	/// and `dotfiles` directories of all sources are overlaid, where the first
+	/// given source has the highest precedence.
+	///
+	/// A priority for the `priority` conflict policy can be appended to a source
+	/// with `@` (e.g. `--source work@10`).
+	#[arg(short, long, env = super::PUNKTF_SOURCE_ENVVAR, required = true)]
+	pub source: Vec<PathBuf>,
+
+	/// Defines what happens if the same dotfile exists in multiple sources.
+	///
+	/// Possible values are `error`, `first-wins`, `last-wins` and `priority`.
+	/// Directories are never a conflict, as their contents are overlaid.
+	#[arg(long, default_value_t = ConflictPolicy::FirstWins)]
+	pub conflict_policy: ConflictPolicy,
 
 	/// Name of the profile to deploy.
 	///
 	/// The name should be the file name of the profile without an extension (e.g.
 	/// `profiles/arch.json` should be given as `arch`).
+	///
+	/// If no profile is given, the `default_profile` of the config file is used.
+	/// If that is not set either and the shell is interactive, a list of all
+	/// available profiles is presented to select from.
 	#[arg(short, long, env = super::PUNKTF_PROFILE_ENVVAR)]
-	pub profile: String,
+	pub profile: Option<String>,
+
+	/// Sets a template variable (e.g. `--var editor=nvim`).
+	///
+	/// Can be given multiple times. Variables given on the command line take
+	/// precedence over variables set with `PUNKTF_VAR_<NAME>` environment
+	/// variables, which take precedence over the variables of all profiles.
+	#[arg(long = "var", value_name = "NAME=VALUE", value_parser = parse_var)]
+	pub vars: Vec<(String, String)>,
+}
+
+/// Parses a variable given as `NAME=VALUE`.
+fn parse_var(var: &str) -> Result<(String, String), String> {
+	match var.split_once('=') {
+		Some((name, value)) if !name.is_empty() => Ok((name.into(), value.into())),
+		_ => Err(format!("expected `NAME=VALUE`, got `{var}`")),
+	}
 }
 
 /// Deploys a profile.
@@ -100,11 +231,130 @@ pub struct Deploy {
 
 	/// Deploys the profile but without actually coping/creating the files.
 	///
-	/// This can be used to test and get an overview over the changes which would
-	/// be applied when run without this flag.
+	/// Instead a plan of all targets and the actions which would be taken is
+	/// printed. This can be used to test and get an overview over the changes
+	/// which would be applied when run without this flag. No hooks are executed.
+	///
+	/// When used together with `--json-output`/`--yaml-output`, the plan is
+	/// written to the output files instead of the deployment status.
 	#[arg(short, long)]
 	pub dry_run: bool,
 
+	/// Deploys the profile into a sandbox directory instead of the real target.
+	///
+	/// All target paths are moved below the given directory (or a new
+	/// temporary directory) while keeping their structure (e.g.
+	/// `/home/demo/.bashrc` is deployed to `<dir>/home/demo/.bashrc`). Hooks
+	/// are not executed but only printed. The resulting tree is printed after
+	/// the deployment and the deployment is not recorded in the history.
+	#[arg(long, value_name = "DIR", num_args = 0..=1, conflicts_with = "dry_run")]
+	pub sandbox: Option<Option<PathBuf>>,
+
+	/// Only deploys dotfiles whose source path matches the given path prefix or
+	/// glob.
+	///
+	/// Paths are relative to the `dotfiles` directory (e.g. `--only nvim --only
+	/// zsh/.zshrc`). Can be given multiple times. When set, hooks and links of
+	/// the profile are not processed.
+	#[arg(long, value_name = "PATH|GLOB")]
+	pub only: Vec<String>,
+
+	/// Skips dotfiles whose source path matches the given path prefix or glob.
+	///
+	/// Paths are relative to the `dotfiles` directory (e.g. `--exclude nvim/lua
+	/// --exclude '**/*.bak'`). Can be given multiple times and takes precedence
+	/// over `--only`. Excluded items are skipped before any template is
+	/// resolved.
+	#[arg(long, value_name = "PATH|GLOB")]
+	pub exclude: Vec<String>,
+
+	/// Only deploys dotfiles which changed since the given git revision.
+	///
+	/// Uses git to find all changed (including uncommitted and untracked) files
+	/// of the source directories and deploys only the dotfiles which contain
+	/// them (e.g. `--since HEAD~3` or `--since origin/main`). If a profile
+	/// changed, all dotfiles are deployed. When set, hooks and links of the
+	/// profile are not processed.
+	#[arg(long, value_name = "REV", conflicts_with = "only")]
+	pub since: Option<String>,
+
+	/// Uses the given merge mode for all dotfiles instead of the ones defined
+	/// by the profile.
+	///
+	/// Possible values are `overwrite`, `keep` and `ask`.
+	#[arg(long, value_name = "MODE")]
+	pub merge: Option<MergeMode>,
+
+	/// Copies existing files into the given directory before they are
+	/// overwritten.
+	///
+	/// The backups keep the structure of the target paths (e.g.
+	/// `/home/demo/.bashrc` is copied to `<DIR>/home/demo/.bashrc`).
+	#[arg(long, value_name = "DIR", conflicts_with = "dry_run")]
+	pub backup_dir: Option<PathBuf>,
+
+	/// Does not write files whose content would not change.
+	///
+	/// This keeps the modification time of unchanged files.
+	#[arg(long)]
+	pub skip_unchanged: bool,
+
+	/// Does not execute the pre- and post-hooks of the profile.
+	#[arg(long)]
+	pub no_hooks: bool,
+
+	/// Does not compile templates whose inputs did not change since their last
+	/// deployment.
+	///
+	/// The cache stores hashes of the inputs (template, variables, content
+	/// transformers and referenced environment variables) and of the deployed
+	/// content of each template. A template is compiled again if any input
+	/// changed or if its target was modified. The cache is stored in the given
+	/// file or by default in `<cache dir>/punktf/deploy-cache`.
+	#[arg(long, value_name = "FILE", num_args = 0..=1, conflicts_with = "dry_run")]
+	pub cache: Option<Option<PathBuf>>,
+
+	/// Considers copied files (`template: false`) as unchanged if the size and
+	/// modification time of their source and target did not change since the
+	/// last deployment.
+	///
+	/// This avoids reading large files (e.g. fonts or wallpapers), but misses
+	/// changes which keep both the size and the modification time. The sizes
+	/// and modification times are stored in the cache. Combine it with
+	/// `--skip-unchanged` to also not write unchanged files.
+	#[arg(long, requires = "cache")]
+	pub fast_compare: bool,
+
+	/// Overwrites existing files which are write protected (read-only or
+	/// immutable).
+	///
+	/// The protection is removed before the file is overwritten. Without this
+	/// flag, such files are skipped unless the dotfile sets `force: true`.
+	#[arg(long)]
+	pub force: bool,
+
+	/// Deploys without asking for confirmation.
+	///
+	/// Before a deployment which overwrites existing files, a summary of all
+	/// actions is printed and a confirmation is requested. This is skipped in
+	/// non-interactive sessions, for sandboxes or if `assume_yes` is set in
+	/// the config.
+	#[arg(short, long)]
+	pub yes: bool,
+
+	/// File with answers to the prompt variables of the profile.
+	///
+	/// The file maps variable names to values (e.g. `email: demo@example.com`).
+	/// The values are used instead of asking for the prompt variables and take
+	/// precedence over stored answers. They are not stored.
+	#[arg(long, value_name = "FILE", env = super::PUNKTF_ANSWERS_ENVVAR)]
+	pub answers: Option<PathBuf>,
+
+	/// Prints the slowest dotfiles together with their compile time, write time
+	/// and the amount of bytes written after the deployment.
+	#[arg(long)]
+	pub timings: bool,
+
 	#[command(flatten)]
 	pub output: OutputShared,
 }
@@ -124,6 +374,56 @@ pub struct Render {
 	pub dotfile: PathBuf,
 }
 
+/// Prints the parsed blocks of a template dotfile.
+///
+/// Each block is printed on its own line together with its location (e.g.
+/// `3:1 if "{{@if {{OS}} == \"linux\"}}"`), blocks inside of `if` and `macro`
+/// blocks are indented. This helps to find out why a template does not render
+/// as expected.
+#[derive(Debug, Parser)]
+pub struct DebugTemplate {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Dotfile to debug.
+	///
+	/// Relative path starting from the `dotfiles` directory.
+	pub dotfile: PathBuf,
+
+	/// Also resolves the template and prints each decision made on the way.
+	///
+	/// These are the values of all variables, the results of the conditions of
+	/// `if` blocks, the values of inline expressions and all rendered templates
+	/// and called macros.
+	#[arg(long)]
+	pub trace: bool,
+}
+
+/// Opens a dotfile in the editor and redeploys it afterwards.
+///
+/// The dotfile is opened in the editor given by the environment variables
+/// `VISUAL` or `EDITOR`. Once the editor exits, only this dotfile is deployed
+/// again (hooks and links are not processed). The merge mode of the dotfile is
+/// honored. Nothing is deployed if the dotfile was not changed.
+#[derive(Debug, Parser)]
+pub struct Edit {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Alternative deployment target path.
+	#[arg(short, long)]
+	pub target: Option<PathBuf>,
+
+	/// Dotfile to edit.
+	///
+	/// Relative path starting from the `dotfiles` directory.
+	pub dotfile: PathBuf,
+
+	/// Only opens the dotfile without deploying it afterwards.
+	#[arg(long)]
+	pub no_deploy: bool,
+}
+
 /// Verifies a profile.
 ///
 /// This includes checking and resolving templates, running hooks.
@@ -170,15 +470,830 @@ pub struct Diff {
 	pub format: DiffFormat,
 }
 
+/// Format of the output of the [`Plan`] command.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PlanFormat {
+	/// Prints the plan as a tree of targets.
+	#[default]
+	Tree,
+
+	/// Prints the plan as json.
+	Json,
+}
+
+/// Prints a plan of all actions a deployment of a profile would take.
+///
+/// Each target is annotated with the action which would be taken (create,
+/// overwrite, skip-unchanged, conflict, link). No files are written and no
+/// hooks are executed.
+#[derive(Debug, Parser)]
+pub struct Plan {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Alternative deployment target path.
+	///
+	/// This path will take precedence over all other ways to define a deployment
+	/// path.
+	#[arg(short, long)]
+	pub target: Option<PathBuf>,
+
+	/// Defines the output format for the plan.
+	#[arg(value_enum, short, long, default_value_t = PlanFormat::Tree)]
+	pub format: PlanFormat,
+
+	/// Exports the plan as scripts (`deploy.sh` and `deploy.ps1`) into the
+	/// given directory.
+	///
+	/// The scripts replay all planned actions without punktf (e.g. for
+	/// air-gapped machines). The resolved files are copied into the `files`
+	/// directory next to them.
+	#[arg(long, value_name = "DIR")]
+	pub emit_script: Option<PathBuf>,
+
+	/// Plans existing files which are write protected (read-only or immutable)
+	/// to be overwritten instead of reporting them as conflicts.
+	#[arg(long)]
+	pub force: bool,
+}
+
+/// Format of the output of the [`Check`] command.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CheckFormat {
+	/// Prints a json summary.
+	#[default]
+	Json,
+
+	/// Prints one line for each drifted target.
+	Text,
+}
+
+/// Checks if any target differs from what a deployment of a profile would
+/// produce.
+///
+/// Exits with a non-zero exit code if any drift is detected. No files are
+/// written and no hooks are executed. Useful for CI or shell prompts.
+#[derive(Debug, Parser)]
+pub struct Check {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Alternative deployment target path.
+	///
+	/// This path will take precedence over all other ways to define a deployment
+	/// path.
+	#[arg(short, long)]
+	pub target: Option<PathBuf>,
+
+	/// Defines the output format for the summary.
+	#[arg(value_enum, short, long, default_value_t = CheckFormat::Json)]
+	pub format: CheckFormat,
+}
+
+/// Compiles profiles and compares the results against snapshots.
+///
+/// Test cases are defined by files `tests/<case>.yaml` in the source directory,
+/// which name the `profile` to compile and pin its `variables`. The expected
+/// outputs are stored in `tests/<case>/` relative to the target of the profile.
+///
+/// Exits with a non-zero exit code if any output differs from its snapshot. No
+/// files are deployed and no hooks are executed. Useful for CI.
+///
+/// With `--in-container`, the given profiles are instead deployed inside of new
+/// containers to verify them end-to-end on other systems.
+#[derive(Debug, Parser)]
+pub struct Test {
+	/// The source directory where the profiles, dotfiles and tests are
+	/// located.
+	#[arg(short, long, env = super::PUNKTF_SOURCE_ENVVAR, required = true)]
+	pub source: Vec<PathBuf>,
+
+	/// Defines what happens if the same dotfile exists in multiple sources.
+	#[arg(long, default_value_t = ConflictPolicy::FirstWins)]
+	pub conflict_policy: ConflictPolicy,
+
+	/// Names of the test cases to run (file names without extension).
+	///
+	/// If none are given, all test cases are run.
+	pub cases: Vec<String>,
+
+	/// Updates the snapshots to the compiled outputs instead of failing.
+	///
+	/// Snapshots of files which are not compiled anymore are removed.
+	#[arg(long)]
+	pub accept: bool,
+
+	/// Deploys the profiles inside of a new container of the given image.
+	///
+	/// The `punktf` binary and the source directories are mounted read-only into
+	/// the container. Can be given multiple times to verify the profiles in
+	/// multiple images (e.g. `--in-container archlinux --in-container
+	/// debian`).
+	#[arg(long, value_name = "IMAGE", conflicts_with_all = ["cases", "accept"])]
+	pub in_container: Vec<String>,
+
+	/// Names of the profiles to deploy inside of the containers.
+	///
+	/// Can be given multiple times.
+	#[arg(short, long, requires = "in_container")]
+	pub profile: Vec<String>,
+
+	/// Container runtime to use (e.g. `docker` or `podman`).
+	///
+	/// If not given, the first one found is used.
+	#[arg(long, requires = "in_container")]
+	pub runtime: Option<String>,
+
+	/// The `punktf` binary which is mounted into the containers.
+	///
+	/// Defaults to the running binary, which must be executable inside of the
+	/// containers (e.g. use a statically linked build for images with another
+	/// libc).
+	#[arg(long, requires = "in_container")]
+	pub binary: Option<PathBuf>,
+}
+
+/// Opens an interactive terminal dashboard.
+///
+/// The dashboard lists all profiles, the dotfiles of the selected profile
+/// together with their drift to the deployed state and allows to deploy or
+/// undeploy single dotfiles.
+///
+/// If a profile is given, it is selected on startup.
+#[derive(Debug, Parser)]
+pub struct Ui {
+	#[command(flatten)]
+	pub shared: RepoShared,
+}
+
+/// Watches the source directories and redeploys a profile on changes.
+///
+/// The profile is deployed once on startup and then each time a file inside of
+/// a source directory changes. Changes to profiles, to files from which
+/// variables are read (`from:file:<path>`) and to the answers of prompt
+/// variables are picked up as well. Only items whose output changed are
+/// written again.
+#[derive(Debug, Parser)]
+pub struct Watch {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Alternative deployment target path.
+	///
+	/// This path will take precedence over all other ways to define a deployment
+	/// path.
+	#[arg(short, long)]
+	pub target: Option<PathBuf>,
+
+	/// Time in milliseconds to wait for further changes before redeploying.
+	#[arg(long, value_name = "MS", default_value_t = 500)]
+	pub debounce: u64,
+
+	/// Shows a desktop notification after each deployment (Windows toast,
+	/// libnotify or macOS notification).
+	#[arg(long)]
+	pub notify: bool,
+
+	/// Also watches the deployed files and reacts when one of them is modified
+	/// outside of punktf.
+	///
+	/// With `warn` (the default if no policy is given), a warning is printed
+	/// (and shown as notification with `--notify`). With `redeploy`, the
+	/// profile is deployed again as well, which restores the modified file.
+	#[arg(
+		long,
+		value_name = "POLICY",
+		num_args = 0..=1,
+		default_missing_value = "warn"
+	)]
+	pub watch_targets: Option<DriftPolicy>,
+}
+
+/// Reaction of the [`Watch`] command to deployed files which were modified
+/// outside of punktf.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DriftPolicy {
+	/// Prints a warning.
+	Warn,
+
+	/// Prints a warning and deploys the profile again.
+	Redeploy,
+}
+
+/// Type of a script generated by the [`Bootstrap`] command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ScriptKind {
+	/// Posix shell script.
+	Sh,
+
+	/// Powershell script.
+	#[value(name = "ps1")]
+	PowerShell,
+}
+
+/// Generates a script which sets up a new machine.
+///
+/// The script installs punktf (if not already installed), clones the dotfiles
+/// repository and deploys the profile. The repository url is taken from the
+/// `origin` remote of the source directory.
+#[derive(Debug, Parser)]
+pub struct Bootstrap {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Path of the generated script.
+	///
+	/// The type of the script is determined by the extension (`.sh` or `.ps1`).
+	#[arg(short, long)]
+	pub output: PathBuf,
+
+	/// Type of the generated script.
+	///
+	/// Takes precedence over the extension of the output path.
+	#[arg(value_enum, long)]
+	pub kind: Option<ScriptKind>,
+
+	/// Url of the dotfiles repository to clone.
+	///
+	/// Defaults to the url of the `origin` remote of the source directory.
+	#[arg(long, value_name = "URL")]
+	pub repo: Option<String>,
+
+	/// Directory into which the dotfiles repository is cloned on the new
+	/// machine.
+	///
+	/// Defaults to `~/.dotfiles`.
+	#[arg(long)]
+	pub dir: Option<String>,
+}
+
+/// Lists previous deployments.
+///
+/// Each deployment is listed with its id, time, profile, status, the amount of
+/// created, updated, unchanged, skipped and failed items and its duration.
+#[derive(Debug, Parser)]
+#[command(args_conflicts_with_subcommands = true)]
+pub struct History {
+	#[command(subcommand)]
+	pub command: Option<HistoryCommand>,
+
+	/// Only lists deployments of the given profile.
+	#[arg(short, long)]
+	pub profile: Option<String>,
+
+	/// Maximum amount of deployments to list.
+	#[arg(short = 'n', long, default_value_t = 20)]
+	pub limit: usize,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HistoryCommand {
+	Show(HistoryShow),
+	Diff(HistoryDiff),
+}
+
+/// Prints all items of a previous deployment.
+#[derive(Debug, Parser)]
+pub struct HistoryShow {
+	/// Id of the deployment (as listed by `punktf history`).
+	pub id: i64,
+}
+
+/// Compares two previous deployments.
+///
+/// Lists all items which were only deployed by one of them, items whose status
+/// changed and files which were deployed with a different content.
+#[derive(Debug, Parser)]
+pub struct HistoryDiff {
+	/// Id of the older deployment (as listed by `punktf history`).
+	pub old: i64,
+
+	/// Id of the newer deployment (as listed by `punktf history`).
+	pub new: i64,
+}
+
+/// Imports dotfiles managed by other tools into a punktf source.
+///
+/// The dotfiles are copied into the `dotfiles` directory of the source and an
+/// equivalent profile is written to `profiles/<profile>.yaml`.
+#[derive(Debug, Parser)]
+pub struct Import {
+	#[command(subcommand)]
+	pub command: ImportCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ImportCommand {
+	Stow(ImportStow),
+	Chezmoi(ImportChezmoi),
+	Dotbot(ImportDotbot),
+	Yadm(ImportYadm),
+}
+
+#[derive(Debug, Args)]
+pub struct ImportShared {
+	/// The source directory into which the dotfiles are imported.
+	///
+	/// The directory is created if it does not exist.
+	#[arg(short, long, env = super::PUNKTF_SOURCE_ENVVAR)]
+	pub source: PathBuf,
+
+	/// Name of the generated profile.
+	#[arg(short, long)]
+	pub profile: String,
+
+	/// Overwrites existing dotfiles and profiles in the source directory.
+	#[arg(long)]
+	pub force: bool,
+}
+
+/// Imports the packages of a GNU Stow directory.
+///
+/// Each package is copied to `dotfiles/<package>` and added as directory
+/// dotfile, which deploys its contents into the target of the profile (the
+/// same as `stow <package>` links them).
+#[derive(Debug, Parser)]
+pub struct ImportStow {
+	#[command(flatten)]
+	pub shared: ImportShared,
+
+	/// The stow directory which contains the packages.
+	pub dir: PathBuf,
+
+	/// Names of the packages to import.
+	///
+	/// If none are given, all packages of the stow directory are imported.
+	pub packages: Vec<String>,
+
+	/// Target directory of the profile.
+	///
+	/// Defaults to the parent of the stow directory, the same as for stow.
+	#[arg(short, long)]
+	pub target: Option<PathBuf>,
+
+	/// Replaces the prefix `dot-` of file and directory names with `.` (the
+	/// same as stow's `--dotfiles`).
+	#[arg(long)]
+	pub dotfiles: bool,
+}
+
+/// Imports a chezmoi source directory.
+///
+/// The attributes of the source file names (e.g. `dot_`, `create_` or
+/// `.tmpl`) are translated into dotfiles, data files (`.chezmoidata.*`) into
+/// variables and scripts (`run_`) into hooks. Simple templates (variables,
+/// comments and `if` blocks) are translated into the punktf syntax.
+///
+/// Everything without an equivalent in punktf (e.g. encrypted files or
+/// `.chezmoiignore`) is listed as needing manual attention.
+#[derive(Debug, Parser)]
+pub struct ImportChezmoi {
+	#[command(flatten)]
+	pub shared: ImportShared,
+
+	/// The chezmoi source directory.
+	///
+	/// Defaults to `~/.local/share/chezmoi`.
+	pub dir: Option<PathBuf>,
+
+	/// Target directory of the profile.
+	///
+	/// Defaults to `~`, the same as for chezmoi.
+	#[arg(short, long)]
+	pub target: Option<PathBuf>,
+}
+
+/// Imports a dotbot configuration.
+///
+/// The sources of all links are copied into the source directory and added
+/// as dotfiles, which are deployed to the path of the link. `create`
+/// directives become pre-hooks which create the directories and `shell`
+/// directives become hooks.
+///
+/// Everything without an equivalent in punktf (e.g. `clean` directives, glob
+/// links or plugins) is listed as needing manual attention.
+#[derive(Debug, Parser)]
+pub struct ImportDotbot {
+	#[command(flatten)]
+	pub shared: ImportShared,
+
+	/// The dotbot configuration file (e.g. `install.conf.yaml`).
+	pub config: PathBuf,
+
+	/// Base directory against which the sources of the links are resolved.
+	///
+	/// Defaults to the directory of the configuration file.
+	#[arg(short, long)]
+	pub base_dir: Option<PathBuf>,
+
+	/// Target directory of the profile.
+	///
+	/// Defaults to `~`. Links below `~` are deployed relative to it.
+	#[arg(short, long)]
+	pub target: Option<PathBuf>,
+}
+
+/// Imports a home directory managed by yadm.
+///
+/// All files tracked by the yadm repository are copied into the source
+/// directory. Files without alternates and default alternates are added to the
+/// given profile, class alternates (`##class.<class>`) to a profile per class
+/// (`<profile>-<class>`) which extends it. Templates of the default template
+/// processor are translated into the punktf syntax.
+///
+/// Everything without an equivalent in punktf (e.g. encrypted files or
+/// alternates for other conditions) is listed as needing manual attention.
+#[derive(Debug, Parser)]
+pub struct ImportYadm {
+	#[command(flatten)]
+	pub shared: ImportShared,
+
+	/// The yadm repository.
+	///
+	/// Defaults to `~/.local/share/yadm/repo.git`.
+	#[arg(long)]
+	pub repo: Option<PathBuf>,
+
+	/// The home directory which is managed by yadm.
+	///
+	/// Defaults to `~`.
+	#[arg(long)]
+	pub home: Option<PathBuf>,
+
+	/// Target directory of the profiles.
+	///
+	/// Defaults to `~`.
+	#[arg(short, long)]
+	pub target: Option<PathBuf>,
+}
+
+/// Exports a profile into the format of another tool.
+///
+/// All items of the profile are rendered as they would be deployed, so the
+/// exported dotfiles can be used without punktf.
+#[derive(Debug, Parser)]
+pub struct Export {
+	#[command(subcommand)]
+	pub command: ExportCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ExportCommand {
+	Chezmoi(ExportChezmoi),
+}
+
+/// Exports a profile into a chezmoi source directory.
+///
+/// The rendered files are written with the attributes of chezmoi encoded in
+/// their names (e.g. `dot_`, `executable_` or `create_` for the merge mode
+/// `Keep`), links become `symlink_` entries and hooks become scripts in
+/// `.chezmoiscripts`. Items deployed outside of the home directory are listed
+/// as needing manual attention.
+#[derive(Debug, Parser)]
+pub struct ExportChezmoi {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// The chezmoi source directory to write to (e.g.
+	/// `~/.local/share/chezmoi`).
+	///
+	/// The directory is created if it does not exist.
+	pub dir: PathBuf,
+
+	/// Overwrites existing files in the chezmoi source directory.
+	#[arg(long)]
+	pub force: bool,
+}
+
+/// Prints a JSON Schema of the profile format.
+///
+/// Editors can use the schema to offer completion and validation for
+/// `profiles/*.yaml` and `profiles/*.json` (e.g. with a
+/// `# yaml-language-server: $schema=<path>` comment or the `$schema` key).
+#[derive(Debug, Parser)]
+pub struct Schema {
+	/// Writes the schema to the given path instead of stdout.
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
+
+	/// Prints the schema of the deployment reports (written with
+	/// `--json-output`/`--yaml-output`) instead of the profile format.
+	#[arg(long)]
+	pub report: bool,
+}
+
+/// Migrates all profiles of a source to the current profile format version.
+///
+/// Profiles without a `version` or with an older one are rewritten in the
+/// current format, which drops comments and normalizes the formatting.
+/// Profiles which already use the current version are not touched.
+#[derive(Debug, Parser)]
+pub struct Migrate {
+	/// The source directory where the profiles are located.
+	#[arg(short, long, env = super::PUNKTF_SOURCE_ENVVAR)]
+	pub source: PathBuf,
+
+	/// Only prints the changes without writing the migrated profiles.
+	#[arg(short, long)]
+	pub dry_run: bool,
+}
+
+/// Formats all profiles of a source.
+///
+/// The keys are ordered as in the profile definition, the indentation is
+/// normalized and enum values (e.g. merge modes) are written in their
+/// canonical casing. Comments are not preserved.
+#[derive(Debug, Parser)]
+pub struct Fmt {
+	/// The source directory where the profiles are located.
+	#[arg(short, long, env = super::PUNKTF_SOURCE_ENVVAR)]
+	pub source: PathBuf,
+
+	/// Only checks if all profiles are formatted without writing them.
+	///
+	/// Exits with a non-zero exit code if any profile is not formatted.
+	#[arg(long)]
+	pub check: bool,
+}
+
+/// Searches the dotfiles of a profile for a pattern.
+///
+/// Prints the source path, the target path and all matching lines of each
+/// dotfile which matches. With `--rendered`, the rendered contents (templates
+/// resolved and transformers applied) are searched as well. Exits with a
+/// non-zero exit code if nothing matched.
+#[derive(Debug, Parser)]
+pub struct Grep {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Regular expression to search for.
+	pub pattern: String,
+
+	/// Also searches the rendered contents of the dotfiles.
+	#[arg(short, long)]
+	pub rendered: bool,
+
+	/// Matches case insensitively.
+	#[arg(short, long)]
+	pub ignore_case: bool,
+}
+
+/// Checks all templates of a profile for problems without deploying them.
+///
+/// Reports templates which fail to parse (e.g. unbalanced blocks), variables
+/// which are not defined for the profile and branches of `if` blocks which can
+/// never be taken. Each problem is printed with its location (e.g.
+/// `.bashrc:3:7: ...`). Exits with a non-zero exit code if any problem was
+/// found.
+#[derive(Debug, Parser)]
+pub struct Lint {
+	#[command(flatten)]
+	pub shared: RepoShared,
+}
+
+/// Benchmarks the compilation of a profile.
+///
+/// Resolves the profile and compiles all of its dotfiles multiple times
+/// without writing anything. Prints the time spent for the profile
+/// resolution, reading, template parsing, template rendering and hashing,
+/// followed by the slowest templates.
+#[derive(Debug, Parser)]
+pub struct Bench {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Amount of times the profile is compiled.
+	#[arg(short = 'n', long, default_value_t = 10)]
+	pub iterations: u32,
+
+	/// Amount of slowest templates to print.
+	#[arg(long, default_value_t = 10)]
+	pub slowest: usize,
+}
+
+/// Shows which dotfile is deployed to a path.
+///
+/// Prints the dotfile entry and the profile layer the item at the path comes
+/// from, what the next deployment would do with it and when it was last
+/// deployed.
+#[derive(Debug, Parser)]
+pub struct Which {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Target path to look up (e.g. `~/.bashrc`).
+	pub path: PathBuf,
+}
+
+/// Shows the variables of a profile.
+///
+/// Prints the value of each variable together with the layer it comes from
+/// (command line, environment or a profile of the extend chain). Values which
+/// are shadowed by a higher layer and variables overridden by single dotfiles
+/// are listed below the value in use.
+#[derive(Debug, Parser)]
+pub struct Vars {
+	#[command(flatten)]
+	pub shared: RepoShared,
+}
+
+/// Encrypts the source of a dotfile.
+///
+/// The source is encrypted in place with the `encryption` of the profile and
+/// the dotfile is marked as `encrypted` in all profiles listing it. For
+/// directories, all files below them are encrypted.
+#[derive(Debug, Parser)]
+pub struct Encrypt {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Source of the dotfile, either relative to the `dotfiles` directory or
+	/// to the current directory.
+	pub file: PathBuf,
+}
+
+/// Decrypts the source of an encrypted dotfile.
+///
+/// The source is decrypted in place with the `encryption` of the profile and
+/// the `encrypted` flag of the dotfile is removed from all profiles listing
+/// it.
+#[derive(Debug, Parser)]
+pub struct Decrypt {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Source of the dotfile, either relative to the `dotfiles` directory or
+	/// to the current directory.
+	pub file: PathBuf,
+}
+
+/// Encrypts all encrypted dotfiles of a profile again.
+///
+/// Used after the recipients of the `encryption` of the profile changed.
+#[derive(Debug, Parser)]
+pub struct Reencrypt {
+	#[command(flatten)]
+	pub shared: RepoShared,
+
+	/// Generates a new age identity and encrypts all sources for it.
+	///
+	/// The old identity is kept with the extension `.old` and the recipients
+	/// of all profiles are replaced with the new public key. Only supported
+	/// for the `age` backend.
+	#[arg(long)]
+	pub rotate_key: bool,
+}
+
+/// Manages the stored answers to the prompt variables of profiles.
+///
+/// The answers are stored in the `answers_file` of the config or in
+/// `<data dir>/punktf/answers.yaml`.
+#[derive(Debug, Parser)]
+pub struct Answers {
+	#[command(subcommand)]
+	pub command: AnswersCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AnswersCommand {
+	Edit(AnswersEdit),
+	Clear(AnswersClear),
+}
+
+/// Opens the answers file in the editor.
+///
+/// The editor is taken from the environment variables `VISUAL` or `EDITOR`.
+/// The file is validated once the editor exits.
+#[derive(Debug, Parser)]
+pub struct AnswersEdit {}
+
+/// Removes stored answers, so they are asked for again on the next
+/// deployment.
+#[derive(Debug, Parser)]
+pub struct AnswersClear {
+	/// Only removes the answers of the given profile.
+	#[arg(short, long)]
+	pub profile: Option<String>,
+}
+
+/// Manages the secrets referenced by `from:secret:<name>` variables.
+///
+/// The secrets are stored in the `secrets_dir` of the config or in
+/// `<data dir>/punktf/secrets`, with one directory per namespace. Profiles
+/// choose their namespace with `secret_namespace`.
+#[derive(Debug, Parser)]
+pub struct Secret {
+	#[command(subcommand)]
+	pub command: SecretCommand,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SecretCommand {
+	Set(SecretSet),
+	Get(SecretGet),
+	List(SecretList),
+	#[command(alias = "remove")]
+	Rm(SecretRm),
+}
+
+#[derive(Debug, Args)]
+pub struct SecretShared {
+	/// Namespace of the secrets, which is the `secret_namespace` of a profile.
+	#[arg(short, long, default_value = punktf_lib::profile::secret::DEFAULT_NAMESPACE)]
+	pub namespace: String,
+}
+
+/// Sets the value of a secret.
+///
+/// The value is read from stdin, or asked for in interactive sessions, if it
+/// is not given with `--value`.
+#[derive(Debug, Parser)]
+pub struct SecretSet {
+	#[command(flatten)]
+	pub shared: SecretShared,
+
+	/// Name of the secret.
+	pub name: String,
+
+	/// Value of the secret.
+	///
+	/// Prefer stdin, as the value might end up in the history of the shell.
+	#[arg(long)]
+	pub value: Option<String>,
+}
+
+/// Prints the value of a secret.
+#[derive(Debug, Parser)]
+pub struct SecretGet {
+	#[command(flatten)]
+	pub shared: SecretShared,
+
+	/// Name of the secret.
+	pub name: String,
+}
+
+/// Lists the names of all secrets.
+#[derive(Debug, Parser)]
+pub struct SecretList {
+	#[command(flatten)]
+	pub shared: SecretShared,
+}
+
+/// Removes a secret.
+#[derive(Debug, Parser)]
+pub struct SecretRm {
+	#[command(flatten)]
+	pub shared: SecretShared,
+
+	/// Name of the secret.
+	pub name: String,
+}
+
+/// Creates a new profile interactively.
+///
+/// Asks for the target path, the operating system, the profiles to extend and
+/// the dotfiles of the profile, which can be picked from the source or found
+/// by scanning a directory. The profile is written to
+/// `profiles/<name>.yaml`.
+#[derive(Debug, Parser)]
+pub struct NewProfile {
+	/// The source directory in which the profile is created.
+	///
+	/// The directory is created if it does not exist.
+	#[arg(short, long, env = super::PUNKTF_SOURCE_ENVVAR)]
+	pub source: PathBuf,
+
+	/// Name of the new profile.
+	///
+	/// If not given, the name is asked for.
+	pub name: Option<String>,
+}
+
 /// Generates man pages for this application.
+///
+/// Renders one roff man page for `punktf` (`punktf.1`) and one for each
+/// subcommand (e.g. `punktf-deploy.1`).
 #[derive(Debug, Parser)]
 pub struct Man {
-	/// Output path for the man pages.
-	#[arg(short, long, default_value = ".")]
-	pub output: PathBuf,
+	/// Output directory for the man pages.
+	///
+	/// The directory is created if it does not exist. Defaults to the current
+	/// directory.
+	#[arg(value_name = "OUTPUT", conflicts_with = "output")]
+	pub dir: Option<PathBuf>,
+
+	/// Output directory for the man pages (the same as the positional
+	/// argument).
+	#[arg(short, long)]
+	pub output: Option<PathBuf>,
 }
 
 /// Generates shell completions for this application.
+///
+/// Besides all subcommands and options, the completions for bash, zsh, fish
+/// and powershell also complete profile names by scanning the `profiles`
+/// directory of the given source directories (or `PUNKTF_SOURCE`).
 #[derive(Debug, Parser)]
 pub struct Completions {
 	/// Shell for which to generate the completions.
@@ -188,3 +1303,20 @@ pub struct Completions {
 	#[arg(short, long, default_value = ".")]
 	pub output: PathBuf,
 }
+
+/// Updates `punktf` to the latest release.
+///
+/// Downloads the release archive for the current platform from GitHub,
+/// verifies its sha256 checksum and replaces the running binary.
+#[cfg(feature = "self-update")]
+#[derive(Debug, Parser)]
+pub struct SelfUpdate {
+	/// Only checks if a newer release is available.
+	#[arg(long)]
+	pub check: bool,
+
+	/// Installs the latest release even if it is not newer than the installed
+	/// version.
+	#[arg(long)]
+	pub force: bool,
+}
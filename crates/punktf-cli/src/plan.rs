@@ -0,0 +1,222 @@
+//! Printing of deployment [plans](`punktf_lib::visit::plan::Plan`).
+
+use std::collections::BTreeMap;
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use console::style;
+use punktf_lib::visit::plan::{Action, Plan, PlanItem};
+use serde::Serialize;
+
+/// A node of the target path tree.
+#[derive(Debug, Default)]
+struct Node<'a> {
+	/// All planned items which target the path of this node.
+	items: Vec<&'a PlanItem>,
+
+	/// Child nodes by path component.
+	children: BTreeMap<OsString, Self>,
+}
+
+impl<'a> Node<'a> {
+	/// Adds an item to the tree below this node.
+	fn insert(&mut self, item: &'a PlanItem) {
+		let node = item.target_path.components().fold(self, |node, component| {
+			node.children
+				.entry(component.as_os_str().to_os_string())
+				.or_default()
+		});
+
+		node.items.push(item);
+	}
+
+	/// Follows all nodes without items which have exactly one child and
+	/// returns the joined path together with the last node.
+	///
+	/// This prevents printing one line for each directory of long paths.
+	fn collapse(&self, mut name: PathBuf) -> (PathBuf, &Self) {
+		let mut node = self;
+
+		while node.items.is_empty() && node.children.len() == 1 {
+			let (component, child) = node.children.iter().next().expect("Node to have one child");
+
+			name.push(component);
+			node = child;
+		}
+
+		(name, node)
+	}
+
+	/// Returns the annotation for all items of this node.
+	fn annotation(&self) -> String {
+		self.items
+			.iter()
+			.map(|item| {
+				let action = style(format!("[{}]", item.action));
+				let action = match item.action {
+					Action::Create => action.green(),
+					Action::Overwrite => action.yellow(),
+					Action::Link => action.cyan(),
+					Action::Conflict => action.magenta(),
+					Action::Error => action.red(),
+					Action::SkipUnchanged | Action::Skip => action.dim(),
+				};
+
+				match &item.reason {
+					Some(reason) => format!(" {action} {}", style(reason).dim()),
+					None => format!(" {action}"),
+				}
+			})
+			.collect()
+	}
+
+	/// Writes all children of this node to `out`.
+	fn write_children(&self, out: &mut String, prefix: &str) {
+		let len = self.children.len();
+
+		for (idx, (name, child)) in self.children.iter().enumerate() {
+			let last = idx + 1 == len;
+			let (name, child) = child.collapse(PathBuf::from(name));
+
+			out.push_str(&format!(
+				"{prefix}{}{}{}\n",
+				if last { "└── " } else { "├── " },
+				name.display(),
+				child.annotation()
+			));
+
+			child.write_children(
+				out,
+				&format!("{prefix}{}", if last { "    " } else { "│   " }),
+			);
+		}
+	}
+}
+
+/// Prints the plan as a tree of target paths annotated with the actions which
+/// would be taken.
+pub fn print_tree(plan: &Plan) {
+	let mut out = String::new();
+
+	if !plan.pre_hooks.is_empty() {
+		out.push_str("Pre-hooks:\n");
+		for hook in &plan.pre_hooks {
+			out.push_str(&format!("    {hook}\n"));
+		}
+	}
+
+	let mut root = Node::default();
+	for item in &plan.items {
+		root.insert(item);
+	}
+
+	let (name, node) = root.collapse(PathBuf::new());
+	if name.as_os_str().is_empty() {
+		node.write_children(&mut out, "");
+	} else {
+		out.push_str(&format!("{}{}\n", name.display(), node.annotation()));
+		node.write_children(&mut out, "");
+	}
+
+	if !plan.post_hooks.is_empty() {
+		out.push_str("Post-hooks:\n");
+		for hook in &plan.post_hooks {
+			out.push_str(&format!("    {hook}\n"));
+		}
+	}
+
+	let count = |action| plan.items.iter().filter(|i| i.action == action).count();
+
+	out.push_str(&format!(
+		"\n{} change(s), {} conflict(s), {} error(s)",
+		plan.changes(),
+		count(Action::Conflict),
+		count(Action::Error)
+	));
+
+	println!("{out}");
+}
+
+/// Returns the amount of items for each action of the plan (e.g. `2 create,
+/// 1 overwrite`). Actions without items are omitted.
+pub fn action_counts(plan: &Plan) -> String {
+	let actions = [
+		Action::Create,
+		Action::Overwrite,
+		Action::Link,
+		Action::SkipUnchanged,
+		Action::Skip,
+		Action::Conflict,
+		Action::Error,
+	];
+
+	let counts: Vec<_> = actions
+		.into_iter()
+		.filter_map(|action| {
+			let count = plan.items.iter().filter(|i| i.action == action).count();
+			(count > 0).then(|| format!("{count} {action}"))
+		})
+		.collect();
+
+	if counts.is_empty() {
+		String::from("nothing to do")
+	} else {
+		counts.join(", ")
+	}
+}
+
+/// Machine-readable summary of the drift between the targets and what a
+/// deployment would produce.
+#[derive(Debug, Serialize)]
+pub struct CheckSummary<'a> {
+	/// Name of the checked profile.
+	pub profile: &'a str,
+
+	/// Indicates if any target differs.
+	pub drift: bool,
+
+	/// Amount of targets which would be modified by a deployment.
+	pub changes: usize,
+
+	/// Amount of targets with a conflict.
+	pub conflicts: usize,
+
+	/// Amount of items which could not be processed.
+	pub errors: usize,
+
+	/// All items for which the target differs.
+	pub items: Vec<&'a PlanItem>,
+}
+
+impl<'a> CheckSummary<'a> {
+	/// Creates the summary of a plan.
+	pub fn new(profile: &'a str, plan: &'a Plan) -> Self {
+		let items: Vec<_> = plan.drift().collect();
+		let count = |action| items.iter().filter(|i| i.action == action).count();
+
+		Self {
+			profile,
+			drift: !items.is_empty(),
+			changes: plan.changes(),
+			conflicts: count(Action::Conflict),
+			errors: count(Action::Error),
+			items,
+		}
+	}
+
+	/// Prints one line for each drifted target.
+	pub fn print_text(&self) {
+		for item in &self.items {
+			match &item.reason {
+				Some(reason) => {
+					println!("{}: {} ({reason})", item.action, item.target_path.display())
+				}
+				None => println!("{}: {}", item.action, item.target_path.display()),
+			}
+		}
+
+		if !self.drift {
+			println!("No drift detected for profile {}", self.profile);
+		}
+	}
+}
@@ -0,0 +1,41 @@
+//! Loading of the WebAssembly plugins of the user.
+//!
+//! All `*.wasm` files in the `plugins_dir` of the global
+//! [`Config`](`crate::config::Config`) or in `<config dir>/punktf/plugins`
+//! (e.g. `~/.config/punktf/plugins` on linux) are loaded for every profile
+//! (see [`punktf_lib::plugin::wasm`]).
+
+use std::path::PathBuf;
+
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use punktf_lib::plugin::wasm;
+use punktf_lib::plugin::Plugins;
+use punktf_lib::profile::provider::VariableProviders;
+
+use crate::config::Config;
+
+/// Returns the directory from which the plugins are loaded.
+fn plugins_dir(config: &Config) -> Result<PathBuf> {
+	config
+		.plugins_dir
+		.clone()
+		.or_else(|| dirs::config_dir().map(|dir| dir.join("punktf").join("plugins")))
+		.ok_or_else(|| eyre!("Failed to determine the directory of the plugins"))
+}
+
+/// Loads all plugins and registers their template functions and hooks in
+/// `plugins` and their variable providers in `providers`.
+///
+/// # Errors
+///
+/// An error is returned if the config could not be loaded or any plugin could
+/// not be loaded.
+pub fn load(plugins: &mut Plugins, providers: &mut VariableProviders) -> Result<()> {
+	for plugin in wasm::discover(&plugins_dir(&Config::load()?)?)? {
+		tracing::debug!("Loaded plugin `{}`", plugin.name());
+		plugin.register(plugins, providers);
+	}
+
+	Ok(())
+}
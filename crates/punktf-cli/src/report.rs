@@ -0,0 +1,206 @@
+//! Rendering of [deployments](`punktf_lib::visit::deploy::deployment::Deployment`)
+//! as standalone html reports.
+//!
+//! A report contains a summary of the deployment, the status of each item and
+//! collapsible diffs of all changed files. It does not reference any external
+//! resources, so it can be shared or archived as a single file.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::Context;
+use color_eyre::Result;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::LayeredProfile;
+use punktf_lib::visit::deploy::deployment::{Deployment, DeploymentStatus};
+use punktf_lib::visit::diff::{Diff, Event};
+use similar::{ChangeTag, TextDiff};
+
+use crate::util::{self, Group};
+
+/// Styles of the report.
+const STYLE: &str = r#"
+body { font-family: system-ui, sans-serif; margin: 2em auto; max-width: 70em; padding: 0 1em; color: #24292f; }
+h1 { font-size: 1.5em; }
+table { border-collapse: collapse; width: 100%; }
+th, td { text-align: left; padding: .3em .6em; border-bottom: 1px solid #d0d7de; vertical-align: top; }
+code, pre { font-family: ui-monospace, monospace; font-size: .9em; }
+pre { background: #f6f8fa; padding: .5em; overflow-x: auto; margin: .3em 0; }
+summary { cursor: pointer; color: #57606a; }
+.badge { display: inline-block; padding: .1em .5em; border-radius: 1em; font-size: .85em; color: #fff; }
+.created { background: #1a7f37; }
+.updated { background: #9a6700; }
+.unchanged { background: #8c959f; }
+.skipped { background: #0969da; }
+.failed { background: #cf222e; }
+.reason { color: #57606a; }
+.add { color: #1a7f37; }
+.del { color: #cf222e; }
+"#;
+
+/// Escapes `s` for the use inside of html.
+fn escape(s: &str) -> String {
+	let mut escaped = String::with_capacity(s.len());
+
+	for c in s.chars() {
+		match c {
+			'&' => escaped.push_str("&amp;"),
+			'<' => escaped.push_str("&lt;"),
+			'>' => escaped.push_str("&gt;"),
+			'"' => escaped.push_str("&quot;"),
+			'\'' => escaped.push_str("&#39;"),
+			c => escaped.push(c),
+		}
+	}
+
+	escaped
+}
+
+/// Collects the diffs of all files which would be changed by a deployment of
+/// `profile` by their target path.
+///
+/// Must be called before the deployment, as the diffs are made against the
+/// currently deployed files.
+pub fn collect_diffs(
+	source: &PunktfSource,
+	profile: &mut LayeredProfile,
+) -> HashMap<PathBuf, String> {
+	let diffs = RefCell::new(HashMap::new());
+
+	Diff::new(|event| {
+		if let Event::Diff {
+			target_path,
+			old_content,
+			new_content,
+			..
+		} = event
+		{
+			diffs.borrow_mut().insert(
+				target_path.to_path_buf(),
+				render_diff(&old_content, &new_content),
+			);
+		}
+	})
+	.diff(source, profile);
+
+	diffs.into_inner()
+}
+
+/// Renders the diff between `old` and `new` as html.
+fn render_diff(old: &str, new: &str) -> String {
+	let diff = TextDiff::from_lines(old, new);
+	let mut out = String::from("<pre>");
+
+	for (idx, group) in diff.grouped_ops(3).iter().enumerate() {
+		if idx > 0 {
+			out.push_str("<span class=\"reason\">…</span>\n");
+		}
+
+		for op in group {
+			for change in diff.iter_changes(op) {
+				let (sign, class) = match change.tag() {
+					ChangeTag::Delete => ("-", Some("del")),
+					ChangeTag::Insert => ("+", Some("add")),
+					ChangeTag::Equal => (" ", None),
+				};
+
+				let line = format!("{sign}{}", escape(change.value().trim_end_matches('\n')));
+				let _ = match class {
+					Some(class) => writeln!(out, "<span class=\"{class}\">{line}</span>"),
+					None => writeln!(out, "{line}"),
+				};
+			}
+		}
+	}
+
+	out.push_str("</pre>");
+	out
+}
+
+/// Renders the html report of the `deployment`.
+///
+/// `diffs` are the diffs of all changed files by their target path (see
+/// [`collect_diffs`]).
+pub fn render(
+	profile_name: &str,
+	deployment: &Deployment,
+	diffs: &HashMap<PathBuf, String>,
+) -> String {
+	let rows = util::deployment_rows(deployment);
+
+	let mut out = format!(
+		"<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<title>punktf \
+		 deployment of {profile}</title>\n<style>{STYLE}</style>\n</head>\n<body>\n<h1>Deployment \
+		 of <code>{profile}</code></h1>\n",
+		profile = escape(profile_name)
+	);
+
+	let status = match deployment.status() {
+		DeploymentStatus::Success => String::from("<span class=\"badge created\">success</span>"),
+		DeploymentStatus::Failed(err) => format!(
+			"<span class=\"badge failed\">failed</span> <span class=\"reason\">{}</span>",
+			escape(&err.to_string())
+		),
+	};
+
+	let counts = Group::ALL
+		.into_iter()
+		.map(|group| {
+			let count = rows.iter().filter(|row| row.group == group).count();
+			format!("<span class=\"badge {0}\">{count} {0}</span>", group.name())
+		})
+		.collect::<Vec<_>>()
+		.join(" ");
+
+	let _ = write!(
+		out,
+		"<p>Status: {status}</p>\n<p>{counts}</p>\n<p>Duration: {:.2?}</p>\n",
+		deployment.duration().unwrap_or_default()
+	);
+
+	out.push_str("<table>\n<tr><th>Status</th><th>Kind</th><th>Path</th></tr>\n");
+
+	for row in &rows {
+		let path = escape(&row.path.display().to_string());
+
+		let mut details = String::new();
+		if let Some(reason) = &row.reason {
+			let _ = write!(details, "<div class=\"reason\">{}</div>", escape(reason));
+		}
+		if let (Group::Updated, Some(diff)) = (row.group, diffs.get(row.path)) {
+			let _ = write!(details, "<details><summary>Diff</summary>{diff}</details>");
+		}
+
+		let _ = writeln!(
+			out,
+			"<tr><td><span class=\"badge {0}\">{0}</span></td><td>{1}</td><td><code>{path}</code>{details}</td></tr>",
+			row.group.name(),
+			row.kind,
+		);
+	}
+
+	let _ = write!(
+		out,
+		"</table>\n<p class=\"reason\">Generated by punktf {}</p>\n</body>\n</html>\n",
+		env!("CARGO_PKG_VERSION")
+	);
+
+	out
+}
+
+/// Writes the html report of the `deployment` to `path`.
+///
+/// # Errors
+///
+/// An error is returned if the file could not be written.
+pub fn write(
+	path: &Path,
+	profile_name: &str,
+	deployment: &Deployment,
+	diffs: &HashMap<PathBuf, String>,
+) -> Result<()> {
+	std::fs::write(path, render(profile_name, deployment, diffs))
+		.wrap_err_with(|| format!("Failed to write html report {}", path.display()))
+}
@@ -0,0 +1,173 @@
+//! Deployments into a sandbox directory (`deploy --sandbox`).
+//!
+//! All target paths of a profile are moved below the sandbox directory while
+//! keeping their absolute structure (e.g. `/home/demo/.bashrc` is deployed to
+//! `<sandbox>/home/demo/.bashrc`). Hooks and command assertions are never
+//! executed inside of a sandbox.
+
+use std::path::{Component, Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use console::style;
+use punktf_lib::profile::assertion::Assertion;
+use punktf_lib::profile::LayeredProfile;
+
+/// Creates the sandbox directory.
+///
+/// If no `dir` is given, a new directory in the temporary directory of the
+/// system is used.
+pub fn create(dir: Option<PathBuf>) -> Result<PathBuf> {
+	let dir = dir.unwrap_or_else(|| {
+		std::env::temp_dir().join(format!("punktf-sandbox-{}", std::process::id()))
+	});
+
+	std::fs::create_dir_all(&dir)
+		.wrap_err_with(|| format!("Failed to create sandbox directory {}", dir.display()))?;
+
+	dir.canonicalize()
+		.wrap_err_with(|| format!("Failed to resolve sandbox directory {}", dir.display()))
+}
+
+/// Expands environment variables and `~` in `path`.
+fn expand(path: &Path) -> Result<PathBuf> {
+	let path_str = path
+		.to_str()
+		.ok_or_else(|| eyre!("Path {} includes non UTF-8 characters", path.display()))?;
+
+	Ok(PathBuf::from(
+		shellexpand::full(path_str)
+			.wrap_err_with(|| format!("Failed to expand path {}", path.display()))?
+			.as_ref(),
+	))
+}
+
+/// Moves the absolute `path` below the `sandbox` directory.
+///
+/// Environment variables and `~` are expanded beforehand.
+fn sandboxed(sandbox: &Path, path: &Path) -> Result<PathBuf> {
+	Ok(expand(path)?
+		.components()
+		.filter(|component| !matches!(component, Component::Prefix(_) | Component::RootDir))
+		.fold(sandbox.to_path_buf(), |sandboxed, component| {
+			sandboxed.join(component)
+		}))
+}
+
+/// Redirects all targets of the `profile` into the `sandbox` directory.
+///
+/// All hooks and command assertions are removed from the profile and only
+/// printed.
+///
+/// # Errors
+///
+/// An error is returned if any target path could not be expanded.
+pub fn apply(profile: &mut LayeredProfile, sandbox: &Path) -> Result<()> {
+	if let Some((_, target)) = &mut profile.target {
+		*target = sandboxed(sandbox, target)?;
+	}
+
+	for (_, dotfile) in &mut profile.dotfiles {
+		if let Some(target) = &mut dotfile.overwrite_target {
+			*target = sandboxed(sandbox, target)?;
+		}
+	}
+
+	for (_, symlink) in &mut profile.symlinks {
+		symlink.target_path = sandboxed(sandbox, &symlink.target_path)?;
+	}
+
+	let mut assertions = Vec::new();
+	for (idx, assertion) in profile.assertions.drain(..) {
+		match assertion {
+			Assertion::Command(command) => println!(
+				"{} Skipping assertion: {command}",
+				style("[sandbox]").cyan()
+			),
+			// Relative paths are already resolved against the sandboxed target
+			assertion => assertions.push((
+				idx,
+				assertion.map_path(|path| {
+					let path = expand(path)?;
+
+					if path.is_relative() {
+						Ok(path)
+					} else {
+						sandboxed(sandbox, &path)
+					}
+				})?,
+			)),
+		}
+	}
+	profile.assertions = assertions;
+
+	for (kind, hooks) in [
+		("pre-hook", &mut profile.pre_hooks),
+		("post-hook", &mut profile.post_hooks),
+	] {
+		for (_, hook) in hooks.drain(..) {
+			println!(
+				"{} Skipping {kind}: {}",
+				style("[sandbox]").cyan(),
+				hook.command()
+			);
+		}
+	}
+
+	Ok(())
+}
+
+/// Writes all entries of the directory `dir` as tree to `out`.
+fn write_tree(out: &mut String, dir: &Path, prefix: &str) -> Result<()> {
+	let mut entries = std::fs::read_dir(dir)
+		.wrap_err_with(|| format!("Failed to read directory {}", dir.display()))?
+		.collect::<std::io::Result<Vec<_>>>()?;
+	entries.sort_by_key(|entry| entry.file_name());
+
+	let len = entries.len();
+
+	for (idx, entry) in entries.into_iter().enumerate() {
+		let last = idx + 1 == len;
+		let path = entry.path();
+		let file_type = entry.file_type()?;
+
+		let name = entry.file_name().to_string_lossy().into_owned();
+		let name = if file_type.is_symlink() {
+			let destination = std::fs::read_link(&path)?;
+			format!("{} -> {}", style(name).cyan(), destination.display())
+		} else if file_type.is_dir() {
+			style(name).bold().to_string()
+		} else {
+			name
+		};
+
+		out.push_str(&format!(
+			"{prefix}{}{name}\n",
+			if last { "└── " } else { "├── " },
+		));
+
+		if file_type.is_dir() {
+			write_tree(
+				out,
+				&path,
+				&format!("{prefix}{}", if last { "    " } else { "│   " }),
+			)?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Prints the contents of the `sandbox` directory as tree.
+///
+/// # Errors
+///
+/// An error is returned if any directory could not be read.
+pub fn print_tree(sandbox: &Path) -> Result<()> {
+	let mut out = format!("{}\n", style(sandbox.display()).bold());
+	write_tree(&mut out, sandbox, "")?;
+
+	print!("{out}");
+
+	Ok(())
+}
@@ -0,0 +1,231 @@
+//! Export of deployment [plans](`punktf_lib::visit::plan::Plan`) as shell
+//! scripts.
+//!
+//! The exported scripts (`deploy.sh` and `deploy.ps1`) replay all planned
+//! actions without `punktf`. The resolved contents of all files which would be
+//! written are copied into a staging directory (`files`) next to the scripts.
+
+use std::fmt::Write as _;
+use std::path::Path;
+
+use color_eyre::eyre::Context;
+use color_eyre::Result;
+use punktf_lib::visit::plan::{Action, ItemKind, Plan, PlanItem};
+
+/// Name of the staging directory inside of the output directory.
+const STAGING_DIR: &str = "files";
+
+/// Quotes a value for a posix shell.
+pub fn quote_sh(value: &str) -> String {
+	format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Quotes a value for powershell.
+pub fn quote_ps1(value: &str) -> String {
+	format!("'{}'", value.replace('\'', "''"))
+}
+
+/// Quotes a path for a posix shell.
+fn path_sh(path: &Path) -> String {
+	quote_sh(&path.to_string_lossy())
+}
+
+/// Quotes a path for powershell.
+fn path_ps1(path: &Path) -> String {
+	quote_ps1(&path.to_string_lossy())
+}
+
+/// A single step of an exported script.
+#[derive(Debug)]
+enum Step<'a> {
+	/// Executes a hook.
+	Hook(&'a str),
+
+	/// Creates a directory.
+	Directory(&'a Path),
+
+	/// Copies a staged file to its target.
+	File {
+		/// Name of the file inside of the staging directory.
+		staged: String,
+
+		/// Target path of the file.
+		target: &'a Path,
+	},
+
+	/// Creates a symlink.
+	Link {
+		/// Path to which the link points.
+		source: &'a Path,
+
+		/// Path of the link.
+		target: &'a Path,
+	},
+
+	/// An item which is not deployed by the script.
+	Skipped(&'a PlanItem),
+}
+
+impl Step<'_> {
+	/// Writes the step as posix shell commands.
+	fn write_sh(&self, out: &mut String) {
+		let _ = match self {
+			Self::Hook(command) => writeln!(out, "{command}"),
+			Self::Directory(path) => writeln!(out, "mkdir -p {}", path_sh(path)),
+			Self::File { staged, target } => {
+				if let Some(parent) = target.parent() {
+					let _ = writeln!(out, "mkdir -p {}", path_sh(parent));
+				}
+				writeln!(
+					out,
+					"cp \"$STAGING\"/{} {}",
+					quote_sh(staged),
+					path_sh(target)
+				)
+			}
+			Self::Link { source, target } => writeln!(
+				out,
+				"rm -rf {target} && ln -s {} {target}",
+				path_sh(source),
+				target = path_sh(target)
+			),
+			Self::Skipped(item) => writeln!(out, "# {}", describe(item)),
+		};
+	}
+
+	/// Writes the step as powershell commands.
+	fn write_ps1(&self, out: &mut String) {
+		let _ = match self {
+			Self::Hook(command) => writeln!(
+				out,
+				"& cmd /C {}\nif ($LASTEXITCODE -ne 0) {{ exit $LASTEXITCODE }}",
+				quote_ps1(command)
+			),
+			Self::Directory(path) => writeln!(
+				out,
+				"New-Item -ItemType Directory -Force -Path {} | Out-Null",
+				path_ps1(path)
+			),
+			Self::File { staged, target } => {
+				if let Some(parent) = target.parent() {
+					let _ = writeln!(
+						out,
+						"New-Item -ItemType Directory -Force -Path {} | Out-Null",
+						path_ps1(parent)
+					);
+				}
+				writeln!(
+					out,
+					"Copy-Item -Force -LiteralPath (Join-Path $Staging {}) -Destination {}",
+					quote_ps1(staged),
+					path_ps1(target)
+				)
+			}
+			Self::Link { source, target } => writeln!(
+				out,
+				"New-Item -ItemType SymbolicLink -Force -Path {} -Target {} | Out-Null",
+				path_ps1(target),
+				path_ps1(source)
+			),
+			Self::Skipped(item) => writeln!(out, "# {}", describe(item)),
+		};
+	}
+}
+
+/// Describes an item which is not deployed by the script.
+fn describe(item: &PlanItem) -> String {
+	let mut description = format!(
+		"{}: {}",
+		item.action,
+		item.target_path.to_string_lossy().replace('\n', " ")
+	);
+
+	if let Some(reason) = &item.reason {
+		let _ = write!(description, " ({})", reason.replace('\n', " "));
+	}
+
+	description
+}
+
+/// Writes the scripts `deploy.sh` and `deploy.ps1` for the `plan` into the
+/// directory `output`.
+///
+/// The plan must have been created with the contents of all files
+/// ([`Planner::with_contents`](`punktf_lib::visit::plan::Planner::with_contents`)).
+/// Items which would not change the target are left out, conflicts and errors
+/// are added as comments.
+///
+/// # Errors
+///
+/// An error is returned if any file could not be written.
+pub fn write(plan: &Plan, profile_name: &str, output: &Path) -> Result<()> {
+	let staging = output.join(STAGING_DIR);
+	std::fs::create_dir_all(&staging)
+		.wrap_err_with(|| format!("Failed to create directory {}", staging.display()))?;
+
+	let mut steps: Vec<Step<'_>> = plan.pre_hooks.iter().map(|hook| Step::Hook(hook)).collect();
+
+	for (idx, item) in plan.items.iter().enumerate() {
+		let step = match (item.kind, item.action, &item.content) {
+			(ItemKind::Directory, Action::Create, _) => Step::Directory(&item.target_path),
+			(ItemKind::File, Action::Create | Action::Overwrite, Some(content)) => {
+				let name = item
+					.target_path
+					.file_name()
+					.map(|name| name.to_string_lossy())
+					.unwrap_or_default();
+				let staged = format!("{idx:04}-{name}");
+
+				let path = staging.join(&staged);
+				std::fs::write(&path, content)
+					.wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+
+				Step::File {
+					staged,
+					target: &item.target_path,
+				}
+			}
+			(ItemKind::Link, Action::Link, _) => Step::Link {
+				source: &item.source_path,
+				target: &item.target_path,
+			},
+			(_, Action::Conflict | Action::Error, _) => Step::Skipped(item),
+			_ => continue,
+		};
+
+		steps.push(step);
+	}
+
+	steps.extend(plan.post_hooks.iter().map(|hook| Step::Hook(hook)));
+
+	let header =
+		format!("# Generated by `punktf plan --emit-script` for the profile `{profile_name}`.");
+
+	let mut sh = format!(
+		"#!/bin/sh\n{header}\nset -eu\n\nSTAGING=\"$(cd \"$(dirname \"$0\")\" && pwd)/{STAGING_DIR}\"\n\n"
+	);
+	let mut ps1 = format!(
+		"{header}\n$ErrorActionPreference = 'Stop'\n\n$Staging = Join-Path $PSScriptRoot '{STAGING_DIR}'\n\n"
+	);
+
+	for step in &steps {
+		step.write_sh(&mut sh);
+		step.write_ps1(&mut ps1);
+	}
+
+	let sh_path = output.join("deploy.sh");
+	std::fs::write(&sh_path, sh)
+		.wrap_err_with(|| format!("Failed to write {}", sh_path.display()))?;
+
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt as _;
+		std::fs::set_permissions(&sh_path, std::fs::Permissions::from_mode(0o755))?;
+	}
+
+	let ps1_path = output.join("deploy.ps1");
+	std::fs::write(&ps1_path, ps1)
+		.wrap_err_with(|| format!("Failed to write {}", ps1_path.display()))?;
+
+	Ok(())
+}
@@ -0,0 +1,149 @@
+//! Machine-local secrets referenced by `from:secret:<name>` variables
+//! (`punktf secret`).
+//!
+//! The secrets are stored by a
+//! [`FileBackend`](`punktf_lib::profile::secret::FileBackend`) in the
+//! `secrets_dir` of the global [`Config`](`crate::config::Config`) or in
+//! `<data dir>/punktf/secrets` (e.g. `~/.local/share/punktf/secrets` on
+//! linux), with one directory per namespace.
+
+use std::io::Read as _;
+use std::path::PathBuf;
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use console::style;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::Password;
+use punktf_lib::profile::provider::VariableProviders;
+use punktf_lib::profile::secret::{FileBackend, SecretBackend, SecretProvider, DEFAULT_NAMESPACE};
+
+use crate::config::Config;
+use crate::util;
+
+/// Returns the directory in which the namespaces of the secrets are stored.
+fn secrets_dir(config: &Config) -> Result<PathBuf> {
+	config
+		.secrets_dir
+		.clone()
+		.or_else(|| dirs::data_dir().map(|dir| dir.join("punktf").join("secrets")))
+		.ok_or_else(|| eyre!("Failed to determine the directory of the secrets"))
+}
+
+/// Returns the backend which stores the secrets of `namespace`.
+///
+/// # Errors
+///
+/// An error is returned if the config could not be loaded or the namespace is
+/// invalid.
+pub fn backend(namespace: Option<&str>) -> Result<FileBackend> {
+	let dir = secrets_dir(&Config::load()?)?;
+
+	FileBackend::namespace(&dir, namespace.unwrap_or(DEFAULT_NAMESPACE))
+}
+
+/// Returns the default variable providers together with the `secret` provider
+/// for the secrets of `namespace`.
+///
+/// # Errors
+///
+/// An error is returned if the backend of the secrets could not be created.
+pub fn providers(namespace: Option<&str>) -> Result<VariableProviders> {
+	let mut providers = VariableProviders::default();
+	providers.register("secret", SecretProvider(backend(namespace)?));
+
+	Ok(providers)
+}
+
+/// Reads the value of a secret from stdin, or asks for it in interactive
+/// sessions.
+///
+/// A single trailing line break is removed from values read from stdin.
+fn read_value(name: &str) -> Result<String> {
+	if util::is_interactive() {
+		return Password::with_theme(&ColorfulTheme::default())
+			.with_prompt(format!("Value of `{name}`"))
+			.interact()
+			.wrap_err("Failed to read the value");
+	}
+
+	let mut value = String::new();
+	std::io::stdin()
+		.read_to_string(&mut value)
+		.wrap_err("Failed to read the value from stdin")?;
+
+	if value.ends_with('\n') {
+		value.pop();
+
+		if value.ends_with('\r') {
+			value.pop();
+		}
+	}
+
+	Ok(value)
+}
+
+/// Sets the secret `name` of `namespace` to `value`, which is read from stdin
+/// if not given.
+///
+/// # Errors
+///
+/// An error is returned if the value could not be read or the secret could
+/// not be written.
+pub fn set(namespace: &str, name: &str, value: Option<String>) -> Result<()> {
+	let backend = backend(Some(namespace))?;
+	let value = match value {
+		Some(value) => value,
+		None => read_value(name)?,
+	};
+
+	backend.set(name, &value)?;
+	eprintln!("{} {namespace}/{name}", style("set").green());
+
+	Ok(())
+}
+
+/// Prints the value of the secret `name` of `namespace`.
+///
+/// # Errors
+///
+/// An error is returned if the secret is not set or could not be read.
+pub fn get(namespace: &str, name: &str) -> Result<()> {
+	let value = backend(Some(namespace))?
+		.get(name)?
+		.ok_or_else(|| eyre!("Secret `{name}` is not set in namespace `{namespace}`"))?;
+
+	println!("{value}");
+
+	Ok(())
+}
+
+/// Prints the names of all secrets of `namespace`.
+///
+/// # Errors
+///
+/// An error is returned if the secrets could not be listed.
+pub fn list(namespace: &str) -> Result<()> {
+	for name in backend(Some(namespace))?.list()? {
+		println!("{name}");
+	}
+
+	Ok(())
+}
+
+/// Removes the secret `name` of `namespace`.
+///
+/// # Errors
+///
+/// An error is returned if the secret is not set or could not be removed.
+pub fn remove(namespace: &str, name: &str) -> Result<()> {
+	if !backend(Some(namespace))?.remove(name)? {
+		return Err(eyre!(
+			"Secret `{name}` is not set in namespace `{namespace}`"
+		));
+	}
+
+	eprintln!("{} {namespace}/{name}", style("removed").green());
+
+	Ok(())
+}
@@ -0,0 +1,308 @@
+//! Snapshot tests of profiles (`punktf test`).
+//!
+//! Each test case is defined by a file `tests/<case>.yaml` (or `.yml`) in the
+//! source directory, which names the profile to compile and pins the variables
+//! used for it:
+//!
+//! ```yaml
+//! profile: work
+//! variables:
+//!   OS: linux
+//! ```
+//!
+//! The expected outputs (golden files) of a case are stored in the directory
+//! `tests/<case>/`, relative to the target of the profile (e.g. the dotfile
+//! deployed to `<target>/.config/git/config` is compared against
+//! `tests/<case>/.config/git/config`).
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use console::style;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::variables::Variables;
+use punktf_lib::profile::{resolve_profile, LayeredProfile, Profile};
+use punktf_lib::visit::plan::{Action, ItemKind, Planner};
+use serde::Deserialize;
+
+/// Name of the directory in the source which contains the test cases.
+const TESTS_DIR: &str = "tests";
+
+/// Definition of a test case.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Case {
+	/// Name of the profile to compile.
+	profile: String,
+
+	/// Variables which take precedence over the ones of the profile.
+	#[serde(default)]
+	variables: Option<Variables>,
+}
+
+/// Result of the comparison of a single file with its snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+	/// The rendered file matches the snapshot.
+	Matched,
+
+	/// There is no snapshot for the rendered file.
+	New,
+
+	/// The rendered file differs from the snapshot.
+	Changed,
+
+	/// There is a snapshot but the file is not rendered anymore.
+	Obsolete,
+}
+
+impl Outcome {
+	/// Returns the label which is printed for the outcome.
+	fn label(self) -> console::StyledObject<&'static str> {
+		match self {
+			Self::Matched => style("ok").green(),
+			Self::New => style("new").cyan(),
+			Self::Changed => style("changed").yellow(),
+			Self::Obsolete => style("obsolete").magenta(),
+		}
+	}
+}
+
+/// Collects the names of all test cases together with the path of their
+/// definition.
+fn collect_cases(tests_dir: &Path) -> Result<BTreeMap<String, PathBuf>> {
+	let mut cases = BTreeMap::new();
+
+	let entries = std::fs::read_dir(tests_dir)
+		.wrap_err_with(|| format!("Failed to read test directory {}", tests_dir.display()))?;
+
+	for entry in entries {
+		let path = entry?.path();
+
+		let is_case = path.is_file()
+			&& path.extension().is_some_and(|extension| {
+				extension.eq_ignore_ascii_case("yaml") || extension.eq_ignore_ascii_case("yml")
+			});
+
+		if let (true, Some(name)) = (is_case, path.file_stem()) {
+			cases.insert(name.to_string_lossy().into_owned(), path);
+		}
+	}
+
+	Ok(cases)
+}
+
+/// Collects all files below `dir` relative to `dir`.
+fn collect_files(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+	let current = dir.join(relative);
+
+	if !current.is_dir() {
+		return Ok(());
+	}
+
+	for entry in std::fs::read_dir(&current)
+		.wrap_err_with(|| format!("Failed to read directory {}", current.display()))?
+	{
+		let entry = entry?;
+		let relative = relative.join(entry.file_name());
+
+		if entry.file_type()?.is_dir() {
+			collect_files(dir, &relative, files)?;
+		} else {
+			files.push(relative);
+		}
+	}
+
+	Ok(())
+}
+
+/// Compiles the profile of a test case into a temporary target and returns the
+/// rendered contents of all files by their path relative to the target.
+///
+/// Nothing is written to the temporary target and no hooks are executed.
+fn render(source: &PunktfSource, name: &str, case: Case) -> Result<BTreeMap<PathBuf, Vec<u8>>> {
+	let target = std::env::temp_dir().join(format!("punktf-test-{}-{name}", std::process::id()));
+
+	let mut builder = LayeredProfile::build();
+	builder.add(
+		String::from("test_case"),
+		Profile {
+			target: Some(target.clone()),
+			variables: case.variables,
+			..Default::default()
+		},
+	);
+	resolve_profile(&mut builder, source, &case.profile)?;
+	let mut profile = builder.finish();
+
+	crate::setup_env(source, &profile, &case.profile);
+
+	let plan = Planner::new()
+		.with_contents(true)
+		.plan(source, &mut profile);
+
+	let mut files = BTreeMap::new();
+
+	for item in plan.items {
+		if item.action == Action::Error {
+			return Err(eyre!(
+				"Failed to compile {}: {}",
+				item.source_path.display(),
+				item.reason.unwrap_or_default()
+			));
+		}
+
+		let (ItemKind::File, Some(content)) = (item.kind, item.content) else {
+			continue;
+		};
+
+		match item.target_path.strip_prefix(&target) {
+			Ok(relative) => {
+				files.insert(relative.to_path_buf(), content);
+			}
+			Err(_) => tracing::debug!(
+				"Skipping {} as it is deployed outside of the target",
+				item.target_path.display()
+			),
+		}
+	}
+
+	Ok(files)
+}
+
+/// Runs a single test case and prints the outcome of each file.
+///
+/// If `accept` is set, all snapshots are updated to the rendered files.
+///
+/// Returns `true` if all files matched their snapshots.
+fn run_case(source: &PunktfSource, name: &str, path: &Path, accept: bool) -> Result<bool> {
+	let content = std::fs::read_to_string(path)
+		.wrap_err_with(|| format!("Failed to read test case {}", path.display()))?;
+	let case: Case = serde_yaml::from_str(&content)
+		.wrap_err_with(|| format!("Failed to parse test case {}", path.display()))?;
+
+	println!(
+		"{} {} (profile {})",
+		style("test").bold(),
+		style(name).bold(),
+		case.profile
+	);
+
+	let snapshot_dir = path.with_extension("");
+	let rendered = render(source, name, case)?;
+
+	let mut snapshots = Vec::new();
+	collect_files(&snapshot_dir, Path::new(""), &mut snapshots)?;
+
+	let mut outcomes = rendered
+		.iter()
+		.map(|(relative, content)| {
+			let outcome = match std::fs::read(snapshot_dir.join(relative)) {
+				Ok(expected) if &expected == content => Outcome::Matched,
+				Ok(_) => Outcome::Changed,
+				Err(_) => Outcome::New,
+			};
+
+			(relative.clone(), outcome)
+		})
+		.collect::<BTreeMap<_, _>>();
+
+	for relative in snapshots {
+		outcomes.entry(relative).or_insert(Outcome::Obsolete);
+	}
+
+	for (relative, outcome) in &outcomes {
+		println!("  {:<9} {}", outcome.label(), relative.display());
+
+		let snapshot = snapshot_dir.join(relative);
+
+		match (outcome, accept) {
+			(Outcome::Matched, _) => {}
+			(Outcome::Changed, false) => {
+				let expected = std::fs::read(&snapshot)?;
+				crate::diff::print_udiff(
+					relative,
+					&String::from_utf8_lossy(&expected),
+					&String::from_utf8_lossy(&rendered[relative]),
+				);
+			}
+			(Outcome::New | Outcome::Obsolete, false) => {}
+			(Outcome::New | Outcome::Changed, true) => {
+				if let Some(parent) = snapshot.parent() {
+					std::fs::create_dir_all(parent).wrap_err_with(|| {
+						format!("Failed to create directory {}", parent.display())
+					})?;
+				}
+
+				std::fs::write(&snapshot, &rendered[relative])
+					.wrap_err_with(|| format!("Failed to write {}", snapshot.display()))?;
+			}
+			(Outcome::Obsolete, true) => {
+				std::fs::remove_file(&snapshot)
+					.wrap_err_with(|| format!("Failed to remove {}", snapshot.display()))?;
+			}
+		}
+	}
+
+	Ok(outcomes
+		.values()
+		.all(|outcome| *outcome == Outcome::Matched))
+}
+
+/// Runs the test cases with the given `names` (or all if empty) of the source.
+///
+/// If `accept` is set, the snapshots are updated to the rendered files instead
+/// of failing on differences.
+///
+/// Returns `true` if all test cases passed.
+///
+/// # Errors
+///
+/// An error is returned if a test case could not be found.
+pub fn run(source: &PunktfSource, names: &[String], accept: bool) -> Result<bool> {
+	let tests_dir = source.root().join(TESTS_DIR);
+	let mut cases = collect_cases(&tests_dir)?;
+
+	if !names.is_empty() {
+		for name in names {
+			if !cases.contains_key(name) {
+				return Err(eyre!("No test case found for name {name}"));
+			}
+		}
+
+		cases.retain(|name, _| names.contains(name));
+	}
+
+	if cases.is_empty() {
+		println!("No test cases found in {}", tests_dir.display());
+		return Ok(true);
+	}
+
+	let mut failed = Vec::new();
+
+	for (name, path) in &cases {
+		match run_case(source, name, path, accept) {
+			Ok(true) => {}
+			Ok(false) if accept => println!("  {}", style("snapshots updated").cyan()),
+			Ok(false) => failed.push(name.as_str()),
+			Err(err) => {
+				println!("  {} {err:#}", style("error").red());
+				failed.push(name.as_str());
+			}
+		}
+	}
+
+	println!(
+		"\n{} passed, {} failed",
+		cases.len() - failed.len(),
+		failed.len()
+	);
+
+	if !failed.is_empty() && !accept {
+		println!("Use `--accept` to update the snapshots");
+	}
+
+	Ok(failed.is_empty())
+}
@@ -0,0 +1,642 @@
+//! Interactive terminal dashboard used by the [`Ui`](`crate::opt::Ui`) command.
+//!
+//! The dashboard lists all profiles of a source, the dotfiles of the selected
+//! profile together with their drift (difference between the deployed state
+//! and the state after a deployment) and allows to deploy or undeploy single
+//! dotfiles.
+
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::{collect_profile_names, suggest_profile_name, LayeredProfile};
+use punktf_lib::visit::deploy::{DeployOptions, Deployer};
+use punktf_lib::visit::diff::{Diff, Event};
+use punktf_lib::visit::{Directory, Errored, File, Rejected, Symlink, Visitor, Walker};
+use ratatui::crossterm::event::{self, Event as TermEvent, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span};
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph};
+use ratatui::{DefaultTerminal, Frame};
+use similar::{ChangeTag, TextDiff};
+
+/// Runs the dashboard until the user quits it.
+///
+/// If `profile_name` is given, the profile is selected and loaded on startup.
+/// The variables `vars` are added to each loaded profile.
+pub fn run(
+	source: PunktfSource,
+	profile_name: Option<String>,
+	vars: Vec<(String, String)>,
+) -> Result<()> {
+	let mut app = App::new(source, vars)?;
+
+	if let Some(profile_name) = profile_name {
+		let Some(idx) = app.profiles.iter().position(|p| p == &profile_name) else {
+			return Err(
+				match suggest_profile_name(&profile_name, app.profiles.iter().map(String::as_str)) {
+					Some(suggestion) => eyre!(
+						"No profile found for name {profile_name}. Did you mean `{suggestion}`?"
+					),
+					None => eyre!("No profile found for name {profile_name}"),
+				},
+			);
+		};
+
+		app.profile_state.select(Some(idx));
+		app.load_profile();
+		app.focus = Focus::Dotfiles;
+	}
+
+	// Any log output would corrupt the terminal ui.
+	crate::logging::set_console_enabled(false);
+
+	let mut terminal = ratatui::init();
+	let res = app.run(&mut terminal);
+	ratatui::restore();
+
+	crate::logging::set_console_enabled(true);
+
+	res
+}
+
+/// Pane of the dashboard which currently receives the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+	/// The list of profiles.
+	Profiles,
+
+	/// The list of dotfiles of the selected profile.
+	Dotfiles,
+}
+
+/// Difference of a single item to the deployed state.
+#[derive(Debug)]
+enum Drift {
+	/// File does not exist at the target.
+	NewFile {
+		/// Absolute path to the target location.
+		target_path: PathBuf,
+	},
+
+	/// Directory does not exist at the target.
+	NewDirectory {
+		/// Absolute path to the target location.
+		target_path: PathBuf,
+	},
+
+	/// File exists at the target but the contents differ.
+	Changed {
+		/// Absolute path to the target location.
+		target_path: PathBuf,
+
+		/// Contents of the current file at the target.
+		old_content: String,
+
+		/// Contents of the file after a deployment.
+		new_content: String,
+	},
+}
+
+impl Drift {
+	/// Returns the absolute target path of the item.
+	fn target_path(&self) -> &Path {
+		match self {
+			Self::NewFile { target_path }
+			| Self::NewDirectory { target_path }
+			| Self::Changed { target_path, .. } => target_path,
+		}
+	}
+}
+
+impl From<Event<'_>> for Drift {
+	fn from(value: Event<'_>) -> Self {
+		match value {
+			Event::NewFile { target_path, .. } => Self::NewFile {
+				target_path: target_path.to_path_buf(),
+			},
+			Event::NewDirectory { target_path, .. } => Self::NewDirectory {
+				target_path: target_path.to_path_buf(),
+			},
+			Event::Diff {
+				target_path,
+				old_content,
+				new_content,
+				..
+			} => Self::Changed {
+				target_path: target_path.to_path_buf(),
+				old_content,
+				new_content,
+			},
+		}
+	}
+}
+
+/// A dotfile of the selected profile together with its drift.
+#[derive(Debug)]
+struct Entry {
+	/// Path of the dotfile relative to the `dotfiles` directory.
+	path: PathBuf,
+
+	/// Absolute target paths of all files which belong to the dotfile.
+	files: Vec<PathBuf>,
+
+	/// All items of the dotfile which differ from the deployed state.
+	drifts: Vec<Drift>,
+}
+
+impl Entry {
+	/// Returns a short human readable summary of the drift.
+	fn summary(&self) -> (String, Color) {
+		let new = self
+			.drifts
+			.iter()
+			.filter(|d| !matches!(d, Drift::Changed { .. }))
+			.count();
+		let changed = self.drifts.len() - new;
+
+		match (new, changed) {
+			(0, 0) => (String::from("in sync"), Color::Green),
+			(new, 0) => (format!("{new} new"), Color::Cyan),
+			(0, changed) => (format!("{changed} changed"), Color::Yellow),
+			(new, changed) => (format!("{new} new, {changed} changed"), Color::Yellow),
+		}
+	}
+}
+
+/// A [`Visitor`] which collects the target paths of all files.
+#[derive(Debug, Default)]
+struct FileCollector(Vec<PathBuf>);
+
+impl Visitor for FileCollector {
+	fn accept_file<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		file: &File<'a>,
+	) -> punktf_lib::visit::Result {
+		self.0.push(file.target_path.clone());
+		Ok(())
+	}
+
+	fn accept_directory<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Directory<'a>,
+	) -> punktf_lib::visit::Result {
+		Ok(())
+	}
+
+	fn accept_link(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Symlink,
+	) -> punktf_lib::visit::Result {
+		Ok(())
+	}
+
+	fn accept_rejected<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Rejected<'a>,
+	) -> punktf_lib::visit::Result {
+		Ok(())
+	}
+
+	fn accept_errored<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		_: &Errored<'a>,
+	) -> punktf_lib::visit::Result {
+		Ok(())
+	}
+}
+
+/// Creates a copy of `profile` which only contains the dotfile with `path`.
+///
+/// Hooks and links are removed, as they belong to the whole profile and not to
+/// a single dotfile.
+fn narrow_profile(profile: &LayeredProfile, path: &Path) -> LayeredProfile {
+	let mut profile = profile.clone();
+
+	profile.dotfiles.retain(|(_, dotfile)| dotfile.path == path);
+	profile.symlinks.clear();
+	profile.pre_hooks.clear();
+	profile.post_hooks.clear();
+	profile.assertions.clear();
+
+	profile
+}
+
+/// State of the dashboard.
+#[derive(Debug)]
+struct App {
+	/// The source from which the profiles are read.
+	source: PunktfSource,
+
+	/// Variables given on the command line.
+	vars: Vec<(String, String)>,
+
+	/// Names of all available profiles.
+	profiles: Vec<String>,
+
+	/// Selection state of the profile list.
+	profile_state: ListState,
+
+	/// The currently loaded profile.
+	profile: Option<LayeredProfile>,
+
+	/// The dotfiles of the currently loaded profile.
+	entries: Vec<Entry>,
+
+	/// Selection state of the dotfile list.
+	entry_state: ListState,
+
+	/// Pane which currently receives the input.
+	focus: Focus,
+
+	/// Scroll offset of the detail pane.
+	scroll: u16,
+
+	/// Message displayed in the status bar.
+	message: String,
+
+	/// Set if the user requested an undeploy which still needs to be
+	/// confirmed.
+	confirm_undeploy: bool,
+
+	/// Set once the user wants to exit the dashboard.
+	should_quit: bool,
+}
+
+impl App {
+	/// Creates a new instance and collects all available profiles of `source`.
+	fn new(source: PunktfSource, vars: Vec<(String, String)>) -> Result<Self> {
+		let mut profiles: Vec<String> = collect_profile_names(&source)?
+			.into_values()
+			.collect::<HashSet<_>>()
+			.into_iter()
+			.filter_map(|path| {
+				path.file_stem()
+					.and_then(|stem| stem.to_str())
+					.map(String::from)
+			})
+			.collect();
+		profiles.sort();
+
+		let mut profile_state = ListState::default();
+		if !profiles.is_empty() {
+			profile_state.select(Some(0));
+		}
+
+		Ok(Self {
+			source,
+			vars,
+			profiles,
+			profile_state,
+			profile: None,
+			entries: Vec::new(),
+			entry_state: ListState::default(),
+			focus: Focus::Profiles,
+			scroll: 0,
+			message: String::from("Select a profile with <enter>"),
+			confirm_undeploy: false,
+			should_quit: false,
+		})
+	}
+
+	/// Processes terminal events until the user quits.
+	fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+		while !self.should_quit {
+			terminal.draw(|frame| self.draw(frame))?;
+
+			if let TermEvent::Key(key) = event::read()? {
+				if key.kind == KeyEventKind::Press {
+					self.handle_key(key.code);
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	/// Updates the state depending on the pressed key.
+	fn handle_key(&mut self, code: KeyCode) {
+		let confirm_undeploy = std::mem::take(&mut self.confirm_undeploy);
+
+		match code {
+			KeyCode::Char('q') | KeyCode::Esc => self.should_quit = true,
+			KeyCode::Tab | KeyCode::Left | KeyCode::Right | KeyCode::Char('h' | 'l') => {
+				self.focus = match self.focus {
+					Focus::Profiles => Focus::Dotfiles,
+					Focus::Dotfiles => Focus::Profiles,
+				};
+			}
+			KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+			KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+			KeyCode::PageDown => self.scroll = self.scroll.saturating_add(10),
+			KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+			KeyCode::Enter if self.focus == Focus::Profiles => {
+				self.load_profile();
+				self.focus = Focus::Dotfiles;
+			}
+			KeyCode::Char('r') => self.refresh(),
+			KeyCode::Char('d') => self.deploy_selected(),
+			KeyCode::Char('u') if confirm_undeploy => self.undeploy_selected(),
+			KeyCode::Char('u') => {
+				if let Some(entry) = self.selected_entry() {
+					self.message = format!(
+						"Press `u` again to remove the deployed files of `{}` which are in sync",
+						entry.path.display()
+					);
+					self.confirm_undeploy = true;
+				}
+			}
+			_ => {}
+		}
+	}
+
+	/// Moves the selection of the focused list by `delta`.
+	fn move_selection(&mut self, delta: isize) {
+		let (state, len) = match self.focus {
+			Focus::Profiles => (&mut self.profile_state, self.profiles.len()),
+			Focus::Dotfiles => (&mut self.entry_state, self.entries.len()),
+		};
+
+		if len == 0 {
+			return;
+		}
+
+		let current = state.selected().unwrap_or(0) as isize;
+		let next = (current + delta).clamp(0, len as isize - 1);
+		state.select(Some(next as usize));
+
+		self.scroll = 0;
+	}
+
+	/// Returns the currently selected dotfile.
+	fn selected_entry(&self) -> Option<&Entry> {
+		self.entry_state
+			.selected()
+			.and_then(|idx| self.entries.get(idx))
+	}
+
+	/// Resolves the selected profile and computes the drift of its dotfiles.
+	fn load_profile(&mut self) {
+		let Some(name) = self
+			.profile_state
+			.selected()
+			.and_then(|idx| self.profiles.get(idx))
+			.cloned()
+		else {
+			return;
+		};
+
+		match crate::setup_profile(&name, &self.source, None, &self.vars) {
+			Ok(profile) => {
+				crate::setup_env(&self.source, &profile, &name);
+
+				self.profile = Some(profile);
+				self.refresh();
+				self.entry_state
+					.select((!self.entries.is_empty()).then_some(0));
+				self.message = format!("Loaded profile `{name}`");
+			}
+			Err(err) => {
+				self.profile = None;
+				self.entries.clear();
+				self.entry_state.select(None);
+				self.message = format!("Failed to load profile `{name}`: {err}");
+			}
+		}
+	}
+
+	/// Recomputes the drift for all dotfiles of the loaded profile.
+	fn refresh(&mut self) {
+		let Some(profile) = &self.profile else {
+			return;
+		};
+
+		if profile.target_path().is_none() {
+			self.entries.clear();
+			self.message = String::from("The profile has no target path set");
+			return;
+		}
+
+		self.entries = profile
+			.dotfiles()
+			.map(|dotfile| {
+				let mut narrowed = narrow_profile(profile, &dotfile.path);
+
+				let mut collector = FileCollector::default();
+				if let Err(err) = Walker::new(&mut narrowed).walk(&self.source, &mut collector) {
+					tracing::error!("Failed to collect files: {err}");
+				}
+
+				let drifts = RefCell::new(Vec::new());
+				Diff::new(|event| drifts.borrow_mut().push(Drift::from(event)))
+					.diff(&self.source, &mut narrowed);
+
+				Entry {
+					path: dotfile.path.clone(),
+					files: collector.0,
+					drifts: drifts.into_inner(),
+				}
+			})
+			.collect();
+
+		self.scroll = 0;
+	}
+
+	/// Deploys the selected dotfile.
+	fn deploy_selected(&mut self) {
+		let (Some(profile), Some(entry)) = (&self.profile, self.selected_entry()) else {
+			return;
+		};
+
+		let path = entry.path.clone();
+		let mut narrowed = narrow_profile(profile, &path);
+
+		// The terminal is in use by the dashboard, so dotfiles with merge mode
+		// `Ask` are skipped.
+		let deployment = Deployer::new(DeployOptions::default(), |_, _| Ok(false))
+			.deploy(&self.source, &mut narrowed);
+
+		let statuses = || deployment.dotfiles().values().map(|d| d.status());
+		let success = statuses().filter(|s| s.is_success()).count();
+		let skipped = statuses().filter(|s| s.is_skipped()).count();
+		let failed = statuses().filter(|s| s.is_failed()).count();
+
+		self.message = format!(
+			"Deployed `{}`: {success} succeeded, {skipped} skipped, {failed} failed",
+			path.display()
+		);
+
+		self.refresh();
+	}
+
+	/// Removes all deployed files of the selected dotfile which are in sync
+	/// with the source.
+	///
+	/// Files which differ from the source are never removed, as they would
+	/// contain changes which would otherwise be lost.
+	fn undeploy_selected(&mut self) {
+		let Some(entry) = self.selected_entry() else {
+			return;
+		};
+
+		let drifted: HashSet<&Path> = entry.drifts.iter().map(Drift::target_path).collect();
+
+		let mut removed = 0;
+		let mut errors = Vec::new();
+
+		for file in entry
+			.files
+			.iter()
+			.filter(|f| f.is_file() && !drifted.contains(f.as_path()))
+		{
+			match std::fs::remove_file(file) {
+				Ok(_) => removed += 1,
+				Err(err) => errors.push(format!("{}: {err}", file.display())),
+			}
+		}
+
+		self.message = if errors.is_empty() {
+			format!("Undeployed `{}`: {removed} removed", entry.path.display())
+		} else {
+			format!(
+				"Undeployed `{}`: {removed} removed, failed: {}",
+				entry.path.display(),
+				errors.join("; ")
+			)
+		};
+
+		self.refresh();
+	}
+
+	/// Draws the whole dashboard.
+	fn draw(&mut self, frame: &mut Frame<'_>) {
+		let [main, status] =
+			Layout::vertical([Constraint::Min(3), Constraint::Length(3)]).areas(frame.area());
+		let [profiles, dotfiles, details] = Layout::horizontal([
+			Constraint::Percentage(20),
+			Constraint::Percentage(35),
+			Constraint::Percentage(45),
+		])
+		.areas(main);
+
+		let highlight = Style::default()
+			.add_modifier(Modifier::REVERSED)
+			.add_modifier(Modifier::BOLD);
+
+		let profile_list = List::new(self.profiles.iter().map(|p| ListItem::new(p.as_str())))
+			.block(self.pane("Profiles", Focus::Profiles))
+			.highlight_style(highlight);
+		frame.render_stateful_widget(profile_list, profiles, &mut self.profile_state);
+
+		let entry_list = List::new(self.entries.iter().map(|entry| {
+			let (summary, color) = entry.summary();
+			ListItem::new(Line::from(vec![
+				Span::raw(entry.path.display().to_string()),
+				Span::raw(" "),
+				Span::styled(format!("[{summary}]"), Style::default().fg(color)),
+			]))
+		}))
+		.block(self.pane("Dotfiles", Focus::Dotfiles))
+		.highlight_style(highlight);
+		frame.render_stateful_widget(entry_list, dotfiles, &mut self.entry_state);
+
+		let detail = Paragraph::new(self.detail_lines())
+			.block(Block::default().borders(Borders::ALL).title("Drift"))
+			.scroll((self.scroll, 0));
+		frame.render_widget(detail, details);
+
+		let help = "q: quit | tab: switch pane | enter: load profile | d: deploy | u: undeploy | r: refresh | pgup/pgdn: scroll";
+		let status_bar = Paragraph::new(vec![
+			Line::from(self.message.as_str()),
+			Line::styled(help, Style::default().fg(Color::DarkGray)),
+		])
+		.block(Block::default().borders(Borders::TOP));
+		frame.render_widget(status_bar, status);
+	}
+
+	/// Creates the surrounding block of a list pane.
+	fn pane(&self, title: &'static str, focus: Focus) -> Block<'static> {
+		let style = if self.focus == focus {
+			Style::default().fg(Color::Cyan)
+		} else {
+			Style::default()
+		};
+
+		Block::default()
+			.borders(Borders::ALL)
+			.border_style(style)
+			.title(title)
+	}
+
+	/// Creates the lines for the detail pane of the selected dotfile.
+	fn detail_lines(&self) -> Vec<Line<'static>> {
+		let Some(entry) = self.selected_entry() else {
+			return Vec::new();
+		};
+
+		if entry.drifts.is_empty() {
+			return vec![Line::styled(
+				format!("{} file(s) in sync", entry.files.len()),
+				Style::default().fg(Color::Green),
+			)];
+		}
+
+		let mut lines = Vec::new();
+
+		for drift in &entry.drifts {
+			match drift {
+				Drift::NewFile { target_path } => lines.push(Line::styled(
+					format!("new file: {}", target_path.display()),
+					Style::default().fg(Color::Cyan),
+				)),
+				Drift::NewDirectory { target_path } => lines.push(Line::styled(
+					format!("new directory: {}", target_path.display()),
+					Style::default().fg(Color::Cyan),
+				)),
+				Drift::Changed {
+					target_path,
+					old_content,
+					new_content,
+				} => {
+					lines.push(Line::styled(
+						format!("changed: {}", target_path.display()),
+						Style::default()
+							.fg(Color::Yellow)
+							.add_modifier(Modifier::BOLD),
+					));
+
+					let diff = TextDiff::from_lines(old_content, new_content);
+
+					for change in diff.iter_all_changes() {
+						let (sign, style) = match change.tag() {
+							ChangeTag::Delete => ("-", Style::default().fg(Color::Red)),
+							ChangeTag::Insert => ("+", Style::default().fg(Color::Green)),
+							ChangeTag::Equal => (" ", Style::default().fg(Color::DarkGray)),
+						};
+
+						lines.push(Line::styled(
+							format!("{sign} {}", change.value().trim_end_matches(['\r', '\n'])),
+							style,
+						));
+					}
+				}
+			}
+		}
+
+		lines
+	}
+}
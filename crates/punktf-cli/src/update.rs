@@ -0,0 +1,269 @@
+//! Updating of the running `punktf` binary from the GitHub releases.
+//!
+//! Each release contains an archive for every supported target
+//! (`punktf-<target>.tar.gz` or `punktf-<target>.zip`) together with its
+//! [minisign](https://jedisct1.github.io/minisign/) signature
+//! (`<archive>.minisig`). The trusted comment of a signature names the version
+//! and the archive it belongs to (`punktf <version> <archive>`), so signed
+//! archives of other releases or targets are rejected as well.
+
+use std::io::Read as _;
+use std::path::Path;
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use minisign_verify::{PublicKey, Signature};
+use serde::Deserialize;
+
+/// Url of the api endpoint for the latest release.
+const LATEST_RELEASE_URL: &str = "https://api.github.com/repos/Shemnei/punktf/releases/latest";
+
+/// Minisign public key (base64) with which the release archives are signed.
+///
+/// It is embedded at build time from the `PUNKTF_UPDATE_PUBLIC_KEY`
+/// environment variable. Builds without a key can not verify and therefore not
+/// install updates.
+const PUBLIC_KEY: Option<&str> = option_env!("PUNKTF_UPDATE_PUBLIC_KEY");
+
+/// Maximum size in bytes of a downloaded release asset.
+const MAX_ASSET_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Name of the release target for the current platform.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+const TARGET: Option<&str> = Some("x86_64-unknown-linux-musl");
+
+/// Name of the release target for the current platform.
+#[cfg(all(windows, target_arch = "x86_64", feature = "windows-static"))]
+const TARGET: Option<&str> = Some("x86_64-pc-windows-msvc-static");
+
+/// Name of the release target for the current platform.
+#[cfg(all(windows, target_arch = "x86_64", not(feature = "windows-static")))]
+const TARGET: Option<&str> = Some("x86_64-pc-windows-msvc");
+
+/// Name of the release target for the current platform.
+#[cfg(all(windows, target_arch = "x86"))]
+const TARGET: Option<&str> = Some("i686-pc-windows-msvc");
+
+/// Name of the release target for the current platform.
+#[cfg(not(any(
+	all(target_os = "linux", target_arch = "x86_64"),
+	all(windows, any(target_arch = "x86_64", target_arch = "x86"))
+)))]
+const TARGET: Option<&str> = None;
+
+/// A release as returned by the GitHub api.
+#[derive(Debug, Deserialize)]
+struct Release {
+	/// Tag of the release (e.g. `v3.1.1`).
+	tag_name: String,
+
+	/// All files attached to the release.
+	assets: Vec<Asset>,
+}
+
+impl Release {
+	/// Returns the version of the release without the `v` prefix.
+	fn version(&self) -> &str {
+		self.tag_name.trim_start_matches('v')
+	}
+
+	/// Returns the asset with the given `name`.
+	fn asset(&self, name: &str) -> Result<&Asset> {
+		self.assets
+			.iter()
+			.find(|asset| asset.name == name)
+			.ok_or_else(|| eyre!("Release {} has no asset {name}", self.tag_name))
+	}
+}
+
+/// A file attached to a release.
+#[derive(Debug, Deserialize)]
+struct Asset {
+	/// File name of the asset.
+	name: String,
+
+	/// Url from which the asset can be downloaded.
+	browser_download_url: String,
+}
+
+/// Sends a GET request to `url` and returns the response body.
+fn download(url: &str) -> Result<Vec<u8>> {
+	tracing::debug!("Downloading {url}");
+
+	ureq::get(url)
+		.header("User-Agent", concat!("punktf/", env!("CARGO_PKG_VERSION")))
+		.call()
+		.wrap_err_with(|| format!("Failed to download {url}"))?
+		.body_mut()
+		.with_config()
+		.limit(MAX_ASSET_SIZE)
+		.read_to_vec()
+		.wrap_err_with(|| format!("Failed to download {url}"))
+}
+
+/// Fetches the latest release.
+fn latest_release() -> Result<Release> {
+	let body = download(LATEST_RELEASE_URL)?;
+	serde_json::from_slice(&body).wrap_err("Failed to parse latest release")
+}
+
+/// Parses the numeric components of a version (e.g. `3.1.1` to `[3, 1, 1]`).
+///
+/// Any pre-release or build suffix is ignored.
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+	version
+		.split(['-', '+'])
+		.next()?
+		.split('.')
+		.map(|part| part.parse().ok())
+		.collect()
+}
+
+/// Verifies that `signature` (as published in the `.minisig` release assets)
+/// is a valid signature of `data` by `public_key` and that its trusted comment
+/// is `expected_comment`.
+fn verify_signature(
+	data: &[u8],
+	signature: &[u8],
+	public_key: &str,
+	expected_comment: &str,
+) -> Result<()> {
+	let public_key = PublicKey::from_base64(public_key.trim())
+		.map_err(|err| eyre!("Invalid update public key: {err}"))?;
+
+	let signature = std::str::from_utf8(signature)
+		.map_err(|err| eyre!(err))
+		.and_then(|signature| Signature::decode(signature).map_err(|err| eyre!(err)))
+		.wrap_err("Invalid signature")?;
+
+	public_key
+		.verify(data, &signature, false)
+		.map_err(|err| eyre!("Invalid signature: {err}"))?;
+
+	let comment = signature.trusted_comment();
+	if comment != expected_comment {
+		return Err(eyre!(
+			"Signature belongs to `{comment}` instead of `{expected_comment}`"
+		));
+	}
+
+	Ok(())
+}
+
+/// Extracts the file `path` from the `tar.gz` or `zip` archive `name`.
+fn extract(name: &str, archive: &[u8], path: &str) -> Result<Vec<u8>> {
+	let mut binary = Vec::new();
+
+	if name.ends_with(".zip") {
+		let mut archive = zip::ZipArchive::new(std::io::Cursor::new(archive))?;
+		archive.by_name(path)?.read_to_end(&mut binary)?;
+		return Ok(binary);
+	}
+
+	let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(archive));
+	for entry in archive.entries()? {
+		let mut entry = entry?;
+		if entry.path()? == Path::new(path) {
+			entry.read_to_end(&mut binary)?;
+			return Ok(binary);
+		}
+	}
+
+	Err(eyre!("Archive {name} does not contain {path}"))
+}
+
+/// Replaces the running binary with the given `binary`.
+fn replace_binary(binary: &[u8]) -> Result<()> {
+	let path = std::env::temp_dir().join(format!("punktf-update-{}", std::process::id()));
+
+	std::fs::write(&path, binary)
+		.wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt as _;
+		std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+	}
+
+	let result = self_replace::self_replace(&path).wrap_err("Failed to replace the binary");
+	let _ = std::fs::remove_file(&path);
+	result
+}
+
+/// Checks for a newer release and, unless `check` is set, installs it.
+///
+/// The minisign signature of the downloaded archive is verified with the
+/// [public key](`PUBLIC_KEY`) embedded in the binary before the running binary
+/// is replaced. If `force` is set, the latest release is installed even if it
+/// is not newer.
+///
+/// # Errors
+///
+/// An error is returned if there is no release for the current platform or if
+/// the release could not be downloaded, verified or installed.
+pub fn run(check: bool, force: bool) -> Result<()> {
+	let target = TARGET.ok_or_else(|| eyre!("There are no prebuilt releases for this platform"))?;
+
+	let current = env!("CARGO_PKG_VERSION");
+	let release = latest_release()?;
+
+	let is_newer = match (parse_version(release.version()), parse_version(current)) {
+		(Some(latest), Some(current)) => latest > current,
+		_ => release.version() != current,
+	};
+
+	if !is_newer && !force {
+		println!("punktf {current} is up to date");
+		return Ok(());
+	}
+
+	if check {
+		println!(
+			"punktf {} is available (installed: {current})",
+			release.version()
+		);
+		return Ok(());
+	}
+
+	let (archive_name, binary_name) = if cfg!(windows) {
+		(format!("punktf-{target}.zip"), "punktf.exe")
+	} else {
+		(format!("punktf-{target}.tar.gz"), "punktf")
+	};
+
+	let public_key = PUBLIC_KEY.ok_or_else(|| {
+		eyre!(
+			"This build of punktf has no key to verify updates with. Update it the same way \
+			 it was installed"
+		)
+	})?;
+
+	let archive_asset = release.asset(&archive_name)?;
+	let signature_asset = release.asset(&format!("{archive_name}.minisig"))?;
+
+	println!("Downloading punktf {}...", release.version());
+
+	let archive = download(&archive_asset.browser_download_url)?;
+	let signature = download(&signature_asset.browser_download_url)?;
+
+	verify_signature(
+		&archive,
+		&signature,
+		public_key,
+		&format!("punktf {} {archive_name}", release.version()),
+	)
+	.wrap_err_with(|| format!("Failed to verify {archive_name}"))?;
+
+	let binary = extract(
+		&archive_name,
+		&archive,
+		&format!("punktf-{target}/{binary_name}"),
+	)
+	.wrap_err_with(|| format!("Failed to extract {archive_name}"))?;
+
+	replace_binary(&binary)?;
+
+	println!("Updated punktf from {current} to {}", release.version());
+
+	Ok(())
+}
@@ -1,14 +1,23 @@
 //! Various utility functions.
 
 use std::{
-	collections::HashMap,
+	collections::BTreeMap,
+	io::IsTerminal as _,
 	path::{Path, PathBuf},
+	sync::{atomic::AtomicBool, Arc},
 };
 
-use color_eyre::owo_colors::OwoColorize;
+use color_eyre::eyre::{eyre, Context};
 use color_eyre::Result;
-use log::Level;
-use punktf_lib::visit::deploy::deployment::{Deployment, DeploymentStatus, ItemStatus};
+use console::Style;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::FuzzySelect;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::variables::Variables;
+use punktf_lib::profile::{collect_profile_names, Profile};
+use punktf_lib::visit::deploy::cancel::CancellationToken;
+use punktf_lib::visit::deploy::deployment::{Deployment, DeploymentStatus, ItemChange, ItemStatus};
+use signal_hook::consts::SIGINT;
 
 /// Retrieves the target path for the deployment by reading the environment
 /// variable with the name determined by [`super::PUNKTF_TARGET_ENVVAR`].
@@ -16,6 +25,174 @@ pub fn get_target_path() -> Option<PathBuf> {
 	std::env::var_os(super::PUNKTF_TARGET_ENVVAR).map(|val| val.into())
 }
 
+/// Returns a token which is cancelled once the user presses Ctrl-C.
+///
+/// Pressing Ctrl-C a second time terminates punktf immediately.
+pub fn cancel_on_interrupt() -> CancellationToken {
+	let flag = Arc::new(AtomicBool::new(false));
+
+	// The shutdown handler is registered first, so it only fires if the flag
+	// was already set by a previous interrupt
+	let registered =
+		signal_hook::flag::register_conditional_shutdown(SIGINT, 130, Arc::clone(&flag))
+			.and_then(|_| signal_hook::flag::register(SIGINT, Arc::clone(&flag)));
+
+	if let Err(err) = registered {
+		tracing::warn!("Failed to register the interrupt handler ({err})");
+	}
+
+	CancellationToken::from(flag)
+}
+
+/// Collects the template variables set by environment variables with the
+/// prefix [`PUNKTF_VAR_ENVVAR_PREFIX`](`super::PUNKTF_VAR_ENVVAR_PREFIX`).
+///
+/// Returns `None` if no such variable is set.
+pub fn get_variables() -> Option<Variables> {
+	let vars = std::env::vars_os()
+		.filter_map(|(name, value)| {
+			name.to_str()?
+				.strip_prefix(super::PUNKTF_VAR_ENVVAR_PREFIX)
+				.filter(|name| !name.is_empty())
+				.map(|name| (name.to_owned(), value.to_string_lossy().into_owned()))
+		})
+		.collect::<Vec<_>>();
+
+	(!vars.is_empty()).then(|| Variables::from_items(vars))
+}
+
+/// Checks if `punktf` runs in an interactive session, meaning the user is able
+/// to answer prompts.
+pub fn is_interactive() -> bool {
+	std::io::stdin().is_terminal() && std::io::stderr().is_terminal()
+}
+
+/// Asks the user the yes/no `question` on stdout.
+///
+/// Anything but `y`/`yes` (including an empty answer) is treated as no.
+pub fn confirm(question: &str) -> Result<bool> {
+	use std::io::Write;
+
+	let mut stdout = std::io::stdout();
+	stdout.write_all(format!("{question} [y/N]: ").as_bytes())?;
+	stdout.flush()?;
+
+	let mut line = String::new();
+	std::io::stdin().read_line(&mut line)?;
+
+	Ok(matches!(
+		line.trim().to_ascii_lowercase().as_str(),
+		"y" | "yes"
+	))
+}
+
+/// Opens `path` in the editor of the user and waits until it exits.
+///
+/// The editor is taken from the environment variables `VISUAL` or `EDITOR`
+/// and may contain arguments (e.g. `code --wait`). If neither is set, `vi`
+/// (`notepad` on windows) is used.
+///
+/// # Errors
+///
+/// An error is returned if the editor could not be started or did not exit
+/// successfully.
+pub fn open_editor(path: &Path) -> Result<()> {
+	let editor = ["VISUAL", "EDITOR"]
+		.into_iter()
+		.filter_map(std::env::var_os)
+		.map(|editor| editor.to_string_lossy().into_owned())
+		.find(|editor| !editor.trim().is_empty())
+		.unwrap_or_else(|| String::from(if cfg!(windows) { "notepad" } else { "vi" }));
+
+	let mut args = editor.split_whitespace();
+	let program = args.next().unwrap_or_default();
+
+	let status = std::process::Command::new(program)
+		.args(args)
+		.arg(path)
+		.status()
+		.wrap_err_with(|| format!("Failed to start editor `{editor}`"))?;
+
+	if status.success() {
+		Ok(())
+	} else {
+		Err(eyre!("Editor `{editor}` exited with {status}"))
+	}
+}
+
+/// Presents a fuzzy searchable list of all profiles found in the `profiles`
+/// directory of `source` and lets the user select one of them.
+///
+/// Each entry shows the name of the profile together with its aliases, the
+/// target and the description of the profile if they are set.
+///
+/// # Errors
+///
+/// An error is returned if no profiles were found or the user aborted the
+/// selection.
+pub fn select_profile(source: &PunktfSource) -> Result<String> {
+	let mut profiles: BTreeMap<PathBuf, Vec<String>> = BTreeMap::new();
+
+	for (name, path) in collect_profile_names(source)? {
+		profiles.entry(path).or_default().push(name);
+	}
+
+	if profiles.is_empty() {
+		return Err(eyre!(
+			"No profiles found in `{}`",
+			source.profiles().display()
+		));
+	}
+
+	let mut names = Vec::with_capacity(profiles.len());
+	let mut items = Vec::with_capacity(profiles.len());
+
+	for (path, mut aliases) in profiles {
+		let name = path
+			.file_stem()
+			.and_then(|stem| stem.to_str())
+			.map(String::from)
+			.unwrap_or_else(|| aliases[0].clone());
+
+		aliases.retain(|alias| alias != &name);
+		aliases.sort();
+
+		let mut item = name.clone();
+
+		if !aliases.is_empty() {
+			item.push_str(&format!(" ({})", aliases.join(", ")));
+		}
+
+		match Profile::from_file(&path) {
+			Ok(profile) => {
+				if let Some(target) = profile.target {
+					item.push_str(&format!(" => {}", target.display()));
+				}
+
+				if let Some(description) = profile.description {
+					item.push_str(&format!(" - {description}"));
+				}
+			}
+			Err(err) => {
+				tracing::debug!("[{}] Failed to read profile: {err}", path.display());
+			}
+		}
+
+		names.push(name);
+		items.push(item);
+	}
+
+	let selection = FuzzySelect::with_theme(&ColorfulTheme::default())
+		.with_prompt("Select a profile")
+		.items(&items)
+		.default(0)
+		.interact_opt()?;
+
+	selection
+		.map(|idx| names.swap_remove(idx))
+		.ok_or_else(|| eyre!("No profile selected"))
+}
+
 /// Function which get's called when a merge conflict arises and the merge mode
 /// of the [dotfile](`punktf_lib::profile::dotfile::Dotfile`) is set to
 /// [MergeMode::Ask](`punktf_lib::profile::MergeMode::Ask`). The function will
@@ -56,322 +233,340 @@ pub fn ask_user_merge(source_path: &Path, deploy_path: &Path) -> Result<bool> {
 	}
 }
 
-/// Outputs the given message `s`.
-///
-/// If `print` is `false` all messages will be logged with the `log` create,
-/// otherwise `stdout` is used.
-///
-/// # NOTE
-/// This will also clear the output.
-/// This is needed to reuse the same buffer `s` but log it with different log levels.
-fn output_and_clear(print: bool, s: &mut String, level: Level) {
-	if !s.is_empty() {
-		if print {
-			println!("{s}");
-		} else {
-			log::log!(level, "{}", s);
-		}
+/// Group of an item in the deployment table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Group {
+	/// The target was created.
+	Created,
 
-		s.clear();
-	}
-}
+	/// The target was changed.
+	Updated,
 
-/// A struct to hold information about the count of deployed items per status.
-///
-/// This is used for dotfiles by [`log_dotfiles`] and for links by [`log_links`].
-#[derive(Debug, Clone, Copy)]
-struct DeployCounts {
-	/// Amount of deployed items that succeeded.
-	success: usize,
+	/// The target already had the same contents.
+	Unchanged,
 
-	/// Amount of deployed items that were skipped.
-	skipped: usize,
+	/// The item was skipped.
+	Skipped,
 
-	/// Amount of deployed items that failed.
-	failed: usize,
+	/// The item failed to deploy.
+	Failed,
 }
 
-/// Iterates for all `items` with a status of
-/// [`ItemStatus::Success`](`punktf_lib::visit::deploy::deployment::ItemStatus::Success`).
-/// For each of them, a formatting function `fmt_fn` is called.
-///
-/// At the end, the complete result is printend and the total count of processed
-/// items is returned.
-fn log_success<T, F>(
-	out: &mut String,
-	print: bool,
-	item_name: &str,
-	items: &HashMap<PathBuf, T>,
-	fmt_fn: F,
-) -> usize
-where
-	T: AsRef<ItemStatus>,
-	F: Fn(&Path, &T) -> String,
-{
-	let mut item_count = 0;
-	for (idx, (path, item)) in items
-		.iter()
-		.filter(|(_, item)| item.as_ref().is_success())
-		.enumerate()
-	{
-		if idx == 0 {
-			out.push_str(&format!("{} ({})", item_name, "SUCCESS".green()));
+impl Group {
+	/// All groups in the order they are displayed.
+	pub const ALL: [Self; 5] = [
+		Self::Created,
+		Self::Updated,
+		Self::Unchanged,
+		Self::Skipped,
+		Self::Failed,
+	];
+
+	/// Determines the group of an item from its status and change.
+	const fn of(status: &ItemStatus, change: Option<ItemChange>) -> Self {
+		match (status, change) {
+			(ItemStatus::Failed(_), _) => Self::Failed,
+			(ItemStatus::Skipped(_) | ItemStatus::Cancelled, _) => Self::Skipped,
+			(ItemStatus::Success, Some(ItemChange::Created)) => Self::Created,
+			(ItemStatus::Success, Some(ItemChange::Unchanged)) => Self::Unchanged,
+			// Deployments of older versions did not record the change
+			(ItemStatus::Success, _) => Self::Updated,
 		}
-
-		out.push_str(&fmt_fn(path, item));
-		item_count += 1;
 	}
 
-	output_and_clear(print, out, Level::Info);
+	/// Returns the name of the group.
+	pub const fn name(self) -> &'static str {
+		match self {
+			Self::Created => "created",
+			Self::Updated => "updated",
+			Self::Unchanged => "unchanged",
+			Self::Skipped => "skipped",
+			Self::Failed => "failed",
+		}
+	}
 
-	item_count
+	/// Returns the style used to display the group.
+	pub fn style(self) -> Style {
+		match self {
+			Self::Created => Style::new().green(),
+			Self::Updated => Style::new().yellow(),
+			Self::Unchanged => Style::new().dim(),
+			Self::Skipped => Style::new().cyan(),
+			Self::Failed => Style::new().red().bold(),
+		}
+	}
 }
 
-/// Iterates for all `items` with a status of
-/// [`ItemStatus::Skipped`](`punktf_lib::visit::deploy::deployment::ItemStatus::Skipped`).
-/// For each of them, a formatting function `fmt_fn` is called.
-///
-/// At the end, the complete result is printend and the total count of processed
-/// items is returned.
-fn log_skipped<T, F>(
-	out: &mut String,
-	print: bool,
-	item_name: &str,
-	items: &HashMap<PathBuf, T>,
-	fmt_fn: F,
-) -> usize
-where
-	T: AsRef<ItemStatus>,
-	F: Fn(&Path, &T, &str) -> String,
-{
-	let mut item_count = 0;
-	for (idx, (path, item, reason)) in items
-		.iter()
-		.filter_map(|(idx, item)| {
-			if let ItemStatus::Skipped(reason) = item.as_ref() {
-				Some((idx, item, reason))
-			} else {
-				None
-			}
-		})
-		.enumerate()
-	{
-		if idx == 0 {
-			out.push_str(&format!("{} ({})", item_name, "SKIPPED".yellow()));
-		}
+/// A single row of the deployment table.
+#[derive(Debug)]
+pub struct Row<'a> {
+	/// Group of the item.
+	pub group: Group,
 
-		out.push_str(&fmt_fn(path, item, reason));
-		item_count += 1;
-	}
+	/// Kind of the item.
+	pub kind: &'static str,
 
-	output_and_clear(print, out, Level::Info);
+	/// Target path of the item.
+	pub path: &'a Path,
 
-	item_count
+	/// Reason why the item was skipped or failed.
+	pub reason: Option<String>,
 }
 
-/// Iterates for all `items` with a status of
-/// [`ItemStatus::Failed`](`punktf_lib::visit::deploy::deployment::ItemStatus::Failed`).
-/// For each of them, a formatting function `fmt_fn` is called.
-///
-/// At the end, the complete result is printend and the total count of processed
-/// items is returned.
-fn log_failed<T, F>(
-	out: &mut String,
-	print: bool,
-	item_name: &str,
-	items: &HashMap<PathBuf, T>,
-	fmt_fn: F,
-) -> usize
-where
-	T: AsRef<ItemStatus>,
-	F: Fn(&Path, &T, &str) -> String,
-{
-	let mut item_count = 0;
-	for (idx, (path, item, reason)) in items
+/// Returns one row for each item of the
+/// [deployment](`punktf_lib::visit::deploy::deployment::Deployment`), sorted
+/// by group and path.
+pub fn deployment_rows(deployment: &Deployment) -> Vec<Row<'_>> {
+	let mut rows: Vec<Row<'_>> = deployment
+		.dotfiles()
 		.iter()
-		.filter_map(|(idx, item)| {
-			if let ItemStatus::Failed(reason) = item.as_ref() {
-				Some((idx, item, reason))
-			} else {
-				None
-			}
+		.map(|(path, dotfile)| Row {
+			group: Group::of(dotfile.status(), dotfile.change()),
+			kind: "file",
+			path,
+			reason: reason(dotfile.status()),
 		})
-		.enumerate()
-	{
-		if idx == 0 {
-			out.push_str(&format!("{} ({})", item_name, "FAILED".red()));
-		}
-
-		out.push_str(&fmt_fn(path, item, reason));
-		item_count += 1;
-	}
+		.chain(deployment.symlinks().iter().map(|(path, link)| Row {
+			group: Group::of(link.status(), link.change()),
+			kind: "link",
+			path,
+			reason: reason(link.status()),
+		}))
+		.collect();
 
-	output_and_clear(print, out, Level::Info);
+	rows.sort_by(|a, b| a.group.cmp(&b.group).then_with(|| a.path.cmp(b.path)));
 
-	item_count
+	rows
 }
 
-/// Logs all deployed dotfiles together with the status.
+/// Minimal width of the path column when truncating paths.
+const MIN_PATH_WIDTH: usize = 20;
+
+/// Truncates `s` from the left so that it fits into `width` columns.
 ///
-/// If `print` is `false` all messages will be logged with the `log` create,
-/// otherwise `stdout` is used.
-fn log_dotfiles(out: &mut String, deployment: &Deployment, print: bool) -> DeployCounts {
-	/// Name of item being processed.
-	/// Used for logging.
-	const ITEM_NAME: &str = "Dotfiles";
-
-	let files_success = log_success(out, print, ITEM_NAME, deployment.dotfiles(), |path, _| {
-		format!("\n\t{}", path.display().bright_black())
-	});
+/// Truncated text is prefixed with `…`.
+fn truncate_left(s: &str, width: usize) -> String {
+	if console::measure_text_width(s) <= width {
+		return s.to_string();
+	}
 
-	let files_skipped = log_skipped(
-		out,
-		print,
-		ITEM_NAME,
-		deployment.dotfiles(),
-		|path, _, reason| format!("\n\t{}: {}", path.display(), reason.bright_black()),
-	);
+	let mut kept = Vec::new();
+	let mut kept_width = 1;
 
-	let files_failed = log_failed(
-		out,
-		print,
-		ITEM_NAME,
-		deployment.dotfiles(),
-		|path, _, reason| format!("\n\t{}: {}", path.display(), reason.bright_black()),
-	);
+	for c in s.chars().rev() {
+		let c_width = console::measure_text_width(c.encode_utf8(&mut [0; 4]));
+		if kept_width + c_width > width {
+			break;
+		}
 
-	DeployCounts {
-		success: files_success,
-		skipped: files_skipped,
-		failed: files_failed,
+		kept_width += c_width;
+		kept.push(c);
 	}
+
+	std::iter::once('…').chain(kept.into_iter().rev()).collect()
 }
 
-/// Logs all deployed links together with the status.
+/// Prints the finished state of the
+/// [deployment](`punktf_lib::visit::deploy::deployment::Deployment`) as a
+/// table grouped by status (created, updated, unchanged, skipped and failed).
 ///
-/// If `print` is `false` all messages will be logged with the `log` create,
-/// otherwise `stdout` is used.
-fn log_links(out: &mut String, deployment: &Deployment, print: bool) -> DeployCounts {
-	/// Name of item being processed.
-	/// Used for logging.
-	const ITEM_NAME: &str = "Links";
-
-	let files_success = log_success(
-		out,
-		print,
-		ITEM_NAME,
-		deployment.symlinks(),
-		|path, link| {
-			format!(
-				"\n\t{} => {}",
-				link.source.display().bright_black(),
-				path.display().bright_black()
-			)
-		},
+/// Paths are truncated to fit the width of the terminal. At the end, the
+/// amount of items per group, the total time and the status of the deployment
+/// are printed.
+pub fn print_deployment(deployment: &Deployment) {
+	let rows = deployment_rows(deployment);
+
+	let width = console::Term::stdout()
+		.size_checked()
+		.map(|(_, cols)| usize::from(cols));
+
+	let mut out = format!(
+		"{}\n",
+		console::style(format!("{:<9}  {:<4}  Path", "Status", "Kind")).bold()
 	);
 
-	let files_skipped = log_skipped(
-		out,
-		print,
-		ITEM_NAME,
-		deployment.symlinks(),
-		|path, link, reason| {
-			format!(
-				"\n\t{} => {}: {}",
-				link.source.display().bright_black(),
-				path.display().bright_black(),
-				reason.bright_black()
-			)
-		},
-	);
+	for row in &rows {
+		let reason = row
+			.reason
+			.as_ref()
+			.map(|r| format!("  {r}"))
+			.unwrap_or_default();
+		let path = row.path.display().to_string();
+		let path = match width {
+			Some(width) => truncate_left(
+				&path,
+				width
+					.saturating_sub(17 + console::measure_text_width(&reason))
+					.max(MIN_PATH_WIDTH),
+			),
+			None => path,
+		};
 
-	let files_failed = log_failed(
-		out,
-		print,
-		ITEM_NAME,
-		deployment.symlinks(),
-		|path, link, reason| {
-			format!(
-				"\n\t{} => {}: {}",
-				link.source.display().bright_black(),
-				path.display().bright_black(),
-				reason.bright_black()
-			)
-		},
-	);
+		out.push_str(&format!(
+			"{}  {:<4}  {path}",
+			row.group
+				.style()
+				.apply_to(format!("{:<9}", row.group.name())),
+			row.kind,
+		));
+
+		if !reason.is_empty() {
+			out.push_str(&console::style(reason).dim().to_string());
+		}
 
-	DeployCounts {
-		success: files_success,
-		skipped: files_skipped,
-		failed: files_failed,
+		out.push('\n');
 	}
-}
 
-/// Logs the finished state of the
-/// [deployment](`punktf_lib::visit::deploy::deployment::Deployment`).
-/// If the `print` argument is `true` then stdout will be used, otherwise the
-/// crate [`log`] is used.
-/// This includes amount, state and the names of the deployed
-/// [dotfiles](`punktf_lib::profile::dotfile::Dotfile`) and also the total time
-/// the deployment took to execute.
-pub fn log_deployment(deployment: &Deployment, print: bool) {
-	let mut out = String::new();
-
-	let DeployCounts {
-		success: dotfiles_success,
-		skipped: dotfiles_skipped,
-		failed: dotfiles_failed,
-	} = log_dotfiles(&mut out, deployment, print);
-
-	let DeployCounts {
-		success: links_success,
-		skipped: links_skipped,
-		failed: links_failed,
-	} = log_links(&mut out, deployment, print);
+	if !deployment.assertions().is_empty() {
+		out.push_str(&format!("\n{}\n", console::style("Assertions").bold()));
+
+		for evaluated in deployment.assertions() {
+			let (mark, reason) = match evaluated.status() {
+				ItemStatus::Failed(reason) => (console::style("✗").red().bold(), Some(reason)),
+				_ => (console::style("✓").green(), None),
+			};
+
+			out.push_str(&format!("{mark} {}", evaluated.assertion()));
+
+			if let Some(reason) = reason {
+				out.push_str(&console::style(format!("  {reason}")).dim().to_string());
+			}
+
+			out.push('\n');
+		}
+	}
+
+	let counts = Group::ALL
+		.into_iter()
+		.map(|group| {
+			let count = rows.iter().filter(|row| row.group == group).count();
+			format!("{} {}", group.style().apply_to(count), group.name())
+		})
+		.collect::<Vec<_>>()
+		.join(", ");
+
+	out.push_str(&format!(
+		"\n{counts} in {:.2?}\n",
+		deployment.duration().unwrap_or_default()
+	));
 
 	match deployment.status() {
 		DeploymentStatus::Success => {
-			out.push_str(&format!("Status: {}", "SUCCESS".green()));
+			out.push_str(&format!("Status: {}", console::style("SUCCESS").green()));
 		}
 		DeploymentStatus::Failed(reason) => {
-			out.push_str(&format!("Status: {}\n\t{}", "FAILED".red(), reason));
+			out.push_str(&format!(
+				"Status: {}\n\t{}",
+				console::style("FAILED").red(),
+				reason
+			));
 		}
 	};
 
-	let dotfiles_total = dotfiles_success + dotfiles_skipped + dotfiles_failed;
-	let links_total = links_success + links_skipped + links_failed;
-
-	let elapsed = deployment
-		.duration()
-		.expect("Failed to get duration from deployment");
-
-	// NOTE: Needs to be indented like this to not mess up the final result.
-	let report = format!(
-		"
-Time            : {:?}
-{hruler}
-Files (deployed): {}
-Files (skipped) : {}
-Files (failed)  : {}
-Files (total)   : {}
-{hruler}
-Links (deployed): {}
-Links (skipped) : {}
-Links (failed)  : {}
-Links (total)   : {}",
-		elapsed,
-		dotfiles_success,
-		dotfiles_skipped,
-		dotfiles_failed,
-		dotfiles_total,
-		links_success,
-		links_skipped,
-		links_failed,
-		links_total,
-		hruler = "-".repeat(80).dimmed(),
+	println!("{out}");
+}
+
+/// Returns the reason of a skipped or failed item.
+fn reason(status: &ItemStatus) -> Option<String> {
+	match status {
+		ItemStatus::Success => None,
+		ItemStatus::Skipped(reason) => Some(reason.to_string()),
+		ItemStatus::Failed(err) => Some(err.to_string()),
+		ItemStatus::Cancelled => Some(String::from("Deployment was cancelled")),
+	}
+}
+
+/// Prints a single paragraph summary of the
+/// [deployment](`punktf_lib::visit::deploy::deployment::Deployment`).
+///
+/// It contains the amount of deployed, skipped and failed items, the time the
+/// deployment took and all failed items together with the reason.
+pub fn print_summary(deployment: &Deployment) {
+	let statuses: Vec<(&Path, &ItemStatus)> = deployment
+		.dotfiles()
+		.iter()
+		.map(|(path, dotfile)| (path.as_path(), &dotfile.status))
+		.chain(
+			deployment
+				.symlinks()
+				.iter()
+				.map(|(path, link)| (path.as_path(), &link.status)),
+		)
+		.collect();
+
+	let count = |f: fn(&ItemStatus) -> bool| statuses.iter().filter(|(_, s)| f(s)).count();
+
+	let mut failures: Vec<String> =
+		statuses
+			.iter()
+			.filter_map(|(path, status)| match status {
+				ItemStatus::Failed(reason) => Some(format!("{} ({reason})", path.display())),
+				_ => None,
+			})
+			.chain(deployment.assertions().iter().filter_map(
+				|evaluated| match evaluated.status() {
+					ItemStatus::Failed(reason) => {
+						Some(format!("{} ({reason})", evaluated.assertion()))
+					}
+					_ => None,
+				},
+			))
+			.collect();
+	failures.sort();
+
+	let mut summary = format!(
+		"{} deployed, {} skipped, {} failed in {:.2?}",
+		count(ItemStatus::is_success),
+		count(ItemStatus::is_skipped),
+		count(ItemStatus::is_failed),
+		deployment.duration().unwrap_or_default()
 	);
 
-	out.push_str(&report);
+	let cancelled = count(ItemStatus::is_cancelled);
+	if cancelled > 0 {
+		summary.push_str(&format!(", {cancelled} cancelled"));
+	}
+
+	if let DeploymentStatus::Failed(reason) = deployment.status() {
+		summary.push_str(&format!("; deployment failed: {reason}"));
+	}
+
+	if !failures.is_empty() {
+		summary.push_str(&format!("; failures: {}", failures.join(", ")));
+	}
+
+	println!("{summary}");
+}
 
-	output_and_clear(print, &mut out, Level::Info)
+/// Prints the `count` slowest dotfiles of the
+/// [deployment](`punktf_lib::visit::deploy::deployment::Deployment`) together
+/// with their compile time, write time and the amount of bytes written.
+pub fn print_timings(deployment: &Deployment, count: usize) {
+	let mut dotfiles: Vec<_> = deployment
+		.dotfiles()
+		.iter()
+		.map(|(path, dotfile)| (path, dotfile.metrics()))
+		.collect();
+
+	dotfiles.sort_by(|(a_path, a), (b_path, b)| {
+		b.total_time()
+			.cmp(&a.total_time())
+			.then_with(|| a_path.cmp(b_path))
+	});
+
+	println!(
+		"Slowest dotfiles:\n{:>12} {:>12} {:>12} {:>12}  Path",
+		"Total", "Compile", "Write", "Bytes"
+	);
+
+	for (path, metrics) in dotfiles.into_iter().take(count) {
+		println!(
+			"{:>12} {:>12} {:>12} {:>12}  {}",
+			format!("{:.2?}", metrics.total_time()),
+			format!("{:.2?}", metrics.compile_time),
+			format!("{:.2?}", metrics.write_time),
+			metrics.bytes_written,
+			path.display()
+		);
+	}
 }
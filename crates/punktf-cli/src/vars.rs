@@ -0,0 +1,110 @@
+//! Inspection of the layered variables of a profile (`punktf vars`).
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use console::style;
+use punktf_lib::profile::LayeredProfile;
+
+use crate::answers::ANSWERS_LAYER;
+use crate::{CLI_LAYER, ENV_VARIABLES_LAYER, PUNKTF_VAR_ENVVAR_PREFIX};
+
+/// Returns a description of the layer with the index `layer` from which the
+/// variable `name` comes.
+fn layer_name(profile: &LayeredProfile, profile_name: &str, layer: usize, name: &str) -> String {
+	match profile.profile_names.get(layer).map(String::as_str) {
+		Some(CLI_LAYER) => String::from("command line"),
+		Some(ENV_VARIABLES_LAYER) => format!("environment {PUNKTF_VAR_ENVVAR_PREFIX}{name}"),
+		Some(ANSWERS_LAYER) => String::from("prompt answer"),
+		Some(layer) if layer == profile_name => format!("profile {layer}"),
+		Some(layer) => format!("extended profile {layer}"),
+		None => String::from("<unknown>"),
+	}
+}
+
+/// Returns `value` or a placeholder if the value is `secret`.
+const fn mask(secret: bool, value: &str) -> &str {
+	if secret {
+		"********"
+	} else {
+		value
+	}
+}
+
+/// Prints all variables of the `profile` with the layer each value comes
+/// from, followed by the shadowed values and the values of dotfiles which
+/// override the variable.
+///
+/// Values of secret prompt variables are masked.
+///
+/// Returns the amount of printed variables.
+pub fn run(profile: &LayeredProfile, profile_name: &str) -> usize {
+	let variables = profile.variables();
+	let secrets: BTreeSet<&str> = profile
+		.prompt_variables()
+		.filter(|variable| variable.secret)
+		.map(|variable| variable.name.as_str())
+		.collect();
+
+	let mut dotfile_vars: BTreeMap<&str, Vec<_>> = BTreeMap::new();
+	for (_, dotfile) in &profile.dotfiles {
+		for (name, value) in dotfile.variables.iter().flat_map(|vars| &vars.inner) {
+			dotfile_vars
+				.entry(name.as_str())
+				.or_default()
+				.push((&dotfile.path, value));
+		}
+	}
+
+	let names = variables
+		.inner
+		.keys()
+		.map(String::as_str)
+		.chain(dotfile_vars.keys().copied())
+		.collect::<BTreeSet<_>>();
+
+	for name in &names {
+		let secret = secrets.contains(name);
+
+		match variables.inner.get(*name) {
+			Some((layer, value)) => println!(
+				"{} = {}  {}",
+				style(name).bold(),
+				style(mask(secret, value)).green(),
+				style(format!(
+					"({})",
+					layer_name(profile, profile_name, *layer, name)
+				))
+				.dim()
+			),
+			None => println!(
+				"{}  {}",
+				style(name).bold(),
+				style("(only set by dotfiles)").dim()
+			),
+		}
+
+		for (layer, value) in variables.shadowed.get(*name).into_iter().flatten() {
+			println!(
+				"  {} {}  {}",
+				style("shadowed").yellow(),
+				style(mask(secret, value)).dim().strikethrough(),
+				style(format!(
+					"({})",
+					layer_name(profile, profile_name, *layer, name)
+				))
+				.dim()
+			);
+		}
+
+		for (path, value) in dotfile_vars.get(name).into_iter().flatten() {
+			println!(
+				"  {} {}  {}",
+				style("dotfile").cyan(),
+				mask(secret, value),
+				style(format!("({})", path.display())).dim()
+			);
+		}
+	}
+
+	names.len()
+}
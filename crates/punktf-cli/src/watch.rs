@@ -0,0 +1,454 @@
+//! Watches the source directories and redeploys a profile on changes.
+//!
+//! Besides the source directories, the files from which variables are read
+//! (e.g. `from:file:~/.secrets`) and the answers to the prompt variables are
+//! watched as well. The profile is resolved anew for each deployment and only
+//! items whose output changed are written again.
+//!
+//! Optionally, the deployed files are watched too. A deployed file whose content
+//! no longer matches the deployed content was modified outside of punktf, which
+//! is reported (and repaired with a redeployment, depending on the
+//! [policy](`DriftPolicy`)).
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher as _};
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::visit::deploy::cache::ContentHash;
+use punktf_lib::visit::deploy::deployment::{Deployment, DeploymentStatus};
+use punktf_lib::visit::deploy::{DeployOptions, Deployer};
+
+use crate::answers::Answers;
+use crate::config::Config;
+use crate::opt::DriftPolicy;
+use crate::{notification, util};
+
+/// Options of the watch mode.
+#[derive(Debug)]
+pub struct WatchOptions {
+	/// Name of the profile to deploy.
+	pub profile_name: String,
+
+	/// Alternative deployment target path.
+	pub target: Option<PathBuf>,
+
+	/// Variables given on the command line.
+	pub vars: Vec<(String, String)>,
+
+	/// Time to wait for further changes before redeploying.
+	pub debounce: Duration,
+
+	/// Show a desktop notification after each deployment.
+	pub notify: bool,
+
+	/// Only print a short summary of each deployment.
+	pub quiet: bool,
+
+	/// Also watch the deployed files and react to modifications outside of
+	/// punktf.
+	pub drift: Option<DriftPolicy>,
+}
+
+/// Files outside of the source directories which are watched.
+#[derive(Debug, Default)]
+struct WatchedFiles {
+	/// Files whose changes trigger a redeployment.
+	external: BTreeSet<PathBuf>,
+
+	/// Deployed files by the hash of their content after the last deployment.
+	targets: BTreeMap<PathBuf, ContentHash>,
+
+	/// The directories of the watched files.
+	///
+	/// These are watched instead of the files themselves, as editors often
+	/// replace a file when saving it.
+	dirs: BTreeSet<PathBuf>,
+}
+
+impl WatchedFiles {
+	/// Watches `external` and `targets` instead of the previously watched
+	/// files.
+	///
+	/// The directories of files inside of the source directories are not
+	/// watched again, as these are already watched.
+	fn update(
+		&mut self,
+		watcher: &mut RecommendedWatcher,
+		source: &PunktfSource,
+		external: Vec<PathBuf>,
+		targets: BTreeMap<PathBuf, ContentHash>,
+	) {
+		let roots: Vec<_> = source
+			.layers()
+			.map(|layer| {
+				layer
+					.root()
+					.canonicalize()
+					.unwrap_or_else(|_| layer.root().to_path_buf())
+			})
+			.collect();
+
+		let external: BTreeSet<_> = external
+			.iter()
+			.map(|file| event_path(file))
+			.filter(|file| !roots.iter().any(|root| file.starts_with(root)))
+			.collect();
+
+		let targets: BTreeMap<_, _> = targets
+			.into_iter()
+			.map(|(file, hash)| (event_path(&file), hash))
+			.collect();
+
+		let dirs: BTreeSet<_> = external
+			.iter()
+			.chain(targets.keys())
+			.filter_map(|file| file.parent())
+			.filter(|dir| !roots.iter().any(|root| dir.starts_with(root)))
+			.map(Path::to_path_buf)
+			.collect();
+
+		for dir in self.dirs.difference(&dirs) {
+			if let Err(err) = watcher.unwatch(dir) {
+				tracing::debug!("Failed to stop watching {}: {err}", dir.display());
+			}
+		}
+
+		for dir in dirs.difference(&self.dirs) {
+			tracing::debug!("Watching {}", dir.display());
+
+			if let Err(err) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+				tracing::warn!("Failed to watch {}: {err}", dir.display());
+			}
+		}
+
+		self.external = external;
+		self.targets = targets;
+		self.dirs = dirs;
+	}
+
+	/// Checks if `path` is only reported because its directory is watched for
+	/// another file.
+	fn is_unrelated(&self, path: &Path) -> bool {
+		!self.external.contains(path)
+			&& !self.targets.contains_key(path)
+			&& path.parent().is_some_and(|dir| self.dirs.contains(dir))
+	}
+
+	/// Checks if `path` is a deployed file.
+	fn is_target(&self, path: &Path) -> bool {
+		self.targets.contains_key(path)
+	}
+
+	/// Returns all deployed files of `paths` whose content changed since the
+	/// last deployment.
+	///
+	/// The changed files are only returned once, as their new content is
+	/// remembered.
+	fn drifted(&mut self, paths: &BTreeSet<PathBuf>) -> Vec<PathBuf> {
+		let mut drifted = Vec::new();
+
+		for path in paths {
+			let Some(hash) = self.targets.get(path) else {
+				continue;
+			};
+
+			match std::fs::read(path) {
+				Ok(content) => {
+					let current = ContentHash::of(&content);
+					if current != *hash {
+						self.targets.insert(path.clone(), current);
+						drifted.push(path.clone());
+					}
+				}
+				Err(_) => {
+					self.targets.remove(path);
+					drifted.push(path.clone());
+				}
+			}
+		}
+
+		drifted
+	}
+}
+
+/// Returns `path` like it appears in file system events, which is with a
+/// canonical parent directory.
+///
+/// The file itself is not canonicalized, as it might not exist (yet) or be a
+/// symlink.
+fn event_path(path: &Path) -> PathBuf {
+	match (
+		path.parent().and_then(|dir| dir.canonicalize().ok()),
+		path.file_name(),
+	) {
+		(Some(dir), Some(name)) => dir.join(name),
+		_ => path.to_path_buf(),
+	}
+}
+
+/// Returns the paths of a file system event which should be handled.
+///
+/// Access events, changes inside of `.git` directories and changes to files
+/// next to `watched` files are ignored.
+fn relevant_paths<'e>(
+	event: &'e notify::Event,
+	watched: &'e WatchedFiles,
+) -> impl Iterator<Item = &'e PathBuf> {
+	event
+		.paths
+		.iter()
+		.filter(move |_| !matches!(event.kind, EventKind::Access(_)))
+		.filter(|path| {
+			!watched.is_unrelated(path)
+				&& !path
+					.components()
+					.any(|c| c == Component::Normal(".git".as_ref()))
+		})
+}
+
+/// Waits for relevant changes and returns all changed paths until no further
+/// changes happen for `debounce`.
+///
+/// # Errors
+///
+/// An error is returned if the file watcher stopped.
+fn wait_for_changes(
+	rx: &mpsc::Receiver<notify::Result<notify::Event>>,
+	debounce: Duration,
+	watched: &WatchedFiles,
+) -> Result<BTreeSet<PathBuf>> {
+	let mut paths = BTreeSet::new();
+
+	// Wait for the first relevant change
+	while paths.is_empty() {
+		match rx.recv() {
+			Ok(Ok(event)) => {
+				paths.extend(relevant_paths(&event, watched).cloned());
+
+				if !paths.is_empty() {
+					tracing::debug!("Change detected: {event:?}");
+				}
+			}
+			Ok(Err(err)) => tracing::warn!("Error while watching: {err}"),
+			Err(_) => return Err(eyre!("File watcher stopped unexpectedly")),
+		}
+	}
+
+	// Wait until no further changes happen
+	while let Ok(result) = rx.recv_timeout(debounce) {
+		if let Ok(event) = result {
+			paths.extend(relevant_paths(&event, watched).cloned());
+		}
+	}
+
+	Ok(paths)
+}
+
+/// Resolves the profile and deploys it.
+///
+/// Returns the deployment together with all files outside of the source from
+/// which the profile was resolved.
+///
+/// If deployed files are watched, the hashes of their content are returned as
+/// well.
+fn deploy(
+	source: &PunktfSource,
+	options: &WatchOptions,
+) -> Result<(Deployment, Vec<PathBuf>, BTreeMap<PathBuf, ContentHash>)> {
+	let mut profile = crate::setup_profile(
+		&options.profile_name,
+		source,
+		options.target.clone(),
+		&options.vars,
+	)?;
+
+	if profile.target_path().is_none() {
+		return Err(eyre!(
+			"No target path for the deployment set. Either use the command line argument \
+			 `-t/--target`, the profile attribute `target` or the environment variable \
+			 `{}`",
+			crate::PUNKTF_TARGET_ENVVAR
+		));
+	}
+
+	crate::setup_env(source, &profile, &options.profile_name);
+
+	let mut files: Vec<_> = profile.variable_files().map(Path::to_path_buf).collect();
+	if !profile.prompt_variables.is_empty() {
+		files.push(
+			Answers::load_default(&Config::load()?)?
+				.path()
+				.to_path_buf(),
+		);
+	}
+
+	// Only items whose output changed are written again
+	let mut deploy_options = DeployOptions::default().with_skip_unchanged(true);
+	if let Some(path) = crate::template_cache_path()? {
+		deploy_options = deploy_options.with_template_cache(path);
+	}
+
+	let deployment =
+		Deployer::new(deploy_options, util::ask_user_merge).deploy(source, &mut profile);
+
+	crate::record_history(&options.profile_name, &deployment);
+
+	if options.quiet {
+		util::print_summary(&deployment);
+	} else {
+		util::print_deployment(&deployment);
+	}
+
+	let targets = if options.drift.is_some() {
+		deployment
+			.dotfiles()
+			.iter()
+			.filter(|(_, dotfile)| dotfile.status().is_success())
+			.filter_map(|(path, _)| {
+				let content = std::fs::read(path).ok()?;
+				Some((path.clone(), ContentHash::of(&content)))
+			})
+			.collect()
+	} else {
+		BTreeMap::new()
+	};
+
+	Ok((deployment, files, targets))
+}
+
+/// Shows a desktop notification for the result of a deployment.
+fn notify(profile_name: &str, result: &Result<Deployment>) {
+	match result {
+		Ok(deployment) => match deployment.status() {
+			DeploymentStatus::Success => notification::send(
+				&format!("punktf: Deployed profile {profile_name}"),
+				&format!(
+					"{} dotfiles deployed in {:.2?}",
+					deployment
+						.dotfiles()
+						.values()
+						.filter(|d| d.status().is_success())
+						.count(),
+					deployment.duration().unwrap_or_default()
+				),
+				false,
+			),
+			DeploymentStatus::Failed(err) => notification::send(
+				&format!("punktf: Deployment of profile {profile_name} failed"),
+				&err.to_string(),
+				true,
+			),
+		},
+		Err(err) => notification::send(
+			&format!("punktf: Deployment of profile {profile_name} failed"),
+			&err.to_string(),
+			true,
+		),
+	}
+}
+
+/// Reports deployed files which were modified outside of punktf.
+fn report_drift(options: &WatchOptions, drifted: &[PathBuf]) {
+	for path in drifted {
+		if path.exists() {
+			tracing::warn!("{} was modified outside of punktf", path.display());
+		} else {
+			tracing::warn!("{} was removed outside of punktf", path.display());
+		}
+	}
+
+	if options.notify {
+		notification::send(
+			&format!(
+				"punktf: Deployed files of profile {} modified",
+				options.profile_name
+			),
+			&drifted
+				.iter()
+				.map(|path| path.display().to_string())
+				.collect::<Vec<_>>()
+				.join("\n"),
+			true,
+		);
+	}
+}
+
+/// Deploys the profile and reports the result.
+///
+/// On success, the `watched` files are updated to the ones the profile was
+/// resolved from and the deployed files.
+fn redeploy(
+	source: &PunktfSource,
+	options: &WatchOptions,
+	watcher: &mut RecommendedWatcher,
+	watched: &mut WatchedFiles,
+) {
+	let result = deploy(source, options).map(|(deployment, files, targets)| {
+		watched.update(watcher, source, files, targets);
+		deployment
+	});
+
+	if let Err(err) = &result {
+		tracing::error!("Failed to deploy profile {}: {err:?}", options.profile_name);
+	}
+
+	if options.notify {
+		notify(&options.profile_name, &result);
+	}
+}
+
+/// Deploys the profile once and then redeploys it each time something changes
+/// inside of the source directories or in one of the files the variables of
+/// the profile are read from.
+///
+/// If enabled, deployed files which are modified outside of punktf are
+/// reported and, depending on the policy, trigger a redeployment.
+///
+/// Errors during a deployment are reported (and shown as notification if
+/// enabled) but do not stop the watch mode.
+///
+/// # Errors
+///
+/// An error is returned if the source directories can not be watched.
+pub fn run(source: &PunktfSource, options: &WatchOptions) -> Result<()> {
+	let (tx, rx) = mpsc::channel();
+	let mut watcher = notify::recommended_watcher(tx).wrap_err("Failed to create file watcher")?;
+
+	for layer in source.layers() {
+		watcher
+			.watch(layer.root(), RecursiveMode::Recursive)
+			.wrap_err_with(|| format!("Failed to watch {}", layer.root().display()))?;
+	}
+
+	let mut watched = WatchedFiles::default();
+
+	redeploy(source, options, &mut watcher, &mut watched);
+	println!("Watching for changes...");
+
+	loop {
+		let paths = wait_for_changes(&rx, options.debounce, &watched)?;
+		let source_changed = paths.iter().any(|path| !watched.is_target(path));
+
+		// Writes of punktf itself do not change the content of deployed files
+		let drifted = watched.drifted(&paths);
+		if !drifted.is_empty() {
+			report_drift(options, &drifted);
+		}
+
+		let changed =
+			source_changed || (!drifted.is_empty() && options.drift == Some(DriftPolicy::Redeploy));
+
+		if changed {
+			redeploy(source, options, &mut watcher, &mut watched);
+		}
+
+		if changed || !drifted.is_empty() {
+			println!("Watching for changes...");
+		}
+	}
+}
@@ -0,0 +1,120 @@
+//! Reverse lookup of a target path (`punktf which`).
+//!
+//! Finds the item of a profile which is deployed to a given path, the dotfile
+//! entry and profile layer it comes from and the last recorded deployment of
+//! the path.
+
+use std::path::Path;
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use console::style;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::LayeredProfile;
+use punktf_lib::visit::plan::{ItemKind, PlanItem, Planner};
+
+use crate::config::Config;
+use crate::history::History;
+
+/// Prints a single labeled line of the output.
+fn print_field(label: &str, value: impl std::fmt::Display) {
+	println!("  {:<12} {value}", style(label).dim());
+}
+
+/// Prints the dotfile entry and the profile layer from which the file or
+/// directory `item` comes.
+fn print_entry(profile: &LayeredProfile, item: &PlanItem) {
+	let entry = profile
+		.dotfiles
+		.iter()
+		.filter(|(_, dotfile)| item.source_path.starts_with(&dotfile.path))
+		.max_by_key(|(_, dotfile)| dotfile.path.components().count());
+
+	print_field("source", item.source_path.display());
+
+	if let Some((layer, dotfile)) = entry {
+		print_field("dotfile", dotfile.path.display());
+		print_field(
+			"profile",
+			profile
+				.profile_names
+				.get(*layer)
+				.map_or("<unknown>", String::as_str),
+		);
+	}
+}
+
+/// Looks up which item of the `profile` is deployed to `path` and when it was
+/// last deployed.
+///
+/// # Errors
+///
+/// An error is returned if `path` is neither managed by the profile nor was
+/// deployed by any recorded deployment.
+pub fn run(source: &PunktfSource, profile: &mut LayeredProfile, path: &Path) -> Result<()> {
+	let path = if path.is_absolute() {
+		path.to_path_buf()
+	} else {
+		std::env::current_dir()
+			.wrap_err("Failed to get the current directory")?
+			.join(path)
+	};
+
+	let plan = Planner::new().plan(source, profile);
+	let item = plan.items.iter().find(|item| item.target_path == path);
+
+	println!("{}", style(path.display()).bold());
+
+	match item {
+		Some(item) => {
+			print_field(
+				"kind",
+				match item.kind {
+					ItemKind::File => "file",
+					ItemKind::Directory => "directory",
+					ItemKind::Link => "link",
+				},
+			);
+
+			if item.kind == ItemKind::Link {
+				print_field("link source", item.source_path.display());
+			} else {
+				print_entry(profile, item);
+			}
+
+			print_field("next deploy", item.action);
+		}
+		None => print_field("managed", "not by this profile"),
+	}
+
+	let last = Config::load()
+		.and_then(|config| History::open_default(&config))
+		.and_then(|history| history.last_deployment_of(&path));
+
+	let deployed = match last {
+		Ok(Some((record, status))) => {
+			print_field(
+				"last deploy",
+				format!(
+					"#{} of profile {} at {} ({status})",
+					record.id, record.profile, record.time
+				),
+			);
+			true
+		}
+		Ok(None) => {
+			print_field("last deploy", "never");
+			false
+		}
+		Err(err) => {
+			tracing::warn!("Failed to read the deployment history: {err:?}");
+			false
+		}
+	};
+
+	if item.is_none() && !deployed {
+		return Err(eyre!("{} is not managed by punktf", path.display()));
+	}
+
+	Ok(())
+}
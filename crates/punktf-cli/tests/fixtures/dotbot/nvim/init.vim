@@ -0,0 +1 @@
+set number
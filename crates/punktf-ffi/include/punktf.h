@@ -0,0 +1,148 @@
+/*
+ * C bindings for punktf, a cross-platform multi-target dotfiles manager.
+ *
+ * All strings are UTF-8 and null terminated. Strings returned by the library
+ * must be released with `punktf_string_free`, profiles with
+ * `punktf_profile_free`. Functions which can fail return `NULL` on failure,
+ * `punktf_last_error` describes the error.
+ *
+ * This file is generated by cbindgen, do not edit it by hand.
+ */
+
+#ifndef PUNKTF_H
+#define PUNKTF_H
+
+#include <stdbool.h>
+
+/**
+ * A resolved profile together with the source it was loaded from.
+ *
+ * This is an opaque type for C.
+ */
+typedef struct PunktfProfile PunktfProfile;
+
+/**
+ * Function which receives the events of a deployment.
+ *
+ * The first argument is the event as json string, which is only valid during
+ * the call. The second argument is the `user_data` given to
+ * [`punktf_profile_deploy_with_events`]. The callback may be `NULL`.
+ */
+typedef void (*PunktfEventCallback)(const char*, void*);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Loads the profile `name` from the source directory `source`.
+ *
+ * `target` overwrites the target of the profile if it is not `NULL`. Variable
+ * values which reference a provider (`from:<provider>:<argument>`) are
+ * resolved.
+ *
+ * Returns `NULL` on failure.
+ *
+ * # Safety
+ *
+ * `source` and `name` must point to valid null terminated strings. `target`
+ * must be `NULL` or point to a valid null terminated string.
+ */
+struct PunktfProfile *punktf_profile_load(const char *source, const char *name, const char *target);
+
+/**
+ * Releases a profile returned by [`punktf_profile_load`].
+ *
+ * # Safety
+ *
+ * `profile` must be `NULL` or a profile returned by [`punktf_profile_load`]
+ * which was not released yet.
+ */
+void punktf_profile_free(struct PunktfProfile *profile);
+
+/**
+ * Returns the plan of all actions a deployment of `profile` would take as
+ * json.
+ *
+ * Returns `NULL` on failure.
+ *
+ * # Safety
+ *
+ * `profile` must be a profile returned by [`punktf_profile_load`].
+ */
+char *punktf_profile_plan(struct PunktfProfile *profile);
+
+/**
+ * Renders the dotfile at the path `dotfile` (relative to the `dotfiles`
+ * directory) with the variables of `profile`.
+ *
+ * Returns `NULL` on failure, e.g. if `dotfile` is absolute or leaves the
+ * `dotfiles` directory.
+ *
+ * # Safety
+ *
+ * `profile` must be a profile returned by [`punktf_profile_load`] and
+ * `dotfile` must point to a valid null terminated string.
+ */
+char *punktf_profile_render(struct PunktfProfile *profile, const char *dotfile);
+
+/**
+ * Deploys `profile` and returns the deployment report as json.
+ *
+ * Existing files with the merge mode `Ask` are not overwritten. If `dry_run`
+ * is set, nothing is written.
+ *
+ * Returns `NULL` on failure. A failed deployment is not a failure of this
+ * function but is described by the returned report.
+ *
+ * # Safety
+ *
+ * `profile` must be a profile returned by [`punktf_profile_load`].
+ */
+char *punktf_profile_deploy(struct PunktfProfile *profile, bool dry_run);
+
+/**
+ * Deploys `profile` like [`punktf_profile_deploy`] and calls `callback` for
+ * each event of the deployment.
+ *
+ * Each event is a json object with an `event` field (`started`,
+ * `hook_started`, `hook_finished`, `item_started`, `item_compiled`,
+ * `item_deployed`, `item_skipped`, `item_failed` or `finished`) and the
+ * fields of the event.
+ *
+ * Returns `NULL` on failure.
+ *
+ * # Safety
+ *
+ * `profile` must be a profile returned by [`punktf_profile_load`] and
+ * `callback` must be safe to call with `user_data`.
+ */
+char *punktf_profile_deploy_with_events(struct PunktfProfile *profile,
+                                        bool dry_run,
+                                        PunktfEventCallback callback,
+                                        void *user_data);
+
+/**
+ * Returns the message of the last error which occurred on the calling thread
+ * or `NULL` if there was none.
+ *
+ * The returned string is owned by the library and valid until the next call
+ * of a function of this library on the same thread.
+ */
+const char *punktf_last_error(void);
+
+/**
+ * Releases a string returned by this library.
+ *
+ * # Safety
+ *
+ * `s` must be `NULL` or a string returned by this library (except
+ * [`punktf_last_error`]) which was not released yet.
+ */
+void punktf_string_free(char *s);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* PUNKTF_H */
@@ -0,0 +1,449 @@
+#![deny(
+	missing_docs,
+	clippy::missing_docs_in_private_items,
+	missing_debug_implementations,
+	rust_2018_idioms,
+	unused_qualifications,
+	clippy::missing_safety_doc
+)]
+
+//! C bindings for `punktf`, a cross-platform multi-target dotfiles manager.
+//!
+//! The bindings expose the resolution of profiles, the rendering of single
+//! dotfiles and deployments. Complex results (plans and deployment reports)
+//! are returned as json strings, using the same format as the `--json-output`
+//! of the command line interface.
+//!
+//! The matching C declarations are located in `include/punktf.h`.
+//!
+//! # Memory
+//!
+//! All returned strings are owned by the caller and must be released with
+//! [`punktf_string_free`]. Profiles must be released with
+//! [`punktf_profile_free`].
+//!
+//! # Errors
+//!
+//! Functions which can fail return `NULL` on failure. The message of the last
+//! error of the calling thread can be retrieved with [`punktf_last_error`].
+
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::{Component, Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use punktf_lib::profile::provider::VariableProviders;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::{resolve_profile, LayeredProfile, Profile};
+use punktf_lib::template::source::Source;
+use punktf_lib::template::Template;
+use punktf_lib::visit::deploy::event::{DeployEvent, HookKind};
+use punktf_lib::visit::deploy::{DeployOptions, Deployer};
+use punktf_lib::visit::plan::Planner;
+use serde_json::json;
+
+thread_local! {
+	/// Message of the last error which occurred on this thread.
+	static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// A resolved profile together with the source it was loaded from.
+///
+/// This is an opaque type for C.
+#[derive(Debug)]
+pub struct PunktfProfile {
+	/// The source of the profile.
+	source: PunktfSource,
+
+	/// The resolved profile.
+	profile: LayeredProfile,
+}
+
+/// Stores `err` as the last error of this thread.
+fn set_last_error(err: &color_eyre::Report) {
+	let message =
+		CString::new(format!("{err:#}").replace('\0', "\\0")).expect("Null bytes are escaped");
+
+	LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Runs `f` and returns its value or `NULL` if it failed, in which case the
+/// error is stored as the last error.
+///
+/// Panics are caught, as they must not unwind into the calling C code.
+fn ffi_result<T>(f: impl FnOnce() -> Result<*mut T>) -> *mut T {
+	let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f))
+		.unwrap_or_else(|_| Err(eyre!("punktf panicked")));
+
+	match result {
+		Ok(value) => value,
+		Err(err) => {
+			set_last_error(&err);
+			std::ptr::null_mut()
+		}
+	}
+}
+
+/// Converts `s` to a string which is owned by the caller.
+fn into_c_string(s: String) -> Result<*mut c_char> {
+	Ok(CString::new(s)
+		.wrap_err("String contains a null byte")?
+		.into_raw())
+}
+
+/// Reads the string at `ptr`.
+///
+/// # Safety
+///
+/// `ptr` must be `NULL` or point to a valid null terminated string.
+unsafe fn read_str<'a>(ptr: *const c_char, name: &str) -> Result<&'a str> {
+	if ptr.is_null() {
+		return Err(eyre!("`{name}` must not be null"));
+	}
+
+	CStr::from_ptr(ptr)
+		.to_str()
+		.wrap_err_with(|| format!("`{name}` is not valid UTF-8"))
+}
+
+/// Reads the optional string at `ptr`.
+///
+/// # Safety
+///
+/// `ptr` must be `NULL` or point to a valid null terminated string.
+unsafe fn read_opt_str<'a>(ptr: *const c_char, name: &str) -> Result<Option<&'a str>> {
+	if ptr.is_null() {
+		Ok(None)
+	} else {
+		read_str(ptr, name).map(Some)
+	}
+}
+
+/// Returns the profile behind `ptr`.
+///
+/// # Safety
+///
+/// `ptr` must be `NULL` or a profile returned by [`punktf_profile_load`].
+unsafe fn read_profile<'a>(ptr: *mut PunktfProfile) -> Result<&'a mut PunktfProfile> {
+	ptr.as_mut()
+		.ok_or_else(|| eyre!("`profile` must not be null"))
+}
+
+/// Loads the profile `name` from the source directory `source`.
+///
+/// `target` overwrites the target of the profile if it is not `NULL`. Variable
+/// values which reference a provider (`from:<provider>:<argument>`) are
+/// resolved.
+///
+/// Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `source` and `name` must point to valid null terminated strings. `target`
+/// must be `NULL` or point to a valid null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn punktf_profile_load(
+	source: *const c_char,
+	name: *const c_char,
+	target: *const c_char,
+) -> *mut PunktfProfile {
+	ffi_result(|| {
+		let source = PunktfSource::from_root(PathBuf::from(read_str(source, "source")?))?;
+		let name = read_str(name, "name")?;
+		let target = read_opt_str(target, "target")?;
+
+		let mut builder = LayeredProfile::build();
+
+		if let Some(target) = target {
+			let target_profile = Profile {
+				target: Some(PathBuf::from(target)),
+				..Default::default()
+			};
+			builder.add(String::from("ffi"), target_profile);
+		}
+
+		resolve_profile(&mut builder, &source, name)?;
+
+		let mut profile = builder.finish();
+		profile.resolve_variables(&VariableProviders::default())?;
+
+		Ok(Box::into_raw(Box::new(PunktfProfile { source, profile })))
+	})
+}
+
+/// Releases a profile returned by [`punktf_profile_load`].
+///
+/// # Safety
+///
+/// `profile` must be `NULL` or a profile returned by [`punktf_profile_load`]
+/// which was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn punktf_profile_free(profile: *mut PunktfProfile) {
+	if !profile.is_null() {
+		drop(Box::from_raw(profile));
+	}
+}
+
+/// Returns the plan of all actions a deployment of `profile` would take as
+/// json.
+///
+/// Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `profile` must be a profile returned by [`punktf_profile_load`].
+#[no_mangle]
+pub unsafe extern "C" fn punktf_profile_plan(profile: *mut PunktfProfile) -> *mut c_char {
+	ffi_result(|| {
+		let PunktfProfile { source, profile } = read_profile(profile)?;
+		let plan = Planner::new().plan(source, profile);
+
+		into_c_string(serde_json::to_string(&plan)?)
+	})
+}
+
+/// Renders the dotfile at the path `dotfile` (relative to the `dotfiles`
+/// directory) with the variables of `profile`.
+///
+/// Returns `NULL` on failure, e.g. if `dotfile` is absolute or leaves the
+/// `dotfiles` directory.
+///
+/// # Safety
+///
+/// `profile` must be a profile returned by [`punktf_profile_load`] and
+/// `dotfile` must point to a valid null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn punktf_profile_render(
+	profile: *mut PunktfProfile,
+	dotfile: *const c_char,
+) -> *mut c_char {
+	ffi_result(|| {
+		let PunktfProfile { source, profile } = read_profile(profile)?;
+		let dotfile = Path::new(read_str(dotfile, "dotfile")?);
+
+		into_c_string(render_dotfile(source, profile, dotfile)?)
+	})
+}
+
+/// Renders the dotfile at the path `dotfile` (relative to the `dotfiles`
+/// directory of `source`) with the variables of `profile`.
+///
+/// # Errors
+///
+/// Fails if `dotfile` is absolute or leaves the `dotfiles` directory, if it
+/// can not be read or if the template can not be rendered.
+pub fn render_dotfile(
+	source: &PunktfSource,
+	profile: &LayeredProfile,
+	dotfile: &Path,
+) -> Result<String> {
+	if !dotfile
+		.components()
+		.all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+	{
+		return Err(eyre!(
+			"`{}` is not a relative path inside the dotfiles directory",
+			dotfile.display()
+		));
+	}
+
+	// The most specific dotfile of the profile which contains the file
+	let dotfile_vars = profile
+		.dotfiles()
+		.filter(|d| dotfile.starts_with(&d.path))
+		.max_by_key(|d| d.path.components().count())
+		.and_then(|d| d.variables.as_ref());
+
+	let path = source.dotfile_path(dotfile);
+	let content = std::fs::read_to_string(&path)
+		.wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+
+	let template = Template::parse(Source::file(&path, &content))?;
+	template.resolve_for_profile(profile, dotfile_vars, source)
+}
+
+/// Deploys `profile` and returns the deployment report as json.
+///
+/// Existing files with the merge mode `Ask` are not overwritten. If `dry_run`
+/// is set, nothing is written.
+///
+/// Returns `NULL` on failure. A failed deployment is not a failure of this
+/// function but is described by the returned report.
+///
+/// # Safety
+///
+/// `profile` must be a profile returned by [`punktf_profile_load`].
+#[no_mangle]
+pub unsafe extern "C" fn punktf_profile_deploy(
+	profile: *mut PunktfProfile,
+	dry_run: bool,
+) -> *mut c_char {
+	ffi_result(|| {
+		let PunktfProfile { source, profile } = read_profile(profile)?;
+
+		let options = DeployOptions::new().with_dry_run(dry_run);
+		let deployment = Deployer::new(options, |_, _| Ok(false)).deploy(source, profile);
+
+		into_c_string(serde_json::to_string(&deployment)?)
+	})
+}
+
+/// Function which receives the events of a deployment.
+///
+/// The first argument is the event as json string, which is only valid during
+/// the call. The second argument is the `user_data` given to
+/// [`punktf_profile_deploy_with_events`]. The callback may be `NULL`.
+pub type PunktfEventCallback = Option<unsafe extern "C" fn(*const c_char, *mut c_void)>;
+
+/// Deploys `profile` like [`punktf_profile_deploy`] and calls `callback` for
+/// each event of the deployment.
+///
+/// Each event is a json object with an `event` field (`started`,
+/// `hook_started`, `hook_finished`, `item_started`, `item_compiled`,
+/// `item_deployed`, `item_skipped`, `item_failed` or `finished`) and the
+/// fields of the event.
+///
+/// Returns `NULL` on failure.
+///
+/// # Safety
+///
+/// `profile` must be a profile returned by [`punktf_profile_load`] and
+/// `callback` must be safe to call with `user_data`.
+#[no_mangle]
+pub unsafe extern "C" fn punktf_profile_deploy_with_events(
+	profile: *mut PunktfProfile,
+	dry_run: bool,
+	callback: PunktfEventCallback,
+	user_data: *mut c_void,
+) -> *mut c_char {
+	ffi_result(|| {
+		let PunktfProfile { source, profile } = read_profile(profile)?;
+
+		let observer = |event: &DeployEvent<'_>| {
+			let Some(callback) = callback else {
+				return;
+			};
+
+			// Events are plain json, which never contains null bytes
+			if let Ok(event) = CString::new(event_json(event).to_string()) {
+				callback(event.as_ptr(), user_data);
+			}
+		};
+
+		let options = DeployOptions::new().with_dry_run(dry_run);
+		let deployment = Deployer::new(options, |_, _| Ok(false))
+			.with_observer(observer)
+			.deploy(source, profile);
+
+		into_c_string(serde_json::to_string(&deployment)?)
+	})
+}
+
+/// Converts `event` to the json object which is passed to the callback of
+/// [`punktf_profile_deploy_with_events`].
+pub fn event_json(event: &DeployEvent<'_>) -> serde_json::Value {
+	let hook_kind = |kind: &HookKind| match kind {
+		HookKind::Pre => "pre",
+		HookKind::Post => "post",
+	};
+
+	match event {
+		DeployEvent::Started { profile } => json!({
+			"event": "started",
+			"profile": profile.profile_names,
+		}),
+		DeployEvent::HookStarted { kind, command } => json!({
+			"event": "hook_started",
+			"kind": hook_kind(kind),
+			"command": command,
+		}),
+		DeployEvent::HookFinished {
+			kind,
+			command,
+			error,
+		} => json!({
+			"event": "hook_finished",
+			"kind": hook_kind(kind),
+			"command": command,
+			"error": error.map(ToString::to_string),
+		}),
+		DeployEvent::ItemStarted {
+			source_path,
+			target_path,
+		} => json!({
+			"event": "item_started",
+			"source": source_path,
+			"target": target_path,
+		}),
+		DeployEvent::ItemCompiled {
+			source_path,
+			target_path,
+			duration,
+		} => json!({
+			"event": "item_compiled",
+			"source": source_path,
+			"target": target_path,
+			"duration": duration.as_secs_f64(),
+		}),
+		DeployEvent::ItemDeployed {
+			source_path,
+			target_path,
+		} => json!({
+			"event": "item_deployed",
+			"source": source_path,
+			"target": target_path,
+		}),
+		DeployEvent::ItemSkipped {
+			source_path,
+			target_path,
+			reason,
+		} => json!({
+			"event": "item_skipped",
+			"source": source_path,
+			"target": target_path,
+			"reason": reason,
+		}),
+		DeployEvent::ItemFailed {
+			source_path,
+			target_path,
+			error,
+		} => json!({
+			"event": "item_failed",
+			"source": source_path,
+			"target": target_path,
+			"error": error.to_string(),
+		}),
+		DeployEvent::Finished { deployment } => json!({
+			"event": "finished",
+			"success": deployment.status().is_success(),
+		}),
+	}
+}
+
+/// Returns the message of the last error which occurred on the calling thread
+/// or `NULL` if there was none.
+///
+/// The returned string is owned by the library and valid until the next call
+/// of a function of this library on the same thread.
+#[no_mangle]
+pub extern "C" fn punktf_last_error() -> *const c_char {
+	LAST_ERROR.with(|last| {
+		last.borrow()
+			.as_ref()
+			.map_or(std::ptr::null(), |message| message.as_ptr())
+	})
+}
+
+/// Releases a string returned by this library.
+///
+/// # Safety
+///
+/// `s` must be `NULL` or a string returned by this library (except
+/// [`punktf_last_error`]) which was not released yet.
+#[no_mangle]
+pub unsafe extern "C" fn punktf_string_free(s: *mut c_char) {
+	if !s.is_null() {
+		drop(CString::from_raw(s));
+	}
+}
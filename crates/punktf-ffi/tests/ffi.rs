@@ -0,0 +1,146 @@
+//! Tests for the C bindings.
+
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::Path;
+
+use color_eyre::Result;
+use punktf_ffi::*;
+
+fn write(path: &Path, content: &str) -> Result<()> {
+	std::fs::create_dir_all(path.parent().expect("Path has a parent"))?;
+	std::fs::write(path, content)?;
+	Ok(())
+}
+
+/// Takes ownership of a string returned by the library.
+unsafe fn take_string(s: *mut c_char) -> String {
+	assert!(!s.is_null(), "{:?}", CStr::from_ptr(punktf_last_error()));
+	let value = CStr::from_ptr(s).to_string_lossy().into_owned();
+	punktf_string_free(s);
+	value
+}
+
+#[test]
+fn ffi_profile() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		"variables:\n  NAME: \"abc\"\ndotfiles:\n  - path: template\n    template: true\n",
+	)?;
+	write(&root.join("dotfiles").join("template"), "{{NAME}}")?;
+
+	let source = CString::new(root.to_string_lossy().as_bytes())?;
+	let name = CString::new("test")?;
+	let target_str = CString::new(target.to_string_lossy().as_bytes())?;
+
+	unsafe {
+		let profile = punktf_profile_load(source.as_ptr(), name.as_ptr(), target_str.as_ptr());
+		assert!(!profile.is_null());
+
+		let dotfile = CString::new("template")?;
+		let rendered = take_string(punktf_profile_render(profile, dotfile.as_ptr()));
+		assert_eq!(rendered, "abc");
+
+		// Only files inside the `dotfiles` directory can be rendered
+		for outside in ["../profiles/test.yaml", "/etc/hostname"] {
+			let dotfile = CString::new(outside)?;
+			assert!(punktf_profile_render(profile, dotfile.as_ptr()).is_null());
+		}
+
+		let plan = take_string(punktf_profile_plan(profile));
+		assert!(plan.contains("\"create\""));
+
+		let report: serde_json::Value =
+			serde_json::from_str(&take_string(punktf_profile_deploy(profile, false)))?;
+		assert_eq!(report["status"], "Success");
+		assert_eq!(std::fs::read_to_string(target.join("template"))?, "abc");
+
+		punktf_profile_free(profile);
+	}
+
+	Ok(())
+}
+
+/// Collects the events passed to the callback into the `Vec<String>` behind
+/// `user_data`.
+unsafe extern "C" fn collect_event(event: *const c_char, user_data: *mut c_void) {
+	let events = &mut *user_data.cast::<Vec<String>>();
+	events.push(CStr::from_ptr(event).to_string_lossy().into_owned());
+}
+
+#[test]
+fn ffi_deploy_events() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		"dotfiles:\n  - path: file\n",
+	)?;
+	write(&root.join("dotfiles").join("file"), "content")?;
+
+	let source = CString::new(root.to_string_lossy().as_bytes())?;
+	let name = CString::new("test")?;
+	let target_str = CString::new(target.to_string_lossy().as_bytes())?;
+	let mut events: Vec<String> = Vec::new();
+
+	unsafe {
+		let profile = punktf_profile_load(source.as_ptr(), name.as_ptr(), target_str.as_ptr());
+		assert!(!profile.is_null());
+
+		take_string(punktf_profile_deploy_with_events(
+			profile,
+			true,
+			Some(collect_event),
+			(&mut events as *mut Vec<String>).cast(),
+		));
+
+		punktf_profile_free(profile);
+	}
+
+	let events = events
+		.iter()
+		.map(|event| serde_json::from_str::<serde_json::Value>(event))
+		.collect::<serde_json::Result<Vec<_>>>()?;
+	let names: Vec<_> = events.iter().map(|event| event["event"].clone()).collect();
+
+	assert_eq!(
+		names,
+		[
+			"started",
+			"item_started",
+			"item_compiled",
+			"item_deployed",
+			"finished"
+		]
+	);
+	assert_eq!(
+		events[3]["target"],
+		target.join("file").to_string_lossy().as_ref()
+	);
+	assert!(!target.join("file").exists());
+
+	Ok(())
+}
+
+#[test]
+fn ffi_error() -> Result<()> {
+	let source = CString::new("/does/not/exist")?;
+	let name = CString::new("test")?;
+
+	unsafe {
+		let profile = punktf_profile_load(source.as_ptr(), name.as_ptr(), std::ptr::null());
+		assert!(profile.is_null());
+		assert!(!punktf_last_error().is_null());
+
+		assert!(punktf_profile_plan(std::ptr::null_mut()).is_null());
+		let error = CStr::from_ptr(punktf_last_error()).to_str()?;
+		assert_eq!(error, "`profile` must not be null");
+	}
+
+	Ok(())
+}
@@ -0,0 +1,133 @@
+//! Errors which are recorded during a deployment.
+//!
+//! The failure of a [deployment](`crate::visit::deploy::deployment::Deployment`)
+//! or of a single item is described by an [`Error`](enum@Error). Each variant represents
+//! a different cause, which allows users of the library to react to specific
+//! failures without parsing the error messages.
+
+use std::borrow::Cow;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::profile::hook::HookError;
+
+/// The cause of a failed deployment or item.
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+pub enum Error {
+	/// The profile is invalid or could not be processed.
+	#[error(transparent)]
+	Profile(#[from] ProfileError),
+
+	/// A template could not be parsed or resolved.
+	#[error("Failed to resolve template: {0}")]
+	Template(#[from] TemplateError),
+
+	/// An operation on the file system failed.
+	#[error(transparent)]
+	Io(#[from] IoError),
+
+	/// A hook failed to execute.
+	#[error("Failed to execute hook `{command}`: {source}")]
+	Hook {
+		/// Command of the hook.
+		command: String,
+
+		/// The error of the hook.
+		source: HookError,
+	},
+
+	/// The deployment was cancelled before all items were deployed.
+	#[error("Deployment was cancelled")]
+	Cancelled,
+
+	/// Any other failure (e.g. a denied merge).
+	#[error("{0}")]
+	Other(Cow<'static, str>),
+}
+
+impl Error {
+	/// Creates an error for the failed hook with the given `command`.
+	pub fn hook<S: Into<String>>(command: S, source: HookError) -> Self {
+		Self::Hook {
+			command: command.into(),
+			source,
+		}
+	}
+
+	/// Creates an error which is described only by `reason`.
+	pub fn other<S: Into<Cow<'static, str>>>(reason: S) -> Self {
+		Self::Other(reason.into())
+	}
+}
+
+impl From<String> for Error {
+	fn from(value: String) -> Self {
+		Self::other(value)
+	}
+}
+
+impl From<&'static str> for Error {
+	fn from(value: &'static str) -> Self {
+		Self::other(value)
+	}
+}
+
+/// The profile is invalid or could not be processed.
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[error("{message}")]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+pub struct ProfileError {
+	/// Description of the error.
+	pub message: String,
+}
+
+impl ProfileError {
+	/// Creates a new error with the given `message`.
+	pub fn new<S: Into<String>>(message: S) -> Self {
+		Self {
+			message: message.into(),
+		}
+	}
+}
+
+/// A template could not be parsed or resolved.
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[error("{message}")]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+pub struct TemplateError {
+	/// Path of the template if it was read from a file.
+	pub path: Option<PathBuf>,
+
+	/// Description of the first error in the template.
+	pub message: String,
+
+	/// Byte range of the template content which caused the error.
+	pub span: Option<Range<usize>>,
+}
+
+/// An operation on the file system failed.
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[error("{message}")]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+pub struct IoError {
+	/// Path of the item on which the operation failed.
+	pub path: PathBuf,
+
+	/// Description of the failed operation together with the cause.
+	pub message: String,
+}
+
+impl IoError {
+	/// Creates a new error for the operation on `path` described by `context`
+	/// which failed with `err`.
+	pub fn new<P: Into<PathBuf>>(path: P, context: &str, err: &std::io::Error) -> Self {
+		Self {
+			path: path.into(),
+			message: format!("{context}: {err}"),
+		}
+	}
+}
@@ -0,0 +1,2561 @@
+//! Expression language shared by the `@if` blocks of
+//! [templates](`crate::template`) and the
+//! [conditions](`crate::profile::condition`) of dotfiles.
+//!
+//! ```text
+//! {{OS}} == "linux" && {{HOSTNAME}} matches "^dev-"     (template)
+//! os == "linux" && HOSTNAME matches "^dev-"             (profile)
+//! ```
+//!
+//! - `a == "b"` / `a != "b"` compare the value of a variable with a string
+//!   (or another variable).
+//! - `a matches "regex"` checks if the value of a variable matches a regular
+//!   expression.
+//! - A variable on its own checks if the variable is defined.
+//! - `!`, `&&`, `||` and parentheses combine expressions, where `&&` binds
+//!   stronger than `||`. Both are short-circuiting.
+//! - `condition ? a : b` chooses one of two operands and can be chained
+//!   (`c ? a : d ? b : c`). Only the chosen operand is evaluated. These
+//!   [values](`Value`) are parsed with [`parse_value`].
+//! - [Functions](`Function`) like `upper(a)`, `path_join(a, "b")` or
+//!   `contains(a, "b")` modify or test strings and paths. They can also be
+//!   written as filters, where the value on the left of `|` is the first
+//!   argument (`a | replace("-", "_") | upper`).
+//!
+//! Strings are enclosed in `"` or `'`. Inside of them, `\` escapes the quote
+//! and itself, any other `\` is kept as is.
+//!
+//! How variables are written is defined by the user of the expression (see
+//! [`parse`]). Comparing a variable which is not defined is an error.
+//!
+//! Lists of operands (e.g. the arguments of a template macro) are separated
+//! by `,` and parsed with [`parse_operands`].
+//! Filters which are applied to a value that is only known later (e.g. the
+//! output of a rendered template) are parsed with [`parse_filters`].
+
+use std::borrow::Cow;
+use std::fmt::{self, Write as _};
+use std::hash::{BuildHasher as _, Hash, Hasher, RandomState};
+use std::path::{Path, PathBuf, MAIN_SEPARATOR_STR};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use regex::Regex;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest as _, Sha256};
+use time::format_description::well_known::Rfc3339;
+use time::{OffsetDateTime, UtcOffset};
+
+use crate::plugin::Plugins;
+
+/// Error which is returned if an expression can not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{message}")]
+pub struct ParseExprError {
+	/// Byte offset of the error in the expression.
+	pub offset: usize,
+
+	/// Description of the error.
+	pub message: String,
+}
+
+impl ParseExprError {
+	/// Creates a new error at the byte `offset`.
+	pub fn new(offset: usize, message: impl Into<String>) -> Self {
+		Self {
+			offset,
+			message: message.into(),
+		}
+	}
+}
+
+/// A regular expression of a `matches` expression.
+///
+/// Two patterns are equal if they were created from the same string.
+#[derive(Debug, Clone)]
+pub struct Pattern(Regex);
+
+impl Pattern {
+	/// Returns the regular expression as written by the user.
+	pub fn as_str(&self) -> &str {
+		self.0.as_str()
+	}
+
+	/// Checks if `value` matches the pattern.
+	pub fn is_match(&self, value: &str) -> bool {
+		self.0.is_match(value)
+	}
+}
+
+impl PartialEq for Pattern {
+	fn eq(&self, other: &Self) -> bool {
+		self.as_str() == other.as_str()
+	}
+}
+
+impl Eq for Pattern {}
+
+impl Serialize for Pattern {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.serialize_str(self.as_str())
+	}
+}
+
+impl<'de> Deserialize<'de> for Pattern {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let pattern = String::deserialize(deserializer)?;
+		Regex::new(&pattern)
+			.map(Self)
+			.map_err(serde::de::Error::custom)
+	}
+}
+
+impl Hash for Pattern {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		self.as_str().hash(state);
+	}
+}
+
+/// A built-in function.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Function {
+	/// `replace(value, from, to)`: Replaces all occurrences of `from` with `to`.
+	Replace,
+
+	/// `split(value, separator, index)`: Returns the part with the literal
+	/// `index` (starting at `0`), or an empty string if there are less parts.
+	Split,
+
+	/// `trim(value)`: Removes leading and trailing white spaces.
+	Trim,
+
+	/// `upper(value)`: Converts all characters to upper case.
+	Upper,
+
+	/// `lower(value)`: Converts all characters to lower case.
+	Lower,
+
+	/// `title(value)`: Converts the first character of each word to upper
+	/// case and all others to lower case.
+	Title,
+
+	/// `contains(value, part)`: Checks if `part` is contained in the value.
+	Contains,
+
+	/// `starts_with(value, prefix)`: Checks if the value starts with `prefix`.
+	StartsWith,
+
+	/// `path_join(path, other)`: Appends `other` to `path` with the separator
+	/// of the current platform. If `other` is absolute, it replaces `path`.
+	PathJoin,
+
+	/// `dirname(path)`: Returns the parent directory of the path, or an empty
+	/// string if it has none.
+	Dirname,
+
+	/// `basename(path)`: Returns the last component of the path, or an empty
+	/// string if it has none.
+	Basename,
+
+	/// `to_native(path)`: Converts all `/` and `\` to the separator of the
+	/// current platform.
+	ToNative,
+
+	/// `home()`: Returns the home directory of the current user, or an empty
+	/// string if it is unknown.
+	Home,
+
+	/// `sha256(value)`: Returns the SHA-256 hash of the value as hexadecimal
+	/// digits.
+	Sha256,
+
+	/// `file_hash(path)`: Returns the SHA-256 hash of the content of the file
+	/// at `path` as hexadecimal digits. Relative paths are resolved from the
+	/// [base directory](`Settings::base_dir`).
+	///
+	/// This fails if the file can not be read or the `io` feature is
+	/// disabled.
+	FileHash,
+
+	/// `b64encode(value)`: Encodes the value with the standard base64
+	/// alphabet (with padding).
+	B64Encode,
+
+	/// `b64decode(value)`: Decodes the base64 encoded value.
+	///
+	/// This fails if the value is not valid base64 or does not decode to
+	/// UTF-8 text.
+	B64Decode,
+
+	/// `uuid()` / `uuid(key)`: Returns a random (version 4) UUID.
+	///
+	/// With a `key` the UUID is derived from the key and the
+	/// [seed](`Settings::seed`), which makes it stable between evaluations.
+	Uuid,
+
+	/// `random(min, max)` / `random(min, max, key)`: Returns a random integer
+	/// between `min` and `max` (both inclusive).
+	///
+	/// With a `key` the number is derived from the key and the
+	/// [seed](`Settings::seed`), which makes it stable between evaluations.
+	///
+	/// This fails if `min` or `max` is not an integer or `min` is greater than
+	/// `max`.
+	Random,
+
+	/// `now(format)` / `now(format, timezone)`: Returns the current time
+	/// formatted with `strftime` like specifiers (e.g. `%Y-%m-%d %H:%M`).
+	///
+	/// The timezone is either `UTC` (default) or an offset like `+02:00`. The
+	/// current time can be frozen with [`FROZEN_TIME_ENVVAR`].
+	///
+	/// This fails if the format contains an unsupported specifier or the
+	/// timezone is invalid.
+	Now,
+
+	/// `today()` / `today(timezone)`: Returns the current date as
+	/// `YYYY-MM-DD` (see [`Function::Now`]).
+	Today,
+
+	/// `indent(value, width)`: Indents all non-empty lines of the value by
+	/// `width` spaces (e.g. to insert a multi-line value into yaml).
+	///
+	/// The width must be a number in quotes (e.g. `"4"`).
+	Indent,
+
+	/// `nindent(value, width)`: Like [`Function::Indent`], but starts with a
+	/// new line.
+	Nindent,
+
+	/// `to_json(value)`: Returns the value as a json string, with quotes and
+	/// escapes.
+	ToJson,
+
+	/// `to_yaml(value)`: Returns the value as a yaml string, which is quoted
+	/// if it would be read as something else (e.g. `yes`, `null` or `1.0`).
+	///
+	/// Values with multiple lines are written as double quoted string, so the
+	/// result always fits on a single line.
+	ToYaml,
+
+	/// `to_toml(value)`: Returns the value as a toml string, with quotes and
+	/// escapes.
+	ToToml,
+
+	/// `comment(text)`: Prefixes all lines of the text with the
+	/// [comment prefix](`Settings::comment_prefix`) of the file which is
+	/// rendered (e.g. `# text`).
+	Comment,
+
+	/// `plugin(value, name)`: Calls the function `name` of a
+	/// [plugin](`crate::plugin`) with the value (e.g.
+	/// `{{USER}} | plugin("corp.email")`).
+	///
+	/// This fails if no [plugins](`Settings::plugins`) are available, the
+	/// function is not registered or the plugin fails.
+	Plugin,
+}
+
+/// Name of the environment variable which freezes the current time of
+/// [`Function::Now`] and [`Function::Today`] (e.g. for reproducible tests).
+///
+/// The value is either a RFC 3339 timestamp (e.g. `2024-01-31T12:00:00Z`) or
+/// the seconds since the unix epoch.
+pub const FROZEN_TIME_ENVVAR: &str = "PUNKTF_FROZEN_TIME";
+
+/// Parses a time in the format of [`FROZEN_TIME_ENVVAR`].
+///
+/// # Errors
+///
+/// An error is returned if the value is neither a RFC 3339 timestamp nor the
+/// seconds since the unix epoch.
+pub fn parse_time(value: &str) -> Result<OffsetDateTime, String> {
+	let value = value.trim();
+
+	value
+		.parse::<i64>()
+		.ok()
+		.and_then(|seconds| OffsetDateTime::from_unix_timestamp(seconds).ok())
+		.or_else(|| OffsetDateTime::parse(value, &Rfc3339).ok())
+		.ok_or_else(|| {
+			format!(
+				"`{value}` is neither a RFC 3339 timestamp (e.g. `2024-01-31T12:00:00Z`) nor \
+				 the seconds since the unix epoch"
+			)
+		})
+}
+
+impl Function {
+	/// Returns the function with the given `name`.
+	pub fn from_name(name: &str) -> Option<Self> {
+		Some(match name {
+			"replace" => Self::Replace,
+			"split" => Self::Split,
+			"trim" => Self::Trim,
+			"upper" => Self::Upper,
+			"lower" => Self::Lower,
+			"title" => Self::Title,
+			"contains" => Self::Contains,
+			"starts_with" => Self::StartsWith,
+			"path_join" => Self::PathJoin,
+			"dirname" => Self::Dirname,
+			"basename" => Self::Basename,
+			"to_native" => Self::ToNative,
+			"home" => Self::Home,
+			"sha256" => Self::Sha256,
+			"file_hash" => Self::FileHash,
+			"b64encode" => Self::B64Encode,
+			"b64decode" => Self::B64Decode,
+			"uuid" => Self::Uuid,
+			"random" => Self::Random,
+			"now" => Self::Now,
+			"today" => Self::Today,
+			"indent" => Self::Indent,
+			"nindent" => Self::Nindent,
+			"to_json" => Self::ToJson,
+			"to_yaml" => Self::ToYaml,
+			"to_toml" => Self::ToToml,
+			"comment" => Self::Comment,
+			"plugin" => Self::Plugin,
+			_ => return None,
+		})
+	}
+
+	/// Returns the name of the function.
+	pub const fn name(self) -> &'static str {
+		match self {
+			Self::Replace => "replace",
+			Self::Split => "split",
+			Self::Trim => "trim",
+			Self::Upper => "upper",
+			Self::Lower => "lower",
+			Self::Title => "title",
+			Self::Contains => "contains",
+			Self::StartsWith => "starts_with",
+			Self::PathJoin => "path_join",
+			Self::Dirname => "dirname",
+			Self::Basename => "basename",
+			Self::ToNative => "to_native",
+			Self::Home => "home",
+			Self::Sha256 => "sha256",
+			Self::FileHash => "file_hash",
+			Self::B64Encode => "b64encode",
+			Self::B64Decode => "b64decode",
+			Self::Uuid => "uuid",
+			Self::Random => "random",
+			Self::Now => "now",
+			Self::Today => "today",
+			Self::Indent => "indent",
+			Self::Nindent => "nindent",
+			Self::ToJson => "to_json",
+			Self::ToYaml => "to_yaml",
+			Self::ToToml => "to_toml",
+			Self::Comment => "comment",
+			Self::Plugin => "plugin",
+		}
+	}
+
+	/// Returns the number of arguments, including the value.
+	///
+	/// The last argument of some functions is optional (see
+	/// [`Function::min_arity`]).
+	pub const fn arity(self) -> usize {
+		match self {
+			Self::Home => 0,
+			Self::Uuid | Self::Today => 1,
+			Self::Trim
+			| Self::Upper
+			| Self::Lower
+			| Self::Title
+			| Self::Dirname
+			| Self::Basename
+			| Self::ToNative
+			| Self::Sha256
+			| Self::FileHash
+			| Self::B64Encode
+			| Self::B64Decode
+			| Self::ToJson
+			| Self::ToYaml
+			| Self::ToToml
+			| Self::Comment => 1,
+			Self::Contains
+			| Self::StartsWith
+			| Self::PathJoin
+			| Self::Now
+			| Self::Indent
+			| Self::Nindent
+			| Self::Plugin => 2,
+			Self::Replace | Self::Split | Self::Random => 3,
+		}
+	}
+
+	/// Returns the number of arguments which must be given, including the
+	/// value.
+	pub const fn min_arity(self) -> usize {
+		match self {
+			Self::Uuid | Self::Random | Self::Now | Self::Today => self.arity() - 1,
+			_ => self.arity(),
+		}
+	}
+
+	/// Checks if the function tests its arguments instead of returning a
+	/// value, which means it can only be used as a condition.
+	pub const fn is_test(self) -> bool {
+		matches!(self, Self::Contains | Self::StartsWith)
+	}
+
+	/// Applies a function which returns a value to the `args`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the function fails (e.g. a file can not be
+	/// read).
+	///
+	/// # Panics
+	///
+	/// Panics if less arguments than [`Function::min_arity`] are given.
+	fn apply(self, args: &[Cow<'_, str>], settings: Settings<'_>) -> Result<String, FunctionError> {
+		Ok(match self {
+			Self::Replace => args[0].replace(&*args[1], &args[2]),
+			Self::Split => args[2]
+				.parse::<usize>()
+				.ok()
+				.and_then(|index| args[0].split(&*args[1]).nth(index))
+				.unwrap_or_default()
+				.to_owned(),
+			Self::Trim => args[0].trim().to_owned(),
+			Self::Upper => args[0].to_uppercase(),
+			Self::Lower => args[0].to_lowercase(),
+			Self::Title => {
+				let mut title = String::with_capacity(args[0].len());
+				let mut word_start = true;
+
+				for c in args[0].chars() {
+					if word_start {
+						title.extend(c.to_uppercase());
+					} else {
+						title.extend(c.to_lowercase());
+					}
+
+					word_start = !c.is_alphanumeric();
+				}
+
+				title
+			}
+			Self::Contains | Self::StartsWith => self.test(args, settings)?.to_string(),
+			Self::PathJoin => Path::new(&*args[0])
+				.join(&*args[1])
+				.to_string_lossy()
+				.into_owned(),
+			Self::Dirname => Path::new(&*args[0])
+				.parent()
+				.map(|parent| parent.to_string_lossy().into_owned())
+				.unwrap_or_default(),
+			Self::Basename => Path::new(&*args[0])
+				.file_name()
+				.map(|name| name.to_string_lossy().into_owned())
+				.unwrap_or_default(),
+			Self::ToNative => args[0].replace(['/', '\\'], MAIN_SEPARATOR_STR),
+			Self::Home => std::env::home_dir()
+				.map(|home| home.to_string_lossy().into_owned())
+				.unwrap_or_default(),
+			Self::Sha256 => hex_digest(args[0].as_bytes()),
+			Self::FileHash => hex_digest(&self.read(&args[0], settings.base_dir)?),
+			Self::B64Encode => BASE64.encode(args[0].as_bytes()),
+			Self::B64Decode => BASE64
+				.decode(args[0].trim().as_bytes())
+				.map_err(|err| err.to_string())
+				.and_then(|bytes| String::from_utf8(bytes).map_err(|err| err.to_string()))
+				.map_err(|message| FunctionError {
+					function: self,
+					message,
+				})?,
+			Self::Uuid => {
+				let mut bytes = self.random_bytes(args.first(), settings.seed);
+				// Version 4 and RFC 4122 variant
+				bytes[6] = (bytes[6] & 0x0f) | 0x40;
+				bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+				bytes
+					.iter()
+					.enumerate()
+					.fold(String::with_capacity(36), |mut uuid, (idx, byte)| {
+						if matches!(idx, 4 | 6 | 8 | 10) {
+							uuid.push('-');
+						}
+						let _ = write!(uuid, "{byte:02x}");
+						uuid
+					})
+			}
+			Self::Random => {
+				let min = self.integer(&args[0])?;
+				let max = self.integer(&args[1])?;
+				if min > max {
+					return Err(FunctionError {
+						function: self,
+						message: format!("`{min}` is greater than `{max}`"),
+					});
+				}
+
+				let bytes = self.random_bytes(args.get(2), settings.seed);
+				let number = u64::from_le_bytes([
+					bytes[0], bytes[1], bytes[2], bytes[3], bytes[4], bytes[5], bytes[6], bytes[7],
+				]);
+				let range = (i128::from(max) - i128::from(min)).unsigned_abs() + 1;
+
+				(i128::from(min) + (u128::from(number) % range) as i128).to_string()
+			}
+			Self::Now => self.format_time(self.now(args.get(1))?, &args[0])?,
+			Self::Today => self.format_time(self.now(args.first())?, "%F")?,
+			Self::Indent => indent(&args[0], self.width(&args[1])?),
+			Self::Nindent => format!("\n{}", indent(&args[0], self.width(&args[1])?)),
+			Self::ToJson => self.serialize_json(&args[0])?,
+			Self::ToYaml => self.serialize_yaml(&args[0])?,
+			Self::ToToml => self.serialize_toml(&args[0])?,
+			Self::Comment => comment(&args[0], settings.comment_prefix),
+			Self::Plugin => settings
+				.plugins
+				.ok_or_else(|| String::from("no plugins are available"))
+				.and_then(|plugins| {
+					plugins
+						.call_function(&args[1], &args[0])
+						.map_err(|err| format!("{err:#}"))
+				})
+				.map_err(|message| FunctionError {
+					function: self,
+					message,
+				})?,
+		})
+	}
+
+	/// Returns the current time, or the time of [`FROZEN_TIME_ENVVAR`] if it
+	/// is set, in the `timezone`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the timezone or the frozen time is invalid.
+	fn now(self, timezone: Option<&Cow<'_, str>>) -> Result<OffsetDateTime, FunctionError> {
+		let error = |message| FunctionError {
+			function: self,
+			message,
+		};
+
+		let now = match std::env::var(FROZEN_TIME_ENVVAR) {
+			Ok(frozen) => parse_time(&frozen)
+				.map_err(|message| error(format!("invalid `{FROZEN_TIME_ENVVAR}`: {message}")))?,
+			Err(_) => OffsetDateTime::now_utc(),
+		};
+
+		let Some(timezone) = timezone else {
+			return Ok(now.to_offset(UtcOffset::UTC));
+		};
+
+		let offset = parse_offset(timezone).ok_or_else(|| {
+			error(format!(
+				"invalid timezone `{timezone}`, expected `UTC` or an offset like `+02:00`"
+			))
+		})?;
+
+		Ok(now.to_offset(offset))
+	}
+
+	/// Formats `time` with the `strftime` like specifiers of `format`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the format contains an unsupported specifier.
+	fn format_time(self, time: OffsetDateTime, format: &str) -> Result<String, FunctionError> {
+		let mut formatted = String::with_capacity(format.len());
+		let mut chars = format.chars();
+
+		while let Some(c) = chars.next() {
+			if c != '%' {
+				formatted.push(c);
+				continue;
+			}
+
+			let _ = match chars.next() {
+				Some('Y') => write!(formatted, "{}", time.year()),
+				Some('y') => write!(formatted, "{:02}", time.year().rem_euclid(100)),
+				Some('m') => write!(formatted, "{:02}", u8::from(time.month())),
+				Some('B') => write!(formatted, "{}", time.month()),
+				Some('b') => write!(formatted, "{:.3}", time.month().to_string()),
+				Some('d') => write!(formatted, "{:02}", time.day()),
+				Some('e') => write!(formatted, "{:2}", time.day()),
+				Some('j') => write!(formatted, "{:03}", time.ordinal()),
+				Some('A') => write!(formatted, "{}", time.weekday()),
+				Some('a') => write!(formatted, "{:.3}", time.weekday().to_string()),
+				Some('H') => write!(formatted, "{:02}", time.hour()),
+				Some('I') => write!(formatted, "{:02}", (time.hour() + 11) % 12 + 1),
+				Some('p') => formatted.write_str(if time.hour() < 12 { "AM" } else { "PM" }),
+				Some('M') => write!(formatted, "{:02}", time.minute()),
+				Some('S') => write!(formatted, "{:02}", time.second()),
+				Some('s') => write!(formatted, "{}", time.unix_timestamp()),
+				Some('z') => {
+					let (hours, minutes, _) = time.offset().as_hms();
+					let sign = if time.offset().is_negative() {
+						'-'
+					} else {
+						'+'
+					};
+					write!(formatted, "{sign}{:02}{:02}", hours.abs(), minutes.abs())
+				}
+				Some('F') => formatted.write_str(&self.format_time(time, "%Y-%m-%d")?),
+				Some('T') => formatted.write_str(&self.format_time(time, "%H:%M:%S")?),
+				Some('%') => formatted.write_char('%'),
+				Some(other) => {
+					return Err(FunctionError {
+						function: self,
+						message: format!("unsupported format specifier `%{other}`"),
+					})
+				}
+				None => {
+					return Err(FunctionError {
+						function: self,
+						message: String::from("the format ends with a single `%`"),
+					})
+				}
+			};
+		}
+
+		Ok(formatted)
+	}
+
+	/// Returns 16 random bytes, which are derived from the `key` and the
+	/// `seed` if a key is given.
+	fn random_bytes(self, key: Option<&Cow<'_, str>>, seed: &str) -> [u8; 16] {
+		let mut bytes = [0; 16];
+
+		if let Some(key) = key {
+			let digest = Sha256::new()
+				.chain_update(seed)
+				.chain_update([0])
+				.chain_update(self.name())
+				.chain_update([0])
+				.chain_update(key.as_bytes())
+				.finalize();
+			bytes.copy_from_slice(&digest[..16]);
+		} else {
+			let state = RandomState::new();
+			for (idx, chunk) in bytes.chunks_mut(8).enumerate() {
+				chunk.copy_from_slice(&state.hash_one(idx).to_le_bytes());
+			}
+		}
+
+		bytes
+	}
+
+	/// Parses an integer argument.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the argument is not an integer.
+	fn integer(self, arg: &str) -> Result<i64, FunctionError> {
+		arg.trim().parse().map_err(|_| FunctionError {
+			function: self,
+			message: format!("`{arg}` is not an integer"),
+		})
+	}
+
+	/// Converts the result of a serializer into the result of the function.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the serializer failed.
+	#[cfg(any(feature = "profile-json", feature = "profile-yaml"))]
+	fn serialized<E: fmt::Display>(
+		self,
+		result: Result<String, E>,
+	) -> Result<String, FunctionError> {
+		result.map_err(|err| FunctionError {
+			function: self,
+			message: err.to_string(),
+		})
+	}
+
+	/// Returns the error of a function which needs the disabled cargo
+	/// `feature`.
+	#[cfg(not(all(feature = "profile-json", feature = "profile-yaml", feature = "toml")))]
+	fn missing_feature(self, feature: &str) -> FunctionError {
+		FunctionError {
+			function: self,
+			message: format!("punktf was built without the `{feature}` feature"),
+		}
+	}
+
+	/// Returns `value` as a json string.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the `profile-json` feature is disabled.
+	fn serialize_json(self, value: &str) -> Result<String, FunctionError> {
+		cfg_if::cfg_if! {
+			if #[cfg(feature = "profile-json")] {
+				self.serialized(serde_json::to_string(value))
+			} else {
+				let _ = value;
+				Err(self.missing_feature("profile-json"))
+			}
+		}
+	}
+
+	/// Returns `value` as a yaml string (see [`to_yaml`]).
+	///
+	/// # Errors
+	///
+	/// An error is returned if the `profile-yaml` feature is disabled.
+	fn serialize_yaml(self, value: &str) -> Result<String, FunctionError> {
+		cfg_if::cfg_if! {
+			if #[cfg(feature = "profile-yaml")] {
+				self.serialized(to_yaml(value))
+			} else {
+				let _ = value;
+				Err(self.missing_feature("profile-yaml"))
+			}
+		}
+	}
+
+	/// Returns `value` as a toml string.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the `toml` feature is disabled.
+	fn serialize_toml(self, value: &str) -> Result<String, FunctionError> {
+		cfg_if::cfg_if! {
+			if #[cfg(feature = "toml")] {
+				Ok(toml::Value::String(value.to_owned()).to_string())
+			} else {
+				let _ = value;
+				Err(self.missing_feature("toml"))
+			}
+		}
+	}
+
+	/// Parses a width argument, which is a non-negative integer.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the argument is not an integer or negative.
+	fn width(self, arg: &str) -> Result<usize, FunctionError> {
+		usize::try_from(self.integer(arg)?).map_err(|_| FunctionError {
+			function: self,
+			message: format!("`{arg}` is negative"),
+		})
+	}
+
+	/// Reads the content of the file at `path`, which is resolved from
+	/// `base_dir` if it is relative.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file can not be read.
+	#[cfg(feature = "io")]
+	fn read(self, path: &str, base_dir: Option<&Path>) -> Result<Vec<u8>, FunctionError> {
+		std::fs::read(resolve(path, base_dir)).map_err(|err| FunctionError {
+			function: self,
+			message: format!("failed to read `{path}`: {err}"),
+		})
+	}
+
+	/// Reads the content of the file at `path`.
+	///
+	/// # Errors
+	///
+	/// Always returns an error, as the `io` feature is disabled.
+	#[cfg(not(feature = "io"))]
+	fn read(self, path: &str, _base_dir: Option<&Path>) -> Result<Vec<u8>, FunctionError> {
+		Err(FunctionError {
+			function: self,
+			message: format!("failed to read `{path}`: reading files is not supported"),
+		})
+	}
+
+	/// Applies a function which tests its arguments to the `args`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the function fails.
+	///
+	/// # Panics
+	///
+	/// Panics if less arguments than [`Function::min_arity`] are given.
+	fn test(self, args: &[Cow<'_, str>], settings: Settings<'_>) -> Result<bool, FunctionError> {
+		Ok(match self {
+			Self::Contains => args[0].contains(&*args[1]),
+			Self::StartsWith => args[0].starts_with(&*args[1]),
+			_ => !self.apply(args, settings)?.is_empty(),
+		})
+	}
+
+	/// Returns the [input](`Input`) from outside of the expression which the
+	/// function reads when it is applied to `args`, if any.
+	fn input(self, args: &[Cow<'_, str>], settings: Settings<'_>) -> Option<Input> {
+		match self {
+			Self::Home => Some(Input::Home),
+			Self::FileHash => Some(Input::File(resolve(&args[0], settings.base_dir))),
+			Self::Uuid if args.is_empty() => Some(Input::Volatile),
+			// Plugins can read anything, so their results are never reused
+			Self::Plugin => Some(Input::Volatile),
+			Self::Random if args.len() < 3 => Some(Input::Volatile),
+			Self::Now | Self::Today => Some(match std::env::var_os(FROZEN_TIME_ENVVAR) {
+				Some(_) => Input::Env(FROZEN_TIME_ENVVAR.to_owned()),
+				None => Input::Volatile,
+			}),
+			_ => None,
+		}
+	}
+
+	/// Records the [input](`Input`) which the function reads when it is
+	/// applied to `args` with `lookup`.
+	fn record<'v, V, E>(self, args: &[Cow<'_, str>], lookup: &impl Lookup<'v, V, E>) {
+		if let Some(input) = self.input(args, lookup.settings()) {
+			lookup.record(input);
+		}
+	}
+}
+
+/// Resolves `path` from `base_dir` if it is relative.
+fn resolve(path: &str, base_dir: Option<&Path>) -> PathBuf {
+	base_dir.map_or_else(|| PathBuf::from(path), |dir| dir.join(path))
+}
+
+/// Boolean values of yaml 1.1 which `serde_yaml` (yaml 1.2) writes without
+/// quotes, but which are read as booleans by many other yaml parsers.
+#[cfg(feature = "profile-yaml")]
+const YAML_11_BOOLS: [&str; 16] = [
+	"y", "Y", "yes", "Yes", "YES", "n", "N", "no", "No", "NO", "on", "On", "ON", "off", "Off",
+	"OFF",
+];
+
+/// Returns `value` as a yaml string, which is quoted if needed.
+///
+/// `serde_yaml` writes strings with multiple lines as block scalars, which only
+/// work on their own line. These are written as double quoted json strings
+/// instead, which are valid yaml strings as well. Values which are booleans in
+/// yaml 1.1 are quoted as well.
+///
+/// # Errors
+///
+/// An error is returned if the value could not be serialized.
+#[cfg(feature = "profile-yaml")]
+fn to_yaml(value: &str) -> Result<String, Box<dyn std::error::Error>> {
+	let yaml = serde_yaml::to_string(value)?;
+	let yaml = yaml.strip_suffix('\n').unwrap_or(&yaml);
+
+	if yaml.contains('\n') {
+		Ok(serde_json::to_string(value)?)
+	} else if YAML_11_BOOLS.contains(&yaml) {
+		Ok(format!("'{yaml}'"))
+	} else {
+		Ok(yaml.to_owned())
+	}
+}
+
+/// Prefixes all lines of `text` with `prefix` and a space, empty lines only
+/// with `prefix`.
+fn comment(text: &str, prefix: &str) -> String {
+	text.split_inclusive('\n')
+		.fold(String::with_capacity(text.len()), |mut commented, line| {
+			commented.push_str(prefix);
+			if !line.trim_end_matches(['\r', '\n']).is_empty() {
+				commented.push(' ');
+			}
+			commented.push_str(line);
+			commented
+		})
+}
+
+/// Indents all non-empty lines of `value` by `width` spaces.
+fn indent(value: &str, width: usize) -> String {
+	let indent = " ".repeat(width);
+
+	value
+		.split_inclusive('\n')
+		.fold(String::with_capacity(value.len()), |mut indented, line| {
+			if !line.trim_end_matches(['\r', '\n']).is_empty() {
+				indented.push_str(&indent);
+			}
+			indented.push_str(line);
+			indented
+		})
+}
+
+/// Parses a timezone which is either `UTC` or an offset like `+02:00`,
+/// `-0530` or `+2`.
+fn parse_offset(timezone: &str) -> Option<UtcOffset> {
+	let timezone = timezone.trim();
+	if timezone.eq_ignore_ascii_case("utc") || timezone == "Z" {
+		return Some(UtcOffset::UTC);
+	}
+
+	let (sign, rest) = match timezone.split_at_checked(1)? {
+		("+", rest) => (1, rest),
+		("-", rest) => (-1, rest),
+		_ => return None,
+	};
+	let (hours, minutes) = match rest.split_once(':') {
+		Some((hours, minutes)) => (hours, minutes),
+		None if rest.len() == 4 => rest.split_at(2),
+		None => (rest, "0"),
+	};
+
+	let hours = hours.parse::<i8>().ok()?;
+	let minutes = minutes.parse::<i8>().ok()?;
+	if !(0..=23).contains(&hours) || !(0..=59).contains(&minutes) {
+		return None;
+	}
+
+	UtcOffset::from_hms(sign * hours, sign * minutes, 0).ok()
+}
+
+/// Returns the SHA-256 hash of `content` as hexadecimal digits.
+fn hex_digest(content: &[u8]) -> String {
+	Sha256::digest(content)
+		.iter()
+		.fold(String::with_capacity(64), |mut hex, byte| {
+			let _ = write!(hex, "{byte:02x}");
+			hex
+		})
+}
+
+/// Error which is returned if a [function](`Function`) fails.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Function `{function}` failed: {message}")]
+pub struct FunctionError {
+	/// The function which failed.
+	pub function: Function,
+
+	/// Description of the error.
+	pub message: String,
+}
+
+impl fmt::Display for Function {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.name())
+	}
+}
+
+/// Provides the values of variables to the evaluation of an expression.
+///
+/// It is implemented for all closures which return the value of a variable
+/// (`|var| ...`), which use the default [settings](`Settings`). Use [`Scope`]
+/// to provide other settings.
+pub trait Lookup<'v, V, E> {
+	/// Returns the value of `var` or an error if it is not defined.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the variable is not defined.
+	fn var(&self, var: &V) -> Result<Cow<'v, str>, E>;
+
+	/// Returns the settings which are used by some functions.
+	fn settings(&self) -> Settings<'_> {
+		Settings::default()
+	}
+
+	/// Records that `input` was read from outside of the expression while
+	/// evaluating it. Does nothing by default.
+	fn record(&self, _input: Input) {}
+}
+
+/// An input from outside of an expression or template which is read while
+/// evaluating it (see [`Lookup::record`]).
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Input {
+	/// The environment variable with this name, which is read whether it is
+	/// set or not.
+	Env(String),
+
+	/// The file at this path (e.g. of [`Function::FileHash`]).
+	File(PathBuf),
+
+	/// The template with this name, which is loaded by a render or import
+	/// block (see [`Includes`](`crate::template::Includes`)).
+	Include(String),
+
+	/// The home directory of the current user (see [`Function::Home`]).
+	Home,
+
+	/// A value which changes with every evaluation, like the current time or
+	/// a random value without a key.
+	Volatile,
+}
+
+/// Settings of the place where an expression is evaluated, which are used by
+/// some functions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Settings<'a> {
+	/// The seed from which [`Function::Uuid`] and [`Function::Random`] derive
+	/// stable values. Defaults to an empty string.
+	pub seed: &'a str,
+
+	/// The prefix of line comments which is used by [`Function::Comment`].
+	/// Defaults to `#`.
+	pub comment_prefix: &'a str,
+
+	/// The directory from which [`Function::FileHash`] resolves relative
+	/// paths (e.g. the directory of a template). Defaults to `None`, which
+	/// resolves them from the current directory.
+	pub base_dir: Option<&'a Path>,
+
+	/// The plugins whose functions are called by [`Function::Plugin`].
+	/// Defaults to `None`, which makes all calls fail.
+	pub plugins: Option<&'a Plugins>,
+}
+
+impl Default for Settings<'_> {
+	fn default() -> Self {
+		Self {
+			seed: "",
+			comment_prefix: "#",
+			base_dir: None,
+			plugins: None,
+		}
+	}
+}
+
+impl<'v, V, E, F> Lookup<'v, V, E> for F
+where
+	F: Fn(&V) -> Result<Cow<'v, str>, E>,
+{
+	fn var(&self, var: &V) -> Result<Cow<'v, str>, E> {
+		self(var)
+	}
+}
+
+/// A [lookup](`Lookup`) of variables with the [settings](`Settings`) of the
+/// place where an expression is evaluated.
+pub struct Scope<F> {
+	/// Returns the value of a variable.
+	lookup: F,
+
+	/// See [`Settings::seed`].
+	seed: String,
+
+	/// See [`Settings::comment_prefix`].
+	comment_prefix: String,
+
+	/// See [`Settings::base_dir`].
+	base_dir: Option<PathBuf>,
+}
+
+impl<F> Scope<F> {
+	/// Creates a new scope which returns the values of variables with
+	/// `lookup` and uses the default settings.
+	pub fn new(lookup: F) -> Self {
+		let Settings {
+			seed,
+			comment_prefix,
+			base_dir,
+			..
+		} = Settings::default();
+
+		Self {
+			lookup,
+			seed: seed.to_owned(),
+			comment_prefix: comment_prefix.to_owned(),
+			base_dir: base_dir.map(Path::to_path_buf),
+		}
+	}
+
+	/// Uses `seed` as the [seed](`Settings::seed`).
+	pub fn with_seed(mut self, seed: String) -> Self {
+		self.seed = seed;
+		self
+	}
+
+	/// Uses `prefix` as the [comment prefix](`Settings::comment_prefix`).
+	pub fn with_comment_prefix(mut self, prefix: String) -> Self {
+		self.comment_prefix = prefix;
+		self
+	}
+
+	/// Uses `dir` as the [base directory](`Settings::base_dir`).
+	pub fn with_base_dir(mut self, dir: PathBuf) -> Self {
+		self.base_dir = Some(dir);
+		self
+	}
+}
+
+impl<F> fmt::Debug for Scope<F> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Scope")
+			.field("seed", &self.seed)
+			.field("comment_prefix", &self.comment_prefix)
+			.field("base_dir", &self.base_dir)
+			.finish_non_exhaustive()
+	}
+}
+
+impl<'v, V, E, F> Lookup<'v, V, E> for Scope<F>
+where
+	F: Fn(&V) -> Result<Cow<'v, str>, E>,
+{
+	fn var(&self, var: &V) -> Result<Cow<'v, str>, E> {
+		(self.lookup)(var)
+	}
+
+	fn settings(&self) -> Settings<'_> {
+		Settings {
+			seed: &self.seed,
+			comment_prefix: &self.comment_prefix,
+			base_dir: self.base_dir.as_deref(),
+			plugins: None,
+		}
+	}
+}
+
+/// A side of a comparison.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Operand<V> {
+	/// The value of a variable.
+	Var(V),
+
+	/// A string literal.
+	Literal(String),
+
+	/// The value of a [function](`Function`) which does not test its
+	/// arguments.
+	Call(Function, Vec<Operand<V>>),
+}
+
+/// Evaluates all `args` of a function.
+fn values<'s, 'v: 's, V, E: From<FunctionError>>(
+	args: &'s [Operand<V>],
+	lookup: &impl Lookup<'v, V, E>,
+) -> Result<Vec<Cow<'s, str>>, E> {
+	args.iter().map(|arg| arg.value(lookup)).collect()
+}
+
+impl<V> Operand<V> {
+	/// Returns the value of the operand.
+	///
+	/// # Errors
+	///
+	/// The error of `lookup` is returned if the variable is not defined.
+	/// An error is returned if a function fails.
+	pub fn value<'s, 'v: 's, E: From<FunctionError>>(
+		&'s self,
+		lookup: &impl Lookup<'v, V, E>,
+	) -> Result<Cow<'s, str>, E> {
+		match self {
+			Self::Var(var) => lookup.var(var),
+			Self::Literal(value) => Ok(Cow::Borrowed(value)),
+			Self::Call(function, args) => {
+				let args = values(args, lookup)?;
+				function.record(&args, lookup);
+
+				Ok(Cow::Owned(function.apply(&args, lookup.settings())?))
+			}
+		}
+	}
+
+	/// Calls `f` for all variables of the operand, including the ones in the
+	/// arguments of functions.
+	pub fn visit_vars<'s>(&'s self, f: &mut impl FnMut(&'s V)) {
+		match self {
+			Self::Var(var) => f(var),
+			Self::Literal(_) => {}
+			Self::Call(_, args) => args.iter().for_each(|arg| arg.visit_vars(f)),
+		}
+	}
+}
+
+/// A parsed expression with variables of type `V`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Expr<V> {
+	/// Holds if both expressions hold.
+	And(Box<Expr<V>>, Box<Expr<V>>),
+
+	/// Holds if any of the expressions holds.
+	Or(Box<Expr<V>>, Box<Expr<V>>),
+
+	/// Holds if the expression does not hold.
+	Not(Box<Expr<V>>),
+
+	/// Holds if the operands are (not) equal.
+	Compare {
+		/// Left hand side of the comparison.
+		lhs: Operand<V>,
+
+		/// Indicates if the operands must be equal (`==`) or not (`!=`).
+		equal: bool,
+
+		/// Right hand side of the comparison.
+		rhs: Operand<V>,
+	},
+
+	/// Holds if the value of the operand matches the pattern.
+	Matches(Operand<V>, Pattern),
+
+	/// Holds if the operand is defined.
+	Defined(Operand<V>),
+
+	/// Holds if the [function](`Function`) which tests its arguments holds.
+	Test(Function, Vec<Operand<V>>),
+}
+
+impl<V> Expr<V> {
+	/// Evaluates the expression.
+	///
+	/// `lookup` returns the value of a variable or an error if the variable is
+	/// not defined.
+	///
+	/// # Errors
+	///
+	/// The error of `lookup` is returned if a variable which is compared is
+	/// not defined. An error is returned if a function fails.
+	pub fn eval<'v, E: From<FunctionError>>(
+		&self,
+		lookup: &impl Lookup<'v, V, E>,
+	) -> Result<bool, E> {
+		Ok(match self {
+			Self::And(lhs, rhs) => lhs.eval(lookup)? && rhs.eval(lookup)?,
+			Self::Or(lhs, rhs) => lhs.eval(lookup)? || rhs.eval(lookup)?,
+			Self::Not(expr) => !expr.eval(lookup)?,
+			Self::Compare { lhs, equal, rhs } => {
+				(lhs.value(lookup)? == rhs.value(lookup)?) == *equal
+			}
+			Self::Matches(operand, pattern) => pattern.is_match(&operand.value(lookup)?),
+			Self::Defined(operand) => operand.value(lookup).is_ok(),
+			Self::Test(function, args) => {
+				let args = values(args, lookup)?;
+				function.record(&args, lookup);
+
+				function.test(&args, lookup.settings())?
+			}
+		})
+	}
+
+	/// Calls `f` for all variables of the expression.
+	///
+	/// The second argument of `f` indicates if the expression only checks if
+	/// the variable is defined (e.g. `!{{EDITOR}}`).
+	pub fn visit_vars<'s>(&'s self, f: &mut impl FnMut(&'s V, bool)) {
+		match self {
+			Self::And(lhs, rhs) | Self::Or(lhs, rhs) => {
+				lhs.visit_vars(f);
+				rhs.visit_vars(f);
+			}
+			Self::Not(expr) => expr.visit_vars(f),
+			Self::Compare { lhs, rhs, .. } => {
+				lhs.visit_vars(&mut |var| f(var, false));
+				rhs.visit_vars(&mut |var| f(var, false));
+			}
+			Self::Matches(operand, _) => operand.visit_vars(&mut |var| f(var, false)),
+			Self::Defined(operand) => operand.visit_vars(&mut |var| f(var, true)),
+			Self::Test(_, args) => args
+				.iter()
+				.for_each(|arg| arg.visit_vars(&mut |var| f(var, false))),
+		}
+	}
+}
+
+/// A value which is either an operand or chosen by an inline conditional.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Value<V> {
+	/// The value of an operand.
+	Operand(Operand<V>),
+
+	/// `condition ? then : otherwise`
+	Ternary {
+		/// Condition which chooses the value.
+		condition: Expr<V>,
+
+		/// Value if the condition holds.
+		then: Operand<V>,
+
+		/// Value if the condition does not hold.
+		otherwise: Box<Value<V>>,
+	},
+}
+
+impl<V> Value<V> {
+	/// Evaluates the value.
+	///
+	/// `lookup` returns the value of a variable or an error if the variable is
+	/// not defined.
+	///
+	/// # Errors
+	///
+	/// The error of `lookup` is returned if a variable which is compared or
+	/// chosen is not defined. An error is returned if a function fails.
+	pub fn eval<'s, 'v: 's, E: From<FunctionError>>(
+		&'s self,
+		lookup: &impl Lookup<'v, V, E>,
+	) -> Result<Cow<'s, str>, E> {
+		match self {
+			Self::Operand(operand) => operand.value(lookup),
+			Self::Ternary {
+				condition,
+				then,
+				otherwise,
+			} => {
+				if condition.eval(lookup)? {
+					then.value(lookup)
+				} else {
+					otherwise.eval(lookup)
+				}
+			}
+		}
+	}
+
+	/// Calls `f` for all variables of the value.
+	///
+	/// The second argument of `f` indicates if the value only checks if the
+	/// variable is defined (see [`Expr::visit_vars`]).
+	pub fn visit_vars<'s>(&'s self, f: &mut impl FnMut(&'s V, bool)) {
+		match self {
+			Self::Operand(operand) => operand.visit_vars(&mut |var| f(var, false)),
+			Self::Ternary {
+				condition,
+				then,
+				otherwise,
+			} => {
+				condition.visit_vars(f);
+				then.visit_vars(&mut |var| f(var, false));
+				otherwise.visit_vars(f);
+			}
+		}
+	}
+}
+
+/// A token of an expression.
+#[derive(Debug)]
+enum Token<V> {
+	/// A variable.
+	Var(V),
+
+	/// A quoted string.
+	Str(String),
+
+	/// `==`
+	Eq,
+
+	/// `!=`
+	NotEq,
+
+	/// `matches`
+	Matches,
+
+	/// `&&`
+	And,
+
+	/// `||`
+	Or,
+
+	/// `!`
+	Not,
+
+	/// `(`
+	Open,
+
+	/// `)`
+	Close,
+
+	/// `,`
+	Comma,
+
+	/// `?`
+	Question,
+
+	/// `:`
+	Colon,
+
+	/// `|`
+	Pipe,
+
+	/// The name of a function.
+	Function(Function),
+}
+
+impl<V> fmt::Display for Token<V> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Var(_) => f.write_str("variable"),
+			Self::Str(value) => write!(f, "string {value:?}"),
+			Self::Eq => f.write_str("`==`"),
+			Self::NotEq => f.write_str("`!=`"),
+			Self::Matches => f.write_str("`matches`"),
+			Self::And => f.write_str("`&&`"),
+			Self::Or => f.write_str("`||`"),
+			Self::Not => f.write_str("`!`"),
+			Self::Open => f.write_str("`(`"),
+			Self::Close => f.write_str("`)`"),
+			Self::Comma => f.write_str("`,`"),
+			Self::Question => f.write_str("`?`"),
+			Self::Colon => f.write_str("`:`"),
+			Self::Pipe => f.write_str("`|`"),
+			Self::Function(function) => write!(f, "function `{function}`"),
+		}
+	}
+}
+
+/// Checks if `c` can be part of a word (e.g. the `matches` keyword).
+fn is_word_char(c: char) -> bool {
+	c.is_alphanumeric() || c == '_'
+}
+
+/// Parses the string starting at the quote at the beginning of `s`.
+///
+/// Returns the length of the string including the quotes and its value.
+fn parse_str(s: &str, offset: usize) -> Result<(usize, String), ParseExprError> {
+	let mut chars = s.char_indices();
+	let quote = chars.next().map(|(_, quote)| quote);
+	let mut value = String::new();
+
+	while let Some((idx, c)) = chars.next() {
+		match c {
+			c if Some(c) == quote => return Ok((idx + c.len_utf8(), value)),
+			'\\' => match chars.clone().next() {
+				Some((_, escaped)) if escaped == '\\' || Some(escaped) == quote => {
+					chars.next();
+					value.push(escaped);
+				}
+				_ => value.push(c),
+			},
+			c => value.push(c),
+		}
+	}
+
+	Err(ParseExprError::new(offset, "unterminated string"))
+}
+
+/// Returns the length of the name of a function at the start of `rest`.
+///
+/// A word is the name of a function if it is followed by `(` or if it follows
+/// a `|`.
+fn function_name_len<V>(rest: &str, previous: Option<&(usize, Token<V>)>) -> Option<usize> {
+	let len = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+
+	(len > 0 && (rest[len..].starts_with('(') || matches!(previous, Some((_, Token::Pipe)))))
+		.then_some(len)
+}
+
+/// Splits the expression `s` into tokens together with their byte offsets.
+fn tokenize<V>(
+	s: &str,
+	parse_var: &impl Fn(&str, usize) -> Option<Result<(usize, V), ParseExprError>>,
+) -> Result<Vec<(usize, Token<V>)>, ParseExprError> {
+	let mut tokens = Vec::new();
+	let mut offset = 0;
+
+	while let Some(c) = s[offset..].chars().next() {
+		let rest = &s[offset..];
+
+		let (len, token) = if c.is_whitespace() {
+			offset += c.len_utf8();
+			continue;
+		} else if rest.starts_with("==") {
+			(2, Token::Eq)
+		} else if rest.starts_with("!=") {
+			(2, Token::NotEq)
+		} else if rest.starts_with("&&") {
+			(2, Token::And)
+		} else if rest.starts_with("||") {
+			(2, Token::Or)
+		} else if c == '!' {
+			(1, Token::Not)
+		} else if c == '(' {
+			(1, Token::Open)
+		} else if c == ')' {
+			(1, Token::Close)
+		} else if c == ',' {
+			(1, Token::Comma)
+		} else if c == '?' {
+			(1, Token::Question)
+		} else if c == ':' {
+			(1, Token::Colon)
+		} else if c == '|' {
+			(1, Token::Pipe)
+		} else if c == '"' || c == '\'' {
+			let (len, value) = parse_str(rest, offset)?;
+			(len, Token::Str(value))
+		} else if rest.starts_with("matches") && !rest[7..].starts_with(is_word_char) {
+			(7, Token::Matches)
+		} else if let Some(len) = function_name_len(rest, tokens.last()) {
+			let function = Function::from_name(&rest[..len]).ok_or_else(|| {
+				ParseExprError::new(offset, format!("unknown function `{}`", &rest[..len]))
+			})?;
+			(len, Token::Function(function))
+		} else if let Some(var) = parse_var(rest, offset) {
+			let (len, var) = var?;
+			(len, Token::Var(var))
+		} else {
+			return Err(ParseExprError::new(
+				offset,
+				format!("unexpected character `{c}`"),
+			));
+		};
+
+		tokens.push((offset, token));
+		offset += len;
+	}
+
+	Ok(tokens)
+}
+
+/// Parses the expression `s`.
+///
+/// Variables are recognized by `parse_var`, which is called with the
+/// remainder of the expression and its byte offset in `s`. It returns `None`
+/// if no variable starts there or the length of the variable and the
+/// variable itself.
+///
+/// # Errors
+///
+/// An error is returned if `s` is not a valid expression or if `parse_var`
+/// fails.
+pub fn parse<V>(
+	s: &str,
+	parse_var: impl Fn(&str, usize) -> Option<Result<(usize, V), ParseExprError>>,
+) -> Result<Expr<V>, ParseExprError> {
+	let mut parser = Parser {
+		tokens: tokenize(s, &parse_var)?.into_iter().peekable(),
+		end: s.len(),
+	};
+
+	let expr = parser.parse_or()?;
+	if let Some((offset, token)) = parser.tokens.next() {
+		return Err(ParseExprError::new(offset, format!("unexpected {token}")));
+	}
+
+	Ok(expr)
+}
+
+/// Parses the [value](`Value`) `s`, which is an operand or an inline
+/// conditional (e.g. `a == "b" ? "c" : "d"`).
+///
+/// Variables are recognized by `parse_var` like in [`parse`].
+///
+/// # Errors
+///
+/// An error is returned if `s` is not a valid value or if `parse_var` fails.
+pub fn parse_value<V>(
+	s: &str,
+	parse_var: impl Fn(&str, usize) -> Option<Result<(usize, V), ParseExprError>>,
+) -> Result<Value<V>, ParseExprError> {
+	let mut parser = Parser {
+		tokens: tokenize(s, &parse_var)?.into_iter().peekable(),
+		end: s.len(),
+	};
+
+	let value = parser.parse_value()?;
+	if let Some((offset, token)) = parser.tokens.next() {
+		return Err(ParseExprError::new(offset, format!("unexpected {token}")));
+	}
+
+	Ok(value)
+}
+
+/// Parses the list of operands `s`, which are separated by `,`.
+///
+/// Variables are recognized by `parse_var` like in [`parse`]. An empty `s`
+/// results in an empty list.
+///
+/// # Errors
+///
+/// An error is returned if `s` is not a valid list of operands or if
+/// `parse_var` fails.
+pub fn parse_operands<V>(
+	s: &str,
+	parse_var: impl Fn(&str, usize) -> Option<Result<(usize, V), ParseExprError>>,
+) -> Result<Vec<Operand<V>>, ParseExprError> {
+	let mut parser = Parser {
+		tokens: tokenize(s, &parse_var)?.into_iter().peekable(),
+		end: s.len(),
+	};
+
+	let mut operands = Vec::new();
+	if parser.tokens.peek().is_none() {
+		return Ok(operands);
+	}
+
+	loop {
+		operands.push(parser.parse_operand()?);
+
+		match parser.tokens.next() {
+			None => return Ok(operands),
+			Some((_, Token::Comma)) => {}
+			Some((offset, token)) => {
+				return Err(ParseExprError::new(
+					offset,
+					format!("expected `,`, got {token}"),
+				))
+			}
+		}
+	}
+}
+
+/// Parses the filters `s` (`| function(...) | ...`), which are applied to a
+/// value that is only known later (e.g. a rendered template).
+///
+/// Variables are recognized by `parse_var` like in [`parse`].
+///
+/// # Errors
+///
+/// An error is returned if `s` is not a valid list of filters or if
+/// `parse_var` fails.
+pub fn parse_filters<V>(
+	s: &str,
+	parse_var: impl Fn(&str, usize) -> Option<Result<(usize, V), ParseExprError>>,
+) -> Result<Vec<Filter<V>>, ParseExprError> {
+	let mut parser = Parser {
+		tokens: tokenize(s, &parse_var)?.into_iter().peekable(),
+		end: s.len(),
+	};
+
+	let mut filters = Vec::new();
+	while let Some((offset, token)) = parser.tokens.next() {
+		if !matches!(token, Token::Pipe) {
+			return Err(ParseExprError::new(
+				offset,
+				format!("expected `|`, got {token}"),
+			));
+		}
+
+		let (offset, function) = match parser.next()? {
+			(offset, Token::Function(function)) => (offset, function),
+			(offset, token) => {
+				return Err(ParseExprError::new(
+					offset,
+					format!("expected a function after `|`, got {token}"),
+				))
+			}
+		};
+
+		// The value is only a placeholder to check the arguments
+		match parser.parse_call(offset, function, vec![Operand::Literal(String::new())])? {
+			Term::Operand(Operand::Call(function, mut args)) => {
+				args.remove(0);
+				filters.push(Filter { function, args });
+			}
+			_ => {
+				return Err(ParseExprError::new(
+					offset,
+					format!("the function `{function}` has no value which can be filtered"),
+				))
+			}
+		}
+	}
+
+	Ok(filters)
+}
+
+/// A [function](`Function`) which is applied to a value that is only known
+/// later (see [`parse_filters`]).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Filter<V> {
+	/// The function.
+	pub function: Function,
+
+	/// The arguments of the function after the value.
+	pub args: Vec<Operand<V>>,
+}
+
+impl<V> Filter<V> {
+	/// Applies the filter to `value`.
+	///
+	/// # Errors
+	///
+	/// The error of `lookup` is returned if a variable is not defined. An
+	/// error is returned if the function fails.
+	pub fn apply<'v, E: From<FunctionError>>(
+		&self,
+		value: &str,
+		lookup: &impl Lookup<'v, V, E>,
+	) -> Result<String, E> {
+		let mut args = vec![Cow::Borrowed(value)];
+		for arg in &self.args {
+			args.push(arg.value(lookup)?);
+		}
+
+		self.function.record(&args, lookup);
+
+		Ok(self.function.apply(&args, lookup.settings())?)
+	}
+}
+
+/// A parsed operand or a function which tests its arguments.
+#[derive(Debug)]
+enum Term<V> {
+	/// An operand which has a value.
+	Operand(Operand<V>),
+
+	/// A function which tests its arguments.
+	Test(Function, Vec<Operand<V>>),
+}
+
+/// Recursive descent parser for expressions.
+#[derive(Debug)]
+struct Parser<V> {
+	/// The remaining tokens of the expression.
+	tokens: std::iter::Peekable<std::vec::IntoIter<(usize, Token<V>)>>,
+
+	/// Byte length of the expression.
+	end: usize,
+}
+
+impl<V> Parser<V> {
+	/// Consumes the next token if `matches` returns `true` for it.
+	fn next_if(&mut self, matches: impl Fn(&Token<V>) -> bool) -> bool {
+		self.tokens.next_if(|(_, token)| matches(token)).is_some()
+	}
+
+	/// Consumes and returns the next token.
+	fn next(&mut self) -> Result<(usize, Token<V>), ParseExprError> {
+		self.tokens
+			.next()
+			.ok_or_else(|| ParseExprError::new(self.end, "unexpected end of expression"))
+	}
+
+	/// Parses `and ('||' and)*`.
+	fn parse_or(&mut self) -> Result<Expr<V>, ParseExprError> {
+		let mut expr = self.parse_and()?;
+
+		while self.next_if(|token| matches!(token, Token::Or)) {
+			expr = Expr::Or(Box::new(expr), Box::new(self.parse_and()?));
+		}
+
+		Ok(expr)
+	}
+
+	/// Parses `unary ('&&' unary)*`.
+	fn parse_and(&mut self) -> Result<Expr<V>, ParseExprError> {
+		let mut expr = self.parse_unary()?;
+
+		while self.next_if(|token| matches!(token, Token::And)) {
+			expr = Expr::And(Box::new(expr), Box::new(self.parse_unary()?));
+		}
+
+		Ok(expr)
+	}
+
+	/// Parses `'!' unary | '(' or ')' | comparison`.
+	fn parse_unary(&mut self) -> Result<Expr<V>, ParseExprError> {
+		if self.next_if(|token| matches!(token, Token::Not)) {
+			Ok(Expr::Not(Box::new(self.parse_unary()?)))
+		} else if self.next_if(|token| matches!(token, Token::Open)) {
+			let expr = self.parse_or()?;
+
+			match self.next()? {
+				(_, Token::Close) => Ok(expr),
+				(offset, token) => Err(ParseExprError::new(
+					offset,
+					format!("expected `)`, got {token}"),
+				)),
+			}
+		} else {
+			self.parse_comparison()
+		}
+	}
+
+	/// Parses `operand (('==' | '!=') operand | 'matches' string)?` or a
+	/// function which tests its arguments.
+	fn parse_comparison(&mut self) -> Result<Expr<V>, ParseExprError> {
+		let lhs = match self.parse_term()? {
+			Term::Operand(operand) => operand,
+			Term::Test(function, args) => return Ok(Expr::Test(function, args)),
+		};
+
+		if self.next_if(|token| matches!(token, Token::Eq)) {
+			Ok(Expr::Compare {
+				lhs,
+				equal: true,
+				rhs: self.parse_operand()?,
+			})
+		} else if self.next_if(|token| matches!(token, Token::NotEq)) {
+			Ok(Expr::Compare {
+				lhs,
+				equal: false,
+				rhs: self.parse_operand()?,
+			})
+		} else if self.next_if(|token| matches!(token, Token::Matches)) {
+			match self.next()? {
+				(offset, Token::Str(pattern)) => {
+					let regex = Regex::new(&pattern).map_err(|err| {
+						ParseExprError::new(offset, format!("invalid regex: {err}"))
+					})?;
+
+					Ok(Expr::Matches(lhs, Pattern(regex)))
+				}
+				(offset, token) => Err(ParseExprError::new(
+					offset,
+					format!("expected a string after `matches`, got {token}"),
+				)),
+			}
+		} else {
+			Ok(Expr::Defined(lhs))
+		}
+	}
+
+	/// Parses `or ('?' operand ':' value)?`, where the `or` must be an operand
+	/// if it is not followed by `?`.
+	fn parse_value(&mut self) -> Result<Value<V>, ParseExprError> {
+		let condition = self.parse_or()?;
+
+		if !self.next_if(|token| matches!(token, Token::Question)) {
+			return match condition {
+				Expr::Defined(operand) => Ok(Value::Operand(operand)),
+				_ => Err(ParseExprError::new(
+					self.tokens.peek().map_or(self.end, |(offset, _)| *offset),
+					"expected `?` after the condition",
+				)),
+			};
+		}
+
+		let then = self.parse_operand()?;
+
+		match self.next()? {
+			(_, Token::Colon) => {}
+			(offset, token) => {
+				return Err(ParseExprError::new(
+					offset,
+					format!("expected `:`, got {token}"),
+				))
+			}
+		}
+
+		Ok(Value::Ternary {
+			condition,
+			then,
+			otherwise: Box::new(self.parse_value()?),
+		})
+	}
+
+	/// Parses a variable, a string or a function which returns a value.
+	fn parse_operand(&mut self) -> Result<Operand<V>, ParseExprError> {
+		let offset = self.tokens.peek().map_or(self.end, |(offset, _)| *offset);
+
+		match self.parse_term()? {
+			Term::Operand(operand) => Ok(operand),
+			Term::Test(function, _) => Err(ParseExprError::new(
+				offset,
+				format!("the function `{function}` can only be used as a condition"),
+			)),
+		}
+	}
+
+	/// Parses `(variable | string | function) ('|' function)*`.
+	fn parse_term(&mut self) -> Result<Term<V>, ParseExprError> {
+		let mut term = match self.next()? {
+			(_, Token::Var(var)) => Term::Operand(Operand::Var(var)),
+			(_, Token::Str(value)) => Term::Operand(Operand::Literal(value)),
+			(offset, Token::Function(function)) => self.parse_call(offset, function, Vec::new())?,
+			(offset, token) => {
+				return Err(ParseExprError::new(
+					offset,
+					format!("expected a variable, a string or a function, got {token}"),
+				))
+			}
+		};
+
+		while let Some((offset, _)) = self
+			.tokens
+			.next_if(|(_, token)| matches!(token, Token::Pipe))
+		{
+			let value = match term {
+				Term::Operand(operand) => operand,
+				Term::Test(function, _) => {
+					return Err(ParseExprError::new(
+						offset,
+						format!("the function `{function}` has no value which can be filtered"),
+					))
+				}
+			};
+
+			term = match self.next()? {
+				(offset, Token::Function(function)) => {
+					self.parse_call(offset, function, vec![value])?
+				}
+				(offset, token) => {
+					return Err(ParseExprError::new(
+						offset,
+						format!("expected a function after `|`, got {token}"),
+					))
+				}
+			};
+		}
+
+		Ok(term)
+	}
+
+	/// Parses the arguments of the `function` at `offset`, which are appended
+	/// to `args`.
+	///
+	/// The arguments are optional if `args` already contains all arguments
+	/// (e.g. `value | upper`).
+	fn parse_call(
+		&mut self,
+		offset: usize,
+		function: Function,
+		mut args: Vec<Operand<V>>,
+	) -> Result<Term<V>, ParseExprError> {
+		if self.next_if(|token| matches!(token, Token::Open))
+			&& !self.next_if(|token| matches!(token, Token::Close))
+		{
+			loop {
+				args.push(self.parse_operand()?);
+
+				match self.next()? {
+					(_, Token::Close) => break,
+					(_, Token::Comma) => {}
+					(offset, token) => {
+						return Err(ParseExprError::new(
+							offset,
+							format!("expected `,` or `)`, got {token}"),
+						))
+					}
+				}
+			}
+		}
+
+		if !(function.min_arity()..=function.arity()).contains(&args.len()) {
+			let expected = if function.min_arity() == function.arity() {
+				function.arity().to_string()
+			} else {
+				format!("{} or {}", function.min_arity(), function.arity())
+			};
+
+			return Err(ParseExprError::new(
+				offset,
+				format!(
+					"the function `{function}` expects {expected} arguments but got {}",
+					args.len()
+				),
+			));
+		}
+
+		let number = match function {
+			Function::Split => Some((2, "index", "0")),
+			Function::Indent | Function::Nindent => Some((1, "width", "4")),
+			_ => None,
+		};
+		if let Some((idx, name, example)) = number {
+			if !matches!(&args[idx], Operand::Literal(number) if number.parse::<usize>().is_ok()) {
+				return Err(ParseExprError::new(
+					offset,
+					format!(
+						"the {name} of `{function}` must be a number in quotes (e.g. \"{example}\")"
+					),
+				));
+			}
+		}
+
+		Ok(if function.is_test() {
+			Term::Test(function, args)
+		} else {
+			Term::Operand(Operand::Call(function, args))
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use pretty_assertions::assert_eq;
+
+	use super::*;
+
+	impl From<FunctionError> for String {
+		fn from(err: FunctionError) -> Self {
+			err.to_string()
+		}
+	}
+
+	/// Parses `s` with variables written as plain names.
+	fn parse_names(s: &str) -> Result<Expr<String>, ParseExprError> {
+		parse(s, |rest, _| {
+			let len = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+			(len > 0).then(|| Ok((len, rest[..len].to_owned())))
+		})
+	}
+
+	fn eval(s: &str, vars: &[(&str, &str)]) -> Result<bool, String> {
+		let vars: HashMap<_, _> = vars.iter().copied().collect();
+
+		parse_names(s).unwrap().eval(&|name: &String| {
+			vars.get(name.as_str())
+				.map(|value| Cow::Borrowed(*value))
+				.ok_or_else(|| name.clone())
+		})
+	}
+
+	#[test]
+	fn expr_eval() {
+		crate::tests::setup_test_env();
+
+		let vars = [("os", "linux"), ("HOSTNAME", "dev-box")];
+
+		assert_eq!(eval(r#"os == "linux""#, &vars), Ok(true));
+		assert_eq!(eval(r#"os != "linux""#, &vars), Ok(false));
+		assert_eq!(
+			eval(r#"os == "linux" && HOSTNAME matches "^dev-""#, &vars),
+			Ok(true)
+		);
+		assert_eq!(
+			eval(r#"os == "linux" && HOSTNAME matches "^prod-""#, &vars),
+			Ok(false)
+		);
+		assert_eq!(eval(r#"os == "windows" || HOSTNAME"#, &vars), Ok(true));
+		assert_eq!(eval(r#"!(os == "windows") && !EDITOR"#, &vars), Ok(true));
+		assert_eq!(
+			eval(r#"os == 'linux' || os == "windows" && EDITOR"#, &vars),
+			Ok(true)
+		);
+		assert_eq!(eval(r#"EDITOR && EDITOR == "vim""#, &vars), Ok(false));
+		assert_eq!(
+			eval(r#"EDITOR == "vim""#, &vars),
+			Err(String::from("EDITOR"))
+		);
+		assert_eq!(eval(r#""a\"b" == 'a"b'"#, &vars), Ok(true));
+		assert_eq!(eval(r#""C:\Users" == 'C:\Users'"#, &vars), Ok(true));
+	}
+
+	#[test]
+	fn expr_parse_errors() {
+		crate::tests::setup_test_env();
+
+		for (s, offset, message) in [
+			("", 0, "unexpected end of expression"),
+			("os ==", 5, "unexpected end of expression"),
+			("(os", 3, "unexpected end of expression"),
+			("(os os", 4, "expected `)`, got variable"),
+			("os == \"linux", 6, "unterminated string"),
+			("os = \"linux\"", 3, "unexpected character `=`"),
+			(
+				"os matches HOSTNAME",
+				11,
+				"expected a string after `matches`, got variable",
+			),
+			("os HOSTNAME", 3, "unexpected variable"),
+			(
+				"== os",
+				0,
+				"expected a variable, a string or a function, got `==`",
+			),
+			("shout(os)", 0, "unknown function `shout`"),
+			(
+				"upper(os, os)",
+				0,
+				"the function `upper` expects 1 arguments but got 2",
+			),
+			(
+				"os | \"a\"",
+				5,
+				"expected a function after `|`, got string \"a\"",
+			),
+			(
+				"\"b\" == contains(os, \"a\")",
+				7,
+				"the function `contains` can only be used as a condition",
+			),
+			(
+				"split(os, \"-\", INDEX)",
+				0,
+				"the index of `split` must be a number in quotes (e.g. \"0\")",
+			),
+		] {
+			assert_eq!(
+				parse_names(s),
+				Err(ParseExprError::new(offset, message)),
+				"{s}"
+			);
+		}
+
+		assert!(parse_names(r#"os matches "(""#)
+			.unwrap_err()
+			.message
+			.starts_with("invalid regex"));
+	}
+
+	#[test]
+	fn expr_parse_operands() {
+		crate::tests::setup_test_env();
+
+		let parse = |s: &str| {
+			parse_operands(s, |rest, _| {
+				let len = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+				(len > 0).then(|| Ok((len, rest[..len].to_owned())))
+			})
+		};
+
+		assert_eq!(parse("  "), Ok(Vec::new()));
+		assert_eq!(
+			parse(r#""font", FONT , 'a, b'"#),
+			Ok(vec![
+				Operand::Literal(String::from("font")),
+				Operand::Var(String::from("FONT")),
+				Operand::Literal(String::from("a, b")),
+			])
+		);
+		assert_eq!(
+			parse(r#""a" "b""#),
+			Err(ParseExprError::new(4, r#"expected `,`, got string "b""#))
+		);
+		assert_eq!(
+			parse(r#""a","#),
+			Err(ParseExprError::new(4, "unexpected end of expression"))
+		);
+	}
+
+	#[test]
+	fn expr_parse_value() {
+		crate::tests::setup_test_env();
+
+		let vars: HashMap<_, _> = [("os", "windows"), ("shell", "nu")].into_iter().collect();
+		let eval = |s: &str| {
+			parse_value(s, |rest, _| {
+				let len = rest.find(|c| !is_word_char(c)).unwrap_or(rest.len());
+				(len > 0).then(|| Ok((len, rest[..len].to_owned())))
+			})
+			.map(|value| {
+				value
+					.eval(&|name: &String| {
+						vars.get(name.as_str())
+							.map(|value| Cow::Borrowed(*value))
+							.ok_or_else(|| name.clone())
+					})
+					.map(Cow::into_owned)
+			})
+		};
+
+		assert_eq!(
+			eval(r#"os == "windows" ? "pwsh" : "zsh""#),
+			Ok(Ok(String::from("pwsh")))
+		);
+		assert_eq!(
+			eval(r#"os == "linux" ? "zsh" : os == "macos" ? "fish" : shell"#),
+			Ok(Ok(String::from("nu")))
+		);
+		assert_eq!(
+			eval(r#"shell ? shell : EDITOR"#),
+			Ok(Ok(String::from("nu")))
+		);
+		assert_eq!(
+			eval(r#"EDITOR ? EDITOR : "vi""#),
+			Ok(Ok(String::from("vi")))
+		);
+		assert_eq!(eval("EDITOR"), Ok(Err(String::from("EDITOR"))));
+		assert_eq!(
+			eval(r#"os == "linux" "zsh""#),
+			Err(ParseExprError::new(14, "expected `?` after the condition"))
+		);
+		assert_eq!(
+			eval(r#"os ? "a" "b""#),
+			Err(ParseExprError::new(9, r#"expected `:`, got string "b""#))
+		);
+	}
+
+	#[test]
+	fn expr_functions() {
+		crate::tests::setup_test_env();
+
+		let vars = [("host", "  dev-box.local "), ("name", "hello WORLD-wide")];
+
+		assert_eq!(
+			eval(r#"trim(host) | replace(".local", "") == "dev-box""#, &vars),
+			Ok(true)
+		);
+		assert_eq!(
+			eval(r#"host | trim | split("-", "0") | upper == "DEV""#, &vars),
+			Ok(true)
+		);
+		assert_eq!(eval(r#"split(host, "-", "5") == """#, &vars), Ok(true));
+		assert_eq!(
+			eval(r#"title(name) == "Hello World-Wide""#, &vars),
+			Ok(true)
+		);
+		assert_eq!(
+			eval(
+				r#"lower(name) | contains("world") && !starts_with(name, "world")"#,
+				&vars
+			),
+			Ok(true)
+		);
+		assert_eq!(
+			eval(r#"contains(EDITOR, "vi")"#, &vars),
+			Err(String::from("EDITOR"))
+		);
+	}
+
+	#[test]
+	fn expr_path_functions() {
+		crate::tests::setup_test_env();
+
+		let value = |s: &str| {
+			parse_value(s, |_, _| None::<Result<(usize, String), _>>)
+				.unwrap()
+				.eval(&|name: &String| Err::<Cow<'_, str>, _>(name.clone()))
+				.map(Cow::into_owned)
+		};
+		let native = |s: &str| s.replace('/', MAIN_SEPARATOR_STR);
+
+		assert_eq!(
+			value(r#"path_join(".config", "nvim") | path_join("init.lua")"#),
+			Ok(native(".config/nvim/init.lua"))
+		);
+		assert_eq!(value(r#"dirname("a/b/c.txt")"#), Ok(String::from("a/b")));
+		assert_eq!(value(r#"basename("a/b/c.txt")"#), Ok(String::from("c.txt")));
+		assert_eq!(value(r#"dirname("c.txt")"#), Ok(String::new()));
+		assert_eq!(value(r#"to_native("a/b\\c")"#), Ok(native("a/b/c")));
+		assert_eq!(
+			value("home()"),
+			Ok(std::env::home_dir()
+				.map(|home| home.to_string_lossy().into_owned())
+				.unwrap_or_default())
+		);
+	}
+
+	#[test]
+	fn expr_base64_functions() {
+		crate::tests::setup_test_env();
+
+		let value = |s: &str| {
+			parse_value(s, |_, _| None::<Result<(usize, String), _>>)
+				.unwrap()
+				.eval(&|name: &String| Err::<Cow<'_, str>, _>(name.clone()))
+				.map(Cow::into_owned)
+		};
+
+		assert_eq!(
+			value(r#""user:secret" | b64encode"#),
+			Ok(String::from("dXNlcjpzZWNyZXQ="))
+		);
+		assert_eq!(
+			value(r#"b64decode("dXNlcjpzZWNyZXQ=")"#),
+			Ok(String::from("user:secret"))
+		);
+		assert_eq!(
+			value(r#"b64decode(b64encode("grüße"))"#),
+			Ok(String::from("grüße"))
+		);
+		assert!(value(r#"b64decode("not base64!")"#)
+			.unwrap_err()
+			.starts_with("Function `b64decode` failed: "));
+		assert!(value(r#"b64decode("/w==")"#)
+			.unwrap_err()
+			.starts_with("Function `b64decode` failed: "));
+	}
+
+	#[test]
+	fn expr_random_functions() {
+		crate::tests::setup_test_env();
+
+		let value = |s: &str, seed: &str| {
+			parse_value(s, |_, _| None::<Result<(usize, String), _>>)
+				.unwrap()
+				.eval(
+					&Scope::new(|name: &String| Err::<Cow<'_, str>, _>(name.clone()))
+						.with_seed(seed.to_owned()),
+				)
+				.map(Cow::into_owned)
+		};
+		let is_uuid = |uuid: &str| {
+			let groups: Vec<_> = uuid.split('-').map(str::len).collect();
+			groups == [8, 4, 4, 4, 12]
+				&& uuid.chars().all(|c| c == '-' || c.is_ascii_hexdigit())
+				&& uuid.as_bytes()[14] == b'4'
+		};
+
+		let random = value("uuid()", "a").unwrap();
+		assert!(is_uuid(&random));
+		assert_ne!(value("uuid()", "a").unwrap(), random);
+
+		let stable = value(r#"uuid("id")"#, "a").unwrap();
+		assert!(is_uuid(&stable));
+		assert_eq!(value(r#""id" | uuid"#, "a").unwrap(), stable);
+		assert_ne!(value(r#"uuid("other")"#, "a").unwrap(), stable);
+		assert_ne!(value(r#"uuid("id")"#, "b").unwrap(), stable);
+
+		for _ in 0..32 {
+			let number: i64 = value(r#"random("-2", "2")"#, "a").unwrap().parse().unwrap();
+			assert!((-2..=2).contains(&number));
+		}
+		assert_eq!(value(r#"random("7", "7")"#, "a"), Ok(String::from("7")));
+		assert_eq!(
+			value(r#"random("1", "1000000", "port")"#, "a"),
+			value(r#"random("1", "1000000", "port")"#, "a")
+		);
+		assert_eq!(
+			value(r#"random("3", "1")"#, "a"),
+			Err(String::from(
+				"Function `random` failed: `3` is greater than `1`"
+			))
+		);
+		assert_eq!(
+			value(r#"random("a", "1")"#, "a"),
+			Err(String::from(
+				"Function `random` failed: `a` is not an integer"
+			))
+		);
+		assert_eq!(
+			parse_value(r#"random("1")"#, |_, _| None::<Result<(usize, String), _>>)
+				.unwrap_err()
+				.message,
+			"the function `random` expects 2 or 3 arguments but got 1"
+		);
+	}
+
+	#[test]
+	fn expr_time_functions() {
+		crate::tests::setup_test_env();
+
+		let value = |s: &str| {
+			parse_value(s, |_, _| None::<Result<(usize, String), _>>)
+				.unwrap()
+				.eval(&|name: &String| Err::<Cow<'_, str>, _>(name.clone()))
+				.map(Cow::into_owned)
+		};
+
+		std::env::set_var(FROZEN_TIME_ENVVAR, "2024-02-29T23:30:05Z");
+
+		assert_eq!(value("today()"), Ok(String::from("2024-02-29")));
+		assert_eq!(value(r#"today("+01:00")"#), Ok(String::from("2024-03-01")));
+		assert_eq!(
+			value(r#"now("%F %T %z")"#),
+			Ok(String::from("2024-02-29 23:30:05 +0000"))
+		);
+		assert_eq!(
+			value(r#"now("%a, %d %b %Y %I:%M %p (%j)", "-0530")"#),
+			Ok(String::from("Thu, 29 Feb 2024 06:00 PM (060)"))
+		);
+		assert_eq!(
+			value(r#"now("%A %B %e %y %s %%", "utc")"#),
+			Ok(String::from("Thursday February 29 24 1709249405 %"))
+		);
+		assert_eq!(
+			value(r#"now("%Q")"#),
+			Err(String::from(
+				"Function `now` failed: unsupported format specifier `%Q`"
+			))
+		);
+		assert!(value(r#"today("Europe/Berlin")"#)
+			.unwrap_err()
+			.starts_with("Function `today` failed: invalid timezone `Europe/Berlin`"));
+
+		std::env::set_var(FROZEN_TIME_ENVVAR, "0");
+		assert_eq!(value("today()"), Ok(String::from("1970-01-01")));
+
+		std::env::set_var(FROZEN_TIME_ENVVAR, "yesterday");
+		assert!(value("today()")
+			.unwrap_err()
+			.starts_with("Function `today` failed: invalid `PUNKTF_FROZEN_TIME`"));
+
+		std::env::remove_var(FROZEN_TIME_ENVVAR);
+		assert_eq!(value("today()").map(|today| today.len()), Ok(10));
+	}
+
+	#[test]
+	fn expr_indent_functions() {
+		crate::tests::setup_test_env();
+
+		let value = |s: &str| {
+			parse_value(s, |_, _| None::<Result<(usize, String), _>>)
+				.unwrap()
+				.eval(&|name: &String| Err::<Cow<'_, str>, _>(name.clone()))
+				.map(Cow::into_owned)
+		};
+
+		assert_eq!(
+			value("indent(\"a:\n  b: 1\n\nc\", \"2\")"),
+			Ok(String::from("  a:\n    b: 1\n\n  c"))
+		);
+		assert_eq!(
+			value("\"a\r\nb\n\" | nindent(\"4\")"),
+			Ok(String::from("\n    a\r\n    b\n"))
+		);
+		assert_eq!(
+			parse_value(r#"indent("a", "two")"#, |_, _| None::<
+				Result<(usize, String), _>,
+			>)
+			.unwrap_err()
+			.message,
+			"the width of `indent` must be a number in quotes (e.g. \"4\")"
+		);
+
+		// Widths which are not known while parsing are checked when applied
+		let apply = |function: Function, width: &str| {
+			function
+				.apply(&[Cow::from("a"), Cow::from(width)], Settings::default())
+				.map_err(|err| err.to_string())
+		};
+		assert_eq!(apply(Function::Indent, " 3"), Ok(String::from("   a")));
+		assert_eq!(
+			apply(Function::Indent, "four"),
+			Err(String::from(
+				"Function `indent` failed: `four` is not an integer"
+			))
+		);
+		assert_eq!(
+			apply(Function::Nindent, "-1"),
+			Err(String::from("Function `nindent` failed: `-1` is negative"))
+		);
+
+		let filters = parse_filters(r#" | upper | nindent("2")"#, |_, _| {
+			None::<Result<(usize, String), _>>
+		})
+		.unwrap();
+		let lookup = |name: &String| Err::<Cow<'_, str>, String>(name.clone());
+		assert_eq!(
+			filters
+				.iter()
+				.try_fold(String::from("a\nb"), |value, filter| filter
+					.apply(&value, &lookup)),
+			Ok(String::from("\n  A\n  B"))
+		);
+
+		for (filters, offset) in [("upper", 0), ("| contains(\"a\")", 2), ("|", 1)] {
+			assert_eq!(
+				parse_filters(filters, |_, _| None::<Result<(usize, String), _>>)
+					.unwrap_err()
+					.offset,
+				offset
+			);
+		}
+	}
+
+	/// Values which need quotes or escapes in at least one of the formats.
+	#[cfg(any(feature = "profile-json", feature = "profile-yaml", feature = "toml"))]
+	const SERIALIZED: [&str; 12] = [
+		"say \"hi\"\\\n\tC:\\x\u{7}",
+		"",
+		"plain",
+		"yes",
+		"null",
+		"1.0",
+		"a: b",
+		"# not a comment",
+		"'single' and \"double\"",
+		"multiple\nlines\n",
+		"- item",
+		"On",
+	];
+
+	/// Applies the serializing `function` to `value`.
+	#[cfg(any(feature = "profile-json", feature = "profile-yaml", feature = "toml"))]
+	fn serialize(function: &str, value: &str) -> String {
+		parse_value(&format!("VALUE | {function}"), |rest, _| {
+			rest.starts_with("VALUE")
+				.then(|| Ok((5, String::from("VALUE"))))
+		})
+		.unwrap()
+		.eval(&|_: &String| Ok::<_, String>(Cow::Borrowed(value)))
+		.unwrap()
+		.into_owned()
+	}
+
+	#[test]
+	#[cfg(feature = "profile-json")]
+	fn expr_to_json() {
+		crate::tests::setup_test_env();
+
+		assert_eq!(
+			serialize("to_json", SERIALIZED[0]),
+			r#""say \"hi\"\\\n\tC:\\x\u0007""#
+		);
+
+		for value in SERIALIZED {
+			let json = serialize("to_json", value);
+			assert_eq!(serde_json::from_str::<String>(&json).unwrap(), value);
+		}
+	}
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn expr_to_yaml() {
+		use std::collections::BTreeMap;
+
+		crate::tests::setup_test_env();
+
+		assert_eq!(serialize("to_yaml", "plain"), "plain");
+		assert_eq!(serialize("to_yaml", "yes"), "'yes'");
+		assert_eq!(serialize("to_yaml", "null"), "'null'");
+
+		for value in SERIALIZED {
+			let yaml = serialize("to_yaml", value);
+			assert!(!yaml.contains('\n'), "{yaml:?} has multiple lines");
+			assert_eq!(serde_yaml::from_str::<String>(&yaml).unwrap(), value);
+
+			// Nested inside of a document
+			let document: BTreeMap<String, Vec<String>> =
+				serde_yaml::from_str(&format!("outer:\n  - {yaml}\n")).unwrap();
+			assert_eq!(document["outer"], [value]);
+		}
+	}
+
+	#[test]
+	#[cfg(feature = "toml")]
+	fn expr_to_toml() {
+		use std::collections::BTreeMap;
+
+		crate::tests::setup_test_env();
+
+		assert_eq!(serialize("to_toml", "plain"), r#""plain""#);
+
+		for value in SERIALIZED {
+			let toml = serialize("to_toml", value);
+			let document: BTreeMap<String, String> =
+				toml::from_str(&format!("key = {toml}\n")).unwrap();
+			assert_eq!(document["key"], value);
+		}
+	}
+
+	#[test]
+	#[cfg(feature = "io")]
+	fn expr_hash_functions() -> std::io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = tempfile::tempdir()?;
+		let path = dir.path().join("asset.txt");
+		std::fs::write(&path, "abc")?;
+
+		let value = |s: &str| {
+			parse_value(s, |_, _| None::<Result<(usize, String), _>>)
+				.unwrap()
+				.eval(&|name: &String| Err::<Cow<'_, str>, _>(name.clone()))
+				.map(Cow::into_owned)
+		};
+		let digest = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+		assert_eq!(value(r#"sha256("abc")"#), Ok(String::from(digest)));
+		assert_eq!(
+			value(&format!("file_hash({:?})", path.display().to_string())),
+			Ok(String::from(digest))
+		);
+		assert!(value(&format!(
+			"file_hash({:?})",
+			dir.path().join("missing").display().to_string()
+		))
+		.unwrap_err()
+		.starts_with("Function `file_hash` failed: failed to read"));
+
+		Ok(())
+	}
+
+	#[test]
+	fn expr_comment_function() {
+		crate::tests::setup_test_env();
+
+		let value = |s: &str, prefix: &str| {
+			parse_value(s, |_, _| None::<Result<(usize, String), _>>)
+				.unwrap()
+				.eval(
+					&Scope::new(|name: &String| Err::<Cow<'_, str>, _>(name.clone()))
+						.with_comment_prefix(prefix.to_owned()),
+				)
+				.map(Cow::into_owned)
+		};
+
+		assert_eq!(
+			value("comment(\"Managed by punktf\n\nDo not edit\n\")", "#"),
+			Ok(String::from("# Managed by punktf\n#\n# Do not edit\n"))
+		);
+		assert_eq!(
+			value("\"a\r\nb\" | comment", "\""),
+			Ok(String::from("\" a\r\n\" b"))
+		);
+		assert_eq!(value(r#"comment("")"#, "//"), Ok(String::new()));
+	}
+}
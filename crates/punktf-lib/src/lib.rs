@@ -32,8 +32,12 @@
 
 //! This is the library powering `punktf`, a cross-platform multi-target dotfiles manager.
 
+pub mod error;
+pub mod expr;
+pub mod plugin;
 pub mod profile;
 pub mod template;
+#[cfg(feature = "io")]
 pub mod visit;
 
 #[cfg(test)]
@@ -44,12 +48,15 @@ mod tests {
 
 	pub fn setup_test_env() {
 		SETUP_GATE.call_once(|| {
-			env_logger::Builder::from_env(
-				env_logger::Env::default().default_filter_or(log::Level::Debug.as_str()),
-			)
-			.is_test(true)
-			.try_init()
-			.expect("Test logger setup to not fail");
+			tracing_subscriber::fmt()
+				.with_env_filter(
+					tracing_subscriber::EnvFilter::builder()
+						.with_default_directive(tracing::Level::DEBUG.into())
+						.from_env_lossy(),
+				)
+				.with_test_writer()
+				.try_init()
+				.expect("Test logger setup to not fail");
 
 			color_eyre::install().expect("color_eyre setup to not fail");
 		})
@@ -0,0 +1,207 @@
+//! Plugins which extend punktf with template functions and hooks without
+//! changing punktf itself (e.g. a function which looks up values in a
+//! corporate secret store).
+//!
+//! The functions and hooks of all plugins are registered by their name in
+//! [`Plugins`], which are part of the
+//! [profile](`crate::profile::LayeredProfile::plugins`):
+//!
+//! - Functions are called by templates with the `plugin` function, where the
+//!   value is the argument and the second argument the name of the function
+//!   (e.g. `{{ {{USER}} | plugin("corp.email") }}`).
+//! - Hooks are called by pre- and post-hooks with the command
+//!   `plugin:<name> <argument>` (e.g. `plugin:corp.notify deployed`).
+//!
+//! Variable sources of plugins are registered as variable providers instead
+//! (see `profile::provider`).
+//!
+//! Library users can register closures directly. WebAssembly modules are
+//! loaded by the `wasm` module if the `plugins` feature is enabled.
+
+#[cfg(feature = "plugins")]
+pub mod wasm;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use color_eyre::eyre::eyre;
+use color_eyre::Result;
+
+/// Prefix of hook commands which call a hook of a plugin.
+pub const HOOK_PREFIX: &str = "plugin:";
+
+/// A template function or hook of a plugin.
+pub trait PluginFunction: Send + Sync {
+	/// Calls the function with `argument` and returns its result.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the function failed.
+	fn call(&self, argument: &str) -> Result<String>;
+}
+
+impl<F> PluginFunction for F
+where
+	F: Fn(&str) -> Result<String> + Send + Sync,
+{
+	fn call(&self, argument: &str) -> Result<String> {
+		self(argument)
+	}
+}
+
+/// The template functions and hooks of all plugins by their name.
+///
+/// Two sets are equal if the same functions are registered under the same
+/// names.
+#[derive(Default, Clone)]
+pub struct Plugins {
+	/// The registered template functions by their name.
+	functions: BTreeMap<String, Arc<dyn PluginFunction>>,
+
+	/// The registered hooks by their name.
+	hooks: BTreeMap<String, Arc<dyn PluginFunction>>,
+}
+
+impl Plugins {
+	/// Creates a new set without any functions or hooks.
+	pub fn empty() -> Self {
+		Self::default()
+	}
+
+	/// Registers the template function `function` under `name`.
+	///
+	/// A previously registered function with the same name is replaced.
+	pub fn register_function<S: Into<String>, F: PluginFunction + 'static>(
+		&mut self,
+		name: S,
+		function: F,
+	) -> &mut Self {
+		self.functions.insert(name.into(), Arc::new(function));
+		self
+	}
+
+	/// Registers the hook `hook` under `name`.
+	///
+	/// A previously registered hook with the same name is replaced.
+	pub fn register_hook<S: Into<String>, F: PluginFunction + 'static>(
+		&mut self,
+		name: S,
+		hook: F,
+	) -> &mut Self {
+		self.hooks.insert(name.into(), Arc::new(hook));
+		self
+	}
+
+	/// Returns the names of all registered template functions.
+	pub fn function_names(&self) -> impl Iterator<Item = &str> {
+		self.functions.keys().map(String::as_str)
+	}
+
+	/// Returns the names of all registered hooks.
+	pub fn hook_names(&self) -> impl Iterator<Item = &str> {
+		self.hooks.keys().map(String::as_str)
+	}
+
+	/// Checks if neither functions nor hooks are registered.
+	pub fn is_empty(&self) -> bool {
+		self.functions.is_empty() && self.hooks.is_empty()
+	}
+
+	/// Calls the template function `name` with `argument`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the function is not registered or if it
+	/// failed.
+	pub fn call_function(&self, name: &str, argument: &str) -> Result<String> {
+		call(&self.functions, "plugin function", name, argument)
+	}
+
+	/// Calls the hook `name` with `argument` and returns its output.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the hook is not registered or if it failed.
+	pub fn call_hook(&self, name: &str, argument: &str) -> Result<String> {
+		call(&self.hooks, "plugin hook", name, argument)
+	}
+}
+
+/// Calls the function `name` of `functions` with `argument`.
+///
+/// `kind` describes the functions in the error if `name` is not registered.
+fn call(
+	functions: &BTreeMap<String, Arc<dyn PluginFunction>>,
+	kind: &str,
+	name: &str,
+	argument: &str,
+) -> Result<String> {
+	let function = functions.get(name).ok_or_else(|| {
+		eyre!(
+			"Unknown {kind} `{name}` (available: {})",
+			functions
+				.keys()
+				.map(String::as_str)
+				.collect::<Vec<_>>()
+				.join(", ")
+		)
+	})?;
+
+	function.call(argument)
+}
+
+/// Checks if both maps contain the same functions under the same names.
+fn same_functions(
+	lhs: &BTreeMap<String, Arc<dyn PluginFunction>>,
+	rhs: &BTreeMap<String, Arc<dyn PluginFunction>>,
+) -> bool {
+	lhs.len() == rhs.len()
+		&& lhs
+			.iter()
+			.zip(rhs)
+			.all(|((lhs_name, lhs), (rhs_name, rhs))| lhs_name == rhs_name && Arc::ptr_eq(lhs, rhs))
+}
+
+impl PartialEq for Plugins {
+	fn eq(&self, other: &Self) -> bool {
+		same_functions(&self.functions, &other.functions)
+			&& same_functions(&self.hooks, &other.hooks)
+	}
+}
+
+impl Eq for Plugins {}
+
+impl fmt::Debug for Plugins {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Plugins")
+			.field("functions", &self.function_names().collect::<Vec<_>>())
+			.field("hooks", &self.hook_names().collect::<Vec<_>>())
+			.finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn call_plugins() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let mut plugins = Plugins::empty();
+		plugins
+			.register_function("corp.upper", |argument: &str| Ok(argument.to_uppercase()))
+			.register_hook("corp.fail", |_: &str| Err(eyre!("failed")));
+
+		assert_eq!(plugins.call_function("corp.upper", "abc")?, "ABC");
+		assert!(plugins.call_function("corp.fail", "abc").is_err());
+		assert!(plugins.call_hook("corp.fail", "abc").is_err());
+		assert!(plugins.call_hook("corp.upper", "abc").is_err());
+
+		assert_eq!(plugins.clone(), plugins);
+		assert_ne!(Plugins::empty(), plugins);
+
+		Ok(())
+	}
+}
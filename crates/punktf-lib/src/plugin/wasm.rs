@@ -0,0 +1,710 @@
+//! Host of WebAssembly plugins.
+//!
+//! Each `*.wasm` file of a plugin directory is a plugin which is named after
+//! the file (e.g. `corp.wasm` is the plugin `corp`). Its template functions,
+//! variable providers and hooks are registered as `<plugin>.<name>`:
+//!
+//! ```text
+//! {{ {{USER}} | plugin("corp.email") }}    (template function)
+//! TOKEN: "from:corp.vault:db/password"     (variable provider)
+//! post_hooks: ["plugin:corp.notify done"]  (hook)
+//! ```
+//!
+//! Plugins are WASI modules (e.g. built for `wasm32-wasip1`) which export:
+//!
+//! - `memory`: The linear memory of the plugin.
+//! - `punktf_alloc(len: i32) -> i32`: Allocates `len` bytes and returns their
+//!   address. The host writes the names and arguments of calls there.
+//! - `punktf_info() -> i64`: Returns a json object with the names of all
+//!   `functions`, `providers` and `hooks` of the plugin (e.g.
+//!   `{"functions": ["email"], "providers": ["vault"]}`).
+//! - `punktf_call(kind: i32, name: i32, name_len: i32, arg: i32, arg_len: i32)
+//!   -> i64`: Calls the function (`kind` 0), provider (1) or hook (2) `name`
+//!   with the UTF-8 argument. The result starts with a byte which is `0` on
+//!   success and `1` on failure, followed by the UTF-8 value or error message.
+//!
+//! Results of type `i64` hold the address in the upper and the length in the
+//! lower 32 bits. The host never frees memory, so plugins can e.g. reset their
+//! allocator on every call.
+//!
+//! Plugins run in a sandbox: They can write to stdout and stderr (which are
+//! logged), read the environment variables, the clock and random numbers. All
+//! other WASI functions (e.g. to open files) fail with `ENOSYS`. Each call
+//! can execute about [`FUEL`] instructions, so a broken plugin can not hang
+//! punktf.
+
+use std::collections::hash_map::RandomState;
+use std::ffi::OsStr;
+use std::fmt;
+use std::hash::BuildHasher as _;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use serde::Deserialize;
+use wasmi::{
+	Caller, Config, Engine, Extern, Linker, Memory, Module, Store, TypedFunc, Val, ValType,
+};
+
+use crate::plugin::{PluginFunction, Plugins};
+use crate::profile::provider::{VariableProvider, VariableProviders};
+
+/// Name of the module from which plugins import the WASI functions.
+pub const WASI_MODULE: &str = "wasi_snapshot_preview1";
+
+/// Number of instructions (roughly) a plugin can execute per call.
+pub const FUEL: u64 = 1_000_000_000;
+
+/// WASI error code for success.
+const ESUCCESS: i32 = 0;
+
+/// WASI error code for a bad file descriptor.
+const EBADF: i32 = 8;
+
+/// WASI error code for an invalid address.
+const EFAULT: i32 = 21;
+
+/// WASI error code for functions which are not supported.
+const ENOSYS: i32 = 52;
+
+/// WASI functions which are implemented by the host, all others are stubs
+/// (see [`Guest::new`]).
+const WASI_FUNCTIONS: [&str; 8] = [
+	"fd_write",
+	"random_get",
+	"clock_time_get",
+	"environ_sizes_get",
+	"environ_get",
+	"args_sizes_get",
+	"args_get",
+	"proc_exit",
+];
+
+/// The kind of an export of a plugin, which is passed to `punktf_call`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Kind {
+	/// A template function.
+	Function = 0,
+
+	/// A variable provider.
+	Provider = 1,
+
+	/// A hook.
+	Hook = 2,
+}
+
+/// The names of the exports of a plugin as returned by `punktf_info`.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Exports {
+	/// Names of the template functions.
+	functions: Vec<String>,
+
+	/// Names of the variable providers.
+	providers: Vec<String>,
+
+	/// Names of the hooks.
+	hooks: Vec<String>,
+}
+
+/// Checks that `name` is a valid name of a plugin or of one of its exports.
+///
+/// Names consist of ASCII letters, digits, `_`, `-` and `.`, so they can be
+/// used in hook commands (separated by a space) and variable values
+/// (separated by `:`).
+fn validate_name(name: &str) -> Result<()> {
+	let valid = !name.is_empty()
+		&& name
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+
+	if valid {
+		Ok(())
+	} else {
+		Err(eyre!(
+			"Invalid name `{name}`, only ASCII letters, digits, `_`, `-` and `.` are allowed"
+		))
+	}
+}
+
+/// State of a plugin which is available to the WASI functions.
+#[derive(Debug)]
+struct Host {
+	/// Name of the plugin, which prefixes the logged output.
+	name: String,
+
+	/// Environment variables as `NAME=value`.
+	environ: Vec<String>,
+}
+
+/// Returns the linear memory of the plugin which calls a WASI function.
+fn memory(caller: &Caller<'_, Host>) -> Option<Memory> {
+	caller.get_export("memory").and_then(Extern::into_memory)
+}
+
+/// Converts an address of a plugin to an index into its memory.
+const fn address(address: i32) -> usize {
+	address as u32 as usize
+}
+
+/// Returns the `len` bytes at `address` of the memory of the plugin.
+fn read(caller: &Caller<'_, Host>, address: i32, len: usize) -> Option<Vec<u8>> {
+	let start = self::address(address);
+	let end = start.checked_add(len)?;
+
+	memory(caller)?
+		.data(caller)
+		.get(start..end)
+		.map(<[u8]>::to_vec)
+}
+
+/// Writes `bytes` to `address` of the memory of the plugin.
+fn write(caller: &mut Caller<'_, Host>, address: i32, bytes: &[u8]) -> Option<()> {
+	let start = self::address(address);
+	let end = start.checked_add(bytes.len())?;
+
+	memory(caller)?
+		.data_mut(caller)
+		.get_mut(start..end)?
+		.copy_from_slice(bytes);
+
+	Some(())
+}
+
+/// Writes the little endian `u32` at `address` of the memory of the plugin.
+fn write_u32(caller: &mut Caller<'_, Host>, address: i32, value: usize) -> Option<()> {
+	write(caller, address, &u32::try_from(value).ok()?.to_le_bytes())
+}
+
+/// Reads the little endian `u32` at `address` of the memory of the plugin.
+fn read_u32(caller: &Caller<'_, Host>, address: i32) -> Option<u32> {
+	let bytes = read(caller, address, 4)?;
+
+	Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+/// Converts the result of a WASI function to its error code.
+fn errno(result: Option<()>) -> i32 {
+	result.map_or(EFAULT, |()| ESUCCESS)
+}
+
+/// Writes the strings of `values` (e.g. the environment variables) to
+/// `buf` with a terminating nul byte and the address of each string to the
+/// array at `pointers`.
+fn write_strings(
+	caller: &mut Caller<'_, Host>,
+	values: &[String],
+	pointers: i32,
+	buf: i32,
+) -> Option<()> {
+	let mut offset = 0;
+
+	for (idx, value) in values.iter().enumerate() {
+		let address = buf.checked_add(i32::try_from(offset).ok()?)?;
+		let pointer = pointers.checked_add(i32::try_from(idx * 4).ok()?)?;
+
+		write(caller, address, value.as_bytes())?;
+		write(
+			caller,
+			address.checked_add(i32::try_from(value.len()).ok()?)?,
+			&[0],
+		)?;
+		write_u32(caller, pointer, self::address(address))?;
+
+		offset += value.len() + 1;
+	}
+
+	Some(())
+}
+
+/// Returns `len` random bytes.
+///
+/// They are not cryptographically secure, which WASI does not require either.
+fn random_bytes(len: usize) -> Vec<u8> {
+	let state = RandomState::new();
+
+	(0..len.div_ceil(8))
+		.flat_map(|idx| state.hash_one(idx).to_le_bytes())
+		.take(len)
+		.collect()
+}
+
+/// Defines the WASI functions which plugins can use in `linker`.
+fn define_wasi(linker: &mut Linker<Host>) -> Result<()> {
+	linker.func_wrap(
+		WASI_MODULE,
+		"fd_write",
+		|mut caller: Caller<'_, Host>, fd: i32, iovs: i32, iovs_len: i32, written: i32| -> i32 {
+			if !matches!(fd, 1 | 2) {
+				return EBADF;
+			}
+
+			let mut output = Vec::new();
+			for idx in 0..iovs_len {
+				let Some(iov) = iovs.checked_add(idx.saturating_mul(8)) else {
+					return EFAULT;
+				};
+				let buf = read_u32(&caller, iov).zip(read_u32(&caller, iov.wrapping_add(4)));
+				let Some(bytes) =
+					buf.and_then(|(buf, len)| read(&caller, buf as i32, len as usize))
+				else {
+					return EFAULT;
+				};
+
+				output.extend(bytes);
+			}
+
+			let name = &caller.data().name;
+			for line in String::from_utf8_lossy(&output).lines() {
+				if fd == 1 {
+					tracing::info!("plugin::{name} > {line}");
+				} else {
+					tracing::warn!("plugin::{name} > {line}");
+				}
+			}
+
+			errno(write_u32(&mut caller, written, output.len()))
+		},
+	)?;
+
+	linker.func_wrap(
+		WASI_MODULE,
+		"random_get",
+		|mut caller: Caller<'_, Host>, buf: i32, len: i32| -> i32 {
+			// Checked before the bytes are generated, as `len` can be up to 4 GiB
+			let len = address(len);
+			if memory(&caller).is_none_or(|memory| memory.data(&caller).len() < len) {
+				return EFAULT;
+			}
+
+			errno(write(&mut caller, buf, &random_bytes(len)))
+		},
+	)?;
+
+	linker.func_wrap(
+		WASI_MODULE,
+		"clock_time_get",
+		|mut caller: Caller<'_, Host>, _id: i32, _precision: i64, time: i32| -> i32 {
+			let nanos = SystemTime::now()
+				.duration_since(UNIX_EPOCH)
+				.map_or(0, |duration| duration.as_nanos() as u64);
+
+			errno(write(&mut caller, time, &nanos.to_le_bytes()))
+		},
+	)?;
+
+	linker.func_wrap(
+		WASI_MODULE,
+		"environ_sizes_get",
+		|mut caller: Caller<'_, Host>, count: i32, size: i32| -> i32 {
+			let environ = &caller.data().environ;
+			let (len, bytes) = (environ.len(), environ.iter().map(|var| var.len() + 1).sum());
+
+			errno(
+				write_u32(&mut caller, count, len)
+					.and_then(|()| write_u32(&mut caller, size, bytes)),
+			)
+		},
+	)?;
+
+	linker.func_wrap(
+		WASI_MODULE,
+		"environ_get",
+		|mut caller: Caller<'_, Host>, pointers: i32, buf: i32| -> i32 {
+			let environ = caller.data().environ.clone();
+
+			errno(write_strings(&mut caller, &environ, pointers, buf))
+		},
+	)?;
+
+	linker.func_wrap(
+		WASI_MODULE,
+		"args_sizes_get",
+		|mut caller: Caller<'_, Host>, count: i32, size: i32| -> i32 {
+			errno(write_u32(&mut caller, count, 0).and_then(|()| write_u32(&mut caller, size, 0)))
+		},
+	)?;
+
+	linker.func_wrap(
+		WASI_MODULE,
+		"args_get",
+		|_: Caller<'_, Host>, _pointers: i32, _buf: i32| -> i32 { ESUCCESS },
+	)?;
+
+	linker.func_wrap(
+		WASI_MODULE,
+		"proc_exit",
+		|_: Caller<'_, Host>, code: i32| -> Result<(), wasmi::Error> {
+			Err(wasmi::Error::i32_exit(code))
+		},
+	)?;
+
+	Ok(())
+}
+
+/// A loaded plugin together with the exports which are called by the host.
+struct Guest {
+	/// The store of the instance of the plugin.
+	store: Store<Host>,
+
+	/// The linear memory of the plugin.
+	memory: Memory,
+
+	/// The `punktf_alloc` export.
+	alloc: TypedFunc<i32, i32>,
+
+	/// The `punktf_info` export.
+	info: TypedFunc<(), i64>,
+
+	/// The `punktf_call` export.
+	call: TypedFunc<(i32, i32, i32, i32, i32), i64>,
+}
+
+impl Guest {
+	/// Instantiates the plugin `name` from the binary `wasm` module.
+	///
+	/// WASI functions which are imported by the plugin but not implemented by
+	/// the host are replaced by stubs, which fail with `ENOSYS` or trap if
+	/// they do not return an error code.
+	fn new(name: &str, wasm: &[u8]) -> Result<Self> {
+		let mut config = Config::default();
+		config.consume_fuel(true);
+
+		let engine = Engine::new(&config);
+		let module = Module::new(&engine, wasm).wrap_err("Invalid WebAssembly module")?;
+
+		let mut linker = Linker::new(&engine);
+		define_wasi(&mut linker)?;
+
+		for import in module.imports() {
+			let Some(ty) = import.ty().func() else {
+				continue;
+			};
+
+			if import.module() != WASI_MODULE || WASI_FUNCTIONS.contains(&import.name()) {
+				continue;
+			}
+
+			let function = import.name().to_owned();
+			let fails = ty.results() == [ValType::I32];
+			linker.func_new(
+				WASI_MODULE,
+				import.name(),
+				ty.clone(),
+				move |_, _, results| {
+					if fails {
+						results[0] = Val::I32(ENOSYS);
+						Ok(())
+					} else {
+						Err(wasmi::Error::new(format!(
+							"WASI function `{function}` is not supported"
+						)))
+					}
+				},
+			)?;
+		}
+
+		let host = Host {
+			name: name.to_owned(),
+			environ: std::env::vars_os()
+				.filter_map(|(name, value)| Some(format!("{}={}", name.to_str()?, value.to_str()?)))
+				.collect(),
+		};
+
+		let mut store = Store::new(&engine, host);
+		store.set_fuel(FUEL)?;
+
+		let instance = linker
+			.instantiate_and_start(&mut store, &module)
+			.wrap_err("Failed to instantiate the plugin")?;
+
+		// Reactor modules initialize themselves with `_initialize`
+		if let Ok(initialize) = instance.get_typed_func::<(), ()>(&store, "_initialize") {
+			initialize
+				.call(&mut store, ())
+				.wrap_err("Failed to initialize the plugin")?;
+		}
+
+		let memory = instance
+			.get_memory(&store, "memory")
+			.ok_or_else(|| eyre!("The plugin exports no `memory`"))?;
+		let alloc = instance
+			.get_typed_func(&store, "punktf_alloc")
+			.wrap_err("The plugin exports no `punktf_alloc(i32) -> i32`")?;
+		let info = instance
+			.get_typed_func(&store, "punktf_info")
+			.wrap_err("The plugin exports no `punktf_info() -> i64`")?;
+		let call = instance
+			.get_typed_func(&store, "punktf_call")
+			.wrap_err("The plugin exports no `punktf_call(i32, i32, i32, i32, i32) -> i64`")?;
+
+		Ok(Self {
+			store,
+			memory,
+			alloc,
+			info,
+			call,
+		})
+	}
+
+	/// Returns the bytes of a result of the plugin, where `packed` holds the
+	/// address and length of the bytes.
+	fn result(&self, packed: i64) -> Result<Vec<u8>> {
+		let packed = packed as u64;
+		let start = (packed >> 32) as usize;
+		let end = start + (packed & 0xffff_ffff) as usize;
+
+		self.memory
+			.data(&self.store)
+			.get(start..end)
+			.map(<[u8]>::to_vec)
+			.ok_or_else(|| eyre!("The plugin returned an invalid address"))
+	}
+
+	/// Copies `bytes` to memory which is allocated by the plugin and returns
+	/// their address and length.
+	fn pass(&mut self, bytes: &[u8]) -> Result<(i32, i32)> {
+		let len = i32::try_from(bytes.len()).wrap_err("The argument is too large")?;
+		if len == 0 {
+			return Ok((0, 0));
+		}
+
+		let address = self.alloc.call(&mut self.store, len)?;
+		self.memory
+			.write(&mut self.store, self::address(address), bytes)
+			.map_err(|err| eyre!("The plugin allocated invalid memory: {err}"))?;
+
+		Ok((address, len))
+	}
+
+	/// Calls `punktf_info` and returns the names of all exports.
+	fn exports(&mut self) -> Result<Exports> {
+		self.store.set_fuel(FUEL)?;
+
+		let packed = self.info.call(&mut self.store, ())?;
+		let exports: Exports =
+			serde_json::from_slice(&self.result(packed)?).wrap_err("Invalid `punktf_info`")?;
+
+		for name in exports
+			.functions
+			.iter()
+			.chain(&exports.providers)
+			.chain(&exports.hooks)
+		{
+			validate_name(name)?;
+		}
+
+		Ok(exports)
+	}
+
+	/// Calls the export `name` of `kind` with `argument`.
+	fn call(&mut self, kind: Kind, name: &str, argument: &str) -> Result<String> {
+		self.store.set_fuel(FUEL)?;
+
+		let (name_address, name_len) = self.pass(name.as_bytes())?;
+		let (argument_address, argument_len) = self.pass(argument.as_bytes())?;
+
+		let packed = self.call.call(
+			&mut self.store,
+			(
+				kind as i32,
+				name_address,
+				name_len,
+				argument_address,
+				argument_len,
+			),
+		)?;
+
+		let result = self.result(packed)?;
+		match result.split_first() {
+			Some((0, value)) => {
+				String::from_utf8(value.to_vec()).wrap_err("The plugin returned invalid UTF-8")
+			}
+			Some((1, message)) => Err(eyre!("{}", String::from_utf8_lossy(message))),
+			_ => Err(eyre!("The plugin returned an invalid result")),
+		}
+	}
+}
+
+/// A WebAssembly plugin.
+pub struct WasmPlugin {
+	/// Name of the plugin, which prefixes the names of its exports.
+	name: String,
+
+	/// Names of the exports of the plugin.
+	exports: Exports,
+
+	/// The instance of the plugin, which handles one call at a time.
+	guest: Mutex<Guest>,
+}
+
+impl WasmPlugin {
+	/// Loads the plugin `name` from the binary `wasm` module.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the name is invalid, the module can not be
+	/// instantiated or it does not export the functions of a plugin.
+	pub fn new<S: Into<String>>(name: S, wasm: &[u8]) -> Result<Self> {
+		let name = name.into();
+		validate_name(&name)?;
+
+		let mut guest = Guest::new(&name, wasm)?;
+		let exports = guest.exports()?;
+
+		Ok(Self {
+			name,
+			exports,
+			guest: Mutex::new(guest),
+		})
+	}
+
+	/// Loads the plugin from the file at `path`, which is named after the
+	/// file.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file can not be read or the plugin can not
+	/// be loaded (see [`WasmPlugin::new`]).
+	pub fn load(path: &Path) -> Result<Self> {
+		let name = path
+			.file_stem()
+			.and_then(OsStr::to_str)
+			.ok_or_else(|| eyre!("Invalid name of plugin {}", path.display()))?;
+
+		let wasm =
+			std::fs::read(path).wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+
+		Self::new(name, &wasm).wrap_err_with(|| format!("Failed to load plugin {}", path.display()))
+	}
+
+	/// Returns the name of the plugin.
+	pub fn name(&self) -> &str {
+		&self.name
+	}
+
+	/// Returns the names of the template functions of the plugin.
+	pub fn functions(&self) -> impl Iterator<Item = &str> {
+		self.exports.functions.iter().map(String::as_str)
+	}
+
+	/// Returns the names of the variable providers of the plugin.
+	pub fn providers(&self) -> impl Iterator<Item = &str> {
+		self.exports.providers.iter().map(String::as_str)
+	}
+
+	/// Returns the names of the hooks of the plugin.
+	pub fn hooks(&self) -> impl Iterator<Item = &str> {
+		self.exports.hooks.iter().map(String::as_str)
+	}
+
+	/// Calls the export `name` of `kind` with `argument`.
+	fn call(&self, kind: Kind, name: &str, argument: &str) -> Result<String> {
+		self.guest
+			.lock()
+			.map_err(|_| eyre!("The plugin `{}` failed before", self.name))?
+			.call(kind, name, argument)
+			.wrap_err_with(|| format!("Plugin `{}.{name}` failed", self.name))
+	}
+
+	/// Registers all template functions and hooks of the plugin in `plugins`
+	/// and all variable providers in `providers`, each as
+	/// `<plugin>.<name>`.
+	pub fn register(self, plugins: &mut Plugins, providers: &mut VariableProviders) {
+		let plugin = Arc::new(self);
+		let export = |kind, name: &str| Export {
+			plugin: Arc::clone(&plugin),
+			kind,
+			name: name.to_owned(),
+		};
+
+		for name in plugin.functions() {
+			plugins.register_function(
+				format!("{}.{name}", plugin.name),
+				export(Kind::Function, name),
+			);
+		}
+
+		for name in plugin.providers() {
+			providers.register(
+				format!("{}.{name}", plugin.name),
+				export(Kind::Provider, name),
+			);
+		}
+
+		for name in plugin.hooks() {
+			plugins.register_hook(format!("{}.{name}", plugin.name), export(Kind::Hook, name));
+		}
+	}
+}
+
+impl fmt::Debug for WasmPlugin {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("WasmPlugin")
+			.field("name", &self.name)
+			.field("exports", &self.exports)
+			.finish_non_exhaustive()
+	}
+}
+
+/// An export of a plugin, which is registered as template function, variable
+/// provider or hook.
+#[derive(Clone)]
+struct Export {
+	/// The plugin which exports it.
+	plugin: Arc<WasmPlugin>,
+
+	/// The kind of the export.
+	kind: Kind,
+
+	/// The name of the export in the plugin.
+	name: String,
+}
+
+impl PluginFunction for Export {
+	fn call(&self, argument: &str) -> Result<String> {
+		self.plugin.call(self.kind, &self.name, argument)
+	}
+}
+
+impl VariableProvider for Export {
+	fn provide(&self, argument: &str) -> Result<String> {
+		self.plugin.call(self.kind, &self.name, argument)
+	}
+}
+
+/// Loads all plugins (`*.wasm` files) of `dir` sorted by their name.
+///
+/// A missing directory contains no plugins.
+///
+/// # Errors
+///
+/// An error is returned if the directory can not be read or any plugin can
+/// not be loaded.
+pub fn discover(dir: &Path) -> Result<Vec<WasmPlugin>> {
+	let entries = match std::fs::read_dir(dir) {
+		Ok(entries) => entries,
+		Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+		Err(err) => return Err(err).wrap_err_with(|| format!("Failed to read {}", dir.display())),
+	};
+
+	let mut paths = Vec::new();
+
+	for entry in entries {
+		let path = entry?.path();
+
+		if path.is_file() && path.extension() == Some(OsStr::new("wasm")) {
+			paths.push(path);
+		}
+	}
+
+	paths.sort();
+
+	paths
+		.iter()
+		.map(|path: &PathBuf| WasmPlugin::load(path))
+		.collect()
+}
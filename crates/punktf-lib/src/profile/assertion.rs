@@ -0,0 +1,282 @@
+//! Assertions which are evaluated after a deployment to verify that the
+//! environment works.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+#[cfg(feature = "io")]
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "io")]
+use crate::profile::hook::Hook;
+
+/// An assertion which is checked after all dotfiles, links and post-hooks of a
+/// [`Profile`](`crate::profile::Profile`) were deployed.
+///
+/// Relative paths are resolved against the target of the profile. Environment
+/// variables and `~` are expanded.
+///
+/// An assertion is (de)serialized as a map with the name of the kind as single
+/// key (e.g. `FileExists: ~/.bashrc`).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+#[serde(try_from = "AssertionDef", into = "AssertionDef")]
+pub enum Assertion {
+	/// Asserts that a file or directory exists at the path.
+	FileExists(PathBuf),
+
+	/// Asserts that the file at `path` contains a match of the regular
+	/// expression `regex`.
+	FileContains {
+		/// Path of the file.
+		path: PathBuf,
+
+		/// Regular expression which must match any part of the file content.
+		regex: String,
+	},
+
+	/// Asserts that the command exits successfully.
+	///
+	/// The command is executed by the native shell, the same way as a
+	/// [`Hook`](`crate::profile::hook::Hook`).
+	Command(String),
+}
+
+/// Arguments of [`Assertion::FileContains`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+struct FileContainsDef {
+	/// Path of the file.
+	path: PathBuf,
+
+	/// Regular expression which must match any part of the file content.
+	regex: String,
+}
+
+/// Serialized form of an [`Assertion`].
+///
+/// This is used instead of an externally tagged enum, as not all profile
+/// formats support those as a map with a single key.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+struct AssertionDef {
+	/// See [`Assertion::FileExists`].
+	#[serde(
+		rename = "FileExists",
+		skip_serializing_if = "Option::is_none",
+		default
+	)]
+	file_exists: Option<PathBuf>,
+
+	/// See [`Assertion::FileContains`].
+	#[serde(
+		rename = "FileContains",
+		skip_serializing_if = "Option::is_none",
+		default
+	)]
+	file_contains: Option<FileContainsDef>,
+
+	/// See [`Assertion::Command`].
+	#[serde(rename = "Command", skip_serializing_if = "Option::is_none", default)]
+	command: Option<String>,
+}
+
+impl TryFrom<AssertionDef> for Assertion {
+	type Error = &'static str;
+
+	fn try_from(value: AssertionDef) -> Result<Self, Self::Error> {
+		match value {
+			AssertionDef {
+				file_exists: Some(path),
+				file_contains: None,
+				command: None,
+			} => Ok(Self::FileExists(path)),
+			AssertionDef {
+				file_exists: None,
+				file_contains: Some(FileContainsDef { path, regex }),
+				command: None,
+			} => Ok(Self::FileContains { path, regex }),
+			AssertionDef {
+				file_exists: None,
+				file_contains: None,
+				command: Some(command),
+			} => Ok(Self::Command(command)),
+			_ => Err(
+				"an assertion must have exactly one of `FileExists`, `FileContains` or `Command`",
+			),
+		}
+	}
+}
+
+impl From<Assertion> for AssertionDef {
+	fn from(value: Assertion) -> Self {
+		match value {
+			Assertion::FileExists(path) => Self {
+				file_exists: Some(path),
+				..Default::default()
+			},
+			Assertion::FileContains { path, regex } => Self {
+				file_contains: Some(FileContainsDef { path, regex }),
+				..Default::default()
+			},
+			Assertion::Command(command) => Self {
+				command: Some(command),
+				..Default::default()
+			},
+		}
+	}
+}
+
+impl Assertion {
+	/// Resolves `path` to an absolute path.
+	#[cfg(feature = "io")]
+	fn resolve_path(path: &Path, target: Option<&Path>) -> Result<PathBuf> {
+		let path_str = path
+			.to_str()
+			.ok_or_else(|| eyre!("Path includes non UTF-8 characters"))?;
+
+		let path = PathBuf::from(shellexpand::full(path_str)?.as_ref());
+
+		Ok(match target {
+			Some(target) if path.is_relative() => target.join(path),
+			_ => path,
+		})
+	}
+
+	/// Evaluates the assertion.
+	///
+	/// Relative paths are resolved against `target` and commands are executed
+	/// inside of `cwd`.
+	///
+	/// # Errors
+	///
+	/// An error describing the reason is returned if the assertion does not
+	/// hold.
+	#[cfg(feature = "io")]
+	pub fn evaluate(&self, target: Option<&Path>, cwd: &Path) -> Result<()> {
+		match self {
+			Self::FileExists(path) => {
+				let path = Self::resolve_path(path, target)?;
+
+				if path.exists() {
+					Ok(())
+				} else {
+					Err(eyre!("{} does not exist", path.display()))
+				}
+			}
+			Self::FileContains { path, regex } => {
+				let regex = regex::Regex::new(regex).wrap_err("Invalid regular expression")?;
+				let path = Self::resolve_path(path, target)?;
+
+				let content = std::fs::read_to_string(&path)
+					.wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+
+				if regex.is_match(&content) {
+					Ok(())
+				} else {
+					Err(eyre!("{} does not contain a match", path.display()))
+				}
+			}
+			Self::Command(command) => Ok(Hook::new(command.as_str()).execute(cwd)?),
+		}
+	}
+
+	/// Returns a copy of the assertion with the result of `f` as path.
+	///
+	/// [`Assertion::Command`] is returned unchanged.
+	pub fn map_path<F>(&self, f: F) -> Result<Self>
+	where
+		F: FnOnce(&Path) -> Result<PathBuf>,
+	{
+		Ok(match self {
+			Self::FileExists(path) => Self::FileExists(f(path)?),
+			Self::FileContains { path, regex } => Self::FileContains {
+				path: f(path)?,
+				regex: regex.clone(),
+			},
+			Self::Command(command) => Self::Command(command.clone()),
+		})
+	}
+}
+
+impl fmt::Display for Assertion {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::FileExists(path) => write!(f, "File exists: {}", path.display()),
+			Self::FileContains { path, regex } => {
+				write!(f, "File contains `{regex}`: {}", path.display())
+			}
+			Self::Command(command) => write!(f, "Command succeeds: {command}"),
+		}
+	}
+}
+
+#[cfg(all(test, any(feature = "io", feature = "profile-yaml")))]
+mod tests {
+	use super::*;
+
+	#[test]
+	#[cfg(feature = "io")]
+	fn evaluate_file_assertions() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = tempfile::tempdir()?;
+		std::fs::write(dir.path().join("config"), "email = demo@example.com\n")?;
+
+		let cwd = dir.path();
+		let target = Some(dir.path());
+
+		assert!(Assertion::FileExists("config".into())
+			.evaluate(target, cwd)
+			.is_ok());
+		assert!(Assertion::FileExists("missing".into())
+			.evaluate(target, cwd)
+			.is_err());
+
+		let contains = |regex: &str| Assertion::FileContains {
+			path: dir.path().join("config"),
+			regex: regex.into(),
+		};
+
+		assert!(contains(r"email = .+@").evaluate(target, cwd).is_ok());
+		assert!(contains(r"^name").evaluate(target, cwd).is_err());
+		assert!(contains(r"(").evaluate(target, cwd).is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn deserialize_assertions() -> Result<()> {
+		let assertions: Vec<Assertion> = serde_yaml::from_str(
+			r#"
+- FileExists: ~/.bashrc
+- FileContains:
+    path: .gitconfig
+    regex: "email = .+"
+- Command: "true"
+"#,
+		)?;
+
+		assert_eq!(
+			assertions,
+			vec![
+				Assertion::FileExists("~/.bashrc".into()),
+				Assertion::FileContains {
+					path: ".gitconfig".into(),
+					regex: "email = .+".into()
+				},
+				Assertion::Command("true".into()),
+			]
+		);
+
+		assert!(serde_yaml::from_str::<Assertion>("{}").is_err());
+		assert!(serde_yaml::from_str::<Assertion>("{ FileExists: a, Command: b }").is_err());
+
+		Ok(())
+	}
+}
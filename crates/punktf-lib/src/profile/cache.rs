@@ -0,0 +1,220 @@
+//! Persistent cache of resolved profiles.
+//!
+//! Resolving a profile (see [`resolve_profile`](`super::resolve_profile`))
+//! reads and parses every profile of its [extend
+//! chain](`super::Profile::extends`). The [`ProfileCache`] stores the resulting
+//! layers together with a hash of all profile files of the
+//! [source](`super::source::PunktfSource`). As long as none of the profile
+//! files change, resolving the same profile again uses the stored layers
+//! instead.
+//!
+//! The cache is stored as a json file.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+
+use super::source::PunktfSource;
+use super::{resolve_profile, LayeredProfileBuilder, Profile};
+use crate::visit::deploy::cache::ContentHash;
+
+/// Version of the cache file format.
+const VERSION: u32 = 1;
+
+/// A single layer of a resolved profile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CachedLayer {
+	/// Name of the profile.
+	name: String,
+
+	/// The parsed profile.
+	profile: Profile,
+}
+
+/// All layers of a resolved profile.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CachedProfile {
+	/// Hash of all profile files at the time the profile was resolved (see
+	/// [`profiles_hash`]).
+	hash: String,
+
+	/// The layers in the order they were resolved.
+	layers: Vec<CachedLayer>,
+}
+
+/// Content of a cache file.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheFile {
+	/// Version of the format.
+	version: u32,
+
+	/// The resolved profiles by their name.
+	profiles: BTreeMap<String, CachedProfile>,
+}
+
+/// Resolved profiles of previous invocations by their name.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct ProfileCache {
+	/// The resolved profiles by their name.
+	profiles: BTreeMap<String, CachedProfile>,
+}
+
+impl ProfileCache {
+	/// Loads the cache from the file at `path`.
+	///
+	/// An empty cache is returned if the file does not exist, is invalid or
+	/// was written by an incompatible version.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file exists but could not be read.
+	pub fn load(path: &Path) -> io::Result<Self> {
+		let content = match fs::read_to_string(path) {
+			Ok(content) => content,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+			Err(err) => return Err(err),
+		};
+
+		match serde_json::from_str::<CacheFile>(&content) {
+			Ok(file) if file.version == VERSION => Ok(Self {
+				profiles: file.profiles,
+			}),
+			Ok(_) => {
+				tracing::debug!("Ignoring profile cache with unknown version");
+				Ok(Self::default())
+			}
+			Err(err) => {
+				tracing::debug!("Ignoring invalid profile cache: {err}");
+				Ok(Self::default())
+			}
+		}
+	}
+
+	/// Writes the cache to the file at `path`.
+	///
+	/// Missing parent directories are created.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file could not be written.
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+
+		let file = CacheFile {
+			version: VERSION,
+			profiles: self.profiles.clone(),
+		};
+
+		fs::write(path, serde_json::to_string(&file)?)
+	}
+
+	/// Resolves the profile `name` like
+	/// [`resolve_profile`](`super::resolve_profile`) and adds all layers to
+	/// `builder`.
+	///
+	/// The layers are taken from the cache if no profile file of `source`
+	/// changed since they were stored. Otherwise the profile is resolved and
+	/// the cache is updated.
+	///
+	/// # Errors
+	///
+	/// An error is returned if a profile file could not be read.
+	/// Bubbles up any error of [`resolve_profile`](`super::resolve_profile`).
+	pub fn resolve(
+		&mut self,
+		builder: &mut LayeredProfileBuilder,
+		source: &PunktfSource,
+		name: &str,
+	) -> Result<()> {
+		let hash = profiles_hash(source)?.to_string();
+
+		if let Some(cached) = self.profiles.get(name).filter(|cached| cached.hash == hash) {
+			tracing::debug!("Using cached layers of profile `{}`", name);
+
+			for layer in &cached.layers {
+				builder.add(layer.name.clone(), layer.profile.clone());
+			}
+
+			return Ok(());
+		}
+
+		let mut resolved = LayeredProfileBuilder::default();
+		resolve_profile(&mut resolved, source, name)?;
+
+		let layers: Vec<_> = resolved
+			.profile_names
+			.into_iter()
+			.zip(resolved.profiles)
+			.map(|(name, profile)| CachedLayer { name, profile })
+			.collect();
+
+		for layer in &layers {
+			builder.add(layer.name.clone(), layer.profile.clone());
+		}
+
+		self.profiles
+			.insert(name.to_owned(), CachedProfile { hash, layers });
+
+		Ok(())
+	}
+
+	/// Returns the amount of cached profiles.
+	pub fn len(&self) -> usize {
+		self.profiles.len()
+	}
+
+	/// Checks if the cache has no profiles.
+	pub fn is_empty(&self) -> bool {
+		self.profiles.is_empty()
+	}
+}
+
+/// Hashes the paths and contents of all files in the `profiles` directories of
+/// all layers of `source`, together with the version of punktf.
+///
+/// # Errors
+///
+/// An error is returned if a `profiles` directory or a file in it could not be
+/// read.
+pub fn profiles_hash(source: &PunktfSource) -> io::Result<ContentHash> {
+	/// Adds a length prefixed `value` to the hash, so that the boundaries of
+	/// consecutive values are unambiguous.
+	fn update(hasher: &mut Sha256, value: &[u8]) {
+		hasher.update((value.len() as u64).to_le_bytes());
+		hasher.update(value);
+	}
+
+	let mut hasher = Sha256::new();
+
+	update(&mut hasher, env!("CARGO_PKG_VERSION").as_bytes());
+
+	for layer in source.layers() {
+		let mut paths = Vec::new();
+
+		for dent in layer.profiles().read_dir()? {
+			let dent = dent?;
+
+			if dent.file_type()?.is_file() {
+				paths.push(dent.path());
+			}
+		}
+
+		paths.sort();
+
+		update(&mut hasher, layer.profiles().to_string_lossy().as_bytes());
+
+		for path in paths {
+			update(&mut hasher, path.to_string_lossy().as_bytes());
+			update(&mut hasher, &fs::read(&path)?);
+		}
+	}
+
+	Ok(ContentHash::from_hasher(hasher))
+}
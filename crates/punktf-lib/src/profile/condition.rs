@@ -0,0 +1,293 @@
+//! Conditions of [dotfiles](`crate::profile::dotfile::Dotfile::when`) which
+//! decide if a dotfile is deployed on the current machine.
+//!
+//! A condition is an [expression](`crate::expr`) over variables and
+//! [built-ins](`builtin`), which are written as plain names:
+//!
+//! ```text
+//! os == "linux" && HOSTNAME matches "^dev-"
+//! !(arch == "aarch64") || EDITOR
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::expr::{self, Expr, FunctionError, Scope};
+
+/// Returns the value of the built-in variable `name`.
+///
+/// The built-ins are:
+///
+/// - `os`: The operating system (e.g. `linux`, `macos` or `windows`).
+/// - `family`: The operating system family (`unix` or `windows`).
+/// - `arch`: The cpu architecture (e.g. `x86_64` or `aarch64`).
+/// - `hostname`: The name of the machine.
+pub fn builtin(name: &str) -> Option<Cow<'static, str>> {
+	match name {
+		"os" => Some(Cow::Borrowed(std::env::consts::OS)),
+		"family" => Some(Cow::Borrowed(std::env::consts::FAMILY)),
+		"arch" => Some(Cow::Borrowed(std::env::consts::ARCH)),
+		"hostname" => hostname().map(Cow::Owned),
+		_ => None,
+	}
+}
+
+/// Returns the name of the machine.
+#[cfg(all(feature = "io", any(target_os = "linux", target_os = "macos")))]
+fn hostname() -> Option<String> {
+	rustix::system::uname()
+		.nodename()
+		.to_str()
+		.ok()
+		.map(ToOwned::to_owned)
+}
+
+/// Returns the name of the machine.
+#[cfg(not(all(feature = "io", any(target_os = "linux", target_os = "macos"))))]
+fn hostname() -> Option<String> {
+	std::env::var("COMPUTERNAME")
+		.or_else(|_| std::env::var("HOSTNAME"))
+		.ok()
+}
+
+/// Error which is returned if a condition can not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid condition `{condition}` at offset {offset}: {message}")]
+pub struct ParseConditionError {
+	/// The condition which failed to parse.
+	condition: String,
+
+	/// Byte offset of the error in the condition.
+	offset: usize,
+
+	/// Description of the error.
+	message: String,
+}
+
+/// Error which is returned if a condition can not be evaluated.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum EvalConditionError {
+	/// The condition compares a variable which is not defined.
+	#[error("Condition `{condition}` compares the undefined variable `{name}`")]
+	UndefinedVariable {
+		/// The condition which failed to evaluate.
+		condition: String,
+
+		/// Name of the variable.
+		name: String,
+	},
+
+	/// A function of the condition failed.
+	#[error("Condition `{condition}` failed to evaluate: {source}")]
+	Function {
+		/// The condition which failed to evaluate.
+		condition: String,
+
+		/// The error of the function.
+		source: FunctionError,
+	},
+}
+
+/// Error of a single step of the evaluation of a condition, which is turned
+/// into an [`EvalConditionError`] afterwards.
+#[derive(Debug)]
+enum EvalFailure {
+	/// A variable is not defined.
+	Undefined(String),
+
+	/// A function failed.
+	Function(FunctionError),
+}
+
+impl From<FunctionError> for EvalFailure {
+	fn from(err: FunctionError) -> Self {
+		Self::Function(err)
+	}
+}
+
+/// A parsed condition.
+///
+/// It is (de)serialized as the expression string.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+#[serde(try_from = "String", into = "String")]
+pub struct Condition(Box<Parsed>);
+
+/// The content of a [`Condition`].
+///
+/// It is boxed to keep [dotfiles](`crate::profile::dotfile::Dotfile`) small.
+#[derive(Debug, Clone)]
+struct Parsed {
+	/// The condition as written by the user.
+	source: String,
+
+	/// The parsed expression.
+	expr: Expr<String>,
+}
+
+impl Condition {
+	/// Returns the condition as written by the user.
+	pub fn as_str(&self) -> &str {
+		&self.0.source
+	}
+
+	/// Evaluates the condition.
+	///
+	/// `lookup` returns the value of a variable or `None` if the variable is
+	/// not defined. Stable random values (e.g. `uuid("key")`) are derived from
+	/// the name of the machine.
+	///
+	/// # Errors
+	///
+	/// An error is returned if a variable which is compared is not defined
+	/// or if a function fails.
+	pub fn eval<'v>(
+		&self,
+		lookup: impl Fn(&str) -> Option<Cow<'v, str>>,
+	) -> Result<bool, EvalConditionError> {
+		self.eval_with_base_dir(None, lookup)
+	}
+
+	/// Evaluates the condition like [`Condition::eval`], but resolves
+	/// relative paths (e.g. of `file_hash`) from `base_dir` instead of the
+	/// current directory.
+	///
+	/// # Errors
+	///
+	/// An error is returned if a variable which is compared is not defined
+	/// or if a function fails.
+	pub fn eval_with_base_dir<'v>(
+		&self,
+		base_dir: Option<&Path>,
+		lookup: impl Fn(&str) -> Option<Cow<'v, str>>,
+	) -> Result<bool, EvalConditionError> {
+		let condition = || self.0.source.clone();
+
+		let mut lookup = Scope::new(|name: &String| {
+			lookup(name).ok_or_else(|| EvalFailure::Undefined(name.clone()))
+		})
+		.with_seed(builtin("hostname").unwrap_or_default().into_owned());
+
+		if let Some(dir) = base_dir {
+			lookup = lookup.with_base_dir(dir.to_path_buf());
+		}
+
+		self.0.expr.eval(&lookup).map_err(|failure| match failure {
+			EvalFailure::Undefined(name) => EvalConditionError::UndefinedVariable {
+				condition: condition(),
+				name,
+			},
+			EvalFailure::Function(source) => EvalConditionError::Function {
+				condition: condition(),
+				source,
+			},
+		})
+	}
+}
+
+impl PartialEq for Condition {
+	fn eq(&self, other: &Self) -> bool {
+		self.0.source == other.0.source
+	}
+}
+
+impl Eq for Condition {}
+
+impl fmt::Display for Condition {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.0.source)
+	}
+}
+
+impl FromStr for Condition {
+	type Err = ParseConditionError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let expr = expr::parse(s, |rest, _| {
+			let len = rest
+				.find(|c: char| !(c.is_alphanumeric() || c == '_'))
+				.unwrap_or(rest.len());
+
+			(len > 0).then(|| Ok((len, rest[..len].to_owned())))
+		})
+		.map_err(|err| ParseConditionError {
+			condition: s.to_owned(),
+			offset: err.offset,
+			message: err.message,
+		})?;
+
+		Ok(Self(Box::new(Parsed {
+			source: s.to_owned(),
+			expr,
+		})))
+	}
+}
+
+impl TryFrom<String> for Condition {
+	type Error = ParseConditionError;
+
+	fn try_from(value: String) -> Result<Self, Self::Error> {
+		value.parse()
+	}
+}
+
+impl From<Condition> for String {
+	fn from(value: Condition) -> Self {
+		value.0.source
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use pretty_assertions::assert_eq;
+
+	use super::*;
+
+	#[test]
+	fn condition_eval() {
+		crate::tests::setup_test_env();
+
+		let vars: HashMap<_, _> = [("os", "linux"), ("HOSTNAME", "dev-box")]
+			.into_iter()
+			.collect();
+		let lookup = |name: &str| vars.get(name).map(|value| Cow::Borrowed(*value));
+		let eval = |s: &str| s.parse::<Condition>().unwrap().eval(lookup);
+
+		assert_eq!(
+			eval(r#"os == "linux" && HOSTNAME matches "^dev-""#),
+			Ok(true)
+		);
+		assert_eq!(eval(r#"os != "linux" || EDITOR"#), Ok(false));
+		assert_eq!(
+			eval(r#"HOSTNAME | starts_with("dev-") && upper(os) == "LINUX""#),
+			Ok(true)
+		);
+		assert_eq!(
+			eval(r#"EDITOR == "vim""#),
+			Err(EvalConditionError::UndefinedVariable {
+				condition: String::from(r#"EDITOR == "vim""#),
+				name: String::from("EDITOR"),
+			})
+		);
+	}
+
+	#[test]
+	fn condition_parse_error() {
+		crate::tests::setup_test_env();
+
+		assert_eq!(
+			"os ==".parse::<Condition>().unwrap_err().to_string(),
+			"Invalid condition `os ==` at offset 5: unexpected end of expression"
+		);
+		assert_eq!(
+			"{{os}}".parse::<Condition>().unwrap_err().to_string(),
+			"Invalid condition `{{os}}` at offset 0: unexpected character `{`"
+		);
+	}
+}
@@ -1,16 +1,27 @@
 //! Defines definitions for a [`Dotfile`] which is the basic building block
 //! to define required/deployable items in a [`Profile`](`crate::profile::Profile`).
 
+use color_eyre::eyre::Context;
+use color_eyre::Result;
 use serde::{Deserialize, Serialize};
 
+use crate::profile::condition::Condition;
+use crate::profile::hook::Hook;
+use crate::profile::variables::Vars;
 use crate::profile::{transform::ContentTransformer, variables::Variables, MergeMode, Priority};
+use crate::template::source::Source;
+use crate::template::Template;
 
-use std::path::PathBuf;
+use std::borrow::Cow;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
 /// A dotfile represents a single item to be deployed by `punktf`. This can
 /// either be a single file or a directory. This struct holds attributes to
 /// control how the item will be deployed.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Dotfile {
 	/// Relative path inside the
@@ -51,6 +62,17 @@ pub struct Dotfile {
 	#[serde(skip_serializing_if = "Vec::is_empty", default)]
 	pub transformers: Vec<ContentTransformer>,
 
+	/// Commands which post-process the content of the dotfile (e.g.
+	/// `dos2unix` or `jq -S .`).
+	///
+	/// After the template is resolved and the
+	/// [`Dotfile::transformers`] are applied, the content is piped through
+	/// each command in order and the output of the last one is deployed. The
+	/// commands are executed by the native shell in the directory of the
+	/// source file.
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub transform: Vec<Hook>,
+
 	/// Merge operation for already existing dotfiles with the same or higher
 	/// priority.
 	#[serde(skip_serializing_if = "Option::is_none", default)]
@@ -60,6 +82,95 @@ pub struct Dotfile {
 	/// no template processing will be done.
 	#[serde(skip_serializing_if = "Option::is_none", default)]
 	pub template: Option<bool>,
+
+	/// Indicates if the source of the dotfile is encrypted with the
+	/// [encryption](`crate::profile::Profile::encryption`) of the profile.
+	///
+	/// The source is decrypted before it is resolved and deployed. For
+	/// directories, all files below them are encrypted. Decrypted sources must
+	/// be text.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub encrypted: Option<bool>,
+
+	/// Maximum depth of the items below a directory dotfile which are
+	/// deployed. The direct children of the directory have a depth of `1`.
+	///
+	/// Deeper items are handled according to [`Dotfile::on_limit`].
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub max_depth: Option<usize>,
+
+	/// Maximum size in bytes of the files of the dotfile which are deployed.
+	///
+	/// Bigger files are handled according to [`Dotfile::on_limit`].
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub max_file_size: Option<u64>,
+
+	/// Action for items which exceed [`Dotfile::max_depth`] or
+	/// [`Dotfile::max_file_size`]. Defaults to [`LimitAction::Skip`].
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub on_limit: Option<LimitAction>,
+
+	/// Indicates if deployed files keep the modification time of their source.
+	///
+	/// Files which are generated from a template or transformed get the latest
+	/// modification time of their source and the profile files instead.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub preserve_mtime: Option<bool>,
+
+	/// Permissions of the directories which are created while deploying the
+	/// dotfile (e.g. `0700` for `~/.ssh`). This includes missing parent
+	/// directories of the target.
+	///
+	/// Existing directories are not changed. If not set, the permissions are
+	/// derived from the umask of the process. This has no effect on systems
+	/// without unix permissions.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub dir_mode: Option<DirMode>,
+
+	/// Indicates if missing parent directories of the target are created.
+	///
+	/// If this is `false`, the dotfile fails to deploy instead, which prevents
+	/// a wrong target from creating directories in unexpected places. Defaults
+	/// to [`Profile::create_parents`](`crate::profile::Profile::create_parents`).
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub create_parents: Option<bool>,
+
+	/// Indicates if existing targets are overwritten even if they are write
+	/// protected (read-only or immutable). The protection is removed before
+	/// the target is overwritten.
+	///
+	/// Otherwise protected targets are skipped.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub force: Option<bool>,
+
+	/// Condition which decides if the dotfile is deployed (e.g.
+	/// `os == "linux" && HOSTNAME matches "^dev-"`).
+	///
+	/// The condition is evaluated against the variables of the dotfile and the
+	/// profile, the [built-ins](`crate::profile::condition::builtin`) and the
+	/// environment variables. Dotfiles whose condition does not hold are
+	/// skipped.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub when: Option<Condition>,
+
+	/// Variables of the instances of the dotfile (e.g. one systemd unit per
+	/// service).
+	///
+	/// If set, the dotfile is deployed once per instance with the variables
+	/// of the instance, which take precedence over [`Dotfile::variables`].
+	/// [`Dotfile::rename`] and [`Dotfile::overwrite_target`] are templates
+	/// which are resolved with these variables to give each instance its own
+	/// target (see [`Dotfile::instantiate`]).
+	#[serde(skip_serializing_if = "<[_]>::is_empty", default)]
+	pub instances: Box<[Variables]>,
+
+	/// Deploys the dotfile once per element of a list variable (e.g. one ssh
+	/// config fragment per host).
+	///
+	/// Each element becomes an instance like the ones of
+	/// [`Dotfile::instances`], which can not be set at the same time.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub for_each: Option<ForEach>,
 }
 
 impl Dotfile {
@@ -67,4 +178,382 @@ impl Dotfile {
 	pub fn is_template(&self) -> bool {
 		self.template.unwrap_or(true)
 	}
+
+	/// Checks if the source of the dotfile is encrypted (see
+	/// [`Dotfile::encrypted`]).
+	pub fn is_encrypted(&self) -> bool {
+		self.encrypted.unwrap_or(false)
+	}
+
+	/// Checks if deployed files keep the modification time of their source
+	/// (see [`Dotfile::preserve_mtime`]).
+	pub fn preserves_mtime(&self) -> bool {
+		self.preserve_mtime.unwrap_or(false)
+	}
+
+	/// Checks if write protected targets are overwritten (see
+	/// [`Dotfile::force`]).
+	pub fn is_forced(&self) -> bool {
+		self.force.unwrap_or(false)
+	}
+
+	/// Returns the action for items which exceed a limit of the dotfile.
+	pub fn on_limit(&self) -> LimitAction {
+		self.on_limit.unwrap_or_default()
+	}
+
+	/// Returns the variables of all instances of the dotfile, which are either
+	/// the [`Dotfile::instances`] or one per element of [`Dotfile::for_each`].
+	///
+	/// Returns `None` if the dotfile has no instances and is deployed once.
+	///
+	/// # Errors
+	///
+	/// An error is returned if both are set or if the list variable is not
+	/// defined.
+	pub fn instance_variables(
+		&self,
+		profile_vars: &impl Vars,
+	) -> Result<Option<Cow<'_, [Variables]>>> {
+		let Some(for_each) = &self.for_each else {
+			return Ok((!self.instances.is_empty()).then_some(Cow::Borrowed(&self.instances)));
+		};
+
+		if !self.instances.is_empty() {
+			color_eyre::eyre::bail!(
+				"Dotfile {} has both `instances` and `for_each`",
+				self.path.display()
+			);
+		}
+
+		let list = self
+			.variables
+			.as_ref()
+			.and_then(|vars| vars.var(&for_each.list))
+			.or_else(|| profile_vars.var(&for_each.list))
+			.ok_or_else(|| {
+				color_eyre::eyre::eyre!(
+					"List variable `{}` of dotfile {} is not defined",
+					for_each.list,
+					self.path.display()
+				)
+			})?;
+
+		Ok(Some(
+			for_each
+				.elements(list)
+				.map(|element| Variables::from_items([(for_each.name.as_str(), element)]))
+				.collect(),
+		))
+	}
+
+	/// Returns the dotfile of a single instance with the variables `instance`
+	/// (see [`Dotfile::instances`]).
+	///
+	/// The returned dotfile has no instances and the templates in
+	/// [`Dotfile::rename`] and [`Dotfile::overwrite_target`] are resolved.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the dotfile has neither a rename nor a target,
+	/// which would deploy all instances to the same path, or if one of them
+	/// fails to resolve.
+	pub fn instantiate(&self, instance: &Variables, profile_vars: &impl Vars) -> Result<Self> {
+		if self.rename.is_none() && self.overwrite_target.is_none() {
+			color_eyre::eyre::bail!(
+				"Dotfile {} has instances but neither a `rename` nor a `target` to tell them apart",
+				self.path.display()
+			);
+		}
+
+		let mut variables = self.variables.clone().unwrap_or_default();
+		variables.inner.extend(instance.inner.clone());
+
+		let resolve = |path: &Option<PathBuf>| {
+			path.as_deref()
+				.map(|path| resolve_path(path, profile_vars, &variables))
+				.transpose()
+		};
+
+		Ok(Self {
+			rename: resolve(&self.rename)?,
+			overwrite_target: resolve(&self.overwrite_target)?,
+			variables: Some(variables.clone()),
+			instances: Box::default(),
+			..self.clone()
+		})
+	}
+}
+
+/// Iterates the elements of a list variable to deploy a [`Dotfile`] once per
+/// element (see [`Dotfile::for_each`]).
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct ForEach {
+	/// Name of the list variable. Its elements are separated by commas or
+	/// whitespace (e.g. `github.com, gitlab.com`).
+	pub list: String,
+
+	/// Name of the variable which holds the element in each instance.
+	#[serde(rename = "as")]
+	pub name: String,
+}
+
+impl ForEach {
+	/// Returns the non-empty elements of the list `value`.
+	pub fn elements<'v>(&self, value: &'v str) -> impl Iterator<Item = &'v str> {
+		value
+			.split(|c: char| c == ',' || c.is_whitespace())
+			.filter(|element| !element.is_empty())
+	}
+}
+
+/// Resolves `path` as a template with the given variables.
+///
+/// Paths which are not valid unicode are returned unchanged.
+fn resolve_path(
+	path: &Path,
+	profile_vars: &impl Vars,
+	dotfile_vars: &Variables,
+) -> Result<PathBuf> {
+	let Some(content) = path.to_str() else {
+		return Ok(path.to_path_buf());
+	};
+
+	Template::parse(Source::anonymous(content))
+		.and_then(|template| template.resolve(Some(profile_vars), Some(dotfile_vars)))
+		.map(PathBuf::from)
+		.wrap_err_with(|| format!("Failed to resolve the path {}", path.display()))
+}
+
+/// What happens with an item of a [`Dotfile`] which exceeds
+/// [`Dotfile::max_depth`] or [`Dotfile::max_file_size`].
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub enum LimitAction {
+	/// The item is skipped with a warning.
+	#[default]
+	Skip,
+
+	/// The item fails to deploy.
+	Error,
+}
+
+impl LimitAction {
+	/// All available limit actions.
+	pub const ALL: [Self; 2] = [Self::Skip, Self::Error];
+
+	/// Returns the name of the limit action.
+	pub const fn as_str(&self) -> &'static str {
+		match self {
+			Self::Skip => "skip",
+			Self::Error => "error",
+		}
+	}
+}
+
+impl fmt::Display for LimitAction {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl FromStr for LimitAction {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::ALL
+			.into_iter()
+			.find(|action| action.as_str().eq_ignore_ascii_case(s))
+			.ok_or_else(|| {
+				format!(
+					"Invalid limit action `{s}` (possible values: {})",
+					Self::ALL.map(|a| a.as_str()).join(", ")
+				)
+			})
+	}
+}
+
+/// Unix permissions of a directory (see [`Dotfile::dir_mode`]).
+///
+/// The mode is written in octal notation, either as string (`"0700"`) or as
+/// number (`0700` or `700`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+#[serde(try_from = "DirModeDef", into = "String")]
+pub struct DirMode(u32);
+
+impl DirMode {
+	/// Highest valid mode (all permission bits together with setuid, setgid
+	/// and sticky).
+	pub const MAX: u32 = 0o7777;
+
+	/// Creates a new instance from the numeric `mode`.
+	///
+	/// Returns `None` if `mode` has bits set other than the permission bits.
+	pub const fn new(mode: u32) -> Option<Self> {
+		if mode <= Self::MAX {
+			Some(Self(mode))
+		} else {
+			None
+		}
+	}
+
+	/// Returns the numeric mode.
+	pub const fn mode(&self) -> u32 {
+		self.0
+	}
+}
+
+impl fmt::Display for DirMode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{:04o}", self.0)
+	}
+}
+
+impl FromStr for DirMode {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let digits = s.strip_prefix("0o").unwrap_or(s);
+
+		u32::from_str_radix(digits, 8)
+			.ok()
+			.and_then(Self::new)
+			.ok_or_else(|| {
+				format!("Invalid directory mode `{s}` (expected an octal mode like 0700)")
+			})
+	}
+}
+
+/// Serialized form of a [`DirMode`].
+///
+/// Profile formats parse a number like `0700` as decimal, so the digits of a
+/// number are interpreted as octal.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+enum DirModeDef {
+	/// Mode written as number.
+	Number(u32),
+
+	/// Mode written as string.
+	Text(String),
+}
+
+impl TryFrom<DirModeDef> for DirMode {
+	type Error = String;
+
+	fn try_from(value: DirModeDef) -> Result<Self, Self::Error> {
+		match value {
+			DirModeDef::Number(mode) => mode.to_string().parse(),
+			DirModeDef::Text(mode) => mode.parse(),
+		}
+	}
+}
+
+impl From<DirMode> for String {
+	fn from(value: DirMode) -> Self {
+		value.to_string()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use pretty_assertions::assert_eq;
+
+	use super::*;
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn instantiate() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let dotfile = Dotfile {
+			rename: Some(PathBuf::from("{{SERVICE}}.service")),
+			variables: Some(Variables::from_items([
+				("SERVICE", "default"),
+				("USER", "demo"),
+			])),
+			instances: Box::new([Variables::from_items([("SERVICE", "backup")])]),
+			..serde_yaml::from_str("path: unit.service")?
+		};
+		let profile_vars = Variables::default();
+
+		let instance = dotfile.instantiate(&dotfile.instances[0], &profile_vars)?;
+
+		assert_eq!(instance.rename, Some(PathBuf::from("backup.service")));
+		assert_eq!(
+			instance.variables,
+			Some(Variables::from_items([
+				("SERVICE", "backup"),
+				("USER", "demo")
+			]))
+		);
+		assert!(instance.instances.is_empty());
+
+		let dotfile = Dotfile {
+			rename: None,
+			..dotfile
+		};
+		assert!(dotfile
+			.instantiate(&dotfile.instances[0], &profile_vars)
+			.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn instance_variables_for_each() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let dotfile = Dotfile {
+			for_each: Some(ForEach {
+				list: String::from("HOSTS"),
+				name: String::from("HOST"),
+			}),
+			..serde_yaml::from_str("path: host.conf")?
+		};
+
+		assert!(dotfile.instance_variables(&Variables::default()).is_err());
+
+		let profile_vars = Variables::from_items([("HOSTS", "github.com, gitlab.com\nwork")]);
+		assert_eq!(
+			dotfile
+				.instance_variables(&profile_vars)?
+				.map(|instances| instances.into_owned()),
+			Some(vec![
+				Variables::from_items([("HOST", "github.com")]),
+				Variables::from_items([("HOST", "gitlab.com")]),
+				Variables::from_items([("HOST", "work")]),
+			])
+		);
+
+		let dotfile = Dotfile {
+			instances: Box::new([Variables::default()]),
+			..dotfile
+		};
+		assert!(dotfile.instance_variables(&profile_vars).is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn dir_mode() {
+		crate::tests::setup_test_env();
+
+		assert_eq!("0700".parse(), Ok(DirMode(0o700)));
+		assert_eq!("0o755".parse(), Ok(DirMode(0o755)));
+		assert!("0800".parse::<DirMode>().is_err());
+		assert!("17777".parse::<DirMode>().is_err());
+
+		assert_eq!(
+			DirMode::try_from(DirModeDef::Number(700)),
+			Ok(DirMode(0o700))
+		);
+		assert_eq!(DirMode(0o700).to_string(), "0700");
+	}
 }
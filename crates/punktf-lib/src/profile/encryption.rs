@@ -0,0 +1,267 @@
+//! Encryption of the sources of dotfiles, so secrets can be kept in the same
+//! repository as the other dotfiles.
+//!
+//! The sources of dotfiles which are [encrypted](`crate::profile::dotfile::Dotfile::encrypted`)
+//! are decrypted with the [`EncryptionBackend`] of the profile before they
+//! are resolved, transformed and deployed. The backend is configured with
+//! [`Encryption`] and runs the command line tool of [age](https://age-encryption.org)
+//! or [GnuPG](https://gnupg.org) to encrypt and decrypt the content.
+
+use std::fmt;
+#[cfg(feature = "io")]
+use std::io::Write as _;
+use std::path::PathBuf;
+#[cfg(feature = "io")]
+use std::process::{Command, Stdio};
+
+#[cfg(feature = "io")]
+use color_eyre::eyre::{eyre, Context as _};
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+/// Encrypts and decrypts the sources of dotfiles.
+pub trait EncryptionBackend: fmt::Debug + Send + Sync {
+	/// Encrypts the `plain` content.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the content could not be encrypted.
+	fn encrypt(&self, plain: &[u8]) -> Result<Vec<u8>>;
+
+	/// Decrypts the `encrypted` content.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the content could not be decrypted (e.g. it was
+	/// encrypted for another key).
+	fn decrypt(&self, encrypted: &[u8]) -> Result<Vec<u8>>;
+}
+
+/// Tool which encrypts and decrypts the sources (see [`Encryption::backend`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+pub enum BackendKind {
+	/// [age](https://age-encryption.org), see [`Age`].
+	Age,
+
+	/// [GnuPG](https://gnupg.org), see [`Gpg`].
+	Gpg,
+}
+
+impl BackendKind {
+	/// Returns the name of the command of the backend.
+	pub const fn as_str(self) -> &'static str {
+		match self {
+			Self::Age => "age",
+			Self::Gpg => "gpg",
+		}
+	}
+}
+
+impl fmt::Display for BackendKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+/// Configuration of the encryption of the sources of a profile.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct Encryption {
+	/// Tool which encrypts and decrypts the sources.
+	pub backend: BackendKind,
+
+	/// Recipients for which the sources are encrypted. These are public keys
+	/// (`age1...`) for age and key ids or email addresses for GnuPG.
+	pub recipients: Vec<String>,
+
+	/// File with the private key with which the sources are decrypted (e.g.
+	/// `~/.config/punktf/key.txt`). Only used by age, GnuPG uses its keyring.
+	///
+	/// The path may contain `~` and environment variables.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub identity: Option<PathBuf>,
+}
+
+impl Encryption {
+	/// Returns the path of the [identity](`Encryption::identity`) with `~` and
+	/// all environment variables expanded.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the path can not be expanded.
+	#[cfg(feature = "io")]
+	pub fn identity(&self) -> Result<Option<PathBuf>> {
+		self.identity
+			.as_deref()
+			.map(|identity| {
+				let identity = identity.to_string_lossy();
+
+				shellexpand::full(&identity)
+					.map(|expanded| PathBuf::from(expanded.as_ref()))
+					.wrap_err_with(|| format!("Failed to expand identity `{identity}`"))
+			})
+			.transpose()
+	}
+
+	/// Creates the backend which encrypts and decrypts the sources.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the path of the identity can not be expanded.
+	#[cfg(feature = "io")]
+	pub fn backend(&self) -> Result<Box<dyn EncryptionBackend>> {
+		Ok(match self.backend {
+			BackendKind::Age => Box::new(Age {
+				recipients: self.recipients.clone(),
+				identity: self.identity()?,
+			}),
+			BackendKind::Gpg => Box::new(Gpg {
+				recipients: self.recipients.clone(),
+			}),
+		})
+	}
+}
+
+/// Encrypts with the `age` command in ASCII armor, so encrypted sources are
+/// text files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Age {
+	/// Public keys for which the content is encrypted.
+	pub recipients: Vec<String>,
+
+	/// File with the private key which decrypts the content.
+	pub identity: Option<PathBuf>,
+}
+
+#[cfg(feature = "io")]
+impl EncryptionBackend for Age {
+	fn encrypt(&self, plain: &[u8]) -> Result<Vec<u8>> {
+		let mut command = Command::new("age");
+		command.args(["--encrypt", "--armor"]);
+
+		for recipient in &self.recipients {
+			command.args(["--recipient", recipient]);
+		}
+
+		pipe(command, plain)
+	}
+
+	fn decrypt(&self, encrypted: &[u8]) -> Result<Vec<u8>> {
+		let identity = self
+			.identity
+			.as_deref()
+			.ok_or_else(|| eyre!("No identity to decrypt with age is configured"))?;
+
+		let mut command = Command::new("age");
+		command.arg("--decrypt").arg("--identity").arg(identity);
+
+		pipe(command, encrypted)
+	}
+}
+
+/// Encrypts with the `gpg` command in ASCII armor, so encrypted sources are
+/// text files.
+///
+/// The keys of the recipients are used without checking their trust, as they
+/// are chosen explicitly in the profile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Gpg {
+	/// Key ids or email addresses for which the content is encrypted.
+	pub recipients: Vec<String>,
+}
+
+#[cfg(feature = "io")]
+impl EncryptionBackend for Gpg {
+	fn encrypt(&self, plain: &[u8]) -> Result<Vec<u8>> {
+		let mut command = Command::new("gpg");
+		command.args([
+			"--batch",
+			"--quiet",
+			"--armor",
+			"--trust-model",
+			"always",
+			"--encrypt",
+		]);
+
+		for recipient in &self.recipients {
+			command.args(["--recipient", recipient]);
+		}
+
+		pipe(command, plain)
+	}
+
+	fn decrypt(&self, encrypted: &[u8]) -> Result<Vec<u8>> {
+		let mut command = Command::new("gpg");
+		command.args(["--batch", "--quiet", "--decrypt"]);
+
+		pipe(command, encrypted)
+	}
+}
+
+/// Runs `command` with `input` on its standard input and returns its standard
+/// output.
+///
+/// # Errors
+///
+/// An error with the standard error of the command is returned if it could
+/// not be executed or exited with a failure status.
+#[cfg(feature = "io")]
+fn pipe(mut command: Command, input: &[u8]) -> Result<Vec<u8>> {
+	let program = command.get_program().to_string_lossy().into_owned();
+
+	let mut child = command
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.wrap_err_with(|| format!("Failed to run `{program}`"))?;
+
+	let mut stdin = child.stdin.take().expect("Failed to get stdin of command");
+
+	// The input is written from another thread, as the command might fill up
+	// its output before it read all of the input.
+	let output = std::thread::scope(|scope| {
+		let writer = scope.spawn(move || stdin.write_all(input));
+		let output = child.wait_with_output();
+		let _ = writer.join();
+
+		output
+	})
+	.wrap_err_with(|| format!("Failed to run `{program}`"))?;
+
+	if !output.status.success() {
+		return Err(eyre!(
+			"`{program}` failed with {}: {}",
+			output.status,
+			String::from_utf8_lossy(&output.stderr).trim()
+		));
+	}
+
+	Ok(output.stdout)
+}
+
+#[cfg(all(test, feature = "io"))]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn age_requires_identity() {
+		crate::tests::setup_test_env();
+
+		let backend = Encryption {
+			backend: BackendKind::Age,
+			recipients: vec![String::from("age1demo")],
+			identity: None,
+		}
+		.backend()
+		.expect("Backend to be created");
+
+		let err = backend.decrypt(b"").expect_err("Decryption to fail");
+		assert_eq!(
+			err.to_string(),
+			"No identity to decrypt with age is configured"
+		);
+	}
+}
@@ -0,0 +1,85 @@
+//! Formatters which are applied to the deployed files of a profile by their
+//! file extension (e.g. `jq -S .` for `json` files).
+//!
+//! They run after the templates are resolved and all transforms of a dotfile
+//! are applied, so every deployed file of a kind is formatted the same way.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::profile::hook::Hook;
+
+/// Formats the content of all deployed files with one of the given
+/// [`Formatter::extensions`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct Formatter {
+	/// File extensions of the targets which are formatted (e.g. `json`).
+	///
+	/// They are compared case insensitively.
+	pub extensions: Vec<String>,
+
+	/// Command which formats the content. The content is piped through the
+	/// command like a [transform command](`crate::profile::dotfile::Dotfile::transform`).
+	pub command: Hook,
+
+	/// What happens if the command fails. Defaults to
+	/// [`FormatFailure::Error`].
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub on_failure: Option<FormatFailure>,
+}
+
+impl Formatter {
+	/// Checks if the formatter applies to the file at `path`.
+	pub fn matches(&self, path: &Path) -> bool {
+		path.extension()
+			.and_then(|extension| extension.to_str())
+			.is_some_and(|extension| {
+				self.extensions
+					.iter()
+					.any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(extension))
+			})
+	}
+
+	/// Returns the action if the command fails.
+	pub fn on_failure(&self) -> FormatFailure {
+		self.on_failure.unwrap_or_default()
+	}
+}
+
+/// What happens with a file whose [`Formatter`] fails.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub enum FormatFailure {
+	/// The file fails to deploy.
+	#[default]
+	Error,
+
+	/// The unformatted content is deployed with a warning.
+	Ignore,
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn formatter_matches() {
+		crate::tests::setup_test_env();
+
+		let formatter = Formatter {
+			extensions: vec![String::from("json"), String::from(".sh")],
+			command: Hook::new("jq -S ."),
+			on_failure: None,
+		};
+
+		assert!(formatter.matches(Path::new("/home/demo/.config/app/config.JSON")));
+		assert!(formatter.matches(Path::new("install.sh")));
+		assert!(!formatter.matches(Path::new("config.json.bak")));
+		assert!(!formatter.matches(Path::new("json")));
+		assert_eq!(formatter.on_failure(), FormatFailure::Error);
+	}
+}
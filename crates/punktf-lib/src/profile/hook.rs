@@ -1,34 +1,60 @@
 //! Hooks which can be execute by the native os shell.
 
-use std::io::{BufRead as _, BufReader};
+#[cfg(feature = "io")]
+use std::io::{BufRead as _, BufReader, Write as _};
+#[cfg(feature = "io")]
 use std::path::Path;
+#[cfg(feature = "io")]
 use std::process::{Command, Stdio};
 
-use color_eyre::eyre::Result;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+#[cfg(feature = "io")]
+use crate::plugin::{Plugins, HOOK_PREFIX};
+
 /// An enum of errors which can occur during the execution of a [`Hook`].
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 pub enum HookError {
 	/// An [`std::io::Error`] which occurred during the execution of a hook.
-	#[error("IO Error")]
-	IoError(#[from] std::io::Error),
+	#[error("IO Error: {0}")]
+	IoError(String),
 
 	/// The hook failed to execute successfully.
-	#[error("Process failed with status `{0}`")]
-	ExitStatusError(std::process::ExitStatus),
+	#[error("Process failed with status `{status}`")]
+	ExitStatusError {
+		/// Exit code of the process if it was not terminated by a signal.
+		code: Option<i32>,
+
+		/// Description of the exit status.
+		status: String,
+	},
+
+	/// The hook of a plugin failed (see [`Hook::execute_with`]).
+	#[error("Plugin failed: {0}")]
+	Plugin(String),
+}
+
+impl From<std::io::Error> for HookError {
+	fn from(value: std::io::Error) -> Self {
+		Self::IoError(value.to_string())
+	}
 }
 
 impl From<std::process::ExitStatus> for HookError {
 	fn from(value: std::process::ExitStatus) -> Self {
-		Self::ExitStatusError(value)
+		Self::ExitStatusError {
+			code: value.code(),
+			status: value.to_string(),
+		}
 	}
 }
 
 // TODO: Replace once `exit_ok` becomes stable
 /// Maps a value to an Result. This is mainly used as a replacement for
 /// [`std::process::ExitStatus::exit_ok`] until it becomes stable.
+#[cfg(feature = "io")]
 trait ExitOk {
 	/// Error type of the returned result.
 	type Error;
@@ -37,6 +63,7 @@ trait ExitOk {
 	fn exit_ok(self) -> Result<(), Self::Error>;
 }
 
+#[cfg(feature = "io")]
 impl ExitOk for std::process::ExitStatus {
 	type Error = HookError;
 
@@ -51,6 +78,7 @@ impl ExitOk for std::process::ExitStatus {
 
 /// Implements the `Hook` trait, which is used to run a command after or before a build.
 #[derive(Default, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Hook(String);
 
@@ -66,7 +94,14 @@ impl Hook {
 	}
 
 	/// Executes the hook command.
-	pub fn execute(&self, cwd: &Path) -> Result<()> {
+	///
+	/// # Errors
+	///
+	/// An error is returned if the command could not be executed or if it
+	/// exited with a failure status.
+	#[cfg(feature = "io")]
+	#[tracing::instrument(name = "hook", skip_all, fields(command = self.command()))]
+	pub fn execute(&self, cwd: &Path) -> Result<(), HookError> {
 		let mut child = self
 			.prepare_command()?
 			.current_dir(cwd)
@@ -80,7 +115,7 @@ impl Hook {
 
 		for line in BufReader::new(stdout).lines() {
 			match line {
-				Ok(line) => log::info!("hook::stdout > {}", line),
+				Ok(line) => tracing::info!("hook::stdout > {}", line),
 				Err(err) => {
 					// Result is explicitly ignored as an error was already
 					// encountered
@@ -96,7 +131,7 @@ impl Hook {
 
 		for line in BufReader::new(stderr).lines() {
 			match line {
-				Ok(line) => log::error!("hook::stderr > {}", line),
+				Ok(line) => tracing::error!("hook::stderr > {}", line),
 				Err(err) => {
 					// Result is explicitly ignored as an error was already
 					// encountered
@@ -106,15 +141,96 @@ impl Hook {
 			}
 		}
 
-		child
-			.wait_with_output()?
-			.status
-			.exit_ok()
-			.map_err(Into::into)
+		child.wait_with_output()?.status.exit_ok()
+	}
+
+	/// Executes the hook like [`Hook::execute`], but calls the hook `<name>` of
+	/// `plugins` with `<argument>` if the command has the form
+	/// `plugin:<name> <argument>` (see [`HOOK_PREFIX`]).
+	///
+	/// # Errors
+	///
+	/// An error is returned if the command or the hook of the plugin failed.
+	#[cfg(feature = "io")]
+	pub fn execute_with(&self, cwd: &Path, plugins: &Plugins) -> Result<(), HookError> {
+		let Some(call) = self.0.strip_prefix(HOOK_PREFIX) else {
+			return self.execute(cwd);
+		};
+
+		let call = call.trim();
+		let (name, argument) = call.split_once(' ').unwrap_or((call, ""));
+
+		let output = plugins
+			.call_hook(name, argument.trim_start())
+			.map_err(|err| HookError::Plugin(format!("{err:#}")))?;
+
+		for line in output.lines() {
+			tracing::info!("hook::stdout > {}", line);
+		}
+
+		Ok(())
+	}
+
+	/// Executes the hook command and returns its standard output.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the command could not be executed or if it
+	/// exited with a failure status.
+	#[cfg(feature = "io")]
+	pub fn output(&self, cwd: &Path) -> Result<String, HookError> {
+		let output = self
+			.prepare_command()?
+			.current_dir(cwd)
+			.stderr(Stdio::inherit())
+			.output()?;
+
+		output.status.exit_ok()?;
+
+		Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+	}
+
+	/// Executes the hook command with `input` as its standard input and returns
+	/// its standard output.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the command could not be executed, if it
+	/// exited with a failure status or if its output is not valid UTF-8.
+	#[cfg(feature = "io")]
+	pub fn pipe(&self, cwd: &Path, input: &str) -> Result<String, HookError> {
+		let mut child = self
+			.prepare_command()?
+			.current_dir(cwd)
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.stderr(Stdio::inherit())
+			.spawn()?;
+
+		let mut stdin = child.stdin.take().expect("Failed to get stdin from hook");
+
+		// The input is written from another thread, as the command might fill
+		// up its output before it read all of the input.
+		let output = std::thread::scope(|scope| {
+			let writer = scope.spawn(move || stdin.write_all(input.as_bytes()));
+			let output = child.wait_with_output();
+
+			// Commands which exit without reading all of the input result in a
+			// broken pipe, which is not an error
+			let _ = writer.join();
+
+			output
+		})?;
+
+		output.status.exit_ok()?;
+
+		String::from_utf8(output.stdout)
+			.map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err).into())
 	}
 
 	/// Prepares the command for execution depending on the platform.
-	fn prepare_command(&self) -> Result<Command> {
+	#[cfg(feature = "io")]
+	fn prepare_command(&self) -> std::io::Result<Command> {
 		cfg_if::cfg_if! {
 			if #[cfg(target_family = "windows")] {
 				let mut cmd = Command::new("cmd");
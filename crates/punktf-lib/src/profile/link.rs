@@ -5,6 +5,7 @@ use std::path::PathBuf;
 
 /// A symlink to be created during the deployment.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Symlink {
 	/// Absolute path of the link source.
@@ -0,0 +1,196 @@
+//! Migrations of profiles written in older versions of the profile format.
+//!
+//! Each [`Migration`] updates a raw profile from the previous format version
+//! to the next one. A profile is migrated by applying all migrations newer
+//! than its [`version`](`crate::profile::Profile::version`) in order.
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+use serde_json::{Map, Value};
+
+use crate::profile::{Profile, PROFILE_VERSION};
+
+/// A single step which migrates a raw profile to the next format version.
+#[derive(Debug, Clone, Copy)]
+struct Migration {
+	/// Format version of the profile after the migration.
+	to: u32,
+
+	/// Applies the migration and returns a description of each change.
+	apply: fn(&mut Map<String, Value>) -> Vec<String>,
+}
+
+/// All migrations in order of their version.
+const MIGRATIONS: &[Migration] = &[Migration {
+	to: 1,
+	apply: migrate_dotfile_target,
+}];
+
+/// Migrates the legacy `target` of dotfiles (either a path or a map with a
+/// `path`) to `overwrite_target`.
+fn migrate_dotfile_target(profile: &mut Map<String, Value>) -> Vec<String> {
+	let mut changes = Vec::new();
+
+	let Some(Value::Array(dotfiles)) = profile.get_mut("dotfiles") else {
+		return changes;
+	};
+
+	for (idx, dotfile) in dotfiles.iter_mut().enumerate() {
+		let Some(dotfile) = dotfile.as_object_mut() else {
+			continue;
+		};
+
+		let Some(target) = dotfile.remove("target") else {
+			continue;
+		};
+
+		let target = match target {
+			Value::Object(mut target) => target.remove("path").unwrap_or(Value::Null),
+			target => target,
+		};
+
+		if dotfile.contains_key("overwrite_target") {
+			changes.push(format!(
+				"dotfiles[{idx}]: removed `target` as `overwrite_target` is already set"
+			));
+		} else {
+			dotfile.insert(String::from("overwrite_target"), target);
+			changes.push(format!(
+				"dotfiles[{idx}]: renamed `target` to `overwrite_target`"
+			));
+		}
+	}
+
+	changes
+}
+
+/// A profile which was migrated to the current [`PROFILE_VERSION`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migrated {
+	/// Format version of the profile before the migration (`0` for profiles
+	/// without a version).
+	pub from: u32,
+
+	/// Descriptions of all changes which were made to the profile.
+	pub changes: Vec<String>,
+
+	/// The migrated profile.
+	pub profile: Profile,
+}
+
+impl Migrated {
+	/// Checks if the profile already used the current format version.
+	pub const fn is_current(&self) -> bool {
+		self.from == PROFILE_VERSION
+	}
+}
+
+/// Migrates the raw `profile` (as parsed from a profile file) to the current
+/// [`PROFILE_VERSION`].
+///
+/// # Errors
+///
+/// An error is returned if the profile is not a map, if it uses a newer format
+/// version than the current one or if it is not valid after the migration.
+pub fn migrate(profile: Value) -> Result<Migrated> {
+	let Value::Object(mut profile) = profile else {
+		return Err(eyre!("Profile must be a map"));
+	};
+
+	let from = match profile.get("version") {
+		None => 0,
+		Some(version) => version
+			.as_u64()
+			.and_then(|version| u32::try_from(version).ok())
+			.ok_or_else(|| eyre!("Invalid profile version `{version}`"))?,
+	};
+
+	if from > PROFILE_VERSION {
+		return Err(eyre!(
+			"Profile uses format version {from}, but only versions up to {PROFILE_VERSION} are \
+			 supported"
+		));
+	}
+
+	let mut changes = Vec::new();
+	for migration in MIGRATIONS.iter().filter(|migration| migration.to > from) {
+		changes.extend((migration.apply)(&mut profile));
+	}
+
+	profile.insert(String::from("version"), PROFILE_VERSION.into());
+
+	let profile = serde_json::from_value(Value::Object(profile))
+		.wrap_err("Profile is not valid after the migration")?;
+
+	Ok(Migrated {
+		from,
+		changes,
+		profile,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+
+	use serde_json::json;
+
+	use super::*;
+
+	#[test]
+	fn migrate_legacy_target() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let migrated = migrate(json!({
+			"dotfiles": [
+				{ "path": "a", "target": "/etc" },
+				{ "path": "b", "target": { "path": "/opt" } },
+				{ "path": "c" },
+			]
+		}))?;
+
+		assert_eq!(migrated.from, 0);
+		assert!(!migrated.is_current());
+		assert_eq!(migrated.changes.len(), 2);
+		assert_eq!(migrated.profile.version, Some(PROFILE_VERSION));
+
+		let targets = migrated
+			.profile
+			.dotfiles
+			.iter()
+			.map(|dotfile| dotfile.overwrite_target.clone())
+			.collect::<Vec<_>>();
+		assert_eq!(
+			targets,
+			[
+				Some(PathBuf::from("/etc")),
+				Some(PathBuf::from("/opt")),
+				None
+			]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn migrate_current() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let migrated = migrate(json!({
+			"version": PROFILE_VERSION,
+			"dotfiles": [{ "path": "a", "overwrite_target": "/etc" }]
+		}))?;
+
+		assert!(migrated.is_current());
+		assert!(migrated.changes.is_empty());
+
+		Ok(())
+	}
+
+	#[test]
+	fn migrate_newer_version() {
+		crate::tests::setup_test_env();
+
+		assert!(migrate(json!({ "version": PROFILE_VERSION + 1 })).is_err());
+	}
+}
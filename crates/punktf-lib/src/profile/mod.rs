@@ -1,34 +1,63 @@
 //! Defines profiles and ways to layer multiple of them.
 
+pub mod assertion;
+#[cfg(all(feature = "io", feature = "profile-json"))]
+pub mod cache;
+pub mod condition;
 pub mod dotfile;
+pub mod encryption;
+pub mod formatter;
 pub mod hook;
 pub mod link;
+#[cfg(feature = "profile-json")]
+pub mod migrate;
+#[cfg(feature = "io")]
+pub mod provider;
+#[cfg(feature = "io")]
+pub mod secret;
+#[cfg(feature = "io")]
 pub mod source;
 pub mod transform;
 pub mod variables;
 
+use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
+#[cfg(feature = "io")]
 use std::fs::File;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-use color_eyre::eyre::{bail, eyre, Context};
+use color_eyre::eyre::eyre;
+#[cfg(feature = "io")]
+use color_eyre::eyre::{bail, Context};
 use color_eyre::Result;
 use serde::{Deserialize, Serialize};
 
+use crate::plugin::Plugins;
+use crate::profile::assertion::Assertion;
+use crate::profile::dotfile::Dotfile;
+use crate::profile::encryption::Encryption;
+use crate::profile::formatter::Formatter;
 use crate::profile::hook::Hook;
 use crate::profile::link::Symlink;
+#[cfg(feature = "io")]
+use crate::profile::provider::VariableProviders;
+#[cfg(feature = "io")]
+use crate::profile::source::PunktfSource;
 use crate::profile::transform::ContentTransformer;
-use crate::profile::variables::{Variables, Vars};
-use crate::profile::{dotfile::Dotfile, source::PunktfSource};
+use crate::profile::variables::{PromptVariable, Variables, Vars};
 
 /// This enum represents all available merge modes `punktf` supports. The merge
 /// mode is important when a file already exists at the target location of a
 /// [`Dotfile`](`crate::profile::dotfile::Dotfile`).
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub enum MergeMode {
 	/// Overwrites the existing file.
+	#[default]
 	Overwrite,
 
 	/// Keeps the existing file.
@@ -38,9 +67,39 @@ pub enum MergeMode {
 	Ask,
 }
 
-impl Default for MergeMode {
-	fn default() -> Self {
-		Self::Overwrite
+impl MergeMode {
+	/// All available merge modes.
+	pub const ALL: [Self; 3] = [Self::Overwrite, Self::Keep, Self::Ask];
+
+	/// Returns the name of the merge mode as used on the command line.
+	pub const fn as_str(&self) -> &'static str {
+		match self {
+			Self::Overwrite => "overwrite",
+			Self::Keep => "keep",
+			Self::Ask => "ask",
+		}
+	}
+}
+
+impl fmt::Display for MergeMode {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl FromStr for MergeMode {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::ALL
+			.into_iter()
+			.find(|mode| mode.as_str().eq_ignore_ascii_case(s))
+			.ok_or_else(|| {
+				format!(
+					"Invalid merge mode `{s}` (possible values: {})",
+					Self::ALL.map(|m| m.as_str()).join(", ")
+				)
+			})
 	}
 }
 
@@ -51,6 +110,7 @@ impl Default for MergeMode {
 #[derive(
 	Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
 )]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Priority(pub u32);
 
@@ -61,14 +121,94 @@ impl Priority {
 	}
 }
 
+/// Defines how symlinks found in the source directory of a
+/// [`Dotfile`](`crate::profile::dotfile::Dotfile`) are handled.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields, rename_all = "kebab-case")]
+pub enum SourceSymlinks {
+	/// Deploys the item the symlink points to as if it was located at the
+	/// symlink.
+	///
+	/// Directories are walked recursively. Symlinks which point to a directory
+	/// which is already being walked are reported as an error.
+	Follow,
+
+	/// Ignores the symlink.
+	Skip,
+
+	/// Deploys a symlink at the target which points to the same item as the
+	/// symlink in the source.
+	DeployAsLink,
+
+	/// Reports the symlink as an error.
+	#[default]
+	Error,
+}
+
+impl SourceSymlinks {
+	/// All available policies.
+	pub const ALL: [Self; 4] = [Self::Follow, Self::Skip, Self::DeployAsLink, Self::Error];
+
+	/// Returns the name of the policy as used in profiles.
+	pub const fn as_str(&self) -> &'static str {
+		match self {
+			Self::Follow => "follow",
+			Self::Skip => "skip",
+			Self::DeployAsLink => "deploy-as-link",
+			Self::Error => "error",
+		}
+	}
+}
+
+impl fmt::Display for SourceSymlinks {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl FromStr for SourceSymlinks {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::ALL
+			.into_iter()
+			.find(|policy| policy.as_str().eq_ignore_ascii_case(s))
+			.ok_or_else(|| {
+				format!(
+					"Invalid source symlink policy `{s}` (possible values: {})",
+					Self::ALL.map(|p| p.as_str()).join(", ")
+				)
+			})
+	}
+}
+
+/// Current version of the profile format (see [`Profile::version`]).
+pub const PROFILE_VERSION: u32 = 1;
+
 /// A profile is a collection of dotfiles and variables, options and hooks.
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct Profile {
+	/// Version of the profile format.
+	///
+	/// Profiles without a version use the format from before versions were
+	/// introduced. Older profiles can be updated to the current
+	/// [`PROFILE_VERSION`] with [`migrate`](`crate::profile::migrate`).
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub version: Option<u32>,
+
 	/// Aliases for this profile which can be used instead of the file name.
 	#[serde(skip_serializing_if = "Vec::is_empty", default)]
 	pub aliases: Vec<String>,
 
+	/// Short human readable description of the profile.
+	///
+	/// This is purely informational and used when listing profiles.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub description: Option<String>,
+
 	/// Defines the base profile. All settings from the base are merged with the
 	/// current profile. The settings from the current profile take precedence.
 	/// Dotfiles are merged on the dotfile level (not specific dotfile settings level).
@@ -79,16 +219,57 @@ pub struct Profile {
 	#[serde(skip_serializing_if = "Option::is_none", default)]
 	pub variables: Option<Variables>,
 
+	/// Variables whose values are asked from the user on the first deployment.
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub prompt_variables: Vec<PromptVariable>,
+
 	/// Content transform of the profile. Each dotfile will have these applied.
 	#[serde(skip_serializing_if = "Vec::is_empty", default)]
 	pub transformers: Vec<ContentTransformer>,
 
+	/// Formatters which are applied to the deployed files by their file
+	/// extension.
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub formatters: Vec<Formatter>,
+
+	/// Prefixes of line comments by file extension, which are used by the
+	/// `comment` function of templates. These take precedence over the
+	/// built-in prefixes.
+	#[serde(skip_serializing_if = "HashMap::is_empty", default)]
+	pub comment_prefixes: HashMap<String, String>,
+
+	/// Encryption of the sources of the
+	/// [encrypted](`crate::profile::dotfile::Dotfile::encrypted`) dotfiles.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub encryption: Option<Encryption>,
+
+	/// Namespace in which the secrets referenced by `from:secret:<name>`
+	/// variables are looked up (see [`secret`](`crate::profile::secret`)).
+	/// Defaults to `default`.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub secret_namespace: Option<String>,
+
 	/// Target root path of the deployment. Will be used as file stem for the dotfiles
 	/// when not overwritten by
 	/// [`Dotfile::overwrite_target`](`crate::profile::dotfile::Dotfile::overwrite_target`).
 	#[serde(skip_serializing_if = "Option::is_none", default)]
 	pub target: Option<PathBuf>,
 
+	/// Defines how symlinks in the source directories of the dotfiles are
+	/// handled.
+	///
+	/// Defaults to [`SourceSymlinks::Error`].
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub source_symlinks: Option<SourceSymlinks>,
+
+	/// Indicates if missing parent directories of the targets of the dotfiles
+	/// are created. Can be overwritten by
+	/// [`Dotfile::create_parents`](`crate::profile::dotfile::Dotfile::create_parents`).
+	///
+	/// Defaults to `true`.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub create_parents: Option<bool>,
+
 	/// Hook will be executed once before the deployment begins. If the hook fails
 	/// the deployment will not be continued.
 	#[serde(skip_serializing_if = "Vec::is_empty", default)]
@@ -98,6 +279,12 @@ pub struct Profile {
 	#[serde(skip_serializing_if = "Vec::is_empty", default)]
 	pub post_hooks: Vec<Hook>,
 
+	/// Assertions which are evaluated after the deployment (including the
+	/// post-hooks) to verify that the environment works. If any assertion does
+	/// not hold, the deployment is marked as failed.
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	pub assertions: Vec<Assertion>,
+
 	/// Dotfiles which will be deployed.
 	#[serde(skip_serializing_if = "Vec::is_empty", default)]
 	pub dotfiles: Vec<Dotfile>,
@@ -117,16 +304,42 @@ impl Profile {
 	///
 	/// An error is returned if the file does not exist or could not be read.
 	/// An error is returned if the file extension is unknown or missing.
+	/// An error is returned if the profile uses a newer format version than
+	/// [`PROFILE_VERSION`].
+	#[cfg(feature = "io")]
 	pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
 		let path = path.as_ref();
 
+		std::fs::read_to_string(path)
+			.map_err(Into::into)
+			.and_then(|content| Self::parse(path, &content))
+			.wrap_err(format!(
+				"Failed to process profile at path `{}`",
+				path.display()
+			))
+	}
+
+	/// Tries to parse a profile from `content`.
+	///
+	/// The deserializer is chosen by the file extension of `path`, which is
+	/// also used to point to the location of parse errors. The file at `path`
+	/// is never accessed, so this works without the `io` feature (e.g. in a
+	/// browser).
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file extension is unknown or missing or if
+	/// `content` is not a valid profile.
+	/// An error is returned if the profile uses a newer format version than
+	/// [`PROFILE_VERSION`].
+	pub fn parse<P: AsRef<Path>>(path: P, content: &str) -> Result<Self> {
+		let path = path.as_ref();
+
 		/// Inner function is used to reduce monomorphizes as path here is a
 		/// concrete type and no generic one.
-		fn from_file_inner(path: &Path) -> Result<Profile> {
-			// Allowed in case no feature is present.
-			#[allow(unused_variables)]
-			let file = File::open(path)?;
-
+		// Allowed in case no feature is present.
+		#[allow(unused_variables)]
+		fn parse_inner(path: &Path, content: &str) -> Result<Profile> {
 			let extension = path.extension().ok_or_else(|| {
 				std::io::Error::new(
 					std::io::ErrorKind::InvalidData,
@@ -137,14 +350,14 @@ impl Profile {
 			#[cfg(feature = "profile-json")]
 			{
 				if extension.eq_ignore_ascii_case("json") {
-					return Profile::from_json_file(file);
+					return Profile::from_json_str(path, content);
 				}
 			}
 
 			#[cfg(feature = "profile-yaml")]
 			{
 				if extension.eq_ignore_ascii_case("yaml") || extension.eq_ignore_ascii_case("yml") {
-					return Profile::from_yaml_file(file);
+					return Profile::from_yaml_str(path, content);
 				}
 			}
 
@@ -154,27 +367,132 @@ impl Profile {
 			))
 		}
 
-		from_file_inner(path).wrap_err(format!(
-			"Failed to process profile at path `{}`",
-			path.display()
-		))
+		parse_inner(path, content).and_then(Self::check_version)
 	}
 
-	/// Tries to load a profile from a json file.
+	/// Fails if the profile uses a newer format [`version`](`Profile::version`)
+	/// than [`PROFILE_VERSION`].
+	fn check_version(self) -> Result<Self> {
+		match self.version {
+			Some(version) if version > PROFILE_VERSION => Err(eyre!(
+				"Profile uses format version {version}, but only versions up to \
+				 {PROFILE_VERSION} are supported. Please update punktf"
+			)),
+			_ => Ok(self),
+		}
+	}
+
+	/// Tries to load a profile from the json `content` of the file at `path`.
 	#[cfg(feature = "profile-json")]
-	fn from_json_file(file: File) -> Result<Self> {
-		serde_json::from_reader(&file).map_err(|err| {
-			color_eyre::Report::msg(err).wrap_err("Failed to parse profile from json content.")
+	fn from_json_str(path: &Path, content: &str) -> Result<Self> {
+		let deserializer = &mut serde_json::Deserializer::from_str(content);
+
+		serde_path_to_error::deserialize(deserializer).map_err(|err| {
+			let key = err.path().to_string();
+			let inner = err.inner();
+
+			// Lines and columns of `serde_json` start at 1
+			let index = content
+				.split_inclusive('\n')
+				.take(inner.line().saturating_sub(1))
+				.map(str::len)
+				.sum::<usize>()
+				+ inner.column().saturating_sub(1);
+
+			parse_error(path, content, index, &key, &inner.to_string())
 		})
 	}
 
-	/// Tries to load a profile from a yaml file.
+	/// Tries to load a profile from the yaml `content` of the file at `path`.
 	#[cfg(feature = "profile-yaml")]
-	fn from_yaml_file(file: File) -> Result<Self> {
-		serde_yaml::from_reader(file).map_err(|err| {
-			color_eyre::Report::msg(err).wrap_err("Failed to parse profile from yaml content.")
+	fn from_yaml_str(path: &Path, content: &str) -> Result<Self> {
+		serde_yaml::from_str(content).map_err(|err| {
+			let index = err.location().map_or(0, |location| location.index());
+
+			// The message of `serde_yaml` already includes the key
+			parse_error(path, content, index, ".", &err.to_string())
 		})
 	}
+
+	/// Returns a [JSON Schema](https://json-schema.org/) of the profile format.
+	///
+	/// The schema is derived from the serde definitions of the profile and
+	/// applies to both json and yaml profiles. It can be used by editors to
+	/// offer completion and validation for profile files.
+	#[cfg(feature = "profile-schema")]
+	pub fn json_schema() -> serde_json::Value {
+		schemars::schema_for!(Profile).to_value()
+	}
+}
+
+/// Creates a diagnostic for an error which occurred while parsing the profile
+/// `content` of the file at `path`.
+///
+/// The diagnostic points to the byte `index` in the content and includes the
+/// `key` at which the error occurred as well as the most similar valid
+/// alternative for unknown fields and variants (if any).
+#[cfg(any(feature = "profile-json", feature = "profile-yaml"))]
+fn parse_error(
+	path: &Path,
+	content: &str,
+	index: usize,
+	key: &str,
+	message: &str,
+) -> color_eyre::Report {
+	use crate::template::diagnostic::DiagnosticFormatter;
+	use crate::template::source::Source;
+	use crate::template::span::ByteSpan;
+
+	// The location is shown by the diagnostic itself
+	let message = message
+		.rsplit_once(" at line ")
+		.map_or(message, |(message, _)| message);
+
+	let message = if key.is_empty() || key == "." {
+		message.to_string()
+	} else {
+		format!("{key}: {message}")
+	};
+
+	let index = index.min(content.len());
+	let token_len = content[index..]
+		.find(|c: char| c.is_whitespace() || matches!(c, ':' | ',' | '"'))
+		.unwrap_or(content.len() - index)
+		.max(1);
+
+	let help =
+		unknown_item_suggestion(&message).map(|name| format!("help: did you mean `{name}`?"));
+
+	let source = Source::file(path, content);
+	let mut formatter = DiagnosticFormatter::new(&source, "Failed to parse profile");
+	formatter
+		.primary_span(&ByteSpan::new(index, index + token_len))
+		.description(&message);
+
+	if let Some(help) = &help {
+		formatter.description(help);
+	}
+
+	color_eyre::Report::msg(formatter.finish())
+}
+
+/// Returns the most similar valid alternative of an unknown field or variant
+/// for a serde error `message` (e.g. ``unknown variant `Aks`, expected one of
+/// `Overwrite`, `Keep`, `Ask` ``).
+#[cfg(any(feature = "profile-json", feature = "profile-yaml"))]
+fn unknown_item_suggestion(message: &str) -> Option<&str> {
+	let (_, rest) = message
+		.split_once("unknown field `")
+		.or_else(|| message.split_once("unknown variant `"))?;
+	let (name, expected) = rest.split_once('`')?;
+
+	let candidates = expected
+		.split('`')
+		.skip(1)
+		.step_by(2)
+		.filter(|candidate| !candidate.is_empty());
+
+	suggest_profile_name(name, candidates)
 }
 
 /// Stores variables defined on different layers.
@@ -185,6 +503,10 @@ pub struct LayeredVariables {
 	/// [`LayeredProfile::profile_names`](`crate::profile::LayeredProfile::profile_names`)
 	/// to retrieve the name of the profile, the variable came from.
 	pub inner: HashMap<String, (usize, String)>,
+
+	/// Stores the values of variables which are shadowed by a value of the same
+	/// variable on a higher layer, ordered from the highest to the lowest layer.
+	pub shadowed: HashMap<String, Vec<(usize, String)>>,
 }
 
 impl Vars for LayeredVariables {
@@ -208,18 +530,60 @@ pub struct LayeredProfile {
 	/// top.
 	pub target: Option<(usize, PathBuf)>,
 
+	/// The policy for symlinks in the source.
+	///
+	/// This is the first value found by traversing the extend chain from the
+	/// top.
+	pub source_symlinks: Option<(usize, SourceSymlinks)>,
+
+	/// Indicates if missing parent directories of targets are created.
+	///
+	/// This is the first value found by traversing the extend chain from the
+	/// top.
+	pub create_parents: Option<(usize, bool)>,
+
 	/// The variables collected from all profiles of the extend chain.
 	pub variables: LayeredVariables,
 
+	/// The prompt variables collected from all profiles of the extend chain.
+	///
+	/// Only the first declaration of a variable name is kept.
+	pub prompt_variables: Vec<(usize, PromptVariable)>,
+
 	/// The content transformer collected from all profiles of the extend chain.
 	pub transformers: Vec<(usize, ContentTransformer)>,
 
+	/// The formatters collected from all profiles of the extend chain.
+	pub formatters: Vec<(usize, Formatter)>,
+
+	/// The prefixes of line comments collected from all profiles of the
+	/// extend chain.
+	///
+	/// This is the first value found for each extension by traversing the
+	/// extend chain from the top.
+	pub comment_prefixes: HashMap<String, String>,
+
+	/// The encryption of the sources.
+	///
+	/// This is the first value found by traversing the extend chain from the
+	/// top.
+	pub encryption: Option<(usize, Encryption)>,
+
+	/// The namespace of the secrets.
+	///
+	/// This is the first value found by traversing the extend chain from the
+	/// top.
+	pub secret_namespace: Option<(usize, String)>,
+
 	/// The pre-hooks collected from all profiles of the extend chain.
 	pub pre_hooks: Vec<(usize, Hook)>,
 
 	/// The post-hooks collected from all profiles of the extend chain.
 	pub post_hooks: Vec<(usize, Hook)>,
 
+	/// The assertions collected from all profiles of the extend chain.
+	pub assertions: Vec<(usize, Assertion)>,
+
 	/// The dotfiles collected from all profiles of the extend chain.
 	///
 	/// The index indexes into
@@ -233,6 +597,16 @@ pub struct LayeredProfile {
 	/// [`LayeredProfile::profile_names`](`crate::profile::LayeredProfile::profile_names`)
 	/// to retrieve the name of the profile from which the link came from.
 	pub symlinks: Vec<(usize, Symlink)>,
+
+	/// The files from which variable values were read by
+	/// [`LayeredProfile::resolve_variables`] (e.g. `from:file:~/.secrets`).
+	pub variable_files: Vec<PathBuf>,
+
+	/// The functions and hooks of the loaded [plugins](`crate::plugin`).
+	///
+	/// Plugins are not part of the profile files, they are registered by the
+	/// user of the library (e.g. all plugins of the plugin directory).
+	pub plugins: Plugins,
 }
 
 impl LayeredProfile {
@@ -254,13 +628,134 @@ impl LayeredProfile {
 		self.target.as_ref().map(|(_, path)| path.deref())
 	}
 
+	/// Returns the policy for symlinks in the source of the profile.
+	pub fn source_symlinks(&self) -> SourceSymlinks {
+		self.source_symlinks
+			.map(|(_, policy)| policy)
+			.unwrap_or_default()
+	}
+
+	/// Returns if missing parent directories of targets are created.
+	pub fn create_parents(&self) -> bool {
+		self.create_parents.is_none_or(|(_, create)| create)
+	}
+
+	/// Returns the encryption of the sources of the profile.
+	pub fn encryption(&self) -> Option<&Encryption> {
+		self.encryption.as_ref().map(|(_, encryption)| encryption)
+	}
+
+	/// Returns the namespace of the secrets of the profile.
+	pub fn secret_namespace(&self) -> Option<&str> {
+		self.secret_namespace
+			.as_ref()
+			.map(|(_, namespace)| namespace.as_str())
+	}
+
 	/// Returns all collected variables for the profile.
 	pub const fn variables(&self) -> &LayeredVariables {
 		&self.variables
 	}
 
+	/// Returns all collected prefixes of line comments by file extension.
+	pub const fn comment_prefixes(&self) -> &HashMap<String, String> {
+		&self.comment_prefixes
+	}
+
+	/// Returns the functions and hooks of the loaded plugins.
+	pub const fn plugins(&self) -> &Plugins {
+		&self.plugins
+	}
+
+	/// Returns the functions and hooks of the loaded plugins to register new
+	/// ones.
+	pub const fn plugins_mut(&mut self) -> &mut Plugins {
+		&mut self.plugins
+	}
+
+	/// Returns the files from which variable values were read (see
+	/// [`LayeredProfile::variable_files`]).
+	pub fn variable_files(&self) -> impl Iterator<Item = &Path> {
+		self.variable_files.iter().map(PathBuf::as_path)
+	}
+
+	/// Returns all collected prompt variables for the profile.
+	pub fn prompt_variables(&self) -> impl Iterator<Item = &PromptVariable> {
+		self.prompt_variables.iter().map(|(_, variable)| variable)
+	}
+
+	/// Adds `variables` as a new lowest layer with the given `name`.
+	///
+	/// Variables which are already defined on a higher layer keep their value.
+	pub fn add_fallback_variables(&mut self, name: String, variables: Variables) {
+		let idx = self.profile_names.len();
+		self.profile_names.push(name);
+
+		for (key, value) in variables.inner {
+			match self.variables.inner.entry(key) {
+				Entry::Occupied(entry) => self
+					.variables
+					.shadowed
+					.entry(entry.key().clone())
+					.or_default()
+					.push((idx, value)),
+				Entry::Vacant(entry) => {
+					entry.insert((idx, value));
+				}
+			}
+		}
+	}
+
+	/// Replaces all variable values of the profile and its dotfiles which
+	/// reference a provider (`from:<provider>:<argument>`) with the value
+	/// supplied by the provider.
+	///
+	/// Shadowed values are not resolved. The files from which values are read
+	/// are recorded in [`LayeredProfile::variable_files`].
+	///
+	/// # Errors
+	///
+	/// An error is returned if any of the referenced providers fails.
+	#[cfg(feature = "io")]
+	pub fn resolve_variables(&mut self, providers: &VariableProviders) -> Result<()> {
+		for (name, (_, value)) in &mut self.variables.inner {
+			self.variable_files.extend(providers.source_file(value));
+
+			if let Some(resolved) = providers
+				.resolve(value)
+				.wrap_err_with(|| format!("Failed to resolve variable `{name}`"))?
+			{
+				*value = resolved;
+			}
+		}
+
+		for (_, dotfile) in &mut self.dotfiles {
+			let Some(variables) = &mut dotfile.variables else {
+				continue;
+			};
+
+			for (name, value) in &mut variables.inner {
+				self.variable_files.extend(providers.source_file(value));
+
+				if let Some(resolved) = providers.resolve(value).wrap_err_with(|| {
+					format!(
+						"Failed to resolve variable `{name}` of dotfile {}",
+						dotfile.path.display()
+					)
+				})? {
+					*value = resolved;
+				}
+			}
+		}
+
+		self.variable_files.sort_unstable();
+		self.variable_files.dedup();
+
+		Ok(())
+	}
+
 	/// Returns all the count of collected transformers for the profile.
-	pub fn transformers_len(&self) -> usize {
+	pub const fn transformers_len(&self) -> usize {
 		self.transformers.len()
 	}
 
@@ -269,6 +764,19 @@ impl LayeredProfile {
 		self.transformers.iter().map(|(_, transformer)| transformer)
 	}
 
+	/// Returns all collected formatters for the profile.
+	pub fn formatters(&self) -> impl Iterator<Item = &Formatter> {
+		self.formatters.iter().map(|(_, formatter)| formatter)
+	}
+
+	/// Returns the formatter for the file at `path`.
+	///
+	/// If multiple formatters match, the first one wins, so the formatters of
+	/// a profile take precedence over the ones of the profiles it extends.
+	pub fn formatter_for(&self, path: &Path) -> Option<&Formatter> {
+		self.formatters().find(|formatter| formatter.matches(path))
+	}
+
 	/// Returns all collected pre-hooks for the profile.
 	pub fn pre_hooks(&self) -> impl Iterator<Item = &Hook> {
 		self.pre_hooks.iter().map(|(_, hook)| hook)
@@ -279,6 +787,11 @@ impl LayeredProfile {
 		self.post_hooks.iter().map(|(_, hook)| hook)
 	}
 
+	/// Returns all collected assertions for the profile.
+	pub fn assertions(&self) -> impl Iterator<Item = &Assertion> {
+		self.assertions.iter().map(|(_, assertion)| assertion)
+	}
+
 	/// Returns all collected dotfiles for the profile.
 	pub fn dotfiles(&self) -> impl Iterator<Item = &Dotfile> {
 		self.dotfiles.iter().map(|(_, dotfile)| dotfile)
@@ -319,6 +832,31 @@ impl LayeredProfileBuilder {
 				.map(move |target| (idx, target.to_path_buf()))
 		});
 
+		let source_symlinks = self
+			.profiles
+			.iter()
+			.enumerate()
+			.find_map(|(idx, profile)| profile.source_symlinks.map(|policy| (idx, policy)));
+
+		let create_parents = self
+			.profiles
+			.iter()
+			.enumerate()
+			.find_map(|(idx, profile)| profile.create_parents.map(|create| (idx, create)));
+
+		let encryption = self
+			.profiles
+			.iter()
+			.enumerate()
+			.find_map(|(idx, profile)| profile.encryption.clone().map(|enc| (idx, enc)));
+
+		let secret_namespace = self.profiles.iter().enumerate().find_map(|(idx, profile)| {
+			profile
+				.secret_namespace
+				.clone()
+				.map(|namespace| (idx, namespace))
+		});
+
 		let mut variables = LayeredVariables::default();
 
 		for (idx, vars) in self
@@ -328,7 +866,13 @@ impl LayeredProfileBuilder {
 			.filter_map(move |(idx, profile)| profile.variables.as_ref().map(|vars| (idx, vars)))
 		{
 			for (key, value) in vars.inner.iter() {
-				if !variables.inner.contains_key(key) {
+				if variables.inner.contains_key(key) {
+					variables
+						.shadowed
+						.entry(key.to_owned())
+						.or_default()
+						.push((idx, value.to_owned()));
+				} else {
 					variables
 						.inner
 						.insert(key.to_owned(), (idx, value.to_owned()));
@@ -336,6 +880,19 @@ impl LayeredProfileBuilder {
 			}
 		}
 
+		let mut prompt_variables: Vec<(usize, PromptVariable)> = Vec::new();
+
+		for (idx, profile) in self.profiles.iter().enumerate() {
+			for variable in &profile.prompt_variables {
+				if !prompt_variables
+					.iter()
+					.any(|(_, added)| added.name == variable.name)
+				{
+					prompt_variables.push((idx, variable.clone()));
+				}
+			}
+		}
+
 		let mut transformers = Vec::new();
 
 		for (idx, transformer) in self
@@ -351,6 +908,29 @@ impl LayeredProfileBuilder {
 			}
 		}
 
+		let formatters = self
+			.profiles
+			.iter()
+			.enumerate()
+			.flat_map(|(idx, profile)| {
+				profile
+					.formatters
+					.iter()
+					.cloned()
+					.map(move |formatter| (idx, formatter))
+			})
+			.collect();
+
+		let mut comment_prefixes = HashMap::new();
+
+		for profile in &self.profiles {
+			for (extension, prefix) in &profile.comment_prefixes {
+				comment_prefixes
+					.entry(extension.to_owned())
+					.or_insert_with(|| prefix.to_owned());
+			}
+		}
+
 		let pre_hooks = self
 			.profiles
 			.iter()
@@ -377,6 +957,19 @@ impl LayeredProfileBuilder {
 			})
 			.collect();
 
+		let assertions = self
+			.profiles
+			.iter()
+			.enumerate()
+			.flat_map(|(idx, profile)| {
+				profile
+					.assertions
+					.iter()
+					.cloned()
+					.map(move |assertion| (idx, assertion))
+			})
+			.collect();
+
 		let mut added_dotfile_paths = HashSet::new();
 		let mut dotfiles = Vec::new();
 
@@ -410,12 +1003,22 @@ impl LayeredProfileBuilder {
 		LayeredProfile {
 			profile_names: self.profile_names,
 			target,
+			source_symlinks,
+			create_parents,
 			variables,
+			prompt_variables,
 			transformers,
+			formatters,
+			comment_prefixes,
+			encryption,
+			secret_namespace,
 			pre_hooks,
 			post_hooks,
+			assertions,
 			dotfiles,
 			symlinks,
+			variable_files: Vec::new(),
+			plugins: Plugins::empty(),
 		}
 	}
 }
@@ -433,13 +1036,22 @@ struct Aliases {
 }
 
 /// Collects all profile names and aliases from the `profiles` directory.
+///
+/// If the source has [`overlays`](`PunktfSource::overlays`), the profiles of
+/// all layers are collected. A name defined in multiple layers resolves to the
+/// profile of the layer with the highest precedence.
+#[cfg(feature = "io")]
 pub fn collect_profile_names(source: &PunktfSource) -> Result<HashMap<String, PathBuf>> {
-	log::info!("Collecting profile names and aliases");
+	tracing::info!("Collecting profile names and aliases");
 
 	/// Tries to read all alias from a given file.
+	#[cfg_attr(
+		not(any(feature = "profile-json", feature = "profile-yaml")),
+		allow(unused_variables)
+	)]
 	fn get_aliases(path: &Path, extension: &str) -> Option<Aliases> {
 		let Ok(file) = File::open(path) else {
-			log::debug!("[{}] Failed to read content", path.display());
+			tracing::debug!("[{}] Failed to read content", path.display());
 			return None;
 		};
 
@@ -447,7 +1059,7 @@ pub fn collect_profile_names(source: &PunktfSource) -> Result<HashMap<String, Pa
 		{
 			if extension.eq_ignore_ascii_case("json") {
 				let Ok(aliases) = serde_json::from_reader(file) else {
-					log::debug!("[{}] Failed to read aliases", path.display());
+					tracing::debug!("[{}] Failed to read aliases", path.display());
 					return None;
 				};
 
@@ -459,7 +1071,7 @@ pub fn collect_profile_names(source: &PunktfSource) -> Result<HashMap<String, Pa
 		{
 			if extension.eq_ignore_ascii_case("yaml") || extension.eq_ignore_ascii_case("yml") {
 				let Ok(aliases) = serde_yaml::from_reader(file) else {
-					log::debug!("[{}] Failed to read aliases", path.display());
+					tracing::debug!("[{}] Failed to read aliases", path.display());
 					return None;
 				};
 
@@ -472,28 +1084,57 @@ pub fn collect_profile_names(source: &PunktfSource) -> Result<HashMap<String, Pa
 
 	let mut names = HashMap::new();
 
-	let dents = source.profiles().read_dir()?;
+	for layer in source.layers() {
+		for (name, path) in collect_layer_profile_names(layer.profiles(), get_aliases)? {
+			match names.entry(name) {
+				Entry::Vacant(entry) => {
+					entry.insert(path);
+				}
+				Entry::Occupied(entry) => {
+					tracing::info!(
+						"[{}] The profile name {} is shadowed by {}",
+						path.display(),
+						entry.key(),
+						entry.get().display()
+					);
+				}
+			}
+		}
+	}
+
+	Ok(names)
+}
+
+/// Collects all profile names and aliases from a single `profiles` directory.
+#[cfg(feature = "io")]
+fn collect_layer_profile_names(
+	profiles: &Path,
+	get_aliases: impl Fn(&Path, &str) -> Option<Aliases>,
+) -> Result<HashMap<String, PathBuf>> {
+	let mut names = HashMap::new();
+
+	let dents = profiles.read_dir()?;
 	for dent in dents {
 		let dent = dent?;
 		let path = dent.path();
 
 		let Ok(ft) = dent.file_type() else {
-			log::debug!("[{}] Failed to get file type", path.display());
+			tracing::debug!("[{}] Failed to get file type", path.display());
 			continue;
 		};
 
 		if !ft.is_file() {
-			log::debug!("[{}] Not a file", path.display());
+			tracing::debug!("[{}] Not a file", path.display());
 			continue;
 		}
 
 		let Some(extension) = path.extension().and_then(|e| e.to_str()) else {
-			log::debug!("[{}] Failed to get file extension", path.display());
+			tracing::debug!("[{}] Failed to get file extension", path.display());
 			continue;
 		};
 
 		let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
-			log::debug!("[{}] Failed to get file name", path.display());
+			tracing::debug!("[{}] Failed to get file name", path.display());
 			continue;
 		};
 		// Remove extension and `.`
@@ -503,7 +1144,7 @@ pub fn collect_profile_names(source: &PunktfSource) -> Result<HashMap<String, Pa
 			.into_iter()
 			.flat_map(|a| a.aliases.into_iter())
 		{
-			log::debug!("[{}] Adding alias {}", path.display(), alias);
+			tracing::debug!("[{}] Adding alias {}", path.display(), alias);
 
 			if let Some(evicted) = names.insert(alias.clone(), path.clone()) {
 				bail!(
@@ -525,14 +1166,68 @@ pub fn collect_profile_names(source: &PunktfSource) -> Result<HashMap<String, Pa
 		}
 	}
 
-	log::info!("Found {} profile names and aliases", names.len());
+	tracing::info!("Found {} profile names and aliases", names.len());
 
 	Ok(names)
 }
 
+/// Returns the name out of `candidates` which is most similar to `name`.
+///
+/// Only names which are close enough to `name` (measured by the edit distance)
+/// are considered, so `None` is returned if nothing similar was found.
+pub fn suggest_profile_name<'a>(
+	name: &str,
+	candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+	let max_distance = (name.chars().count() / 3).max(1);
+
+	candidates
+		.into_iter()
+		.map(|candidate| (edit_distance(name, candidate), candidate))
+		.filter(|(distance, _)| *distance <= max_distance)
+		// Ties are resolved by name to get a deterministic result
+		.min()
+		.map(|(_, candidate)| candidate)
+}
+
+/// Calculates the optimal string alignment distance between `a` and `b`.
+///
+/// This is the levenshtein distance, where swapping two adjacent characters
+/// (e.g. `acrh` for `arch`) counts as a single edit.
+///
+/// Comparison is done case insensitive.
+fn edit_distance(a: &str, b: &str) -> usize {
+	let a: Vec<char> = a.to_lowercase().chars().collect();
+	let b: Vec<char> = b.to_lowercase().chars().collect();
+
+	let mut before: Vec<usize> = vec![0; b.len() + 1];
+	let mut prev: Vec<usize> = (0..=b.len()).collect();
+	let mut curr = vec![0; b.len() + 1];
+
+	for (i, ca) in a.iter().enumerate() {
+		curr[0] = i + 1;
+
+		for (j, cb) in b.iter().enumerate() {
+			let substitution = prev[j] + usize::from(ca != cb);
+			curr[j + 1] = substitution.min(prev[j + 1] + 1).min(curr[j] + 1);
+
+			if i > 0 && j > 0 && *ca == b[j - 1] && a[i - 1] == *cb {
+				curr[j + 1] = curr[j + 1].min(before[j - 1] + 1);
+			}
+		}
+
+		std::mem::swap(&mut before, &mut prev);
+		std::mem::swap(&mut prev, &mut curr);
+	}
+
+	prev[b.len()]
+}
+
 /// Recursively resolves a profile and it's [extend
 /// chain](`crate::profile::Profile::extends`) and adds them to the layered
 /// profile in order of occurrence.
+#[cfg(feature = "io")]
+#[tracing::instrument(skip_all, fields(profile = name))]
 pub fn resolve_profile(
 	builder: &mut LayeredProfileBuilder,
 	source: &PunktfSource,
@@ -547,11 +1242,18 @@ pub fn resolve_profile(
 		name: &str,
 		resolved_profiles: &mut Vec<String>,
 	) -> Result<()> {
-		log::trace!("Resolving profile `{}`", name);
-
-		let path = profiles
-			.get(name)
-			.ok_or_else(|| eyre!("No profile found for name {}", name))?;
+		tracing::trace!("Resolving profile `{}`", name);
+
+		let path = profiles.get(name).ok_or_else(|| {
+			match suggest_profile_name(name, profiles.keys().map(String::as_str)) {
+				Some(suggestion) => eyre!(
+					"No profile found for name {}. Did you mean `{}`?",
+					name,
+					suggestion
+				),
+				None => eyre!("No profile found for name {}", name),
+			}
+		})?;
 
 		let mut profile = Profile::from_file(path)?;
 		let name = name.to_string();
@@ -593,13 +1295,33 @@ pub fn resolve_profile(
 
 #[cfg(test)]
 mod tests {
-	use std::collections::HashMap;
-
 	use super::*;
-	use crate::profile::hook::Hook;
-	use crate::profile::variables::Variables;
+	use crate::profile::variables::{PromptVariable, Variables};
+	use crate::profile::Priority;
 	use crate::profile::Profile;
-	use crate::profile::{MergeMode, Priority};
+
+	#[test]
+	fn edit_distance_values() {
+		assert_eq!(edit_distance("", ""), 0);
+		assert_eq!(edit_distance("windows", "windows"), 0);
+		assert_eq!(edit_distance("windwos", "windows"), 1);
+		assert_eq!(edit_distance("acrh", "arch"), 1);
+		assert_eq!(edit_distance("ca", "abc"), 3);
+		assert_eq!(edit_distance("Arch", "arch"), 0);
+		assert_eq!(edit_distance("kitten", "sitting"), 3);
+		assert_eq!(edit_distance("", "abc"), 3);
+	}
+
+	#[test]
+	fn profile_name_suggestion() {
+		let names = ["windows", "arch", "macos", "base"];
+
+		assert_eq!(suggest_profile_name("windwos", names), Some("windows"));
+		assert_eq!(suggest_profile_name("acrh", names), Some("arch"));
+		assert_eq!(suggest_profile_name("arc", names), Some("arch"));
+		assert_eq!(suggest_profile_name("mac", names), None);
+		assert_eq!(suggest_profile_name("linux", names), None);
+	}
 
 	#[test]
 	fn priority_order() {
@@ -610,9 +1332,131 @@ mod tests {
 		assert!(Priority::new(2) > Priority::new(1));
 	}
 
+	#[test]
+	fn layered_variables_shadowed() {
+		crate::tests::setup_test_env();
+
+		let mut builder = LayeredProfile::build();
+		for (name, editor) in [("top", "nvim"), ("middle", "vim"), ("base", "nano")] {
+			builder.add(
+				String::from(name),
+				Profile {
+					variables: Some(Variables::from_items([("editor", editor)])),
+					..Default::default()
+				},
+			);
+		}
+		builder.add(
+			String::from("other"),
+			Profile {
+				variables: Some(Variables::from_items([("shell", "zsh")])),
+				..Default::default()
+			},
+		);
+
+		let profile = builder.finish();
+		let variables = profile.variables();
+
+		assert_eq!(variables.var("editor"), Some("nvim"));
+		assert_eq!(
+			variables.shadowed.get("editor"),
+			Some(&vec![(1, String::from("vim")), (2, String::from("nano"))])
+		);
+		assert_eq!(variables.var("shell"), Some("zsh"));
+		assert!(!variables.shadowed.contains_key("shell"));
+	}
+
+	#[test]
+	#[cfg(feature = "io")]
+	fn layered_variable_files() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = tempfile::tempdir()?;
+		let file = dir.path().join("editor");
+		std::fs::write(&file, "nvim\n")?;
+		let reference = format!("from:file:{}", file.display());
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("base"),
+			Profile {
+				variables: Some(Variables::from_items([
+					("editor", reference.as_str()),
+					("visual", reference.as_str()),
+					("shell", "zsh"),
+				])),
+				..Default::default()
+			},
+		);
+
+		let mut profile = builder.finish();
+		profile.resolve_variables(&VariableProviders::default())?;
+
+		assert_eq!(profile.variables().var("editor"), Some("nvim"));
+		assert_eq!(profile.variable_files().collect::<Vec<_>>(), [&file]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn layered_prompt_variables() {
+		crate::tests::setup_test_env();
+
+		let prompt = |name: &str, default: &str| PromptVariable {
+			name: String::from(name),
+			default: Some(String::from(default)),
+			..Default::default()
+		};
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("top"),
+			Profile {
+				variables: Some(Variables::from_items([("editor", "nvim")])),
+				prompt_variables: vec![prompt("email", "top@example.com")],
+				..Default::default()
+			},
+		);
+		builder.add(
+			String::from("base"),
+			Profile {
+				prompt_variables: vec![
+					prompt("email", "base@example.com"),
+					prompt("editor", "vim"),
+				],
+				..Default::default()
+			},
+		);
+
+		let mut profile = builder.finish();
+		assert_eq!(
+			profile.prompt_variables().cloned().collect::<Vec<_>>(),
+			vec![prompt("email", "top@example.com"), prompt("editor", "vim")]
+		);
+
+		profile.add_fallback_variables(
+			String::from("answers"),
+			Variables::from_items([("email", "me@example.com"), ("editor", "nano")]),
+		);
+
+		let variables = profile.variables();
+		assert_eq!(variables.var("email"), Some("me@example.com"));
+		assert_eq!(variables.inner.get("email").map(|(idx, _)| *idx), Some(2));
+		assert_eq!(variables.var("editor"), Some("nvim"));
+		assert_eq!(
+			variables.shadowed.get("editor"),
+			Some(&vec![(2, String::from("nano"))])
+		);
+	}
+
 	#[test]
 	#[cfg(feature = "profile-json")]
 	fn profile_serde() {
+		use std::collections::HashMap;
+
+		use crate::profile::hook::Hook;
+		use crate::profile::MergeMode;
+
 		crate::tests::setup_test_env();
 
 		let mut profile_vars = HashMap::new();
@@ -624,15 +1468,25 @@ mod tests {
 		dotfile_vars.insert(String::from("USERNAME"), String::from("demo"));
 
 		let profile = Profile {
+			version: None,
 			extends: Vec::new(),
 			aliases: vec![],
+			description: Some(String::from("Test profile")),
 			variables: Some(Variables {
 				inner: profile_vars,
 			}),
+			prompt_variables: Vec::new(),
 			transformers: Vec::new(),
+			formatters: Vec::new(),
+			comment_prefixes: HashMap::new(),
+			encryption: None,
+			secret_namespace: None,
 			target: Some(PathBuf::from("/home/demo/.config")),
+			source_symlinks: None,
+			create_parents: None,
 			pre_hooks: vec![Hook::new("echo \"Foo\"")],
 			post_hooks: vec![Hook::new("profiles/test.sh")],
+			assertions: vec![Assertion::FileExists(PathBuf::from("init.vim"))],
 			dotfiles: vec![
 				Dotfile {
 					path: PathBuf::from("init.vim.ubuntu"),
@@ -641,8 +1495,20 @@ mod tests {
 					priority: Some(Priority::new(2)),
 					variables: None,
 					transformers: Vec::new(),
+					transform: Vec::new(),
 					merge: Some(MergeMode::Overwrite),
 					template: None,
+					encrypted: None,
+					max_depth: None,
+					max_file_size: None,
+					on_limit: None,
+					preserve_mtime: None,
+					dir_mode: None,
+					create_parents: None,
+					force: None,
+					when: None,
+					instances: Box::default(),
+					for_each: None,
 				},
 				Dotfile {
 					path: PathBuf::from(".bashrc"),
@@ -653,8 +1519,20 @@ mod tests {
 						inner: dotfile_vars,
 					}),
 					transformers: Vec::new(),
+					transform: Vec::new(),
 					merge: Some(MergeMode::Overwrite),
 					template: Some(false),
+					encrypted: None,
+					max_depth: None,
+					max_file_size: None,
+					on_limit: None,
+					preserve_mtime: None,
+					dir_mode: None,
+					create_parents: None,
+					force: None,
+					when: None,
+					instances: Box::default(),
+					for_each: None,
 				},
 			],
 			symlinks: vec![],
@@ -666,4 +1544,93 @@ mod tests {
 
 		assert_eq!(parsed, profile);
 	}
+
+	#[test]
+	#[cfg(feature = "profile-schema")]
+	fn profile_json_schema() {
+		crate::tests::setup_test_env();
+
+		let schema = Profile::json_schema();
+		let properties = schema["properties"]
+			.as_object()
+			.expect("Schema to have properties");
+
+		assert!(properties.contains_key("dotfiles"));
+		assert!(properties.contains_key("links"));
+		assert!(!properties.contains_key("symlinks"));
+		assert_eq!(
+			schema["additionalProperties"],
+			serde_json::Value::Bool(false)
+		);
+	}
+
+	#[test]
+	#[cfg(all(feature = "io", feature = "profile-yaml"))]
+	fn profile_yaml_parse_error() {
+		crate::tests::setup_test_env();
+
+		let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+		let path = dir.path().join("demo.yaml");
+		std::fs::write(
+			&path,
+			"target: /tmp\ndotfiles:\n  - path: a\n    merge: Aks\n",
+		)
+		.expect("Failed to write profile");
+
+		let err = format!(
+			"{:?}",
+			Profile::from_file(&path).expect_err("Profile to be invalid")
+		);
+
+		assert!(err.contains("demo.yaml:4:12"));
+		assert!(err.contains("dotfiles[0].merge: unknown variant `Aks`"));
+	}
+
+	#[test]
+	#[cfg(feature = "profile-json")]
+	fn profile_json_parse_error() {
+		crate::tests::setup_test_env();
+
+		let err = format!(
+			"{:?}",
+			Profile::parse("demo.json", "{\n  \"dotfles\": []\n}\n")
+				.expect_err("Profile to be invalid")
+		);
+
+		assert!(err.contains("demo.json:2:"));
+		assert!(err.contains("unknown field `dotfles`"));
+		assert!(err.contains("did you mean `dotfiles`?"));
+	}
+
+	#[test]
+	#[cfg(feature = "profile-yaml")]
+	fn render_parsed_profile() -> Result<()> {
+		use crate::template::source::Source;
+		use crate::template::Template;
+
+		crate::tests::setup_test_env();
+
+		let profile = Profile::parse(
+			"demo.yaml",
+			"variables:\n  EDITOR: nvim\ndotfiles:\n  - path: bashrc\n    variables:\n      PS1: '> '\n",
+		)?;
+
+		let mut builder = LayeredProfile::build();
+		builder.add(String::from("demo"), profile);
+		let profile = builder.finish();
+
+		let dotfile = profile
+			.dotfiles()
+			.next()
+			.expect("Profile to have a dotfile");
+		let template =
+			Template::parse(Source::anonymous("export EDITOR={{EDITOR}} PS1='{{PS1}}'"))?;
+
+		assert_eq!(
+			template.resolve(Some(profile.variables()), dotfile.variables.as_ref())?,
+			"export EDITOR=nvim PS1='> '"
+		);
+
+		Ok(())
+	}
 }
@@ -0,0 +1,263 @@
+//! Providers which supply the values of variables from external sources.
+//!
+//! The value of a variable can reference a provider with the syntax
+//! `from:<provider>:<argument>` (e.g. `from:env:GITHUB_TOKEN`). Once the
+//! profile is resolved, such values are replaced with the value returned by the
+//! [`VariableProvider`] registered under the name `<provider>` (see
+//! [`LayeredProfile::resolve_variables`](`crate::profile::LayeredProfile::resolve_variables`)).
+//!
+//! The following providers are registered by default:
+//!
+//! - `env`: Value of the environment variable with the given name.
+//! - `file`: Content of the file at the given path (`~` and environment
+//!   variables are expanded).
+//! - `command`: Standard output of the given command, which is executed by the
+//!   native shell.
+//!
+//! Library users can register their own providers (e.g. for a secret store)
+//! with [`VariableProviders::register`]. Secrets of a
+//! [`SecretBackend`](`crate::profile::secret::SecretBackend`) are provided by a
+//! [`SecretProvider`](`crate::profile::secret::SecretProvider`), which is
+//! usually registered under `secret`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+
+use crate::profile::hook::Hook;
+
+/// Prefix of variable values which reference a provider.
+pub const PROVIDER_PREFIX: &str = "from:";
+
+/// Supplies the value of a variable.
+pub trait VariableProvider {
+	/// Returns the value for the given `argument` (the part after
+	/// `from:<provider>:`).
+	///
+	/// # Errors
+	///
+	/// An error is returned if no value could be retrieved.
+	fn provide(&self, argument: &str) -> Result<String>;
+
+	/// Returns the path of the file from which the value for `argument` is
+	/// read, if the provider reads one.
+	///
+	/// This allows to watch the file for changes (e.g. in `watch` mode).
+	fn source_file(&self, _argument: &str) -> Option<PathBuf> {
+		None
+	}
+}
+
+impl<F> VariableProvider for F
+where
+	F: Fn(&str) -> Result<String>,
+{
+	fn provide(&self, argument: &str) -> Result<String> {
+		self(argument)
+	}
+}
+
+/// Provides the value of an environment variable.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EnvProvider;
+
+impl VariableProvider for EnvProvider {
+	fn provide(&self, argument: &str) -> Result<String> {
+		std::env::var(argument)
+			.wrap_err_with(|| format!("Failed to read environment variable `{argument}`"))
+	}
+}
+
+/// Provides the content of a file.
+///
+/// A single trailing line break is removed from the content.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileProvider;
+
+impl VariableProvider for FileProvider {
+	fn provide(&self, argument: &str) -> Result<String> {
+		let path = shellexpand::full(argument)?;
+		let content = std::fs::read_to_string(path.as_ref())
+			.wrap_err_with(|| format!("Failed to read {path}"))?;
+
+		Ok(trim_line_break(content))
+	}
+
+	fn source_file(&self, argument: &str) -> Option<PathBuf> {
+		shellexpand::full(argument)
+			.ok()
+			.map(|path| PathBuf::from(path.as_ref()))
+	}
+}
+
+/// Provides the standard output of a command.
+///
+/// The command is executed by the native shell in the current working
+/// directory. A single trailing line break is removed from the output.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CommandProvider;
+
+impl VariableProvider for CommandProvider {
+	fn provide(&self, argument: &str) -> Result<String> {
+		let output = Hook::new(argument)
+			.output(Path::new("."))
+			.wrap_err_with(|| format!("Failed to execute `{argument}`"))?;
+
+		Ok(trim_line_break(output))
+	}
+}
+
+/// Removes a single trailing line break from `value`.
+fn trim_line_break(mut value: String) -> String {
+	if value.ends_with('\n') {
+		value.pop();
+
+		if value.ends_with('\r') {
+			value.pop();
+		}
+	}
+
+	value
+}
+
+/// A set of [`VariableProvider`]s which are identified by their name.
+pub struct VariableProviders {
+	/// The registered providers by their name.
+	providers: BTreeMap<String, Box<dyn VariableProvider>>,
+}
+
+impl VariableProviders {
+	/// Creates a new set without any providers.
+	pub fn empty() -> Self {
+		Self {
+			providers: BTreeMap::new(),
+		}
+	}
+
+	/// Registers `provider` under `name`.
+	///
+	/// A previously registered provider with the same name is replaced.
+	pub fn register<S: Into<String>, P: VariableProvider + 'static>(
+		&mut self,
+		name: S,
+		provider: P,
+	) -> &mut Self {
+		self.providers.insert(name.into(), Box::new(provider));
+		self
+	}
+
+	/// Returns the names of all registered providers.
+	pub fn names(&self) -> impl Iterator<Item = &str> {
+		self.providers.keys().map(String::as_str)
+	}
+
+	/// Resolves the variable `value`.
+	///
+	/// Returns `None` if the value does not reference a provider.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the referenced provider is not registered or if
+	/// it failed to provide a value.
+	pub fn resolve(&self, value: &str) -> Result<Option<String>> {
+		let Some(reference) = value.strip_prefix(PROVIDER_PREFIX) else {
+			return Ok(None);
+		};
+
+		let (name, argument) = reference
+			.split_once(':')
+			.ok_or_else(|| eyre!("Expected `{PROVIDER_PREFIX}<provider>:<argument>`"))?;
+
+		let provider = self.providers.get(name).ok_or_else(|| {
+			eyre!(
+				"Unknown variable provider `{name}` (available: {})",
+				self.names().collect::<Vec<_>>().join(", ")
+			)
+		})?;
+
+		provider.provide(argument).map(Some)
+	}
+
+	/// Returns the path of the file from which the variable `value` is read
+	/// (see [`VariableProvider::source_file`]).
+	///
+	/// Returns `None` if the value does not reference a registered provider or
+	/// if the provider does not read a file.
+	pub fn source_file(&self, value: &str) -> Option<PathBuf> {
+		let (name, argument) = value.strip_prefix(PROVIDER_PREFIX)?.split_once(':')?;
+
+		self.providers.get(name)?.source_file(argument)
+	}
+}
+
+impl Default for VariableProviders {
+	/// Creates a new set with the `env`, `file` and `command` providers.
+	fn default() -> Self {
+		let mut providers = Self::empty();
+		providers
+			.register("env", EnvProvider)
+			.register("file", FileProvider)
+			.register("command", CommandProvider);
+		providers
+	}
+}
+
+impl fmt::Debug for VariableProviders {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_set().entries(self.names()).finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn resolve_providers() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = tempfile::tempdir()?;
+		let file = dir.path().join("secret");
+		std::fs::write(&file, "file value\n")?;
+		std::env::set_var("PUNKTF_TEST_PROVIDER", "env value");
+
+		let mut providers = VariableProviders::default();
+		providers.register("upper", |argument: &str| Ok(argument.to_uppercase()));
+
+		assert_eq!(providers.resolve("plain value")?, None);
+		assert_eq!(
+			providers
+				.resolve("from:env:PUNKTF_TEST_PROVIDER")?
+				.as_deref(),
+			Some("env value")
+		);
+		assert_eq!(
+			providers
+				.resolve(&format!("from:file:{}", file.display()))?
+				.as_deref(),
+			Some("file value")
+		);
+		assert_eq!(providers.resolve("from:upper:abc")?.as_deref(), Some("ABC"));
+		assert!(providers.resolve("from:unknown:abc").is_err());
+		assert!(providers.resolve("from:env").is_err());
+
+		assert_eq!(
+			providers.source_file(&format!("from:file:{}", file.display())),
+			Some(file)
+		);
+		assert_eq!(providers.source_file("from:env:PUNKTF_TEST_PROVIDER"), None);
+		assert_eq!(providers.source_file("from:unknown:abc"), None);
+		assert_eq!(providers.source_file("plain value"), None);
+
+		#[cfg(unix)]
+		assert_eq!(
+			providers.resolve("from:command:echo a:b")?.as_deref(),
+			Some("a:b")
+		);
+
+		Ok(())
+	}
+}
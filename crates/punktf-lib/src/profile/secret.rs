@@ -0,0 +1,260 @@
+//! Secrets which are stored outside of the source and referenced by the
+//! variables of profiles.
+//!
+//! Variables reference a secret with `from:secret:<name>` (see
+//! [`provider`](`crate::profile::provider`)), which is looked up in the
+//! [namespace](`crate::profile::Profile::secret_namespace`) of the profile of a
+//! [`SecretBackend`]. The [`FileBackend`] stores each secret in its own file.
+
+use std::fmt;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{eyre, Context};
+use color_eyre::Result;
+
+use crate::profile::provider::VariableProvider;
+
+/// Namespace of the secrets of profiles which do not set one.
+pub const DEFAULT_NAMESPACE: &str = "default";
+
+/// Stores secrets by their name.
+pub trait SecretBackend: fmt::Debug {
+	/// Returns the value of the secret `name`, or `None` if it is not set.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the secret could not be read.
+	fn get(&self, name: &str) -> Result<Option<String>>;
+
+	/// Sets the secret `name` to `value`, replacing a previous value.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the secret could not be written.
+	fn set(&self, name: &str, value: &str) -> Result<()>;
+
+	/// Removes the secret `name`.
+	///
+	/// Returns `false` if the secret was not set.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the secret could not be removed.
+	fn remove(&self, name: &str) -> Result<bool>;
+
+	/// Returns the names of all secrets sorted alphabetically.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the secrets could not be listed.
+	fn list(&self) -> Result<Vec<String>>;
+}
+
+/// Checks that `name` is a valid name of a secret or namespace.
+///
+/// Names consist of ASCII letters, digits, `_`, `-` and `.` and do not start
+/// with a `.`, so they can be used as file names.
+///
+/// # Errors
+///
+/// An error is returned if the name is invalid.
+pub fn validate_name(name: &str) -> Result<()> {
+	let valid = !name.is_empty()
+		&& !name.starts_with('.')
+		&& name
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+
+	if valid {
+		Ok(())
+	} else {
+		Err(eyre!(
+			"Invalid name `{name}`, only ASCII letters, digits, `_`, `-` and `.` are allowed"
+		))
+	}
+}
+
+/// Stores each secret in a file named after it inside of a directory.
+///
+/// On unix, the directory is only accessible and the files are only readable
+/// by the owner.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileBackend {
+	/// Directory in which the secrets are stored.
+	dir: PathBuf,
+}
+
+impl FileBackend {
+	/// Creates a backend which stores the secrets in `dir`.
+	pub const fn new(dir: PathBuf) -> Self {
+		Self { dir }
+	}
+
+	/// Creates a backend which stores the secrets of `namespace` in a
+	/// directory of the same name inside of `root`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the namespace is no valid name (see
+	/// [`validate_name`]).
+	pub fn namespace(root: &Path, namespace: &str) -> Result<Self> {
+		validate_name(namespace)?;
+
+		Ok(Self::new(root.join(namespace)))
+	}
+
+	/// Returns the directory in which the secrets are stored.
+	pub fn dir(&self) -> &Path {
+		&self.dir
+	}
+
+	/// Returns the path of the file of the secret `name`.
+	fn path(&self, name: &str) -> Result<PathBuf> {
+		validate_name(name)?;
+
+		Ok(self.dir.join(name))
+	}
+}
+
+impl SecretBackend for FileBackend {
+	fn get(&self, name: &str) -> Result<Option<String>> {
+		let path = self.path(name)?;
+
+		match std::fs::read_to_string(&path) {
+			Ok(value) => Ok(Some(value)),
+			Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+			Err(err) => Err(err).wrap_err_with(|| format!("Failed to read {}", path.display())),
+		}
+	}
+
+	fn set(&self, name: &str, value: &str) -> Result<()> {
+		let path = self.path(name)?;
+
+		std::fs::create_dir_all(&self.dir)
+			.wrap_err_with(|| format!("Failed to create {}", self.dir.display()))?;
+
+		#[cfg(unix)]
+		{
+			use std::fs::{OpenOptions, Permissions};
+			use std::io::Write as _;
+			use std::os::unix::fs::{OpenOptionsExt as _, PermissionsExt as _};
+
+			std::fs::set_permissions(&self.dir, Permissions::from_mode(0o700))?;
+
+			OpenOptions::new()
+				.write(true)
+				.create(true)
+				.truncate(true)
+				.mode(0o600)
+				.open(&path)
+				.and_then(|mut file| file.write_all(value.as_bytes()))
+				.wrap_err_with(|| format!("Failed to write {}", path.display()))
+		}
+
+		#[cfg(not(unix))]
+		std::fs::write(&path, value).wrap_err_with(|| format!("Failed to write {}", path.display()))
+	}
+
+	fn remove(&self, name: &str) -> Result<bool> {
+		let path = self.path(name)?;
+
+		match std::fs::remove_file(&path) {
+			Ok(()) => Ok(true),
+			Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+			Err(err) => Err(err).wrap_err_with(|| format!("Failed to remove {}", path.display())),
+		}
+	}
+
+	fn list(&self) -> Result<Vec<String>> {
+		let entries = match std::fs::read_dir(&self.dir) {
+			Ok(entries) => entries,
+			Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+			Err(err) => {
+				return Err(err).wrap_err_with(|| format!("Failed to read {}", self.dir.display()))
+			}
+		};
+
+		let mut names = Vec::new();
+
+		for entry in entries {
+			let entry = entry?;
+
+			if !entry.file_type()?.is_file() {
+				continue;
+			}
+
+			if let Some(name) = entry.file_name().to_str() {
+				if validate_name(name).is_ok() {
+					names.push(name.to_owned());
+				}
+			}
+		}
+
+		names.sort();
+
+		Ok(names)
+	}
+}
+
+/// Provides the values of `from:secret:<name>` variables from a
+/// [`SecretBackend`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecretProvider<B>(pub B);
+
+impl<B: SecretBackend> VariableProvider for SecretProvider<B> {
+	fn provide(&self, argument: &str) -> Result<String> {
+		self.0
+			.get(argument)?
+			.ok_or_else(|| eyre!("Secret `{argument}` is not set"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn file_backend() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = tempfile::tempdir()?;
+		let backend = FileBackend::namespace(dir.path(), "work")?;
+
+		assert_eq!(backend.list()?, Vec::<String>::new());
+		assert_eq!(backend.get("token")?, None);
+
+		backend.set("token", "abc")?;
+		backend.set("api.key", "def\n")?;
+		backend.set("token", "ghi")?;
+
+		assert_eq!(backend.list()?, vec!["api.key", "token"]);
+		assert_eq!(backend.get("token")?.as_deref(), Some("ghi"));
+		assert_eq!(backend.get("api.key")?.as_deref(), Some("def\n"));
+		assert_eq!(
+			SecretProvider(backend.clone()).provide("token")?,
+			String::from("ghi")
+		);
+		assert!(SecretProvider(backend.clone()).provide("missing").is_err());
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt as _;
+
+			let mode = std::fs::metadata(backend.dir().join("token"))?
+				.permissions()
+				.mode();
+			assert_eq!(mode & 0o777, 0o600);
+		}
+
+		assert!(backend.remove("token")?);
+		assert!(!backend.remove("token")?);
+		assert_eq!(backend.list()?, vec!["api.key"]);
+
+		assert!(backend.get("../escape").is_err());
+		assert!(backend.set(".hidden", "").is_err());
+		assert!(FileBackend::namespace(dir.path(), "a/b").is_err());
+
+		Ok(())
+	}
+}
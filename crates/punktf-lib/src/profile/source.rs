@@ -13,9 +13,81 @@
 //! + dotfiles/
 //!   ...
 //! ```
+//!
+//! Multiple source directories can be layered on top of each other (e.g. a
+//! shared team repository and a personal one). In this case the `profiles` and
+//! `dotfiles` trees of all sources are overlaid, where the first source has the
+//! highest precedence. If the same item exists in multiple sources, the
+//! [`ConflictPolicy`] decides which one is used.
 
-use color_eyre::eyre::Context as _;
+use color_eyre::eyre::{eyre, Context as _};
+use std::fmt;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::profile::Priority;
+use crate::template::Includes;
+
+/// Defines what happens if the same relative dotfile exists in more than one
+/// [layer](`PunktfSource::layers`) of a source.
+///
+/// Directories which exist in multiple layers are never a conflict, as their
+/// contents are overlaid.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ConflictPolicy {
+	/// The item is not deployed and reported as an error.
+	Error,
+
+	/// The item of the layer with the highest precedence (given first) is used.
+	#[default]
+	FirstWins,
+
+	/// The item of the layer with the lowest precedence (given last) is used.
+	LastWins,
+
+	/// The item of the layer with the highest
+	/// [`priority`](`PunktfSource::priority`) is used. If multiple layers
+	/// share the highest priority, the one with the highest precedence is
+	/// used.
+	Priority,
+}
+
+impl ConflictPolicy {
+	/// All available policies.
+	pub const ALL: [Self; 4] = [Self::Error, Self::FirstWins, Self::LastWins, Self::Priority];
+
+	/// Returns the name of the policy as used on the command line.
+	pub const fn as_str(&self) -> &'static str {
+		match self {
+			Self::Error => "error",
+			Self::FirstWins => "first-wins",
+			Self::LastWins => "last-wins",
+			Self::Priority => "priority",
+		}
+	}
+}
+
+impl fmt::Display for ConflictPolicy {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+impl FromStr for ConflictPolicy {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::ALL
+			.into_iter()
+			.find(|policy| policy.as_str().eq_ignore_ascii_case(s))
+			.ok_or_else(|| {
+				format!(
+					"Invalid conflict policy `{s}` (possible values: {})",
+					Self::ALL.map(|p| p.as_str()).join(", ")
+				)
+			})
+	}
+}
 
 /// This struct represents the source directory used by `punktf`. The source
 /// directory is the central repository used to store
@@ -41,6 +113,20 @@ pub struct PunktfSource {
 
 	/// The absolute path to the `dotfiles` directory.
 	pub dotfiles: PathBuf,
+
+	/// Additional sources which are overlaid below this one.
+	///
+	/// They are ordered by precedence, from highest to lowest. Items of this
+	/// source always take precedence over the items of the overlays.
+	pub overlays: Vec<PunktfSource>,
+
+	/// Priority of this source, used by [`ConflictPolicy::Priority`].
+	pub priority: Priority,
+
+	/// Defines what happens if the same dotfile exists in multiple layers.
+	///
+	/// Only the policy of the primary source is used.
+	pub conflict_policy: ConflictPolicy,
 }
 
 impl PunktfSource {
@@ -104,9 +190,36 @@ impl PunktfSource {
 			root: source,
 			profiles,
 			dotfiles,
+			overlays: Vec::new(),
+			priority: Priority::default(),
+			conflict_policy: ConflictPolicy::default(),
 		})
 	}
 
+	/// Creates a layered instance from multiple `roots`.
+	///
+	/// The first root is used as the primary source and has the highest
+	/// precedence. All other roots are added as
+	/// [`overlays`](`PunktfSource::overlays`) in the given order.
+	/// Each root is checked as described in [`PunktfSource::from_root`].
+	///
+	/// # Errors
+	///
+	/// If no root is given or any of the checks fail an error will be returned.
+	pub fn from_roots(roots: impl IntoIterator<Item = PathBuf>) -> color_eyre::Result<Self> {
+		let mut roots = roots.into_iter();
+
+		let mut source = Self::from_root(
+			roots
+				.next()
+				.ok_or_else(|| eyre!("At least one source directory is required"))?,
+		)?;
+
+		source.overlays = roots.map(Self::from_root).collect::<Result<_, _>>()?;
+
+		Ok(source)
+	}
+
 	/// Returns the absolute path for the `root` directory.
 	pub fn root(&self) -> &Path {
 		&self.root
@@ -121,4 +234,49 @@ impl PunktfSource {
 	pub fn dotfiles(&self) -> &Path {
 		&self.dotfiles
 	}
+
+	/// Returns an iterator over this source and all its
+	/// [`overlays`](`PunktfSource::overlays`) in order of precedence.
+	pub fn layers(&self) -> impl Iterator<Item = &Self> {
+		std::iter::once(self).chain(self.overlays.iter())
+	}
+
+	/// Returns the absolute path of a `relative` path inside the `dotfiles`
+	/// directory.
+	///
+	/// The path of the first layer in which it exists is returned. If it does
+	/// not exist in any of the layers, the path inside the primary `dotfiles`
+	/// directory is returned.
+	pub fn dotfile_path(&self, relative: &Path) -> PathBuf {
+		self.layers()
+			.map(|layer| layer.dotfiles.join(relative))
+			.find(|path| path.exists())
+			.unwrap_or_else(|| self.dotfiles.join(relative))
+	}
+
+	/// Discovers all profiles in the `root/profiles` directories of all layers
+	/// and returns their names and aliases sorted alphabetically.
+	///
+	/// # Errors
+	///
+	/// If the `profiles` directory could not be read or a name is used by
+	/// multiple profiles an error will be returned.
+	pub fn profile_names(&self) -> color_eyre::Result<Vec<String>> {
+		let mut names: Vec<String> = crate::profile::collect_profile_names(self)?
+			.into_keys()
+			.collect();
+		names.sort();
+
+		Ok(names)
+	}
+}
+
+impl Includes for PunktfSource {
+	/// Reads the dotfile `name` (see [`PunktfSource::dotfile_path`]).
+	fn load(&self, name: &str) -> color_eyre::Result<String> {
+		let path = self.dotfile_path(Path::new(name));
+
+		std::fs::read_to_string(&path)
+			.wrap_err_with(|| format!("Failed to read dotfile {}", path.display()))
+	}
 }
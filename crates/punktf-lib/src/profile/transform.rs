@@ -30,6 +30,7 @@ pub trait Transform {
 /// These can be added to a [`Profile`](`crate::profile::Profile`) or a
 /// [`Dotfile`](`crate::profile::dotfile::Dotfile`) to modify the text content.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub enum ContentTransformer {
 	/// Transformer which replaces line termination characters with either unix
@@ -54,6 +55,7 @@ impl fmt::Display for ContentTransformer {
 /// Transformer which replaces line termination characters with either unix
 /// style (`\n`) or windows style (`\r\b`).
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 pub enum LineTerminator {
 	/// Replaces all occurrences of `\r\n` with `\n` (unix style).
 	LF,
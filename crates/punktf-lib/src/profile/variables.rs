@@ -14,6 +14,7 @@ pub trait Vars {
 
 /// User defined variables
 #[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 pub struct Variables {
 	/// User defined variables with a name and value.
 	#[serde(flatten)]
@@ -46,3 +47,29 @@ impl Variables {
 		Self { inner }
 	}
 }
+
+/// A variable whose value is asked from the user on the first deployment.
+///
+/// The answers are stored machine-locally and reused for all subsequent
+/// deployments. Variables which are already defined (e.g. by a profile or on
+/// the command line) are never asked for.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+#[serde(deny_unknown_fields)]
+pub struct PromptVariable {
+	/// Name of the variable.
+	pub name: String,
+
+	/// Description which is shown when asking for the value.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub description: Option<String>,
+
+	/// Value which is suggested when asking and used if the user can not be
+	/// asked (e.g. in non-interactive sessions).
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub default: Option<String>,
+
+	/// Hides the input while asking for the value and the value in outputs.
+	#[serde(default)]
+	pub secret: bool,
+}
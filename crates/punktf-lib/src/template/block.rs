@@ -2,7 +2,10 @@
 
 use std::fmt;
 
+use serde::{Deserialize, Serialize};
+
 use super::span::{ByteSpan, Spanned};
+use crate::expr::{Expr, Filter, Operand, Value};
 
 /// A parsed instruction from a template.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -13,10 +16,24 @@ pub enum BlockHint {
 	Comment,
 	/// Starts an escaped block
 	Escaped,
+	/// Starts a `Raw` block (including the closing `end`)
+	Raw,
 	/// Starts a `Variable` block
 	Var,
+	/// Starts a `Value` block
+	Value,
 	/// Starts a `Print` block
 	Print,
+	/// Starts a `Render` block
+	Render,
+	/// Starts an `Import` block
+	Import,
+	/// Starts a `Macro` block
+	MacroStart,
+	/// End a `Macro` block
+	MacroEnd,
+	/// Starts a `Call` block
+	Call,
 	/// Starts a `If` block
 	IfStart,
 	/// Continues an `If` block with an `ElIf` block
@@ -35,7 +52,7 @@ impl BlockHint {
 }
 
 /// A instruction that opens a new block.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum BlockKind {
 	/// A `Text` block, that contains text that is copied to the output.
 	Text,
@@ -43,12 +60,24 @@ pub enum BlockKind {
 	Comment,
 	/// An escaped block, that contains escaped text that is copied to the output.
 	Escaped(ByteSpan),
+	/// A `Raw` block, that contains text between `{{@raw}}` and `{{@end}}` that is copied to the output.
+	Raw(ByteSpan),
 	/// A `Variable` block, that contains a variable name that is replaced with its value.
 	Var(Var),
+	/// A `Value` block, that contains an expression (e.g. an inline if or a function) which is replaced with its value.
+	Value(ValueExpr),
 	/// A `Print` block, that contains text that is printed to the log.
 	Print(ByteSpan),
 	/// An `If` block, that contains a condition that is evaluated and compiles the block conditionally.
 	If(If),
+	/// A `Render` block, that contains the name of another template which is rendered in its place.
+	Render(Render),
+	/// An `Import` block, that contains the name of another template whose macros can be called.
+	Import(ByteSpan),
+	/// A `Macro` block, that defines a macro which is resolved by `Call` blocks.
+	Macro(Macro),
+	/// A `Call` block, that resolves a macro with the given arguments.
+	Call(Call),
 }
 
 impl BlockKind {
@@ -58,15 +87,21 @@ impl BlockKind {
 			BlockKind::Text => BlockHint::Text,
 			BlockKind::Comment => BlockHint::Comment,
 			BlockKind::Escaped(_) => BlockHint::Escaped,
+			BlockKind::Raw(_) => BlockHint::Raw,
 			BlockKind::Var(_) => BlockHint::Var,
+			BlockKind::Value(_) => BlockHint::Value,
 			BlockKind::Print(_) => BlockHint::Print,
 			BlockKind::If(_) => BlockHint::IfEnd,
+			BlockKind::Render(_) => BlockHint::Render,
+			BlockKind::Import(_) => BlockHint::Import,
+			BlockKind::Macro(_) => BlockHint::MacroStart,
+			BlockKind::Call(_) => BlockHint::Call,
 		}
 	}
 }
 
 /// A block can be a single construction or open up a multi-line block that contains sub-blocks.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Block {
 	/// The span of this block.
 	pub span: ByteSpan,
@@ -92,7 +127,7 @@ impl Block {
 }
 
 /// The different types of sources for variables values
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum VarEnv {
 	/// A variable that is defined by the system's environment.
 	Environment,
@@ -109,7 +144,7 @@ impl fmt::Display for VarEnv {
 }
 
 /// Defines a set of variables sources that can be used to resolve variables.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct VarEnvSet(pub [Option<VarEnv>; 3]);
 
 impl VarEnvSet {
@@ -159,7 +194,7 @@ impl fmt::Display for VarEnvSet {
 }
 
 /// A variable that is defined in the template.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Var {
 	/// The `VarEnvSet` for the variable
 	pub envs: VarEnvSet,
@@ -168,7 +203,7 @@ pub struct Var {
 }
 
 /// Defines an if block.
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct If {
 	/// The head of an if statement.
 	///
@@ -191,50 +226,59 @@ pub struct If {
 	pub end: ByteSpan,
 }
 
-/// The different types of if expression operations.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum IfOp {
-	/// Operand to check for equality.
-	Eq,
+/// Defines a macro.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Macro {
+	/// The name of the macro.
+	///
+	/// `{{@macro NAME(KEY, VALUE)}}`
+	pub name: ByteSpan,
+
+	/// The names of the parameters, which are available as variables in the
+	/// body.
+	pub params: Vec<ByteSpan>,
+
+	/// The blocks which are resolved when the macro is called.
+	pub body: Vec<Block>,
 
-	/// Operand to check for inequality.
-	NotEq,
+	/// The closing end statement.
+	///
+	/// `{{@end}}`
+	pub end: ByteSpan,
 }
 
-impl IfOp {
-	/// Evaluates an if expression.
-	pub fn eval(&self, lhs: &str, rhs: &str) -> bool {
-		match self {
-			Self::Eq => lhs == rhs,
-			Self::NotEq => lhs != rhs,
-		}
-	}
+/// Renders another template.
+///
+/// `{{@render "NAME" | nindent("4")}}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Render {
+	/// The name of the rendered template.
+	pub name: ByteSpan,
+
+	/// Filters which are applied to the output of the template (e.g. to
+	/// indent it).
+	pub filters: Vec<Filter<Var>>,
 }
 
-/// The different if expression types.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-pub enum IfExpr {
-	/// An if expression that compares two values.
-	Compare {
-		/// Left hand side of the compare operation.
-		var: Var,
-
-		/// Compare operand.
-		op: IfOp,
-
-		/// Right hand side of the compare operation.
-		other: ByteSpan,
-	},
-
-	/// An if expression that checks if a value is defined.
-	Exists {
-		/// Variable to check existence for.
-		var: Var,
-	},
-
-	/// An if expression that checks if a value is not defined.
-	NotExists {
-		/// Variable to check not existence for.
-		var: Var,
-	},
+/// Calls a macro.
+///
+/// `{{@call NAME("key", {{VAR}})}}`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Call {
+	/// The name of the called macro.
+	pub name: ByteSpan,
+
+	/// The arguments, which are either variable blocks or strings.
+	pub args: Vec<Operand<Var>>,
 }
+
+/// The expression of an if or elif block.
+///
+/// Variables are written as variable blocks (e.g. `{{OS}} == "linux"`), see
+/// [`expr`](`crate::expr`) for the syntax.
+pub type IfExpr = Expr<Var>;
+
+/// The expression of a value block.
+///
+/// `{{ {{OS}} == "windows" ? "pwsh" : upper({{SHELL}}) }}`
+pub type ValueExpr = Value<Var>;
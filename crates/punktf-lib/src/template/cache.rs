@@ -0,0 +1,211 @@
+//! Persistent cache of parsed templates.
+//!
+//! Parsing a template turns its content into [blocks](`super::block::Block`).
+//! The [`TemplateCache`] stores these blocks together with a hash of the
+//! content they were parsed from. As long as the content of a template does
+//! not change, parsing it again with the same cache uses the stored blocks
+//! instead (e.g. for repeated deployments and `watch`).
+//!
+//! Only templates which were parsed without errors are cached. Warnings of the
+//! parser are only emitted when a template is actually parsed.
+//!
+//! The cache is stored as a json file.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use color_eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use super::block::Block;
+use super::source::Source;
+use super::Template;
+use crate::visit::deploy::cache::ContentHash;
+
+/// Version of the cache file format.
+const VERSION: u32 = 1;
+
+/// Content of a cache file.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct CacheFile {
+	/// Version of the format.
+	version: u32,
+
+	/// Version of punktf which wrote the cache.
+	///
+	/// The blocks of other versions might differ, so the cache is only used by
+	/// the same version.
+	punktf: String,
+
+	/// The parsed blocks by the hash of the content of their template.
+	templates: BTreeMap<String, Vec<Block>>,
+}
+
+/// Parsed templates of previous invocations by the hash of their content.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct TemplateCache {
+	/// The parsed blocks by the hash of the content of their template.
+	templates: BTreeMap<String, Vec<Block>>,
+
+	/// Hashes of all templates which were parsed with the cache since it was
+	/// loaded.
+	used: BTreeSet<String>,
+
+	/// Amount of templates which were taken from the cache.
+	hits: usize,
+}
+
+impl TemplateCache {
+	/// Loads the cache from the file at `path`.
+	///
+	/// An empty cache is returned if the file does not exist, is invalid or
+	/// was written by another version.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file exists but could not be read.
+	pub fn load(path: &Path) -> io::Result<Self> {
+		let content = match fs::read_to_string(path) {
+			Ok(content) => content,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+			Err(err) => return Err(err),
+		};
+
+		match serde_json::from_str::<CacheFile>(&content) {
+			Ok(file) if file.version == VERSION && file.punktf == env!("CARGO_PKG_VERSION") => {
+				Ok(Self {
+					templates: file.templates,
+					..Self::default()
+				})
+			}
+			Ok(_) => {
+				tracing::debug!("Ignoring template cache of another version");
+				Ok(Self::default())
+			}
+			Err(err) => {
+				tracing::debug!("Ignoring invalid template cache: {err}");
+				Ok(Self::default())
+			}
+		}
+	}
+
+	/// Writes the cache to the file at `path`.
+	///
+	/// Only the templates which were parsed with the cache since it was loaded
+	/// are written, so templates which were changed or removed do not
+	/// accumulate. Missing parent directories are created.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file could not be written.
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+
+		let file = CacheFile {
+			version: VERSION,
+			punktf: String::from(env!("CARGO_PKG_VERSION")),
+			templates: self
+				.templates
+				.iter()
+				.filter(|(hash, _)| self.used.contains(*hash))
+				.map(|(hash, blocks)| (hash.clone(), blocks.clone()))
+				.collect(),
+		};
+
+		fs::write(path, serde_json::to_string(&file)?)
+	}
+
+	/// Parses the template `source` like [`Template::parse`].
+	///
+	/// The blocks are taken from the cache if a template with the same content
+	/// was parsed before. Otherwise the template is parsed and the cache is
+	/// updated.
+	///
+	/// # Errors
+	///
+	/// Bubbles up any error of [`Template::parse`].
+	pub fn parse<'a>(&mut self, source: Source<'a>) -> Result<Template<'a>> {
+		let hash = ContentHash::of(source.content().as_bytes()).to_string();
+
+		if let Some(blocks) = self.templates.get(&hash) {
+			tracing::trace!("Using cached blocks of template {}", source.origin());
+
+			let blocks = blocks.clone();
+			self.hits += 1;
+			self.used.insert(hash);
+
+			return Ok(Template { source, blocks });
+		}
+
+		let template = Template::parse(source)?;
+
+		self.templates.insert(hash.clone(), template.blocks.clone());
+		self.used.insert(hash);
+
+		Ok(template)
+	}
+
+	/// Returns the amount of templates which were taken from the cache since
+	/// it was loaded.
+	pub const fn hits(&self) -> usize {
+		self.hits
+	}
+
+	/// Returns the amount of cached templates.
+	pub fn len(&self) -> usize {
+		self.templates.len()
+	}
+
+	/// Checks if the cache has no templates.
+	pub fn is_empty(&self) -> bool {
+		self.templates.is_empty()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn parse_cached() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = tempfile::tempdir()?;
+		let path = dir.path().join("cache").join("templates.json");
+
+		let content = "{{@if {{OS}} matches \"^linux\"}}{{ {{EDITOR}} | upper }}{{@fi}}\n";
+
+		let mut cache = TemplateCache::load(&path)?;
+		assert!(cache.is_empty());
+
+		let parsed = cache.parse(Source::anonymous(content))?;
+		assert_eq!(cache.hits(), 0);
+		cache.save(&path)?;
+
+		let mut loaded = TemplateCache::load(&path)?;
+		assert_eq!(loaded.len(), 1);
+
+		let cached = loaded.parse(Source::anonymous(content))?;
+		assert_eq!(loaded.hits(), 1);
+		assert_eq!(cached.blocks, parsed.blocks);
+
+		loaded.parse(Source::anonymous("{{OS}}\n"))?;
+		assert_eq!(loaded.hits(), 1);
+		assert_eq!(loaded.len(), 2);
+
+		// Templates which were not parsed since loading are not written
+		let mut other = TemplateCache::load(&path)?;
+		other.parse(Source::anonymous("{{OS}}\n"))?;
+		other.save(&path)?;
+		assert_eq!(TemplateCache::load(&path)?.len(), 1);
+
+		fs::write(&path, "{\"version\": 0}")?;
+		assert!(TemplateCache::load(&path)?.is_empty());
+
+		Ok(())
+	}
+}
@@ -78,7 +78,7 @@ impl Diagnostic {
 	}
 
 	/// Formats the diagnostic with [`DiagnosticFormatter`] and emits it with
-	/// the crate [`log`].
+	/// the crate [`tracing`].
 	pub fn emit(&self, source: &'_ Source<'_>) {
 		let mut fmt = DiagnosticFormatter::new(source, &self.msg);
 
@@ -102,9 +102,9 @@ impl Diagnostic {
 
 		match self.level {
 			DiagnosticLevel::Error => {
-				log::error!("{}{} {}", "error".bright_red().bold(), ':'.bold(), out)
+				tracing::error!("{}{} {}", "error".bright_red().bold(), ':'.bold(), out)
 			}
-			DiagnosticLevel::Warning => log::warn!("{}", out),
+			DiagnosticLevel::Warning => tracing::warn!("{}", out),
 		};
 	}
 
@@ -112,6 +112,16 @@ impl Diagnostic {
 	pub const fn level(&self) -> &DiagnosticLevel {
 		&self.level
 	}
+
+	/// Returns the main message of this diagnostic.
+	pub fn message(&self) -> &str {
+		&self.msg
+	}
+
+	/// Returns the first primary span of this diagnostic.
+	pub fn primary_span(&self) -> Option<&ByteSpan> {
+		self.span.as_ref().and_then(|span| span.primary.first())
+	}
 }
 
 /// A builder for a [`Diagnostic`].
@@ -302,9 +312,9 @@ impl<'a> LineMap<'a> {
 	/// The search is done in this order:
 	///
 	/// 1) First searches the primary spans for the smallest location and
-	///       returns any if found.
+	///    returns any if found.
 	/// 2) After that it searches the label spans for the smallest location and
-	///       returns any if found.
+	///    returns any if found.
 	pub fn min_location(&self) -> Option<Location> {
 		self.primary_spans
 			.iter()
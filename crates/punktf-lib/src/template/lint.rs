@@ -0,0 +1,357 @@
+//! Static checks of [templates](`super::Template`) which find problems without
+//! resolving them (see [`Template::lint`](`super::Template::lint`)).
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+
+use color_eyre::eyre::{eyre, Result};
+
+use super::block::{Block, BlockKind, If, IfExpr, Macro, Var, VarEnv};
+use super::source::Location;
+use super::span::ByteSpan;
+use super::Template;
+use crate::expr::{Expr, Operand};
+
+/// Environment variables which are always defined while a template is
+/// resolved, as they default to values of the compiling system.
+const BUILTIN_ENV_VARS: [&str; 3] = [
+	"PUNKTF_TARGET_ARCH",
+	"PUNKTF_TARGET_OS",
+	"PUNKTF_TARGET_FAMILY",
+];
+
+/// Returns if the variable with the given name is defined in the given
+/// environment.
+type Defined<'a> = &'a dyn Fn(&str, VarEnv) -> bool;
+
+/// The kind of a [`Lint`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum LintKind {
+	/// A variable is used which is not defined in any of its environments.
+	UnknownVariable,
+
+	/// A branch of an `if` block can never be taken.
+	UnreachableBranch,
+}
+
+impl fmt::Display for LintKind {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::UnknownVariable => "unknown-variable",
+			Self::UnreachableBranch => "unreachable-branch",
+		})
+	}
+}
+
+/// A problem which was found in a template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lint {
+	/// The kind of the problem.
+	pub kind: LintKind,
+
+	/// Location of the block which causes the problem.
+	pub location: Location,
+
+	/// Description of the problem.
+	pub message: String,
+}
+
+impl fmt::Display for Lint {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{}: {} [{}]",
+			self.location.display(),
+			self.message,
+			self.kind
+		)
+	}
+}
+
+/// Checks the blocks of a template.
+pub(super) struct Linter<'a, 't> {
+	/// The checked template.
+	template: &'t Template<'t>,
+
+	/// Returns if a variable is defined in one of its environments. If not
+	/// set, variables are not checked.
+	defined: Option<Defined<'a>>,
+
+	/// Names of all variables which are only checked if they are defined
+	/// somewhere in the template, so they are optional.
+	optional: HashSet<&'t str>,
+
+	/// Names of the parameters of the macro which is checked.
+	params: Vec<&'t str>,
+
+	/// All found problems.
+	lints: Vec<Lint>,
+}
+
+impl fmt::Debug for Linter<'_, '_> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("Linter")
+			.field("template", &self.template)
+			.field("checks_vars", &self.defined.is_some())
+			.field("lints", &self.lints)
+			.finish_non_exhaustive()
+	}
+}
+
+impl<'a, 't> Linter<'a, 't> {
+	/// Creates a new linter for `template`.
+	///
+	/// If `defined` is given, all variables are checked to be defined in one
+	/// of their environments with it.
+	pub(super) fn new(template: &'t Template<'t>, defined: Option<Defined<'a>>) -> Self {
+		Self {
+			template,
+			defined,
+			optional: HashSet::new(),
+			params: Vec::new(),
+			lints: Vec::new(),
+		}
+	}
+
+	/// Checks all blocks of the template and returns the found problems
+	/// ordered by their location.
+	pub(super) fn lint(mut self) -> Vec<Lint> {
+		let template = self.template;
+
+		for_each_var(&template.blocks, &mut |var, checked| {
+			if checked {
+				self.optional.insert(&template.source[var.name]);
+			}
+		});
+
+		self.lint_blocks(&template.blocks);
+
+		self.lints.sort_by_key(|lint| lint.location);
+		self.lints
+	}
+
+	/// Adds a problem of `kind` for the block at `span`.
+	fn report(&mut self, kind: LintKind, span: ByteSpan, message: String) {
+		self.lints.push(Lint {
+			kind,
+			location: self.template.source.get_pos_location(span.low),
+			message,
+		});
+	}
+
+	/// Checks all `blocks` and the blocks nested in them.
+	fn lint_blocks(&mut self, blocks: &'t [Block]) {
+		for block in blocks {
+			self.lint_vars(block);
+
+			match &block.kind {
+				BlockKind::If(if_block) => self.lint_if(if_block),
+				BlockKind::Macro(Macro { params, body, .. }) => {
+					let outer = std::mem::replace(
+						&mut self.params,
+						params
+							.iter()
+							.map(|param| &self.template.source[param])
+							.collect(),
+					);
+
+					self.lint_blocks(body);
+					self.params = outer;
+				}
+				_ => {}
+			}
+		}
+	}
+
+	/// Reports all variables which are used directly by `block` (not by its
+	/// nested blocks) and which are not defined.
+	fn lint_vars(&mut self, block: &'t Block) {
+		let Some(defined) = self.defined else {
+			return;
+		};
+
+		let mut unknown = Vec::new();
+
+		visit_block_vars(block, &mut |var, _| {
+			let name = &self.template.source[var.name];
+
+			let known = self.params.contains(&name)
+				|| self.optional.contains(name)
+				|| var.envs.envs().any(|env| match env {
+					VarEnv::Environment if BUILTIN_ENV_VARS.contains(&name) => true,
+					env => defined(name, *env),
+				});
+
+			if !known {
+				unknown.push(*var);
+			}
+		});
+
+		for var in unknown {
+			self.report(
+				LintKind::UnknownVariable,
+				var.name,
+				format!(
+					"variable `{}` is not defined in environments {}",
+					&self.template.source[var.name], var.envs
+				),
+			);
+		}
+	}
+
+	/// Reports all branches of `if_block` which can never be taken and checks
+	/// the blocks of all branches.
+	fn lint_if(&mut self, if_block: &'t If) {
+		let If {
+			head,
+			elifs,
+			els,
+			end: _,
+		} = if_block;
+
+		let branches =
+			std::iter::once((head, "if", 6)).chain(elifs.iter().map(|elif| (elif, "elif", 8)));
+
+		let mut conditions = Vec::new();
+		let mut always = false;
+
+		for ((condition, nested), name, offset) in branches {
+			let span = *condition.span();
+			let text = self.template.source[span.offset_low(offset).offset_high(-2)]
+				.split_whitespace()
+				.collect::<Vec<_>>()
+				.join(" ");
+
+			if always {
+				self.report(
+					LintKind::UnreachableBranch,
+					span,
+					format!(
+						"the `{name}` branch is unreachable, as a previous condition always holds"
+					),
+				);
+			} else if conditions.contains(&text) {
+				self.report(
+					LintKind::UnreachableBranch,
+					span,
+					format!("the `{name}` branch is unreachable, as a previous branch has the same condition"),
+				);
+			} else {
+				match constant(condition.value()) {
+					Some(true) => always = true,
+					Some(false) => self.report(
+						LintKind::UnreachableBranch,
+						span,
+						format!("the `{name}` branch is unreachable, as its condition never holds"),
+					),
+					None => {}
+				}
+			}
+
+			conditions.push(text);
+			self.lint_blocks(nested);
+		}
+
+		if let Some((span, nested)) = els {
+			if always {
+				self.report(
+					LintKind::UnreachableBranch,
+					*span,
+					String::from(
+						"the `else` branch is unreachable, as a previous condition always holds",
+					),
+				);
+			}
+
+			self.lint_blocks(nested);
+		}
+	}
+}
+
+/// Returns the result of `condition` if it does not depend on variables or
+/// functions.
+fn constant(condition: &IfExpr) -> Option<bool> {
+	/// Checks if `operand` is a literal.
+	const fn is_literal(operand: &Operand<Var>) -> bool {
+		matches!(operand, Operand::Literal(_))
+	}
+
+	/// Checks if `expr` only consists of literals.
+	fn is_constant(expr: &IfExpr) -> bool {
+		match expr {
+			Expr::And(lhs, rhs) | Expr::Or(lhs, rhs) => is_constant(lhs) && is_constant(rhs),
+			Expr::Not(expr) => is_constant(expr),
+			Expr::Compare { lhs, rhs, .. } => is_literal(lhs) && is_literal(rhs),
+			Expr::Matches(operand, _) | Expr::Defined(operand) => is_literal(operand),
+			Expr::Test(..) => false,
+		}
+	}
+
+	if !is_constant(condition) {
+		return None;
+	}
+
+	condition
+		.eval(&|var: &Var| -> Result<Cow<'_, str>> {
+			Err(eyre!("Constant condition uses variable {var:?}"))
+		})
+		.ok()
+}
+
+/// Calls `f` for all variables which are used directly by `block`, not by its
+/// nested blocks.
+///
+/// The second argument of `f` indicates if it is only checked if the variable
+/// is defined.
+fn visit_block_vars<'t>(block: &'t Block, f: &mut impl FnMut(&'t Var, bool)) {
+	match &block.kind {
+		BlockKind::Var(var) => f(var, false),
+		BlockKind::Value(value) => value.visit_vars(f),
+		BlockKind::Render(render) => render.filters.iter().for_each(|filter| {
+			filter
+				.args
+				.iter()
+				.for_each(|arg| arg.visit_vars(&mut |var| f(var, false)))
+		}),
+		BlockKind::Call(call) => call
+			.args
+			.iter()
+			.for_each(|arg| arg.visit_vars(&mut |var| f(var, false))),
+		BlockKind::If(If { head, elifs, .. }) => {
+			head.0.value().visit_vars(f);
+			elifs
+				.iter()
+				.for_each(|(elif, _)| elif.value().visit_vars(f));
+		}
+		BlockKind::Text
+		| BlockKind::Comment
+		| BlockKind::Escaped(_)
+		| BlockKind::Raw(_)
+		| BlockKind::Print(_)
+		| BlockKind::Import(_)
+		| BlockKind::Macro(_) => {}
+	}
+}
+
+/// Calls `f` for all variables which are used by `blocks` and their nested
+/// blocks.
+fn for_each_var<'t>(blocks: &'t [Block], f: &mut impl FnMut(&'t Var, bool)) {
+	for block in blocks {
+		visit_block_vars(block, f);
+
+		match &block.kind {
+			BlockKind::If(If {
+				head, elifs, els, ..
+			}) => {
+				for_each_var(&head.1, f);
+				elifs.iter().for_each(|(_, nested)| for_each_var(nested, f));
+				if let Some((_, nested)) = els {
+					for_each_var(nested, f);
+				}
+			}
+			BlockKind::Macro(Macro { body, .. }) => for_each_var(body, f),
+			_ => {}
+		}
+	}
+}
@@ -20,6 +20,18 @@
 //!
 //! `{{{ This will be copied over {{ as is }} even with the "{{" inside }}}`
 //!
+//! ## Raw blocks
+//!
+//! Everything between `{{@raw}}` and the next `{{@end}}` will be copied over as is, even if it contains `}}}` or other template blocks. This allows to deploy files which use a similar template syntax themselves (e.g. helm charts or go templates). A line feed directly after `{{@raw}}` is omitted, so both can be written on their own lines. The content can not contain `{{@end}}`.
+//!
+//! ### Syntax
+//!
+//! ```text
+//! {{@raw}}
+//! image: {{ .Values.image | default "nginx" }}
+//! {{@end}}
+//! ```
+//!
 //! ## Variable blocks
 //!
 //! Define a variable which will be inserted instead of the block. The value of the variable can be gotten from three different environments which can be defined by specifying a prefix:
@@ -46,16 +58,44 @@
 //!
 //! `{{@print Hello World}}`
 //!
+//! ## Render blocks
+//!
+//! Render blocks render another template of the `dotfiles` directory in their place, with the same variables. This allows to build one big file from multiple fragments. The final line feed of the rendered template is omitted, so the block can be written on its own line. A template which renders itself (directly or through other templates) is an error. The output can be modified with filters after the name (e.g. `| nindent("4")` to insert it into yaml).
+//!
+//! ### Syntax
+//!
+//! `{{@render "zsh/aliases.zsh"}}`
+//!
+//! `{{@render "k8s/env.yaml" | nindent("4")}}`
+//!
+//! ## Macro blocks
+//!
+//! Macro blocks define reusable snippets with parameters, which are resolved by call blocks. The arguments of a call are strings or variable blocks, and inside of the macro the parameters are available as variables which take precedence over all other variables. Macros can only be defined at the top-level of a template and are available in the whole template. The macros of another template of the `dotfiles` directory (e.g. a partial which only contains macros) are made available with an import block, which inserts nothing itself. As with `if` blocks, a first or last line of the output of a call which only contains white spaces is omitted. A macro which calls itself is an error.
+//!
+//! ### Syntax
+//!
+//! ```text
+//! {{@import "partials/alacritty.tmpl"}}
+//! {{@macro kv(key, value)}}
+//! {{key}} = "{{value}}"
+//! {{@end}}
+//! {{@call kv("font", {{FONT}})}}
+//! ```
+//!
 //! ## If blocks
 //!
 //! Supported are `if`, `elif`, `else` and `fi`. Each `if` block must have a `fi` block as a final closing block.
 //! In between the `if` and `fi` block can be zero or multiple `elif` blocks with a final optional `else` block.
 //! Each if related block must be prefixed with `{{@` and end with `}}`.
 //!
-//! Currently the only supported if syntax is:
+//! The condition of an `if` or `elif` block is an [expression](`crate::expr`), which is shared with the `when` condition of dotfiles. Variables are written as variable blocks:
 //!
 //! - Check if the value of a variable is (not) equal to the literal given: `{{VAR}} (==|!=) "LITERAL"`
+//! - Check if the value of a variable matches a regular expression: `{{VAR}} matches "^dev-"`
 //! - Check if a value for a variable exists: `{{VAR}}`
+//! - Combine conditions with `&&`, `||`, `!` and parentheses: `{{OS}} == "linux" && !{{$CI}}`
+//!
+//! Comparing a variable which has no value is an error.
 //!
 //! Other blocks can be nested inside the `if`, `elif` and `else` bodies.
 //!
@@ -77,6 +117,74 @@
 //! {{@fi}}
 //! ```
 //!
+//! ## Value blocks
+//!
+//! Value blocks are replaced with the value of an expression, which uses the same expression language as `if` blocks.
+//!
+//! Inline ifs choose one of two values with a condition, so simple values which differ between machines do not need an `if` block spanning multiple lines. The values are strings, variable blocks or functions. Inline ifs can be chained and only the chosen value is resolved, so `{{ {{EDITOR}} ? {{EDITOR}} : "vi" }}` falls back to a default if a variable is not set.
+//!
+//! Functions modify values and can also be written as filters, where the value on the left of `|` is the first argument:
+//!
+//! - `replace(value, from, to)`: Replaces all occurrences of `from` with `to`
+//! - `split(value, separator, index)`: The part with the number `index` (starting at `"0"`) or nothing if there are less parts
+//! - `trim(value)`: Removes leading and trailing white spaces
+//! - `upper(value)` / `lower(value)`: Converts all characters to upper/lower case
+//! - `title(value)`: Converts the first character of each word to upper case and all others to lower case
+//! - `path_join(path, other)`: Appends `other` to `path` with the separator of the current platform
+//! - `dirname(path)` / `basename(path)`: The parent directory/last component of a path
+//! - `to_native(path)`: Converts all `/` and `\` to the separator of the current platform
+//! - `home()`: The home directory of the current user
+//! - `sha256(value)`: The SHA-256 hash of the value
+//! - `file_hash(path)`: The SHA-256 hash of the content of a file (e.g. to notice changes of a referenced asset), relative paths are resolved from the current directory
+//! - `b64encode(value)` / `b64decode(value)`: Encodes the value as base64 or decodes it (e.g. to embed a secret in a config which requires base64)
+//! - `uuid()` / `uuid(key)`: A random UUID
+//! - `random(min, max)` / `random(min, max, key)`: A random integer between `min` and `max` (both inclusive)
+//! - `now(format)` / `now(format, timezone)`: The current time formatted with `strftime` like specifiers (`%Y`, `%y`, `%m`, `%B`, `%b`, `%d`, `%e`, `%j`, `%A`, `%a`, `%H`, `%I`, `%p`, `%M`, `%S`, `%s`, `%z`, `%F`, `%T` and `%%`), e.g. `now("%F %T")`
+//! - `today()` / `today(timezone)`: The current date as `YYYY-MM-DD`
+//! - `indent(value, width)` / `nindent(value, width)`: Indents all non-empty lines of a multi-line value by `width` spaces (e.g. `{{ {{CERT}} | indent("4") }}` in yaml), `nindent` also starts with a new line
+//! - `to_json(value)` / `to_yaml(value)` / `to_toml(value)`: The value as a quoted string of the format, in which quotes, backslashes and control characters are escaped (e.g. `password = {{ {{PASSWORD}} | to_toml }}`)
+//! - `comment(text)`: Prefixes all lines of the text with the line comment syntax of the rendered file (e.g. `{{@render "header.txt" | comment}}`)
+//! - `plugin(value, name)`: Calls the function `name` of a plugin with the value (e.g. `{{ {{USER}} | plugin("corp.email") }}`), see [`plugin`](`crate::plugin`)
+//! - `contains(value, part)` / `starts_with(value, prefix)`: Tests the value, which can only be used in conditions (e.g. `{{@if {{HOST}} | starts_with("dev-")}}`)
+//!
+//! With a `key`, `uuid` and `random` return stable values which are derived from the key, the name of the machine and the path of the template. They stay the same between deployments (e.g. `{{ uuid("device-id") }}`), while the values without a key change on every deployment.
+//!
+//! The timezone of `now` and `today` is either `UTC` (default) or an offset like `+02:00`. The current time can be frozen with `--frozen-time <TIME>` or the `PUNKTF_FROZEN_TIME` environment variable (a RFC 3339 timestamp or the seconds since the unix epoch), which keeps snapshot tests deterministic. Templates which use them are always compiled again, even with a deploy cache.
+//!
+//! The comment syntax of `comment` is chosen by the extension of the template, or by its name for files without one (e.g. `"` for `.vimrc` and `*.vim`, `--` for `*.lua`, `//` for `*.js`, `;` for `*.ini` and `#` for `.gitconfig`, `*.ps1` and all unknown files). Profiles can add or replace prefixes with `comment_prefixes` (e.g. `comment_prefixes: { tmpl: "//" }`).
+//!
+//! ### Syntax
+//!
+//! ```text
+//! shell = {{ {{OS}} == "windows" ? "pwsh" : "zsh" }}
+//! term = {{ {{OS}} == "linux" ? "kitty" : {{OS}} == "macos" ? "iterm2" : "wt" }}
+//! login = {{ {{USER}} | replace(" ", ".") | lower }}
+//! name = {{ title({{USER}}) }}
+//! config = {{ path_join(home(), ".config") | path_join("nvim") }}
+//! ```
+//!
+//! # Usage
+//!
+//! A [`Template`] is parsed once and can then be rendered any number of times
+//! with different variables given by a [`Context`]:
+//!
+//! ```
+//! use punktf_lib::template::source::Source;
+//! use punktf_lib::template::{Context, Template};
+//!
+//! let template = Template::parse(Source::anonymous("Hello {{NAME}}!"))?;
+//!
+//! for name in ["World", "punktf"] {
+//!     let context = Context::new().with_dotfile_var("NAME", name);
+//!     assert_eq!(template.render(&context)?, format!("Hello {name}!"));
+//! }
+//! # Ok::<(), color_eyre::Report>(())
+//! ```
+//!
+//! Large templates can be rendered chunk-wise from a reader into a writer with
+//! a [`StreamRenderer`](`stream::StreamRenderer`), without holding the whole
+//! content in memory.
+//!
 //! # Copyright Notice
 //!
 //! The code for error/diagnostics and source input handling is heavily inspired by
@@ -95,20 +203,32 @@
 //! - <https://github.com/rust-lang/rust/blob/master/compiler/rustc_errors/src/emitter.rs>
 
 mod block;
-mod diagnostic;
+#[cfg(feature = "io")]
+pub mod cache;
+pub(crate) mod diagnostic;
+pub mod lint;
 mod parse;
 mod resolve;
 mod session;
 pub mod source;
-mod span;
+pub(crate) mod span;
+pub mod stream;
+mod tree;
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt;
 
 use color_eyre::eyre::Result;
 
-use self::block::Block;
+use self::block::{Block, VarEnv};
+use self::lint::{Lint, Linter};
 use self::parse::Parser;
 use self::resolve::Resolver;
-use self::source::Source;
-use crate::profile::variables::Vars;
+use self::source::{Location, Source};
+use crate::expr::Input;
+use crate::profile::variables::{Variables, Vars};
+use crate::profile::LayeredProfile;
 
 /// A `Template` is a file from the Source folder that is not yet deployed. It might contain statements and variables.
 #[derive(Debug, Clone)]
@@ -129,6 +249,9 @@ impl<'a> Template<'a> {
 	}
 
 	/// Resolves the variables in the template and returns a `Template` object.
+	///
+	/// Render blocks fail to resolve, use [`Template::resolve_with_includes`]
+	/// to support them.
 	pub fn resolve<PV: Vars, DV: Vars>(
 		&self,
 		profile_vars: Option<&PV>,
@@ -136,6 +259,216 @@ impl<'a> Template<'a> {
 	) -> Result<String> {
 		Resolver::new(self, profile_vars, dotfile_vars).resolve()
 	}
+
+	/// Resolves the template like [`Template::resolve`] and loads the
+	/// templates of render blocks with `includes`.
+	pub fn resolve_with_includes<PV: Vars, DV: Vars>(
+		&self,
+		profile_vars: Option<&PV>,
+		dotfile_vars: Option<&DV>,
+		includes: &dyn Includes,
+	) -> Result<String> {
+		Resolver::new(self, profile_vars, dotfile_vars)
+			.with_includes(includes)
+			.resolve()
+	}
+
+	/// Resolves the template like [`Template::resolve_with_includes`] with the
+	/// variables, comment prefixes and plugins of `profile`.
+	pub fn resolve_for_profile<DV: Vars>(
+		&self,
+		profile: &LayeredProfile,
+		dotfile_vars: Option<&DV>,
+		includes: &dyn Includes,
+	) -> Result<String> {
+		Resolver::new(self, Some(profile.variables()), dotfile_vars)
+			.with_includes(includes)
+			.with_comment_prefixes(profile.comment_prefixes())
+			.with_plugins(profile.plugins())
+			.resolve()
+	}
+
+	/// Resolves the template like [`Template::resolve_for_profile`] and adds
+	/// all inputs which are read from outside of the template (e.g.
+	/// environment variables or rendered templates) to `inputs`.
+	pub fn resolve_recorded<DV: Vars>(
+		&self,
+		profile: &LayeredProfile,
+		dotfile_vars: Option<&DV>,
+		includes: &dyn Includes,
+		inputs: &mut BTreeSet<Input>,
+	) -> Result<String> {
+		let recorded = RefCell::new(std::mem::take(inputs));
+
+		let resolved = Resolver::new(self, Some(profile.variables()), dotfile_vars)
+			.with_includes(includes)
+			.with_comment_prefixes(profile.comment_prefixes())
+			.with_plugins(profile.plugins())
+			.with_inputs(&recorded)
+			.resolve();
+
+		*inputs = recorded.into_inner();
+
+		resolved
+	}
+
+	/// Resolves the template like [`Template::resolve_for_profile`] and adds
+	/// all decisions which are made on the way (e.g. the values of variables
+	/// and which branches of `if` blocks are taken) to `trace`.
+	///
+	/// The decisions are also added if the template fails to resolve.
+	pub fn resolve_traced<DV: Vars>(
+		&self,
+		profile: &LayeredProfile,
+		dotfile_vars: Option<&DV>,
+		includes: &dyn Includes,
+		trace: &mut Vec<TraceEvent>,
+	) -> Result<String> {
+		let events = RefCell::new(Vec::new());
+
+		let resolved = Resolver::new(self, Some(profile.variables()), dotfile_vars)
+			.with_includes(includes)
+			.with_comment_prefixes(profile.comment_prefixes())
+			.with_plugins(profile.plugins())
+			.with_trace(&events)
+			.resolve();
+
+		trace.extend(events.into_inner());
+
+		resolved
+	}
+
+	/// Returns the parsed blocks of the template as indented tree with one
+	/// line per block (e.g. `3:1 if "{{@if {{OS}} == \"linux\"}}"`).
+	///
+	/// Nested blocks (e.g. the body of an `if` block) are indented by two
+	/// spaces.
+	pub fn tree(&self) -> String {
+		let mut out = String::new();
+		tree::write_blocks(&mut out, self, &self.blocks, 0);
+		out
+	}
+
+	/// Checks the template for problems which can be found without resolving
+	/// it, like branches of `if` blocks which can never be taken.
+	///
+	/// Use [`Template::lint_with_vars`] to also check for unknown variables.
+	pub fn lint(&self) -> Vec<Lint> {
+		Linter::new(self, None).lint()
+	}
+
+	/// Checks the template like [`Template::lint`] and additionally reports all
+	/// variables which are not defined in any of their environments.
+	///
+	/// Variables which are checked with `defined` somewhere in the template
+	/// and parameters of macros are never reported.
+	pub fn lint_with_vars<PV: Vars, DV: Vars>(
+		&self,
+		profile_vars: Option<&PV>,
+		dotfile_vars: Option<&DV>,
+	) -> Vec<Lint> {
+		let defined = |name: &str, env: VarEnv| match env {
+			VarEnv::Environment => std::env::var_os(name).is_some(),
+			VarEnv::Profile => profile_vars.and_then(|vars| vars.var(name)).is_some(),
+			VarEnv::Dotfile => dotfile_vars.and_then(|vars| vars.var(name)).is_some(),
+		};
+
+		Linter::new(self, Some(&defined)).lint()
+	}
+
+	/// Resolves the template with the variables of the given `context`.
+	///
+	/// The template is not modified, so it can be rendered multiple times.
+	pub fn render(&self, context: &Context) -> Result<String> {
+		self.resolve(Some(&context.profile_vars), Some(&context.dotfile_vars))
+	}
+}
+
+/// Loads the templates which are referenced by render blocks
+/// (`{{@render "NAME"}}`).
+pub trait Includes {
+	/// Returns the content of the template `name`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the template can not be loaded.
+	fn load(&self, name: &str) -> Result<String>;
+}
+
+/// Variables which are available when a [`Template`] is
+/// [rendered](`Template::render`).
+///
+/// Environment variables (`$` prefix) are always read from the current
+/// process.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct Context {
+	/// Variables of the profile (`#` prefix).
+	profile_vars: Variables,
+
+	/// Variables of the dotfile (`&` prefix).
+	dotfile_vars: Variables,
+}
+
+impl Context {
+	/// Creates a new context without any variables.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Uses `vars` as the variables of the profile.
+	pub fn with_profile_vars(mut self, vars: Variables) -> Self {
+		self.profile_vars = vars;
+		self
+	}
+
+	/// Uses `vars` as the variables of the dotfile.
+	pub fn with_dotfile_vars(mut self, vars: Variables) -> Self {
+		self.dotfile_vars = vars;
+		self
+	}
+
+	/// Sets the profile variable `name` to `value`.
+	pub fn with_profile_var<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+		self.profile_vars.inner.insert(name.into(), value.into());
+		self
+	}
+
+	/// Sets the dotfile variable `name` to `value`.
+	pub fn with_dotfile_var<K: Into<String>, V: Into<String>>(mut self, name: K, value: V) -> Self {
+		self.dotfile_vars.inner.insert(name.into(), value.into());
+		self
+	}
+}
+
+/// A decision which was made while a template was resolved (e.g. the value of
+/// a variable or which branch of an `if` block was taken).
+///
+/// These are collected by [`Template::resolve_traced`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+	/// Nesting level of the template in which the decision was made.
+	///
+	/// This is `0` for the resolved template and increases by one for each
+	/// rendered template or called macro.
+	pub depth: usize,
+
+	/// Location of the block in the template in which the decision was made.
+	pub location: Location,
+
+	/// Description of the decision.
+	pub message: String,
+}
+
+impl fmt::Display for TraceEvent {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f,
+			"{}{} {}",
+			"  ".repeat(self.depth),
+			self.location.display(),
+			self.message
+		)
+	}
 }
 
 #[cfg(test)]
@@ -199,6 +532,24 @@ mod tests {
 		Ok(())
 	}
 
+	#[test]
+	fn render_template() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let source = Source::anonymous("{{#OS}} {{NAME}}");
+		let template = Template::parse(source)?;
+
+		let context = Context::new()
+			.with_profile_var("OS", "linux")
+			.with_dotfile_var("NAME", "a");
+		assert_eq!(template.render(&context)?, "linux a");
+
+		let context = context.with_dotfile_var("NAME", "b");
+		assert_eq!(template.render(&context)?, "linux b");
+
+		Ok(())
+	}
+
 	#[test]
 	fn parse_template_vars() -> Result<()> {
 		crate::tests::setup_test_env();
@@ -289,4 +640,135 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[test]
+	fn template_tree() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let source = Source::anonymous(
+			"os = {{OS}}\n{{@if {{OS}} == \"linux\"}}\n{{@print linux}}\n{{@else}}\n{{ \"other\" | upper }}\n{{@fi}}\n",
+		);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.tree(),
+			r#"1:1 text "os = "
+1:6 var OS [Dotfile, Profile]
+1:12 text "\n"
+2:1 if "{{@if {{OS}} == \"linux\"}}"
+  2:26 text "\n"
+  3:1 print "linux"
+  3:17 text "\n"
+4:1 else
+  4:10 text "\n"
+  5:1 value "{{ \"other\" | upper }}"
+  5:22 text "\n"
+6:1 fi
+6:8 text "\n"
+"#
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn resolve_traced() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		/// Provides the template `header`.
+		struct Header;
+
+		impl Includes for Header {
+			fn load(&self, _: &str) -> Result<String> {
+				Ok(String::from("# {{NAME}}\n"))
+			}
+		}
+
+		let mut builder = LayeredProfile::build();
+		builder.add(
+			String::from("base"),
+			crate::profile::Profile {
+				variables: Some(Variables::from_items([("OS", "macos"), ("NAME", "demo")])),
+				..Default::default()
+			},
+		);
+		let profile = builder.finish();
+
+		let source = Source::anonymous(
+			"{{@render \"header\"}}\n{{@if {{OS}} == \"linux\"}}\nlinux\n{{@elif {{EDITOR}}}}\nedit\n{{@else}}\n{{ {{OS}} | upper }}\n{{@fi}}\n",
+		);
+		let template = Template::parse(source)?;
+
+		let mut trace = Vec::new();
+		let resolved =
+			template.resolve_traced(&profile, None::<&Variables>, &Header, &mut trace)?;
+		assert_eq!(resolved, "# demo\nMACOS\n");
+
+		assert_eq!(
+			trace.iter().map(ToString::to_string).collect::<Vec<_>>(),
+			[
+				"1:1 render `header`",
+				"  1:5 variable `NAME` is \"demo\"",
+				"2:9 variable `OS` is \"macos\"",
+				"2:1 `if` condition is false",
+				"4:11 variable `EDITOR` is not set",
+				"4:1 `elif` condition is false",
+				"6:1 `else` branch is taken",
+				"7:6 variable `OS` is \"macos\"",
+				"7:1 value is \"MACOS\"",
+			]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn lint_unreachable_branches() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let source = Source::anonymous(
+			"{{@if {{OS}} == \"linux\"}}a{{@elif {{OS}}  ==  \"linux\"}}b{{@fi}}\n{{@if \"a\" == \"a\"}}c{{@elif {{OS}}}}d{{@else}}e{{@fi}}\n{{@if \"a\" == \"b\"}}f{{@fi}}\n",
+		);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template
+				.lint()
+				.iter()
+				.map(ToString::to_string)
+				.collect::<Vec<_>>(),
+			[
+				"1:27: the `elif` branch is unreachable, as a previous branch has the same condition [unreachable-branch]",
+				"2:20: the `elif` branch is unreachable, as a previous condition always holds [unreachable-branch]",
+				"2:37: the `else` branch is unreachable, as a previous condition always holds [unreachable-branch]",
+				"3:1: the `if` branch is unreachable, as its condition never holds [unreachable-branch]",
+			]
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn lint_unknown_variables() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let source = Source::anonymous(
+			"{{OS}} {{#EDITOR}} {{$PUNKTF_TARGET_OS}}\n{{@if {{SHELL}}}}{{SHELL}}{{@fi}}\n{{@macro greet(name)}}{{name}}{{@end}}\n",
+		);
+		let template = Template::parse(source)?;
+
+		let profile_vars = Variables::from_items(vec![("OS", "linux")]);
+
+		assert_eq!(
+			template
+				.lint_with_vars(Some(&profile_vars), None::<&Variables>)
+				.iter()
+				.map(ToString::to_string)
+				.collect::<Vec<_>>(),
+			["1:11: variable `EDITOR` is not defined in environments [Profile] [unknown-variable]"]
+		);
+		assert!(template.lint().is_empty());
+
+		Ok(())
+	}
 }
@@ -6,12 +6,15 @@ mod tests;
 use color_eyre::eyre::{eyre, Result};
 use color_eyre::Report;
 
-use super::block::{Block, BlockHint, If, IfExpr, IfOp, Var, VarEnv, VarEnvSet};
+use super::block::{
+	Block, BlockHint, Call, If, IfExpr, Macro, Render, ValueExpr, Var, VarEnv, VarEnvSet,
+};
 use super::diagnostic::{Diagnostic, DiagnosticBuilder, DiagnosticLevel};
 use super::session::Session;
 use super::source::Source;
 use super::span::{ByteSpan, Pos, Spanned};
 use super::Template;
+use crate::expr::{self, ParseExprError};
 use crate::template::block::BlockKind;
 
 /// This is the parser which converts a [source](`super::source::Source`) into
@@ -56,7 +59,7 @@ impl<'a> Parser<'a> {
 		}
 
 		self.session.emit(&self.source);
-		self.session.try_finish()?;
+		self.session.try_finish(&self.source)?;
 
 		Ok(Template {
 			source: self.source,
@@ -97,16 +100,39 @@ impl<'a> Parser<'a> {
 			Err(err) => return Some(Err(err)),
 		};
 
-		log::trace!("{:?}: {}", hint, &self.source[span]);
+		tracing::trace!("{:?}: {}", hint, &self.source[span]);
 
 		let block = match hint {
 			BlockHint::Text => Ok(self.parse_text(span)),
 			BlockHint::Comment => Ok(self.parse_comment(span)),
 			BlockHint::Escaped => Ok(self.parse_escaped(span)),
+			BlockHint::Raw => Ok(self.parse_raw(span)),
 			BlockHint::Var => self
 				.parse_variable(span)
 				.map(|var| Block::new(span, BlockKind::Var(var))),
+			BlockHint::Value => self
+				.parse_value(span)
+				.map(|expr| Block::new(span, BlockKind::Value(expr))),
 			BlockHint::Print => Ok(self.parse_print(span)),
+			BlockHint::Render => self
+				.parse_render(span)
+				.map(|render| Block::new(span, BlockKind::Render(render))),
+			BlockHint::Import => self
+				.parse_template_name(span, "import")
+				.and_then(|(name, rest)| {
+					if self.source[rest].trim().is_empty() {
+						Ok(name)
+					} else {
+						Err(template_name_error(span, "import"))
+					}
+				})
+				.map(|name| Block::new(span, BlockKind::Import(name))),
+			BlockHint::MacroStart => self
+				.parse_macro(span)
+				.map(|Spanned { span, value }| Block::new(span, BlockKind::Macro(value))),
+			BlockHint::Call => self
+				.parse_call(span)
+				.map(|call| Block::new(span, BlockKind::Call(call))),
 			BlockHint::IfStart => self
 				.parse_if(span)
 				.map(|Spanned { span, value }| Block::new(span, BlockKind::If(value))),
@@ -124,6 +150,10 @@ impl<'a> Parser<'a> {
 				.message("top-level `fi` block")
 				.description("an `fi` can only be used to close an open `if` block")
 				.primary_span(span)),
+			BlockHint::MacroEnd => Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message("top-level `end` block")
+				.description("an `end` can only be used to close an open `macro` block")
+				.primary_span(span)),
 		};
 
 		Some(block)
@@ -149,6 +179,26 @@ impl<'a> Parser<'a> {
 		Block::new(span, BlockKind::Escaped(span.offset_low(3).offset_high(-3)))
 	}
 
+	/// Resolves the `span` to a block with
+	/// [BlockKind::Raw](`super::block::BlockKind::Raw`).
+	///
+	/// A line feed directly after the opening `{{@raw}}` is not part of the
+	/// content, so the opening can be written on its own line.
+	fn parse_raw(&self, span: ByteSpan) -> Block {
+		// {{@raw}} ... {{@end}}
+		let inner = span.offset_low(8).offset_high(-8);
+		let content = &self.source[inner];
+		let skip = if content.starts_with("\r\n") {
+			2
+		} else if content.starts_with('\n') {
+			1
+		} else {
+			0
+		};
+
+		Block::new(span, BlockKind::Raw(inner.offset_low(skip)))
+	}
+
 	/// Tries to resolves the `span` to a block with
 	/// [BlockKind::Var](`super::block::BlockKind::Var`).
 	///
@@ -170,6 +220,31 @@ impl<'a> Parser<'a> {
 		})
 	}
 
+	/// Tries to resolve the `span` to a block with
+	/// [BlockKind::Value](`super::block::BlockKind::Value`).
+	///
+	/// # Errors
+	///
+	/// Returns an error if the content is not a valid value expression.
+	fn parse_value(&self, span: ByteSpan) -> Result<ValueExpr, DiagnosticBuilder> {
+		// {{ EXPR ? VALUE : VALUE }} or {{ FUNCTION(VALUE) }}
+		let inner = span.offset_low(2).offset_high(-2);
+		let base = inner.low().as_usize();
+
+		expr::parse_value(&self.source[inner], |rest, offset| {
+			parse_expr_var(rest, offset, base)
+		})
+		.map_err(|err| {
+			let low = (base + err.offset).min(span.high().as_usize().saturating_sub(1));
+
+			DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message("failed to parse value expression")
+				.description(err.message)
+				.primary_span(span)
+				.label_span(ByteSpan::new(low, low + 1), "here")
+		})
+	}
+
 	/// Resolves the `span` to a block with
 	/// [BlockKind::Print](`super::block::BlockKind::Print`).
 	fn parse_print(&self, span: ByteSpan) -> Block {
@@ -177,6 +252,236 @@ impl<'a> Parser<'a> {
 		Block::new(span, BlockKind::Print(span.offset_low(9).offset_high(-2)))
 	}
 
+	/// Tries to resolve the `span` to a block with
+	/// [BlockKind::Render](`super::block::BlockKind::Render`).
+	///
+	/// # Errors
+	///
+	/// Returns an error if the name is not a double quoted string.
+	/// Returns an error if the filters after the name are invalid.
+	fn parse_render(&self, span: ByteSpan) -> Result<Render, DiagnosticBuilder> {
+		// {{@render "NAME" | FILTER}}
+		let (name, rest) = self.parse_template_name(span, "render")?;
+		let base = rest.low().as_usize();
+
+		let filters = expr::parse_filters(&self.source[rest], |rest, offset| {
+			parse_expr_var(rest, offset, base)
+		})
+		.map_err(|err| {
+			let low = (base + err.offset).min(span.high().as_usize().saturating_sub(1));
+
+			DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message("failed to parse render block")
+				.description(err.message)
+				.primary_span(span)
+				.label_span(ByteSpan::new(low, low + 1), "here")
+		})?;
+
+		Ok(Render { name, filters })
+	}
+
+	/// Tries to resolve the `span` to the name of the template of a
+	/// [BlockKind::Render](`super::block::BlockKind::Render`) or
+	/// [BlockKind::Import](`super::block::BlockKind::Import`) block.
+	///
+	/// Returns the name and the rest of the block after it.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the name is not a double quoted string.
+	fn parse_template_name(
+		&self,
+		span: ByteSpan,
+		block: &str,
+	) -> Result<(ByteSpan, ByteSpan), DiagnosticBuilder> {
+		// {{@BLOCK "NAME" ...}}
+		let inner = span.offset_low(block.len() as i32 + 4).offset_high(-2);
+		let content = &self.source[inner];
+		let start = content.len() - content.trim_start().len();
+
+		match content[start..]
+			.strip_prefix('"')
+			.and_then(|name| name.split_once('"'))
+		{
+			Some((name, _)) if !name.is_empty() => {
+				let low = inner.low().as_usize() + start + 1;
+				let high = low + name.len();
+
+				Ok((
+					ByteSpan::new(low, high),
+					ByteSpan::new(high + 1, inner.high().as_usize()),
+				))
+			}
+			_ => Err(template_name_error(span, block)),
+		}
+	}
+
+	/// Tries to resolve the `span` to a block with
+	/// [BlockKind::Macro](`super::block::BlockKind::Macro`).
+	///
+	/// During this operation it will also try to parse all blocks of the body
+	/// up to the closing [BlockHint::MacroEnd](`super::block::BlockHint::MacroEnd`).
+	///
+	/// # Errors
+	///
+	/// Returns an error if the name or the parameters are invalid.
+	/// Returns an error if no closing [BlockHint::MacroEnd](`super::block::BlockHint::MacroEnd`) was found.
+	fn parse_macro(&mut self, span: ByteSpan) -> Result<Spanned<Macro>, DiagnosticBuilder> {
+		// {{@macro NAME(PARAM, ...)}}
+		let (name, params) = self
+			.parse_signature(span.offset_low(9).offset_high(-2))
+			.map_err(|message| {
+				DiagnosticBuilder::new(DiagnosticLevel::Error)
+					.message("failed to parse macro block")
+					.description(message)
+					.primary_span(span)
+			})?;
+
+		let mut params_spans = Vec::new();
+		if !self.source[params].trim().is_empty() {
+			let mut low = params.low().as_usize();
+
+			for param in self.source[params].split(',') {
+				let trimmed = param.trim();
+				let start = low + (param.len() - param.trim_start().len());
+
+				if trimmed.is_empty() || !trimmed.bytes().all(is_var_name_symbol) {
+					return Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
+						.message("failed to parse macro block")
+						.description(format!("invalid parameter name `{trimmed}`"))
+						.primary_span(span));
+				}
+
+				params_spans.push(ByteSpan::new(start, start + trimmed.len()));
+				low += param.len() + 1;
+			}
+		}
+
+		let mut body = Vec::new();
+
+		loop {
+			// Create a copy of the block iter to not mess up the state while peeking
+			let mut peek = self.blocks;
+
+			match peek.next() {
+				Some(Ok(Spanned {
+					value: BlockHint::MacroEnd,
+					..
+				})) => break,
+				Some(_) => {}
+				None => {
+					return Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
+						.message("unexpected end of `macro` block")
+						.description("close the `macro` block with `{{@end}}`")
+						.primary_span(span))
+				}
+			}
+
+			match self.next_nested_block() {
+				Some(Ok(block)) => body.push(block),
+				Some(Err(builder)) => self.report_diagnostic(
+					builder
+						.label_span(span, "while parsing this `macro` block")
+						.build(),
+				),
+				None => {}
+			}
+		}
+
+		let end = self
+			.blocks
+			.next()
+			.expect("Some block to be present after peek")
+			.map_err(|build| build.label_span(span, "while parsing this `macro` block"))?
+			.span;
+
+		if &self.source[end] != "{{@end}}" {
+			return Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message("expected a `end` block")
+				.primary_span(end)
+				.label_span(span, "while parsing this `macro` block"));
+		}
+
+		Ok(span.union(&end).span(Macro {
+			name,
+			params: params_spans,
+			body,
+			end,
+		}))
+	}
+
+	/// Tries to resolve the `span` to a block with
+	/// [BlockKind::Call](`super::block::BlockKind::Call`).
+	///
+	/// # Errors
+	///
+	/// Returns an error if the name or the arguments are invalid.
+	fn parse_call(&self, span: ByteSpan) -> Result<Call, DiagnosticBuilder> {
+		// {{@call NAME(ARG, ...)}}
+		let (name, args) = self
+			.parse_signature(span.offset_low(8).offset_high(-2))
+			.map_err(|message| {
+				DiagnosticBuilder::new(DiagnosticLevel::Error)
+					.message("failed to parse call block")
+					.description(message)
+					.primary_span(span)
+			})?;
+
+		let base = args.low().as_usize();
+
+		let args = expr::parse_operands(&self.source[args], |rest, offset| {
+			parse_expr_var(rest, offset, base)
+		})
+		.map_err(|err| {
+			let low = (base + err.offset).min(span.high().as_usize().saturating_sub(1));
+
+			DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message("failed to parse call arguments")
+				.description(err.message)
+				.primary_span(span)
+				.label_span(ByteSpan::new(low, low + 1), "here")
+		})?;
+
+		Ok(Call { name, args })
+	}
+
+	/// Splits the `span` of a signature like `NAME(...)` into the span of the
+	/// name and the span of the content between the parentheses.
+	///
+	/// # Errors
+	///
+	/// Returns an error if the name is not a valid identifier or if it is not
+	/// followed by parentheses.
+	fn parse_signature(&self, span: ByteSpan) -> Result<(ByteSpan, ByteSpan), &'static str> {
+		let content = &self.source[span];
+		let trimmed = content.trim();
+		let low = span.low().as_usize() + (content.len() - content.trim_start().len());
+
+		let name_len = trimmed
+			.bytes()
+			.position(|b| !is_var_name_symbol(b))
+			.unwrap_or(trimmed.len());
+
+		if name_len == 0 {
+			return Err("expected the name of a macro (e.g. `kv(key, value)`)");
+		}
+
+		let rest = &trimmed[name_len..];
+		let Some(inner) = rest
+			.strip_prefix('(')
+			.and_then(|rest| rest.strip_suffix(')'))
+		else {
+			return Err("expected parentheses after the name of the macro (e.g. `kv(key, value)`)");
+		};
+
+		let inner_low = low + name_len + 1;
+
+		Ok((
+			ByteSpan::new(low, low + name_len),
+			ByteSpan::new(inner_low, inner_low + inner.len()),
+		))
+	}
+
 	/// Tries to resolves the `span` to a block with
 	/// [BlockKind::If](`super::block::BlockKind::If`).
 	///
@@ -356,7 +661,7 @@ impl<'a> Parser<'a> {
 	/// An error is returned if it fails to resolve the expression (related:
 	/// [`Parser::parse_if_expr`]).
 	fn parse_if_start(&self, span: ByteSpan) -> Result<IfExpr, DiagnosticBuilder> {
-		// {{@if {{VAR}} (!=|==) "LIT" }}
+		// {{@if EXPR}}
 		let expr_span = span.offset_low(6).offset_high(-2);
 		self.parse_if_expr(expr_span)
 	}
@@ -370,7 +675,7 @@ impl<'a> Parser<'a> {
 	/// An error is returned if it fails to resolve the expression (related:
 	/// [`Parser::parse_if_expr`]).
 	fn parse_elif(&self, span: ByteSpan) -> Result<IfExpr, DiagnosticBuilder> {
-		// {{@elif {{VAR}} (!=|==) "LIT" }}
+		// {{@elif EXPR}}
 		let expr_span = span.offset_low(8).offset_high(-2);
 		self.parse_if_expr(expr_span)
 	}
@@ -410,77 +715,26 @@ impl<'a> Parser<'a> {
 		}
 	}
 
-	/// Tries to resolve all components that make up an if expression.
-	///
-	/// These currently come in two forms:
-	///
-	/// - {{VAR}} (!=|==) "OTHER": Compare value of VAR with the literal OTHER
-	/// - (!){{VAR}}: Checks if the variable is (not) present/can (not) be resolved.
+	/// Tries to parse `span` as an [expression](`crate::expr`) whose
+	/// variables are variable blocks (e.g. `{{VAR}} == "OTHER" && !{{OTHER}}`).
 	///
 	/// # Errors
 	///
 	/// An error is returned if `span` can not be interpreted as an if
 	/// expression.
 	fn parse_if_expr(&self, span: ByteSpan) -> Result<IfExpr, DiagnosticBuilder> {
-		// {{VAR}} (!=|==) "OTHER" OR (!){{VAR}}
 		let content = &self.source[span];
+		let base = span.low().as_usize();
 
-		// Read optional `!` for not_exists
-		let hat_not_present_prefix = content.trim().as_bytes().starts_with(b"!");
+		expr::parse(content, |rest, offset| parse_expr_var(rest, offset, base)).map_err(|err| {
+			let low = (base + err.offset).min(span.high().as_usize().saturating_sub(1));
 
-		// read var
-		let var_block_start = content.find("{{").ok_or_else(|| {
 			DiagnosticBuilder::new(DiagnosticLevel::Error)
-				.message("expected a variable block")
-				.description("add a variable block with `{{VARIABLE_NAME}}`")
+				.message("failed to parse if expression")
+				.description(err.message)
 				.primary_span(span)
-		})?;
-
-		let var_block_end = content.find("}}").ok_or_else(|| {
-			DiagnosticBuilder::new(DiagnosticLevel::Error)
-				.message("variable block not closed")
-				.description("add `}}` to the close the open variable block")
-				.primary_span(ByteSpan::new(var_block_start, var_block_start + 2))
-		})? + 2;
-
-		let var_block_span = ByteSpan::new(
-			span.low().as_usize() + var_block_start,
-			span.low().as_usize() + var_block_end,
-		);
-
-		let var = self.parse_variable(var_block_span)?;
-
-		// check if it is an exits expr
-		// exclude the closing `}}` with -2.
-		let remainder = &content[var_block_end..];
-
-		if remainder.trim().is_empty() {
-			if hat_not_present_prefix {
-				Ok(IfExpr::NotExists { var })
-			} else {
-				Ok(IfExpr::Exists { var })
-			}
-		} else {
-			let op = parse_ifop(&content[var_block_end..]).map_err(|_| {
-				DiagnosticBuilder::new(DiagnosticLevel::Error)
-					.message("failed to find if operation")
-					.description("add either `==` or `!=` after the variable block")
-					.primary_span(var_block_span)
-			})?;
-
-			let other = parse_other(
-				&content[var_block_end..],
-				span.low().as_usize() + var_block_end,
-			)
-			.map_err(|_| {
-				DiagnosticBuilder::new(DiagnosticLevel::Error)
-					.message("failed to find right hand side of the if operation")
-					.description("add a literal to compare against with `\"LITERAL\"`")
-					.primary_span(var_block_span)
-			})?;
-
-			Ok(IfExpr::Compare { var, op, other })
-		}
+				.label_span(ByteSpan::new(low, low + 1), "here")
+		})
 	}
 
 	/// Eagerly tries to parse all "non-if blocks" (related:
@@ -493,7 +747,7 @@ impl<'a> Parser<'a> {
 
 		while let Some(true) = self.peek_block_hint().map(|hint| !hint.is_if_subblock()) {
 			let next_block = self
-				.next_top_level_block()
+				.next_nested_block()
 				.expect("Some block to be present after peek");
 
 			enclosed_blocks.push(next_block);
@@ -502,6 +756,24 @@ impl<'a> Parser<'a> {
 		enclosed_blocks
 	}
 
+	/// Tries to resolve the next block inside of an `if` or `macro` block.
+	///
+	/// This works like [`Parser::next_top_level_block`] but fails for
+	/// [BlockKind::Macro](`super::block::BlockKind::Macro`) blocks, as macros
+	/// can only be defined at the top-level.
+	fn next_nested_block(&mut self) -> Option<Result<Block, DiagnosticBuilder>> {
+		Some(self.next_top_level_block()?.and_then(|block| {
+			if let BlockKind::Macro(_) = block.kind {
+				Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
+					.message("nested `macro` block")
+					.description("macros can only be defined at the top-level of a template")
+					.primary_span(block.span))
+			} else {
+				Ok(block)
+			}
+		}))
+	}
+
 	/// Peeks at the next block hint. This does not affect any state of the
 	/// resolver.
 	fn peek_block_hint(&self) -> Option<BlockHint> {
@@ -549,6 +821,17 @@ fn next_block(s: &str) -> Option<Result<NextBlock, NextBlockError>> {
 					eyre!("Found opening for an escaped block but no closing"),
 				)))
 			}
+		} else if s[low..].starts_with("{{@raw}}") {
+			// block is a raw block, which ends at the first `{{@end}}`
+			if let Some(high) = s[low + 8..].find("{{@end}}") {
+				let high = high + 8 + (low + 8);
+				Some(Ok((ByteSpan::new(low, high), Some(BlockHint::Raw))))
+			} else {
+				Some(Err((
+					Some(8),
+					eyre!("Found opening for a raw block but no closing `{{@end}}`"),
+				)))
+			}
 		} else if let Some(b"!--") = s.as_bytes().get(low + 2..low + 5) {
 			// block is an comment block
 			if let Some(high) = s.find("--}}") {
@@ -677,44 +960,41 @@ fn parse_var(inner: &str, mut offset: usize) -> Result<Var> {
 	}
 }
 
-/// Tries to parse the content of `inner` as an (IfOp)[`super::block::IfOp`].
+/// Tries to parse a variable block at the start of `rest`, which is part of an
+/// [expression](`crate::expr`) that starts at the byte `base` of the source.
 ///
-/// # Errors
-///
-/// An error is returned if `inner` could not be interpreted as an if operand.
-fn parse_ifop(inner: &str) -> Result<IfOp> {
-	match (inner.find("=="), inner.find("!=")) {
-		(Some(eq_idx), Some(noteq_idx)) => {
-			if eq_idx < noteq_idx {
-				Ok(IfOp::Eq)
-			} else {
-				Ok(IfOp::NotEq)
-			}
-		}
-		(Some(_), None) => Ok(IfOp::Eq),
-		(None, Some(_)) => Ok(IfOp::NotEq),
-		_ => Err(eyre!("Failed to find a if operand")),
-	}
+/// This is used as the hook to recognize variables in [`expr::parse`] and
+/// [`expr::parse_operands`].
+fn parse_expr_var(
+	rest: &str,
+	offset: usize,
+	base: usize,
+) -> Option<Result<(usize, Var), ParseExprError>> {
+	let inner = rest.strip_prefix("{{")?;
+
+	let Some(end) = inner.find("}}") else {
+		return Some(Err(ParseExprError::new(
+			offset,
+			"variable block not closed, add `}}` to close it",
+		)));
+	};
+
+	// +2 for block opening
+	Some(
+		parse_var(&inner[..end], base + offset + 2)
+			.map(|var| (end + 4, var))
+			.map_err(|err| ParseExprError::new(offset, err.to_string())),
+	)
 }
 
-/// Parses the right hand side of an if/elif compare operand, which is string literal. The `"`
-/// characters are not included in the returned span.
-///
-/// # Errors
-///
-/// An error is returned if no `"` was found.
-/// An error is returned if a opening `"` was found but no closing one.
-fn parse_other(inner: &str, offset: usize) -> Result<ByteSpan> {
-	let mut matches = inner.match_indices('"').map(|(idx, _)| idx);
-
-	match (matches.next(), matches.next()) {
-		(Some(low), Some(high)) => Ok(ByteSpan::new(offset + low + 1, offset + high)),
-		(Some(low), None) => Err(eyre!(
-			"Found opening `\"` at {} but no closing",
-			offset + low
-		)),
-		_ => Err(eyre!("Found no other")),
-	}
+/// Returns the error of a `block` whose template name could not be parsed.
+fn template_name_error(span: ByteSpan, block: &str) -> DiagnosticBuilder {
+	DiagnosticBuilder::new(DiagnosticLevel::Error)
+		.message(format!("failed to parse {block} block"))
+		.description(format!(
+			"expected the name of a template in double quotes (e.g. `{{{{@{block} \"zsh/aliases.zsh\"}}}}`)"
+		))
+		.primary_span(span)
 }
 
 /// Checks if `b` is considered to be a valid byte for a [variable](`super::block::Var`)
@@ -723,6 +1003,40 @@ const fn is_var_name_symbol(b: u8) -> bool {
 	b.is_ascii_alphanumeric() || b == b'_'
 }
 
+/// Returns the last index of `content` at which it can be split into two
+/// parts which render the same on their own as `content` does as a whole.
+///
+/// Such an index is always directly after a line feed inside of a top-level
+/// text block. This means the left part contains no unclosed `if` block and
+/// the right part starts at the beginning of a line. The search stops at the
+/// first block which can not be parsed, as it may be completed by content
+/// following `content`.
+pub(super) fn last_split_point(content: &str) -> Option<usize> {
+	let mut split = None;
+	let mut depth = 0usize;
+
+	for block in BlockIter::new(content) {
+		let Ok(Spanned { span, value: hint }) = block else {
+			break;
+		};
+
+		match hint {
+			// Macros can be called anywhere in the template
+			BlockHint::MacroStart => return None,
+			BlockHint::IfStart => depth += 1,
+			BlockHint::IfEnd => depth = depth.saturating_sub(1),
+			BlockHint::Text if depth == 0 => {
+				if let Some(idx) = content[span].rfind('\n') {
+					split = Some(span.low().as_usize() + idx + 1);
+				}
+			}
+			_ => {}
+		}
+	}
+
+	split
+}
+
 /// An iterator over all [blocks](`super::block::BlockHint`) of a string.
 #[derive(Debug, Clone, Copy)]
 struct BlockIter<'a> {
@@ -751,13 +1065,13 @@ impl<'a> Iterator for BlockIter<'a> {
 				let span = ByteSpan::new(self.index, self.index);
 				if let Some(skip) = skip {
 					self.index += skip;
-					log::trace!("Skipping: {} ({})", skip, &self.content[self.index..]);
+					tracing::trace!("Skipping: {} ({})", skip, &self.content[self.index..]);
 				} else {
 					self.index = self.content.len();
 				}
 				let span = span.with_high(self.index);
 
-				log::trace!("Span: {}/{}", span, err);
+				tracing::trace!("Span: {}/{}", span, err);
 
 				return Some(Err(DiagnosticBuilder::new(DiagnosticLevel::Error)
 					.message("failed to parse block")
@@ -801,6 +1115,36 @@ impl<'a> Iterator for BlockIter<'a> {
 			return Some(Ok(span.span(BlockHint::Print)));
 		}
 
+		// Check for render
+		// e.g. `{{@render "NAME"}}`
+		if content.starts_with("@render ") {
+			return Some(Ok(span.span(BlockHint::Render)));
+		}
+
+		// Check for import
+		// e.g. `{{@import "NAME"}}`
+		if content.starts_with("@import ") {
+			return Some(Ok(span.span(BlockHint::Import)));
+		}
+
+		// Check for macro
+		// e.g. `{{@macro NAME(PARAM)}}`
+		if content.starts_with("@macro ") {
+			return Some(Ok(span.span(BlockHint::MacroStart)));
+		}
+
+		// Check for call
+		// e.g. `{{@call NAME("ARG")}}`
+		if content.starts_with("@call ") {
+			return Some(Ok(span.span(BlockHint::Call)));
+		}
+
+		// Check for end
+		// e.g. `{{@end}}`
+		if content.starts_with("@end") {
+			return Some(Ok(span.span(BlockHint::MacroEnd)));
+		}
+
 		// Check for if
 		// e.g. `{{@if {{VAR}} == "LITERAL"}}`
 		if content.starts_with("@if ") {
@@ -825,6 +1169,12 @@ impl<'a> Iterator for BlockIter<'a> {
 			return Some(Ok(span.span(BlockHint::IfEnd)));
 		}
 
+		// Check for value, as `?`, `(` and `|` are not allowed in variable names
+		// e.g. `{{ {{VAR}} == "LITERAL" ? "A" : "B" }}` or `{{ {{VAR}} | upper }}`
+		if content.contains(['?', '(', '|']) {
+			return Some(Ok(span.span(BlockHint::Value)));
+		}
+
 		Some(Ok(span.span(BlockHint::Var)))
 	}
 }
@@ -4,7 +4,8 @@ use color_eyre::eyre::{eyre, Result};
 use pretty_assertions::assert_eq;
 
 use super::*;
-use crate::template::block::{Block, BlockKind, If, IfExpr, IfOp, Var, VarEnv, VarEnvSet};
+use crate::expr::{Expr, Operand, Value};
+use crate::template::block::{Block, BlockKind, Call, If, Macro, Var, VarEnv, VarEnvSet};
 use crate::template::source::Source;
 use crate::template::span::ByteSpan;
 
@@ -253,11 +254,6 @@ fn parse_single_if_eq() -> Result<()> {
 	assert_eq!(&content[name], "OS");
 	let envs = VarEnvSet([Some(VarEnv::Dotfile), Some(VarEnv::Profile), None]);
 
-	let op = IfOp::Eq;
-
-	let other = ByteSpan::new(17usize, 24usize);
-	assert_eq!(&content[other], "windows");
-
 	let end_span = ByteSpan::new(27usize, 34usize);
 	assert_eq!(&content[end_span], r#"{{@fi}}"#);
 
@@ -265,10 +261,10 @@ fn parse_single_if_eq() -> Result<()> {
 		block.kind(),
 		&BlockKind::If(If {
 			head: (
-				if_span.span(IfExpr::Compare {
-					var: Var { envs, name },
-					op,
-					other
+				if_span.span(Expr::Compare {
+					lhs: Operand::Var(Var { envs, name }),
+					equal: true,
+					rhs: Operand::Literal(String::from("windows"))
 				}),
 				vec![]
 			),
@@ -303,11 +299,6 @@ fn parse_single_if_neq() -> Result<()> {
 	assert_eq!(&content[name], "OS");
 	let envs = VarEnvSet([Some(VarEnv::Dotfile), Some(VarEnv::Profile), None]);
 
-	let op = IfOp::NotEq;
-
-	let other = ByteSpan::new(17usize, 24usize);
-	assert_eq!(&content[other], "windows");
-
 	let end_span = ByteSpan::new(27usize, 34usize);
 	assert_eq!(&content[end_span], r#"{{@fi}}"#);
 
@@ -315,10 +306,10 @@ fn parse_single_if_neq() -> Result<()> {
 		block.kind(),
 		&BlockKind::If(If {
 			head: (
-				if_span.span(IfExpr::Compare {
-					var: Var { envs, name },
-					op,
-					other
+				if_span.span(Expr::Compare {
+					lhs: Operand::Var(Var { envs, name }),
+					equal: false,
+					rhs: Operand::Literal(String::from("windows"))
 				}),
 				vec![]
 			),
@@ -360,9 +351,7 @@ fn parse_single_if_exists() -> Result<()> {
 		block.kind(),
 		&BlockKind::If(If {
 			head: (
-				if_span.span(IfExpr::Exists {
-					var: Var { envs, name }
-				}),
+				if_span.span(Expr::Defined(Operand::Var(Var { envs, name }))),
 				vec![]
 			),
 			elifs: vec![],
@@ -403,9 +392,10 @@ fn parse_single_if_not_exists() -> Result<()> {
 		block.kind(),
 		&BlockKind::If(If {
 			head: (
-				if_span.span(IfExpr::NotExists {
-					var: Var { envs, name }
-				}),
+				if_span.span(Expr::Not(Box::new(Expr::Defined(Operand::Var(Var {
+					envs,
+					name
+				}))))),
 				vec![]
 			),
 			elifs: vec![],
@@ -498,6 +488,30 @@ fn parse_escaped() -> Result<()> {
 	Ok(())
 }
 
+#[test]
+fn parse_raw() -> Result<()> {
+	crate::tests::setup_test_env();
+
+	let content = "{{@raw}}\n{{ .Values }} {{@if}} }}}\n{{@end}}";
+
+	let source = Source::anonymous(content);
+	let mut parser = Parser::new(source);
+	let block = parser
+		.next_top_level_block()
+		.expect("Found no block")
+		.expect("Encountered a parse error");
+
+	let inner = ByteSpan::new(9usize, content.len() - 8);
+	assert_eq!(&content[inner], "{{ .Values }} {{@if}} }}}\n");
+	assert_eq!(
+		block,
+		Block::new(ByteSpan::new(0usize, content.len()), BlockKind::Raw(inner))
+	);
+	assert!(parser.next_top_level_block().is_none());
+
+	Ok(())
+}
+
 #[test]
 fn parse_if_cmp() -> Result<()> {
 	crate::tests::setup_test_env();
@@ -665,17 +679,157 @@ fn parse_variables() -> Result<()> {
 }
 
 #[test]
-fn parse_others() -> Result<()> {
+fn parse_if_compound() -> Result<()> {
 	crate::tests::setup_test_env();
 
-	assert_eq!(parse_other("\"BAZ_1\"", 0)?, ByteSpan::new(1usize, 6usize));
+	let content = r#"{{@if {{OS}} == "linux" && !({{HOST}} matches "^dev-" || {{$#CI}})}}{{@fi}}"#;
+
+	let source = Source::anonymous(content);
+	let mut parser = Parser::new(source);
+	let block = parser
+		.next_top_level_block()
+		.expect("Found no block")
+		.expect("Encountered a parse error");
+
+	let BlockKind::If(If { head, .. }) = block.kind() else {
+		return Err(eyre!("Expected an if block"));
+	};
+
+	let Expr::And(lhs, rhs) = head.0.value() else {
+		return Err(eyre!("Expected an and expression"));
+	};
+	assert!(matches!(**lhs, Expr::Compare { equal: true, .. }));
+	assert!(matches!(**rhs, Expr::Not(_)));
+
+	Ok(())
+}
+
+#[test]
+fn parse_if_invalid() {
+	crate::tests::setup_test_env();
+
+	for content in [
+		r#"{{@if {{OS}} == "linux}}{{@fi}}"#,
+		r#"{{@if {{OS}} ==}}{{@fi}}"#,
+		r#"{{@if {{OS}} && }}{{@fi}}"#,
+		r#"{{@if {{OS}} matches "("}}{{@fi}}"#,
+		r#"{{@if OS == "linux"}}{{@fi}}"#,
+	] {
+		let source = Source::anonymous(content);
+		let mut parser = Parser::new(source);
+		assert!(
+			parser
+				.next_top_level_block()
+				.expect("Found no block")
+				.is_err(),
+			"{content}"
+		);
+	}
+}
+
+#[test]
+fn parse_macro() -> Result<()> {
+	crate::tests::setup_test_env();
+
+	let content =
+		r#"{{@macro kv( key, value )}}{{key}} = {{value}}{{@end}}{{@call kv("font", {{FONT}})}}"#;
+	let source = Source::anonymous(content);
+	let mut parser = Parser::new(source);
+
+	let block = parser
+		.next_top_level_block()
+		.expect("Found no block")
+		.expect("Encountered a parse error");
+
+	let BlockKind::Macro(Macro {
+		name, params, body, ..
+	}) = block.kind()
+	else {
+		return Err(eyre!("Expected a macro block"));
+	};
+
+	assert_eq!(&content[*name], "kv");
+	assert_eq!(
+		params
+			.iter()
+			.map(|param| &content[*param])
+			.collect::<Vec<_>>(),
+		["key", "value"]
+	);
+	assert_eq!(body.len(), 3);
+
+	let block = parser
+		.next_top_level_block()
+		.expect("Found no block")
+		.expect("Encountered a parse error");
+
+	let BlockKind::Call(Call { name, args }) = block.kind() else {
+		return Err(eyre!("Expected a call block"));
+	};
+
+	assert_eq!(&content[*name], "kv");
+	assert_eq!(args[0], Operand::Literal(String::from("font")));
+	assert!(matches!(&args[1], Operand::Var(var) if &content[var.name] == "FONT"));
+
+	Ok(())
+}
+
+#[test]
+fn parse_macro_invalid() {
+	crate::tests::setup_test_env();
+
+	for content in [
+		r#"{{@macro kv(key, value)}}{{key}}"#,
+		r#"{{@macro kv(key value)}}{{@end}}"#,
+		r#"{{@macro (key)}}{{@end}}"#,
+		r#"{{@if {{OS}}}}{{@macro kv()}}{{@end}}{{@fi}}"#,
+		r#"{{@call kv("a" "b")}}"#,
+		r#"{{@call kv}}"#,
+		r#"{{@import partial}}"#,
+		r#"{{@end}}"#,
+	] {
+		let source = Source::anonymous(content);
+		assert!(Template::parse(source).is_err(), "{content}");
+	}
+}
+
+#[test]
+fn parse_inline_if() -> Result<()> {
+	crate::tests::setup_test_env();
+
+	let content = r#"{{ {{OS}} == "windows" ? "pwsh" : "zsh" }}"#;
+	let source = Source::anonymous(content);
+	let mut parser = Parser::new(source);
+
+	let block = parser
+		.next_top_level_block()
+		.expect("Found no block")
+		.expect("Encountered a parse error");
+
+	let BlockKind::Value(Value::Ternary {
+		condition,
+		then,
+		otherwise,
+	}) = block.kind()
+	else {
+		return Err(eyre!("Expected a value block"));
+	};
+
+	assert!(matches!(condition, Expr::Compare { equal: true, .. }));
+	assert_eq!(then, &Operand::Literal(String::from("pwsh")));
 	assert_eq!(
-		parse_other("This is a test \"Hello World How are you today\"", 0)?,
-		ByteSpan::new(16usize, 45usize)
+		**otherwise,
+		Value::Operand(Operand::Literal(String::from("zsh")))
 	);
 
-	assert!(parse_other("This is a test \"Hello World How are you today", 0).is_err());
-	assert!(parse_other("This is a test", 0).is_err());
+	for content in [
+		r#"{{ {{OS}} == "windows" ? "pwsh" }}"#,
+		r#"{{ {{OS}} == "windows" "pwsh" : "zsh" }}"#,
+		r#"{{ OS ? "a" : "b" }}"#,
+	] {
+		let source = Source::anonymous(content);
+		assert!(Template::parse(source).is_err(), "{content}");
+	}
 
 	Ok(())
 }
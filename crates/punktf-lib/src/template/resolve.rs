@@ -3,13 +3,20 @@
 //! variable blocks and evaluation of if blocks.
 
 use std::borrow::Cow;
-use std::ops::Deref;
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
 
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{eyre, Result};
 
-use super::block::{Block, BlockKind, If, IfExpr, Var, VarEnv};
+use super::block::{Block, BlockKind, Call, If, IfExpr, Macro, Render, Var, VarEnv};
 use super::session::Session;
-use super::Template;
+use super::source::{Source, SourceOrigin};
+use super::span::BytePos;
+use super::{Includes, Template, TraceEvent};
+use crate::expr::{FunctionError, Input, Lookup, Scope, Settings};
+use crate::plugin::Plugins;
+use crate::profile::condition;
 use crate::profile::variables::Vars;
 use crate::template::diagnostic::{Diagnostic, DiagnosticBuilder, DiagnosticLevel};
 
@@ -81,7 +88,7 @@ macro_rules! family {
 				"unix"
 			} else if #[cfg(target_os = "windows")] {
 				"windows"
-			} else if #[cfg(target_os = "wasm")] {
+			} else if #[cfg(target_family = "wasm")] {
 				"wasm"
 			} else {
 				"unknown"
@@ -105,6 +112,47 @@ pub struct Resolver<'a, PV, DV> {
 	/// which corresponds to the template.
 	dotfile_vars: Option<&'a DV>,
 
+	/// Loads the templates of render blocks.
+	includes: Option<&'a dyn Includes>,
+
+	/// Origin of the file which is rendered.
+	///
+	/// For templates which are rendered as part of another template (e.g. by
+	/// a render block) this is the origin of the outermost template.
+	origin: SourceOrigin<'a>,
+
+	/// Comment prefixes by file extension which take precedence over the
+	/// [built-in ones](`builtin_comment_prefix`).
+	comment_prefixes: Option<&'a HashMap<String, String>>,
+
+	/// Plugins whose functions are called by the `plugin` function.
+	plugins: Option<&'a Plugins>,
+
+	/// Names of the templates which are currently rendered, used to detect
+	/// templates which render themselves.
+	rendering: Vec<String>,
+
+	/// Values of the parameters of the macro which is resolved.
+	///
+	/// They take precedence over all other variables.
+	args: Vec<(String, String)>,
+
+	/// Names of the macros which are currently called, used to detect macros
+	/// which call themselves.
+	calling: Vec<String>,
+
+	/// Records all decisions which are made while resolving (e.g. the values
+	/// of variables), if set.
+	trace: Option<&'a RefCell<Vec<TraceEvent>>>,
+
+	/// Records all inputs which are read from outside of the template (e.g.
+	/// environment variables), if set.
+	inputs: Option<&'a RefCell<BTreeSet<Input>>>,
+
+	/// Nesting level of the template, which is increased for each rendered
+	/// template or called macro.
+	depth: usize,
+
 	/// Session where all errors/diagnostic which occur during the resolving
 	/// process are recorded to.
 	session: Session,
@@ -133,21 +181,78 @@ where
 			template,
 			profile_vars,
 			dotfile_vars,
+			includes: None,
+			origin: template.source.origin,
+			comment_prefixes: None,
+			plugins: None,
+			rendering: Vec::new(),
+			args: Vec::new(),
+			calling: Vec::new(),
+			trace: None,
+			inputs: None,
+			depth: 0,
 			session: Session::new(),
 			should_skip_next_newline: false,
 		}
 	}
 
+	/// Loads the templates of render blocks with `includes`.
+	pub const fn with_includes(mut self, includes: &'a dyn Includes) -> Self {
+		self.includes = Some(includes);
+		self
+	}
+
+	/// Uses the `comment_prefixes` by file extension in addition to the
+	/// built-in ones.
+	pub const fn with_comment_prefixes(
+		mut self,
+		comment_prefixes: &'a HashMap<String, String>,
+	) -> Self {
+		self.comment_prefixes = Some(comment_prefixes);
+		self
+	}
+
+	/// Calls the functions of `plugins` with the `plugin` function.
+	pub const fn with_plugins(mut self, plugins: &'a Plugins) -> Self {
+		self.plugins = Some(plugins);
+		self
+	}
+
+	/// Records all decisions which are made while resolving to `trace`.
+	pub const fn with_trace(mut self, trace: &'a RefCell<Vec<TraceEvent>>) -> Self {
+		self.trace = Some(trace);
+		self
+	}
+
+	/// Records all inputs which are read from outside of the template to
+	/// `inputs`.
+	pub const fn with_inputs(mut self, inputs: &'a RefCell<BTreeSet<Input>>) -> Self {
+		self.inputs = Some(inputs);
+		self
+	}
+
 	/// Consumes the resolver and tries to resolve all blocks defined by the
 	/// template.
 	///
 	/// # Errors
 	///
 	/// An error is returned if a variable could not be resolved.
-	pub fn resolve(mut self) -> Result<String> {
+	pub fn resolve(self) -> Result<String> {
+		let template = self.template;
+
+		self.resolve_blocks(&template.blocks)
+	}
+
+	/// Consumes the resolver and tries to resolve the given `blocks` of the
+	/// template.
+	///
+	/// # Errors
+	///
+	/// An error is returned if a variable could not be resolved.
+	fn resolve_blocks(mut self, blocks: &[Block]) -> Result<String> {
 		let mut output = String::new();
 
-		for block in &self.template.blocks {
+		for block in blocks {
 			if let Err(builder) = self.process_block(&mut output, block) {
 				self.report_diagnostic(builder.build());
 			}
@@ -155,9 +260,13 @@ where
 
 		self.session.emit(&self.template.source);
 
-		let Resolver { session, .. } = self;
+		let Resolver {
+			session, template, ..
+		} = self;
 
-		session.try_finish().map(|_| output)
+		session.try_finish(&template.source)?;
+
+		Ok(output)
 	}
 
 	/// Adds a diagnostic to the session.
@@ -231,11 +340,32 @@ where
 
 				output.push_str(content);
 			}
+			BlockKind::Raw(inner) => {
+				let content = &self.template.source[inner];
+
+				// Should skip new line if the block started at the beginning of
+				// a line and the `end` block is on its own line.
+				self.should_skip_next_newline = (content.is_empty() || content.ends_with('\n'))
+					&& self.template.source.get_pos_location(span.low).column() == 0;
+
+				output.push_str(content);
+			}
 			BlockKind::Var(var) => {
 				self.should_skip_next_newline = false;
 
 				output.push_str(&self.resolve_var(var)?);
 			}
+			BlockKind::Value(expr) => {
+				self.should_skip_next_newline = false;
+
+				let value = expr
+					.eval(&self.lookup())
+					.map_err(|builder| builder.label_span(*span, "while resolving this block"))?;
+
+				self.trace(span.low, || format!("value is {value:?}"));
+
+				output.push_str(&value);
+			}
 			BlockKind::Print(inner) => {
 				// Should skip new line if started at the beginning of a line.
 				// As a `print` block has no final `content` is the above the
@@ -243,7 +373,63 @@ where
 				self.should_skip_next_newline =
 					self.template.source.get_pos_location(span.low).column() == 0;
 
-				log::info!("Print: {}", &self.template.source[inner]);
+				tracing::info!("Print: {}", &self.template.source[inner]);
+			}
+			BlockKind::Render(Render { name, filters }) => {
+				self.should_skip_next_newline = false;
+
+				let name = &self.template.source[name];
+				self.trace(span.low, || format!("render `{name}`"));
+
+				let rendered = self.render(name).map_err(|err| {
+					DiagnosticBuilder::new(DiagnosticLevel::Error)
+						.message(format!("failed to render template `{name}`"))
+						.description(format!("{err:#}"))
+						.primary_span(*span)
+				})?;
+
+				// A render block on its own line inserts the lines of the
+				// template, so its final line feed is not duplicated
+				let rendered = rendered
+					.strip_suffix('\n')
+					.map(|r| r.strip_suffix('\r').unwrap_or(r))
+					.unwrap_or(&rendered);
+
+				let mut rendered = Cow::Borrowed(rendered);
+				for filter in filters {
+					rendered =
+						Cow::Owned(filter.apply(&rendered, &self.lookup()).map_err(|builder| {
+							builder.label_span(*span, "while resolving this block")
+						})?);
+				}
+
+				output.push_str(&rendered);
+			}
+			BlockKind::Import(_) | BlockKind::Macro(_) => {
+				// Should skip new line if started at the beginning of a line.
+				// As a `macro` block has no final `content` is the above the
+				// only condition.
+				self.should_skip_next_newline =
+					self.template.source.get_pos_location(span.low).column() == 0;
+
+				// NOP
+			}
+			BlockKind::Call(call) => {
+				self.trace(span.low, || {
+					format!("call `{}`", &self.template.source[call.name])
+				});
+
+				let called = self.call(call).map_err(|builder| {
+					builder.label_span(*span, "while resolving this `call` block")
+				})?;
+				let called = trim_block_lines(&called);
+
+				// Should skip new line if started at the beginning of a line
+				// and no new content was added.
+				self.should_skip_next_newline = called.is_empty()
+					&& self.template.source.get_pos_location(span.low).column() == 0;
+
+				output.push_str(called);
 			}
 			BlockKind::If(If {
 				head,
@@ -264,6 +450,8 @@ where
 					}
 				};
 
+				self.trace(head.span().low, || format!("`if` condition is {matched}"));
+
 				if matched {
 					for block in head_nested {
 						self.process_block(&mut if_output, block)?;
@@ -279,6 +467,8 @@ where
 							}
 						};
 
+						self.trace(elif.span().low, || format!("`elif` condition is {matched}"));
+
 						if matched {
 							found_elif = true;
 
@@ -291,7 +481,9 @@ where
 					}
 
 					if !found_elif {
-						if let Some((_, els_nested)) = els {
+						if let Some((els, els_nested)) = els {
+							self.trace(els.low, || String::from("`else` branch is taken"));
+
 							for block in els_nested {
 								self.process_block(&mut if_output, block)?;
 							}
@@ -299,26 +491,7 @@ where
 					}
 				}
 
-				let mut if_output_prepared = if_output.deref();
-
-				// Check if characters before first line feed are all considered
-				// to be white spaces and if so omit them from the output.
-				if let Some(idx) = if_output_prepared.find('\n') {
-					// include line feed
-					if if_output_prepared[..idx].trim_start().is_empty() {
-						// Also trim line feed
-						if_output_prepared = &if_output_prepared[idx + 1..];
-					}
-				}
-
-				// Check if characters after last line feed are all considered
-				// to be white spaces and if so omit them from the output.
-				if let Some(idx) = if_output_prepared.rfind('\n') {
-					// include line feed
-					if if_output_prepared[idx..].trim_start().is_empty() {
-						if_output_prepared = &if_output_prepared[..idx];
-					}
-				}
+				let if_output_prepared = trim_block_lines(&if_output);
 
 				// Should skip new line if started at the beginning of a line
 				// and no new content was added.
@@ -332,6 +505,173 @@ where
 		Ok(())
 	}
 
+	/// Loads the template `name` and resolves it with the same variables.
+	///
+	/// # Errors
+	///
+	/// An error is returned if no [`Includes`] are given, if the template
+	/// renders itself or if it fails to load or resolve.
+	fn render(&self, name: &str) -> Result<String> {
+		let Some(includes) = self.includes else {
+			return Err(eyre!("rendering other templates is not supported here"));
+		};
+
+		if self.rendering.iter().any(|rendering| rendering == name) {
+			return Err(eyre!("the template renders itself"));
+		}
+
+		self.record(Input::Include(name.to_owned()));
+
+		let content = includes.load(name)?;
+		let template = Template::parse(Source::anonymous(&content))?;
+
+		let mut resolver = self.nested(&template);
+		resolver.rendering.push(name.to_owned());
+
+		resolver.resolve()
+	}
+
+	/// Creates a resolver for `template` which is resolved as part of this
+	/// template, e.g. by a render or call block.
+	fn nested<'t>(&'t self, template: &'t Template<'t>) -> Resolver<'t, PV, DV> {
+		let mut resolver = Resolver::new(template, self.profile_vars, self.dotfile_vars);
+		resolver.includes = self.includes;
+		resolver.origin = self.origin;
+		resolver.comment_prefixes = self.comment_prefixes;
+		resolver.plugins = self.plugins;
+		resolver.rendering = self.rendering.clone();
+		resolver.calling = self.calling.clone();
+		resolver.trace = self.trace;
+		resolver.inputs = self.inputs;
+		resolver.depth = self.depth + 1;
+
+		resolver
+	}
+
+	/// Records the decision which is described by `message` for the block at
+	/// `pos`, if the decisions are traced.
+	fn trace(&self, pos: BytePos, message: impl FnOnce() -> String) {
+		if let Some(trace) = self.trace {
+			trace.borrow_mut().push(TraceEvent {
+				depth: self.depth,
+				location: self.template.source.get_pos_location(pos),
+				message: message(),
+			});
+		}
+	}
+
+	/// Records that `input` was read, if the inputs are
+	/// [recorded](`Resolver::with_inputs`).
+	fn record(&self, input: Input) {
+		if let Some(inputs) = self.inputs {
+			inputs.borrow_mut().insert(input);
+		}
+	}
+
+	/// Resolves the macro of the `call` block with its arguments.
+	///
+	/// The macro is searched in the template and then in the templates of
+	/// all import blocks in order.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the macro can not be found, if it calls itself
+	/// or if it fails to resolve.
+	fn call(&self, call: &Call) -> Result<String, DiagnosticBuilder> {
+		let name = &self.template.source[call.name];
+
+		let error = |description: String| {
+			DiagnosticBuilder::new(DiagnosticLevel::Error)
+				.message(format!("failed to call macro `{name}`"))
+				.description(description)
+				.primary_span(call.name)
+		};
+
+		if self.calling.iter().any(|calling| calling == name) {
+			return Err(error(String::from("the macro calls itself")));
+		}
+
+		let args = call
+			.args
+			.iter()
+			.map(|arg| arg.value(&self.lookup()).map(Cow::into_owned))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		if let Some(found) = find_macro(self.template, name) {
+			return self
+				.expand(self.template, found, args)
+				.map_err(|err| error(format!("{err:#}")));
+		}
+
+		let imports = self
+			.template
+			.blocks
+			.iter()
+			.filter_map(|block| match &block.kind {
+				BlockKind::Import(import) => Some(&self.template.source[import]),
+				_ => None,
+			});
+
+		for import in imports {
+			let Some(includes) = self.includes else {
+				return Err(error(String::from(
+					"importing other templates is not supported here",
+				)));
+			};
+
+			self.record(Input::Include(import.to_owned()));
+
+			let template = includes
+				.load(import)
+				.and_then(|content| {
+					let template = Template::parse(Source::anonymous(&content))?;
+
+					find_macro(&template, name)
+						.map(|found| self.expand(&template, found, args.clone()))
+						.transpose()
+				})
+				.map_err(|err| error(format!("failed to import template `{import}`: {err:#}")))?;
+
+			if let Some(output) = template {
+				return Ok(output);
+			}
+		}
+
+		Err(error(String::from(
+			"no macro with this name is defined or imported",
+		)))
+	}
+
+	/// Resolves the body of the macro `found` of `template` with the values
+	/// `args` of its parameters.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the number of arguments does not match the
+	/// parameters or if the body fails to resolve.
+	fn expand(&self, template: &Template<'_>, found: &Macro, args: Vec<String>) -> Result<String> {
+		if args.len() != found.params.len() {
+			return Err(eyre!(
+				"expected {} arguments but got {}",
+				found.params.len(),
+				args.len()
+			));
+		}
+
+		let mut resolver = self.nested(template);
+		resolver
+			.calling
+			.push(template.source[found.name].to_owned());
+		resolver.args = found
+			.params
+			.iter()
+			.map(|param| template.source[param].to_owned())
+			.zip(args)
+			.collect();
+
+		resolver.resolve_blocks(&found.body)
+	}
+
 	/// Tries to resolve an [if expression](`super::block::IfExpr`) and returns
 	/// the result of the evaluated expression.
 	///
@@ -339,16 +679,65 @@ where
 	///
 	/// An error is returned if a variable could not be resolved.
 	fn resolve_if_expr(&self, expr: &IfExpr) -> Result<bool, DiagnosticBuilder> {
-		match expr {
-			IfExpr::Compare { var, op, other } => {
-				let var = self.resolve_var(var)?;
-				Ok(op.eval(&var, &self.template.source[other]))
-			}
-			IfExpr::Exists { var } => Ok(self.resolve_var(var).is_ok()),
-			IfExpr::NotExists { var } => Ok(self.resolve_var(var).is_err()),
+		expr.eval(&self.lookup())
+	}
+
+	/// Returns the [lookup](`crate::expr::Lookup`) of variables for
+	/// expressions.
+	///
+	/// Its seed is derived from the name of the machine and the path of the
+	/// template, so stable random values (e.g. `uuid("key")`) only change
+	/// between machines and templates. Relative paths (e.g. of `file_hash`)
+	/// are resolved from the directory of the template.
+	fn lookup<'s>(&'s self) -> impl Lookup<'s, Var, DiagnosticBuilder> + 's {
+		let hostname = condition::builtin("hostname").unwrap_or_default();
+
+		let scope = Scope::new(|var: &Var| self.resolve_var(var))
+			.with_seed(format!("{hostname}\0{}", self.origin))
+			.with_comment_prefix(self.comment_prefix().to_owned());
+
+		let scope = match self.origin {
+			SourceOrigin::File(path) => match path.parent() {
+				Some(dir) => scope.with_base_dir(dir.to_path_buf()),
+				None => scope,
+			},
+			SourceOrigin::Anonymous => scope,
+		};
+
+		Recording {
+			scope,
+			plugins: self.plugins,
+			inputs: self.inputs,
 		}
 	}
 
+	/// Returns the prefix of line comments in the file which is rendered.
+	///
+	/// It is looked up by the extension of the file, or by its name without
+	/// a leading `.` if it has no extension (e.g. `vimrc` for `.vimrc`).
+	/// Defaults to `#`.
+	fn comment_prefix(&self) -> &str {
+		let SourceOrigin::File(path) = self.origin else {
+			return "#";
+		};
+
+		let Some(kind) = file_kind(path) else {
+			return "#";
+		};
+
+		self.comment_prefixes
+			.and_then(|prefixes| {
+				prefixes.iter().find(|(extension, _)| {
+					extension
+						.trim_start_matches('.')
+						.eq_ignore_ascii_case(&kind)
+				})
+			})
+			.map(|(_, prefix)| prefix.as_str())
+			.or_else(|| builtin_comment_prefix(&kind))
+			.unwrap_or("#")
+	}
+
 	/// Tries to resolve a [variable](`super::block::Var`) by looking for the
 	/// value in [`Resolver::profile_vars`], [`Resolver::dotfile_vars`] and the
 	/// system environment.
@@ -360,15 +749,44 @@ where
 	/// - `PUNKTF_TARGET_OS`: Operating system of the compiling system
 	/// - `PUNKTF_TARGET_FAMILY`: Operating system family of the compiling system
 	///
+	/// The value is recorded if the decisions are [traced](`Resolver::with_trace`).
+	///
 	/// # Errors
 	///
 	/// An error is returned if the variable could not be resolved.
 	fn resolve_var(&self, var: &Var) -> Result<Cow<'_, str>, DiagnosticBuilder> {
+		let value = self.find_var(var);
+
+		self.trace(var.name.low, || {
+			let name = &self.template.source[var.name];
+
+			match &value {
+				Ok(value) => format!("variable `{name}` is {value:?}"),
+				Err(_) => format!("variable `{name}` is not set"),
+			}
+		});
+
+		value
+	}
+
+	/// Searches the value of `var` in the arguments of the called macro and
+	/// then in its environments in order.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the variable is not set in any environment.
+	fn find_var(&self, var: &Var) -> Result<Cow<'_, str>, DiagnosticBuilder> {
 		let name = &self.template.source[var.name];
 
+		if let Some((_, value)) = self.args.iter().find(|(param, _)| param == name) {
+			return Ok(Cow::Borrowed(value));
+		}
+
 		for env in var.envs.envs() {
 			match env {
 				VarEnv::Environment => {
+					self.record(Input::Env(name.to_owned()));
+
 					match (name, std::env::var(name)) {
 						("PUNKTF_TARGET_ARCH", Err(std::env::VarError::NotPresent)) => {
 							return Ok(arch!().into())
@@ -406,14 +824,126 @@ where
 	}
 }
 
+/// A [lookup](`Lookup`) which records the inputs which are read by functions
+/// (e.g. `home()`) to the inputs of the resolver and provides its plugins.
+struct Recording<'a, F> {
+	/// The lookup of variables.
+	scope: Scope<F>,
+
+	/// See [`Resolver::plugins`].
+	plugins: Option<&'a Plugins>,
+
+	/// See [`Resolver::inputs`].
+	inputs: Option<&'a RefCell<BTreeSet<Input>>>,
+}
+
+impl<'v, V, E, F> Lookup<'v, V, E> for Recording<'_, F>
+where
+	F: Fn(&V) -> Result<Cow<'v, str>, E>,
+{
+	fn var(&self, var: &V) -> Result<Cow<'v, str>, E> {
+		self.scope.var(var)
+	}
+
+	fn settings(&self) -> Settings<'_> {
+		Settings {
+			plugins: self.plugins,
+			..self.scope.settings()
+		}
+	}
+
+	fn record(&self, input: Input) {
+		if let Some(inputs) = self.inputs {
+			inputs.borrow_mut().insert(input);
+		}
+	}
+}
+
+/// Returns the lowercase extension of the file at `path`, or its name
+/// without a leading `.` if it has no extension.
+fn file_kind(path: &Path) -> Option<String> {
+	path.extension()
+		.or_else(|| path.file_name())
+		.and_then(|kind| kind.to_str())
+		.map(|kind| kind.trim_start_matches('.').to_ascii_lowercase())
+}
+
+/// Returns the built-in prefix of line comments for files of the given
+/// `kind` (see [`file_kind`]).
+fn builtin_comment_prefix(kind: &str) -> Option<&'static str> {
+	Some(match kind {
+		"vim" | "vimrc" | "gvimrc" | "exrc" => "\"",
+		"lua" | "sql" | "hs" | "elm" => "--",
+		"c" | "h" | "cc" | "cpp" | "hpp" | "cs" | "java" | "kt" | "go" | "rs" | "swift"
+		| "dart" | "zig" | "js" | "mjs" | "ts" | "jsx" | "tsx" | "jsonc" | "json5" | "scss"
+		| "less" | "glsl" => "//",
+		"ini" | "lisp" | "el" | "clj" | "scm" | "asm" | "ahk" => ";",
+		"tex" | "sty" | "erl" => "%",
+		"xresources" | "xdefaults" => "!",
+		"bat" | "cmd" => "REM",
+		"sh" | "bash" | "zsh" | "fish" | "ps1" | "psm1" | "py" | "rb" | "pl" | "r" | "toml"
+		| "yaml" | "yml" | "conf" | "cfg" | "env" | "nix" | "gitconfig" | "gitignore"
+		| "gitattributes" | "bashrc" | "zshrc" | "profile" | "bash_profile" | "zprofile"
+		| "inputrc" | "tmux" => "#",
+		_ => return None,
+	})
+}
+
+impl From<FunctionError> for DiagnosticBuilder {
+	fn from(err: FunctionError) -> Self {
+		Self::new(DiagnosticLevel::Error)
+			.message(format!("failed to evaluate function `{}`", err.function))
+			.description(err.message)
+	}
+}
+
+/// Returns the top-level macro `name` of `template`.
+fn find_macro<'t>(template: &'t Template<'_>, name: &str) -> Option<&'t Macro> {
+	template.blocks.iter().find_map(|block| match &block.kind {
+		BlockKind::Macro(found) if &template.source[found.name] == name => Some(found),
+		_ => None,
+	})
+}
+
+/// Omits the first and the last line of the `output` of a block if they only
+/// contain white spaces.
+///
+/// This allows to write the content of e.g. `if` blocks on their own lines
+/// without adding empty lines to the output.
+fn trim_block_lines(output: &str) -> &str {
+	let mut output = output;
+
+	// Check if characters before first line feed are all considered
+	// to be white spaces and if so omit them from the output.
+	if let Some(idx) = output.find('\n') {
+		// include line feed
+		if output[..idx].trim_start().is_empty() {
+			// Also trim line feed
+			output = &output[idx + 1..];
+		}
+	}
+
+	// Check if characters after last line feed are all considered
+	// to be white spaces and if so omit them from the output.
+	if let Some(idx) = output.rfind('\n') {
+		// include line feed
+		if output[idx..].trim_start().is_empty() {
+			output = &output[..idx];
+		}
+	}
+
+	output
+}
+
 #[cfg(test)]
 mod tests {
 	use pretty_assertions::assert_eq;
 
+	use std::collections::HashMap;
+	use std::path::Path;
+
 	use super::*;
 	use crate::profile::variables::Variables;
-	use crate::template::source::Source;
-	use crate::template::Template;
 
 	#[rustfmt::skip]
 	const IF_FMT_TEST_CASES: &[(&str, &str)] = &[
@@ -629,4 +1159,295 @@ World"#
 
 		Ok(())
 	}
+
+	#[test]
+	fn if_compound() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars = Variables::from_items([("OS", "linux"), ("HOST", "dev-box")]);
+
+		let source = Source::anonymous(
+			r#"{{@if {{OS}} == "linux" && {{HOST}} matches "^dev-"}}dev{{@fi}}{{@if !{{SHELL}} || {{OS}} == "windows"}}no shell{{@fi}}"#,
+		);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve::<Variables, Variables>(Some(&vars), None)?,
+			"devno shell"
+		);
+
+		let source = Source::anonymous(r#"{{@if {{EDITOR}} == "vim"}}vim{{@fi}}"#);
+		let template = Template::parse(source)?;
+
+		assert!(template
+			.resolve::<Variables, Variables>(Some(&vars), None)
+			.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn inline_if() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars = Variables::from_items([("OS", "windows"), ("TERM", "alacritty")]);
+
+		let source = Source::anonymous(
+			r#"shell = {{ {{OS}} == "windows" ? "pwsh" : "zsh" }}
+term = {{ {{OS}} == "linux" ? "kitty" : {{OS}} == "macos" ? "iterm" : {{TERM}} }}
+editor = {{ {{EDITOR}} ? {{EDITOR}} : "vi" }}"#,
+		);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve::<Variables, Variables>(Some(&vars), None)?,
+			"shell = pwsh\nterm = alacritty\neditor = vi"
+		);
+
+		let source = Source::anonymous(r#"{{ {{EDITOR}} == "vim" ? "a" : "b" }}"#);
+		let template = Template::parse(source)?;
+
+		assert!(template
+			.resolve::<Variables, Variables>(Some(&vars), None)
+			.is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn value_functions() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars = Variables::from_items([("USER", "jane doe"), ("HOST", "dev-box")]);
+
+		let source = Source::anonymous(
+			r#"name = {{ title({{USER}}) }}
+login = {{ {{USER}} | replace(" ", ".") | upper }}
+{{@if {{HOST}} | starts_with("dev-")}}
+env = {{ split({{HOST}}, "-", "0") }}
+{{@fi}}"#,
+		);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve::<Variables, Variables>(Some(&vars), None)?,
+			"name = Jane Doe\nlogin = JANE.DOE\nenv = dev"
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn value_random_functions() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let resolve = |content: &str, path: &str| {
+			Template::parse(Source::file(Path::new(path), content))?
+				.resolve::<Variables, Variables>(None, None)
+		};
+		let content = r#"id = {{ uuid("id") }}
+port = {{ random("1024", "65535", "port") }}"#;
+
+		let stable = resolve(content, "app/config.toml")?;
+		assert_eq!(resolve(content, "app/config.toml")?, stable);
+		assert_ne!(resolve(content, "other/config.toml")?, stable);
+		assert_ne!(
+			resolve(r#"id = {{ uuid() }}"#, "app/config.toml")?,
+			resolve(r#"id = {{ uuid() }}"#, "app/config.toml")?
+		);
+
+		Ok(())
+	}
+
+	#[test]
+	fn comment() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let prefixes = HashMap::from([(String::from("ps1"), String::from("##"))]);
+		let resolve = |path: &str| {
+			Template::parse(Source::file(
+				Path::new(path),
+				"{{ \"Managed by punktf\" | comment }}",
+			))?
+			.resolve::<Variables, Variables>(None, None)
+		};
+
+		assert_eq!(resolve(".vimrc")?, "\" Managed by punktf");
+		assert_eq!(resolve("nvim/init.lua")?, "-- Managed by punktf");
+		assert_eq!(resolve(".gitconfig")?, "# Managed by punktf");
+		assert_eq!(resolve("Profile.PS1")?, "# Managed by punktf");
+		assert_eq!(resolve("unknown.xyz")?, "# Managed by punktf");
+
+		let template = Template::parse(Source::file(
+			Path::new("Profile.PS1"),
+			"{{ comment(\"Managed by punktf\") }}",
+		))?;
+		assert_eq!(
+			Resolver::new(&template, None::<&Variables>, None::<&Variables>)
+				.with_comment_prefixes(&prefixes)
+				.resolve()?,
+			"## Managed by punktf"
+		);
+
+		Ok(())
+	}
+
+	impl Includes for HashMap<&str, &str> {
+		fn load(&self, name: &str) -> Result<String> {
+			self.get(name)
+				.map(|content| String::from(*content))
+				.ok_or_else(|| eyre!("unknown template"))
+		}
+	}
+
+	#[test]
+	fn render() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars = Variables::from_items([("EDITOR", "nvim")]);
+		let includes: HashMap<_, _> = [
+			(
+				"zsh/aliases.zsh",
+				"alias vim={{EDITOR}}\n{{@render \"zsh/env.zsh\"}}\n",
+			),
+			("zsh/env.zsh", "export EDITOR={{EDITOR}}\n"),
+			("zsh/loop.zsh", "{{@render \"zsh/loop.zsh\"}}"),
+		]
+		.into_iter()
+		.collect();
+
+		let source = Source::anonymous("# zshrc\n{{@render \"zsh/aliases.zsh\"}}\nbindkey -v\n");
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve_with_includes::<Variables, Variables>(Some(&vars), None, &includes)?,
+			"# zshrc\nalias vim=nvim\nexport EDITOR=nvim\nbindkey -v\n"
+		);
+		assert!(template
+			.resolve::<Variables, Variables>(Some(&vars), None)
+			.is_err());
+
+		for content in [r#"{{@render "zsh/loop.zsh"}}"#, r#"{{@render "missing"}}"#] {
+			let template = Template::parse(Source::anonymous(content))?;
+
+			assert!(template
+				.resolve_with_includes::<Variables, Variables>(Some(&vars), None, &includes)
+				.is_err());
+		}
+
+		assert!(Template::parse(Source::anonymous("{{@render zsh/aliases.zsh}}")).is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn indent() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars = Variables::from_items([("CERT", "-----BEGIN-----\nabc\n-----END-----")]);
+		let includes: HashMap<_, _> = [("k8s/env.yaml", "- name: EDITOR\n\n  value: nvim\n")]
+			.into_iter()
+			.collect();
+
+		let source = Source::anonymous(
+			r#"spec:
+  env:{{@render "k8s/env.yaml" | nindent("4")}}
+  cert: |
+{{ {{CERT}} | indent("4") }}"#,
+		);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve_with_includes::<Variables, Variables>(Some(&vars), None, &includes)?,
+			"spec:\n  env:\n    - name: EDITOR\n\n      value: nvim\n  cert: |\n    -----BEGIN-----\n    abc\n    -----END-----"
+		);
+
+		for content in [
+			r#"{{@render "k8s/env.yaml" nindent("4")}}"#,
+			r#"{{@render "k8s/env.yaml" | nindent({{WIDTH}})}}"#,
+			r#"{{@render "k8s/env.yaml" | contains("a")}}"#,
+			r#"{{@import "k8s/env.yaml" | indent("4")}}"#,
+		] {
+			assert!(Template::parse(Source::anonymous(content)).is_err());
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn macros() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars = Variables::from_items([("FONT", "Hack")]);
+		let includes: HashMap<_, _> = [(
+			"partials/colors",
+			"{{@macro color(name, value)}}\n{{name}}: '{{value}}'\n{{@end}}\n",
+		)]
+		.into_iter()
+		.collect();
+
+		let source = Source::anonymous(
+			r##"{{@import "partials/colors"}}
+{{@macro kv(key, value)}}
+{{key}} = {{value}}
+{{@end}}
+{{@call kv("font", {{FONT}})}}
+colors:
+  {{@call color("black", "#000000")}}
+{{@call kv("size", "12")}}
+"##,
+		);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve_with_includes::<Variables, Variables>(Some(&vars), None, &includes)?,
+			"font = Hack\ncolors:\n  black: '#000000'\nsize = 12\n"
+		);
+
+		for content in [
+			r#"{{@call missing()}}"#,
+			r#"{{@macro kv(key, value)}}{{key}}{{@end}}{{@call kv("a")}}"#,
+			r#"{{@macro loop()}}{{@call loop()}}{{@end}}{{@call loop()}}"#,
+			r#"{{@import "missing"}}{{@call color("a", "b")}}"#,
+		] {
+			let template = Template::parse(Source::anonymous(content))?;
+
+			assert!(
+				template
+					.resolve_with_includes::<Variables, Variables>(Some(&vars), None, &includes)
+					.is_err(),
+				"{content}"
+			);
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn raw() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let vars = Variables::from_items([("NAME", "app")]);
+
+		let source = Source::anonymous(
+			r#"name: {{NAME}}
+{{@raw}}
+image: {{ .Values.image | default "nginx" }}
+{{@if x}}{{{ }}}
+{{@end}}
+port: 80
+inline: {{@raw}}{{ .Port }}{{@end}}"#,
+		);
+		let template = Template::parse(source)?;
+
+		assert_eq!(
+			template.resolve::<Variables, Variables>(Some(&vars), None)?,
+			"name: app\nimage: {{ .Values.image | default \"nginx\" }}\n{{@if x}}{{{ }}}\nport: 80\ninline: {{ .Port }}"
+		);
+
+		let source = Source::anonymous("{{@raw}}{{ .Values }}");
+		assert!(Template::parse(source).is_err());
+
+		Ok(())
+	}
 }
@@ -2,10 +2,10 @@
 //! a specific task and a specific [source](`super::source::Source`). It is
 //! used to bundle the diagnostics and emit them after the task has finished.
 
-use color_eyre::eyre::{eyre, Result};
-
-use super::diagnostic::Diagnostic;
-use super::source::Source;
+use super::diagnostic::{Diagnostic, DiagnosticLevel};
+use super::source::{Source, SourceOrigin};
+use super::span::Pos as _;
+use crate::error::TemplateError;
 
 /// A session collects [diagnostics](`super::diagnostic::Diagnostic`) for a
 /// task. Additionally it keeps track if the task failed.
@@ -33,7 +33,7 @@ impl Session {
 	}
 
 	/// Mark the session as failed.
-	pub fn mark_failed(&mut self) {
+	pub const fn mark_failed(&mut self) {
 		self.failed = true;
 	}
 
@@ -47,16 +47,36 @@ impl Session {
 	}
 
 	/// This will consume the session and return `Ok` if [`Session::failed`] is
-	/// `false`. If `failed` is `true` it will return an error.
+	/// `false`. If `failed` is `true` it will return an error which describes
+	/// the first error diagnostic. `source` should be the
+	/// [source](`super::source::Source`) from which all the diagnostics are
+	/// collected.
 	///
 	/// # Errors
 	///
 	/// Returns an error if the session is marked as `failed`.
-	pub fn try_finish(self) -> Result<()> {
-		if self.failed {
-			Err(eyre!("Session contains errors"))
-		} else {
-			Ok(())
+	pub fn try_finish(self, source: &Source<'_>) -> Result<(), TemplateError> {
+		if !self.failed {
+			return Ok(());
 		}
+
+		let diagnostic = self
+			.diagnostics
+			.iter()
+			.find(|diagnostic| diagnostic.level() == &DiagnosticLevel::Error);
+
+		Err(TemplateError {
+			path: match source.origin {
+				SourceOrigin::File(path) => Some(path.to_path_buf()),
+				SourceOrigin::Anonymous => None,
+			},
+			message: diagnostic.map_or_else(
+				|| String::from("Session contains errors"),
+				|diagnostic| diagnostic.message().to_owned(),
+			),
+			span: diagnostic
+				.and_then(Diagnostic::primary_span)
+				.map(|span| span.low.as_usize()..span.high.as_usize()),
+		})
 	}
 }
@@ -276,7 +276,7 @@ impl<'a> Source<'a> {
 
 		let cpos = CharPos::from_usize((pos.as_usize() + offset) - count);
 
-		log::trace!("Translating pos: {} > {}", pos, cpos,);
+		tracing::trace!("Translating pos: {} > {}", pos, cpos,);
 
 		cpos
 	}
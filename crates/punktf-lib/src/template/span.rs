@@ -4,6 +4,8 @@
 use std::fmt;
 use std::ops::{Deref, Index};
 
+use serde::{Deserialize, Serialize};
+
 // COPYRIGHT by Rust project contributors
 // <https://github.com/rust-lang/rust/graphs/contributors>
 //
@@ -87,7 +89,7 @@ macro_rules! pos {
 
 pos! {
 	/// A position of a byte.
-	#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+	#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 	pub struct BytePos(pub u32);
 
 	/// A position of a character.
@@ -100,7 +102,7 @@ pos! {
 //
 // Inspired by <https://github.com/rust-lang/rust/blob/362e0f55eb1f36d279e5c4a58fb0fe5f9a2c579d/compiler/rustc_span/src/lib.rs#L419>.
 /// A span with a [start position](`ByteSpan::low`) and an [end position](`ByteSpan::high`).
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct ByteSpan {
 	/// Start position of the span.
 	pub low: BytePos,
@@ -220,6 +222,7 @@ impl Index<&ByteSpan> for str {
 }
 
 /// Associates a [`ByteSpan`] with a generic `value`.
+#[derive(Serialize, Deserialize)]
 pub struct Spanned<T> {
 	/// A span.
 	pub span: ByteSpan,
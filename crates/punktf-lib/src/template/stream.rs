@@ -0,0 +1,222 @@
+//! Renders [templates](`super::Template`) chunk-wise from a reader into a
+//! writer.
+//!
+//! Resolving a [`Template`](`super::Template`) needs the whole content and
+//! the resolved output in memory. For large generated files this is a lot, so
+//! the [`StreamRenderer`] instead reads the template line by line and renders
+//! it in chunks. A chunk always ends after a line feed outside of any `if`
+//! block, which makes the output identical to rendering the whole template at
+//! once.
+//!
+//! The memory used is bound by the chunk size, except when a single block
+//! (e.g. a large `if` block) is bigger. In that case the chunk grows until the
+//! block is closed.
+//!
+//! As macros can be called anywhere in a template, the rest of a template is
+//! rendered at once from the first macro definition on. Macros must be
+//! defined before they are called in such templates.
+
+use std::io::{BufRead, Write};
+
+use color_eyre::eyre::{Context as _, Result};
+
+use super::parse::last_split_point;
+use super::source::{Source, SourceOrigin};
+use super::{Context, Template};
+use crate::error::TemplateError;
+
+/// Default size in bytes after which a chunk is rendered.
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Renders a template read from a [`BufRead`] chunk-wise into a [`Write`].
+///
+/// # Note
+///
+/// Diagnostics emitted while rendering reference the line numbers within the
+/// chunk. The [`TemplateError::span`] of a returned error is relative to the
+/// whole template.
+#[derive(Debug, Clone)]
+pub struct StreamRenderer<'a> {
+	/// Origin of the template content.
+	origin: SourceOrigin<'a>,
+
+	/// Variables used to render the template.
+	context: &'a Context,
+
+	/// Size in bytes after which a chunk is rendered.
+	chunk_size: usize,
+}
+
+impl<'a> StreamRenderer<'a> {
+	/// Creates a new renderer which uses the variables of `context`.
+	pub const fn new(context: &'a Context) -> Self {
+		Self {
+			origin: SourceOrigin::Anonymous,
+			context,
+			chunk_size: DEFAULT_CHUNK_SIZE,
+		}
+	}
+
+	/// Sets the origin of the template content, which is used in errors and
+	/// diagnostics.
+	pub const fn with_origin(mut self, origin: SourceOrigin<'a>) -> Self {
+		self.origin = origin;
+		self
+	}
+
+	/// Sets the size in bytes after which a chunk is rendered.
+	///
+	/// Defaults to [`DEFAULT_CHUNK_SIZE`].
+	pub const fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+		self.chunk_size = chunk_size;
+		self
+	}
+
+	/// Reads the template from `reader` and writes the rendered output to
+	/// `writer`.
+	///
+	/// Output of chunks which were rendered before an error occurred has
+	/// already been written to `writer`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if reading from `reader` or writing to `writer`
+	/// fails.
+	/// An error is returned if a chunk of the template could not be parsed or
+	/// resolved.
+	pub fn render<R: BufRead, W: Write>(&self, mut reader: R, mut writer: W) -> Result<()> {
+		let mut buffer = String::new();
+		let mut target = self.chunk_size;
+		let mut offset = 0;
+
+		loop {
+			let mut eof = false;
+
+			while buffer.len() < target {
+				if reader
+					.read_line(&mut buffer)
+					.wrap_err("Failed to read template")?
+					== 0
+				{
+					eof = true;
+					break;
+				}
+			}
+
+			if eof {
+				break;
+			}
+
+			if let Some(split) = last_split_point(&buffer) {
+				self.render_chunk(&buffer[..split], offset, &mut writer)?;
+
+				buffer.drain(..split);
+				offset += split;
+				target = self.chunk_size;
+			} else {
+				// No safe point to split; read more until the block is closed.
+				target = buffer.len() + self.chunk_size;
+			}
+		}
+
+		if !buffer.is_empty() {
+			self.render_chunk(&buffer, offset, &mut writer)?;
+		}
+
+		writer.flush().wrap_err("Failed to write rendered template")
+	}
+
+	/// Renders a single `chunk` which starts at the byte `offset` within the
+	/// whole template.
+	fn render_chunk<W: Write>(&self, chunk: &str, offset: usize, writer: &mut W) -> Result<()> {
+		let output = Template::parse(Source::new(self.origin, chunk))
+			.and_then(|template| template.render(self.context))
+			.map_err(|mut err| {
+				if let Some(err) = err.downcast_mut::<TemplateError>() {
+					err.span = err
+						.span
+						.take()
+						.map(|span| span.start + offset..span.end + offset);
+				}
+
+				err
+			})?;
+
+		writer
+			.write_all(output.as_bytes())
+			.wrap_err("Failed to write rendered template")
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const CONTENT: &str = r#"[header]
+name = {{NAME}}
+{{!--
+	A comment spanning
+	multiple lines
+--}}
+{{@if {{&OS}} == "linux"}}
+[linux]
+escaped = {{{ {{NAME}} }}}
+	{{@if {{#ARCH}}}}
+arch = {{#ARCH}}
+	{{@fi}}
+{{@else}}
+[other]
+{{@fi}}
+{{@print Rendering...}}
+[footer]
+values = 1, 2, 3
+"#;
+
+	fn render_streamed(content: &str, context: &Context, chunk_size: usize) -> Result<String> {
+		let mut output = Vec::new();
+
+		StreamRenderer::new(context)
+			.with_chunk_size(chunk_size)
+			.render(content.as_bytes(), &mut output)?;
+
+		Ok(String::from_utf8(output)?)
+	}
+
+	#[test]
+	fn stream_matches_render() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let content = CONTENT.repeat(20);
+		let template = Template::parse(Source::anonymous(&content))?;
+
+		for os in ["linux", "windows"] {
+			let context = Context::new()
+				.with_profile_var("ARCH", "x86_64")
+				.with_dotfile_var("NAME", "punktf")
+				.with_dotfile_var("OS", os);
+
+			let expected = template.render(&context)?;
+
+			for chunk_size in [1, 16, 100, DEFAULT_CHUNK_SIZE] {
+				assert_eq!(render_streamed(&content, &context, chunk_size)?, expected);
+			}
+		}
+
+		Ok(())
+	}
+
+	#[test]
+	fn stream_error_span() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let content = format!("{}{{{{ IN-VALID }}}}\n", "text\n".repeat(10));
+		let context = Context::new();
+
+		let err = render_streamed(&content, &context, 8).unwrap_err();
+		let err = err.downcast_ref::<TemplateError>().expect("Template error");
+
+		assert_eq!(err.span, Some(50..64));
+
+		Ok(())
+	}
+}
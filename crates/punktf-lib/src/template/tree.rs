@@ -0,0 +1,119 @@
+//! Prints the parsed [blocks](`super::block::Block`) of a
+//! [template](`super::Template`) as an indented tree, which helps to find out
+//! how a template was understood by the parser.
+
+use std::fmt::Write as _;
+
+use super::block::{Block, BlockKind, If, Macro};
+use super::span::ByteSpan;
+use super::Template;
+
+/// Maximum amount of characters of the content of a block which are printed.
+const MAX_SNIPPET_LEN: usize = 60;
+
+/// Writes the `blocks` of `template` with the given `depth` of indentation to
+/// `out`, one line per block.
+pub(super) fn write_blocks(
+	out: &mut String,
+	template: &Template<'_>,
+	blocks: &[Block],
+	depth: usize,
+) {
+	for block in blocks {
+		write_block(out, template, block, depth);
+	}
+}
+
+/// Writes `block` and all its nested blocks to `out`.
+fn write_block(out: &mut String, template: &Template<'_>, block: &Block, depth: usize) {
+	let source = &template.source;
+	let snippet = |span: ByteSpan| snippet(&source[span]);
+
+	let description = match &block.kind {
+		BlockKind::Text => format!("text {}", snippet(block.span)),
+		BlockKind::Comment => String::from("comment"),
+		BlockKind::Escaped(inner) => format!("escaped {}", snippet(*inner)),
+		BlockKind::Raw(inner) => format!("raw {}", snippet(*inner)),
+		BlockKind::Var(var) => format!("var {} {}", &source[var.name], var.envs),
+		BlockKind::Value(_) => format!("value {}", snippet(block.span)),
+		BlockKind::Print(inner) => format!("print {}", snippet(*inner)),
+		BlockKind::Render(_) => format!("render {}", snippet(block.span)),
+		BlockKind::Import(name) => format!("import {}", snippet(*name)),
+		BlockKind::Call(_) => format!("call {}", snippet(block.span)),
+		BlockKind::Macro(Macro {
+			name,
+			params,
+			body,
+			end,
+		}) => {
+			let params: Vec<_> = params.iter().map(|param| &source[param]).collect();
+			let head = format!("macro {}({})", &source[name], params.join(", "));
+
+			write_line(out, template, block.span, depth, &head);
+			write_blocks(out, template, body, depth + 1);
+			write_line(out, template, *end, depth, "end");
+
+			return;
+		}
+		BlockKind::If(If {
+			head: (head, head_nested),
+			elifs,
+			els,
+			end,
+		}) => {
+			let head = *head.span();
+			write_line(out, template, head, depth, &format!("if {}", snippet(head)));
+			write_blocks(out, template, head_nested, depth + 1);
+
+			for (elif, elif_nested) in elifs {
+				let elif = *elif.span();
+				write_line(
+					out,
+					template,
+					elif,
+					depth,
+					&format!("elif {}", snippet(elif)),
+				);
+				write_blocks(out, template, elif_nested, depth + 1);
+			}
+
+			if let Some((els, els_nested)) = els {
+				write_line(out, template, *els, depth, "else");
+				write_blocks(out, template, els_nested, depth + 1);
+			}
+
+			write_line(out, template, *end, depth, "fi");
+
+			return;
+		}
+	};
+
+	write_line(out, template, block.span, depth, &description);
+}
+
+/// Writes one line with the location of `span` and the `description` of the
+/// block to `out`.
+fn write_line(
+	out: &mut String,
+	template: &Template<'_>,
+	span: ByteSpan,
+	depth: usize,
+	description: &str,
+) {
+	let location = template.source.get_pos_location(*span.low());
+	let _ = writeln!(
+		out,
+		"{}{} {description}",
+		"  ".repeat(depth),
+		location.display()
+	);
+}
+
+/// Returns `content` as quoted and escaped string, which is shortened to
+/// [`MAX_SNIPPET_LEN`] characters.
+fn snippet(content: &str) -> String {
+	match content.char_indices().nth(MAX_SNIPPET_LEN) {
+		Some((idx, _)) => format!("{:?}...", &content[..idx]),
+		None => format!("{content:?}"),
+	}
+}
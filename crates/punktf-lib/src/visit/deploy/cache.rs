@@ -0,0 +1,595 @@
+//! Persistent cache of compiled templates for incremental deployments.
+//!
+//! For each deployed template the cache stores a hash of all inputs which
+//! influence the compiled content together with a hash of the content which
+//! was written to the target. The inputs are the template source, variables
+//! and content transformers, as well as everything the template read from
+//! outside while it was compiled (environment variables, files, rendered
+//! templates and the home directory, see [`Input`]). The latter are recorded
+//! in the cache, so their current values can be hashed without compiling the
+//! template again. Templates which read values that change with every
+//! compilation (e.g. `now()` or `uuid()`) are not cached.
+//!
+//! When a [`Deployer`](`super::Deployer`) with a cache (see
+//! [`DeployOptions::with_cache`](`super::DeployOptions::with_cache`))
+//! encounters a template whose inputs hash is unchanged and whose target still
+//! has the recorded content, the template is not compiled again.
+//!
+//! If [`DeployOptions::fast_compare`](`super::DeployOptions::fast_compare`) is
+//! set, the cache also stores the size and modification time of the source and
+//! the target of each copied file. A file whose source and target both still
+//! have the recorded [`FileStamp`]s is considered unchanged without reading
+//! their contents.
+//!
+//! The cache is stored as a plain text file with one entry per line.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::UNIX_EPOCH;
+
+use sha2::{Digest as _, Sha256};
+
+use crate::expr::Input;
+use crate::profile::condition;
+use crate::profile::LayeredProfile;
+use crate::template::Includes;
+use crate::visit::deploy::filesystem::Metadata;
+use crate::visit::File;
+
+/// First line of a cache file, which identifies the format.
+const HEADER: &str = "punktf-deploy-cache 3";
+
+/// A SHA-256 hash of some content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ContentHash([u8; 32]);
+
+impl ContentHash {
+	/// Hashes the given `content`.
+	pub fn of(content: &[u8]) -> Self {
+		Self(Sha256::digest(content).into())
+	}
+
+	/// Finishes `hasher` and returns the resulting hash.
+	pub(crate) fn from_hasher(hasher: Sha256) -> Self {
+		Self(hasher.finalize().into())
+	}
+}
+
+impl fmt::Display for ContentHash {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+	}
+}
+
+impl FromStr for ContentHash {
+	type Err = &'static str;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if s.len() != 64 || !s.is_ascii() {
+			return Err("Expected 64 hexadecimal digits");
+		}
+
+		let mut hash = [0; 32];
+
+		for (byte, digits) in hash.iter_mut().zip(s.as_bytes().chunks(2)) {
+			let digits = std::str::from_utf8(digits).map_err(|_| "Invalid hexadecimal digit")?;
+			*byte = u8::from_str_radix(digits, 16).map_err(|_| "Invalid hexadecimal digit")?;
+		}
+
+		Ok(Self(hash))
+	}
+}
+
+/// A cached template which was deployed to a target.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheEntry {
+	/// Hash of all inputs of the template (see [`reads_hash`]).
+	pub inputs: ContentHash,
+
+	/// Inputs which the template read from outside while it was compiled.
+	pub reads: BTreeSet<Input>,
+
+	/// Hash of the compiled content which was written to the target.
+	pub target: ContentHash,
+}
+
+/// Size and modification time of a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileStamp {
+	/// Size of the file in bytes.
+	pub len: u64,
+
+	/// Time of the last modification in nanoseconds since the unix epoch.
+	pub modified: u128,
+}
+
+impl FileStamp {
+	/// Creates the stamp of a file with the given `metadata`.
+	///
+	/// Returns `None` if the modification time is unknown.
+	pub fn of(metadata: &Metadata) -> Option<Self> {
+		let modified = metadata.modified?.duration_since(UNIX_EPOCH).ok()?;
+
+		Some(Self {
+			len: metadata.len,
+			modified: modified.as_nanos(),
+		})
+	}
+}
+
+impl fmt::Display for FileStamp {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}:{}", self.len, self.modified)
+	}
+}
+
+impl FromStr for FileStamp {
+	type Err = &'static str;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let (len, modified) = s.split_once(':').ok_or("Expected `<len>:<modified>`")?;
+
+		Ok(Self {
+			len: len.parse().map_err(|_| "Invalid length")?,
+			modified: modified.parse().map_err(|_| "Invalid modification time")?,
+		})
+	}
+}
+
+/// A file which was copied to a target.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct FileEntry {
+	/// Stamp of the source at the time it was deployed.
+	pub source: FileStamp,
+
+	/// Stamp of the target after it was deployed.
+	pub target: FileStamp,
+}
+
+/// Compiled templates and copied files of previous deployments by their target
+/// path.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct DeployCache {
+	/// The templates by the path of their target.
+	templates: BTreeMap<PathBuf, CacheEntry>,
+
+	/// The copied files by the path of their target.
+	files: BTreeMap<PathBuf, FileEntry>,
+}
+
+impl DeployCache {
+	/// Loads the cache from the file at `path`.
+	///
+	/// An empty cache is returned if the file does not exist or was written
+	/// by an incompatible version. Invalid entries are ignored.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file exists but could not be read.
+	pub fn load(path: &Path) -> io::Result<Self> {
+		let content = match fs::read_to_string(path) {
+			Ok(content) => content,
+			Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+			Err(err) => return Err(err),
+		};
+
+		let mut lines = content.lines();
+
+		if lines.next() != Some(HEADER) {
+			tracing::debug!("Ignoring deploy cache with unknown format");
+			return Ok(Self::default());
+		}
+
+		let mut cache = Self::default();
+		// Target of the last template, to which the following reads belong
+		let mut template: Option<PathBuf> = None;
+
+		for line in lines {
+			if let Some(read) = line.strip_prefix("read ") {
+				let Some(target) = &template else {
+					continue;
+				};
+
+				match parse_read(read) {
+					Some(read) => {
+						if let Some(entry) = cache.templates.get_mut(target) {
+							entry.reads.insert(read);
+						}
+					}
+					// An entry with an unknown read could miss a change
+					None => {
+						cache.templates.remove(target);
+					}
+				}
+
+				continue;
+			}
+
+			template = None;
+			let mut parts = line.splitn(4, ' ');
+
+			let (Some(kind), Some(first), Some(second), Some(path)) =
+				(parts.next(), parts.next(), parts.next(), parts.next())
+			else {
+				continue;
+			};
+
+			match kind {
+				"template" => {
+					if let (Ok(inputs), Ok(target)) = (first.parse(), second.parse()) {
+						let entry = CacheEntry {
+							inputs,
+							reads: BTreeSet::new(),
+							target,
+						};
+
+						cache.insert(path.into(), entry);
+						template = Some(path.into());
+					}
+				}
+				"file" => {
+					if let (Ok(source), Ok(target)) = (first.parse(), second.parse()) {
+						cache.insert_file(path.into(), FileEntry { source, target });
+					}
+				}
+				_ => {}
+			}
+		}
+
+		Ok(cache)
+	}
+
+	/// Writes the cache to the file at `path`.
+	///
+	/// Missing parent directories are created. Entries whose target path or
+	/// reads can not be represented on a single line are not written.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the file could not be written.
+	pub fn save(&self, path: &Path) -> io::Result<()> {
+		/// Returns `target` if it can be written on a single line.
+		fn line_path(target: &Path) -> Option<&str> {
+			target.to_str().filter(|target| !target.contains('\n'))
+		}
+
+		if let Some(parent) = path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+
+		let mut content = format!("{HEADER}\n");
+
+		for (target, entry) in &self.templates {
+			let Some(target) = line_path(target) else {
+				continue;
+			};
+
+			let Some(reads) = entry
+				.reads
+				.iter()
+				.map(|read| read_line(read).filter(|line| !line.contains('\n')))
+				.collect::<Option<Vec<_>>>()
+			else {
+				continue;
+			};
+
+			content.push_str(&format!(
+				"template {} {} {target}\n",
+				entry.inputs, entry.target
+			));
+
+			for read in reads {
+				content.push_str(&format!("read {read}\n"));
+			}
+		}
+
+		for (target, entry) in &self.files {
+			if let Some(target) = line_path(target) {
+				content.push_str(&format!(
+					"file {} {} {target}\n",
+					entry.source, entry.target
+				));
+			}
+		}
+
+		fs::write(path, content)
+	}
+
+	/// Returns the entry for the template which was deployed to `target`.
+	pub fn get(&self, target: &Path) -> Option<&CacheEntry> {
+		self.templates.get(target)
+	}
+
+	/// Records that a template with the given entry was deployed to `target`.
+	///
+	/// A copied file which was previously recorded for `target` is removed.
+	pub fn insert(&mut self, target: PathBuf, entry: CacheEntry) {
+		self.files.remove(&target);
+		self.templates.insert(target, entry);
+	}
+
+	/// Returns the entry for the file which was copied to `target`.
+	pub fn get_file(&self, target: &Path) -> Option<&FileEntry> {
+		self.files.get(target)
+	}
+
+	/// Records that a file with the given entry was copied to `target`.
+	///
+	/// A template which was previously recorded for `target` is removed.
+	pub fn insert_file(&mut self, target: PathBuf, entry: FileEntry) {
+		self.templates.remove(&target);
+		self.files.insert(target, entry);
+	}
+
+	/// Returns the amount of entries.
+	pub fn len(&self) -> usize {
+		self.templates.len() + self.files.len()
+	}
+
+	/// Checks if the cache has no entries.
+	pub fn is_empty(&self) -> bool {
+		self.templates.is_empty() && self.files.is_empty()
+	}
+}
+
+/// Adds a length prefixed `value` to the hash, so that the boundaries of
+/// consecutive values are unambiguous.
+fn update(hasher: &mut Sha256, value: &[u8]) {
+	hasher.update((value.len() as u64).to_le_bytes());
+	hasher.update(value);
+}
+
+/// Hashes all inputs which influence the compiled `content` of the template
+/// `file` and are known before it is compiled.
+///
+/// These are the version of punktf, the name of the machine (from which
+/// stable random values are derived), the source path, the template itself,
+/// the variables of the profile and the dotfile, all content transformers,
+/// transform commands and formatters and the comment prefixes.
+pub fn inputs_hash(profile: &LayeredProfile, file: &File<'_>, content: &str) -> ContentHash {
+	let mut hasher = Sha256::new();
+
+	update(&mut hasher, env!("CARGO_PKG_VERSION").as_bytes());
+	update(
+		&mut hasher,
+		condition::builtin("hostname")
+			.unwrap_or_default()
+			.as_bytes(),
+	);
+	update(&mut hasher, file.source_path.to_string_lossy().as_bytes());
+	update(&mut hasher, content.as_bytes());
+
+	let profile_vars: BTreeMap<_, _> = profile
+		.variables()
+		.inner
+		.iter()
+		.map(|(name, (_, value))| (name, value))
+		.collect();
+
+	for (name, value) in profile_vars {
+		update(&mut hasher, name.as_bytes());
+		update(&mut hasher, value.as_bytes());
+	}
+
+	// Separates the profile from the dotfile variables
+	update(&mut hasher, &[]);
+
+	if let Some(variables) = &file.dotfile().variables {
+		for (name, value) in variables.inner.iter().collect::<BTreeMap<_, _>>() {
+			update(&mut hasher, name.as_bytes());
+			update(&mut hasher, value.as_bytes());
+		}
+	}
+
+	for transformer in profile
+		.transformers()
+		.chain(file.dotfile().transformers.iter())
+	{
+		update(&mut hasher, format!("{transformer:?}").as_bytes());
+	}
+
+	// Separates the content transformers from the transform commands
+	update(&mut hasher, &[]);
+
+	for command in &file.dotfile().transform {
+		update(&mut hasher, command.command().as_bytes());
+	}
+
+	// Separates the transform commands from the formatter
+	update(&mut hasher, &[]);
+
+	if let Some(formatter) = profile.formatter_for(&file.target_path) {
+		update(&mut hasher, format!("{formatter:?}").as_bytes());
+	}
+
+	// Separates the formatter from the comment prefixes
+	update(&mut hasher, &[]);
+
+	for (extension, prefix) in profile
+		.comment_prefixes()
+		.iter()
+		.collect::<BTreeMap<_, _>>()
+	{
+		update(&mut hasher, extension.as_bytes());
+		update(&mut hasher, prefix.as_bytes());
+	}
+
+	ContentHash::from_hasher(hasher)
+}
+
+/// Hashes the current values of the `reads` of a template (see
+/// [`CacheEntry::reads`]) together with the hash of its other `inputs` (see
+/// [`inputs_hash`]). Rendered and imported templates are loaded with
+/// `includes`.
+///
+/// Returns `None` if one of the reads changes with every compilation, in
+/// which case the template can not be cached.
+pub fn reads_hash(
+	inputs: &ContentHash,
+	reads: &BTreeSet<Input>,
+	includes: &dyn Includes,
+) -> Option<ContentHash> {
+	let mut hasher = Sha256::new();
+
+	update(&mut hasher, &inputs.0);
+
+	for read in reads {
+		update(&mut hasher, read_line(read).unwrap_or_default().as_bytes());
+
+		// Prefixes the value to distinguish missing from empty values
+		let value = match read {
+			Input::Env(name) => std::env::var_os(name)
+				.map(|value| value.to_string_lossy().into_owned().into_bytes()),
+			Input::File(path) => fs::read(path)
+				.ok()
+				.map(|content| ContentHash::of(&content).0.to_vec()),
+			Input::Include(name) => includes.load(name).ok().map(String::into_bytes),
+			Input::Home => {
+				std::env::home_dir().map(|home| home.to_string_lossy().into_owned().into_bytes())
+			}
+			Input::Volatile => return None,
+		};
+
+		match value {
+			Some(value) => {
+				update(&mut hasher, &[1]);
+				update(&mut hasher, &value);
+			}
+			None => update(&mut hasher, &[0]),
+		}
+	}
+
+	Some(ContentHash::from_hasher(hasher))
+}
+
+/// Returns the line of `read` in the cache file (without the `read` prefix),
+/// or `None` if it can not be represented.
+fn read_line(read: &Input) -> Option<String> {
+	Some(match read {
+		Input::Env(name) => format!("env {name}"),
+		Input::File(path) => format!("file {}", path.to_str()?),
+		Input::Include(name) => format!("include {name}"),
+		Input::Home => String::from("home"),
+		Input::Volatile => return None,
+	})
+}
+
+/// Parses a line of the cache file which was written by [`read_line`].
+fn parse_read(line: &str) -> Option<Input> {
+	let (kind, value) = line.split_once(' ').unwrap_or((line, ""));
+
+	Some(match kind {
+		"env" => Input::Env(value.to_owned()),
+		"file" => Input::File(PathBuf::from(value)),
+		"include" => Input::Include(value.to_owned()),
+		"home" => Input::Home,
+		_ => return None,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn content_hash_roundtrip() {
+		let hash = ContentHash::of(b"punktf");
+		let hex = hash.to_string();
+
+		assert_eq!(hex.len(), 64);
+		assert_eq!(hex.parse(), Ok(hash));
+		assert!("xyz".parse::<ContentHash>().is_err());
+		assert!("zz".repeat(32).parse::<ContentHash>().is_err());
+	}
+
+	#[test]
+	fn reads_hash_of_template() {
+		/// Loads all templates with the same content.
+		struct Fixed(&'static str);
+
+		impl Includes for Fixed {
+			fn load(&self, _: &str) -> color_eyre::Result<String> {
+				Ok(self.0.to_owned())
+			}
+		}
+
+		let inputs = ContentHash::of(b"inputs");
+		let reads = BTreeSet::from([
+			Input::Env(String::from("PUNKTF_TEST_CACHE_READS")),
+			Input::Include(String::from("zsh/env.zsh")),
+			Input::Home,
+		]);
+		let hash = |includes: &Fixed| reads_hash(&inputs, &reads, includes);
+
+		std::env::remove_var("PUNKTF_TEST_CACHE_READS");
+		let unset = hash(&Fixed("a"));
+		assert!(unset.is_some());
+		assert_eq!(hash(&Fixed("a")), unset);
+		assert_ne!(hash(&Fixed("b")), unset);
+
+		std::env::set_var("PUNKTF_TEST_CACHE_READS", "");
+		assert_ne!(hash(&Fixed("a")), unset);
+
+		let mut volatile = reads.clone();
+		volatile.insert(Input::Volatile);
+		assert_eq!(reads_hash(&inputs, &volatile, &Fixed("a")), None);
+	}
+
+	#[test]
+	fn save_and_load() -> io::Result<()> {
+		let dir = tempfile::tempdir()?;
+		let path = dir.path().join("cache").join("deploy");
+
+		assert!(DeployCache::load(&path)?.is_empty());
+
+		let entry = CacheEntry {
+			inputs: ContentHash::of(b"inputs"),
+			reads: BTreeSet::from([
+				Input::Env(String::from("EDITOR")),
+				Input::File(PathBuf::from("/home/demo/key file.pub")),
+				Input::Include(String::from("zsh/env.zsh")),
+				Input::Home,
+			]),
+			target: ContentHash::of(b"target"),
+		};
+
+		let file_entry = FileEntry {
+			source: FileStamp {
+				len: 4,
+				modified: 1_700_000_000_000_000_000,
+			},
+			target: "4:1700000000500000000".parse().expect("Stamp to be valid"),
+		};
+
+		let mut cache = DeployCache::default();
+		cache.insert(PathBuf::from("/home/demo/.bash rc"), entry.clone());
+		cache.insert_file(PathBuf::from("/home/demo/font.ttf"), file_entry);
+		cache.save(&path)?;
+
+		let loaded = DeployCache::load(&path)?;
+		assert_eq!(loaded, cache);
+		assert_eq!(loaded.len(), 2);
+		assert_eq!(loaded.get(Path::new("/home/demo/.bash rc")), Some(&entry));
+		assert_eq!(
+			loaded.get_file(Path::new("/home/demo/font.ttf")),
+			Some(&file_entry)
+		);
+
+		// Entries with unknown reads are ignored
+		let content = fs::read_to_string(&path)?.replace("read home", "read hostname");
+		fs::write(&path, content)?;
+		assert_eq!(
+			DeployCache::load(&path)?.get(Path::new("/home/demo/.bash rc")),
+			None
+		);
+
+		fs::write(&path, "punktf-deploy-cache 0\n")?;
+		assert!(DeployCache::load(&path)?.is_empty());
+
+		Ok(())
+	}
+}
@@ -0,0 +1,50 @@
+//! Cancellation of a running deployment.
+//!
+//! A [`CancellationToken`] can be attached to a deployer with
+//! [`Deployer::with_cancellation`](`super::Deployer::with_cancellation`). Once
+//! the token is cancelled, the item which is currently deployed is finished
+//! and all remaining items are marked as
+//! [cancelled](`crate::visit::deploy::deployment::ItemStatus::Cancelled`).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A shared flag which signals that a deployment should be cancelled.
+///
+/// Clones of the token share the same flag, so the deployment can be
+/// cancelled from another thread (e.g. a signal handler).
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+	/// Creates a new token which is not cancelled.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Requests the cancellation of all deployments using this token.
+	pub fn cancel(&self) {
+		self.0.store(true, Ordering::SeqCst);
+	}
+
+	/// Checks if the cancellation was requested.
+	pub fn is_cancelled(&self) -> bool {
+		self.0.load(Ordering::SeqCst)
+	}
+}
+
+impl From<Arc<AtomicBool>> for CancellationToken {
+	/// Uses an existing `flag` as token, which is cancelled once it is set to
+	/// `true`.
+	fn from(flag: Arc<AtomicBool>) -> Self {
+		Self(flag)
+	}
+}
+
+impl PartialEq for CancellationToken {
+	fn eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.0, &other.0)
+	}
+}
+
+impl Eq for CancellationToken {}
@@ -8,18 +8,23 @@ use std::time::{Duration, SystemTime, SystemTimeError};
 
 use serde::{Deserialize, Serialize};
 
+use crate::error::Error;
+use crate::profile::assertion::Assertion;
 use crate::profile::dotfile::Dotfile;
 use crate::profile::Priority;
 
 /// Contains the status of a deployed item.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 pub enum ItemStatus {
 	/// The item was successfully created.
 	Success,
 	/// The item deployment failed.
-	Failed(Cow<'static, str>),
+	Failed(Error),
 	/// The item deployment was skipped.
 	Skipped(Cow<'static, str>),
+	/// The item was not deployed as the deployment was cancelled.
+	Cancelled,
 }
 
 impl ItemStatus {
@@ -29,8 +34,8 @@ impl ItemStatus {
 	}
 
 	/// Marks the item operation as failed.
-	pub fn failed<S: Into<Cow<'static, str>>>(reason: S) -> Self {
-		Self::Failed(reason.into())
+	pub fn failed<E: Into<Error>>(error: E) -> Self {
+		Self::Failed(error.into())
 	}
 
 	/// Indicates that the item operation was skipped.
@@ -38,6 +43,17 @@ impl ItemStatus {
 		Self::Skipped(reason.into())
 	}
 
+	/// Returns a short name of the status which is used to identify it in
+	/// structured logs.
+	pub const fn action(&self) -> &'static str {
+		match self {
+			Self::Success => "deployed",
+			Self::Failed(_) => "failed",
+			Self::Skipped(_) => "skipped",
+			Self::Cancelled => "cancelled",
+		}
+	}
+
 	/// Checks if the item operation was successful.
 	pub fn is_success(&self) -> bool {
 		self == &Self::Success
@@ -52,6 +68,11 @@ impl ItemStatus {
 	pub const fn is_skipped(&self) -> bool {
 		matches!(self, &Self::Skipped(_))
 	}
+
+	/// Checks if the item operation was cancelled.
+	pub const fn is_cancelled(&self) -> bool {
+		matches!(self, &Self::Cancelled)
+	}
 }
 
 impl fmt::Display for ItemStatus {
@@ -60,6 +81,7 @@ impl fmt::Display for ItemStatus {
 			Self::Success => f.write_str("Success"),
 			Self::Failed(reason) => write!(f, "Failed: {reason}"),
 			Self::Skipped(reason) => write!(f, "Skipped: {reason}"),
+			Self::Cancelled => f.write_str("Cancelled"),
 		}
 	}
 }
@@ -75,9 +97,10 @@ where
 
 /// Defines the type of dotfile.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 pub enum DeployedDotfileKind {
 	/// A normal dotfile.
-	Dotfile(Dotfile),
+	Dotfile(Box<Dotfile>),
 	/// A dotfile that is contained in a directory that is deployed.
 	///
 	/// PathBuf is the deploy path of the `parent` dotfile.
@@ -97,14 +120,82 @@ impl DeployedDotfileKind {
 	}
 }
 
+/// Describes how a successful deployment changed the target of an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+pub enum ItemChange {
+	/// The target did not exist before.
+	Created,
+
+	/// The target existed before and was changed.
+	Updated,
+
+	/// The target existed before with the same contents.
+	Unchanged,
+}
+
+impl ItemChange {
+	/// Returns the name of the change.
+	pub const fn as_str(&self) -> &'static str {
+		match self {
+			Self::Created => "created",
+			Self::Updated => "updated",
+			Self::Unchanged => "unchanged",
+		}
+	}
+}
+
+impl fmt::Display for ItemChange {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+/// Timing and size metrics of a deployed dotfile.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+pub struct ItemMetrics {
+	/// Time it took to read, resolve and transform the contents.
+	///
+	/// This is zero if the file was copied directly.
+	pub compile_time: Duration,
+
+	/// Time it took to write the dotfile to the target.
+	pub write_time: Duration,
+
+	/// Amount of bytes written to the target.
+	pub bytes_written: u64,
+}
+
+impl ItemMetrics {
+	/// Returns the total time spent on the dotfile.
+	pub fn total_time(&self) -> Duration {
+		self.compile_time + self.write_time
+	}
+}
+
 /// Stores the result of a dotfile deployment operation.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 pub struct DeployedDotfile {
 	/// The status of the deployed dotfile.
 	pub status: ItemStatus,
 
 	/// The kind of the deployed dotfile.
 	pub kind: DeployedDotfileKind,
+
+	/// Timing and size metrics of the deployment.
+	#[serde(default)]
+	pub metrics: ItemMetrics,
+
+	/// How the target was changed if the deployment was successful.
+	#[serde(default)]
+	pub change: Option<ItemChange>,
+
+	/// SHA-256 hash (hex) of the content deployed to the target if the
+	/// deployer computed it anyway (for templates and transformed files).
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub content_hash: Option<String>,
 }
 
 impl DeployedDotfile {
@@ -117,6 +208,22 @@ impl DeployedDotfile {
 	pub const fn kind(&self) -> &DeployedDotfileKind {
 		&self.kind
 	}
+
+	/// Returns the timing and size metrics of the dotfile operation.
+	pub const fn metrics(&self) -> &ItemMetrics {
+		&self.metrics
+	}
+
+	/// Returns how the target was changed by the dotfile operation.
+	pub const fn change(&self) -> Option<ItemChange> {
+		self.change
+	}
+
+	/// Returns the SHA-256 hash (hex) of the content deployed to the target if
+	/// it is known.
+	pub fn content_hash(&self) -> Option<&str> {
+		self.content_hash.as_deref()
+	}
 }
 
 impl AsRef<ItemStatus> for DeployedDotfile {
@@ -127,12 +234,17 @@ impl AsRef<ItemStatus> for DeployedDotfile {
 
 /// Stores the result of a symlink deployment operation.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 pub struct DeployedSymlink {
 	/// The status of the deployed symlink.
 	pub status: ItemStatus,
 
 	/// The source path of the link.
 	pub source: PathBuf,
+
+	/// How the target was changed if the deployment was successful.
+	#[serde(default)]
+	pub change: Option<ItemChange>,
 }
 
 impl DeployedSymlink {
@@ -145,6 +257,11 @@ impl DeployedSymlink {
 	pub fn source(&self) -> &Path {
 		self.source.as_path()
 	}
+
+	/// Returns how the target was changed by the link operation.
+	pub const fn change(&self) -> Option<ItemChange> {
+		self.change
+	}
 }
 
 impl AsRef<ItemStatus> for DeployedSymlink {
@@ -153,13 +270,46 @@ impl AsRef<ItemStatus> for DeployedSymlink {
 	}
 }
 
+/// Stores the result of an assertion evaluated after the deployment.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
+pub struct EvaluatedAssertion {
+	/// The evaluated assertion.
+	pub assertion: Assertion,
+
+	/// The status of the assertion.
+	///
+	/// Is [`ItemStatus::Failed`] with the reason if the assertion does not
+	/// hold.
+	pub status: ItemStatus,
+}
+
+impl EvaluatedAssertion {
+	/// Returns the evaluated assertion.
+	pub const fn assertion(&self) -> &Assertion {
+		&self.assertion
+	}
+
+	/// Returns the status of the assertion.
+	pub const fn status(&self) -> &ItemStatus {
+		&self.status
+	}
+}
+
+impl AsRef<ItemStatus> for EvaluatedAssertion {
+	fn as_ref(&self) -> &ItemStatus {
+		self.status()
+	}
+}
+
 /// Describes the status of a profile deployment.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 pub enum DeploymentStatus {
 	/// The profile is deployed successfully.
 	Success,
 	/// There were errors during the deployment.
-	Failed(Cow<'static, str>),
+	Failed(Error),
 }
 
 impl DeploymentStatus {
@@ -169,8 +319,8 @@ impl DeploymentStatus {
 	}
 
 	/// Returns a failure.
-	pub fn failed<S: Into<Cow<'static, str>>>(reason: S) -> Self {
-		Self::Failed(reason.into())
+	pub fn failed<E: Into<Error>>(error: E) -> Self {
+		Self::Failed(error.into())
 	}
 
 	/// Checks if the deployment was successful.
@@ -203,8 +353,20 @@ where
 }
 
 /// Describes the deployment of a profile.
+///
+/// The serialized form of a deployment (e.g. a json report) contains a
+/// `schema_version` field. See [`Deployment::SCHEMA_VERSION`] for the
+/// compatibility guarantees.
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "profile-schema", derive(schemars::JsonSchema))]
 pub struct Deployment {
+	/// Version of the serialization format.
+	///
+	/// Reports which were written before the format was versioned have the
+	/// version `0`.
+	#[serde(default, deserialize_with = "deserialize_schema_version")]
+	schema_version: u32,
+
 	/// The time the deployment was started.
 	time_start: SystemTime,
 
@@ -219,9 +381,27 @@ pub struct Deployment {
 
 	/// The links that were deployed.
 	symlinks: HashMap<PathBuf, DeployedSymlink>,
+
+	/// The assertions that were evaluated after the deployment.
+	#[serde(skip_serializing_if = "Vec::is_empty", default)]
+	assertions: Vec<EvaluatedAssertion>,
 }
 
 impl Deployment {
+	/// Current version of the serialization format.
+	///
+	/// The version is only increased for changes which are not backwards
+	/// compatible (e.g. removed or renamed fields). Adding new fields keeps the
+	/// version, so consumers must ignore unknown fields. Reports with a newer
+	/// version than this one can not be deserialized.
+	pub const SCHEMA_VERSION: u32 = 1;
+
+	/// Returns the version of the serialization format this deployment was
+	/// read from.
+	pub const fn schema_version(&self) -> u32 {
+		self.schema_version
+	}
+
 	/// Returns the time the deployment was started.
 	pub const fn time_start(&self) -> &SystemTime {
 		&self.time_start
@@ -252,10 +432,107 @@ impl Deployment {
 		&self.symlinks
 	}
 
+	/// Returns the assertions in the order they were evaluated.
+	pub fn assertions(&self) -> &[EvaluatedAssertion] {
+		&self.assertions
+	}
+
 	/// Builds the deployment.
 	pub fn build() -> DeploymentBuilder {
 		DeploymentBuilder::default()
 	}
+
+	/// Returns a [JSON Schema](https://json-schema.org/) of the serialized
+	/// deployment.
+	#[cfg(feature = "profile-schema")]
+	pub fn json_schema() -> serde_json::Value {
+		schemars::schema_for!(Deployment).to_value()
+	}
+
+	/// Returns the status of all items (dotfiles and symlinks) by their target
+	/// path.
+	fn item_statuses(&self) -> HashMap<&Path, &ItemStatus> {
+		self.dotfiles
+			.iter()
+			.map(|(path, dotfile)| (path.as_path(), dotfile.status()))
+			.chain(
+				self.symlinks
+					.iter()
+					.map(|(path, link)| (path.as_path(), link.status())),
+			)
+			.collect()
+	}
+
+	/// Compares this deployment with a `newer` one.
+	///
+	/// Returns all items which were only part of one of the deployments and all
+	/// items whose status changed. All lists are sorted by the target path.
+	pub fn diff(&self, newer: &Self) -> DeploymentDiff {
+		let old = self.item_statuses();
+		let new = newer.item_statuses();
+
+		let mut diff = DeploymentDiff::default();
+
+		for (path, status) in &new {
+			match old.get(path) {
+				None => diff.added.push(path.to_path_buf()),
+				Some(old_status) if old_status != status => {
+					diff.status_changed.push(ItemStatusChange {
+						path: path.to_path_buf(),
+						old: (*old_status).clone(),
+						new: (*status).clone(),
+					});
+				}
+				Some(_) => {}
+			}
+		}
+
+		diff.removed = old
+			.keys()
+			.filter(|path| !new.contains_key(*path))
+			.map(|path| path.to_path_buf())
+			.collect();
+
+		diff.added.sort();
+		diff.removed.sort();
+		diff.status_changed.sort_by(|a, b| a.path.cmp(&b.path));
+
+		diff
+	}
+}
+
+/// The change of the status of an item between two deployments.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStatusChange {
+	/// Target path of the item.
+	pub path: PathBuf,
+
+	/// Status of the item in the older deployment.
+	pub old: ItemStatus,
+
+	/// Status of the item in the newer deployment.
+	pub new: ItemStatus,
+}
+
+/// The differences between two deployments (see [`Deployment::diff`]).
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeploymentDiff {
+	/// Target paths of items which are only part of the newer deployment.
+	pub added: Vec<PathBuf>,
+
+	/// Target paths of items which are only part of the older deployment.
+	pub removed: Vec<PathBuf>,
+
+	/// Items which are part of both deployments but with a different status.
+	pub status_changed: Vec<ItemStatusChange>,
+}
+
+impl DeploymentDiff {
+	/// Indicates if both deployments contain the same items with the same
+	/// status.
+	pub const fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.removed.is_empty() && self.status_changed.is_empty()
+	}
 }
 
 /// A builder for a [`Deployment`].
@@ -272,6 +549,9 @@ pub struct DeploymentBuilder {
 
 	/// All symlinks which were already process by the deployment process.
 	symlinks: HashMap<PathBuf, DeployedSymlink>,
+
+	/// All assertions which were already evaluated.
+	assertions: Vec<EvaluatedAssertion>,
 }
 
 impl DeploymentBuilder {
@@ -285,8 +565,11 @@ impl DeploymentBuilder {
 		self.dotfiles.insert(
 			path,
 			DeployedDotfile {
-				kind: DeployedDotfileKind::Dotfile(dotfile),
+				kind: DeployedDotfileKind::Dotfile(Box::new(dotfile)),
 				status,
+				metrics: ItemMetrics::default(),
+				change: None,
+				content_hash: None,
 			},
 		);
 
@@ -301,16 +584,70 @@ impl DeploymentBuilder {
 			DeployedDotfile {
 				kind: DeployedDotfileKind::Child(parent),
 				status,
+				metrics: ItemMetrics::default(),
+				change: None,
+				content_hash: None,
 			},
 		);
 
 		self
 	}
 
+	/// Sets the metrics of an already added dotfile.
+	pub fn set_metrics<P: AsRef<Path>>(&mut self, path: P, metrics: ItemMetrics) -> &mut Self {
+		if let Some(dotfile) = self.dotfiles.get_mut(path.as_ref()) {
+			dotfile.metrics = metrics;
+		}
+
+		self
+	}
+
+	/// Sets the SHA-256 hash (hex) of the content of the target of an already
+	/// added dotfile.
+	pub fn set_content_hash<P: AsRef<Path>>(&mut self, path: P, hash: String) -> &mut Self {
+		if let Some(dotfile) = self.dotfiles.get_mut(path.as_ref()) {
+			dotfile.content_hash = Some(hash);
+		}
+
+		self
+	}
+
+	/// Sets how the target of an already added dotfile was changed.
+	pub fn set_change<P: AsRef<Path>>(&mut self, path: P, change: ItemChange) -> &mut Self {
+		if let Some(dotfile) = self.dotfiles.get_mut(path.as_ref()) {
+			dotfile.change = Some(change);
+		}
+
+		self
+	}
+
+	/// Sets how the target of an already added symlink was changed.
+	pub fn set_link_change<P: AsRef<Path>>(&mut self, target: P, change: ItemChange) -> &mut Self {
+		if let Some(link) = self.symlinks.get_mut(target.as_ref()) {
+			link.change = Some(change);
+		}
+
+		self
+	}
+
 	/// Adds a symlink with the given `status` to the builder.
 	pub fn add_link(&mut self, source: PathBuf, target: PathBuf, status: ItemStatus) -> &mut Self {
-		self.symlinks
-			.insert(target, DeployedSymlink { source, status });
+		self.symlinks.insert(
+			target,
+			DeployedSymlink {
+				source,
+				status,
+				change: None,
+			},
+		);
+
+		self
+	}
+
+	/// Adds an evaluated assertion with the given `status` to the builder.
+	pub fn add_assertion(&mut self, assertion: Assertion, status: ItemStatus) -> &mut Self {
+		self.assertions
+			.push(EvaluatedAssertion { assertion, status });
 
 		self
 	}
@@ -390,20 +727,30 @@ impl DeploymentBuilder {
 			.filter(|d| d.status.is_failed())
 			.count();
 
+		let failed_assertions = self
+			.assertions
+			.iter()
+			.filter(|a| a.status.is_failed())
+			.count();
+
 		let status = if failed_dotfiles > 0 {
 			DeploymentStatus::failed(format!(
 				"Deployment of {failed_dotfiles} dotfiles and {failed_links} links failed"
 			))
+		} else if failed_assertions > 0 {
+			DeploymentStatus::failed(format!("{failed_assertions} assertions failed"))
 		} else {
 			DeploymentStatus::Success
 		};
 
 		Deployment {
+			schema_version: Deployment::SCHEMA_VERSION,
 			time_start: self.time_start,
 			time_end: SystemTime::now(),
 			status,
 			dotfiles: self.dotfiles,
 			symlinks: self.symlinks,
+			assertions: self.assertions,
 		}
 	}
 
@@ -412,35 +759,57 @@ impl DeploymentBuilder {
 	/// This will mark the deployment as success.
 	pub fn success(self) -> Deployment {
 		Deployment {
+			schema_version: Deployment::SCHEMA_VERSION,
 			time_start: self.time_start,
 			time_end: SystemTime::now(),
 			status: DeploymentStatus::Success,
 			dotfiles: self.dotfiles,
 			symlinks: self.symlinks,
+			assertions: self.assertions,
 		}
 	}
 
 	/// Consumes self and creates a [`Deployment`] from it.
 	///
-	/// This will mark the deployment as failed with the reason given with
-	/// `reason`.
-	pub fn failed<S: Into<Cow<'static, str>>>(self, reason: S) -> Deployment {
+	/// This will mark the deployment as failed with the given `error`.
+	pub fn failed<E: Into<Error>>(self, error: E) -> Deployment {
 		Deployment {
+			schema_version: Deployment::SCHEMA_VERSION,
 			time_start: self.time_start,
 			time_end: SystemTime::now(),
-			status: DeploymentStatus::Failed(reason.into()),
+			status: DeploymentStatus::Failed(error.into()),
 			dotfiles: self.dotfiles,
 			symlinks: self.symlinks,
+			assertions: self.assertions,
 		}
 	}
 }
 
+/// Deserializes the schema version of a [`Deployment`] and rejects versions
+/// which are newer than [`Deployment::SCHEMA_VERSION`].
+fn deserialize_schema_version<'de, D>(deserializer: D) -> Result<u32, D::Error>
+where
+	D: serde::Deserializer<'de>,
+{
+	let version = u32::deserialize(deserializer)?;
+
+	if version > Deployment::SCHEMA_VERSION {
+		return Err(serde::de::Error::custom(format!(
+			"Unsupported report schema version {version} (supported up to {})",
+			Deployment::SCHEMA_VERSION
+		)));
+	}
+
+	Ok(version)
+}
+
 impl Default for DeploymentBuilder {
 	fn default() -> Self {
 		Self {
 			time_start: SystemTime::now(),
 			dotfiles: HashMap::new(),
 			symlinks: HashMap::new(),
+			assertions: Vec::new(),
 		}
 	}
 }
@@ -463,4 +832,65 @@ mod tests {
 
 		Ok(())
 	}
+
+	#[test]
+	fn deployment_diff() {
+		crate::tests::setup_test_env();
+
+		let mut builder = Deployment::build();
+		builder
+			.add_link("source".into(), "removed".into(), ItemStatus::success())
+			.add_link("source".into(), "same".into(), ItemStatus::success())
+			.add_link("source".into(), "changed".into(), ItemStatus::success());
+		let old = builder.success();
+
+		let mut builder = Deployment::build();
+		builder
+			.add_link("source".into(), "same".into(), ItemStatus::success())
+			.add_link(
+				"source".into(),
+				"changed".into(),
+				ItemStatus::failed("error"),
+			)
+			.add_link("source".into(), "added".into(), ItemStatus::success());
+		let new = builder.success();
+
+		let diff = old.diff(&new);
+
+		assert_eq!(diff.added, vec![PathBuf::from("added")]);
+		assert_eq!(diff.removed, vec![PathBuf::from("removed")]);
+		assert_eq!(
+			diff.status_changed,
+			vec![ItemStatusChange {
+				path: PathBuf::from("changed"),
+				old: ItemStatus::success(),
+				new: ItemStatus::failed("error"),
+			}]
+		);
+		assert!(!diff.is_empty());
+		assert!(new.diff(&new).is_empty());
+	}
+
+	#[test]
+	fn deployment_failed_assertions() {
+		crate::tests::setup_test_env();
+
+		let mut builder = Deployment::build();
+		builder
+			.add_link("source".into(), "target".into(), ItemStatus::success())
+			.add_assertion(
+				Assertion::FileExists("target".into()),
+				ItemStatus::success(),
+			);
+		assert!(builder.clone().finish().status().is_success());
+
+		builder.add_assertion(
+			Assertion::Command("false".into()),
+			ItemStatus::failed("Process failed"),
+		);
+		let deployment = builder.finish();
+
+		assert!(deployment.status().is_failed());
+		assert_eq!(deployment.assertions().len(), 2);
+	}
 }
@@ -0,0 +1,141 @@
+//! Events which are emitted by the [`Deployer`](`super::Deployer`) during a
+//! deployment.
+//!
+//! An observer can be attached to a deployer with
+//! [`Deployer::with_observer`](`super::Deployer::with_observer`) to follow the
+//! progress of a deployment (e.g. to display a progress bar).
+
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::profile::hook::HookError;
+use crate::profile::LayeredProfile;
+use crate::visit::deploy::deployment::Deployment;
+
+/// Kind of a [hook](`crate::profile::hook::Hook`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookKind {
+	/// A hook which is executed before any item is deployed.
+	Pre,
+
+	/// A hook which is executed after all items were deployed.
+	Post,
+}
+
+/// An event which occurred during a deployment.
+#[derive(Debug, Clone, Copy)]
+pub enum DeployEvent<'a> {
+	/// The deployment of the resolved `profile` started.
+	Started {
+		/// The profile which is deployed.
+		profile: &'a LayeredProfile,
+	},
+
+	/// A hook is about to be executed.
+	HookStarted {
+		/// Kind of the hook.
+		kind: HookKind,
+
+		/// Command of the hook.
+		command: &'a str,
+	},
+
+	/// A hook was executed.
+	HookFinished {
+		/// Kind of the hook.
+		kind: HookKind,
+
+		/// Command of the hook.
+		command: &'a str,
+
+		/// The error if the hook failed.
+		error: Option<&'a HookError>,
+	},
+
+	/// An item is about to be deployed.
+	ItemStarted {
+		/// Absolute source path of the item.
+		source_path: &'a Path,
+
+		/// Absolute target path of the item.
+		target_path: &'a Path,
+	},
+
+	/// The contents of a file were compiled (templates resolved and
+	/// transformers applied).
+	ItemCompiled {
+		/// Absolute source path of the file.
+		source_path: &'a Path,
+
+		/// Absolute target path of the file.
+		target_path: &'a Path,
+
+		/// Time it took to compile the file.
+		duration: Duration,
+	},
+
+	/// An item was deployed (or would have been deployed during a dry run).
+	ItemDeployed {
+		/// Absolute source path of the item.
+		source_path: &'a Path,
+
+		/// Absolute target path of the item.
+		target_path: &'a Path,
+	},
+
+	/// An item was skipped.
+	ItemSkipped {
+		/// Absolute source path of the item.
+		source_path: &'a Path,
+
+		/// Absolute target path of the item.
+		target_path: &'a Path,
+
+		/// Reason why the item was skipped.
+		reason: &'a str,
+	},
+
+	/// The deployment of an item failed.
+	ItemFailed {
+		/// Absolute source path of the item.
+		source_path: &'a Path,
+
+		/// Absolute target path of the item.
+		target_path: &'a Path,
+
+		/// The error which caused the deployment of the item to fail.
+		error: &'a Error,
+	},
+
+	/// The deployment finished.
+	Finished {
+		/// The result of the deployment.
+		deployment: &'a Deployment,
+	},
+}
+
+/// Receives the [`DeployEvent`]s of a deployment.
+///
+/// This is implemented for all closures which accept a [`DeployEvent`].
+pub trait DeployObserver {
+	/// Called for each event of the deployment in the order they occur.
+	fn on_event(&mut self, event: &DeployEvent<'_>);
+}
+
+impl<F> DeployObserver for F
+where
+	F: FnMut(&DeployEvent<'_>),
+{
+	fn on_event(&mut self, event: &DeployEvent<'_>) {
+		self(event)
+	}
+}
+
+/// A [`DeployObserver`] which ignores all events.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NoObserver;
+
+impl DeployObserver for NoObserver {
+	fn on_event(&mut self, _: &DeployEvent<'_>) {}
+}
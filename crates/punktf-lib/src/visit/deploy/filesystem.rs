@@ -0,0 +1,877 @@
+//! Abstraction over the file system of the deployment target.
+//!
+//! The [`Deployer`](`super::Deployer`) performs all operations on the target
+//! through a [`Filesystem`]. By default the [`RealFilesystem`] is used, while
+//! the [`MemoryFilesystem`] keeps all items in memory, which allows to test
+//! deployments without touching the disk.
+//!
+//! The sources of the dotfiles are always read from the real file system.
+//! Hooks and assertions are not affected by the file system and always run
+//! on the real system.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+/// Kind of an item of a [`Filesystem`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FileKind {
+	/// A regular file.
+	File,
+
+	/// A directory.
+	Directory,
+
+	/// A symbolic link.
+	Symlink,
+}
+
+/// Write protection of a file which prevents it from being overwritten.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Protection {
+	/// The file is read-only.
+	ReadOnly,
+
+	/// The file has the immutable attribute (`chattr +i` on linux).
+	Immutable,
+}
+
+impl Protection {
+	/// Returns the name of the protection.
+	pub const fn as_str(&self) -> &'static str {
+		match self {
+			Self::ReadOnly => "read-only",
+			Self::Immutable => "immutable",
+		}
+	}
+}
+
+impl fmt::Display for Protection {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+/// Metadata of an item of a [`Filesystem`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Metadata {
+	/// Kind of the item.
+	pub kind: FileKind,
+
+	/// Size of the item in bytes.
+	pub len: u64,
+
+	/// Time of the last modification of the item (if supported).
+	pub modified: Option<SystemTime>,
+}
+
+impl Metadata {
+	/// Checks if the item is a regular file.
+	pub fn is_file(&self) -> bool {
+		self.kind == FileKind::File
+	}
+
+	/// Checks if the item is a directory.
+	pub fn is_dir(&self) -> bool {
+		self.kind == FileKind::Directory
+	}
+
+	/// Checks if the item is a symbolic link.
+	pub fn is_symlink(&self) -> bool {
+		self.kind == FileKind::Symlink
+	}
+}
+
+impl From<std::fs::Metadata> for Metadata {
+	fn from(metadata: std::fs::Metadata) -> Self {
+		let kind = if metadata.is_symlink() {
+			FileKind::Symlink
+		} else if metadata.is_dir() {
+			FileKind::Directory
+		} else {
+			FileKind::File
+		};
+
+		Self {
+			kind,
+			len: metadata.len(),
+			modified: metadata.modified().ok(),
+		}
+	}
+}
+
+/// Operations on the file system of the deployment target.
+///
+/// This is also implemented for references, so a file system can be inspected
+/// after it was used for a deployment.
+pub trait Filesystem {
+	/// Reads the whole content of the file at `path`.
+	fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+	/// Writes `content` to the file at `path`, replacing any existing content.
+	///
+	/// The parent directory must exist.
+	fn write(&self, path: &Path, content: &[u8]) -> io::Result<()>;
+
+	/// Returns the metadata of the item at `path`, following symbolic links.
+	fn metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+	/// Returns the metadata of the item at `path` without following symbolic
+	/// links.
+	fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata>;
+
+	/// Returns the path a symbolic link points to.
+	fn read_link(&self, path: &Path) -> io::Result<PathBuf>;
+
+	/// Creates a symbolic link at `link` which points to `original`.
+	fn symlink(&self, original: &Path, link: &Path) -> io::Result<()>;
+
+	/// Renames the item at `from` to `to`.
+	fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+	/// Creates the directory at `path` together with all missing parents.
+	fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+	/// Removes the file or symbolic link at `path`.
+	fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+	/// Removes the empty directory at `path`.
+	fn remove_dir(&self, path: &Path) -> io::Result<()>;
+
+	/// Returns the absolute path of `path` with all symbolic links resolved.
+	fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+	/// Sets the time of the last modification of the file at `path`.
+	fn set_modified(&self, path: &Path, modified: SystemTime) -> io::Result<()>;
+
+	/// Sets the unix permissions of the item at `path` to `mode`.
+	///
+	/// This does nothing on systems without unix permissions.
+	fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()>;
+
+	/// Returns the write protection of the file at `path` if it has any.
+	fn protection(&self, path: &Path) -> io::Result<Option<Protection>>;
+
+	/// Removes any write protection (see [`Filesystem::protection`]) from the
+	/// file at `path`.
+	fn unprotect(&self, path: &Path) -> io::Result<()>;
+
+	/// Checks if an item exists at `path`, following symbolic links.
+	fn exists(&self, path: &Path) -> bool {
+		self.metadata(path).is_ok()
+	}
+
+	/// Checks if a directory exists at `path`, following symbolic links.
+	fn is_dir(&self, path: &Path) -> bool {
+		self.metadata(path).is_ok_and(|metadata| metadata.is_dir())
+	}
+
+	/// Copies the file `source` of the real file system to `target` and
+	/// returns the amount of copied bytes.
+	fn copy_from_source(&self, source: &Path, target: &Path) -> io::Result<u64> {
+		let content = std::fs::read(source)?;
+		self.write(target, &content)?;
+
+		Ok(content.len() as u64)
+	}
+}
+
+impl<T: Filesystem + ?Sized> Filesystem for &T {
+	fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+		(**self).read(path)
+	}
+
+	fn write(&self, path: &Path, content: &[u8]) -> io::Result<()> {
+		(**self).write(path, content)
+	}
+
+	fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+		(**self).metadata(path)
+	}
+
+	fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+		(**self).symlink_metadata(path)
+	}
+
+	fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+		(**self).read_link(path)
+	}
+
+	fn symlink(&self, original: &Path, link: &Path) -> io::Result<()> {
+		(**self).symlink(original, link)
+	}
+
+	fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+		(**self).rename(from, to)
+	}
+
+	fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+		(**self).create_dir_all(path)
+	}
+
+	fn remove_file(&self, path: &Path) -> io::Result<()> {
+		(**self).remove_file(path)
+	}
+
+	fn remove_dir(&self, path: &Path) -> io::Result<()> {
+		(**self).remove_dir(path)
+	}
+
+	fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+		(**self).canonicalize(path)
+	}
+
+	fn set_modified(&self, path: &Path, modified: SystemTime) -> io::Result<()> {
+		(**self).set_modified(path, modified)
+	}
+
+	fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+		(**self).set_mode(path, mode)
+	}
+
+	fn protection(&self, path: &Path) -> io::Result<Option<Protection>> {
+		(**self).protection(path)
+	}
+
+	fn unprotect(&self, path: &Path) -> io::Result<()> {
+		(**self).unprotect(path)
+	}
+
+	fn copy_from_source(&self, source: &Path, target: &Path) -> io::Result<u64> {
+		(**self).copy_from_source(source, target)
+	}
+}
+
+/// The file system of the running system.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RealFilesystem;
+
+impl Filesystem for RealFilesystem {
+	fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+		std::fs::read(path)
+	}
+
+	fn write(&self, path: &Path, content: &[u8]) -> io::Result<()> {
+		std::fs::write(path, content)
+	}
+
+	fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+		std::fs::metadata(path).map(Into::into)
+	}
+
+	fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+		std::fs::symlink_metadata(path).map(Into::into)
+	}
+
+	fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+		std::fs::read_link(path)
+	}
+
+	fn symlink(&self, original: &Path, link: &Path) -> io::Result<()> {
+		cfg_if::cfg_if! {
+			if #[cfg(unix)] {
+				std::os::unix::fs::symlink(original, link)
+			} else if #[cfg(windows)] {
+				let metadata = original.symlink_metadata()?;
+
+				if metadata.is_dir() {
+					std::os::windows::fs::symlink_dir(original, link)
+				} else if metadata.is_file() {
+					std::os::windows::fs::symlink_file(original, link)
+				} else {
+					Err(io::Error::new(io::ErrorKind::InvalidInput, "Invalid type of link source"))
+				}
+			} else {
+				let _ = (original, link);
+
+				Err(io::Error::new(
+					io::ErrorKind::Unsupported,
+					"Link operations are only supported on unix and windows systems",
+				))
+			}
+		}
+	}
+
+	fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+		std::fs::rename(from, to)
+	}
+
+	fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+		std::fs::create_dir_all(path)
+	}
+
+	fn remove_file(&self, path: &Path) -> io::Result<()> {
+		std::fs::remove_file(path)
+	}
+
+	fn remove_dir(&self, path: &Path) -> io::Result<()> {
+		std::fs::remove_dir(path)
+	}
+
+	fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+		path.canonicalize()
+	}
+
+	fn set_modified(&self, path: &Path, modified: SystemTime) -> io::Result<()> {
+		std::fs::File::options()
+			.write(true)
+			.open(path)?
+			.set_modified(modified)
+	}
+
+	fn set_mode(&self, path: &Path, mode: u32) -> io::Result<()> {
+		cfg_if::cfg_if! {
+			if #[cfg(unix)] {
+				use std::os::unix::fs::PermissionsExt as _;
+
+				std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+			} else {
+				let _ = (path, mode);
+
+				Ok(())
+			}
+		}
+	}
+
+	fn protection(&self, path: &Path) -> io::Result<Option<Protection>> {
+		let metadata = std::fs::metadata(path)?;
+
+		if !metadata.is_file() {
+			Ok(None)
+		} else if is_immutable(path)? {
+			Ok(Some(Protection::Immutable))
+		} else if metadata.permissions().readonly() {
+			Ok(Some(Protection::ReadOnly))
+		} else {
+			Ok(None)
+		}
+	}
+
+	/// Clearing the immutable attribute requires the `CAP_LINUX_IMMUTABLE`
+	/// capability (usually root).
+	fn unprotect(&self, path: &Path) -> io::Result<()> {
+		if is_immutable(path)? {
+			set_immutable(path, false)?;
+		}
+
+		let mut permissions = std::fs::metadata(path)?.permissions();
+
+		if permissions.readonly() {
+			cfg_if::cfg_if! {
+				if #[cfg(unix)] {
+					use std::os::unix::fs::PermissionsExt as _;
+
+					// Only allow the owner to write instead of everyone
+					permissions.set_mode(permissions.mode() | 0o200);
+				} else {
+					permissions.set_readonly(false);
+				}
+			}
+
+			std::fs::set_permissions(path, permissions)?;
+		}
+
+		Ok(())
+	}
+
+	/// Clones the file if the file system supports it (see [`clone_file`])
+	/// and copies its contents otherwise.
+	fn copy_from_source(&self, source: &Path, target: &Path) -> io::Result<u64> {
+		match clone_file(source, target)? {
+			Some(len) => Ok(len),
+			None => std::fs::copy(source, target),
+		}
+	}
+}
+
+/// Copies the file `source` to `target` by cloning its data (copy-on-write)
+/// instead of copying its contents. This is supported by e.g. Btrfs and XFS
+/// on linux.
+///
+/// Returns the size of the file or `None` if the file system does not support
+/// cloning, in which case the contents need to be copied.
+#[cfg(target_os = "linux")]
+fn clone_file(source: &Path, target: &Path) -> io::Result<Option<u64>> {
+	use rustix::io::Errno;
+
+	let source = std::fs::File::open(source)?;
+	let metadata = source.metadata()?;
+	let target = std::fs::File::create(target)?;
+
+	match rustix::fs::ioctl_ficlone(&target, &source) {
+		Ok(()) => {
+			target.set_permissions(metadata.permissions())?;
+			Ok(Some(metadata.len()))
+		}
+		Err(Errno::OPNOTSUPP | Errno::XDEV | Errno::INVAL | Errno::NOTTY | Errno::NOSYS) => {
+			Ok(None)
+		}
+		Err(err) => Err(err.into()),
+	}
+}
+
+/// Copies the file `source` to `target` by cloning its data (copy-on-write)
+/// instead of copying its contents. This is supported by APFS on macOS.
+///
+/// Returns the size of the file or `None` if the file system does not support
+/// cloning, in which case the contents need to be copied.
+#[cfg(target_os = "macos")]
+fn clone_file(source: &Path, target: &Path) -> io::Result<Option<u64>> {
+	use rustix::fs::{CloneFlags, CWD};
+	use rustix::io::Errno;
+
+	let Some(file_name) = target.file_name() else {
+		return Ok(None);
+	};
+
+	// `clonefile` does not replace existing files, so the clone is created
+	// next to the target and then moved over it
+	let mut clone_name = std::ffi::OsString::from(".");
+	clone_name.push(file_name);
+	clone_name.push(".punktf-clone");
+	let clone = target.with_file_name(clone_name);
+
+	// Remove a leftover of an interrupted deployment
+	let _ = std::fs::remove_file(&clone);
+
+	let source = std::fs::File::open(source)?;
+	let len = source.metadata()?.len();
+
+	match rustix::fs::fclonefileat(&source, CWD, &clone, CloneFlags::empty()) {
+		Ok(()) => {}
+		Err(Errno::NOTSUP | Errno::XDEV) => return Ok(None),
+		Err(err) => return Err(err.into()),
+	}
+
+	if let Err(err) = std::fs::rename(&clone, target) {
+		let _ = std::fs::remove_file(&clone);
+		return Err(err);
+	}
+
+	Ok(Some(len))
+}
+
+/// Checks if the file at `path` has the immutable attribute.
+///
+/// File systems which do not support attributes have no immutable files.
+#[cfg(target_os = "linux")]
+fn is_immutable(path: &Path) -> io::Result<bool> {
+	use rustix::fs::IFlags;
+	use rustix::io::Errno;
+
+	// Without read permission the attributes can not be read
+	let Ok(file) = std::fs::File::open(path) else {
+		return Ok(false);
+	};
+
+	match rustix::fs::ioctl_getflags(&file) {
+		Ok(flags) => Ok(flags.contains(IFlags::IMMUTABLE)),
+		Err(Errno::NOTTY | Errno::OPNOTSUPP | Errno::INVAL | Errno::NOSYS) => Ok(false),
+		Err(err) => Err(err.into()),
+	}
+}
+
+/// Sets or clears the immutable attribute of the file at `path`.
+#[cfg(target_os = "linux")]
+fn set_immutable(path: &Path, immutable: bool) -> io::Result<()> {
+	use rustix::fs::IFlags;
+
+	let file = std::fs::File::open(path)?;
+	let mut flags = rustix::fs::ioctl_getflags(&file)?;
+	flags.set(IFlags::IMMUTABLE, immutable);
+
+	Ok(rustix::fs::ioctl_setflags(&file, flags)?)
+}
+
+/// The immutable attribute is only supported on linux.
+#[cfg(not(target_os = "linux"))]
+const fn is_immutable(_path: &Path) -> io::Result<bool> {
+	Ok(false)
+}
+
+/// The immutable attribute is only supported on linux.
+#[cfg(not(target_os = "linux"))]
+const fn set_immutable(_path: &Path, _immutable: bool) -> io::Result<()> {
+	Ok(())
+}
+
+/// Cloning files is not supported on this platform, so the contents always
+/// need to be copied.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const fn clone_file(_source: &Path, _target: &Path) -> io::Result<Option<u64>> {
+	Ok(None)
+}
+
+/// An item of the [`MemoryFilesystem`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Entry {
+	/// A file with its content.
+	File(Vec<u8>),
+
+	/// A directory.
+	Directory,
+
+	/// A symbolic link with the path it points to.
+	Symlink(PathBuf),
+}
+
+/// A file system which keeps all items in memory.
+///
+/// Paths are used as given without being resolved against a working
+/// directory, so only absolute paths should be used. Root directories always
+/// exist. Modification times are not tracked.
+#[derive(Default, Debug, Clone, PartialEq, Eq)]
+pub struct MemoryFilesystem {
+	/// All items by their path.
+	entries: RefCell<BTreeMap<PathBuf, Entry>>,
+}
+
+/// Maximum amount of symbolic links which are followed to resolve a path.
+const MAX_SYMLINK_DEPTH: usize = 40;
+
+/// Returns the error for a missing item at `path`.
+fn not_found(path: &Path) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::NotFound,
+		format!("{} does not exist", path.display()),
+	)
+}
+
+/// Returns the error for an item at `path` which is of the wrong kind.
+fn invalid(path: &Path, reason: &str) -> io::Error {
+	io::Error::new(
+		io::ErrorKind::InvalidInput,
+		format!("{}: {reason}", path.display()),
+	)
+}
+
+/// Checks if `path` is a root directory (e.g. `/` or `C:\`).
+fn is_root(path: &Path) -> bool {
+	path.components()
+		.all(|component| matches!(component, Component::RootDir | Component::Prefix(_)))
+}
+
+impl MemoryFilesystem {
+	/// Creates a new empty file system.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Returns the paths of all items sorted alphabetically.
+	pub fn paths(&self) -> Vec<PathBuf> {
+		self.entries.borrow().keys().cloned().collect()
+	}
+
+	/// Resolves all symbolic links of the last component of `path`.
+	fn resolve(&self, path: &Path) -> io::Result<PathBuf> {
+		let entries = self.entries.borrow();
+		let mut path = path.to_path_buf();
+
+		for _ in 0..MAX_SYMLINK_DEPTH {
+			match entries.get(&path) {
+				Some(Entry::Symlink(original)) => {
+					path = match path.parent() {
+						Some(parent) => parent.join(original),
+						None => original.clone(),
+					};
+				}
+				Some(_) => return Ok(path),
+				None if is_root(&path) => return Ok(path),
+				None => return Err(not_found(&path)),
+			}
+		}
+
+		Err(invalid(&path, "too many levels of symbolic links"))
+	}
+
+	/// Checks that the parent directory of `path` exists.
+	fn check_parent(&self, path: &Path) -> io::Result<()> {
+		match path.parent() {
+			Some(parent) if !parent.as_os_str().is_empty() && !self.is_dir(parent) => {
+				Err(not_found(parent))
+			}
+			_ => Ok(()),
+		}
+	}
+}
+
+impl Filesystem for MemoryFilesystem {
+	fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+		let path = self.resolve(path)?;
+
+		match self.entries.borrow().get(&path) {
+			Some(Entry::File(content)) => Ok(content.clone()),
+			_ => Err(invalid(&path, "not a file")),
+		}
+	}
+
+	fn write(&self, path: &Path, content: &[u8]) -> io::Result<()> {
+		let path = match self.resolve(path) {
+			Ok(path) => path,
+			Err(_) => path.to_path_buf(),
+		};
+
+		self.check_parent(&path)?;
+
+		let mut entries = self.entries.borrow_mut();
+		match entries.get(&path) {
+			Some(Entry::Directory) => Err(invalid(&path, "is a directory")),
+			_ => {
+				entries.insert(path, Entry::File(content.to_vec()));
+				Ok(())
+			}
+		}
+	}
+
+	fn metadata(&self, path: &Path) -> io::Result<Metadata> {
+		let path = self.resolve(path)?;
+		self.symlink_metadata(&path)
+	}
+
+	fn symlink_metadata(&self, path: &Path) -> io::Result<Metadata> {
+		let (kind, len) = match self.entries.borrow().get(path) {
+			Some(Entry::File(content)) => (FileKind::File, content.len() as u64),
+			Some(Entry::Directory) => (FileKind::Directory, 0),
+			Some(Entry::Symlink(original)) => {
+				(FileKind::Symlink, original.as_os_str().len() as u64)
+			}
+			None if is_root(path) => (FileKind::Directory, 0),
+			None => return Err(not_found(path)),
+		};
+
+		Ok(Metadata {
+			kind,
+			len,
+			modified: None,
+		})
+	}
+
+	fn read_link(&self, path: &Path) -> io::Result<PathBuf> {
+		match self.entries.borrow().get(path) {
+			Some(Entry::Symlink(original)) => Ok(original.clone()),
+			Some(_) => Err(invalid(path, "not a symbolic link")),
+			None => Err(not_found(path)),
+		}
+	}
+
+	fn symlink(&self, original: &Path, link: &Path) -> io::Result<()> {
+		self.check_parent(link)?;
+
+		if self.symlink_metadata(link).is_ok() {
+			return Err(io::Error::new(
+				io::ErrorKind::AlreadyExists,
+				format!("{} already exists", link.display()),
+			));
+		}
+
+		self.entries
+			.borrow_mut()
+			.insert(link.to_path_buf(), Entry::Symlink(original.to_path_buf()));
+
+		Ok(())
+	}
+
+	fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+		self.symlink_metadata(from)?;
+		self.check_parent(to)?;
+
+		let mut entries = self.entries.borrow_mut();
+		let moved = entries
+			.keys()
+			.filter(|path| path.starts_with(from))
+			.cloned()
+			.collect::<Vec<_>>();
+
+		for path in moved {
+			if let Some(entry) = entries.remove(&path) {
+				let relative = path.strip_prefix(from).unwrap_or(&path);
+				entries.insert(to.join(relative), entry);
+			}
+		}
+
+		Ok(())
+	}
+
+	fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+		for ancestor in path.ancestors().collect::<Vec<_>>().into_iter().rev() {
+			if ancestor.as_os_str().is_empty() || is_root(ancestor) {
+				continue;
+			}
+
+			match self.metadata(ancestor) {
+				Ok(metadata) if metadata.is_dir() => {}
+				Ok(_) => return Err(invalid(ancestor, "not a directory")),
+				Err(_) => {
+					self.entries
+						.borrow_mut()
+						.insert(ancestor.to_path_buf(), Entry::Directory);
+				}
+			}
+		}
+
+		Ok(())
+	}
+
+	fn remove_file(&self, path: &Path) -> io::Result<()> {
+		let mut entries = self.entries.borrow_mut();
+
+		match entries.get(path) {
+			Some(Entry::File(_) | Entry::Symlink(_)) => {
+				entries.remove(path);
+				Ok(())
+			}
+			Some(Entry::Directory) => Err(invalid(path, "is a directory")),
+			None => Err(not_found(path)),
+		}
+	}
+
+	fn remove_dir(&self, path: &Path) -> io::Result<()> {
+		let mut entries = self.entries.borrow_mut();
+
+		match entries.get(path) {
+			Some(Entry::Directory) => {
+				if entries
+					.keys()
+					.any(|child| child != path && child.starts_with(path))
+				{
+					return Err(invalid(path, "directory is not empty"));
+				}
+
+				entries.remove(path);
+				Ok(())
+			}
+			Some(_) => Err(invalid(path, "not a directory")),
+			None => Err(not_found(path)),
+		}
+	}
+
+	fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+		self.resolve(path)
+	}
+
+	/// Modification times are not tracked, so this only checks that the file
+	/// exists.
+	fn set_modified(&self, path: &Path, _: SystemTime) -> io::Result<()> {
+		if self.metadata(path)?.is_file() {
+			Ok(())
+		} else {
+			Err(invalid(path, "not a file"))
+		}
+	}
+
+	/// Permissions are not tracked, so this only checks that the item exists.
+	fn set_mode(&self, path: &Path, _: u32) -> io::Result<()> {
+		self.symlink_metadata(path).map(|_| ())
+	}
+
+	/// Permissions are not tracked, so files are never protected.
+	fn protection(&self, path: &Path) -> io::Result<Option<Protection>> {
+		self.metadata(path).map(|_| None)
+	}
+
+	/// Permissions are not tracked, so this only checks that the item exists.
+	fn unprotect(&self, path: &Path) -> io::Result<()> {
+		self.metadata(path).map(|_| ())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn memory_write_read() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let fs = MemoryFilesystem::new();
+		let file = Path::new("/home/demo/.bashrc");
+
+		assert!(fs.write(file, b"content").is_err());
+
+		fs.create_dir_all(Path::new("/home/demo"))?;
+		fs.write(file, b"content")?;
+
+		assert_eq!(fs.read(file)?, b"content");
+		assert!(fs.is_dir(Path::new("/home")));
+		assert_eq!(
+			fs.metadata(file)?,
+			Metadata {
+				kind: FileKind::File,
+				len: 7,
+				modified: None
+			}
+		);
+		assert!(fs.create_dir_all(&file.join("sub")).is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn memory_symlink() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let fs = MemoryFilesystem::new();
+		fs.create_dir_all(Path::new("/a"))?;
+		fs.write(Path::new("/a/file"), b"content")?;
+		fs.symlink(Path::new("/a/file"), Path::new("/a/link"))?;
+
+		assert_eq!(fs.read(Path::new("/a/link"))?, b"content");
+		assert_eq!(fs.read_link(Path::new("/a/link"))?, Path::new("/a/file"));
+		assert!(fs.symlink_metadata(Path::new("/a/link"))?.is_symlink());
+		assert!(fs.metadata(Path::new("/a/link"))?.is_file());
+		assert!(fs
+			.symlink(Path::new("/a/file"), Path::new("/a/link"))
+			.is_err());
+
+		fs.remove_file(Path::new("/a/file"))?;
+		assert!(!fs.exists(Path::new("/a/link")));
+
+		Ok(())
+	}
+
+	#[test]
+	fn memory_rename_remove() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let fs = MemoryFilesystem::new();
+		fs.create_dir_all(Path::new("/a/b"))?;
+		fs.write(Path::new("/a/b/file"), b"content")?;
+
+		assert!(fs.remove_dir(Path::new("/a/b")).is_err());
+
+		fs.rename(Path::new("/a/b"), Path::new("/a/c"))?;
+		assert_eq!(fs.read(Path::new("/a/c/file"))?, b"content");
+		assert!(!fs.exists(Path::new("/a/b")));
+
+		fs.remove_file(Path::new("/a/c/file"))?;
+		fs.remove_dir(Path::new("/a/c"))?;
+		assert_eq!(fs.paths(), [PathBuf::from("/a")]);
+
+		Ok(())
+	}
+
+	#[test]
+	fn real_copy_from_source() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = tempfile::tempdir()?;
+		let source = dir.path().join("source");
+		let target = dir.path().join("target");
+
+		std::fs::write(&source, b"content")?;
+		std::fs::write(&target, b"previous longer content")?;
+
+		// Either clones or copies the file, depending on the file system
+		assert_eq!(RealFilesystem.copy_from_source(&source, &target)?, 7);
+		assert_eq!(std::fs::read(&target)?, b"content");
+		assert_eq!(
+			std::fs::metadata(&target)?.permissions(),
+			std::fs::metadata(&source)?.permissions()
+		);
+
+		Ok(())
+	}
+}
@@ -1,65 +1,143 @@
 //! A [`Visit`](`crate::visit::Visitor`) implementation which deploys the items.
 
+pub mod cache;
+pub mod cancel;
 pub mod deployment;
+pub mod event;
+pub mod filesystem;
+#[cfg(feature = "async")]
+pub mod task;
 
-use cfg_if::cfg_if;
 use color_eyre::eyre::Context;
 
-use crate::profile::{source::PunktfSource, MergeMode};
+use crate::error::{Error, IoError, ProfileError, TemplateError};
+use crate::profile::dotfile::DirMode;
+use crate::profile::encryption::EncryptionBackend;
+use crate::profile::formatter::FormatFailure;
+use crate::profile::hook::HookError;
+use crate::profile::{collect_profile_names, source::PunktfSource, MergeMode};
+use crate::visit::filter::PathFilter;
 use crate::visit::*;
 
 use crate::profile::transform::Transform as _;
 use crate::profile::LayeredProfile;
-use crate::visit::deploy::deployment::{Deployment, DeploymentBuilder, ItemStatus};
+use crate::visit::deploy::cache::{CacheEntry, ContentHash, DeployCache, FileEntry, FileStamp};
+use crate::visit::deploy::cancel::CancellationToken;
+use crate::visit::deploy::deployment::{
+	Deployment, DeploymentBuilder, ItemChange, ItemMetrics, ItemStatus,
+};
+use crate::visit::deploy::event::{DeployEvent, DeployObserver, HookKind, NoObserver};
+use crate::visit::deploy::filesystem::{Filesystem, Metadata, RealFilesystem};
 use std::borrow::Borrow;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
+use crate::template::cache::TemplateCache;
 use crate::visit::{ResolvingVisitor, TemplateVisitor};
 
 impl<'a> Item<'a> {
 	/// Adds this item to the given
 	/// [`DeploymentBuilder`](`crate::visit::deploy::deployment::DeploymentBuilder`).
-	fn add_to_builder<S: Into<ItemStatus>>(&self, builder: &mut DeploymentBuilder, status: S) {
+	///
+	/// The target paths are resolved with the given `filesystem`.
+	///
+	/// Returns the resolved target path under which the item was added.
+	fn add_to_builder<S: Into<ItemStatus>>(
+		&self,
+		builder: &mut DeploymentBuilder,
+		filesystem: &impl Filesystem,
+		status: S,
+	) -> PathBuf {
 		let status = status.into();
 
-		let resolved_target_path = self
-			.target_path
-			.canonicalize()
+		tracing::info!(
+			path = %self.target_path.display(),
+			action = status.action(),
+			"{}: {status}",
+			self.relative_source_path.display()
+		);
+
+		let resolved_target_path = filesystem
+			.canonicalize(&self.target_path)
 			.unwrap_or_else(|_| self.target_path.clone());
 
 		match &self.kind {
 			Kind::Root(dotfile) => {
-				builder.add_dotfile(resolved_target_path, (*dotfile).clone(), status)
+				builder.add_dotfile(resolved_target_path.clone(), (*dotfile).clone(), status)
 			}
 			Kind::Child {
 				root_target_path, ..
 			} => {
-				let resolved_root_target_path = root_target_path
-					.canonicalize()
+				let resolved_root_target_path = filesystem
+					.canonicalize(root_target_path)
 					.unwrap_or_else(|_| root_target_path.clone());
 
-				builder.add_child(resolved_target_path, resolved_root_target_path, status)
+				builder.add_child(
+					resolved_target_path.clone(),
+					resolved_root_target_path,
+					status,
+				)
 			}
 		};
+
+		resolved_target_path
 	}
 }
 
 impl Symlink {
 	/// Adds this item to the given
 	/// [`DeploymentBuilder`](`crate::visit::deploy::deployment::DeploymentBuilder`).
-	fn add_to_builder<S: Into<ItemStatus>>(&self, builder: &mut DeploymentBuilder, status: S) {
-		builder.add_link(
-			self.source_path.clone(),
-			self.target_path.clone(),
-			status.into(),
+	///
+	/// The paths of links are recorded as given, so the file system is not
+	/// needed.
+	fn add_to_builder<S: Into<ItemStatus>>(
+		&self,
+		builder: &mut DeploymentBuilder,
+		_: &impl Filesystem,
+		status: S,
+	) {
+		let status = status.into();
+
+		tracing::info!(
+			path = %self.target_path.display(),
+			action = status.action(),
+			"{} => {}: {status}",
+			self.source_path.display(),
+			self.target_path.display()
 		);
+
+		builder.add_link(self.source_path.clone(), self.target_path.clone(), status);
 	}
 }
 
 /// Marks the given item as successfully deployed.
 macro_rules! success {
-	($builder:expr, $item:expr) => {
-		$item.add_to_builder($builder, ItemStatus::success());
+	($this:expr, $item:expr) => {
+		$this.report(&$item.source_path, &$item.target_path, &ItemStatus::success());
+		$item.add_to_builder(&mut $this.builder, &$this.filesystem, ItemStatus::success());
+	};
+	($this:expr, $item:expr, change: $change:expr) => {
+		$this.report(&$item.source_path, &$item.target_path, &ItemStatus::success());
+		let path =
+			$item.add_to_builder(&mut $this.builder, &$this.filesystem, ItemStatus::success());
+		$this.builder.set_change(path, $change);
+	};
+	($this:expr, $item:expr, change: $change:expr, metrics: $metrics:expr) => {
+		$this.report(&$item.source_path, &$item.target_path, &ItemStatus::success());
+		let path =
+			$item.add_to_builder(&mut $this.builder, &$this.filesystem, ItemStatus::success());
+		$this.builder.set_change(&path, $change).set_metrics(path, $metrics);
+	};
+	($this:expr, $item:expr, change: $change:expr, metrics: $metrics:expr, hash: $hash:expr) => {
+		$this.report(&$item.source_path, &$item.target_path, &ItemStatus::success());
+		let path =
+			$item.add_to_builder(&mut $this.builder, &$this.filesystem, ItemStatus::success());
+		$this.builder.set_change(&path, $change).set_metrics(&path, $metrics);
+
+		if let Some(hash) = $hash {
+			$this.builder.set_content_hash(path, hash.to_string());
+		}
 	};
 }
 
@@ -67,12 +145,16 @@ macro_rules! success {
 ///
 /// This will instantly return from the out function after reporting the skip.
 macro_rules! skipped {
-	($builder:expr, $item:expr, $reason:expr => $ret:expr ) => {
-		$item.add_to_builder($builder, ItemStatus::skipped($reason));
+	($this:expr, $item:expr, $reason:expr => $ret:expr ) => {
+		let status = ItemStatus::skipped($reason);
+		$this.report(&$item.source_path, &$item.target_path, &status);
+		$item.add_to_builder(&mut $this.builder, &$this.filesystem, status);
 		return Ok($ret);
 	};
-	($builder:expr, $item:expr, $reason:expr) => {
-		$item.add_to_builder($builder, ItemStatus::skipped($reason));
+	($this:expr, $item:expr, $reason:expr) => {
+		let status = ItemStatus::skipped($reason);
+		$this.report(&$item.source_path, &$item.target_path, &status);
+		$item.add_to_builder(&mut $this.builder, &$this.filesystem, status);
 		return Ok(());
 	};
 }
@@ -81,37 +163,206 @@ macro_rules! skipped {
 ///
 /// This will instantly return from the out function after reporting the error.
 macro_rules! failed {
-	($builder:expr, $item:expr, $reason:expr => Err($ret:expr) ) => {
-		$item.add_to_builder($builder, ItemStatus::failed($reason));
+	($this:expr, $item:expr, $reason:expr => Err($ret:expr) ) => {
+		let status = ItemStatus::failed($reason);
+		$this.report(&$item.source_path, &$item.target_path, &status);
+		$item.add_to_builder(&mut $this.builder, &$this.filesystem, status);
 		return Err($ret);
 	};
-	($builder:expr, $item:expr, $reason:expr => $ret:expr ) => {
-		$item.add_to_builder($builder, ItemStatus::failed($reason));
+	($this:expr, $item:expr, $reason:expr => $ret:expr ) => {
+		let status = ItemStatus::failed($reason);
+		$this.report(&$item.source_path, &$item.target_path, &status);
+		$item.add_to_builder(&mut $this.builder, &$this.filesystem, status);
 		return Ok($ret);
 	};
-	($builder:expr, $item:expr, $reason:expr) => {
-		$item.add_to_builder($builder, ItemStatus::failed($reason));
+	($this:expr, $item:expr, $reason:expr) => {
+		let status = ItemStatus::failed($reason);
+		$this.report(&$item.source_path, &$item.target_path, &status);
+		$item.add_to_builder(&mut $this.builder, &$this.filesystem, status);
 		return Ok(());
 	};
 }
 
+/// Marks the given item as cancelled if the deployment was cancelled.
+///
+/// This will instantly return from the out function after reporting the
+/// cancellation.
+macro_rules! cancelled {
+	($this:expr, $item:expr) => {
+		if $this.cancellation.is_cancelled() {
+			$this.report(&$item.source_path, &$item.target_path, &ItemStatus::Cancelled);
+			$item.add_to_builder(&mut $this.builder, &$this.filesystem, ItemStatus::Cancelled);
+			return Ok(());
+		}
+	};
+}
+
 /// Configuration options for the [`Deployer`].
-#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+///
+/// The options can either be set directly or with the `with_*` methods:
+///
+/// ```
+/// use punktf_lib::profile::MergeMode;
+/// use punktf_lib::visit::deploy::DeployOptions;
+///
+/// let options = DeployOptions::new()
+///     .with_merge_mode(MergeMode::Keep)
+///     .with_backup_dir("/tmp/backup")
+///     .with_skip_unchanged(true);
+/// ```
+///
+/// Events of the deployment (e.g. for reporting) are received by attaching an
+/// observer with [`Deployer::with_observer`].
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct DeployOptions {
 	/// If this flag is set, it will prevent any write operations from occurring
 	/// during the deployment.
 	///
 	/// This includes write, copy and directory creation operations.
 	pub dry_run: bool,
+
+	/// Only items accepted by this filter are deployed.
+	pub filter: PathFilter,
+
+	/// Existing files are copied into this directory before they are
+	/// overwritten.
+	///
+	/// The backups keep the structure of the target paths (e.g.
+	/// `/home/demo/.bashrc` is copied to `<dir>/home/demo/.bashrc`).
+	pub backup_dir: Option<PathBuf>,
+
+	/// Merge mode which is used for all dotfiles instead of the one defined by
+	/// the profile.
+	pub merge: Option<MergeMode>,
+
+	/// If this flag is set, files whose content would not change are not
+	/// written again.
+	pub skip_unchanged: bool,
+
+	/// If this flag is set, the pre- and post-hooks of the profile are not
+	/// executed.
+	pub skip_hooks: bool,
+
+	/// Path of the [cache](`crate::visit::deploy::cache`) of compiled
+	/// templates.
+	///
+	/// Templates whose inputs did not change since they were deployed with the
+	/// same cache are not compiled again. The cache is not updated during a
+	/// dry run.
+	pub cache: Option<PathBuf>,
+
+	/// Path of the [cache](`crate::template::cache`) of parsed templates.
+	///
+	/// Templates whose content did not change since they were parsed with the
+	/// same cache are not parsed again.
+	pub template_cache: Option<PathBuf>,
+
+	/// If this flag is set, copied files (no templates and no content
+	/// transformers) whose source and target have the same size and
+	/// modification time as recorded in the
+	/// [cache](`DeployOptions::cache`) are considered unchanged without
+	/// comparing their contents.
+	///
+	/// This avoids reading large files (e.g. fonts or images) on each
+	/// deployment, but misses changes which keep both the size and the
+	/// modification time. Together with [`DeployOptions::skip_unchanged`]
+	/// unchanged files are neither read nor written. It has no effect without a
+	/// cache.
+	pub fast_compare: bool,
+
+	/// If this flag is set, existing files which are write protected
+	/// (read-only or immutable) are overwritten after their protection is
+	/// removed. Otherwise they are skipped, unless
+	/// [`Dotfile::force`](`crate::profile::dotfile::Dotfile::force`) is set.
+	pub force: bool,
+}
+
+impl DeployOptions {
+	/// Creates new options with the default values.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Sets [`DeployOptions::dry_run`].
+	pub const fn with_dry_run(mut self, dry_run: bool) -> Self {
+		self.dry_run = dry_run;
+		self
+	}
+
+	/// Sets [`DeployOptions::filter`].
+	pub fn with_filter(mut self, filter: PathFilter) -> Self {
+		self.filter = filter;
+		self
+	}
+
+	/// Sets [`DeployOptions::backup_dir`].
+	pub fn with_backup_dir<P: Into<PathBuf>>(mut self, backup_dir: P) -> Self {
+		self.backup_dir = Some(backup_dir.into());
+		self
+	}
+
+	/// Sets [`DeployOptions::merge`].
+	pub const fn with_merge_mode(mut self, merge: MergeMode) -> Self {
+		self.merge = Some(merge);
+		self
+	}
+
+	/// Sets [`DeployOptions::skip_unchanged`].
+	pub const fn with_skip_unchanged(mut self, skip_unchanged: bool) -> Self {
+		self.skip_unchanged = skip_unchanged;
+		self
+	}
+
+	/// Sets [`DeployOptions::skip_hooks`].
+	pub const fn with_skip_hooks(mut self, skip_hooks: bool) -> Self {
+		self.skip_hooks = skip_hooks;
+		self
+	}
+
+	/// Sets [`DeployOptions::cache`].
+	pub fn with_cache<P: Into<PathBuf>>(mut self, cache: P) -> Self {
+		self.cache = Some(cache.into());
+		self
+	}
+
+	/// Sets [`DeployOptions::template_cache`].
+	pub fn with_template_cache<P: Into<PathBuf>>(mut self, template_cache: P) -> Self {
+		self.template_cache = Some(template_cache.into());
+		self
+	}
+
+	/// Sets [`DeployOptions::fast_compare`].
+	pub const fn with_fast_compare(mut self, fast_compare: bool) -> Self {
+		self.fast_compare = fast_compare;
+		self
+	}
+
+	/// Sets [`DeployOptions::force`].
+	pub const fn with_force(mut self, force: bool) -> Self {
+		self.force = force;
+		self
+	}
 }
 
+/// [`EncryptionBackend`] of a [`Deployer`] which is shared between its clones.
+#[derive(Debug, Clone)]
+struct SharedEncryption(Arc<dyn EncryptionBackend>);
+
+impl PartialEq for SharedEncryption {
+	fn eq(&self, other: &Self) -> bool {
+		Arc::ptr_eq(&self.0, &other.0)
+	}
+}
+
+impl Eq for SharedEncryption {}
+
 /// Responsible for deploying a [profile](`crate::profile::Profile`).
 ///
 /// This includes checking for merge conflicts, resolving children of a
 /// directory dotfile, parsing and resolving of templates and the actual
 /// writing of the dotfile to the target destination.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Deployer<F> {
+pub struct Deployer<F, O = NoObserver, S = RealFilesystem> {
 	/// Configuration options
 	options: DeployOptions,
 
@@ -127,6 +378,28 @@ pub struct Deployer<F> {
 	/// This holds information about each item which was processed,
 	/// keeps track of the time and also stores a overall status of the deployment.
 	builder: DeploymentBuilder,
+
+	/// Receives the events of the deployment.
+	observer: O,
+
+	/// File system of the target on which all items are deployed.
+	filesystem: S,
+
+	/// Signals that the deployment should stop after the current item.
+	cancellation: CancellationToken,
+
+	/// Cache of compiled templates if [`DeployOptions::cache`] is set.
+	cache: Option<DeployCache>,
+
+	/// Latest modification time of the files of the deployed profiles, which is
+	/// used for generated files of dotfiles with
+	/// [`Dotfile::preserve_mtime`](`crate::profile::dotfile::Dotfile::preserve_mtime`).
+	profiles_modified: Option<SystemTime>,
+
+	/// Decrypts the sources of encrypted dotfiles. Defaults to the backend of
+	/// the [encryption](`crate::profile::LayeredProfile::encryption`) of the
+	/// profile.
+	encryption: Option<SharedEncryption>,
 }
 
 impl<F> Deployer<F>
@@ -139,8 +412,75 @@ where
 			options,
 			merge_ask_fn,
 			builder: DeploymentBuilder::default(),
+			observer: NoObserver,
+			filesystem: RealFilesystem,
+			cancellation: CancellationToken::default(),
+			cache: None,
+			profiles_modified: None,
+			encryption: None,
 		}
 	}
+}
+
+impl<F, O, S> Deployer<F, O, S>
+where
+	F: Fn(&Path, &Path) -> color_eyre::Result<bool>,
+	O: DeployObserver,
+	S: Filesystem,
+{
+	/// Attaches an `observer` which receives all
+	/// [`DeployEvent`](`crate::visit::deploy::event::DeployEvent`)s of the
+	/// deployment.
+	pub fn with_observer<P: DeployObserver>(self, observer: P) -> Deployer<F, P, S> {
+		Deployer {
+			options: self.options,
+			merge_ask_fn: self.merge_ask_fn,
+			builder: self.builder,
+			observer,
+			filesystem: self.filesystem,
+			cancellation: self.cancellation,
+			cache: self.cache,
+			profiles_modified: self.profiles_modified,
+			encryption: self.encryption,
+		}
+	}
+
+	/// Deploys all items on the given `filesystem` instead of the
+	/// [`RealFilesystem`](`crate::visit::deploy::filesystem::RealFilesystem`).
+	///
+	/// Hooks and assertions are still executed on the real system.
+	pub fn with_filesystem<T: Filesystem>(self, filesystem: T) -> Deployer<F, O, T> {
+		Deployer {
+			options: self.options,
+			merge_ask_fn: self.merge_ask_fn,
+			builder: self.builder,
+			observer: self.observer,
+			filesystem,
+			cancellation: self.cancellation,
+			cache: self.cache,
+			profiles_modified: self.profiles_modified,
+			encryption: self.encryption,
+		}
+	}
+
+	/// Stops the deployment once the given `token` is cancelled.
+	///
+	/// The item which is deployed at the time of the cancellation is finished,
+	/// all remaining items are marked as
+	/// [cancelled](`crate::visit::deploy::deployment::ItemStatus::Cancelled`).
+	/// Post-hooks are still executed to allow them to clean up, while
+	/// assertions are skipped.
+	pub fn with_cancellation(mut self, token: CancellationToken) -> Self {
+		self.cancellation = token;
+		self
+	}
+
+	/// Decrypts the sources of encrypted dotfiles with the given `backend`
+	/// instead of the one configured in the profile.
+	pub fn with_encryption_backend(mut self, backend: Arc<dyn EncryptionBackend>) -> Self {
+		self.encryption = Some(SharedEncryption(backend));
+		self
+	}
 
 	/// Retrieves the finished deployment from this instance.
 	pub fn into_deployment(self) -> Deployment {
@@ -154,7 +494,8 @@ where
 	/// Only hard errors will be returned as error, everything else will be
 	/// recorded in the [Deployment](`crate::visit::deploy::deployment::Deployment`)
 	/// on a dotfile level.
-	pub fn deploy(self, source: &PunktfSource, profile: &mut LayeredProfile) -> Deployment {
+	#[tracing::instrument(name = "deploy", skip_all, fields(dry_run = self.options.dry_run))]
+	pub fn deploy(mut self, source: &PunktfSource, profile: &mut LayeredProfile) -> Deployment {
 		// General flow:
 		//	- get deployment path
 		//	- check if dotfile already deployed
@@ -173,86 +514,253 @@ where
 		//	- IF FILE: write dotfile
 		//	- IF DIR: for each dotfile in dir START AT TOP
 
-		for hook in profile.pre_hooks() {
-			log::info!("Executing pre-hook: {}", hook.command());
+		self.observer.on_event(&DeployEvent::Started { profile });
+		self.cache = self.options.cache.as_deref().map(load_cache);
+
+		if profile.dotfiles().any(Dotfile::preserves_mtime) {
+			self.profiles_modified = profiles_modified(source, profile);
+		}
+
+		if self.encryption.is_none() {
+			if let Some(encryption) = profile.encryption() {
+				match encryption.backend() {
+					Ok(backend) => self.encryption = Some(SharedEncryption(backend.into())),
+					Err(err) => {
+						let err = ProfileError::new(format!("{err:#}"));
+						return Self::finish(self.observer, self.builder.failed(err));
+					}
+				}
+			}
+		}
+
+		let hooks = !self.options.skip_hooks;
+
+		for hook in profile.pre_hooks().filter(|_| hooks) {
+			tracing::info!("Executing pre-hook: {}", hook.command());
 			// No files are deployed yet, meaning if an error during hook
 			// execution occurs it will return with an error instead of just
 			// logging it.
 
-			if let Err(err) = hook
-				.execute(source.profiles())
-				.wrap_err("Failed to execute pre-hook")
-			{
-				log::error!("Failed to execute pre-hook ({})", err);
-				return self.builder.failed(err.to_string());
+			self.observer.on_event(&DeployEvent::HookStarted {
+				kind: HookKind::Pre,
+				command: hook.command(),
+			});
+
+			let result = hook.execute_with(source.profiles(), profile.plugins());
+			self.report_hook(HookKind::Pre, hook.command(), &result);
+
+			if let Err(err) = result {
+				tracing::error!("Failed to execute pre-hook ({})", err);
+				let err = Error::hook(hook.command(), err);
+				return Self::finish(self.observer, self.builder.failed(err));
 			};
 		}
 
-		let mut resolver = ResolvingVisitor(self);
-		let walker = Walker::new(profile);
+		let filter = self.options.filter.clone();
+		let templates = self.options.template_cache.as_deref().map(load_template_cache);
+		let mut resolver = ResolvingVisitor::new(self).with_template_cache(templates);
+		let walker = Walker::new(profile).with_filter(filter);
 		if let Err(err) = walker.walk(source, &mut resolver) {
-			return resolver.into_inner().builder.failed(err.to_string());
+			let (this, templates) = resolver.into_parts();
+			this.save_cache();
+			this.save_template_cache(templates.as_ref());
+			let err = ProfileError::new(err.to_string());
+			return Self::finish(this.observer, this.builder.failed(err));
+		}
+
+		let (mut this, templates) = resolver.into_parts();
+		this.save_cache();
+		this.save_template_cache(templates.as_ref());
+
+		for hook in profile.post_hooks().filter(|_| hooks) {
+			tracing::info!("Executing post-hook: {}", hook.command());
+			this.observer.on_event(&DeployEvent::HookStarted {
+				kind: HookKind::Post,
+				command: hook.command(),
+			});
+
+			let result = hook.execute_with(source.profiles(), profile.plugins());
+			this.report_hook(HookKind::Post, hook.command(), &result);
+
+			if let Err(err) = result {
+				tracing::error!("Failed to execute post-hook ({})", err);
+				let err = Error::hook(hook.command(), err);
+				return Self::finish(this.observer, this.builder.failed(err));
+			}
+		}
+
+		if this.cancellation.is_cancelled() {
+			tracing::warn!("Deployment was cancelled");
+			return Self::finish(this.observer, this.builder.failed(Error::Cancelled));
 		}
 
-		let this = resolver.into_inner();
+		// Nothing was written during a dry run, so the assertions would only
+		// check the previous state.
+		if !this.options.dry_run {
+			for assertion in profile.assertions() {
+				tracing::info!("Evaluating assertion: {assertion}");
 
-		for hook in profile.post_hooks() {
-			log::info!("Executing post-hook: {}", hook.command());
-			if let Err(err) = hook.execute(source.profiles()) {
-				log::error!("Failed to execute post-hook ({})", err);
-				return this.builder.failed(err.to_string());
+				let status = match assertion.evaluate(profile.target_path(), source.profiles()) {
+					Ok(()) => ItemStatus::success(),
+					Err(err) => {
+						tracing::error!("Assertion failed ({assertion}): {err:#}");
+						ItemStatus::failed(format!("{err:#}"))
+					}
+				};
+
+				this.builder.add_assertion(assertion.clone(), status);
 			}
 		}
 
-		this.into_deployment()
+		Self::finish(this.observer, this.builder.finish())
+	}
+
+	/// Emits the [`DeployEvent::Finished`] event and returns the `deployment`.
+	fn finish(mut observer: O, deployment: Deployment) -> Deployment {
+		observer.on_event(&DeployEvent::Finished {
+			deployment: &deployment,
+		});
+
+		deployment
+	}
+
+	/// Emits the event for a hook which was executed with the given `result`.
+	fn report_hook(
+		&mut self,
+		kind: HookKind,
+		command: &str,
+		result: &std::result::Result<(), HookError>,
+	) {
+		self.observer.on_event(&DeployEvent::HookFinished {
+			kind,
+			command,
+			error: result.as_ref().err(),
+		});
+	}
+
+	/// Emits the event for an item which was processed with the given `status`.
+	fn report(&mut self, source_path: &Path, target_path: &Path, status: &ItemStatus) {
+		let event = match status {
+			ItemStatus::Success => DeployEvent::ItemDeployed {
+				source_path,
+				target_path,
+			},
+			ItemStatus::Skipped(reason) => DeployEvent::ItemSkipped {
+				source_path,
+				target_path,
+				reason,
+			},
+			ItemStatus::Failed(error) => DeployEvent::ItemFailed {
+				source_path,
+				target_path,
+				error,
+			},
+			ItemStatus::Cancelled => DeployEvent::ItemSkipped {
+				source_path,
+				target_path,
+				reason: "Deployment was cancelled",
+			},
+		};
+
+		self.observer.on_event(&event);
+	}
+
+	/// Emits the event for an item which is about to be deployed.
+	fn report_started(&mut self, source_path: &Path, target_path: &Path) {
+		self.observer.on_event(&DeployEvent::ItemStarted {
+			source_path,
+			target_path,
+		});
+	}
+
+	/// Emits the event for a file whose contents were compiled.
+	fn report_compiled(&mut self, file: &File<'_>, duration: Duration) {
+		self.observer.on_event(&DeployEvent::ItemCompiled {
+			source_path: &file.source_path,
+			target_path: &file.target_path,
+			duration,
+		});
 	}
 
 	/// Checks common things for a given file item before deploying it.
 	///
 	/// The returned boolean indicates if the deployment of the file should
 	/// continue.
-	fn pre_deploy_checks(&mut self, file: &File<'_>) -> color_eyre::Result<bool> {
+	fn pre_deploy_checks(
+		&mut self,
+		profile: &LayeredProfile,
+		file: &File<'_>,
+	) -> color_eyre::Result<bool> {
+		if !self.priority_checks(file)? {
+			return Ok(false);
+		}
+
+		self.target_checks(profile, file)
+	}
+
+	/// Checks if a dotfile with a higher priority was already deployed to the
+	/// target of `file`.
+	///
+	/// The returned boolean indicates if the deployment of the file should
+	/// continue.
+	fn priority_checks(&mut self, file: &File<'_>) -> color_eyre::Result<bool> {
 		let other_priority = self.builder.get_priority(&file.target_path);
 
 		match (file.dotfile().priority.as_ref(), other_priority) {
 			(Some(a), Some(b)) if b > a => {
-				log::info!(
+				tracing::info!(
 					"{}: Dotfile with higher priority is already deployed at {}",
 					file.relative_source_path.display(),
 					file.target_path.display()
 				);
 
-				skipped!(&mut self.builder, file, "Dotfile with higher priority is already deployed" => false);
+				skipped!(self, file, "Dotfile with higher priority is already deployed" => false);
 			}
 			(_, _) => {}
 		};
 
-		if file.target_path.exists() {
+		Ok(true)
+	}
+
+	/// Prepares the target of `file` for the deployment, which includes asking
+	/// to overwrite or backing up an existing file and creating the parent
+	/// directory.
+	///
+	/// The returned boolean indicates if the deployment of the file should
+	/// continue.
+	fn target_checks(
+		&mut self,
+		profile: &LayeredProfile,
+		file: &File<'_>,
+	) -> color_eyre::Result<bool> {
+		if self.filesystem.exists(&file.target_path) {
 			// No previously deployed dotfile at `deploy_path`. Check for merge.
 
-			log::debug!(
+			tracing::debug!(
 				"{}: Dotfile already exists at {}",
 				file.relative_source_path.display(),
 				file.target_path.display()
 			);
 
-			match file.dotfile().merge.unwrap_or_default() {
+			let merge = self.options.merge.or(file.dotfile().merge);
+
+			match merge.unwrap_or_default() {
 				MergeMode::Overwrite => {
-					log::info!(
+					tracing::info!(
 						"{}: Overwriting existing dotfile",
 						file.relative_source_path.display()
 					)
 				}
 				MergeMode::Keep => {
-					log::info!(
+					tracing::info!(
 						"{}: Skipping existing dotfile",
 						file.relative_source_path.display()
 					);
 
-					skipped!(&mut self.builder, file, format!("Dotfile already exists and merge mode is {:?}", MergeMode::Keep) => false);
+					skipped!(self, file, format!("Dotfile already exists and merge mode is {:?}", MergeMode::Keep) => false);
 				}
 				MergeMode::Ask => {
-					log::info!("{}: Asking for action", file.relative_source_path.display());
+					tracing::info!("{}: Asking for action", file.relative_source_path.display());
 
 					let should_deploy =
 						match (self.merge_ask_fn)(&file.source_path, file.target_path.borrow())
@@ -260,37 +768,103 @@ where
 						{
 							Ok(should_deploy) => should_deploy,
 							Err(err) => {
-								log::error!(
+								tracing::error!(
 									"{}: Failed to execute ask function ({})",
 									file.relative_source_path.display(),
 									err
 								);
 
-								failed!(&mut self.builder, file, format!("Failed to execute merge ask function: {err}") => false);
+								failed!(self, file, format!("Failed to execute merge ask function: {err}") => false);
 							}
 						};
 
 					if !should_deploy {
-						log::info!("{}: Merge was denied", file.relative_source_path.display());
+						tracing::info!("{}: Merge was denied", file.relative_source_path.display());
+
+						skipped!(self, file, "Dotfile already exists and merge ask was denied" => false);
+					}
+				}
+			}
+
+			match self.filesystem.protection(&file.target_path) {
+				Ok(None) => {}
+				Ok(Some(protection)) if self.options.force || file.dotfile().is_forced() => {
+					tracing::info!(
+						"{}: Removing {} protection of existing dotfile",
+						file.relative_source_path.display(),
+						protection
+					);
 
-						skipped!(&mut self.builder, file, "Dotfile already exists and merge ask was denied" => false);
+					if !self.options.dry_run {
+						if let Err(err) = self.filesystem.unprotect(&file.target_path) {
+							failed!(self, file, IoError::new(&file.target_path, "Failed to remove write protection", &err) => false);
+						}
 					}
 				}
+				Ok(Some(protection)) => {
+					tracing::info!(
+						"{}: Skipping {} existing dotfile",
+						file.relative_source_path.display(),
+						protection
+					);
+
+					skipped!(self, file, format!("Dotfile already exists and is {protection} (use `--force` to overwrite it)") => false);
+				}
+				Err(err) => {
+					failed!(self, file, IoError::new(&file.target_path, "Failed to check write protection", &err) => false);
+				}
+			}
+		}
+
+		if let (Some(backup_dir), false) = (&self.options.backup_dir, self.options.dry_run) {
+			let is_file = self
+				.filesystem
+				.metadata(&file.target_path)
+				.is_ok_and(|metadata| metadata.is_file());
+
+			if is_file {
+				let backup_path = backup_path(backup_dir, &file.target_path);
+
+				tracing::debug!(
+					"{}: Backing up existing file to {}",
+					file.relative_source_path.display(),
+					backup_path.display()
+				);
+
+				if let Err(err) = backup(&self.filesystem, &file.target_path, &backup_path) {
+					tracing::error!(
+						"{}: Failed to back up existing file ({})",
+						file.relative_source_path.display(),
+						err
+					);
+
+					failed!(self, file, IoError::new(backup_path, "Failed to back up existing file", &err) => false);
+				}
 			}
 		}
 
+		if let Some(parent) = self.missing_parent(profile, file) {
+			tracing::error!(
+				"{}: Parent directory {} does not exist",
+				file.relative_source_path.display(),
+				parent.display()
+			);
+
+			failed!(self, file, missing_parent_error(parent) => false);
+		}
+
 		if let Some(parent) = file.target_path.parent() {
 			if !self.options.dry_run {
-				match std::fs::create_dir_all(parent) {
+				match self.create_dir_all(parent, file.dotfile().dir_mode) {
 					Ok(_) => {}
 					Err(err) => {
-						log::error!(
+						tracing::error!(
 							"{}: Failed to create directory ({})",
 							file.relative_source_path.display(),
 							err
 						);
 
-						failed!(&mut self.builder, file, format!("Failed to create parent directory: {err}") => false);
+						failed!(self, file, IoError::new(parent, "Failed to create parent directory", &err) => false);
 					}
 				}
 			}
@@ -299,6 +873,181 @@ where
 		Ok(true)
 	}
 
+	/// Writes the [cache](`DeployOptions::cache`) of compiled templates.
+	///
+	/// Nothing is written during a dry run. Errors are only logged as the
+	/// cache does not affect the result of the deployment.
+	fn save_cache(&self) {
+		if let (Some(cache), Some(path), false) =
+			(&self.cache, &self.options.cache, self.options.dry_run)
+		{
+			if let Err(err) = cache.save(path) {
+				tracing::warn!("Failed to write deploy cache to {}: {err}", path.display());
+			}
+		}
+	}
+
+	/// Writes the [cache](`DeployOptions::template_cache`) of parsed templates.
+	///
+	/// Errors are only logged as the cache does not affect the result of the
+	/// deployment.
+	fn save_template_cache(&self, templates: Option<&TemplateCache>) {
+		if let (Some(templates), Some(path)) = (templates, &self.options.template_cache) {
+			tracing::debug!(
+				"Used {} of {} cached templates",
+				templates.hits(),
+				templates.len()
+			);
+
+			if let Err(err) = templates.save(path) {
+				tracing::warn!("Failed to write template cache to {}: {err}", path.display());
+			}
+		}
+	}
+
+	/// Checks if the template which was deployed to `target` with the given
+	/// `inputs` hash (see [`cache::inputs_hash`]) is still present there
+	/// according to the cache.
+	///
+	/// The current values of the reads which were recorded in the cache are
+	/// hashed together with `inputs`.
+	fn is_cached(&self, source: &PunktfSource, target: &Path, inputs: &ContentHash) -> bool {
+		let Some(entry) = self.cache.as_ref().and_then(|cache| cache.get(target)) else {
+			return false;
+		};
+
+		cache::reads_hash(inputs, &entry.reads, source) == Some(entry.inputs)
+			&& self
+				.filesystem
+				.read(target)
+				.is_ok_and(|content| ContentHash::of(&content) == entry.target)
+	}
+
+	/// Returns the current stamps of the source and target of the copied
+	/// `file` if they should be compared (see [`DeployOptions::fast_compare`]).
+	fn file_stamps(&self, file: &File<'_>) -> Option<FileEntry> {
+		if !self.options.fast_compare || self.cache.is_none() {
+			return None;
+		}
+
+		let source = file.source_path.metadata().ok().map(Metadata::from)?;
+		let target = self.filesystem.metadata(&file.target_path).ok()?;
+
+		Some(FileEntry {
+			source: FileStamp::of(&source)?,
+			target: FileStamp::of(&target)?,
+		})
+	}
+
+	/// Checks if the source and target of the copied `file` still have the
+	/// stamps which were recorded in the cache.
+	fn has_unchanged_stamps(&self, file: &File<'_>) -> bool {
+		let Some(cached) = self
+			.cache
+			.as_ref()
+			.and_then(|cache| cache.get_file(&file.target_path))
+		else {
+			return false;
+		};
+
+		self.file_stamps(file).as_ref() == Some(cached)
+	}
+
+	/// Returns the missing parent directory of the target of the dotfile of
+	/// `item` if it may not be created (see
+	/// [`Dotfile::create_parents`](`crate::profile::dotfile::Dotfile::create_parents`)).
+	///
+	/// For children of a directory, the parent of the directory is checked, as
+	/// the directories between them are created by the deployment.
+	fn missing_parent(&self, profile: &LayeredProfile, item: &Item<'_>) -> Option<PathBuf> {
+		let create_parents = item
+			.dotfile()
+			.create_parents
+			.unwrap_or_else(|| profile.create_parents());
+
+		if create_parents {
+			return None;
+		}
+
+		let root_target_path = match &item.kind {
+			Kind::Root(_) => &item.target_path,
+			Kind::Child {
+				root_target_path, ..
+			} => root_target_path,
+		};
+
+		root_target_path
+			.parent()
+			.filter(|parent| !parent.as_os_str().is_empty() && !self.filesystem.is_dir(parent))
+			.map(Path::to_path_buf)
+	}
+
+	/// Creates the directory at `path` together with all missing parents.
+	///
+	/// If a `mode` is given, it is applied to all directories which are
+	/// created.
+	fn create_dir_all(&self, path: &Path, mode: Option<DirMode>) -> io::Result<()> {
+		let Some(mode) = mode else {
+			return self.filesystem.create_dir_all(path);
+		};
+
+		// Ordered from the deepest directory, so that restrictive modes do not
+		// prevent changing the mode of the directories below
+		let missing: Vec<_> = path
+			.ancestors()
+			.take_while(|dir| !dir.as_os_str().is_empty() && !self.filesystem.exists(dir))
+			.collect();
+
+		self.filesystem.create_dir_all(path)?;
+
+		for dir in missing {
+			self.filesystem.set_mode(dir, mode.mode())?;
+		}
+
+		Ok(())
+	}
+
+	/// Sets the modification time of the deployed `file` if
+	/// [`Dotfile::preserve_mtime`](`crate::profile::dotfile::Dotfile::preserve_mtime`)
+	/// is set.
+	///
+	/// Copied files get the modification time of their source. `generated`
+	/// files (templates or transformed files) get the latest modification time
+	/// of their source and the profile files.
+	fn preserve_mtime(&self, file: &File<'_>, generated: bool) -> io::Result<()> {
+		if !file.dotfile().preserves_mtime() {
+			return Ok(());
+		}
+
+		let mut modified = file.source_path.metadata()?.modified()?;
+
+		if let Some(profiles_modified) = self.profiles_modified.filter(|_| generated) {
+			modified = modified.max(profiles_modified);
+		}
+
+		self.filesystem.set_modified(&file.target_path, modified)
+	}
+
+	/// Checks if the content of an item, which would cause the given `change`,
+	/// should be written to the target.
+	fn should_write(&self, change: ItemChange) -> bool {
+		let unchanged = self.options.skip_unchanged && change == ItemChange::Unchanged;
+
+		!self.options.dry_run && !unchanged
+	}
+
+	/// Reads the source of `file` and decrypts it if the dotfile is
+	/// [encrypted](`Dotfile::encrypted`).
+	fn read_source(&self, file: &File<'_>) -> std::result::Result<String, Error> {
+		if !file.dotfile().is_encrypted() {
+			return std::fs::read_to_string(&file.source_path)
+				.map_err(|err| IoError::new(&file.source_path, "Failed to read", &err).into());
+		}
+
+		read_source(file, self.encryption.as_ref().map(|shared| &*shared.0))
+			.map_err(|err| Error::other(format!("{err:#}")))
+	}
+
 	/// Applies any relevant [`Transform`](`crate::profile::transform::Transform`)
 	/// for the given file.
 	fn transform_content(
@@ -320,14 +1069,59 @@ where
 			content = match transformer.transform(content) {
 				Ok(content) => content,
 				Err(err) => {
-					log::info!(
+					tracing::info!(
 						"{}: Failed to apply content transformer `{}`: `{}`",
 						file.relative_source_path.display(),
 						transformer,
 						err
 					);
 
-					failed!(&mut self.builder, file, format!("Failed to apply content transformer `{transformer}`: `{err}`") => Err(err));
+					failed!(self, file, ProfileError::new(format!("Failed to apply content transformer `{transformer}`: `{err}`")) => Err(err));
+				}
+			};
+		}
+
+		let cwd = file.source_path.parent().unwrap_or(&file.source_path);
+		for command in &file.dotfile().transform {
+			content = match command.pipe(cwd, &content) {
+				Ok(content) => content,
+				Err(err) => {
+					tracing::info!(
+						"{}: Failed to run transform command `{}`: `{}`",
+						file.relative_source_path.display(),
+						command.command(),
+						err
+					);
+
+					failed!(self, file, Error::hook(command.command(), err.clone()) => Err(err.into()));
+				}
+			};
+		}
+
+		if let Some(formatter) = profile.formatter_for(&file.target_path) {
+			let command = formatter.command.command();
+
+			content = match formatter.command.pipe(cwd, &content) {
+				Ok(formatted) => formatted,
+				Err(err) if formatter.on_failure() == FormatFailure::Ignore => {
+					tracing::warn!(
+						"{}: Formatter `{}` failed, keeping the unformatted content: `{}`",
+						file.relative_source_path.display(),
+						command,
+						err
+					);
+
+					content
+				}
+				Err(err) => {
+					tracing::info!(
+						"{}: Failed to run formatter `{}`: `{}`",
+						file.relative_source_path.display(),
+						command,
+						err
+					);
+
+					failed!(self, file, Error::hook(command, err.clone()) => Err(err.into()));
 				}
 			};
 		}
@@ -336,52 +1130,109 @@ where
 	}
 }
 
-impl<F> Visitor for Deployer<F>
+impl<F, O, S> Visitor for Deployer<F, O, S>
 where
 	F: Fn(&Path, &Path) -> color_eyre::Result<bool>,
+	O: DeployObserver,
+	S: Filesystem,
 {
 	/// Accepts a file item and tries to deploy it.
+	#[tracing::instrument(
+		name = "file",
+		skip_all,
+		fields(path = %file.relative_source_path.display())
+	)]
 	fn accept_file<'a>(
 		&mut self,
 		_: &PunktfSource,
 		profile: &LayeredProfile,
 		file: &File<'a>,
 	) -> Result {
-		log::info!("{}: Deploying file", file.relative_source_path.display());
+		tracing::info!("{}: Deploying file", file.relative_source_path.display());
+		cancelled!(self, file);
+		self.report_started(&file.source_path, &file.target_path);
 
-		let cont = self.pre_deploy_checks(file)?;
+		let cont = self.pre_deploy_checks(profile, file)?;
 
 		if !cont {
 			return Ok(());
 		}
 
+		let mut metrics = ItemMetrics::default();
+		let change;
+		let mut hash = None;
+
 		// Fast path
-		if profile.transformers_len() == 0 && file.dotfile().transformers.is_empty() {
+		if !is_transformed(profile, file) {
 			// File is no template and no transformers are specified. This means
 			// we can take the fast path of just copying via the filesystem.
 
-			// Allowed for readability
-			#[allow(clippy::collapsible_else_if)]
-			if !self.options.dry_run {
-				if let Err(err) = std::fs::copy(&file.source_path, &file.target_path) {
-					log::info!(
-						"{}: Failed to copy file",
-						file.relative_source_path.display()
+			change = if self.has_unchanged_stamps(file) {
+				tracing::debug!(
+					"{}: Size and modification time are unchanged",
+					file.relative_source_path.display()
+				);
+
+				ItemChange::Unchanged
+			} else {
+				item_change(
+					&self.filesystem,
+					&file.target_path,
+					&file.source_path,
+					|| std::fs::read(&file.source_path),
+				)
+			};
+
+			if self.should_write(change) {
+				let start = Instant::now();
+
+				match tracing::info_span!("write").in_scope(|| {
+					self.filesystem
+						.copy_from_source(&file.source_path, &file.target_path)
+				}) {
+					Ok(bytes) => metrics.bytes_written = bytes,
+					Err(err) => {
+						tracing::info!(
+							"{}: Failed to copy file",
+							file.relative_source_path.display()
+						);
+
+						failed!(
+							self,
+							file,
+							IoError::new(&file.target_path, "Failed to copy", &err)
+						);
+					}
+				}
+
+				metrics.write_time = start.elapsed();
+
+				if let Err(err) = self.preserve_mtime(file, false) {
+					failed!(
+						self,
+						file,
+						IoError::new(&file.target_path, "Failed to set modification time", &err)
 					);
+				}
+			}
 
-					failed!(&mut self.builder, file, format!("Failed to copy: {err}"));
+			if let (Some(stamps), false) = (self.file_stamps(file), self.options.dry_run) {
+				if let Some(cache) = &mut self.cache {
+					cache.insert_file(file.target_path.clone(), stamps);
 				}
 			}
 		} else {
-			let content = match std::fs::read_to_string(&file.source_path) {
+			let start = Instant::now();
+
+			let content = match self.read_source(file) {
 				Ok(content) => content,
 				Err(err) => {
-					log::info!(
+					tracing::info!(
 						"{}: Failed to read file",
 						file.relative_source_path.display()
 					);
 
-					failed!(&mut self.builder, file, format!("Failed to read: {err}"));
+					failed!(self, file, err);
 				}
 			};
 
@@ -390,28 +1241,48 @@ where
 				return Ok(());
 			};
 
-			if !self.options.dry_run {
-				if let Err(err) = std::fs::write(&file.target_path, content.as_bytes()) {
-					log::info!(
+			metrics.compile_time = start.elapsed();
+			self.report_compiled(file, metrics.compile_time);
+			change = content_change(&self.filesystem, &file.target_path, content.as_bytes());
+			hash = Some(ContentHash::of(content.as_bytes()));
+
+			if self.should_write(change) {
+				let start = Instant::now();
+
+				if let Err(err) = tracing::info_span!("write")
+					.in_scope(|| self.filesystem.write(&file.target_path, content.as_bytes()))
+				{
+					tracing::info!(
 						"{}: Failed to write content",
 						file.relative_source_path.display()
 					);
 
 					failed!(
-						&mut self.builder,
+						self,
 						file,
-						format!("Failed to write content: {err}")
+						IoError::new(&file.target_path, "Failed to write content", &err)
+					);
+				}
+
+				metrics.write_time = start.elapsed();
+				metrics.bytes_written = content.len() as u64;
+
+				if let Err(err) = self.preserve_mtime(file, true) {
+					failed!(
+						self,
+						file,
+						IoError::new(&file.target_path, "Failed to set modification time", &err)
 					);
 				}
 			}
 		}
 
-		log::info!(
+		tracing::info!(
 			"{}: File successfully deployed",
 			file.relative_source_path.display()
 		);
 
-		success!(&mut self.builder, file);
+		success!(self, file, change: change, metrics: metrics, hash: hash);
 
 		Ok(())
 	}
@@ -420,35 +1291,55 @@ where
 	fn accept_directory<'a>(
 		&mut self,
 		_: &PunktfSource,
-		_: &LayeredProfile,
+		profile: &LayeredProfile,
 		directory: &Directory<'a>,
 	) -> Result {
-		log::info!(
+		tracing::info!(
 			"{}: Deploying directory",
 			directory.relative_source_path.display()
 		);
+		cancelled!(self, directory);
+		self.report_started(&directory.source_path, &directory.target_path);
+
+		if let Some(parent) = self.missing_parent(profile, directory) {
+			tracing::error!(
+				"{}: Parent directory {} does not exist",
+				directory.relative_source_path.display(),
+				parent.display()
+			);
+
+			failed!(self, directory, missing_parent_error(parent));
+		}
+
+		let change = if self.filesystem.is_dir(&directory.target_path) {
+			ItemChange::Unchanged
+		} else {
+			ItemChange::Created
+		};
 
 		if !self.options.dry_run {
-			if let Err(err) = std::fs::create_dir_all(&directory.target_path) {
-				log::error!(
+			if let Err(err) =
+				self.create_dir_all(&directory.target_path, directory.dotfile().dir_mode)
+			{
+				tracing::error!(
 					"{}: Failed to create directory ({})",
 					directory.relative_source_path.display(),
 					err
 				);
 
 				failed!(
-					&mut self.builder,
+					self,
 					directory,
-					format!("Failed to create directory: {err}")
+					IoError::new(&directory.target_path, "Failed to create directory", &err)
 				);
 			} else {
-				success!(&mut self.builder, directory);
+				success!(self, directory, change: change);
 			}
 		} else {
-			success!(&mut self.builder, directory);
+			success!(self, directory, change: change);
 		}
 
-		log::info!(
+		tracing::info!(
 			"{}: Directory successfully deployed",
 			directory.relative_source_path.display()
 		);
@@ -458,18 +1349,20 @@ where
 
 	/// Accepts a link item and tries to deploy it.
 	fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, link: &Symlink) -> Result {
-		log::info!("{}: Deploying symlink", link.source_path.display());
+		tracing::info!("{}: Deploying symlink", link.source_path.display());
+		cancelled!(self, link);
+		self.report_started(&link.source_path, &link.target_path);
 
 		// Log an warning if deploying of links is not supported for the
 		// operating system.
 		#[cfg(all(not(unix), not(windows)))]
 		{
-			log::warn!(
+			tracing::warn!(
 				"[{}]: Symlink operations are only supported for unix and windows systems",
 				source_path.display()
 			);
 			skipped!(
-				&mut self.builder,
+				self,
 				link,
 				"Symlink operations are only supported on unix and windows systems"
 			);
@@ -479,124 +1372,114 @@ where
 		let target_path = &link.target_path;
 
 		// Check that the source exists
-		if !source_path.exists() {
-			log::error!("[{}]: Links source does not exist", source_path.display());
+		if !self.filesystem.exists(source_path) {
+			tracing::error!("[{}]: Links source does not exist", source_path.display());
 
-			failed!(&mut self.builder, link, "Link source does not exist");
+			failed!(
+				self,
+				link,
+				IoError {
+					path: source_path.clone(),
+					message: String::from("Link source does not exist"),
+				}
+			);
 		}
 
+		let change = if self.filesystem.symlink_metadata(target_path).is_err() {
+			ItemChange::Created
+		} else if self
+			.filesystem
+			.read_link(target_path)
+			.is_ok_and(|path| &path == source_path)
+		{
+			ItemChange::Unchanged
+		} else {
+			ItemChange::Updated
+		};
+
 		// Check that either the target does not exist or that i can be replaced
-		if target_path.exists() {
+		if self.filesystem.exists(target_path) {
 			if link.replace {
 				if !self.options.dry_run {
 					// Verify that the target is a symlink
-					let target_metadata = match target_path.symlink_metadata() {
+					let target_metadata = match self.filesystem.symlink_metadata(target_path) {
 						Ok(m) => m,
 						Err(err) => {
-							log::error!("[{}]: Failed to read metadata", source_path.display());
+							tracing::error!("[{}]: Failed to read metadata", source_path.display());
 
 							failed!(
-								&mut self.builder,
+								self,
 								link,
-								format!("Failed get link target metadata: {err}")
+								IoError::new(target_path, "Failed get link target metadata", &err)
 							);
 						}
 					};
 
 					if target_metadata.is_symlink() {
 						// Get metadata of symlink target
-						let res = if let Ok(target_metadata) = target_path.metadata() {
-							if target_metadata.is_dir() {
-								std::fs::remove_dir(target_path)
+						let res = if let Ok(metadata) = self.filesystem.metadata(target_path) {
+							if metadata.is_dir() {
+								self.filesystem.remove_dir(target_path)
 							} else {
-								std::fs::remove_file(target_path)
+								self.filesystem.remove_file(target_path)
 							}
 						} else {
-							std::fs::remove_file(target_path)
-								.or_else(|_| std::fs::remove_dir(target_path))
+							self.filesystem
+								.remove_file(target_path)
+								.or_else(|_| self.filesystem.remove_dir(target_path))
 						};
 
 						if let Err(err) = res {
-							log::error!(
+							tracing::error!(
 								"[{}]: Failed to remove old link at target",
 								source_path.display()
 							);
 
 							failed!(
-								&mut self.builder,
+								self,
 								link,
-								format!("Failed to remove old link target: {err}")
+								IoError::new(target_path, "Failed to remove old link target", &err)
 							);
 						} else {
-							log::info!(
+							tracing::info!(
 								"[{}]: Removed old link target at {}",
 								source_path.display(),
 								target_path.display()
 							);
 						}
 					} else {
-						log::error!(
+						tracing::error!(
 							"[{}]: Target already exists and is no link",
 							source_path.display()
 						);
 
-						failed!(&mut self.builder, link, "Not allowed to replace target");
+						failed!(self, link, "Not allowed to replace target");
 					}
 				}
 			} else {
-				log::error!(
+				tracing::error!(
 					"[{}]: Target already exists and is not allowed to be replaced",
 					source_path.display()
 				);
 
-				skipped!(&mut self.builder, link, "Link target does already exist");
+				skipped!(self, link, "Link target does already exist");
 			}
 		}
 
 		if !self.options.dry_run {
-			cfg_if! {
-				if #[cfg(unix)] {
-					if let Err(err) = std::os::unix::fs::symlink(source_path, target_path) {
-						log::error!("[{}]: Failed to create link", source_path.display());
-
-						failed!(&mut self.builder, link, format!("Failed create link: {err}"));
-					};
-				} else if #[cfg(windows)] {
-					let metadata = match source_path.symlink_metadata() {
-						Ok(m) => m,
-						Err(err) => {
-							log::error!("[{}]: Failed to read metadata", source_path.display());
-
-							failed!(&mut self.builder, link, format!("Failed get link source metadata: {err}"));
-						}
-					};
-
-					if metadata.is_dir() {
-						if let Err(err) = std::os::windows::fs::symlink_dir(source_path, target_path) {
-							log::error!("[{}]: Failed to create directory link", source_path.display());
-
-							failed!(&mut self.builder, link, format!("Failed create directory link: {err}"));
-						};
-					} else if metadata.is_file() {
-						if let Err(err) = std::os::windows::fs::symlink_file(source_path, target_path) {
-							log::error!("[{}]: Failed to create file link", source_path.display());
+			if let Err(err) = self.filesystem.symlink(source_path, target_path) {
+				tracing::error!("[{}]: Failed to create link", source_path.display());
 
-							failed!(&mut self.builder, link, format!("Failed create file link: {err}"));
-						};
-					} else {
-						log::error!("[{}]: Invalid link source type", source_path.display());
-
-						failed!(&mut self.builder, link, "Invalid type of link source");
-					}
-				} else {
-					log::warn!("[{}]: Link operations are only supported for unix and windows systems", source_path.display());
-
-					skipped!(&mut self.builder, link, "Link operations are only supported on unix and windows systems");
-				}
-			}
+				failed!(
+					self,
+					link,
+					IoError::new(target_path, "Failed create link", &err)
+				);
+			};
 		}
 
-		success!(&mut self.builder, link);
+		success!(self, link);
+		self.builder.set_link_change(target_path, change);
 
 		Ok(())
 	}
@@ -608,13 +1491,13 @@ where
 		_: &LayeredProfile,
 		rejected: &Rejected<'a>,
 	) -> Result {
-		log::info!(
+		tracing::info!(
 			"[{}]: Rejected - {}",
 			rejected.relative_source_path.display(),
 			rejected.reason
 		);
 
-		skipped!(&mut self.builder, rejected, rejected.reason.clone());
+		skipped!(self, rejected, rejected.reason.clone());
 	}
 
 	/// Accepts a errored item and reports it.
@@ -624,65 +1507,131 @@ where
 		_: &LayeredProfile,
 		errored: &Errored<'a>,
 	) -> Result {
-		log::error!(
+		tracing::error!(
 			"[{}]: Failed - {}",
 			errored.relative_source_path.display(),
 			errored
 		);
 
-		failed!(&mut self.builder, errored, errored.to_string());
+		let error = match errored
+			.error
+			.as_deref()
+			.and_then(|err| err.downcast_ref::<io::Error>())
+		{
+			Some(_) => Error::from(IoError {
+				path: errored.source_path.clone(),
+				message: errored.to_string(),
+			}),
+			None => Error::from(ProfileError::new(errored.to_string())),
+		};
+
+		failed!(self, errored, error);
 	}
 }
 
-impl<F> TemplateVisitor for Deployer<F>
+impl<F, O, S> TemplateVisitor for Deployer<F, O, S>
 where
 	F: Fn(&Path, &Path) -> color_eyre::Result<bool>,
+	O: DeployObserver,
+	S: Filesystem,
 {
 	/// Accepts a file template item and tries to deploy it.
 	///
 	/// Before the deployment the template is parsed and resolved.
+	#[tracing::instrument(
+		name = "template",
+		skip_all,
+		fields(path = %file.relative_source_path.display())
+	)]
 	fn accept_template<'a>(
 		&mut self,
-		_: &PunktfSource,
+		source: &PunktfSource,
 		profile: &LayeredProfile,
 		file: &File<'a>,
 		// Returns a function to resolve the content to make the resolving lazy
 		// for upstream visitors.
-		resolve_content: impl FnOnce(&str) -> color_eyre::Result<String>,
+		resolve_content: impl FnOnce(&str, &mut BTreeSet<Input>) -> color_eyre::Result<String>,
 	) -> Result {
-		log::info!(
+		tracing::info!(
 			"{}: Deploying template",
 			file.relative_source_path.display()
 		);
+		cancelled!(self, file);
+		self.report_started(&file.source_path, &file.target_path);
 
-		let cont = self.pre_deploy_checks(file)?;
+		let cont = self.priority_checks(file)?;
 
 		if !cont {
 			return Ok(());
 		}
 
-		let content = match std::fs::read_to_string(&file.source_path) {
+		let mut metrics = ItemMetrics::default();
+		let start = Instant::now();
+
+		let content = match self.read_source(file) {
 			Ok(content) => content,
 			Err(err) => {
-				log::info!("{}: Failed read file", file.relative_source_path.display());
+				tracing::info!("{}: Failed read file", file.relative_source_path.display());
 
-				failed!(&mut self.builder, file, format!("Failed to read: {err}"));
+				failed!(self, file, err);
 			}
 		};
 
-		let content = match resolve_content(&content) {
+		// Checked before the target is prepared, so an unchanged target is
+		// neither backed up nor needs to be confirmed
+		let inputs = self
+			.cache
+			.is_some()
+			.then(|| cache::inputs_hash(profile, file, &content));
+
+		if let Some(inputs) = &inputs {
+			if self.is_cached(source, &file.target_path, inputs) {
+				tracing::info!(
+					"{}: Template is unchanged since the last deployment",
+					file.relative_source_path.display()
+				);
+
+				let hash = self
+					.cache
+					.as_ref()
+					.and_then(|cache| cache.get(&file.target_path))
+					.map(|entry| entry.target);
+
+				success!(self, file, change: ItemChange::Unchanged, metrics: metrics, hash: hash);
+
+				return Ok(());
+			}
+		}
+
+		let cont = self.target_checks(profile, file)?;
+
+		if !cont {
+			return Ok(());
+		}
+
+		let mut reads = BTreeSet::new();
+
+		let content = match tracing::info_span!("resolve")
+			.in_scope(|| resolve_content(&content, &mut reads))
+		{
 			Ok(content) => content,
 			Err(err) => {
-				log::info!(
+				tracing::info!(
 					"{}: Failed to resolve template",
 					file.relative_source_path.display()
 				);
 
-				failed!(
-					&mut self.builder,
-					file,
-					format!("Failed to resolve template: {err}")
-				);
+				// Keep the location of the error if it is known
+				let err = err
+					.downcast_ref::<TemplateError>()
+					.cloned()
+					.unwrap_or_else(|| TemplateError {
+						path: Some(file.source_path.clone()),
+						message: err.to_string(),
+						span: None,
+					});
+
+				failed!(self, file, err);
 			}
 		};
 
@@ -691,28 +1640,182 @@ where
 			return Ok(());
 		};
 
-		if !self.options.dry_run {
-			if let Err(err) = std::fs::write(&file.target_path, content.as_bytes()) {
-				log::info!(
+		metrics.compile_time = start.elapsed();
+		self.report_compiled(file, metrics.compile_time);
+		let change = content_change(&self.filesystem, &file.target_path, content.as_bytes());
+
+		if self.should_write(change) {
+			let start = Instant::now();
+
+			if let Err(err) = tracing::info_span!("write")
+				.in_scope(|| self.filesystem.write(&file.target_path, content.as_bytes()))
+			{
+				tracing::info!(
 					"{}: Failed to write content",
 					file.relative_source_path.display()
 				);
 
 				failed!(
-					&mut self.builder,
+					self,
 					file,
-					format!("Failed to write content: {err}")
+					IoError::new(&file.target_path, "Failed to write content", &err)
+				);
+			}
+
+			metrics.write_time = start.elapsed();
+			metrics.bytes_written = content.len() as u64;
+
+			if let Err(err) = self.preserve_mtime(file, true) {
+				failed!(
+					self,
+					file,
+					IoError::new(&file.target_path, "Failed to set modification time", &err)
 				);
 			}
 		}
 
-		log::info!(
+		// Templates which read volatile values (e.g. the current time) are
+		// not cached
+		let inputs = inputs.and_then(|inputs| cache::reads_hash(&inputs, &reads, source));
+		let hash = ContentHash::of(content.as_bytes());
+
+		if let (Some(cache), Some(inputs)) = (&mut self.cache, inputs) {
+			let entry = CacheEntry {
+				inputs,
+				reads,
+				target: hash,
+			};
+
+			cache.insert(file.target_path.clone(), entry);
+		}
+
+		tracing::info!(
 			"{}: Template successfully deployed",
 			file.relative_source_path.display()
 		);
 
-		success!(&mut self.builder, file);
+		success!(self, file, change: change, metrics: metrics, hash: Some(hash));
 
 		Ok(())
 	}
 }
+
+/// Loads the [cache](`DeployOptions::cache`) of compiled templates from `path`.
+///
+/// An empty cache is used if it could not be read.
+fn load_cache(path: &Path) -> DeployCache {
+	DeployCache::load(path).unwrap_or_else(|err| {
+		tracing::warn!("Failed to read deploy cache from {}: {err}", path.display());
+		DeployCache::default()
+	})
+}
+
+/// Loads the [cache](`DeployOptions::template_cache`) of parsed templates from
+/// `path`.
+///
+/// An empty cache is used if it could not be read.
+fn load_template_cache(path: &Path) -> TemplateCache {
+	TemplateCache::load(path).unwrap_or_else(|err| {
+		tracing::warn!("Failed to read template cache from {}: {err}", path.display());
+		TemplateCache::default()
+	})
+}
+
+/// Creates the error for an item whose parent directory does not exist and
+/// may not be created.
+fn missing_parent_error(parent: PathBuf) -> IoError {
+	IoError {
+		path: parent,
+		message: String::from("Parent directory does not exist and `create_parents` is disabled"),
+	}
+}
+
+/// Returns the latest modification time of the files of all profiles in the
+/// extend chain of `profile`.
+fn profiles_modified(source: &PunktfSource, profile: &LayeredProfile) -> Option<SystemTime> {
+	let paths = match collect_profile_names(source) {
+		Ok(paths) => paths,
+		Err(err) => {
+			tracing::warn!("Failed to collect profiles: {err}");
+			return None;
+		}
+	};
+
+	profile
+		.profile_names
+		.iter()
+		.filter_map(|name| paths.get(name))
+		.filter_map(|path| path.metadata().and_then(|m| m.modified()).ok())
+		.max()
+}
+
+/// Returns the path below `backup_dir` at which the existing file at `target`
+/// is backed up.
+fn backup_path(backup_dir: &Path, target: &Path) -> PathBuf {
+	let relative: PathBuf = target
+		.components()
+		.filter(|component| matches!(component, std::path::Component::Normal(_)))
+		.collect();
+
+	backup_dir.join(relative)
+}
+
+/// Copies the file at `target` to `backup_path` on `filesystem`.
+fn backup(filesystem: &impl Filesystem, target: &Path, backup_path: &Path) -> io::Result<()> {
+	let content = filesystem.read(target)?;
+
+	if let Some(parent) = backup_path.parent() {
+		filesystem.create_dir_all(parent)?;
+	}
+
+	filesystem.write(backup_path, &content)
+}
+
+/// Determines how deploying `source` to `target` on `filesystem` would change
+/// the target.
+///
+/// Like `rsync`, files with the same size and modification time (e.g. targets
+/// of dotfiles with
+/// [`Dotfile::preserve_mtime`](`crate::profile::dotfile::Dotfile::preserve_mtime`))
+/// are considered unchanged. Only if the sizes are equal but the modification
+/// times differ, the contents are read (with `read_source`) and compared.
+fn item_change(
+	filesystem: &impl Filesystem,
+	target: &Path,
+	source: &Path,
+	read_source: impl FnOnce() -> io::Result<Vec<u8>>,
+) -> ItemChange {
+	let Ok(target_metadata) = filesystem.metadata(target) else {
+		return ItemChange::Created;
+	};
+
+	if let Some(source_metadata) = source.metadata().ok().map(Metadata::from) {
+		if source_metadata.len != target_metadata.len {
+			return ItemChange::Updated;
+		}
+
+		if source_metadata.modified.is_some()
+			&& source_metadata.modified == target_metadata.modified
+		{
+			return ItemChange::Unchanged;
+		}
+	}
+
+	match (filesystem.read(target), read_source()) {
+		(Ok(old), Ok(new)) if old == new => ItemChange::Unchanged,
+		_ => ItemChange::Updated,
+	}
+}
+
+/// Determines how writing `content` to `target` on `filesystem` would change
+/// the target.
+fn content_change(filesystem: &impl Filesystem, target: &Path, content: &[u8]) -> ItemChange {
+	if !filesystem.exists(target) {
+		return ItemChange::Created;
+	}
+
+	match filesystem.read(target) {
+		Ok(old) if old == content => ItemChange::Unchanged,
+		_ => ItemChange::Updated,
+	}
+}
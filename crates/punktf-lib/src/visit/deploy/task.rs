@@ -0,0 +1,95 @@
+//! Asynchronous deployments.
+//!
+//! [`Deployer::deploy_async`] runs a deployment on the blocking thread pool of
+//! the current [`tokio`] runtime (see [`tokio::task::spawn_blocking`]), so
+//! async applications can await a deployment without blocking the threads of
+//! their executor. Planning, compiling and writing the items is shared with
+//! [`Deployer::deploy`].
+
+use std::path::Path;
+
+use crate::profile::source::PunktfSource;
+use crate::profile::LayeredProfile;
+use crate::visit::deploy::deployment::Deployment;
+use crate::visit::deploy::event::DeployObserver;
+use crate::visit::deploy::filesystem::Filesystem;
+use crate::visit::deploy::Deployer;
+
+impl<F, O, S> Deployer<F, O, S>
+where
+	F: Fn(&Path, &Path) -> color_eyre::Result<bool> + Send + 'static,
+	O: DeployObserver + Send + 'static,
+	S: Filesystem + Send + 'static,
+{
+	/// Deploys the given `profile` on the blocking thread pool of the current
+	/// [`tokio`] runtime.
+	///
+	/// Resolves to the same
+	/// [`Deployment`](`crate::visit::deploy::deployment::Deployment`) which
+	/// [`Deployer::deploy`] would return.
+	///
+	/// # Panics
+	///
+	/// Panics if it is not called from within a [`tokio`] runtime. A panic of
+	/// the deployment is forwarded to the awaiting task.
+	pub async fn deploy_async(
+		self,
+		source: PunktfSource,
+		mut profile: LayeredProfile,
+	) -> Deployment {
+		let result = tokio::task::spawn_blocking(move || self.deploy(&source, &mut profile)).await;
+
+		match result {
+			Ok(deployment) => deployment,
+			Err(err) => match err.try_into_panic() {
+				Ok(panic) => std::panic::resume_unwind(panic),
+				// Blocking tasks are only cancelled if the runtime shuts down
+				// before they started, which also drops the awaiting task
+				Err(err) => unreachable!("Deployment was cancelled: {err}"),
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use color_eyre::Result;
+
+	use super::*;
+	use crate::visit::deploy::DeployOptions;
+
+	#[test]
+	fn deploy_async() -> Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = tempfile::tempdir()?;
+		let root = dir.path().join("source");
+		let target = dir.path().join("target");
+
+		std::fs::create_dir_all(root.join("profiles"))?;
+		std::fs::create_dir_all(root.join("dotfiles"))?;
+		std::fs::write(
+			root.join("profiles").join("test.yaml"),
+			format!(
+				"target: {:?}\ndotfiles:\n  - path: file\n",
+				target.display().to_string()
+			),
+		)?;
+		std::fs::write(root.join("dotfiles").join("file"), "content")?;
+
+		let source = PunktfSource::from_root(root)?;
+		let mut builder = LayeredProfile::build();
+		crate::profile::resolve_profile(&mut builder, &source, "test")?;
+
+		let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+		let deployment = runtime.block_on(
+			Deployer::new(DeployOptions::default(), |_, _| Ok(true))
+				.deploy_async(source, builder.finish()),
+		);
+
+		assert!(deployment.status().is_success());
+		assert_eq!(std::fs::read_to_string(target.join("file"))?, "content");
+
+		Ok(())
+	}
+}
@@ -1,36 +1,9 @@
 //! A [`Visitor`](`crate::visit::Visitor`) implementation which creates events for
 //! files which differ from the content it would have once deployed.
 
-use crate::{
-	profile::LayeredProfile,
-	profile::{source::PunktfSource, transform::Transform},
-	visit::*,
-};
+use crate::{profile::source::PunktfSource, profile::LayeredProfile, visit::*};
 use std::path::Path;
 
-/// Applies any relevant [`Transform`](`crate::profile::transform::Transform`)
-/// for the given file.
-fn transform_content(
-	profile: &LayeredProfile,
-	file: &File<'_>,
-	content: String,
-) -> color_eyre::Result<String> {
-	let mut content = content;
-
-	// Copy so we exec_dotfile is not referenced by this in case an error occurs.
-	let exec_transformers: Vec<_> = file.dotfile().transformers.to_vec();
-
-	// Apply transformers.
-	// Order:
-	//   - Transformers which are specified in the profile root
-	//   - Transformers which are specified on a specific dotfile of a profile
-	for transformer in profile.transformers().chain(exec_transformers.iter()) {
-		content = transformer.transform(content)?;
-	}
-
-	Ok(content)
-}
-
 /// An event which is emitted for every differing item.
 #[derive(Debug)]
 pub enum Event<'a> {
@@ -100,11 +73,11 @@ where
 
 	/// Runs the visitor to completion for a given profile.
 	pub fn diff(self, source: &PunktfSource, profile: &mut LayeredProfile) {
-		let mut resolver = ResolvingVisitor(self);
+		let mut resolver = ResolvingVisitor::new(self);
 		let walker = Walker::new(profile);
 
 		if let Err(err) = walker.walk(source, &mut resolver) {
-			log::error!("Failed to execute diff: {err}");
+			tracing::error!("Failed to execute diff: {err}");
 		}
 	}
 
@@ -125,17 +98,38 @@ macro_rules! safe_read_file_content {
 		match std::fs::read_to_string($path) {
 			Ok(old) => old,
 			Err(err) if err.kind() == std::io::ErrorKind::InvalidData => {
-				log::info!("[{}] Ignored - Binary data", $display_path);
+				tracing::info!("[{}] Ignored - Binary data", $display_path);
 				return Ok(());
 			}
 			Err(err) => {
-				log::error!("[{}] Error - Failed to read file: {err}", $display_path);
+				tracing::error!("[{}] Error - Failed to read file: {err}", $display_path);
 				return Ok(());
 			}
 		}
 	}};
 }
 
+/// Reads the source of the given `file` like [`safe_read_file_content`] and
+/// decrypts it if the dotfile is [encrypted](`crate::profile::dotfile::Dotfile::encrypted`).
+macro_rules! safe_read_source {
+	($profile:expr, $file:expr) => {{
+		if $file.dotfile().is_encrypted() {
+			match read_profile_source($profile, $file) {
+				Ok(new) => new,
+				Err(err) => {
+					tracing::error!(
+						"[{}] Error - Failed to read file: {err:#}",
+						$file.relative_source_path.display()
+					);
+					return Ok(());
+				}
+			}
+		} else {
+			safe_read_file_content!(&$file.source_path, $file.relative_source_path.display())
+		}
+	}};
+}
+
 impl<F> Visitor for Diff<F>
 where
 	F: Fn(Event<'_>),
@@ -154,13 +148,12 @@ where
 			let old =
 				safe_read_file_content!(&file.target_path, file.relative_source_path.display());
 
-			let new =
-				safe_read_file_content!(&file.source_path, file.relative_source_path.display());
+			let new = safe_read_source!(profile, file);
 
 			let new = match transform_content(profile, file, new) {
 				Ok(new) => new,
 				Err(err) => {
-					log::error!(
+					tracing::error!(
 						"[{}] Error - Failed to apply transformer: {err}",
 						file.relative_source_path.display(),
 					);
@@ -210,7 +203,7 @@ where
 	/// # NOTE
 	/// Links are currently not supported for diffing.
 	fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, link: &Symlink) -> Result {
-		log::info!(
+		tracing::info!(
 			"[{}] Ignoring - Symlinks are not supported for diffs",
 			link.source_path.display()
 		);
@@ -225,7 +218,7 @@ where
 		_: &LayeredProfile,
 		rejected: &Rejected<'a>,
 	) -> Result {
-		log::info!(
+		tracing::info!(
 			"[{}] Rejected - {}",
 			rejected.relative_source_path.display(),
 			rejected.reason,
@@ -241,7 +234,7 @@ where
 		_: &LayeredProfile,
 		errored: &Errored<'a>,
 	) -> Result {
-		log::error!(
+		tracing::error!(
 			"[{}] Error - {}",
 			errored.relative_source_path.display(),
 			errored
@@ -266,19 +259,18 @@ where
 		file: &File<'a>,
 		// Returns a function to resolve the content to make the resolving lazy
 		// for upstream visitors.
-		resolve_content: impl FnOnce(&str) -> color_eyre::Result<String>,
+		resolve_content: impl FnOnce(&str, &mut BTreeSet<Input>) -> color_eyre::Result<String>,
 	) -> Result {
 		if file.target_path.exists() {
 			let old =
 				safe_read_file_content!(&file.target_path, file.relative_source_path.display());
 
-			let new =
-				safe_read_file_content!(&file.source_path, file.relative_source_path.display());
+			let new = safe_read_source!(profile, file);
 
-			let new = match resolve_content(&new) {
+			let new = match resolve_content(&new, &mut BTreeSet::new()) {
 				Ok(content) => content,
 				Err(err) => {
-					log::error!(
+					tracing::error!(
 						"[{}] Error - Failed to resolve template: {err}",
 						file.source_path.display()
 					);
@@ -290,7 +282,7 @@ where
 			let new = match transform_content(profile, file, new) {
 				Ok(new) => new,
 				Err(err) => {
-					log::error!(
+					tracing::error!(
 						"[{}] Error - Failed to apply transformer: {err}",
 						file.relative_source_path.display(),
 					);
@@ -0,0 +1,298 @@
+//! Filtering of items by their source path.
+//!
+//! A [`PathFilter`] is used by the [`Walker`](`crate::visit::Walker`) to
+//! restrict a run to a subset of the dotfiles.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::Context;
+use color_eyre::Result;
+use globset::{Glob, GlobBuilder, GlobMatcher};
+
+/// A single pattern of a [`PathFilter`].
+#[derive(Debug, Clone)]
+enum Pattern {
+	/// Matches the path and everything below it.
+	Prefix(PathBuf),
+
+	/// Matches all paths which match the glob and everything below them.
+	Glob(Glob, GlobMatcher),
+}
+
+impl Pattern {
+	/// Creates a new pattern.
+	///
+	/// If `pattern` contains any glob meta characters (`*?[{`) it is
+	/// interpreted as glob, otherwise as path prefix.
+	fn new(pattern: &str) -> Result<Self> {
+		if pattern.contains(['*', '?', '[', '{']) {
+			let glob = GlobBuilder::new(pattern)
+				.literal_separator(true)
+				.build()
+				.wrap_err_with(|| format!("Invalid filter pattern `{pattern}`"))?;
+			let matcher = glob.compile_matcher();
+
+			Ok(Self::Glob(glob, matcher))
+		} else {
+			Ok(Self::Prefix(PathBuf::from(pattern.trim_end_matches('/'))))
+		}
+	}
+
+	/// Returns the pattern as it was given.
+	fn as_str(&self) -> &str {
+		match self {
+			Self::Prefix(prefix) => prefix.to_str().unwrap_or_default(),
+			Self::Glob(glob, _) => glob.glob(),
+		}
+	}
+
+	/// Checks if `path` or any of its ancestors match the pattern.
+	fn matches(&self, path: &Path) -> bool {
+		match self {
+			Self::Prefix(prefix) => path.starts_with(prefix),
+			Self::Glob(_, matcher) => path
+				.ancestors()
+				.filter(|p| !p.as_os_str().is_empty())
+				.any(|p| matcher.is_match(p)),
+		}
+	}
+
+	/// Checks if any path below the directory `path` could match the pattern.
+	fn may_match_below(&self, path: &Path) -> bool {
+		match self {
+			Self::Prefix(prefix) => prefix.starts_with(path),
+			// Globs can match at any depth
+			Self::Glob(..) => true,
+		}
+	}
+}
+
+/// The result of checking a path against a [`PathFilter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FilterMatch {
+	/// The item is included.
+	Accept,
+
+	/// The directory itself is not included but items below it may be, so its
+	/// contents need to be checked.
+	Descend,
+
+	/// The item and everything below it is excluded.
+	Reject,
+}
+
+/// Restricts a run to the items whose source path (relative to the `dotfiles`
+/// directory) matches the filter.
+///
+/// Each pattern is either a path prefix (e.g. `nvim` matches `nvim` and
+/// everything below it) or a glob (e.g. `zsh/*.zsh`). An empty filter accepts
+/// everything.
+///
+/// Exclude patterns always take precedence over `only` patterns.
+#[derive(Debug, Default, Clone)]
+pub struct PathFilter {
+	/// Only items which match any of these patterns are included.
+	only: Vec<Pattern>,
+
+	/// Items which match any of these patterns are excluded.
+	exclude: Vec<Pattern>,
+}
+
+impl PathFilter {
+	/// Creates a new filter which accepts everything.
+	pub const fn new() -> Self {
+		Self {
+			only: Vec::new(),
+			exclude: Vec::new(),
+		}
+	}
+
+	/// Restricts the filter to items which match `pattern`.
+	///
+	/// If this is called multiple times, items matching any of the patterns
+	/// are included.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the pattern is not a valid glob.
+	pub fn only(mut self, pattern: &str) -> Result<Self> {
+		self.only.push(Pattern::new(pattern)?);
+		Ok(self)
+	}
+
+	/// Excludes all items which match `pattern`.
+	///
+	/// # Errors
+	///
+	/// An error is returned if the pattern is not a valid glob.
+	pub fn exclude(mut self, pattern: &str) -> Result<Self> {
+		self.exclude.push(Pattern::new(pattern)?);
+		Ok(self)
+	}
+
+	/// Indicates if the filter has no patterns and accepts everything.
+	pub const fn is_empty(&self) -> bool {
+		self.only.is_empty() && self.exclude.is_empty()
+	}
+
+	/// Indicates if the filter only accepts explicitly included items.
+	///
+	/// This is the case if any `only` pattern is set.
+	pub const fn is_restricted(&self) -> bool {
+		!self.only.is_empty()
+	}
+
+	/// Checks the source path of an item relative to the `dotfiles` directory.
+	pub fn check(&self, relative: &Path, is_dir: bool) -> FilterMatch {
+		if self.exclude.iter().any(|p| p.matches(relative)) {
+			FilterMatch::Reject
+		} else if self.only.is_empty() || self.only.iter().any(|p| p.matches(relative)) {
+			FilterMatch::Accept
+		} else if is_dir && self.only.iter().any(|p| p.may_match_below(relative)) {
+			FilterMatch::Descend
+		} else {
+			FilterMatch::Reject
+		}
+	}
+}
+
+impl fmt::Display for PathFilter {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let only: Vec<_> = self.only.iter().map(Pattern::as_str).collect();
+		let exclude: Vec<_> = self.exclude.iter().map(Pattern::as_str).collect();
+
+		write!(
+			f,
+			"only: [{}], exclude: [{}]",
+			only.join(", "),
+			exclude.join(", ")
+		)
+	}
+}
+
+impl PartialEq for PathFilter {
+	fn eq(&self, other: &Self) -> bool {
+		/// Compares two pattern lists by their original pattern strings.
+		fn eq_patterns(a: &[Pattern], b: &[Pattern]) -> bool {
+			a.iter()
+				.map(Pattern::as_str)
+				.eq(b.iter().map(Pattern::as_str))
+		}
+
+		eq_patterns(&self.only, &other.only) && eq_patterns(&self.exclude, &other.exclude)
+	}
+}
+
+impl Eq for PathFilter {}
+
+impl Hash for PathFilter {
+	fn hash<H: Hasher>(&self, state: &mut H) {
+		for patterns in [&self.only, &self.exclude] {
+			patterns.len().hash(state);
+
+			for pattern in patterns {
+				pattern.as_str().hash(state);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn empty_filter() {
+		let filter = PathFilter::new();
+
+		assert!(filter.is_empty());
+		assert_eq!(filter.check(Path::new("nvim"), true), FilterMatch::Accept);
+	}
+
+	#[test]
+	fn prefix_filter() -> Result<()> {
+		let filter = PathFilter::new().only("nvim")?.only("zsh/.zshrc")?;
+
+		assert_eq!(filter.check(Path::new("nvim"), true), FilterMatch::Accept);
+		assert_eq!(
+			filter.check(Path::new("nvim/init.vim"), false),
+			FilterMatch::Accept
+		);
+		assert_eq!(filter.check(Path::new("nvim2"), false), FilterMatch::Reject);
+		assert_eq!(filter.check(Path::new("zsh"), true), FilterMatch::Descend);
+		assert_eq!(
+			filter.check(Path::new("zsh/.zshrc"), false),
+			FilterMatch::Accept
+		);
+		assert_eq!(
+			filter.check(Path::new("zsh/.zshenv"), false),
+			FilterMatch::Reject
+		);
+		assert_eq!(filter.check(Path::new("git"), true), FilterMatch::Reject);
+
+		Ok(())
+	}
+
+	#[test]
+	fn glob_filter() -> Result<()> {
+		let filter = PathFilter::new().only("*/*.vim")?;
+
+		assert_eq!(filter.check(Path::new("nvim"), true), FilterMatch::Descend);
+		assert_eq!(
+			filter.check(Path::new("nvim/init.vim"), false),
+			FilterMatch::Accept
+		);
+		assert_eq!(
+			filter.check(Path::new("nvim/lua/init.lua"), false),
+			FilterMatch::Reject
+		);
+		assert_eq!(
+			filter.check(Path::new("init.vim"), false),
+			FilterMatch::Reject
+		);
+
+		assert!(PathFilter::new().only("[").is_err());
+
+		Ok(())
+	}
+
+	#[test]
+	fn exclude_filter() -> Result<()> {
+		let filter = PathFilter::new().exclude("nvim/lua")?.exclude("**/*.bak")?;
+
+		assert!(!filter.is_restricted());
+		assert_eq!(filter.check(Path::new("nvim"), true), FilterMatch::Accept);
+		assert_eq!(
+			filter.check(Path::new("nvim/lua"), true),
+			FilterMatch::Reject
+		);
+		assert_eq!(
+			filter.check(Path::new("nvim/lua/init.lua"), false),
+			FilterMatch::Reject
+		);
+		assert_eq!(
+			filter.check(Path::new("zsh/.zshrc.bak"), false),
+			FilterMatch::Reject
+		);
+		assert_eq!(
+			filter.check(Path::new("zsh/.zshrc"), false),
+			FilterMatch::Accept
+		);
+
+		let filter = PathFilter::new().only("nvim")?.exclude("nvim/lua")?;
+
+		assert!(filter.is_restricted());
+		assert_eq!(
+			filter.check(Path::new("nvim/init.vim"), false),
+			FilterMatch::Accept
+		);
+		assert_eq!(
+			filter.check(Path::new("nvim/lua/init.lua"), false),
+			FilterMatch::Reject
+		);
+
+		Ok(())
+	}
+}
@@ -4,25 +4,180 @@
 
 pub mod deploy;
 pub mod diff;
+pub mod filter;
+pub mod plan;
+mod scan;
 
 use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
 use std::fmt;
+use std::fs::FileType;
 use std::io;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
 
+use crate::expr::Input;
+use crate::profile::condition::{self, EvalConditionError};
+use crate::profile::dotfile::LimitAction;
+use crate::profile::encryption::EncryptionBackend;
+use crate::profile::formatter::FormatFailure;
 use crate::profile::link;
-use crate::profile::LayeredProfile;
+use crate::profile::source::ConflictPolicy;
+use crate::profile::transform::Transform as _;
+use crate::profile::variables::Vars as _;
 use crate::profile::{dotfile::Dotfile, source::PunktfSource};
+use crate::profile::{LayeredProfile, Priority, SourceSymlinks};
+use crate::visit::filter::{FilterMatch, PathFilter};
+use crate::visit::scan::Scan;
 
-use color_eyre::eyre::Context;
+use color_eyre::eyre::{eyre, Context};
 
+use crate::template::cache::TemplateCache;
 use crate::template::source::Source;
 use crate::template::Template;
 
 /// Result type for this module.
 pub type Result = std::result::Result<(), Box<dyn std::error::Error>>;
 
+/// Applies any relevant [`Transform`](`crate::profile::transform::Transform`)
+/// for the given file.
+pub(crate) fn transform_content(
+	profile: &LayeredProfile,
+	file: &File<'_>,
+	content: String,
+) -> color_eyre::Result<String> {
+	let mut content = content;
+
+	// Copy so we exec_dotfile is not referenced by this in case an error occurs.
+	let exec_transformers: Vec<_> = file.dotfile().transformers.to_vec();
+
+	// Apply transformers.
+	// Order:
+	//   - Transformers which are specified in the profile root
+	//   - Transformers which are specified on a specific dotfile of a profile
+	for transformer in profile.transformers().chain(exec_transformers.iter()) {
+		content = transformer.transform(content)?;
+	}
+
+	let cwd = file.source_path.parent().unwrap_or(&file.source_path);
+	for command in &file.dotfile().transform {
+		content = command
+			.pipe(cwd, &content)
+			.wrap_err_with(|| format!("Failed to run transform command `{}`", command.command()))?;
+	}
+
+	if let Some(formatter) = profile.formatter_for(&file.target_path) {
+		content = match formatter.command.pipe(cwd, &content) {
+			Ok(formatted) => formatted,
+			Err(err) if formatter.on_failure() == FormatFailure::Ignore => {
+				tracing::warn!(
+					"{}: Formatter `{}` failed, keeping the unformatted content: {err}",
+					file.relative_source_path.display(),
+					formatter.command.command()
+				);
+				content
+			}
+			Err(err) => {
+				return Err(err).wrap_err_with(|| {
+					format!("Failed to run formatter `{}`", formatter.command.command())
+				})
+			}
+		};
+	}
+
+	Ok(content)
+}
+
+/// Reads the source of `file`.
+///
+/// Sources of [encrypted](`Dotfile::encrypted`) dotfiles are decrypted with
+/// `encryption`.
+///
+/// # Errors
+///
+/// An error is returned if the source could not be read or decrypted, or if
+/// its content is not valid UTF-8.
+pub(crate) fn read_source(
+	file: &File<'_>,
+	encryption: Option<&dyn EncryptionBackend>,
+) -> color_eyre::Result<String> {
+	if !file.dotfile().is_encrypted() {
+		return Ok(std::fs::read_to_string(&file.source_path)?);
+	}
+
+	let encryption = encryption
+		.ok_or_else(|| eyre!("Dotfile is encrypted but the profile has no `encryption`"))?;
+
+	let encrypted = std::fs::read(&file.source_path)?;
+	let content = encryption
+		.decrypt(&encrypted)
+		.wrap_err("Failed to decrypt")?;
+
+	String::from_utf8(content).wrap_err("Decrypted content is not valid UTF-8")
+}
+
+/// Reads the source of `file` like [`read_source`] and decrypts it with the
+/// backend of the [encryption](`LayeredProfile::encryption`) of `profile`.
+///
+/// # Errors
+///
+/// An error is returned if the backend could not be created or if the source
+/// could not be read or decrypted.
+pub(crate) fn read_profile_source(
+	profile: &LayeredProfile,
+	file: &File<'_>,
+) -> color_eyre::Result<String> {
+	let backend = match profile.encryption() {
+		Some(encryption) if file.dotfile().is_encrypted() => Some(encryption.backend()?),
+		_ => None,
+	};
+
+	read_source(file, backend.as_deref())
+}
+
+/// Checks if the content of `file` is changed by any transformer, transform
+/// command or formatter before it is deployed, or if its source is encrypted.
+pub(crate) fn is_transformed(profile: &LayeredProfile, file: &File<'_>) -> bool {
+	file.dotfile().is_encrypted()
+		|| profile.transformers_len() != 0
+		|| !file.dotfile().transformers.is_empty()
+		|| !file.dotfile().transform.is_empty()
+		|| profile.formatter_for(&file.target_path).is_some()
+}
+
+/// Resolves the type of the item the symlink at `path` points to.
+///
+/// If it points to a directory, the canonical path of the directory is returned
+/// as well.
+///
+/// # Errors
+///
+/// An error is returned if the symlink is broken.
+/// An error is returned if the symlink points to a directory which contains it
+/// or which is in `followed`, as following it would result in a loop.
+fn follow_symlink(path: &Path, followed: &[PathBuf]) -> io::Result<(FileType, Option<PathBuf>)> {
+	let metadata = path.metadata()?;
+
+	if !metadata.is_dir() {
+		return Ok((metadata.file_type(), None));
+	}
+
+	let directory = path.canonicalize()?;
+	let parent = path.parent().map(Path::canonicalize).transpose()?;
+
+	if followed.contains(&directory) || parent.is_some_and(|parent| parent.starts_with(&directory))
+	{
+		return Err(io::Error::new(
+			io::ErrorKind::InvalidInput,
+			format!("Symlink loop detected at {}", directory.display()),
+		));
+	}
+
+	Ok((metadata.file_type(), Some(directory)))
+}
+
 /// A struct to keep two paths in sync while appending relative child paths.
 #[derive(Debug, Clone)]
 struct PathLink {
@@ -91,6 +246,24 @@ impl Paths {
 		}
 	}
 
+	/// Replaces the source path of the root dotfile.
+	///
+	/// This is used to switch to the same dotfile inside another source
+	/// [layer](`crate::profile::source::PunktfSource::layers`).
+	fn with_root_source(mut self, root_source: PathBuf) -> Self {
+		self.root.source = root_source;
+		self
+	}
+
+	/// Retrieves the source path of the child relative to the root dotfile.
+	///
+	/// If this is not a child instance, an empty path will be returned instead.
+	fn child_relative_source_path(&self) -> &Path {
+		self.child
+			.as_ref()
+			.map_or_else(|| Path::new(""), |child| child.source.as_path())
+	}
+
 	/// Checks if this instance points to a actual
 	/// [`Dotfile`](`crate::profile::dotfile::Dotfile`).
 	pub const fn is_root(&self) -> bool {
@@ -200,8 +373,9 @@ impl<'a> Item<'a> {
 	fn new(source: &PunktfSource, paths: Paths, dotfile: &'a Dotfile) -> Self {
 		let source_path = paths.child_source_path().into_owned();
 		let target_path = paths.child_target_path().into_owned();
-		let relative_source_path = source_path
-			.strip_prefix(&source.dotfiles)
+		let relative_source_path = source
+			.layers()
+			.find_map(|layer| source_path.strip_prefix(&layer.dotfiles).ok())
 			.expect("Dotfile is not in the dotfile root")
 			.to_path_buf();
 		let kind = Kind::from_paths(paths, dotfile);
@@ -373,14 +547,27 @@ pub trait Visitor {
 	) -> Result;
 }
 
+/// State of a single [`Walker::walk`] which is passed through all items.
+#[derive(Debug)]
+struct WalkState {
+	/// The contents of all directories read before the walk.
+	scan: Scan,
+
+	/// Canonical paths of the directories reached through followed symlinks
+	/// (see [`SourceSymlinks::Follow`]) which are currently walked.
+	followed: Vec<PathBuf>,
+}
+
 /// Walks over each item of a [`LayeredProfile`](`crate::profile::LayeredProfile`)
 /// and calls the appropriate functions of the given visitor.
 #[derive(Debug)]
 pub struct Walker<'a> {
-	// Filter? "--filter='name=*'"
 	// Sort by priority and eliminate duplicate lower ones
 	/// The profile to walk.
 	profile: &'a LayeredProfile,
+
+	/// Only items accepted by this filter are walked.
+	filter: PathFilter,
 }
 
 impl<'a> Walker<'a> {
@@ -396,27 +583,157 @@ impl<'a> Walker<'a> {
 			dotfiles.sort_by_key(|(_, d)| -(d.priority.map(|p| p.0).unwrap_or(0) as i64));
 		};
 
-		Self { profile }
+		Self {
+			profile,
+			filter: PathFilter::new(),
+		}
+	}
+
+	/// Restricts the walk to the items accepted by `filter`.
+	///
+	/// Items which are not accepted are passed to
+	/// [`Visitor::accept_rejected`].
+	pub fn with_filter(mut self, filter: PathFilter) -> Self {
+		self.filter = filter;
+		self
 	}
 
 	/// Walks the profile and calls the appropriate functions on the given [`Visitor`].
+	///
+	/// All directories of the dotfiles are read in parallel before the first
+	/// item is visited (see [`Scan`]). Directories rejected by the filter are
+	/// not read at all.
 	pub fn walk(&self, source: &PunktfSource, visitor: &mut impl Visitor) -> Result {
+		let mut state = WalkState {
+			scan: self.scan(source),
+			followed: Vec::new(),
+		};
+
 		for dotfile in self.profile.dotfiles() {
-			self.walk_dotfile(source, visitor, dotfile)?;
+			for instance in self.instances(dotfile) {
+				match instance {
+					Ok(instance) => self.walk_dotfile(source, visitor, &mut state, &instance)?,
+					Err(err) => {
+						let paths = Paths::new(dotfile.path.clone(), dotfile.path.clone());
+
+						self.walk_errored(
+							source,
+							visitor,
+							paths,
+							dotfile,
+							None::<io::Error>,
+							Some(format!("{err:#}")),
+						)?;
+					}
+				}
+			}
 		}
 
-		for link in self.profile.symlinks() {
-			self.walk_link(source, visitor, link)?;
+		// Links have no source path inside the `dotfiles` directory, which means
+		// they can not be explicitly included by a filter.
+		if !self.filter.is_restricted() {
+			for link in self.profile.symlinks() {
+				self.walk_link(source, visitor, link)?;
+			}
+		} else {
+			tracing::info!("Skipping links as a filter is set");
 		}
 
 		Ok(())
 	}
 
+	/// Reads the source directories of all dotfiles in parallel.
+	///
+	/// Directories whose children exceed the
+	/// [`Dotfile::max_depth`](`crate::profile::dotfile::Dotfile::max_depth`)
+	/// are not read.
+	fn scan(&self, source: &PunktfSource) -> Scan {
+		let mut roots: Vec<(PathBuf, Option<usize>)> = self
+			.profile
+			.dotfiles()
+			.flat_map(|dotfile| self.instances(dotfile))
+			.filter_map(|instance| instance.ok())
+			.filter(|dotfile| self.is_enabled(source, dotfile).unwrap_or(true))
+			.flat_map(|dotfile| {
+				self.resolve_layer_source_paths(source, &dotfile)
+					.into_iter()
+					.map(|(_, path)| (path, dotfile.max_depth))
+					.collect::<Vec<_>>()
+			})
+			.filter(|(path, _)| path.is_dir())
+			.collect();
+
+		roots.sort();
+		roots.dedup();
+
+		let within_depth = |path: &Path| {
+			roots.iter().any(|(root, max_depth)| {
+				path.strip_prefix(root).is_ok_and(|relative| {
+					max_depth.is_none_or(|max_depth| relative.components().count() < max_depth)
+				})
+			})
+		};
+
+		Scan::new(
+			roots.iter().map(|(root, _)| root.clone()).collect(),
+			|path| within_depth(path) && self.accept(source, path) != FilterMatch::Reject,
+		)
+	}
+
+	/// Returns the dotfiles which are deployed for `dotfile`.
+	///
+	/// This is the dotfile itself or, if it has
+	/// [instances](`crate::profile::dotfile::Dotfile::instance_variables`), one
+	/// dotfile per instance.
+	fn instances<'d>(&self, dotfile: &'d Dotfile) -> Vec<color_eyre::Result<Cow<'d, Dotfile>>> {
+		let instances = match dotfile.instance_variables(self.profile.variables()) {
+			Ok(Some(instances)) => instances,
+			Ok(None) => return vec![Ok(Cow::Borrowed(dotfile))],
+			Err(err) => return vec![Err(err)],
+		};
+
+		instances
+			.iter()
+			.map(|instance| {
+				dotfile
+					.instantiate(instance, self.profile.variables())
+					.map(Cow::Owned)
+			})
+			.collect()
+	}
+
+	/// Checks if the [`Dotfile::when`](`crate::profile::dotfile::Dotfile::when`)
+	/// condition of `dotfile` holds.
+	///
+	/// Variables are looked up in the dotfile, the profile, the
+	/// [built-ins](`condition::builtin`) and the environment, in this order.
+	/// Relative paths are resolved from the `dotfiles` directory of the
+	/// `source`. Dotfiles without a condition are always enabled.
+	fn is_enabled(
+		&self,
+		source: &PunktfSource,
+		dotfile: &Dotfile,
+	) -> std::result::Result<bool, EvalConditionError> {
+		dotfile.when.as_ref().map_or(Ok(true), |condition| {
+			condition.eval_with_base_dir(Some(source.dotfiles()), |name| {
+				dotfile
+					.variables
+					.as_ref()
+					.and_then(|vars| vars.var(name))
+					.or_else(|| self.profile.variables().var(name))
+					.map(Cow::Borrowed)
+					.or_else(|| condition::builtin(name))
+					.or_else(|| std::env::var(name).ok().map(Cow::Owned))
+			})
+		})
+	}
+
 	/// Walks each item of a [`Dotfile`](`crate::profile::dotfile::Dotfile`).
 	fn walk_dotfile(
 		&self,
 		source: &PunktfSource,
 		visitor: &mut impl Visitor,
+		state: &mut WalkState,
 		dotfile: &Dotfile,
 	) -> Result {
 		let source_path = match self.resolve_source_path(source, dotfile) {
@@ -435,6 +752,15 @@ impl<'a> Walker<'a> {
 			}
 		};
 
+		let layer_source_paths = self.resolve_layer_source_paths(source, dotfile);
+		let selection = self.select_layer(source, &dotfile.path, &layer_source_paths);
+		let source_path = match selection {
+			Ok(Some(idx)) => layer_source_paths[idx].1.clone(),
+			Ok(None) => source_path,
+			// Report the conflict for the item with the highest precedence
+			Err(_) => layer_source_paths[0].1.clone(),
+		};
+
 		let target_path = match self.resolve_target_path(dotfile, source_path.is_dir()) {
 			Ok(p) => p,
 			Err(err) => {
@@ -453,6 +779,42 @@ impl<'a> Walker<'a> {
 
 		let paths = Paths::new(source_path, target_path);
 
+		match self.is_enabled(source, dotfile) {
+			Ok(true) => {}
+			Ok(false) => {
+				let reason = format!(
+					"Condition `{}` does not hold",
+					dotfile
+						.when
+						.as_ref()
+						.map_or("", |condition| condition.as_str())
+				);
+
+				return self.walk_rejected(source, visitor, paths, dotfile, reason);
+			}
+			Err(err) => {
+				return self.walk_errored(
+					source,
+					visitor,
+					paths,
+					dotfile,
+					Some(err),
+					Some("Failed to evaluate the condition of the dotfile"),
+				);
+			}
+		}
+
+		if let Err(context) = selection {
+			return self.walk_errored(
+				source,
+				visitor,
+				paths,
+				dotfile,
+				None::<io::Error>,
+				Some(context),
+			);
+		}
+
 		if !paths.child_source_path().exists() {
 			let context = format!(
 				"Dotfile at {} does not exist",
@@ -470,7 +832,7 @@ impl<'a> Walker<'a> {
 			);
 		};
 
-		self.walk_path(source, visitor, paths, dotfile)
+		self.walk_path(source, visitor, state, paths, dotfile)
 	}
 
 	/// Walks a specific path of a [`Dotfile`](`crate::profile::dotfile::Dotfile`).
@@ -480,19 +842,36 @@ impl<'a> Walker<'a> {
 		&self,
 		source: &PunktfSource,
 		visitor: &mut impl Visitor,
+		state: &mut WalkState,
 		paths: Paths,
 		dotfile: &Dotfile,
 	) -> Result {
 		let source_path = paths.child_source_path();
 
-		if !self.accept(&source_path) {
-			return self.walk_rejected(source, visitor, paths, dotfile);
+		match self.accept(source, &source_path) {
+			FilterMatch::Accept => {}
+			FilterMatch::Descend => {
+				return self.walk_children(source, visitor, state, paths, dotfile);
+			}
+			FilterMatch::Reject => {
+				return self.walk_rejected(source, visitor, paths, dotfile, "Rejected by filter");
+			}
+		}
+
+		let depth = paths.child_relative_source_path().components().count();
+		if let Some(max_depth) = dotfile.max_depth.filter(|&max_depth| depth > max_depth) {
+			let reason = format!("Exceeds the maximum depth of {max_depth}");
+			return self.walk_limited(source, visitor, paths, dotfile, reason);
 		}
 
-		// For now dont follow symlinks (`metadata()` would get the metadata of the target of a
-		// link).
-		let metadata = match source_path.symlink_metadata() {
-			Ok(metadata) => metadata,
+		// Dont follow symlinks here (`metadata()` would get the metadata of the target of a
+		// link), as they are handled according to the `source_symlinks` policy.
+		let mut file_type = match state
+			.scan
+			.file_type(&source_path)
+			.map_or_else(|| source_path.symlink_metadata().map(|m| m.file_type()), Ok)
+		{
+			Ok(file_type) => file_type,
 			Err(err) => {
 				return self.walk_errored(
 					source,
@@ -505,10 +884,88 @@ impl<'a> Walker<'a> {
 			}
 		};
 
-		if metadata.is_file() {
+		let mut followed = None;
+
+		if file_type.is_symlink() {
+			match self.profile.source_symlinks() {
+				SourceSymlinks::Follow => match follow_symlink(&source_path, &state.followed) {
+					Ok((target_type, directory)) => {
+						file_type = target_type;
+						followed = directory;
+					}
+					Err(err) => {
+						return self.walk_errored(
+							source,
+							visitor,
+							paths,
+							dotfile,
+							Some(err),
+							Some("Failed to follow symlink"),
+						);
+					}
+				},
+				SourceSymlinks::Skip => {
+					return self.walk_rejected(
+						source,
+						visitor,
+						paths,
+						dotfile,
+						"Symlink in source",
+					);
+				}
+				SourceSymlinks::DeployAsLink => {
+					return self.walk_source_link(source, visitor, paths, dotfile);
+				}
+				SourceSymlinks::Error => {
+					let err = io::Error::new(io::ErrorKind::Unsupported, "Symlink in source");
+
+					return self.walk_errored(
+						source,
+						visitor,
+						paths,
+						dotfile,
+						Some(err),
+						Some("Handling of symlinks is configured with `source_symlinks`"),
+					);
+				}
+			}
+		}
+
+		if file_type.is_file() {
+			if let Some(max_file_size) = dotfile.max_file_size {
+				let len = match source_path.metadata() {
+					Ok(metadata) => metadata.len(),
+					Err(err) => {
+						return self.walk_errored(
+							source,
+							visitor,
+							paths,
+							dotfile,
+							Some(err),
+							Some("Failed to resolve metadata"),
+						);
+					}
+				};
+
+				if len > max_file_size {
+					let reason =
+						format!("Size of {len} bytes exceeds the maximum of {max_file_size} bytes");
+					return self.walk_limited(source, visitor, paths, dotfile, reason);
+				}
+			}
+
 			self.walk_file(source, visitor, paths, dotfile)
-		} else if metadata.is_dir() {
-			self.walk_directory(source, visitor, paths, dotfile)
+		} else if file_type.is_dir() {
+			let is_followed = followed.is_some();
+			state.followed.extend(followed);
+
+			let result = self.walk_directory(source, visitor, state, paths, dotfile);
+
+			if is_followed {
+				state.followed.pop();
+			}
+
+			result
 		} else {
 			let err = io::Error::new(io::ErrorKind::Unsupported, "Invalid file type");
 
@@ -536,32 +993,46 @@ impl<'a> Walker<'a> {
 		&self,
 		source: &PunktfSource,
 		visitor: &mut impl Visitor,
+		state: &mut WalkState,
 		paths: Paths,
 		dotfile: &Dotfile,
 	) -> Result {
-		let source_path = paths.child_source_path();
-
 		let directory = Directory(Item::new(source, paths.clone(), dotfile));
 
 		visitor.accept_directory(source, self.profile, &directory)?;
 
-		let read_dir = match std::fs::read_dir(source_path) {
-			Ok(path) => path,
-			Err(err) => {
-				return self.walk_errored(
-					source,
-					visitor,
-					paths,
-					dotfile,
-					Some(err),
-					Some("Failed to read directory"),
-				);
-			}
-		};
+		self.walk_children(source, visitor, state, paths, dotfile)
+	}
+
+	/// Walks all child items of a directory.
+	///
+	/// The contents of the directory are taken from `scan` if it was read
+	/// during the scan.
+	fn walk_children(
+		&self,
+		source: &PunktfSource,
+		visitor: &mut impl Visitor,
+		state: &mut WalkState,
+		paths: Paths,
+		dotfile: &Dotfile,
+	) -> Result {
+		// Overlay the contents of the directory from all source layers in which
+		// it exists
+		let relative = paths.child_relative_source_path();
+		let mut children: BTreeMap<OsString, Vec<(Priority, PathBuf)>> = BTreeMap::new();
+
+		for (priority, layer_source_path) in self.resolve_layer_source_paths(source, dotfile) {
+			let directory_path = layer_source_path.join(relative);
+
+			let names = match state.scan.read_dir(&directory_path) {
+				Some(names) => names,
+				None if !directory_path.is_dir() => continue,
+				None => std::fs::read_dir(&directory_path)
+					.and_then(|dents| dents.map(|dent| dent.map(|d| d.file_name())).collect()),
+			};
 
-		for dent in read_dir {
-			let dent = match dent {
-				Ok(dent) => dent,
+			let names = match names {
+				Ok(names) => names,
 				Err(err) => {
 					return self.walk_errored(
 						source,
@@ -574,12 +1045,47 @@ impl<'a> Walker<'a> {
 				}
 			};
 
-			self.walk_path(
-				source,
-				visitor,
-				paths.clone().with_child(dent.file_name()),
-				dotfile,
-			)?;
+			for name in names {
+				children
+					.entry(name)
+					.or_default()
+					.push((priority, layer_source_path.clone()));
+			}
+		}
+
+		for (name, layer_source_paths) in children {
+			let child_paths = paths.clone().with_child(&name);
+			let relative = child_paths.child_relative_source_path().to_path_buf();
+
+			let candidates: Vec<(Priority, PathBuf)> = layer_source_paths
+				.iter()
+				.map(|(priority, p)| (*priority, p.join(&relative)))
+				.collect();
+
+			match self.select_layer(source, &dotfile.path.join(&relative), &candidates) {
+				Ok(idx) => {
+					let idx = idx.expect("Child exists in at least one layer");
+
+					self.walk_path(
+						source,
+						visitor,
+						state,
+						child_paths.with_root_source(layer_source_paths[idx].1.clone()),
+						dotfile,
+					)?;
+				}
+				Err(context) => {
+					// Report the conflict for the item with the highest precedence
+					self.walk_errored(
+						source,
+						visitor,
+						child_paths.with_root_source(layer_source_paths[0].1.clone()),
+						dotfile,
+						None::<io::Error>,
+						Some(context),
+					)?;
+				}
+			}
 		}
 
 		Ok(())
@@ -603,6 +1109,45 @@ impl<'a> Walker<'a> {
 		visitor.accept_link(source, self.profile, &link)
 	}
 
+	/// Calls [`Visitor::accept_link`] for a symlink found in the source of a
+	/// dotfile (see [`SourceSymlinks::DeployAsLink`]).
+	///
+	/// The deployed link points to the same item as the symlink in the source.
+	fn walk_source_link(
+		&self,
+		source: &PunktfSource,
+		visitor: &mut impl Visitor,
+		paths: Paths,
+		dotfile: &Dotfile,
+	) -> Result {
+		let source_path = paths.child_source_path();
+		let destination = match std::fs::read_link(&source_path) {
+			Ok(destination) => destination,
+			Err(err) => {
+				return self.walk_errored(
+					source,
+					visitor,
+					paths,
+					dotfile,
+					Some(err),
+					Some("Failed to read symlink"),
+				);
+			}
+		};
+
+		// Relative links are relative to the directory which contains them
+		let link = Symlink {
+			source_path: match source_path.parent() {
+				Some(parent) => parent.join(destination),
+				None => destination,
+			},
+			target_path: paths.child_target_path().into_owned(),
+			replace: true,
+		};
+
+		visitor.accept_link(source, self.profile, &link)
+	}
+
 	/// Calls [`Visitor::accept_rejected`].
 	fn walk_rejected(
 		&self,
@@ -610,15 +1155,49 @@ impl<'a> Walker<'a> {
 		visitor: &mut impl Visitor,
 		paths: Paths,
 		dotfile: &Dotfile,
+		reason: impl Into<Cow<'static, str>>,
 	) -> Result {
 		let rejected = Rejected {
 			item: Item::new(source, paths, dotfile),
-			reason: Cow::Borrowed("Rejected by filter"),
+			reason: reason.into(),
 		};
 
 		visitor.accept_rejected(source, self.profile, &rejected)
 	}
 
+	/// Handles an item which exceeds a limit of its dotfile.
+	///
+	/// Depending on [`Dotfile::on_limit`](`crate::profile::dotfile::Dotfile::on_limit`)
+	/// this either calls [`Walker::walk_rejected`] or [`Walker::walk_errored`].
+	fn walk_limited(
+		&self,
+		source: &PunktfSource,
+		visitor: &mut impl Visitor,
+		paths: Paths,
+		dotfile: &Dotfile,
+		reason: String,
+	) -> Result {
+		match dotfile.on_limit() {
+			LimitAction::Skip => {
+				tracing::warn!(
+					"[{}]: Skipped - {}",
+					paths.child_source_path().display(),
+					reason
+				);
+
+				self.walk_rejected(source, visitor, paths, dotfile, reason)
+			}
+			LimitAction::Error => self.walk_errored(
+				source,
+				visitor,
+				paths,
+				dotfile,
+				None::<io::Error>,
+				Some(reason),
+			),
+		}
+	}
+
 	/// Calls [`Visitor::accept_errored`].
 	fn walk_errored(
 		&self,
@@ -650,7 +1229,7 @@ impl<'a> Walker<'a> {
 
 		shellexpand::full(path_str)
 			.map(|resolved| PathBuf::from(resolved.as_ref()))
-			.map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+			.map_err(io::Error::other)
 	}
 
 	/// Resolves the dotfile to a absolute source path.
@@ -658,6 +1237,99 @@ impl<'a> Walker<'a> {
 		self.resolve_path(&source.dotfiles.join(&dotfile.path))
 	}
 
+	/// Resolves the dotfile to a absolute source path for each
+	/// [layer](`crate::profile::source::PunktfSource::layers`) of the source
+	/// in which it exists.
+	///
+	/// The paths are ordered by the precedence of the layers and returned
+	/// together with the priority of their layer.
+	fn resolve_layer_source_paths(
+		&self,
+		source: &PunktfSource,
+		dotfile: &Dotfile,
+	) -> Vec<(Priority, PathBuf)> {
+		source
+			.layers()
+			.filter_map(|layer| {
+				self.resolve_source_path(layer, dotfile)
+					.ok()
+					.map(|path| (layer.priority, path))
+			})
+			.filter(|(_, path)| path.exists())
+			.collect()
+	}
+
+	/// Selects which of the source paths of an item existing in multiple
+	/// [layers](`crate::profile::source::PunktfSource::layers`) is used.
+	///
+	/// The candidates must be ordered by the precedence of the layers. If the
+	/// item is a directory in all layers, the contents are overlaid and no
+	/// selection is necessary. Otherwise the
+	/// [`ConflictPolicy`](`crate::profile::source::ConflictPolicy`) of the
+	/// source decides which layer wins. Each shadowed item is reported.
+	///
+	/// Returns the index of the selected candidate or `None` if there are no
+	/// candidates.
+	///
+	/// # Errors
+	///
+	/// If the policy is [`ConflictPolicy::Error`](`crate::profile::source::ConflictPolicy::Error`)
+	/// and a conflict is found, a description of the conflict is returned.
+	fn select_layer(
+		&self,
+		source: &PunktfSource,
+		relative: &Path,
+		candidates: &[(Priority, PathBuf)],
+	) -> std::result::Result<Option<usize>, String> {
+		if candidates.len() < 2 || candidates.iter().all(|(_, path)| path.is_dir()) {
+			return Ok((!candidates.is_empty()).then_some(0));
+		}
+
+		let policy = source.conflict_policy;
+
+		let selected = match policy {
+			ConflictPolicy::Error => {
+				let paths = candidates
+					.iter()
+					.map(|(_, path)| path.display().to_string())
+					.collect::<Vec<_>>()
+					.join(", ");
+
+				return Err(format!(
+					"Dotfile {} exists in multiple sources ({paths})",
+					relative.display()
+				));
+			}
+			ConflictPolicy::FirstWins => 0,
+			ConflictPolicy::LastWins => candidates.len() - 1,
+			ConflictPolicy::Priority => candidates
+				.iter()
+				.enumerate()
+				// Prefer the layer with the higher precedence on equal priority
+				.max_by_key(|(idx, (priority, _))| (*priority, Reverse(*idx)))
+				.map(|(idx, _)| idx)
+				.expect("At least two candidates"),
+		};
+
+		let selected_path = &candidates[selected].1;
+
+		for (_, path) in candidates
+			.iter()
+			.enumerate()
+			.filter(|(idx, _)| *idx != selected)
+			.map(|(_, candidate)| candidate)
+		{
+			tracing::warn!(
+				"[{}] {} is shadowed by {} (conflict policy: {policy})",
+				relative.display(),
+				path.display(),
+				selected_path.display()
+			);
+		}
+
+		Ok(Some(selected))
+	}
+
 	/// Resolves the dotfile to a absolute target path.
 	///
 	/// Some special logic is applied for directories.
@@ -678,10 +1350,19 @@ impl<'a> Walker<'a> {
 		self.resolve_path(&path)
 	}
 
-	/// TODO
-	const fn accept(&self, _path: &Path) -> bool {
-		// TODO: Apply filter
-		true
+	/// Checks if the item at the absolute source `path` is accepted by the
+	/// filter.
+	fn accept(&self, source: &PunktfSource, path: &Path) -> FilterMatch {
+		if self.filter.is_empty() {
+			return FilterMatch::Accept;
+		}
+
+		let relative = source
+			.layers()
+			.find_map(|layer| path.strip_prefix(&layer.dotfiles).ok())
+			.unwrap_or(path);
+
+		self.filter.check(relative, path.is_dir())
 	}
 }
 
@@ -691,7 +1372,9 @@ pub trait TemplateVisitor: Visitor {
 	/// Accepts a template [`File`] item for further processing.
 	///
 	/// This also provides a function to resolve the contents of the template
-	/// by calling it with the original template contents.
+	/// by calling it with the original template contents. It adds all
+	/// [inputs](`Input`) which are read from outside of the template to the
+	/// given set.
 	fn accept_template<'a>(
 		&mut self,
 		source: &PunktfSource,
@@ -699,7 +1382,7 @@ pub trait TemplateVisitor: Visitor {
 		file: &File<'a>,
 		// Returns a function to resolve the content to make the resolving lazy
 		// for upstream visitors.
-		resolve_content: impl FnOnce(&str) -> color_eyre::Result<String>,
+		resolve_content: impl FnOnce(&str, &mut BTreeSet<Input>) -> color_eyre::Result<String>,
 	) -> Result;
 }
 
@@ -709,17 +1392,46 @@ pub trait TemplateVisitor: Visitor {
 /// All accepted files are checked up on receiving and the either directly send
 /// out with [`Visitor::accept_file`] if they are a normal file or with
 /// [`TemplateVisitor::accept_template`] if it is a template.
+///
+/// Templates are parsed with a [cache](`TemplateCache`) if one is set with
+/// [`ResolvingVisitor::with_template_cache`].
 #[derive(Debug)]
-pub struct ResolvingVisitor<V>(V);
+pub struct ResolvingVisitor<V> {
+	/// The base visitor.
+	visitor: V,
+
+	/// Cache of parsed templates.
+	templates: Option<TemplateCache>,
+}
 
 impl<V> ResolvingVisitor<V>
 where
 	V: TemplateVisitor,
 {
+	/// Creates a new instance which passes all files to `visitor`.
+	pub(crate) const fn new(visitor: V) -> Self {
+		Self {
+			visitor,
+			templates: None,
+		}
+	}
+
+	/// Parses templates with `cache`.
+	pub(crate) fn with_template_cache(mut self, cache: Option<TemplateCache>) -> Self {
+		self.templates = cache;
+		self
+	}
+
 	/// Gets the base [`Visitor`].
 	#[allow(clippy::missing_const_for_fn)]
 	pub fn into_inner(self) -> V {
-		self.0
+		self.visitor
+	}
+
+	/// Gets the base [`Visitor`] together with the cache of parsed templates.
+	#[allow(clippy::missing_const_for_fn)]
+	pub fn into_parts(self) -> (V, Option<TemplateCache>) {
+		(self.visitor, self.templates)
 	}
 }
 
@@ -731,19 +1443,22 @@ impl<V: TemplateVisitor> Visitor for ResolvingVisitor<V> {
 		file: &File<'a>,
 	) -> Result {
 		if file.dotfile().is_template() {
-			let resolve_fn = |content: &str| {
-				let source = Source::file(&file.source_path, content);
-				let template = Template::parse(source)
-					.with_context(|| format!("File: {}", file.source_path.display()))?;
+			let resolve_fn = |content: &str, inputs: &mut BTreeSet<Input>| {
+				let file_source = Source::file(&file.source_path, content);
+				let template = match &mut self.templates {
+					Some(cache) => cache.parse(file_source),
+					None => Template::parse(file_source),
+				}
+				.with_context(|| format!("File: {}", file.source_path.display()))?;
 
 				template
-					.resolve(Some(profile.variables()), file.dotfile().variables.as_ref())
+					.resolve_recorded(profile, file.dotfile().variables.as_ref(), source, inputs)
 					.with_context(|| format!("File: {}", file.source_path.display()))
 			};
 
-			self.0.accept_template(source, profile, file, resolve_fn)
+			self.visitor.accept_template(source, profile, file, resolve_fn)
 		} else {
-			self.0.accept_file(source, profile, file)
+			self.visitor.accept_file(source, profile, file)
 		}
 	}
 
@@ -753,7 +1468,7 @@ impl<V: TemplateVisitor> Visitor for ResolvingVisitor<V> {
 		profile: &LayeredProfile,
 		directory: &Directory<'a>,
 	) -> Result {
-		self.0.accept_directory(source, profile, directory)
+		self.visitor.accept_directory(source, profile, directory)
 	}
 
 	fn accept_link(
@@ -762,7 +1477,7 @@ impl<V: TemplateVisitor> Visitor for ResolvingVisitor<V> {
 		profile: &LayeredProfile,
 		symlink: &Symlink,
 	) -> Result {
-		self.0.accept_link(source, profile, symlink)
+		self.visitor.accept_link(source, profile, symlink)
 	}
 
 	fn accept_rejected<'a>(
@@ -771,7 +1486,7 @@ impl<V: TemplateVisitor> Visitor for ResolvingVisitor<V> {
 		profile: &LayeredProfile,
 		rejected: &Rejected<'a>,
 	) -> Result {
-		self.0.accept_rejected(source, profile, rejected)
+		self.visitor.accept_rejected(source, profile, rejected)
 	}
 
 	fn accept_errored<'a>(
@@ -780,6 +1495,6 @@ impl<V: TemplateVisitor> Visitor for ResolvingVisitor<V> {
 		profile: &LayeredProfile,
 		errored: &Errored<'a>,
 	) -> Result {
-		self.0.accept_errored(source, profile, errored)
+		self.visitor.accept_errored(source, profile, errored)
 	}
 }
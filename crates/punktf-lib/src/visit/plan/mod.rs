@@ -0,0 +1,506 @@
+//! A [`Visitor`](`crate::visit::Visitor`) implementation which creates a
+//! [`Plan`] of all actions a deployment would take, without modifying
+//! anything.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::profile::source::PunktfSource;
+use crate::profile::{LayeredProfile, MergeMode, Priority};
+use crate::visit::deploy::filesystem::{Filesystem as _, RealFilesystem};
+use crate::visit::filter::PathFilter;
+use crate::visit::*;
+
+/// The action a deployment would take for an item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+	/// The item does not exist at the target and would be created.
+	Create,
+
+	/// The item exists at the target with different contents and would be
+	/// overwritten.
+	Overwrite,
+
+	/// The item exists at the target with the same contents and would be left
+	/// untouched.
+	SkipUnchanged,
+
+	/// The item exists at the target with different contents but would not be
+	/// overwritten automatically (e.g. because of the merge mode).
+	Conflict,
+
+	/// A symlink would be created.
+	Link,
+
+	/// The item was rejected (e.g. by a dotfile with a higher priority) and
+	/// would be skipped.
+	Skip,
+
+	/// The item could not be processed.
+	Error,
+}
+
+impl Action {
+	/// Returns the name of the action.
+	pub const fn as_str(&self) -> &'static str {
+		match self {
+			Self::Create => "create",
+			Self::Overwrite => "overwrite",
+			Self::SkipUnchanged => "skip-unchanged",
+			Self::Conflict => "conflict",
+			Self::Link => "link",
+			Self::Skip => "skip",
+			Self::Error => "error",
+		}
+	}
+
+	/// Indicates if the action would modify the target.
+	pub const fn is_change(&self) -> bool {
+		matches!(self, Self::Create | Self::Overwrite | Self::Link)
+	}
+
+	/// Indicates if the target differs from what a deployment would produce.
+	pub const fn is_drift(&self) -> bool {
+		self.is_change() || matches!(self, Self::Conflict | Self::Error)
+	}
+}
+
+impl fmt::Display for Action {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(self.as_str())
+	}
+}
+
+/// The kind of a planned item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ItemKind {
+	/// A regular file.
+	File,
+
+	/// A directory.
+	Directory,
+
+	/// A symlink.
+	Link,
+}
+
+/// A single item of a [`Plan`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanItem {
+	/// The kind of the item.
+	pub kind: ItemKind,
+
+	/// Source path of the item.
+	///
+	/// For dotfiles this is relative to the `dotfiles` directory, for links it
+	/// is absolute.
+	pub source_path: PathBuf,
+
+	/// Absolute target path of the item.
+	pub target_path: PathBuf,
+
+	/// The action which would be taken.
+	pub action: Action,
+
+	/// Additional information why the action would be taken.
+	#[serde(skip_serializing_if = "Option::is_none", default)]
+	pub reason: Option<String>,
+
+	/// The resolved contents which would be written to the target.
+	///
+	/// Only set for files which would be written and if the plan was created
+	/// with [`Planner::with_contents`].
+	#[serde(skip)]
+	pub content: Option<Vec<u8>>,
+}
+
+/// All actions a deployment of a profile would take.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Plan {
+	/// Commands of the pre-hooks which would be executed.
+	pub pre_hooks: Vec<String>,
+
+	/// All items in the order they would be processed.
+	pub items: Vec<PlanItem>,
+
+	/// Commands of the post-hooks which would be executed.
+	pub post_hooks: Vec<String>,
+}
+
+impl Plan {
+	/// Returns the amount of items which would modify the target.
+	pub fn changes(&self) -> usize {
+		self.items.iter().filter(|i| i.action.is_change()).count()
+	}
+
+	/// Returns all items for which the target differs from what a deployment
+	/// would produce.
+	pub fn drift(&self) -> impl Iterator<Item = &PlanItem> {
+		self.items.iter().filter(|i| i.action.is_drift())
+	}
+
+	/// Indicates if any item could not be processed.
+	pub fn has_errors(&self) -> bool {
+		self.items.iter().any(|i| i.action == Action::Error)
+	}
+
+	/// Indicates if any item has a conflict.
+	pub fn has_conflicts(&self) -> bool {
+		self.items.iter().any(|i| i.action == Action::Conflict)
+	}
+}
+
+/// Creates a [`Plan`] for a [profile](`crate::profile::Profile`).
+///
+/// This mirrors the decisions of the
+/// [`Deployer`](`crate::visit::deploy::Deployer`) (merge modes, priorities,
+/// templates and transformers) but never writes anything or executes hooks.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Planner {
+	/// The plan which is built.
+	plan: Plan,
+
+	/// Priorities of all already planned target paths.
+	priorities: HashMap<PathBuf, Option<Priority>>,
+
+	/// Only items accepted by this filter are planned.
+	filter: PathFilter,
+
+	/// Indicates if the resolved contents of files are kept in the plan.
+	keep_contents: bool,
+
+	/// Indicates if all items are planned as if their targets did not exist.
+	ignore_targets: bool,
+
+	/// Indicates if write protected targets are planned to be overwritten.
+	force: bool,
+}
+
+impl Planner {
+	/// Creates a new instance.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Restricts the plan to the items accepted by `filter`.
+	pub fn with_filter(mut self, filter: PathFilter) -> Self {
+		self.filter = filter;
+		self
+	}
+
+	/// Keeps the resolved contents of all files which would be written in
+	/// [`PlanItem::content`].
+	pub const fn with_contents(mut self, keep_contents: bool) -> Self {
+		self.keep_contents = keep_contents;
+		self
+	}
+
+	/// Plans all items as if none of their targets existed.
+	///
+	/// Together with [`Planner::with_contents`] this yields the resolved
+	/// contents of all files, regardless of the current state of the target.
+	pub const fn ignore_targets(mut self, ignore_targets: bool) -> Self {
+		self.ignore_targets = ignore_targets;
+		self
+	}
+
+	/// Plans write protected (read-only or immutable) targets to be
+	/// overwritten like [`DeployOptions::force`](`crate::visit::deploy::DeployOptions::force`).
+	///
+	/// Otherwise they are planned as conflicts, unless
+	/// [`Dotfile::force`](`crate::profile::dotfile::Dotfile::force`) is set.
+	pub const fn with_force(mut self, force: bool) -> Self {
+		self.force = force;
+		self
+	}
+
+	/// Runs the planner to completion for a given profile.
+	pub fn plan(self, source: &PunktfSource, profile: &mut LayeredProfile) -> Plan {
+		let filter = self.filter.clone();
+		let mut resolver = ResolvingVisitor::new(self);
+		let walker = Walker::new(profile).with_filter(filter);
+
+		if let Err(err) = walker.walk(source, &mut resolver) {
+			tracing::error!("Failed to create plan: {err}");
+		}
+
+		let mut plan = resolver.into_inner().plan;
+
+		plan.pre_hooks = profile
+			.pre_hooks()
+			.map(|hook| hook.command().to_string())
+			.collect();
+		plan.post_hooks = profile
+			.post_hooks()
+			.map(|hook| hook.command().to_string())
+			.collect();
+
+		plan
+	}
+
+	/// Adds a dotfile item to the plan.
+	fn push_item(
+		&mut self,
+		item: &Item<'_>,
+		kind: ItemKind,
+		action: Action,
+		reason: Option<String>,
+	) {
+		self.plan.items.push(PlanItem {
+			kind,
+			source_path: item.relative_source_path.clone(),
+			target_path: item.target_path.clone(),
+			action,
+			reason,
+			content: None,
+		});
+	}
+
+	/// Adds a file item which would be written with `content` to the plan.
+	fn push_file(&mut self, file: &File<'_>, action: Action, content: Vec<u8>) {
+		self.push_item(file, ItemKind::File, action, None);
+
+		if self.keep_contents {
+			if let Some(item) = self.plan.items.last_mut() {
+				item.content = Some(content);
+			}
+		}
+	}
+
+	/// Plans a file with the given new contents.
+	fn plan_file(&mut self, file: &File<'_>, content: color_eyre::Result<Vec<u8>>) {
+		let new = match content {
+			Ok(new) => new,
+			Err(err) => {
+				self.push_item(file, ItemKind::File, Action::Error, Some(err.to_string()));
+				return;
+			}
+		};
+
+		let priority = file.dotfile().priority;
+
+		// Same rule as the deployer: Dotfiles with a lower priority can not
+		// overwrite ones with a higher priority.
+		if let (Some(priority), Some(Some(other))) =
+			(priority, self.priorities.get(&file.target_path))
+		{
+			if *other > priority {
+				self.push_item(
+					file,
+					ItemKind::File,
+					Action::Skip,
+					Some(String::from(
+						"Dotfile with higher priority targets the same path",
+					)),
+				);
+				return;
+			}
+		}
+
+		self.priorities.insert(file.target_path.clone(), priority);
+
+		if self.ignore_targets || !file.target_path.exists() {
+			self.push_file(file, Action::Create, new);
+			return;
+		}
+
+		let old = match std::fs::read(&file.target_path) {
+			Ok(old) => old,
+			Err(err) => {
+				self.push_item(
+					file,
+					ItemKind::File,
+					Action::Error,
+					Some(format!("Failed to read target file: {err}")),
+				);
+				return;
+			}
+		};
+
+		if old == new {
+			self.push_item(file, ItemKind::File, Action::SkipUnchanged, None);
+			return;
+		}
+
+		let (action, reason) = match file.dotfile().merge.unwrap_or_default() {
+			MergeMode::Overwrite => match RealFilesystem.protection(&file.target_path) {
+				Ok(None) => {
+					self.push_file(file, Action::Overwrite, new);
+					return;
+				}
+				Ok(Some(protection)) if self.force || file.dotfile().is_forced() => {
+					self.push_file(file, Action::Overwrite, new);
+
+					if let Some(item) = self.plan.items.last_mut() {
+						item.reason = Some(format!("Removes the {protection} protection"));
+					}
+
+					return;
+				}
+				Ok(Some(protection)) => (
+					Action::Conflict,
+					Some(format!(
+						"Existing file is {protection} (use `--force` to overwrite it)"
+					)),
+				),
+				Err(err) => (
+					Action::Error,
+					Some(format!("Failed to check write protection: {err}")),
+				),
+			},
+			MergeMode::Keep => (
+				Action::Conflict,
+				Some(String::from("Existing file is kept (merge mode `Keep`)")),
+			),
+			MergeMode::Ask => (
+				Action::Conflict,
+				Some(String::from("Requires confirmation (merge mode `Ask`)")),
+			),
+		};
+
+		self.push_item(file, ItemKind::File, action, reason);
+	}
+}
+
+/// Reads the contents of a file and applies all relevant transformers.
+///
+/// Binary files are returned as is, encrypted files are decrypted first.
+fn file_content(profile: &LayeredProfile, file: &File<'_>) -> color_eyre::Result<Vec<u8>> {
+	if file.dotfile().is_encrypted() {
+		let content = read_profile_source(profile, file)?;
+		return Ok(transform_content(profile, file, content)?.into_bytes());
+	}
+
+	let content = std::fs::read(&file.source_path)?;
+
+	match String::from_utf8(content) {
+		Ok(content) => Ok(transform_content(profile, file, content)?.into_bytes()),
+		Err(err) => Ok(err.into_bytes()),
+	}
+}
+
+impl Visitor for Planner {
+	/// Accepts a file item and plans it depending on the deployed file.
+	fn accept_file<'a>(
+		&mut self,
+		_: &PunktfSource,
+		profile: &LayeredProfile,
+		file: &File<'a>,
+	) -> Result {
+		self.plan_file(file, file_content(profile, file));
+
+		Ok(())
+	}
+
+	/// Accepts a directory item and plans its creation if it does not exist.
+	fn accept_directory<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		directory: &Directory<'a>,
+	) -> Result {
+		let action = if !self.ignore_targets && directory.target_path.is_dir() {
+			Action::SkipUnchanged
+		} else {
+			Action::Create
+		};
+
+		self.push_item(directory, ItemKind::Directory, action, None);
+
+		Ok(())
+	}
+
+	/// Accepts a link item and plans its creation.
+	fn accept_link(&mut self, _: &PunktfSource, _: &LayeredProfile, link: &Symlink) -> Result {
+		let existing = !self.ignore_targets && link.target_path.symlink_metadata().is_ok();
+
+		let (action, reason) = if !existing {
+			(Action::Link, None)
+		} else if std::fs::read_link(&link.target_path).is_ok_and(|p| p == link.source_path) {
+			(Action::SkipUnchanged, None)
+		} else if link.replace {
+			(Action::Link, Some("Replaces the existing item"))
+		} else {
+			(Action::Conflict, Some("Target already exists"))
+		};
+
+		self.plan.items.push(PlanItem {
+			kind: ItemKind::Link,
+			source_path: link.source_path.clone(),
+			target_path: link.target_path.clone(),
+			action,
+			reason: reason.map(String::from),
+			content: None,
+		});
+
+		Ok(())
+	}
+
+	/// Accepts a rejected item and plans it as skipped.
+	fn accept_rejected<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		rejected: &Rejected<'a>,
+	) -> Result {
+		let kind = item_kind(&rejected.source_path);
+		self.push_item(
+			rejected,
+			kind,
+			Action::Skip,
+			Some(rejected.reason.to_string()),
+		);
+
+		Ok(())
+	}
+
+	/// Accepts a errored item and plans it as error.
+	fn accept_errored<'a>(
+		&mut self,
+		_: &PunktfSource,
+		_: &LayeredProfile,
+		errored: &Errored<'a>,
+	) -> Result {
+		let kind = item_kind(&errored.source_path);
+		self.push_item(errored, kind, Action::Error, Some(errored.to_string()));
+
+		Ok(())
+	}
+}
+
+impl TemplateVisitor for Planner {
+	/// Accepts a template item, resolves it and plans it depending on the
+	/// deployed file.
+	fn accept_template<'a>(
+		&mut self,
+		_: &PunktfSource,
+		profile: &LayeredProfile,
+		file: &File<'a>,
+		// Returns a function to resolve the content to make the resolving lazy
+		// for upstream visitors.
+		resolve_content: impl FnOnce(&str, &mut BTreeSet<Input>) -> color_eyre::Result<String>,
+	) -> Result {
+		let content = read_profile_source(profile, file)
+			.and_then(|content| resolve_content(&content, &mut BTreeSet::new()))
+			.and_then(|content| transform_content(profile, file, content))
+			.map(String::into_bytes);
+
+		self.plan_file(file, content);
+
+		Ok(())
+	}
+}
+
+/// Determines the kind of an item from its source path.
+fn item_kind(path: &Path) -> ItemKind {
+	if path.is_dir() {
+		ItemKind::Directory
+	} else {
+		ItemKind::File
+	}
+}
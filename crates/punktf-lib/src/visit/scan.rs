@@ -0,0 +1,245 @@
+//! Parallel scanning of the directories visited by a [`Walker`](`super::Walker`).
+//!
+//! Reading directories and the types of their entries makes up most of the
+//! time spent walking large dotfile directories. Before visiting any item, the
+//! walker reads all directories below its dotfiles with multiple threads into
+//! a [`Scan`] and afterwards only looks up the results. Directories which
+//! should not be descended into (e.g. because they are rejected by the
+//! [filter](`super::filter::PathFilter`)) are skipped during the scan, so
+//! large ignored trees like `.git` or `node_modules` are never read.
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs::{self, FileType};
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+use std::thread;
+
+/// Listing of a directory or the error which occurred while reading it.
+///
+/// The error is stored by its parts, as [`io::Error`] can not be cloned.
+type Listing = Result<Vec<OsString>, (io::ErrorKind, String)>;
+
+/// Directories which still need to be read by the workers of a scan.
+#[derive(Debug, Default)]
+struct Queue {
+	/// Directories which are not yet read.
+	pending: Vec<PathBuf>,
+
+	/// Number of directories which are currently read by a worker.
+	active: usize,
+}
+
+/// Directories read by a single worker.
+#[derive(Debug, Default)]
+struct Partial {
+	/// Names of the entries of each read directory.
+	listings: HashMap<PathBuf, Listing>,
+
+	/// Types of all entries found, which do not follow symlinks.
+	file_types: HashMap<PathBuf, FileType>,
+}
+
+/// The contents of all directories below some root directories, read in
+/// parallel.
+#[derive(Debug, Default)]
+pub struct Scan {
+	/// Names of the entries of each read directory.
+	listings: HashMap<PathBuf, Listing>,
+
+	/// Types of all entries found, which do not follow symlinks.
+	file_types: HashMap<PathBuf, FileType>,
+}
+
+impl Scan {
+	/// Reads the directories `roots` and all directories below them with one
+	/// thread per available CPU.
+	///
+	/// A directory is only read if `descend` returns `true` for its path.
+	pub fn new<F>(roots: Vec<PathBuf>, descend: F) -> Self
+	where
+		F: Fn(&Path) -> bool + Sync,
+	{
+		let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+
+		Self::with_threads(roots, threads, descend)
+	}
+
+	/// Same as [`Scan::new`] but uses the given amount of `threads`.
+	pub fn with_threads<F>(roots: Vec<PathBuf>, threads: usize, descend: F) -> Self
+	where
+		F: Fn(&Path) -> bool + Sync,
+	{
+		let queue = Mutex::new(Queue {
+			pending: roots.into_iter().filter(|root| descend(root)).collect(),
+			active: 0,
+		});
+		let changed = Condvar::new();
+
+		let partials: Vec<Partial> = thread::scope(|scope| {
+			let workers: Vec<_> = (0..threads.max(1))
+				.map(|_| scope.spawn(|| scan_worker(&queue, &changed, &descend)))
+				.collect();
+
+			workers
+				.into_iter()
+				.map(|worker| worker.join().expect("Scan worker panicked"))
+				.collect()
+		});
+
+		let mut scan = Self::default();
+
+		for partial in partials {
+			scan.listings.extend(partial.listings);
+			scan.file_types.extend(partial.file_types);
+		}
+
+		scan
+	}
+
+	/// Returns the names of all entries of the directory at `path`.
+	///
+	/// Returns `None` if the directory was not read during the scan.
+	pub fn read_dir(&self, path: &Path) -> Option<io::Result<Vec<OsString>>> {
+		self.listings.get(path).map(|listing| match listing {
+			Ok(names) => Ok(names.clone()),
+			Err((kind, message)) => Err(io::Error::new(*kind, message.clone())),
+		})
+	}
+
+	/// Returns the type of the item at `path` without following symlinks.
+	///
+	/// Returns `None` if the item was not found during the scan.
+	pub fn file_type(&self, path: &Path) -> Option<FileType> {
+		self.file_types.get(path).copied()
+	}
+}
+
+/// Reads directories from `queue` until all directories are read.
+///
+/// Sub-directories for which `descend` returns `true` are added to the queue.
+fn scan_worker<F>(queue: &Mutex<Queue>, changed: &Condvar, descend: &F) -> Partial
+where
+	F: Fn(&Path) -> bool + Sync,
+{
+	let mut partial = Partial::default();
+
+	loop {
+		let directory = {
+			let mut queue = queue.lock().expect("Scan queue poisoned");
+
+			loop {
+				if let Some(directory) = queue.pending.pop() {
+					queue.active += 1;
+					break directory;
+				}
+
+				// Nothing left to read and no worker can add new directories
+				if queue.active == 0 {
+					return partial;
+				}
+
+				queue = changed.wait(queue).expect("Scan queue poisoned");
+			}
+		};
+
+		let mut children = Vec::new();
+		let listing = read_dir(&directory, |path, file_type| {
+			partial.file_types.insert(path.clone(), file_type);
+
+			if file_type.is_dir() && descend(&path) {
+				children.push(path);
+			}
+		});
+
+		partial.listings.insert(
+			directory,
+			listing.map_err(|err| (err.kind(), err.to_string())),
+		);
+
+		let mut queue = queue.lock().expect("Scan queue poisoned");
+		queue.pending.extend(children);
+		queue.active -= 1;
+		changed.notify_all();
+	}
+}
+
+/// Reads the names of all entries of the directory at `path`.
+///
+/// `found` is called with the path and type of each entry whose type could be
+/// determined.
+fn read_dir(path: &Path, mut found: impl FnMut(PathBuf, FileType)) -> io::Result<Vec<OsString>> {
+	let mut names = Vec::new();
+
+	for dent in fs::read_dir(path)? {
+		let dent = dent?;
+
+		if let Ok(file_type) = dent.file_type() {
+			found(dent.path(), file_type);
+		}
+
+		names.push(dent.file_name());
+	}
+
+	Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn scan_directories() -> io::Result<()> {
+		crate::tests::setup_test_env();
+
+		let dir = tempfile::tempdir()?;
+		let root = dir.path().join("root");
+
+		for path in ["a/b/c", "a/.git/objects", "d", "node_modules/pkg"] {
+			fs::create_dir_all(root.join(path))?;
+		}
+
+		for path in [
+			"file",
+			"a/b/c/file",
+			"a/.git/HEAD",
+			"node_modules/pkg/index.js",
+		] {
+			fs::write(root.join(path), "content")?;
+		}
+
+		let descend = |path: &Path| {
+			!matches!(
+				path.file_name().and_then(|name| name.to_str()),
+				Some(".git" | "node_modules")
+			)
+		};
+
+		for threads in [1, 4] {
+			let scan = Scan::with_threads(vec![root.clone()], threads, descend);
+
+			let mut names = scan.read_dir(&root).expect("Root was scanned")?;
+			names.sort();
+			assert_eq!(names, ["a", "d", "file", "node_modules"]);
+
+			let mut names = scan.read_dir(&root.join("a")).expect("a was scanned")?;
+			names.sort();
+			assert_eq!(names, [".git", "b"]);
+
+			assert!(scan.read_dir(&root.join("a/b/c")).is_some());
+			assert!(scan
+				.file_type(&root.join("a/b/c/file"))
+				.is_some_and(|file_type| file_type.is_file()));
+
+			// Directories which are not descended into are never read
+			assert!(scan.file_type(&root.join("a/.git")).is_some());
+			assert!(scan.read_dir(&root.join("a/.git")).is_none());
+			assert!(scan.read_dir(&root.join("node_modules")).is_none());
+			assert!(scan.file_type(&root.join("node_modules/pkg")).is_none());
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,1315 @@
+//! Tests for the deployment.
+#![cfg(all(feature = "io", feature = "profile-yaml"))]
+
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+use punktf_lib::error::{Error, TemplateError};
+use punktf_lib::profile::encryption::EncryptionBackend;
+use punktf_lib::profile::hook::HookError;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::{resolve_profile, LayeredProfile, MergeMode};
+use punktf_lib::visit::deploy::cancel::CancellationToken;
+use punktf_lib::visit::deploy::deployment::{DeploymentStatus, ItemChange, ItemStatus};
+use punktf_lib::visit::deploy::event::{DeployEvent, HookKind};
+use punktf_lib::visit::deploy::filesystem::{Filesystem as _, MemoryFilesystem};
+use punktf_lib::visit::deploy::{DeployOptions, Deployer};
+
+fn write(path: &Path, content: &str) -> Result<()> {
+	fs::create_dir_all(path.parent().expect("Path has a parent"))?;
+	fs::write(path, content)?;
+	Ok(())
+}
+
+#[test]
+fn deploy_metrics() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\nvariables:\n  NAME: \"abc\"\ndotfiles:\n  - path: copied\n  - path: template\n    template: true\n",
+			target.display().to_string()
+		),
+	)?;
+	write(&root.join("dotfiles").join("copied"), "copied")?;
+	write(&root.join("dotfiles").join("template"), "{{NAME}}")?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+
+	let metrics = |name: &str| {
+		*deployment
+			.dotfiles()
+			.get(&target.canonicalize().expect("Target exists").join(name))
+			.expect("Dotfile to be deployed")
+			.metrics()
+	};
+
+	assert_eq!(metrics("copied").bytes_written, 6);
+	assert_eq!(metrics("template").bytes_written, 3);
+
+	Ok(())
+}
+
+#[test]
+fn deploy_changes() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\ndotfiles:\n  - path: new\n  - path: changed\n  - path: same\n",
+			target.display().to_string()
+		),
+	)?;
+	write(&root.join("dotfiles").join("new"), "new")?;
+	write(&root.join("dotfiles").join("changed"), "new")?;
+	write(&target.join("changed"), "old")?;
+	write(&root.join("dotfiles").join("same"), "same")?;
+	write(&target.join("same"), "same")?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	let change = |name: &str| {
+		deployment
+			.dotfiles()
+			.get(&target.canonicalize().expect("Target exists").join(name))
+			.expect("Dotfile to be deployed")
+			.change()
+	};
+
+	assert_eq!(change("new"), Some(ItemChange::Created));
+	assert_eq!(change("changed"), Some(ItemChange::Updated));
+	assert_eq!(change("same"), Some(ItemChange::Unchanged));
+
+	Ok(())
+}
+
+#[test]
+fn deploy_events() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\npre_hooks:\n  - \"echo pre\"\ndotfiles:\n  - path: copied\n    template: false\n  - path: template\n    template: true\n  - path: kept\n    merge: Keep\n",
+			target.display().to_string()
+		),
+	)?;
+	write(&root.join("dotfiles").join("copied"), "copied")?;
+	write(&root.join("dotfiles").join("template"), "template")?;
+	write(&root.join("dotfiles").join("kept"), "new")?;
+	write(&target.join("kept"), "old")?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let mut events = Vec::new();
+	let deployment = Deployer::new(DeployOptions::default(), |_, _| Ok(true))
+		.with_observer(|event: &DeployEvent<'_>| {
+			events.push(match event {
+				DeployEvent::Started { .. } => String::from("started"),
+				DeployEvent::HookStarted { kind, .. } => format!("hook started {kind:?}"),
+				DeployEvent::HookFinished { kind, error, .. } => {
+					format!("hook finished {kind:?} {}", error.is_none())
+				}
+				DeployEvent::ItemStarted { source_path, .. } => {
+					format!("started {}", source_path.display())
+				}
+				DeployEvent::ItemCompiled { source_path, .. } => {
+					format!("compiled {}", source_path.display())
+				}
+				DeployEvent::ItemDeployed { source_path, .. } => {
+					format!("deployed {}", source_path.display())
+				}
+				DeployEvent::ItemSkipped { source_path, .. } => {
+					format!("skipped {}", source_path.display())
+				}
+				DeployEvent::ItemFailed { source_path, .. } => {
+					format!("failed {}", source_path.display())
+				}
+				DeployEvent::Finished { deployment } => {
+					format!("finished {}", deployment.status().is_success())
+				}
+			});
+		})
+		.deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+
+	let dotfile = |name: &str| source.dotfile_path(Path::new(name)).display().to_string();
+	assert_eq!(
+		events,
+		[
+			String::from("started"),
+			format!("hook started {:?}", HookKind::Pre),
+			format!("hook finished {:?} true", HookKind::Pre),
+			format!("started {}", dotfile("copied")),
+			format!("deployed {}", dotfile("copied")),
+			format!("started {}", dotfile("template")),
+			format!("compiled {}", dotfile("template")),
+			format!("deployed {}", dotfile("template")),
+			format!("started {}", dotfile("kept")),
+			format!("skipped {}", dotfile("kept")),
+			String::from("finished true"),
+		]
+	);
+
+	Ok(())
+}
+
+#[test]
+fn deploy_memory_filesystem() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\nvariables:\n  name: template\ndotfiles:\n  - path: copied\n    template: false\n  - path: template\n  - path: kept\n    merge: Keep\n  - path: nested\n",
+			target.display().to_string()
+		),
+	)?;
+	write(&root.join("dotfiles").join("copied"), "copied")?;
+	write(&root.join("dotfiles").join("template"), "{{name}}")?;
+	write(&root.join("dotfiles").join("kept"), "new")?;
+	write(&root.join("dotfiles").join("nested").join("file"), "nested")?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let filesystem = MemoryFilesystem::new();
+	filesystem.create_dir_all(&target)?;
+	filesystem.write(&target.join("kept"), b"old")?;
+
+	let deployment = Deployer::new(DeployOptions::default(), |_, _| Ok(true))
+		.with_filesystem(&filesystem)
+		.deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+	assert!(!target.exists());
+
+	assert_eq!(filesystem.read(&target.join("copied"))?, b"copied");
+	assert_eq!(filesystem.read(&target.join("template"))?, b"template");
+	assert_eq!(filesystem.read(&target.join("kept"))?, b"old");
+	assert_eq!(filesystem.read(&target.join("file"))?, b"nested");
+	assert_eq!(
+		deployment.dotfiles()[&target.join("copied")].change(),
+		Some(ItemChange::Created)
+	);
+
+	Ok(())
+}
+
+#[test]
+fn deploy_errors() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\ndotfiles:\n  - path: template\n",
+			target.display().to_string()
+		),
+	)?;
+	write(&root.join("dotfiles").join("template"), "a {{missing}}")?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	let status = deployment
+		.dotfiles()
+		.values()
+		.next()
+		.expect("Dotfile to be deployed")
+		.status();
+
+	let ItemStatus::Failed(Error::Template(TemplateError { path, span, .. })) = status else {
+		panic!("Expected a template error, got {status:?}");
+	};
+	assert_eq!(
+		path.as_deref(),
+		Some(&*source.dotfile_path(Path::new("template")))
+	);
+	assert_eq!(span.clone(), Some(4..11));
+
+	write(
+		&source.profiles().join("hook.yaml"),
+		"pre_hooks:\n  - \"exit 3\"\n",
+	)?;
+
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "hook")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(matches!(
+		deployment.status(),
+		DeploymentStatus::Failed(Error::Hook {
+			source: HookError::ExitStatusError { code: Some(3), .. },
+			..
+		})
+	));
+
+	Ok(())
+}
+
+#[test]
+fn deploy_cancelled() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+	let marker = dir.path().join("marker");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\npost_hooks:\n  - \"touch {}\"\ndotfiles:\n  - path: a\n  - path: b\n  - path: c\n",
+			target.display().to_string(),
+			marker.display()
+		),
+	)?;
+	for name in ["a", "b", "c"] {
+		write(&root.join("dotfiles").join(name), name)?;
+	}
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	// Cancel as soon as the first item was deployed
+	let token = CancellationToken::new();
+	let cancel = token.clone();
+	let deployment = Deployer::new(DeployOptions::default(), |_, _| Ok(true))
+		.with_cancellation(token)
+		.with_observer(move |event: &DeployEvent<'_>| {
+			if let DeployEvent::ItemDeployed { .. } = event {
+				cancel.cancel();
+			}
+		})
+		.deploy(&source, &mut profile);
+
+	assert_eq!(
+		deployment.status(),
+		&DeploymentStatus::Failed(Error::Cancelled)
+	);
+	assert!(target.join("a").exists());
+	assert!(!target.join("b").exists());
+	assert!(!target.join("c").exists());
+	assert!(marker.exists());
+
+	let cancelled = deployment
+		.dotfiles()
+		.values()
+		.filter(|dotfile| dotfile.status().is_cancelled())
+		.count();
+	assert_eq!(cancelled, 2);
+
+	Ok(())
+}
+
+#[test]
+fn deploy_options() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+	let backup = dir.path().join("backup");
+	let marker = dir.path().join("marker");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\npre_hooks:\n  - \"touch {}\"\ndotfiles:\n  - path: changed\n    merge: Keep\n  - path: same\n",
+			target.display().to_string(),
+			marker.display()
+		),
+	)?;
+	write(&root.join("dotfiles").join("changed"), "new")?;
+	write(&target.join("changed"), "old")?;
+	write(&root.join("dotfiles").join("same"), "same")?;
+	write(&target.join("same"), "same")?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let options = DeployOptions::new()
+		.with_merge_mode(MergeMode::Overwrite)
+		.with_backup_dir(&backup)
+		.with_skip_unchanged(true)
+		.with_skip_hooks(true);
+	let deployment = Deployer::new(options, |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+	assert!(!marker.exists());
+	assert_eq!(fs::read_to_string(target.join("changed"))?, "new");
+
+	let backup_root = backup.join(
+		target
+			.components()
+			.filter(|component| matches!(component, std::path::Component::Normal(_)))
+			.collect::<std::path::PathBuf>(),
+	);
+	assert_eq!(fs::read_to_string(backup_root.join("changed"))?, "old");
+
+	let same = deployment
+		.dotfiles()
+		.get(&target.canonicalize()?.join("same"))
+		.expect("Dotfile to be deployed");
+	assert_eq!(same.change(), Some(ItemChange::Unchanged));
+	assert_eq!(same.metrics().bytes_written, 0);
+
+	Ok(())
+}
+
+#[test]
+fn deploy_cache() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+	let cache = dir.path().join("cache");
+
+	let write_profile = |name: &str| {
+		write(
+			&root.join("profiles").join("test.yaml"),
+			&format!(
+				"target: {:?}\nvariables:\n  NAME: {name:?}\ndotfiles:\n  - path: template\n    template: true\n",
+				target.display().to_string()
+			),
+		)
+	};
+
+	write_profile("abc")?;
+	write(&root.join("dotfiles").join("template"), "{{NAME}}")?;
+
+	let source = PunktfSource::from_root(root.clone())?;
+
+	// Returns how often the template was compiled and the resulting change
+	let deploy = || -> Result<(usize, Option<ItemChange>)> {
+		let mut builder = LayeredProfile::build();
+		resolve_profile(&mut builder, &source, "test")?;
+		let mut profile = builder.finish();
+
+		let mut compiled = 0;
+		let deployment = Deployer::new(DeployOptions::new().with_cache(&cache), |_, _| Ok(true))
+			.with_observer(|event: &DeployEvent<'_>| {
+				if matches!(event, DeployEvent::ItemCompiled { .. }) {
+					compiled += 1;
+				}
+			})
+			.deploy(&source, &mut profile);
+
+		assert!(deployment.status().is_success());
+
+		let change = deployment
+			.dotfiles()
+			.get(&target.canonicalize()?.join("template"))
+			.and_then(|dotfile| dotfile.change());
+
+		Ok((compiled, change))
+	};
+
+	assert_eq!(deploy()?, (1, Some(ItemChange::Created)));
+	assert!(cache.exists());
+	assert_eq!(deploy()?, (0, Some(ItemChange::Unchanged)));
+
+	// A modified target is compiled again
+	fs::write(target.join("template"), "modified")?;
+	assert_eq!(deploy()?, (1, Some(ItemChange::Updated)));
+	assert_eq!(fs::read_to_string(target.join("template"))?, "abc");
+	assert_eq!(deploy()?, (0, Some(ItemChange::Unchanged)));
+
+	// Changed variables invalidate the cache
+	write_profile("def")?;
+	assert_eq!(deploy()?, (1, Some(ItemChange::Updated)));
+	assert_eq!(fs::read_to_string(target.join("template"))?, "def");
+
+	// Changed templates invalidate the cache
+	write(&root.join("dotfiles").join("template"), "{{NAME}}!")?;
+	assert_eq!(deploy()?, (1, Some(ItemChange::Updated)));
+	assert_eq!(fs::read_to_string(target.join("template"))?, "def!");
+
+	Ok(())
+}
+
+#[test]
+fn deploy_cache_reads() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+	let cache = dir.path().join("cache");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\ndotfiles:\n  - path: template\n    template: true\n  - path: volatile\n    template: true\n",
+			target.display().to_string()
+		),
+	)?;
+	write(
+		&root.join("dotfiles").join("template"),
+		"{{ upper({{$PUNKTF_TEST_CACHE_READS}}) }} {{ file_hash(\"data\") }}\n{{@render \"part\"}}",
+	)?;
+	write(&root.join("dotfiles").join("data"), "a")?;
+	write(&root.join("dotfiles").join("part"), "part")?;
+	write(&root.join("dotfiles").join("volatile"), "{{ uuid() }}")?;
+	std::env::set_var("PUNKTF_TEST_CACHE_READS", "abc");
+
+	let source = PunktfSource::from_root(root.clone())?;
+
+	// Returns which templates were compiled, how often the merge was asked
+	// and the content of the template
+	let deploy = || -> Result<(Vec<String>, usize, String)> {
+		let mut builder = LayeredProfile::build();
+		resolve_profile(&mut builder, &source, "test")?;
+		let mut profile = builder.finish();
+
+		let mut compiled = Vec::new();
+		let asked = std::cell::Cell::new(0);
+		let options = DeployOptions::new()
+			.with_cache(&cache)
+			.with_merge_mode(MergeMode::Ask);
+		let deployment = Deployer::new(options, |_, _| {
+			asked.set(asked.get() + 1);
+			Ok(true)
+		})
+		.with_observer(|event: &DeployEvent<'_>| {
+			if let DeployEvent::ItemCompiled { target_path, .. } = event {
+				compiled.push(
+					target_path
+						.file_name()
+						.unwrap()
+						.to_string_lossy()
+						.into_owned(),
+				);
+			}
+		})
+		.deploy(&source, &mut profile);
+
+		assert!(deployment.status().is_success());
+
+		Ok((
+			compiled,
+			asked.get(),
+			fs::read_to_string(target.join("template"))?,
+		))
+	};
+
+	let (compiled, _, content) = deploy()?;
+	assert_eq!(compiled, ["template", "volatile"]);
+	assert!(content.starts_with("ABC "));
+
+	// Templates with random values are always compiled, unchanged targets of
+	// cached templates are not even confirmed
+	assert_eq!(
+		deploy()?,
+		(vec![String::from("volatile")], 1, content.clone())
+	);
+
+	// Environment variables, hashed and rendered files invalidate the cache
+	std::env::set_var("PUNKTF_TEST_CACHE_READS", "def");
+	let (compiled, _, content) = deploy()?;
+	assert_eq!(compiled, ["template", "volatile"]);
+	assert!(content.starts_with("DEF "));
+
+	write(&root.join("dotfiles").join("data"), "b")?;
+	let (compiled, _, changed) = deploy()?;
+	assert_eq!(compiled, ["template", "volatile"]);
+	assert_ne!(changed, content);
+
+	write(&root.join("dotfiles").join("part"), "other")?;
+	let (compiled, _, content) = deploy()?;
+	assert_eq!(compiled, ["template", "volatile"]);
+	assert!(content.ends_with("other"));
+
+	Ok(())
+}
+
+#[test]
+fn deploy_fast_compare() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+	let cache = dir.path().join("cache");
+	let font = root.join("dotfiles").join("font");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\ndotfiles:\n  - path: font\n    template: false\n",
+			target.display().to_string()
+		),
+	)?;
+	write(&font, "aaaa")?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let mut deploy = |fast_compare: bool| -> Result<Option<ItemChange>> {
+		let options = DeployOptions::new()
+			.with_cache(&cache)
+			.with_fast_compare(fast_compare)
+			.with_skip_unchanged(true);
+		let deployment = Deployer::new(options, |_, _| Ok(true)).deploy(&source, &mut profile);
+
+		assert!(deployment.status().is_success());
+
+		Ok(deployment
+			.dotfiles()
+			.get(&target.canonicalize()?.join("font"))
+			.and_then(|dotfile| dotfile.change()))
+	};
+
+	assert_eq!(deploy(true)?, Some(ItemChange::Created));
+	assert_eq!(deploy(true)?, Some(ItemChange::Unchanged));
+
+	// Changes which keep the size and modification time are not detected
+	let modified = fs::metadata(&font)?.modified()?;
+	fs::write(&font, "bbbb")?;
+	fs::File::options()
+		.write(true)
+		.open(&font)?
+		.set_modified(modified)?;
+
+	assert_eq!(deploy(true)?, Some(ItemChange::Unchanged));
+	assert_eq!(fs::read_to_string(target.join("font"))?, "aaaa");
+
+	// Without the fast path the contents are compared
+	assert_eq!(deploy(false)?, Some(ItemChange::Updated));
+	assert_eq!(fs::read_to_string(target.join("font"))?, "bbbb");
+	assert_eq!(deploy(true)?, Some(ItemChange::Unchanged));
+
+	write(&font, "ccccc")?;
+	assert_eq!(deploy(true)?, Some(ItemChange::Updated));
+	assert_eq!(fs::read_to_string(target.join("font"))?, "ccccc");
+
+	Ok(())
+}
+
+#[test]
+fn deploy_limits() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	let write_profile = |on_limit: &str| {
+		write(
+			&root.join("profiles").join("test.yaml"),
+			&format!(
+				"target: {:?}\ndotfiles:\n  - path: config\n    template: false\n    max_depth: 2\n    max_file_size: 8\n    on_limit: {on_limit}\n",
+				target.display().to_string()
+			),
+		)
+	};
+
+	write_profile("Skip")?;
+	write(&root.join("dotfiles/config/small"), "small")?;
+	write(&root.join("dotfiles/config/large"), "too large")?;
+	write(&root.join("dotfiles/config/a/small"), "small")?;
+	write(&root.join("dotfiles/config/a/cache/deep"), "deep")?;
+
+	let source = PunktfSource::from_root(root.clone())?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+	assert!(target.join("small").exists());
+	assert!(target.join("a/small").exists());
+	assert!(!target.join("large").exists());
+	assert!(!target.join("a/cache/deep").exists());
+
+	let status = |name: &str| {
+		deployment
+			.dotfiles()
+			.get(&target.canonicalize().expect("Target exists").join(name))
+			.expect("Item to be visited")
+			.status()
+			.clone()
+	};
+
+	assert!(matches!(status("large"), ItemStatus::Skipped(_)));
+	assert!(matches!(status("a/cache/deep"), ItemStatus::Skipped(_)));
+
+	fs::remove_dir_all(&target)?;
+	write_profile("Error")?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_failed());
+	assert!(target.join("small").exists());
+	assert!(!target.join("large").exists());
+
+	Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn deploy_source_symlinks() -> Result<()> {
+	use std::os::unix::fs::symlink;
+
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(&root.join("dotfiles/config/file"), "file")?;
+	write(&root.join("dotfiles/shared/inner"), "inner")?;
+	symlink("../shared", root.join("dotfiles/config/shared"))?;
+
+	let deploy = |policy: &str| -> Result<bool> {
+		if target.exists() {
+			fs::remove_dir_all(&target)?;
+		}
+
+		write(
+			&root.join("profiles").join("test.yaml"),
+			&format!(
+				"target: {:?}\nsource_symlinks: {policy}\ndotfiles:\n  - path: config\n    template: false\n",
+				target.display().to_string()
+			),
+		)?;
+
+		let source = PunktfSource::from_root(root.clone())?;
+		let mut builder = LayeredProfile::build();
+		resolve_profile(&mut builder, &source, "test")?;
+		let mut profile = builder.finish();
+
+		let deployment =
+			Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+		Ok(deployment.status().is_success())
+	};
+
+	assert!(!deploy("error")?);
+	assert!(!target.join("shared").exists());
+
+	assert!(deploy("skip")?);
+	assert!(target.join("file").exists());
+	assert!(!target.join("shared").exists());
+
+	assert!(deploy("deploy-as-link")?);
+	assert!(target.join("shared").symlink_metadata()?.is_symlink());
+	assert_eq!(fs::read_to_string(target.join("shared/inner"))?, "inner");
+
+	assert!(deploy("follow")?);
+	assert!(target.join("shared").symlink_metadata()?.is_dir());
+	assert_eq!(fs::read_to_string(target.join("shared/inner"))?, "inner");
+
+	// A link to a directory which contains it would be followed forever
+	symlink("..", root.join("dotfiles/shared/parent"))?;
+
+	assert!(!deploy("follow")?);
+	assert_eq!(fs::read_to_string(target.join("shared/inner"))?, "inner");
+
+	Ok(())
+}
+
+#[test]
+fn deploy_preserve_mtime() -> Result<()> {
+	use std::time::{Duration, SystemTime};
+
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+	let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\ndotfiles:\n  - path: copied\n    template: false\n    preserve_mtime: true\n  - path: templated\n    preserve_mtime: true\n  - path: other\n    template: false\n",
+			target.display().to_string()
+		),
+	)?;
+
+	for name in ["copied", "templated", "other"] {
+		let path = root.join("dotfiles").join(name);
+		write(&path, name)?;
+		fs::File::options()
+			.write(true)
+			.open(path)?
+			.set_modified(old)?;
+	}
+
+	let profile_modified = root.join("profiles/test.yaml").metadata()?.modified()?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+
+	let modified =
+		|name: &str| -> Result<SystemTime> { Ok(target.join(name).metadata()?.modified()?) };
+
+	assert_eq!(modified("copied")?, old);
+	assert_eq!(modified("templated")?, profile_modified);
+	assert_ne!(modified("other")?, old);
+
+	Ok(())
+}
+
+#[test]
+fn deploy_compare_mtime() -> Result<()> {
+	use std::time::{Duration, SystemTime};
+
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+	let old = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\ndotfiles:\n  - path: same_mtime\n    template: false\n  - path: other_mtime\n    template: false\n",
+			target.display().to_string()
+		),
+	)?;
+
+	// Files with the same size and modification time are not read, so the
+	// different content of `same_mtime` is not noticed
+	for name in ["same_mtime", "other_mtime"] {
+		write(&root.join("dotfiles").join(name), "new")?;
+		write(&target.join(name), "old")?;
+		fs::File::options()
+			.write(true)
+			.open(root.join("dotfiles").join(name))?
+			.set_modified(old)?;
+	}
+	fs::File::options()
+		.write(true)
+		.open(target.join("same_mtime"))?
+		.set_modified(old)?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment = Deployer::new(DeployOptions::default().with_dry_run(true), |_, _| Ok(true))
+		.deploy(&source, &mut profile);
+
+	let change = |name: &str| {
+		deployment
+			.dotfiles()
+			.get(&target.canonicalize().expect("Target exists").join(name))
+			.expect("Dotfile to be deployed")
+			.change()
+	};
+
+	assert_eq!(change("same_mtime"), Some(ItemChange::Unchanged));
+	assert_eq!(change("other_mtime"), Some(ItemChange::Updated));
+
+	Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn deploy_dir_mode() -> Result<()> {
+	use std::os::unix::fs::PermissionsExt as _;
+
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\ndotfiles:\n  - path: ssh\n    rename: .ssh\n    template: false\n    dir_mode: 0700\n",
+			target.display().to_string()
+		),
+	)?;
+	write(&root.join("dotfiles/ssh/config"), "config")?;
+	write(&root.join("dotfiles/ssh/keys/id"), "key")?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+	assert_eq!(fs::read_to_string(target.join(".ssh/keys/id"))?, "key");
+
+	for path in [
+		target.clone(),
+		target.join(".ssh"),
+		target.join(".ssh/keys"),
+	] {
+		assert_eq!(path.metadata()?.permissions().mode() & 0o7777, 0o700);
+	}
+
+	Ok(())
+}
+
+#[test]
+fn deploy_create_parents() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\ncreate_parents: false\ndotfiles:\n  - path: file\n    rename: typo/file\n  - path: dir\n    rename: typo/dir\n  - path: allowed\n    rename: created/allowed\n    create_parents: true\n  - path: existing\n",
+			target.display().to_string()
+		),
+	)?;
+	write(&root.join("dotfiles/file"), "file")?;
+	write(&root.join("dotfiles/dir/child"), "child")?;
+	write(&root.join("dotfiles/allowed"), "allowed")?;
+	write(&root.join("dotfiles/existing/nested/child"), "child")?;
+	fs::create_dir_all(&target)?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_failed());
+	assert!(!target.join("typo").exists());
+	assert!(target.join("created/allowed").exists());
+	assert!(target.join("nested/child").exists());
+
+	let status = |name: &str| {
+		deployment
+			.dotfiles()
+			.get(&target.canonicalize().expect("Target exists").join(name))
+			.expect("Item to be visited")
+			.status()
+			.clone()
+	};
+
+	assert!(matches!(
+		status("typo/file"),
+		ItemStatus::Failed(Error::Io(_))
+	));
+	assert!(matches!(
+		status("typo/dir"),
+		ItemStatus::Failed(Error::Io(_))
+	));
+
+	Ok(())
+}
+
+#[test]
+fn deploy_protected_target() -> Result<()> {
+	use punktf_lib::visit::plan::{Action, Planner};
+
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\ndotfiles:\n  - path: file\n",
+			target.display().to_string()
+		),
+	)?;
+	write(&root.join("dotfiles/file"), "new")?;
+	write(&target.join("file"), "old")?;
+
+	let mut permissions = target.join("file").metadata()?.permissions();
+	permissions.set_readonly(true);
+	fs::set_permissions(target.join("file"), permissions)?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let plan = Planner::new().plan(&source, &mut profile);
+	assert_eq!(plan.items[0].action, Action::Conflict);
+
+	let plan = Planner::new().with_force(true).plan(&source, &mut profile);
+	assert_eq!(plan.items[0].action, Action::Overwrite);
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+	assert_eq!(fs::read_to_string(target.join("file"))?, "old");
+
+	let status = deployment
+		.dotfiles()
+		.get(&target.canonicalize()?.join("file"))
+		.expect("Item to be visited")
+		.status()
+		.clone();
+	assert!(matches!(status, ItemStatus::Skipped(_)));
+
+	let deployment = Deployer::new(DeployOptions::new().with_force(true), |_, _| Ok(true))
+		.deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+	assert_eq!(fs::read_to_string(target.join("file"))?, "new");
+	assert!(!target.join("file").metadata()?.permissions().readonly());
+
+	Ok(())
+}
+
+#[test]
+fn deploy_when() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\nvariables:\n  HOST: dev-box\ndotfiles:\n  - path: matching\n    when: 'os == \"{}\" && HOST matches \"^dev-\"'\n  - path: other\n    when: 'os != \"{}\" || HOST == \"prod\"'\n  - path: missing\n    when: 'UNDEFINED'\n",
+			target.display().to_string(),
+			std::env::consts::OS,
+			std::env::consts::OS,
+		),
+	)?;
+	write(&root.join("dotfiles/matching"), "matching")?;
+	write(&root.join("dotfiles/other"), "other")?;
+	fs::create_dir_all(&target)?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+	assert!(target.join("matching").exists());
+	assert!(!target.join("other").exists());
+	assert!(matches!(
+		deployment
+			.dotfiles()
+			.get(&target.canonicalize()?.join("other"))
+			.expect("Item to be visited")
+			.status(),
+		ItemStatus::Skipped(_)
+	));
+
+	Ok(())
+}
+
+#[test]
+fn deploy_instances() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\nvariables:\n  USER: demo\ndotfiles:\n  - path: unit.service\n    rename: 'systemd/{{{{NAME}}}}.service'\n    instances:\n      - NAME: backup\n        CMD: restic backup\n      - NAME: sync\n        CMD: rclone sync\n",
+			target.display().to_string(),
+		),
+	)?;
+	write(
+		&root.join("dotfiles/unit.service"),
+		"ExecStart={{CMD}}\nUser={{USER}}\n",
+	)?;
+	fs::create_dir_all(&target)?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+	assert_eq!(
+		fs::read_to_string(target.join("systemd/backup.service"))?,
+		"ExecStart=restic backup\nUser=demo\n"
+	);
+	assert_eq!(
+		fs::read_to_string(target.join("systemd/sync.service"))?,
+		"ExecStart=rclone sync\nUser=demo\n"
+	);
+
+	Ok(())
+}
+
+#[test]
+fn deploy_for_each() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\nvariables:\n  SSH_HOSTS: github.com, gitlab.com\ndotfiles:\n  - path: host.conf\n    rename: '.ssh/config.d/{{{{HOST}}}}.conf'\n    for_each:\n      list: SSH_HOSTS\n      as: HOST\n",
+			target.display().to_string(),
+		),
+	)?;
+	write(&root.join("dotfiles/host.conf"), "Host {{HOST}}\n")?;
+	fs::create_dir_all(&target)?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+	assert_eq!(
+		fs::read_to_string(target.join(".ssh/config.d/github.com.conf"))?,
+		"Host github.com\n"
+	);
+	assert_eq!(
+		fs::read_to_string(target.join(".ssh/config.d/gitlab.com.conf"))?,
+		"Host gitlab.com\n"
+	);
+
+	Ok(())
+}
+
+#[test]
+fn deploy_render() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\nvariables:\n  EDITOR: nvim\ndotfiles:\n  - path: .zshrc\n",
+			target.display().to_string(),
+		),
+	)?;
+	write(
+		&root.join("dotfiles/.zshrc"),
+		"# zshrc\n{{@render \"zsh/aliases.zsh\"}}\nbindkey -v\n",
+	)?;
+	write(
+		&root.join("dotfiles/zsh/aliases.zsh"),
+		"alias vim={{EDITOR}}\n",
+	)?;
+	fs::create_dir_all(&target)?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+	assert_eq!(
+		fs::read_to_string(target.join(".zshrc"))?,
+		"# zshrc\nalias vim=nvim\nbindkey -v\n"
+	);
+
+	Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn deploy_transform_commands() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\nvariables:\n  NAME: demo\ndotfiles:\n  - path: upper\n    transform: ['tr a-z A-Z', 'sed s/$/!/']\n  - path: failing\n    transform: ['exit 1']\n",
+			target.display().to_string(),
+		),
+	)?;
+	write(&root.join("dotfiles/upper"), "hello {{NAME}}\n")?;
+	write(&root.join("dotfiles/failing"), "failing")?;
+	fs::create_dir_all(&target)?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert_eq!(fs::read_to_string(target.join("upper"))?, "HELLO DEMO!\n");
+	assert!(!target.join("failing").exists());
+	assert!(matches!(
+		deployment
+			.dotfiles()
+			.get(&target.canonicalize()?.join("failing"))
+			.expect("Item to be visited")
+			.status(),
+		ItemStatus::Failed(_)
+	));
+
+	Ok(())
+}
+
+#[test]
+#[cfg(unix)]
+fn deploy_formatters() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\nformatters:\n  - extensions: [txt]\n    command: tr a-z A-Z\n  - extensions: [cfg]\n    command: exit 1\n    on_failure: Ignore\n  - extensions: [ini]\n    command: exit 1\ndotfiles:\n  - path: copied.txt\n    template: false\n  - path: kept.cfg\n  - path: failing.ini\n  - path: other.md\n",
+			target.display().to_string(),
+		),
+	)?;
+	write(&root.join("dotfiles/copied.txt"), "copied")?;
+	write(&root.join("dotfiles/kept.cfg"), "kept")?;
+	write(&root.join("dotfiles/failing.ini"), "failing")?;
+	write(&root.join("dotfiles/other.md"), "other")?;
+	fs::create_dir_all(&target)?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert_eq!(fs::read_to_string(target.join("copied.txt"))?, "COPIED");
+	assert_eq!(fs::read_to_string(target.join("kept.cfg"))?, "kept");
+	assert_eq!(fs::read_to_string(target.join("other.md"))?, "other");
+	assert!(!target.join("failing.ini").exists());
+	assert!(matches!(
+		deployment
+			.dotfiles()
+			.get(&target.canonicalize()?.join("failing.ini"))
+			.expect("Item to be visited")
+			.status(),
+		ItemStatus::Failed(_)
+	));
+
+	Ok(())
+}
+
+/// Encrypts by reversing the content.
+#[derive(Debug)]
+struct Reversed;
+
+impl EncryptionBackend for Reversed {
+	fn encrypt(&self, plain: &[u8]) -> Result<Vec<u8>> {
+		Ok(plain.iter().rev().copied().collect())
+	}
+
+	fn decrypt(&self, encrypted: &[u8]) -> Result<Vec<u8>> {
+		self.encrypt(encrypted)
+	}
+}
+
+#[test]
+fn deploy_encrypted() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\nvariables:\n  NAME: \"abc\"\ndotfiles:\n  - path: copied\n    template: false\n    encrypted: true\n  - path: template\n    encrypted: true\n  - path: dir\n    encrypted: true\n  - path: plain\n",
+			target.display().to_string()
+		),
+	)?;
+	write(&root.join("dotfiles/copied"), "deipoc")?;
+	write(&root.join("dotfiles/template"), "}}EMAN{{")?;
+	write(&root.join("dotfiles/dir/nested"), "detsen")?;
+	write(&root.join("dotfiles/plain"), "plain")?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	// Without any encryption, the encrypted dotfiles can not be deployed
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert_eq!(fs::read_to_string(target.join("plain"))?, "plain");
+	assert!(!target.join("copied").exists());
+	assert!(matches!(
+		deployment
+			.dotfiles()
+			.get(&target.canonicalize()?.join("template"))
+			.expect("Item to be visited")
+			.status(),
+		ItemStatus::Failed(_)
+	));
+
+	let deployment = Deployer::new(DeployOptions::default(), |_, _| Ok(true))
+		.with_encryption_backend(Arc::new(Reversed))
+		.deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+	assert_eq!(fs::read_to_string(target.join("copied"))?, "copied");
+	assert_eq!(fs::read_to_string(target.join("template"))?, "abc");
+	assert_eq!(fs::read_to_string(target.join("nested"))?, "nested");
+
+	Ok(())
+}
@@ -0,0 +1,69 @@
+//! Tests that `file_hash` resolves relative paths from the source instead of
+//! the current directory.
+//!
+//! This is the only test of this file, as it changes the current directory of
+//! the process.
+#![cfg(all(feature = "io", feature = "profile-yaml"))]
+
+use std::fs;
+use std::path::Path;
+
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::{resolve_profile, LayeredProfile};
+use punktf_lib::visit::deploy::{DeployOptions, Deployer};
+
+fn write(path: &Path, content: &str) -> Result<()> {
+	fs::create_dir_all(path.parent().expect("Path has a parent"))?;
+	fs::write(path, content)?;
+	Ok(())
+}
+
+#[test]
+fn file_hash_relative_to_source() -> Result<()> {
+	// SHA-256 of `abc`
+	let digest = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+	let elsewhere = dir.path().join("elsewhere");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			"target: {:?}\ndotfiles:\n  - path: app/app.conf\n    template: true\n  - path: marker\n    when: file_hash(\"app/key.pub\") == \"{digest}\"\n",
+			target.display().to_string()
+		),
+	)?;
+	write(
+		&root.join("dotfiles").join("app").join("app.conf"),
+		"key = {{ file_hash(\"key.pub\") }}\n",
+	)?;
+	write(&root.join("dotfiles").join("app").join("key.pub"), "abc")?;
+	write(&root.join("dotfiles").join("marker"), "enabled")?;
+
+	// A file with the same relative path next to the current directory must
+	// not be used
+	write(&elsewhere.join("key.pub"), "other")?;
+	write(&elsewhere.join("app").join("key.pub"), "other")?;
+	std::env::set_current_dir(&elsewhere)?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+	assert_eq!(
+		fs::read_to_string(target.join("app").join("app.conf"))?,
+		format!("key = {digest}\n")
+	);
+	assert_eq!(fs::read_to_string(target.join("marker"))?, "enabled");
+
+	Ok(())
+}
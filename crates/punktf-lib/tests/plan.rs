@@ -0,0 +1,153 @@
+//! Tests for the deployment plan.
+#![cfg(all(feature = "io", feature = "profile-yaml"))]
+
+use std::fs;
+use std::path::Path;
+
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::{resolve_profile, LayeredProfile};
+use punktf_lib::visit::plan::{Action, Planner};
+
+fn write(path: &Path, content: &str) -> Result<()> {
+	fs::create_dir_all(path.parent().expect("Path has a parent"))?;
+	fs::write(path, content)?;
+	Ok(())
+}
+
+#[test]
+fn plan_actions() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+	let dotfiles = root.join("dotfiles");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			r#"target: {:?}
+variables:
+  NAME: "punktf"
+pre_hooks:
+  - "echo pre"
+dotfiles:
+  - path: new
+  - path: unchanged
+  - path: changed
+  - path: kept
+    merge: Keep
+  - path: template
+    template: true
+"#,
+			target.display().to_string()
+		),
+	)?;
+
+	write(&dotfiles.join("new"), "new")?;
+	write(&dotfiles.join("unchanged"), "same")?;
+	write(&target.join("unchanged"), "same")?;
+	write(&dotfiles.join("changed"), "new")?;
+	write(&target.join("changed"), "old")?;
+	write(&dotfiles.join("kept"), "new")?;
+	write(&target.join("kept"), "old")?;
+	write(&dotfiles.join("template"), "Hello {{NAME}}")?;
+	write(&target.join("template"), "Hello punktf")?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let plan = Planner::new().plan(&source, &mut profile);
+
+	let actions: Vec<_> = plan
+		.items
+		.iter()
+		.map(|item| (item.source_path.to_str().expect("UTF-8 path"), item.action))
+		.collect();
+
+	assert_eq!(
+		actions,
+		vec![
+			("new", Action::Create),
+			("unchanged", Action::SkipUnchanged),
+			("changed", Action::Overwrite),
+			("kept", Action::Conflict),
+			("template", Action::SkipUnchanged),
+		]
+	);
+	assert_eq!(plan.pre_hooks, vec![String::from("echo pre")]);
+	assert_eq!(plan.changes(), 2);
+	assert_eq!(
+		plan.drift()
+			.map(|item| item.source_path.to_str().expect("UTF-8 path"))
+			.collect::<Vec<_>>(),
+		vec!["new", "changed", "kept"]
+	);
+	assert!(plan.has_conflicts());
+	assert!(!plan.has_errors());
+
+	// Planning must not modify anything
+	assert!(!target.join("new").exists());
+	assert_eq!(fs::read_to_string(target.join("changed"))?, "old");
+
+	Ok(())
+}
+
+#[test]
+fn plan_contents() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+	let dotfiles = root.join("dotfiles");
+
+	write(
+		&root.join("profiles").join("test.yaml"),
+		&format!(
+			r#"target: {:?}
+variables:
+  NAME: "punktf"
+dotfiles:
+  - path: template
+    template: true
+  - path: unchanged
+"#,
+			target.display().to_string()
+		),
+	)?;
+
+	write(&dotfiles.join("template"), "Hello {{NAME}}")?;
+	write(&dotfiles.join("unchanged"), "same")?;
+	write(&target.join("unchanged"), "same")?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let plan = Planner::new().plan(&source, &mut profile);
+	assert!(plan.items.iter().all(|item| item.content.is_none()));
+
+	let plan = Planner::new()
+		.with_contents(true)
+		.plan(&source, &mut profile);
+
+	let contents: Vec<_> = plan
+		.items
+		.iter()
+		.map(|item| item.content.as_deref())
+		.collect();
+
+	assert_eq!(contents, vec![Some(&b"Hello punktf"[..]), None]);
+
+	let plan = Planner::new()
+		.with_contents(true)
+		.ignore_targets(true)
+		.plan(&source, &mut profile);
+
+	assert!(plan.items.iter().all(|item| item.action == Action::Create));
+	assert_eq!(plan.items[1].content.as_deref(), Some(&b"same"[..]));
+
+	Ok(())
+}
@@ -0,0 +1,162 @@
+//! Tests for the WebAssembly plugins.
+#![cfg(all(feature = "plugins", feature = "profile-yaml"))]
+
+use std::fs;
+use std::path::Path;
+
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+use punktf_lib::plugin::wasm::{self, WasmPlugin};
+use punktf_lib::plugin::Plugins;
+use punktf_lib::profile::hook::{Hook, HookError};
+use punktf_lib::profile::provider::VariableProviders;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::{resolve_profile, LayeredProfile};
+use punktf_lib::visit::deploy::{DeployOptions, Deployer};
+
+/// Exports of the test plugin.
+const INFO: &str = r#"{"functions":["echo"],"providers":["echo"],"hooks":["log","fail"]}"#;
+
+/// Returns the test plugin, which returns the argument for all functions and
+/// providers. Its hooks write the argument to stdout (`log`) or fail
+/// (`fail`).
+fn test_plugin() -> Result<Vec<u8>> {
+	let wat = format!(
+		r#"(module
+			(import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+			(import "wasi_snapshot_preview1" "fd_close" (func $fd_close (param i32) (result i32)))
+			(memory (export "memory") 1)
+			(global $heap (mut i32) (i32.const 1024))
+			(data (i32.const 0) "{info}")
+			(data (i32.const 512) "\01hook failed")
+			(func $alloc (export "punktf_alloc") (param $len i32) (result i32)
+				(local $address i32)
+				(local.set $address (global.get $heap))
+				(global.set $heap (i32.add (global.get $heap) (local.get $len)))
+				(local.get $address))
+			(func (export "punktf_info") (result i64)
+				(i64.const {info_len}))
+			(func (export "punktf_call")
+				(param $kind i32) (param $name i32) (param $name_len i32) (param $arg i32) (param $arg_len i32)
+				(result i64)
+				(local $result i32)
+				(if (i32.eq (local.get $kind) (i32.const 2))
+					(then
+						(if (i32.eq (i32.load8_u (local.get $name)) (i32.const 102))
+							(then (return (i64.const {failed}))))
+						(local.set $result (call $alloc (i32.const 12)))
+						(i32.store (local.get $result) (local.get $arg))
+						(i32.store offset=4 (local.get $result) (local.get $arg_len))
+						(drop (call $fd_write (i32.const 1) (local.get $result) (i32.const 1)
+							(i32.add (local.get $result) (i32.const 8))))
+						(if (i32.ne (call $fd_close (i32.const 3)) (i32.const 52))
+							(then unreachable))
+						(local.set $arg_len (i32.const 0))))
+				(local.set $result (call $alloc (i32.add (local.get $arg_len) (i32.const 1))))
+				(i32.store8 (local.get $result) (i32.const 0))
+				(memory.copy (i32.add (local.get $result) (i32.const 1)) (local.get $arg) (local.get $arg_len))
+				(i64.or
+					(i64.shl (i64.extend_i32_u (local.get $result)) (i64.const 32))
+					(i64.extend_i32_u (i32.add (local.get $arg_len) (i32.const 1))))))"#,
+		info = INFO.replace('"', "\\\""),
+		info_len = INFO.len(),
+		failed = (512_i64 << 32) | 12,
+	);
+
+	Ok(wat::parse_str(wat)?)
+}
+
+fn write(path: &Path, content: &[u8]) -> Result<()> {
+	fs::create_dir_all(path.parent().expect("Path has a parent"))?;
+	fs::write(path, content)?;
+	Ok(())
+}
+
+#[test]
+fn call_wasm_plugin() -> Result<()> {
+	let plugin = WasmPlugin::new("test", &test_plugin()?)?;
+	assert_eq!(plugin.functions().collect::<Vec<_>>(), vec!["echo"]);
+	assert_eq!(plugin.providers().collect::<Vec<_>>(), vec!["echo"]);
+	assert_eq!(plugin.hooks().collect::<Vec<_>>(), vec!["log", "fail"]);
+
+	let mut plugins = Plugins::empty();
+	let mut providers = VariableProviders::empty();
+	plugin.register(&mut plugins, &mut providers);
+
+	assert_eq!(plugins.call_function("test.echo", "abc")?, "abc");
+	assert_eq!(plugins.call_function("test.echo", "")?, "");
+	assert_eq!(
+		providers.resolve("from:test.echo:a:b")?.as_deref(),
+		Some("a:b")
+	);
+	assert_eq!(plugins.call_hook("test.log", "abc")?, "");
+	assert!(plugins.call_hook("test.fail", "abc").is_err());
+	assert!(plugins.call_function("test.log", "abc").is_err());
+
+	assert!(matches!(
+		Hook::new("plugin:test.fail abc").execute_with(Path::new("."), &plugins),
+		Err(HookError::Plugin(_))
+	));
+	assert!(WasmPlugin::new("invalid name", &test_plugin()?).is_err());
+	assert!(WasmPlugin::new("test", b"\0asm").is_err());
+
+	Ok(())
+}
+
+#[test]
+fn deploy_with_wasm_plugin() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let target = dir.path().join("target");
+	let plugins = dir.path().join("plugins");
+
+	write(&plugins.join("test.wasm"), &test_plugin()?)?;
+	write(&plugins.join("README.md"), b"Not a plugin")?;
+	write(
+		&root.join("profiles").join("test.yaml"),
+		format!(
+			"target: {:?}\nvariables:\n  NAME: \"from:test.echo:provided\"\npost_hooks:\n  - \"plugin:test.log deployed\"\ndotfiles:\n  - path: template\n    template: true\n",
+			target.display().to_string()
+		)
+		.as_bytes(),
+	)?;
+	write(
+		&root.join("dotfiles").join("template"),
+		br#"{{NAME}} {{ "called" | plugin("test.echo") | upper }}"#,
+	)?;
+
+	let source = PunktfSource::from_root(root)?;
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let mut profile = builder.finish();
+
+	let mut providers = VariableProviders::default();
+	let discovered = wasm::discover(&plugins)?;
+	assert_eq!(discovered.len(), 1);
+	for plugin in discovered {
+		plugin.register(profile.plugins_mut(), &mut providers);
+	}
+	profile.resolve_variables(&providers)?;
+
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(deployment.status().is_success());
+	assert_eq!(
+		fs::read_to_string(target.join("template"))?,
+		"provided CALLED"
+	);
+
+	// Without the plugins, the template can not be rendered
+	*profile.plugins_mut() = Plugins::empty();
+	fs::remove_file(target.join("template"))?;
+	let deployment =
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true)).deploy(&source, &mut profile);
+
+	assert!(!target.join("template").exists());
+	assert!(!deployment.status().is_success());
+
+	assert!(wasm::discover(&dir.path().join("missing"))?.is_empty());
+
+	Ok(())
+}
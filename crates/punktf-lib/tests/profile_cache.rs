@@ -0,0 +1,72 @@
+//! Tests for the cache of resolved profiles.
+#![cfg(all(feature = "io", feature = "profile-json", feature = "profile-yaml"))]
+
+use std::fs;
+use std::path::Path;
+
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+use punktf_lib::profile::cache::ProfileCache;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::{resolve_profile, LayeredProfile};
+
+fn write(path: &Path, content: &str) -> Result<()> {
+	fs::create_dir_all(path.parent().expect("Path has a parent"))?;
+	fs::write(path, content)?;
+	Ok(())
+}
+
+fn resolve_cached(cache: &mut ProfileCache, source: &PunktfSource) -> Result<LayeredProfile> {
+	let mut builder = LayeredProfile::build();
+	cache.resolve(&mut builder, source, "test")?;
+	Ok(builder.finish())
+}
+
+#[test]
+fn profile_cache() -> Result<()> {
+	let dir = tempfile::tempdir()?;
+	let root = dir.path().join("source");
+	let profiles = root.join("profiles");
+	let cache_path = dir.path().join("cache").join("profiles.json");
+
+	write(
+		&profiles.join("test.yaml"),
+		"extends: [base]\nvariables:\n  NAME: test\n",
+	)?;
+	write(
+		&profiles.join("base.yaml"),
+		"target: /base\nvariables:\n  NAME: base\n  OS: linux\n",
+	)?;
+	fs::create_dir_all(root.join("dotfiles"))?;
+
+	let source = PunktfSource::from_root(root)?;
+
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, &source, "test")?;
+	let expected = builder.finish();
+
+	let mut cache = ProfileCache::load(&cache_path)?;
+	assert!(cache.is_empty());
+	assert_eq!(resolve_cached(&mut cache, &source)?, expected);
+	assert_eq!(cache.len(), 1);
+	cache.save(&cache_path)?;
+
+	// Modify the stored layers to see if they are used
+	let content = fs::read_to_string(&cache_path)?;
+	fs::write(&cache_path, content.replace("/base", "/cached"))?;
+
+	let mut cache = ProfileCache::load(&cache_path)?;
+	let profile = resolve_cached(&mut cache, &source)?;
+	assert_eq!(profile.target_path(), Some(Path::new("/cached")));
+
+	// Any change of a profile invalidates the cache
+	write(
+		&profiles.join("base.yaml"),
+		"target: /changed\nvariables:\n  NAME: base\n",
+	)?;
+
+	let profile = resolve_cached(&mut cache, &source)?;
+	assert_eq!(profile.target_path(), Some(Path::new("/changed")));
+
+	Ok(())
+}
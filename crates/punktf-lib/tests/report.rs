@@ -0,0 +1,157 @@
+//! Compatibility tests for the serialized deployment reports.
+#![cfg(all(feature = "io", feature = "profile-json"))]
+
+use std::path::Path;
+use std::time::Duration;
+
+use pretty_assertions::assert_eq;
+use punktf_lib::error::{Error, IoError};
+use punktf_lib::visit::deploy::deployment::{
+	Deployment, DeploymentStatus, ItemChange, ItemMetrics, ItemStatus,
+};
+
+/// A report in the format of schema version 1.
+const REPORT_V1: &str = r#"{
+	"schema_version": 1,
+	"time_start": { "secs_since_epoch": 1700000000, "nanos_since_epoch": 0 },
+	"time_end": { "secs_since_epoch": 1700000002, "nanos_since_epoch": 500000000 },
+	"status": { "Failed": { "Other": "Deployment of 1 dotfiles and 0 links failed" } },
+	"dotfiles": {
+		"/home/demo/.bashrc": {
+			"status": "Success",
+			"kind": { "Dotfile": { "path": ".bashrc" } },
+			"metrics": {
+				"compile_time": { "secs": 0, "nanos": 1000 },
+				"write_time": { "secs": 0, "nanos": 2000 },
+				"bytes_written": 42
+			},
+			"change": "Updated"
+		},
+		"/home/demo/.config/nvim/init.lua": {
+			"status": { "Failed": { "Io": { "path": "/home/demo/.config/nvim/init.lua", "message": "Failed to copy: denied" } } },
+			"kind": { "Child": "/home/demo/.config/nvim" },
+			"metrics": {
+				"compile_time": { "secs": 0, "nanos": 0 },
+				"write_time": { "secs": 0, "nanos": 0 },
+				"bytes_written": 0
+			},
+			"change": null
+		}
+	},
+	"symlinks": {
+		"/home/demo/link": {
+			"status": { "Skipped": "Deployment was cancelled" },
+			"source": "/home/demo/source",
+			"change": null
+		}
+	},
+	"unknown_future_field": true
+}"#;
+
+#[test]
+fn report_v1() -> serde_json::Result<()> {
+	let deployment: Deployment = serde_json::from_str(REPORT_V1)?;
+
+	assert_eq!(deployment.schema_version(), 1);
+	assert_eq!(
+		deployment.duration().expect("End after start"),
+		Duration::from_millis(2500)
+	);
+	assert_eq!(
+		deployment.status(),
+		&DeploymentStatus::failed("Deployment of 1 dotfiles and 0 links failed")
+	);
+
+	let bashrc = &deployment.dotfiles()[Path::new("/home/demo/.bashrc")];
+	assert_eq!(bashrc.status(), &ItemStatus::Success);
+	assert!(bashrc.kind().is_dotfile());
+	assert_eq!(bashrc.change(), Some(ItemChange::Updated));
+	assert_eq!(
+		bashrc.metrics(),
+		&ItemMetrics {
+			compile_time: Duration::from_micros(1),
+			write_time: Duration::from_micros(2),
+			bytes_written: 42,
+		}
+	);
+
+	let init = &deployment.dotfiles()[Path::new("/home/demo/.config/nvim/init.lua")];
+	assert!(init.kind().is_child());
+	assert_eq!(
+		init.status(),
+		&ItemStatus::Failed(Error::Io(IoError {
+			path: "/home/demo/.config/nvim/init.lua".into(),
+			message: "Failed to copy: denied".into(),
+		}))
+	);
+
+	let link = &deployment.symlinks()[Path::new("/home/demo/link")];
+	assert_eq!(
+		link.status(),
+		&ItemStatus::skipped("Deployment was cancelled")
+	);
+	assert_eq!(link.source(), Path::new("/home/demo/source"));
+
+	Ok(())
+}
+
+#[test]
+fn report_roundtrip() -> serde_json::Result<()> {
+	let mut builder = Deployment::build();
+	builder.add_link("source".into(), "target".into(), ItemStatus::success());
+	let deployment = builder.success();
+
+	let json: serde_json::Value = serde_json::to_value(&deployment)?;
+	assert_eq!(json["schema_version"], Deployment::SCHEMA_VERSION);
+
+	let parsed: Deployment = serde_json::from_value(json)?;
+	assert_eq!(parsed, deployment);
+
+	Ok(())
+}
+
+#[test]
+fn report_unversioned() -> serde_json::Result<()> {
+	let mut json: serde_json::Value = serde_json::from_str(REPORT_V1)?;
+	json.as_object_mut()
+		.expect("Report is an object")
+		.remove("schema_version");
+
+	let deployment: Deployment = serde_json::from_value(json)?;
+	assert_eq!(deployment.schema_version(), 0);
+
+	Ok(())
+}
+
+#[test]
+fn report_newer_version() -> serde_json::Result<()> {
+	let mut json: serde_json::Value = serde_json::from_str(REPORT_V1)?;
+	json["schema_version"] = (Deployment::SCHEMA_VERSION + 1).into();
+
+	let err = serde_json::from_value::<Deployment>(json).expect_err("Newer version is rejected");
+	assert!(err
+		.to_string()
+		.contains("Unsupported report schema version"));
+
+	Ok(())
+}
+
+#[test]
+#[cfg(feature = "profile-schema")]
+fn report_json_schema() {
+	let schema = Deployment::json_schema();
+	let properties = schema["properties"]
+		.as_object()
+		.expect("Schema to have properties");
+
+	for key in [
+		"schema_version",
+		"time_start",
+		"time_end",
+		"status",
+		"dotfiles",
+		"symlinks",
+	] {
+		assert!(properties.contains_key(key), "Schema is missing `{key}`");
+	}
+}
@@ -0,0 +1,129 @@
+//! Tests for layered source directories.
+#![cfg(all(feature = "io", feature = "profile-yaml"))]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+use punktf_lib::profile::source::{ConflictPolicy, PunktfSource};
+use punktf_lib::profile::{resolve_profile, LayeredProfile, Priority};
+use punktf_lib::visit::deploy::deployment::Deployment;
+use punktf_lib::visit::deploy::{DeployOptions, Deployer};
+use tempfile::TempDir;
+
+fn write(path: &Path, content: &str) -> Result<()> {
+	fs::create_dir_all(path.parent().expect("Path has a parent"))?;
+	fs::write(path, content)?;
+	Ok(())
+}
+
+/// Creates a primary and a secondary source with overlapping dotfiles.
+fn setup() -> Result<(TempDir, PunktfSource, PathBuf)> {
+	let dir = tempfile::tempdir()?;
+	let primary = dir.path().join("primary");
+	let secondary = dir.path().join("secondary");
+	let target = dir.path().join("target");
+
+	write(
+		&secondary.join("profiles").join("shared.yaml"),
+		&format!(
+			"target: {:?}\ndotfiles:\n  - path: base\n  - path: single\n",
+			target.display().to_string()
+		),
+	)?;
+	write(&primary.join("dotfiles").join("base").join("a"), "primary")?;
+	write(
+		&secondary.join("dotfiles").join("base").join("a"),
+		"secondary",
+	)?;
+	write(
+		&secondary.join("dotfiles").join("base").join("b"),
+		"secondary",
+	)?;
+	write(&primary.join("dotfiles").join("single"), "primary")?;
+
+	let source = PunktfSource::from_roots([primary, secondary])?;
+
+	Ok((dir, source, target))
+}
+
+fn deploy(source: &PunktfSource) -> Result<Deployment> {
+	let mut builder = LayeredProfile::build();
+	resolve_profile(&mut builder, source, "shared")?;
+	let mut profile = builder.finish();
+
+	Ok(
+		Deployer::new(DeployOptions::default(), |_, _| Ok(true))
+			.deploy(source, &mut profile),
+	)
+}
+
+#[test]
+fn overlay_sources() -> Result<()> {
+	let (_dir, source, target) = setup()?;
+
+	assert_eq!(source.layers().count(), 2);
+	assert_eq!(source.profile_names()?, vec![String::from("shared")]);
+
+	let deployment = deploy(&source)?;
+
+	assert!(deployment.status().is_success());
+	assert_eq!(fs::read_to_string(target.join("a"))?, "primary");
+	assert_eq!(fs::read_to_string(target.join("b"))?, "secondary");
+	assert_eq!(fs::read_to_string(target.join("single"))?, "primary");
+
+	Ok(())
+}
+
+#[test]
+fn conflict_policy_parse() {
+	assert_eq!("error".parse(), Ok(ConflictPolicy::Error));
+	assert_eq!("first-wins".parse(), Ok(ConflictPolicy::FirstWins));
+	assert_eq!("Last-Wins".parse(), Ok(ConflictPolicy::LastWins));
+	assert_eq!("priority".parse(), Ok(ConflictPolicy::Priority));
+	assert!("latest".parse::<ConflictPolicy>().is_err());
+}
+
+#[test]
+fn conflict_policy_error() -> Result<()> {
+	let (_dir, mut source, target) = setup()?;
+	source.conflict_policy = ConflictPolicy::Error;
+
+	let deployment = deploy(&source)?;
+
+	assert!(deployment.status().is_failed());
+	assert!(deployment
+		.dotfiles()
+		.get(&target.join("a"))
+		.expect("Conflict to be reported")
+		.status()
+		.is_failed());
+	assert!(!target.join("a").exists());
+	assert_eq!(fs::read_to_string(target.join("b"))?, "secondary");
+
+	Ok(())
+}
+
+#[test]
+fn conflict_policy_last_wins() -> Result<()> {
+	let (_dir, mut source, target) = setup()?;
+	source.conflict_policy = ConflictPolicy::LastWins;
+
+	assert!(deploy(&source)?.status().is_success());
+	assert_eq!(fs::read_to_string(target.join("a"))?, "secondary");
+
+	Ok(())
+}
+
+#[test]
+fn conflict_policy_priority() -> Result<()> {
+	let (_dir, mut source, target) = setup()?;
+	source.conflict_policy = ConflictPolicy::Priority;
+	source.overlays[0].priority = Priority::new(5);
+
+	assert!(deploy(&source)?.status().is_success());
+	assert_eq!(fs::read_to_string(target.join("a"))?, "secondary");
+
+	Ok(())
+}
@@ -0,0 +1,33 @@
+//! Tests for the built-in `PUNKTF_TARGET_FAMILY` variable of templates.
+
+use color_eyre::Result;
+use pretty_assertions::assert_eq;
+use punktf_lib::profile::variables::Variables;
+use punktf_lib::template::source::Source;
+use punktf_lib::template::Template;
+
+#[test]
+fn target_family() -> Result<()> {
+	std::env::remove_var("PUNKTF_TARGET_FAMILY");
+
+	// `wasm32-unknown-unknown` has the target os `unknown`, so wasm can only be
+	// detected by its target family
+	let expected = if cfg!(target_family = "unix") {
+		"unix"
+	} else if cfg!(target_family = "windows") {
+		"windows"
+	} else if cfg!(target_family = "wasm") {
+		"wasm"
+	} else {
+		"unknown"
+	};
+
+	let template = Template::parse(Source::anonymous("{{$PUNKTF_TARGET_FAMILY}}"))?;
+
+	assert_eq!(
+		template.resolve::<Variables, Variables>(None, None)?,
+		expected
+	);
+
+	Ok(())
+}
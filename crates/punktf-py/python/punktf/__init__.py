@@ -0,0 +1,17 @@
+"""Python bindings for punktf, a cross-platform multi-target dotfiles manager.
+
+The bindings are implemented by the `punktf._punktf` extension module, which
+is built from the `punktf-py` crate.
+
+Example:
+
+    import punktf
+
+    with punktf.Profile("/home/user/dotfiles", "arch") as profile:
+        print(profile.render("bashrc"))
+        report = profile.deploy(dry_run=True, on_event=print)
+"""
+
+from ._punktf import Profile, PunktfError
+
+__all__ = ["Profile", "PunktfError"]
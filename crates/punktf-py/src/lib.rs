@@ -0,0 +1,244 @@
+#![deny(
+	missing_docs,
+	clippy::missing_docs_in_private_items,
+	missing_debug_implementations,
+	rust_2018_idioms,
+	unused_qualifications
+)]
+// The code generated by pyo3 for methods returning `PyResult` converts the
+// error with `From`
+#![allow(clippy::useless_conversion)]
+
+//! Python bindings for `punktf`, a cross-platform multi-target dotfiles
+//! manager.
+//!
+//! The bindings are built with [PyO3](https://pyo3.rs) into the extension
+//! module `punktf._punktf`, which is re-exported by the `punktf` Python
+//! package (see `python/punktf/__init__.py`).
+//!
+//! Complex results (plans, deployment reports and deployment events) are
+//! returned as Python dicts, which have the same structure as the
+//! `--json-output` of the command line interface.
+
+use std::path::PathBuf;
+
+use punktf_ffi::{event_json, render_dotfile};
+use punktf_lib::profile::provider::VariableProviders;
+use punktf_lib::profile::source::PunktfSource;
+use punktf_lib::profile::{resolve_profile, LayeredProfile, Profile as ProfileLayer};
+use punktf_lib::visit::deploy::event::DeployEvent;
+use punktf_lib::visit::deploy::{DeployOptions, Deployer};
+use punktf_lib::visit::plan::Planner;
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyTuple};
+
+create_exception!(
+	punktf,
+	PunktfError,
+	PyException,
+	"An error which was reported by punktf."
+);
+
+/// Converts `err` to a [`PunktfError`].
+fn to_py_err(err: impl Into<color_eyre::Report>) -> PyErr {
+	PunktfError::new_err(format!("{:#}", err.into()))
+}
+
+/// Converts the json `value` to the matching Python object.
+fn to_python(py: Python<'_>, value: &serde_json::Value) -> PyResult<PyObject> {
+	use serde_json::Value;
+
+	Ok(match value {
+		Value::Null => py.None(),
+		Value::Bool(value) => value.into_py(py),
+		Value::Number(value) => {
+			if let Some(value) = value.as_i64() {
+				value.into_py(py)
+			} else if let Some(value) = value.as_u64() {
+				value.into_py(py)
+			} else {
+				value.as_f64().into_py(py)
+			}
+		}
+		Value::String(value) => value.into_py(py),
+		Value::Array(values) => {
+			let list = PyList::empty_bound(py);
+			for value in values {
+				list.append(to_python(py, value)?)?;
+			}
+			list.into_py(py)
+		}
+		Value::Object(values) => {
+			let dict = PyDict::new_bound(py);
+			for (key, value) in values {
+				dict.set_item(key, to_python(py, value)?)?;
+			}
+			dict.into_py(py)
+		}
+	})
+}
+
+/// Serializes `value` to json and converts it to the matching Python object.
+fn serialize<T: serde::Serialize>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+	to_python(py, &serde_json::to_value(value).map_err(to_py_err)?)
+}
+
+/// A profile which was resolved together with the source it was loaded from.
+#[derive(Debug)]
+struct Loaded {
+	/// The source of the profile.
+	source: PunktfSource,
+
+	/// The resolved profile.
+	profile: LayeredProfile,
+}
+
+/// A resolved profile of a punktf source directory.
+///
+/// Variable values which reference a provider (`from:<provider>:<argument>`)
+/// are resolved when the profile is loaded. The profile can be used as context
+/// manager, which closes it on exit.
+#[pyclass(module = "punktf")]
+#[derive(Debug)]
+struct Profile {
+	/// The loaded profile or `None` if it was closed.
+	loaded: Option<Loaded>,
+}
+
+impl Profile {
+	/// Returns the loaded profile.
+	fn loaded(&mut self) -> PyResult<&mut Loaded> {
+		self.loaded
+			.as_mut()
+			.ok_or_else(|| PunktfError::new_err("The profile was already closed"))
+	}
+}
+
+#[pymethods]
+impl Profile {
+	/// Loads the profile `name` from the source directory `source`.
+	///
+	/// `target` overwrites the target of the profile if it is given.
+	#[new]
+	#[pyo3(signature = (source, name, target = None))]
+	fn new(source: PathBuf, name: &str, target: Option<PathBuf>) -> PyResult<Self> {
+		let source = PunktfSource::from_root(source).map_err(to_py_err)?;
+		let mut builder = LayeredProfile::build();
+
+		if let Some(target) = target {
+			let target_profile = ProfileLayer {
+				target: Some(target),
+				..Default::default()
+			};
+			builder.add(String::from("python"), target_profile);
+		}
+
+		resolve_profile(&mut builder, &source, name).map_err(to_py_err)?;
+
+		let mut profile = builder.finish();
+		profile
+			.resolve_variables(&VariableProviders::default())
+			.map_err(to_py_err)?;
+
+		Ok(Self {
+			loaded: Some(Loaded { source, profile }),
+		})
+	}
+
+	/// Releases the profile. Closing a closed profile has no effect.
+	fn close(&mut self) {
+		self.loaded = None;
+	}
+
+	/// Returns the profile itself.
+	fn __enter__(slf: Py<Self>) -> Py<Self> {
+		slf
+	}
+
+	/// Closes the profile.
+	#[pyo3(signature = (*_args))]
+	fn __exit__(&mut self, _args: &Bound<'_, PyTuple>) -> bool {
+		self.close();
+		false
+	}
+
+	/// Returns the names of the layers the profile was resolved from, starting
+	/// with the profile itself.
+	#[getter]
+	fn names(&mut self) -> PyResult<Vec<String>> {
+		Ok(self.loaded()?.profile.profile_names.clone())
+	}
+
+	/// Returns the plan of all actions a deployment would take.
+	fn plan(&mut self, py: Python<'_>) -> PyResult<PyObject> {
+		let Loaded { source, profile } = self.loaded()?;
+		let plan = Planner::new().plan(source, profile);
+
+		serialize(py, &plan)
+	}
+
+	/// Renders the dotfile at the path `dotfile` (relative to the `dotfiles`
+	/// directory) with the variables of the profile.
+	///
+	/// Raises if `dotfile` is absolute or leaves the `dotfiles` directory.
+	fn render(&mut self, dotfile: PathBuf) -> PyResult<String> {
+		let Loaded { source, profile } = self.loaded()?;
+
+		render_dotfile(source, profile, &dotfile).map_err(to_py_err)
+	}
+
+	/// Deploys the profile and returns the deployment report.
+	///
+	/// `on_event` is called with each event of the deployment as dict (see
+	/// `punktf_profile_deploy_with_events` of the C bindings). If it raises,
+	/// the deployment is finished without calling it again and the exception
+	/// is raised afterwards. Existing files with the merge mode `Ask` are not
+	/// overwritten. If `dry_run` is set, nothing is written.
+	///
+	/// A failed deployment does not raise but is described by the report.
+	#[pyo3(signature = (dry_run = false, on_event = None))]
+	fn deploy(
+		&mut self,
+		py: Python<'_>,
+		dry_run: bool,
+		on_event: Option<Bound<'_, PyAny>>,
+	) -> PyResult<PyObject> {
+		let Loaded { source, profile } = self.loaded()?;
+		let mut error = None;
+
+		let observer = |event: &DeployEvent<'_>| {
+			let Some(on_event) = &on_event else {
+				return;
+			};
+
+			if error.is_none() {
+				if let Err(err) =
+					to_python(py, &event_json(event)).and_then(|event| on_event.call1((event,)))
+				{
+					error = Some(err);
+				}
+			}
+		};
+
+		let options = DeployOptions::new().with_dry_run(dry_run);
+		let deployment = Deployer::new(options, |_, _| Ok(false))
+			.with_observer(observer)
+			.deploy(source, profile);
+
+		match error {
+			Some(err) => Err(err),
+			None => serialize(py, &deployment),
+		}
+	}
+}
+
+/// The `punktf._punktf` extension module.
+#[pymodule]
+fn _punktf(m: &Bound<'_, PyModule>) -> PyResult<()> {
+	m.add_class::<Profile>()?;
+	m.add("PunktfError", m.py().get_type_bound::<PunktfError>())?;
+
+	Ok(())
+}
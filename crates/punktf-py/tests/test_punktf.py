@@ -0,0 +1,149 @@
+"""Tests for the Python bindings.
+
+The `punktf` package must be installed (e.g. with `maturin develop`).
+"""
+
+import tempfile
+import unittest
+from pathlib import Path
+
+import punktf
+
+PROFILE = """\
+variables:
+  NAME: "python"
+post_hooks:
+  - "echo deployed"
+dotfiles:
+  - path: file
+  - path: template
+    template: true
+    variables:
+      GREETING: "hello"
+"""
+
+
+def write(path, content):
+    path.parent.mkdir(parents=True, exist_ok=True)
+    path.write_text(content)
+
+
+class ProfileTest(unittest.TestCase):
+    def setUp(self):
+        self.dir = tempfile.TemporaryDirectory()
+        root = Path(self.dir.name)
+        self.source = root / "source"
+        self.target = root / "target"
+
+        write(self.source / "profiles" / "test.yaml", PROFILE)
+        write(self.source / "dotfiles" / "file", "content")
+        write(self.source / "dotfiles" / "template", "{{GREETING}} {{NAME}}")
+
+    def tearDown(self):
+        self.dir.cleanup()
+
+    def profile(self):
+        return punktf.Profile(self.source, "test", target=self.target)
+
+    def test_render(self):
+        with self.profile() as profile:
+            self.assertEqual(profile.names, ["python", "test"])
+            self.assertEqual(profile.render("template"), "hello python")
+
+            with self.assertRaises(punktf.PunktfError):
+                profile.render("missing")
+
+            # Only files inside the `dotfiles` directory can be rendered
+            for outside in ["../profiles/test.yaml", self.source / "dotfiles" / "file"]:
+                with self.assertRaisesRegex(punktf.PunktfError, "dotfiles directory"):
+                    profile.render(outside)
+
+    def test_plan(self):
+        with self.profile() as profile:
+            plan = profile.plan()
+
+        self.assertIsInstance(plan, dict)
+        self.assertFalse((self.target / "file").exists())
+
+    def test_deploy_dry_run(self):
+        events = []
+
+        with self.profile() as profile:
+            report = profile.deploy(dry_run=True, on_event=events.append)
+
+        self.assertEqual(
+            [event["event"] for event in events],
+            [
+                "started",
+                "item_started",
+                "item_compiled",
+                "item_deployed",
+                "item_started",
+                "item_compiled",
+                "item_deployed",
+                "hook_started",
+                "hook_finished",
+                "finished",
+            ],
+        )
+        self.assertEqual(events[0]["profile"], ["python", "test"])
+        self.assertEqual(events[3]["target"], str(self.target / "file"))
+        self.assertIsInstance(events[2]["duration"], float)
+        self.assertEqual(events[7]["kind"], "post")
+        self.assertEqual(events[7]["command"], "echo deployed")
+        self.assertIsNone(events[8]["error"])
+        self.assertTrue(events[-1]["success"])
+
+        self.assertIsInstance(report, dict)
+        self.assertFalse((self.target / "file").exists())
+
+    def test_deploy(self):
+        with self.profile() as profile:
+            profile.deploy()
+
+        self.assertEqual((self.target / "file").read_text(), "content")
+        self.assertEqual((self.target / "template").read_text(), "hello python")
+
+    def test_deploy_failed_item(self):
+        (self.source / "dotfiles" / "template").write_text("{{MISSING}}")
+        events = []
+
+        with self.profile() as profile:
+            profile.deploy(on_event=events.append)
+
+        failed = [event for event in events if event["event"] == "item_failed"]
+        self.assertEqual(len(failed), 1)
+        self.assertEqual(failed[0]["target"], str(self.target / "template"))
+        self.assertFalse(events[-1]["success"])
+        self.assertFalse((self.target / "template").exists())
+
+    def test_callback_error(self):
+        events = []
+
+        def on_event(event):
+            events.append(event)
+            raise ValueError("callback failed")
+
+        with self.profile() as profile:
+            with self.assertRaisesRegex(ValueError, "callback failed"):
+                profile.deploy(on_event=on_event)
+
+        # The deployment is finished without calling the callback again
+        self.assertEqual(len(events), 1)
+        self.assertEqual((self.target / "file").read_text(), "content")
+
+    def test_closed(self):
+        profile = self.profile()
+        profile.close()
+        profile.close()
+
+        with self.assertRaisesRegex(punktf.PunktfError, "closed"):
+            profile.deploy()
+
+    def test_missing_profile(self):
+        with self.assertRaises(punktf.PunktfError):
+            punktf.Profile(self.source, "missing")
+
+
+if __name__ == "__main__":
+    unittest.main()